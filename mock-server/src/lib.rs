@@ -0,0 +1,457 @@
+//! 测试用的简易http server。每个连接仅处理一个请求，并记录收到的请求
+use log::debug;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_native_tls::TlsAcceptor;
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    /// 忽略大小写获取header
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn body_str(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+
+    pub fn body_json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("request body is not json")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn text(status: u16, body: &str) -> Self {
+        Self::new(status)
+            .header("content-type", "text/html; charset=utf-8")
+            .body(body.as_bytes())
+    }
+
+    pub fn json(status: u16, body: &serde_json::Value) -> Self {
+        Self::new(status)
+            .header("content-type", "application/json")
+            .body(body.to_string().as_bytes())
+    }
+
+    pub fn header(mut self, name: &str, val: &str) -> Self {
+        self.headers.push((name.to_string(), val.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: &[u8]) -> Self {
+        self.body = body.to_vec();
+        self
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+pub struct MockServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    /// 在随机端口上启动server，所有请求通过handler响应
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        Self::bind(None, handler).await
+    }
+
+    /// 启动一个https server，使用pem格式的证书与pkcs8私钥
+    pub async fn start_tls<F>(cert_pem: &[u8], key_pem: &[u8], handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let identity = native_tls::Identity::from_pkcs8(cert_pem, key_pem).unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        Self::bind(Some(TlsAcceptor::from(acceptor)), handler).await
+    }
+
+    async fn bind<F>(tls: Option<TlsAcceptor>, handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let recorded = requests.clone();
+        let tls = tls.map(Arc::new);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let recorded = recorded.clone();
+                let tls = tls.clone();
+                tokio::spawn(async move {
+                    let result = match tls {
+                        Some(tls) => match tls.accept(stream).await {
+                            Ok(stream) => Self::serve(stream, handler, recorded).await,
+                            Err(e) => {
+                                debug!("mock server tls handshake error: {}", e);
+                                return;
+                            }
+                        },
+                        None => Self::serve(stream, handler, recorded).await,
+                    };
+                    if let Err(e) = result {
+                        debug!("mock server connection error: {}", e);
+                    }
+                });
+            }
+        });
+        Self { addr, requests }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    async fn serve<S: AsyncRead + AsyncWrite + Unpin>(
+        mut stream: S,
+        handler: Arc<Handler>,
+        recorded: Arc<Mutex<Vec<MockRequest>>>,
+    ) -> std::io::Result<()> {
+        let mut buf = vec![];
+        let mut chunk = [0u8; 4096];
+        let header_end = loop {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+        let mut lines = head.split("\r\n");
+        let mut start_line = lines.next().unwrap_or_default().split_whitespace();
+        let method = start_line.next().unwrap_or_default().to_string();
+        let path = start_line.next().unwrap_or_default().to_string();
+        let headers = lines
+            .filter(|l| !l.is_empty())
+            .filter_map(|l| {
+                let mut kv = l.splitn(2, ':');
+                Some((kv.next()?.trim().to_string(), kv.next()?.trim().to_string()))
+            })
+            .collect::<Vec<_>>();
+        let content_length = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, v)| v.parse::<usize>().ok())
+            .unwrap_or(0);
+        let mut body = buf[header_end..].to_vec();
+        while body.len() < content_length {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+        let req = MockRequest {
+            method,
+            path,
+            headers,
+            body,
+        };
+        let resp = handler(&req);
+        recorded.lock().unwrap().push(req);
+
+        let mut out = format!("HTTP/1.1 {} Mock\r\n", resp.status);
+        for (k, v) in &resp.headers {
+            out.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        out.push_str(&format!(
+            "content-length: {}\r\nconnection: close\r\n\r\n",
+            resp.body.len()
+        ));
+        stream.write_all(out.as_bytes()).await?;
+        stream.write_all(&resp.body).await?;
+        stream.shutdown().await
+    }
+}
+
+/// 测试用的youdao与maimemo配置，请求的url对应[`recorded_response`]中录制的响应
+pub const FIXTURE_CONFIG: &str = include_str!("../../tests/fixtures/dict-config.yml");
+
+/// 录制的maimemo服务接受的验证码
+pub const FIXTURE_CAPTCHA: &str = "1234";
+
+impl MockServer {
+    /// 启动一个使用录制的响应代理youdao与maimemo的http proxy
+    pub async fn start_recorded() -> Self {
+        Self::start(recorded_response).await
+    }
+
+    /// 将[`FIXTURE_CONFIG`]写入dir，并通过`http.proxy`将所有请求发送到当前server。
+    /// 数据文件保存在dir中。返回配置文件路径
+    pub fn write_fixture_config(&self, dir: &Path) -> String {
+        let path = dir.join("dict-config.yml");
+        let contents = format!(
+            "data_dir: .\n{}http:\n    proxy: {}\n",
+            FIXTURE_CONFIG,
+            self.url("")
+        );
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+}
+
+/// 响应通过proxy发送的absolute-form请求。未登录或参数错误时返回与真实服务类似的失败响应
+pub fn recorded_response(req: &MockRequest) -> MockResponse {
+    let (path, query) = req.path.split_once('?').unwrap_or((req.path.as_str(), ""));
+    let cookie = req.header("cookie").unwrap_or_default();
+    let body = req.body_str();
+    match (req.method.as_str(), path) {
+        ("GET", "http://account.youdao.com/login") => MockResponse::text(200, "<html></html>")
+            .header(
+                "set-cookie",
+                "OUTFOX_SEARCH_USER_ID=fixture-user-id; Domain=youdao.com; Path=/; Max-Age=86400",
+            ),
+        ("POST", "http://logindict.youdao.com/login/acc/login") => {
+            if !cookie.contains("OUTFOX_SEARCH_USER_ID")
+                || !body.contains("username=fixture%40163.com")
+            {
+                return MockResponse::text(200, "login failed");
+            }
+            MockResponse::new(302)
+                .header("location", "http://dict.youdao.com/wordbook/wordlist")
+                .header(
+                    "set-cookie",
+                    "DICT_PERS=fixture-pers; Domain=youdao.com; Path=/; Max-Age=86400",
+                )
+        }
+        ("GET", "http://dict.youdao.com/wordbook/webapi/words") if cookie.contains("DICT_PERS") => {
+            youdao_words(query)
+        }
+        ("GET", "http://dict.youdao.com/wordbook/webapi/books") if cookie.contains("DICT_PERS") => {
+            fixture_json(include_bytes!("../../tests/fixtures/youdao-books.json"))
+        }
+        ("GET", "http://dict.youdao.com/jsonapi") => {
+            let body: &[u8] = match query.split('&').find_map(|kv| kv.strip_prefix("q=")) {
+                Some("serendipity") => {
+                    include_bytes!("../../tests/fixtures/youdao-lookup-word.json")
+                }
+                Some("look+up") => include_bytes!("../../tests/fixtures/youdao-lookup-phrase.json"),
+                _ => br#"{"input": "", "meta": {"dicts": ["meta"]}, "le": "en", "lang": "eng"}"#,
+            };
+            fixture_json(body)
+        }
+        ("GET", "http://dict.youdao.com/wordbook/webapi/v2/ajax/add")
+        | ("GET", "http://dict.youdao.com/wordbook/webapi/v2/ajax/delete")
+        | ("GET", "http://dict.youdao.com/wordbook/webapi/v2/ajax/modify") => {
+            if cookie.contains("DICT_PERS") {
+                fixture_json(include_bytes!(
+                    "../../tests/fixtures/youdao-wordbook-ok.json"
+                ))
+            } else {
+                fixture_json(include_bytes!(
+                    "../../tests/fixtures/youdao-wordbook-not-login.json"
+                ))
+            }
+        }
+        ("POST", "http://www.maimemo.com/auth/login") => {
+            if !body.contains("email=fixture%40gmail.com") {
+                return fixture_json(include_bytes!(
+                    "../../tests/fixtures/maimemo-login-failed.json"
+                ));
+            }
+            fixture_json(include_bytes!("../../tests/fixtures/maimemo-login.json")).header(
+                "set-cookie",
+                "userToken=fixture-token; Path=/; Max-Age=86400",
+            )
+        }
+        ("POST", "http://www.maimemo.com/v3/api/notepad/search")
+            if query == "token=fixture-token" && req.body_json()["scope"] == "ALL" =>
+        {
+            public_notepads(&req.body_json())
+        }
+        ("POST", "http://www.maimemo.com/v3/api/notepad/search")
+            if query == "token=fixture-token" =>
+        {
+            fixture_json(include_bytes!(
+                "../../tests/fixtures/maimemo-notepad-search.json"
+            ))
+        }
+        ("GET", p) if p.starts_with("http://www.maimemo.com/notepad/detail/") => {
+            let html = include_str!("../../tests/fixtures/maimemo-notepad-detail.html");
+            // 公开的notepads与695835的内容相同
+            if p.ends_with("/695835") || p.contains("/detail/9") {
+                MockResponse::text(200, html)
+            } else {
+                MockResponse::text(200, &html.replace("accommodate\nbenevolent", ""))
+            }
+        }
+        ("GET", "http://www.maimemo.com/service/captcha/image2") => MockResponse::new(200)
+            .header("content-type", "image/png")
+            .body(include_bytes!("../../tests/fixtures/maimemo-captcha.png")),
+        ("POST", "http://www.maimemo.com/notepad/save") if cookie.contains("userToken") => {
+            if body.contains(&format!("captcha={}", FIXTURE_CAPTCHA)) {
+                fixture_json(include_bytes!(
+                    "../../tests/fixtures/maimemo-notepad-save.json"
+                ))
+            } else {
+                fixture_json(include_bytes!(
+                    "../../tests/fixtures/maimemo-notepad-save-captcha-error.json"
+                ))
+            }
+        }
+        _ => MockResponse::text(404, "not found"),
+    }
+}
+
+/// 录制在tests/fixtures中的json响应
+fn fixture_json(body: &[u8]) -> MockResponse {
+    MockResponse::new(200)
+        .header("content-type", "application/json")
+        .body(body)
+}
+
+/// 公开notepads的数量，[`public_notepads`]重复使用录制的notepads
+pub const PUBLIC_NOTEPADS_TOTAL: usize = 40;
+
+/// 通过payload中的offset与limit分页返回公开的notepads。recommend时仅返回推荐的notepad
+fn public_notepads(payload: &serde_json::Value) -> MockResponse {
+    let mut page: serde_json::Value = serde_json::from_str(include_str!(
+        "../../tests/fixtures/maimemo-notepad-search-public.json"
+    ))
+    .unwrap();
+    let recorded = page["notepad"].as_array().unwrap().clone();
+    let notepads = if payload["recommend"] == true {
+        recorded
+            .into_iter()
+            .filter(|n| n["recommend"] == true)
+            .collect::<Vec<_>>()
+    } else {
+        (0..PUBLIC_NOTEPADS_TOTAL)
+            .map(|i| {
+                let mut notepad = recorded[i % recorded.len()].clone();
+                notepad["notepad_id"] = serde_json::json!((900001 + i).to_string());
+                notepad
+            })
+            .collect()
+    };
+    let offset = payload["offset"].as_u64().unwrap_or(0) as usize;
+    let limit = payload["limit"].as_u64().unwrap_or(0) as usize;
+    page["total"] = serde_json::json!(notepads.len());
+    page["notepad"] = serde_json::json!(notepads
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .collect::<Vec<_>>());
+    MockResponse::json(200, &page)
+}
+
+/// 通过query中的limit与offset分页返回录制的单词
+fn youdao_words(query: &str) -> MockResponse {
+    let param = |name: &str| {
+        query
+            .split('&')
+            .find_map(|kv| kv.strip_prefix(name)?.strip_prefix('='))
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(0)
+    };
+    let mut page: serde_json::Value =
+        serde_json::from_str(include_str!("../../tests/fixtures/youdao-get-words.json")).unwrap();
+    let items = page["data"]["itemList"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .skip(param("offset"))
+        .take(param("limit"))
+        .cloned()
+        .collect::<Vec<_>>();
+    page["data"]["itemList"] = serde_json::json!(items);
+    MockResponse::json(200, &page)
+}
+
+/// [`maimemo_open_api`]接受的api_token
+pub const FIXTURE_API_TOKEN: &str = "test-token";
+
+/// maimemo open api的云词本接口，api_url为server的`/notepads`。
+/// 保存更新后的内容，之后的获取返回新的内容。token不是[`FIXTURE_API_TOKEN`]时返回401
+pub fn maimemo_open_api() -> impl Fn(&MockRequest) -> MockResponse + Send + Sync {
+    let detail: serde_json::Value = serde_json::from_str(include_str!(
+        "../../tests/fixtures/maimemo-open-notepad.json"
+    ))
+    .unwrap();
+    let detail = Mutex::new(detail);
+    let authorization = format!("Bearer {}", FIXTURE_API_TOKEN);
+    move |req| {
+        if req.header("authorization") != Some(authorization.as_str()) {
+            return MockResponse::new(401);
+        }
+        let mut detail = detail.lock().unwrap();
+        match (req.method.as_str(), req.path.as_str()) {
+            ("GET", "/notepads?limit=100&offset=0") => fixture_json(include_bytes!(
+                "../../tests/fixtures/maimemo-open-notepads.json"
+            )),
+            ("GET", "/notepads/695835") => MockResponse::json(200, &detail),
+            ("POST", "/notepads/695835") => {
+                let body = req.body_json();
+                let notepad = &mut detail["data"]["notepad"];
+                for field in ["title", "brief", "content", "status", "tags"] {
+                    notepad[field] = body["notepad"][field].clone();
+                }
+                notepad["updated_time"] = "2024-05-02T08:00:00.000Z".into();
+                MockResponse::json(200, &detail)
+            }
+            ("POST", "/notepads") => {
+                let mut created = req.body_json();
+                created["notepad"]["id"] = "700000".into();
+                MockResponse::json(
+                    201,
+                    &serde_json::json!({"success": true, "errors": [], "data": created}),
+                )
+            }
+            ("DELETE", "/notepads/695835") => MockResponse::json(
+                200,
+                &serde_json::json!({"success": true, "errors": [], "data": {}}),
+            ),
+            ("GET", _) => MockResponse::json(
+                404,
+                &serde_json::json!({"success": false, "errors": [{"code": "common_not_found", "msg": "not found"}]}),
+            ),
+            _ => MockResponse::new(400),
+        }
+    }
+}
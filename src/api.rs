@@ -0,0 +1,166 @@
+//! 用于嵌入其它应用的高层api，如在tauri应用中使用youdao与maimemo。
+//!
+//! 所有函数都不会panic，也不会输出到stdout/stderr，错误通过`Result`返回。日志通过[`log`]输出，
+//! 由调用者决定是否初始化。cookies在每次调用后保存到配置的cookie_path中
+//!
+//! 通过[`HttpContext::with_recorder`]与[`Recorder::replay`]可以在不访问网络时运行：
+//!
+//! ```no_run
+//! use dict::api::{self, Config, HttpContext, Recorder};
+//! use std::sync::Arc;
+//!
+//! # async fn run() -> Result<(), String> {
+//! let mut config = Config::from_yaml_file("dict-config.yml")?;
+//! let context = HttpContext::new()?.with_recorder(Recorder::replay("recorded")?);
+//! let words = api::fetch_youdao_words(config.youdao(), Arc::new(context)).await?;
+//! println!("{} words", words.len());
+//! # Ok(())
+//! # }
+//! ```
+use crate::client::{maimemo_client::MaimemoClient, youdao_client::YoudaoClient, WordSource};
+use std::sync::Arc;
+
+pub use crate::client::{
+    context::HttpContext,
+    maimemo_client::{Captcha, Notepad, NotepadQuery},
+    record::Recorder,
+    youdao_client::WordItem,
+    BoxFuture, CaptchaProvider,
+};
+pub use crate::config::{AppConfig, Config, HttpConfig};
+
+/// 获取youdao单词本中的所有单词，需要时先登录
+pub async fn fetch_youdao_words(
+    config: AppConfig,
+    context: Arc<HttpContext>,
+) -> Result<Vec<WordItem>, String> {
+    let mut client = YoudaoClient::with_context(config, context)?;
+    let words = client.fetch_words().await?;
+    client.save().await?;
+    Ok(words)
+}
+
+/// 获取maimemo中自己的notepads与内容，需要时先登录
+pub async fn fetch_maimemo_notepads(
+    config: AppConfig,
+    context: Arc<HttpContext>,
+) -> Result<Vec<Notepad>, String> {
+    let mut client = logged_maimemo(config, context).await?;
+    let notepads = client.get_notepads().await?;
+    client.save().await?;
+    Ok(notepads)
+}
+
+/// 用contents替换maimemo中notepad_id的内容，标题与简介不变。
+///
+/// 验证码通过captcha_provider获取，验证码错误等失败时不会重试
+pub async fn upload_notepad(
+    config: AppConfig,
+    context: Arc<HttpContext>,
+    notepad_id: &str,
+    contents: &str,
+    mut captcha_provider: impl CaptchaProvider,
+) -> Result<(), String> {
+    let mut client = logged_maimemo(config, context).await?;
+    let mut notepad = client
+        .get_notepad_list(&NotepadQuery::default())
+        .await?
+        .into_iter()
+        .find(|n| n.get_notepad_id() == notepad_id)
+        .ok_or_else(|| format!("not found notepad_id: {}", notepad_id))?;
+    notepad.set_contents(Some(contents.to_string()));
+    let captcha = client.refresh_captcha().await?;
+    captcha.ensure_image()?;
+    let answer = captcha_provider.solve(&captcha).await?;
+    let result = client.save_notepad(notepad, answer).await;
+    client.save().await?;
+    result.map_err(String::from)
+}
+
+async fn logged_maimemo(
+    config: AppConfig,
+    context: Arc<HttpContext>,
+) -> Result<MaimemoClient, String> {
+    let mut client = MaimemoClient::with_context(config, context)?;
+    if !client.has_logged() {
+        debug!("Signing in maimemo");
+        client
+            .login()
+            .await
+            .map_err(|e| format!("maimemo login error: {}", e))?;
+    }
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::{MockServer, FIXTURE_CAPTCHA};
+
+    fn fixture_config(server: &MockServer, dir: &tempfile::TempDir) -> (Config, Arc<HttpContext>) {
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path())).unwrap();
+        let context = HttpContext::from_config(&config.http()).unwrap();
+        (config, Arc::new(context))
+    }
+
+    #[tokio::test]
+    async fn fetch_and_upload() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut config, context) = fixture_config(&server, &dir);
+        let words = fetch_youdao_words(config.youdao(), context.clone()).await?;
+        assert!(!words.is_empty());
+
+        let (mut config, _) = fixture_config(&server, &dir);
+        let notepads = fetch_maimemo_notepads(config.maimemo(), context.clone()).await?;
+        assert_eq!(notepads[0].get_contents(), Some("accommodate\nbenevolent"));
+        // 已保存cookies，不再登录
+        let logins = |server: &MockServer| {
+            server
+                .requests()
+                .iter()
+                .filter(|r| r.path.contains("maimemo.com/auth/login"))
+                .count()
+        };
+        let logged = logins(&server);
+
+        let mut shown = 0;
+        let (mut config, _) = fixture_config(&server, &dir);
+        upload_notepad(
+            config.maimemo(),
+            context.clone(),
+            "695835",
+            "run",
+            |captcha: &Captcha| {
+                shown += captcha.bytes.len();
+                Ok(FIXTURE_CAPTCHA.to_string())
+            },
+        )
+        .await?;
+        assert!(shown > 0);
+        assert_eq!(logins(&server), logged);
+        let save = server.requests().pop().unwrap();
+        assert!(save.body_str().contains("id=695835&title=english"));
+        assert!(save.body_str().contains("content=run"));
+
+        let (mut config, _) = fixture_config(&server, &dir);
+        let e = upload_notepad(
+            config.maimemo(),
+            context.clone(),
+            "1",
+            "run",
+            |_: &Captcha| Ok(FIXTURE_CAPTCHA.to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(e.contains("not found notepad_id"), "{}", e);
+
+        let (mut config, _) = fixture_config(&server, &dir);
+        let cancel = |_: &Captcha| Err("canceled".to_string());
+        let e = upload_notepad(config.maimemo(), context, "695835", "run", cancel)
+            .await
+            .unwrap_err();
+        assert_eq!(e, "canceled");
+        Ok(())
+    }
+}
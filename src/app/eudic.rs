@@ -0,0 +1,114 @@
+//! 欧路词典生词本
+
+use dict::{
+    client::{context::HttpContext, eudic_client::EudicClient},
+    config::AppConfig,
+    msg,
+    prompt::{self, UserPrompt},
+    store::{open_word_storage, MergeReport, WordStorage},
+};
+
+use std::io::{self, prelude::*, Write};
+use std::sync::Arc;
+
+use super::{list_words, read_words, refresh_words};
+use crate::OutputFormat;
+
+/// 欧路词典生词本，本地单词与youdao使用相同的存储
+pub(crate) struct EudicApp<'a> {
+    word_items: Box<dyn WordStorage>,
+    client: EudicClient,
+    prompt: Option<UserPrompt<'a>>,
+    pub(crate) output: io::BufWriter<Box<dyn Write>>,
+    assume_yes: bool,
+    quiet: bool,
+}
+
+impl<'a> EudicApp<'a> {
+    /// 打开本地单词本，不存在时使用空的单词本。refresh时从eudic生词本中重新加载
+    pub async fn new(
+        config: AppConfig,
+        context: Arc<HttpContext>,
+        refresh: bool,
+        prompt: Option<UserPrompt<'a>>,
+        output: impl Write + 'static,
+    ) -> Result<EudicApp<'a>, String> {
+        let word_items = open_word_storage(&config, true, context.is_read_only())
+            .await
+            .map_err(|e| format!("eudic load words failed. {}", e))?;
+        let client = EudicClient::with_context(config, context)
+            .map_err(|e| format!("eudic client new failed. {}", e))?;
+        let mut app = Self {
+            word_items,
+            client,
+            prompt,
+            output: io::BufWriter::new(Box::new(output)),
+            assume_yes: false,
+            quiet: false,
+        };
+        if refresh {
+            let report = app.refresh().await?;
+            debug!("refreshed eudic words: {:?}", report);
+        }
+        Ok(app)
+    }
+
+    pub fn with_assume_yes(mut self, assume_yes: bool) -> Self {
+        self.assume_yes = assume_yes;
+        self
+    }
+
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// 用生词本中的单词替换本地单词
+    pub async fn refresh(&mut self) -> Result<MergeReport, String> {
+        refresh_words(&mut self.client, self.word_items.as_mut()).await
+    }
+
+    /// 按修改时间降序输出单词，json时输出完整的单词
+    pub fn list(&mut self, offset: isize, format: OutputFormat) -> Result<(), String> {
+        list_words(&mut self.output, self.word_items.words(), offset, format)
+    }
+
+    /// 从input中读取单词添加到生词本后刷新本地单词。返回添加的单词数量
+    pub async fn add(&mut self, input: impl Read) -> Result<usize, String> {
+        let words = self.read_words(input)?;
+        let list = self.client.find_study_list().await?;
+        self.client.add_words(&list, &words).await?;
+        self.refresh().await?;
+        Ok(words.len())
+    }
+
+    /// 从input中读取单词，确认后从生词本中删除并刷新本地单词。返回删除的单词数量，未确认时为0
+    pub async fn delete(&mut self, input: impl Read) -> Result<usize, String> {
+        let words = self.read_words(input)?;
+        let list = self.client.find_study_list().await?;
+        let summary = msg!("confirm.delete_eudic_words", words.len(), list.name);
+        if !words.is_empty()
+            && !prompt::confirm(self.prompt.as_mut(), self.assume_yes, &summary).await?
+        {
+            info!("canceled deleting words");
+            return Ok(0);
+        }
+        self.client.delete_words(&list, &words).await?;
+        self.refresh().await?;
+        Ok(words.len())
+    }
+
+    /// 读取规范化后的单词，见[`read_words`]
+    fn read_words(&self, mut input: impl Read) -> Result<Vec<String>, String> {
+        let mut contents = String::new();
+        input
+            .read_to_string(&mut contents)
+            .map_err(|e| format!("read contents to string error: {}", e))?;
+        Ok(read_words(&contents, self.quiet))
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        self.output.flush().map_err(|e| e.to_string())?;
+        self.word_items.flush().map(|_| ())
+    }
+}
@@ -0,0 +1,2615 @@
+//! maimemo notepads的上传、比较、备份与恢复
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+use dict::{
+    api::CaptchaProvider,
+    backup::NotepadBackups,
+    captcha::{self, CommandCaptcha},
+    client::{
+        context::HttpContext,
+        maimemo_client::{
+            self, Captcha, CaptchaSink, MaimemoClient, Notepad, NotepadQuery, NotepadSort,
+            SaveNotepadError,
+        },
+        maimemo_open_client::MaimemoOpenClient,
+        BoxFuture,
+    },
+    color::ColorChoice,
+    config::{AppConfig, CaptchaCase},
+    content_lint, diff, duplicates, error,
+    events::Event,
+    msg,
+    prompt::{self, UserPrompt},
+    store::NotepadStore,
+    table::{self, Table},
+};
+use serde::Serialize;
+use similar::TextDiff;
+
+use std::io::{self, prelude::*, Write};
+use std::str;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::{emit_result, normalize_input, push_notepad, replace_local_notepad, RefreshReport};
+use crate::{format_count, OutputFormat};
+
+/// 本地缓存的notepad与maimemo中当前版本的比较
+#[derive(Debug, Serialize)]
+struct NotepadDiff {
+    notepad_id: String,
+    /// 本地是否有缓存
+    cached: bool,
+    /// maimemo中已删除
+    deleted: bool,
+    local_updated_time: Option<String>,
+    remote_updated_time: Option<String>,
+    /// 内容相同或已删除时为None
+    diff: Option<String>,
+    /// text格式时输出的摘要diff，见[`diff::render_diff`]
+    #[serde(skip)]
+    preview: Option<String>,
+}
+
+impl std::fmt::Display for NotepadDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.deleted {
+            return write!(f, "notepad {} was deleted remotely", self.notepad_id);
+        }
+        if !self.cached {
+            writeln!(f, "notepad {} is not cached locally", self.notepad_id)?;
+        }
+        let time = |t: &Option<String>| t.clone().unwrap_or_else(|| "-".to_string());
+        write!(
+            f,
+            "updated_time: local {}, remote {} ({})",
+            time(&self.local_updated_time),
+            time(&self.remote_updated_time),
+            if self.local_updated_time == self.remote_updated_time {
+                "unchanged"
+            } else {
+                "changed"
+            }
+        )?;
+        match self.preview.as_ref().or(self.diff.as_ref()) {
+            Some(diff) => write!(f, "\n{}", diff.trim_end_matches('\n')),
+            None => write!(f, "\ncontents are identical"),
+        }
+    }
+}
+
+/// 覆盖notepad前的提示
+fn replace_summary(old: &Notepad, new: &Notepad) -> String {
+    let lines = |n: &Notepad| n.get_contents().map_or(0, |s| s.lines().count());
+    msg!(
+        "confirm.replace_notepad",
+        old.get_title(),
+        format_count(lines(old)),
+        format_count(lines(new))
+    )
+}
+
+/// maimemo的后端。配置api_token时通过open api获取与上传notepads，不再登录与输入验证码
+#[allow(clippy::large_enum_variant)]
+enum MaimemoBackend {
+    Web(MaimemoClient),
+    Open(MaimemoOpenClient),
+}
+
+impl MaimemoBackend {
+    fn context(&self) -> &HttpContext {
+        match self {
+            Self::Web(client) => client.context(),
+            Self::Open(client) => client.context(),
+        }
+    }
+
+    /// open api不需要登录
+    fn has_logged(&self) -> bool {
+        match self {
+            Self::Web(client) => client.has_logged(),
+            Self::Open(_) => true,
+        }
+    }
+
+    /// 通过网页登录的client。使用open api时返回不支持operation的error
+    fn web(&mut self, operation: &str) -> Result<&mut MaimemoClient, String> {
+        match self {
+            Self::Web(client) => Ok(client),
+            Self::Open(_) => Err(msg!("notepad.open_api_unsupported", operation)),
+        }
+    }
+
+    /// open api的client。未配置api_token时返回需要open api的error
+    fn open(&self, operation: &str) -> Result<&MaimemoOpenClient, String> {
+        match self {
+            Self::Web(_) => Err(msg!("notepad.open_api_required", operation)),
+            Self::Open(client) => Ok(client),
+        }
+    }
+}
+
+/// 登录后获取所有notepads，未修改的notepad使用store中本地的内容。
+/// 失败时保存已获取的notepads与cookies，下次refresh时继续
+async fn fetch_web_notepads(
+    client: &mut MaimemoClient,
+    store: &mut NotepadStore,
+) -> Result<Vec<Notepad>, String> {
+    debug!("Signing in");
+    client
+        .login()
+        .await
+        .map_err(|e| format!("maimemo client login failed: {}", e))?;
+    match client.get_notepads_cached(store.get()).await {
+        Ok(notepads) => Ok(notepads),
+        Err(e) => {
+            let msg = format!("get notepads failed: {}", e);
+            if !e.fetched.is_empty() {
+                let report = store.merge(e.fetched);
+                match store.flush() {
+                    Ok(_) => info!("saved partially fetched notepads: {:?}", report),
+                    Err(e) => warn!("save partially fetched notepads error: {}", e),
+                }
+            }
+            if let Err(e) = client.save().await {
+                warn!("save maimemo cookies error: {}", e);
+            }
+            Err(msg)
+        }
+    }
+}
+
+pub struct MaimemoApp<'a> {
+    pub(crate) notepads: NotepadStore,
+    backend: MaimemoBackend,
+    /// 没有终端时为None
+    prompt: Option<UserPrompt<'a>>,
+    pub(crate) output: io::BufWriter<Box<dyn Write + 'a>>,
+    captcha: Option<String>,
+    /// 本次运行中上次被maimemo接受的验证码
+    solved_captcha: Option<String>,
+    /// 上传多个notepads时在保存的同时获取下一个验证码，见[`MaimemoApp::upload_notepads`]
+    prefetch_captcha: bool,
+    /// 覆盖notepad前不询问
+    assume_yes: bool,
+    /// 覆盖notepad前不检查maimemo中是否有refresh后的修改
+    force: bool,
+    upload_options: UploadOptions,
+    /// 覆盖notepad前的本地备份，为None时不备份
+    backups: Option<NotepadBackups>,
+    /// 从web上加载时refresh的结果
+    pub(crate) refresh_report: Option<RefreshReport>,
+    captcha_command: Option<CommandCaptcha>,
+    /// diff与覆盖notepad前预览的颜色
+    color: ColorChoice,
+    /// diff与预览最多输出的hunk数量，None时输出全部
+    diff_hunks: Option<usize>,
+}
+
+/// upload时处理stdin内容的选项
+#[derive(Debug, Default, Clone)]
+pub struct UploadOptions {
+    /// 不规范化内容
+    pub(crate) raw: bool,
+    /// 有像短语的行时报错而不是警告
+    pub(crate) strict: bool,
+    /// 修复会被maimemo删除的字符而不是警告
+    pub(crate) fix: bool,
+    /// 不输出规范化的统计等提示信息
+    pub(crate) quiet: bool,
+    /// 为空时使用[`DEFAULT_TIMESTAMP_FORMAT`]
+    pub(crate) timestamp_format: Option<String>,
+    /// 为空时使用[`DEFAULT_HEADER_TEMPLATE`]
+    pub(crate) header_template: Option<String>,
+    /// 从新内容的前几行生成简介，为None时不修改简介
+    pub(crate) brief_lines: Option<usize>,
+}
+
+/// search_public默认最多输出的数量
+pub(crate) const DEFAULT_SEARCH_LIMIT: usize = 30;
+
+/// 默认插入时间戳的格式
+const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// 默认插入的header
+const DEFAULT_HEADER_TEMPLATE: &str = "# {date} Auto insert";
+
+/// 检查strftime格式是否合法
+pub(crate) fn validate_timestamp_format(format: &str) -> Result<(), String> {
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        Err(format!("invalid timestamp format: {}", format))
+    } else {
+        Ok(())
+    }
+}
+
+/// 使用now生成header，template中的`{date}`替换为format格式的时间
+fn build_header(
+    template: Option<&str>,
+    format: Option<&str>,
+    now: DateTime<Local>,
+) -> Result<String, String> {
+    let format = format.unwrap_or(DEFAULT_TIMESTAMP_FORMAT);
+    validate_timestamp_format(format)?;
+    let date = now.format(format).to_string();
+    Ok(template
+        .unwrap_or(DEFAULT_HEADER_TEMPLATE)
+        .replace("{date}", &date))
+}
+
+impl<'a> std::ops::Drop for MaimemoApp<'a> {
+    /// 如果notepads在修改后未通过[`MaimemoApp::save`]保存，则在drop时保存
+    fn drop(&mut self) {
+        match self.notepads.flush() {
+            Ok(true) => warn!("notepads were not saved explicitly, saved late in drop"),
+            Ok(false) => {}
+            Err(e) => error!("save notepads failed in drop: {}", e),
+        }
+    }
+}
+
+impl<'a> MaimemoApp<'a> {
+    pub async fn new(
+        config: AppConfig,
+        context: Arc<HttpContext>,
+        is_local: bool,
+        prompt: Option<UserPrompt<'a>>,
+        output: impl io::Write + 'a,
+    ) -> Result<MaimemoApp<'a>, String> {
+        let dictionary_path = config.get_dictionary_path().to_string();
+        let pretty = config.is_pretty_store();
+        let mut backend = match config.get_api_token() {
+            Some(_) => MaimemoBackend::Open(
+                MaimemoOpenClient::with_context(config, context)
+                    .map_err(|e| format!("new maimemo open client failed: {}", e))?,
+            ),
+            None => MaimemoBackend::Web(
+                MaimemoClient::with_context(config, context)
+                    .map_err(|e| format!("new maimemo client failed: {}", e))?,
+            ),
+        };
+
+        let (notepads, refresh_report) = if is_local {
+            let notepads = NotepadStore::load(&dictionary_path).await.map_err(|e| {
+                format!(
+                    "load maimemo dictionary error: {}, dictionary_path: {}",
+                    e, dictionary_path
+                )
+            })?;
+            (notepads, None)
+        } else {
+            // load from web
+            let started = Instant::now();
+            backend.context().emit(Event::RefreshStarted {
+                service: "maimemo".to_string(),
+            });
+            // 与本地的notepads对比，无法读取时作为新的notepads
+            let mut store = NotepadStore::load_or_default(&dictionary_path)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("ignored local notepads: {}", e);
+                    NotepadStore::new(&dictionary_path, vec![])
+                })
+                .with_pretty(pretty);
+            let notepads = match &mut backend {
+                MaimemoBackend::Open(open) => {
+                    debug!("Fetching notepads by open api");
+                    open.get_notepads()
+                        .await
+                        .map_err(|e| format!("get notepads by open api failed: {}", e))
+                }
+                MaimemoBackend::Web(client) => fetch_web_notepads(client, &mut store).await,
+            };
+            let notepads = notepads.inspect_err(|e| backend.context().emit(Event::error(e)))?;
+            let report = store.replace(notepads);
+            let report = RefreshReport::new("maimemo", report, started, &dictionary_path);
+            (store, Some(report))
+        };
+        let notepads = notepads.with_pretty(pretty);
+        Ok(Self::with_parts(
+            backend,
+            notepads,
+            prompt,
+            output,
+            refresh_report,
+        ))
+    }
+
+    fn with_parts(
+        backend: MaimemoBackend,
+        notepads: NotepadStore,
+        prompt: Option<UserPrompt<'a>>,
+        output: impl io::Write + 'a,
+        refresh_report: Option<RefreshReport>,
+    ) -> Self {
+        Self {
+            backend,
+            notepads,
+            prompt,
+            output: io::BufWriter::new(Box::new(output)),
+            captcha: None,
+            solved_captcha: None,
+            prefetch_captcha: false,
+            assume_yes: false,
+            force: false,
+            upload_options: UploadOptions::default(),
+            backups: None,
+            refresh_report,
+            captcha_command: None,
+            color: ColorChoice::Never,
+            diff_hunks: Some(diff::DEFAULT_MAX_HUNKS),
+        }
+    }
+
+    /// 配置api_token时使用open api，否则需要已通过cookies登录
+    fn has_logged(&self) -> bool {
+        self.backend.has_logged()
+    }
+
+    /// maimemo中notepad_id的元数据，open api时同时包含内容。不存在时返回None
+    async fn get_notepad_info(&mut self, notepad_id: &str) -> Result<Option<Notepad>, String> {
+        match &mut self.backend {
+            MaimemoBackend::Open(open_client) => open_client.get_notepad(notepad_id).await,
+            MaimemoBackend::Web(client) => client.get_notepad_info(notepad_id).await,
+        }
+    }
+    /// 从web maimemo上加载notepads
+    pub async fn with_stdio(
+        config: AppConfig,
+        context: Arc<HttpContext>,
+        is_local: bool,
+    ) -> Result<MaimemoApp<'a>, String> {
+        // 修复在stdin使用管道线时无法使用用户输入问题
+        let prompt = UserPrompt::open()
+            .map_err(|e| debug!("prompts are unavailable: {}", e))
+            .ok();
+        MaimemoApp::new(config, context, is_local, prompt, io::stdout()).await
+    }
+
+    /// upload时使用已获取的验证码
+    pub fn with_captcha(mut self, captcha: Option<String>) -> Self {
+        self.captcha = captcha;
+        self
+    }
+
+    /// 上传多个notepads时预先获取验证码，用户连续回答所有验证码
+    pub fn with_prefetch_captcha(mut self, prefetch: bool) -> Self {
+        self.prefetch_captcha = prefetch;
+        self
+    }
+
+    /// upload覆盖notepad前不询问
+    pub fn with_assume_yes(mut self, assume_yes: bool) -> Self {
+        self.assume_yes = assume_yes;
+        self
+    }
+
+    /// 覆盖notepad时忽略与maimemo中修改的冲突
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn with_upload_options(mut self, options: UploadOptions) -> Self {
+        self.upload_options = options;
+        self
+    }
+
+    /// 覆盖notepad前备份本地的内容
+    pub fn with_backups(mut self, backups: NotepadBackups) -> Self {
+        self.backups = Some(backups);
+        self
+    }
+
+    /// 通过command识别验证码，`--captcha`优先
+    pub fn with_captcha_command(mut self, command: Option<CommandCaptcha>) -> Self {
+        self.captcha_command = command;
+        self
+    }
+
+    /// diff与覆盖notepad前预览的颜色与最多输出的hunk数量，max_hunks为None时输出全部
+    pub fn with_diff_view(mut self, color: ColorChoice, max_hunks: Option<usize>) -> Self {
+        self.color = color;
+        self.diff_hunks = max_hunks;
+        self
+    }
+
+    /// old与new之间的摘要diff，相同时返回None
+    fn render_diff(
+        &self,
+        old: &str,
+        new: &str,
+        color: bool,
+        width: Option<usize>,
+    ) -> Option<String> {
+        let view = diff::DiffView {
+            max_hunks: self.diff_hunks,
+            color,
+        };
+        Some(diff::render_diff(
+            &TextDiff::from_lines(old, new),
+            width,
+            view,
+        ))
+        .filter(|s| !s.is_empty())
+    }
+
+    pub async fn with_stdio_local(config: AppConfig) -> Result<MaimemoApp<'a>, String> {
+        Self::with_stdio(config, Self::default_context(), true).await
+    }
+
+    pub async fn with_stdio_web(config: AppConfig) -> Result<MaimemoApp<'a>, String> {
+        Self::with_stdio(config, Self::default_context(), false).await
+    }
+
+    fn default_context() -> Arc<HttpContext> {
+        Arc::new(HttpContext::new().unwrap_or_else(|e| panic!("new http context failed: {}", e)))
+    }
+
+    /// 从stdin将指定notepad_id的内容更新到maimemo web上。当保存成功后更新
+    /// notepads，需要调用[`MaimemoApp::save`]保存
+    ///
+    /// 如果is_appending=true则在原notepad上添加，否则清楚仅用stdin中的内容
+    ///
+    /// 如果timestamp=true则自动插入时间戳
+    ///
+    /// # Errors
+    ///
+    /// 如果client未登录或上传失败，见[`MaimemoApp::try_upload_notepad`]
+    pub async fn upload_notepad(
+        &mut self,
+        contents_read: impl io::Read,
+        notepad_id: &str,
+        is_appending: bool,
+        timestamp: bool,
+    ) -> Result<(), String> {
+        self.try_upload_notepad(contents_read, notepad_id, is_appending, timestamp)
+            .await
+            .map(|_| ())
+    }
+
+    /// 将contents上传到ids中的每个notepad，一个失败时继续上传之后的，询问超时时不再上传之后的。
+    /// 返回已尝试的每个notepad的结果，
+    /// 见[`MaimemoApp::try_upload_notepad`]。同一次运行中输入的验证码在maimemo允许时被复用。
+    ///
+    /// 开启prefetch_captcha时先确认所有的notepads，之后见[`MaimemoApp::save_prefetched`]
+    pub async fn upload_notepads(
+        &mut self,
+        contents: &str,
+        notepad_ids: &[String],
+        is_appending: bool,
+        timestamp: bool,
+    ) -> Vec<(String, Result<bool, String>)> {
+        if self.prefetch_captcha
+            && matches!(self.backend, MaimemoBackend::Web(_))
+            && self.captcha.is_none()
+            && notepad_ids.len() > 1
+        {
+            return self
+                .upload_prefetched(contents, notepad_ids, is_appending, timestamp)
+                .await;
+        }
+        let mut results = vec![];
+        for notepad_id in notepad_ids {
+            let result = self
+                .try_upload_notepad(contents.as_bytes(), notepad_id, is_appending, timestamp)
+                .await;
+            let timed_out = matches!(&result, Err(e) if error::DictError::classify(e) == Some(error::DictError::PromptTimeout));
+            if let Err(e) = &result {
+                warn!("upload notepad {} error: {}", notepad_id, e);
+            }
+            results.push((notepad_id.clone(), result));
+            if timed_out {
+                break;
+            }
+        }
+        results
+    }
+
+    /// 同[`MaimemoApp::upload_notepad`]，返回是否已上传，用户取消时为false
+    ///
+    /// # Errors
+    ///
+    /// 如果client未登录、notepad不存在或上传失败
+    pub async fn try_upload_notepad(
+        &mut self,
+        contents_read: impl io::Read,
+        notepad_id: &str,
+        is_appending: bool,
+        timestamp: bool,
+    ) -> Result<bool, String> {
+        let new_notepad = match self
+            .prepare_upload(contents_read, notepad_id, is_appending, timestamp)
+            .await?
+        {
+            Some(notepad) => notepad,
+            None => return Ok(false),
+        };
+        match self.push(new_notepad).await {
+            Ok(true) => {
+                debug!("upload notepad successful for notepad_id: {}", notepad_id);
+                Ok(true)
+            }
+            Ok(false) => {
+                debug!("upload aborted by user");
+                Ok(false)
+            }
+            Err(e) => {
+                self.backend.context().emit(Event::error(&e));
+                Err(e)
+            }
+        }
+    }
+
+    /// 上传前检查冲突，生成新的notepad，覆盖时确认并备份。用户取消时返回None
+    async fn prepare_upload(
+        &mut self,
+        contents_read: impl io::Read,
+        notepad_id: &str,
+        is_appending: bool,
+        timestamp: bool,
+    ) -> Result<Option<Notepad>, String> {
+        if !self.has_logged() {
+            return Err(msg!("notepad.not_logged_in"));
+        }
+        self.check_conflict(notepad_id).await?;
+        let new_notepad = self
+            .build_uploaded_notepad(contents_read, notepad_id, is_appending, timestamp)
+            .await
+            .map_err(|e| msg!("notepad.build_error", e))?;
+        if !is_appending {
+            let old_notepad = self
+                .notepads
+                .get()
+                .iter()
+                .find(|n| n.get_notepad_id() == notepad_id)
+                .ok_or_else(|| format!("not found notepad_id: {}", notepad_id))?;
+            let summary = replace_summary(old_notepad, &new_notepad);
+            if !self.assume_yes {
+                let preview = self.render_diff(
+                    old_notepad.get_contents().unwrap_or_default(),
+                    new_notepad.get_contents().unwrap_or_default(),
+                    self.color.stderr(),
+                    table::stderr_width(),
+                );
+                if let Some(preview) = preview {
+                    eprint!("{}", preview);
+                }
+            }
+            let confirmed = prompt::confirm(self.prompt.as_mut(), self.assume_yes, &summary)
+                .await
+                .map_err(|e| msg!("prompt.read_error", e))?;
+            if !confirmed {
+                eprintln!("{}", msg!("aborted"));
+                return Ok(None);
+            }
+            self.backup_notepad(notepad_id)
+                .map_err(|e| msg!("notepad.backup_error", e))?;
+        }
+        Ok(Some(new_notepad))
+    }
+
+    /// 同[`MaimemoApp::upload_notepads`]，先确认所有的notepads再通过预先获取的验证码保存。
+    /// 验证码错误时通过[`MaimemoApp::push`]重新上传
+    async fn upload_prefetched(
+        &mut self,
+        contents: &str,
+        notepad_ids: &[String],
+        is_appending: bool,
+        timestamp: bool,
+    ) -> Vec<(String, Result<bool, String>)> {
+        let mut results = vec![];
+        let mut prepared = vec![];
+        for notepad_id in notepad_ids {
+            let result = self
+                .prepare_upload(contents.as_bytes(), notepad_id, is_appending, timestamp)
+                .await;
+            let result = match result {
+                // 保存后替换
+                Ok(Some(notepad)) => {
+                    prepared.push((results.len(), notepad));
+                    Ok(true)
+                }
+                Ok(None) => Ok(false),
+                Err(e) => {
+                    warn!("upload notepad {} error: {}", notepad_id, e);
+                    Err(e)
+                }
+            };
+            let timed_out = matches!(&result, Err(e) if error::DictError::classify(e) == Some(error::DictError::PromptTimeout));
+            results.push((notepad_id.clone(), result));
+            if timed_out {
+                break;
+            }
+        }
+        let notepads = prepared.iter().map(|(_, n)| n.clone()).collect::<Vec<_>>();
+        let saved = self.save_prefetched(&notepads).await;
+        for ((index, notepad), result) in prepared.into_iter().zip(saved) {
+            let notepad_id = notepad.get_notepad_id().to_string();
+            let result = match result {
+                Ok(_) => {
+                    self.update_remote_time(&notepad_id).await;
+                    Ok(true)
+                }
+                Err(SaveNotepadError::WrongCaptcha(_)) => {
+                    debug!(
+                        "the prefetched captcha of notepad {} was rejected",
+                        notepad_id
+                    );
+                    self.push(notepad).await
+                }
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = &result {
+                warn!("upload notepad {} error: {}", notepad_id, e);
+                self.backend.context().emit(Event::error(e));
+            }
+            results[index].1 = result;
+        }
+        results
+    }
+
+    /// 保存notepads并更新本地的notepads，不更新updated_time。返回每个notepad的结果。
+    ///
+    /// 获取与回答验证码在一个任务中连续进行，保存在另一个任务中使用回答的验证码。
+    /// 保存一个notepad的同时获取下一个验证码，最多提前一个，获取时仍然遵守`captcha_min_interval_secs`
+    async fn save_prefetched(&mut self, notepads: &[Notepad]) -> Vec<Result<(), SaveNotepadError>> {
+        // 只读模式在读取验证码前失败
+        if let Err(e) = self.backend.context().check_writable("save notepads") {
+            return notepads.iter().map(|_| Err(e.clone().into())).collect();
+        }
+        let (answer_tx, mut answer_rx) = tokio::sync::mpsc::channel::<String>(1);
+        let (saved_tx, mut saved_rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+        let available = self.prompt.is_some() || self.captcha_command.is_some();
+        let client = match &self.backend {
+            MaimemoBackend::Web(client) => client,
+            MaimemoBackend::Open(_) => {
+                let e = msg!("notepad.open_api_unsupported", "captcha");
+                return notepads.iter().map(|_| Err(e.clone().into())).collect();
+            }
+        };
+        let provider = UserCaptcha {
+            context: client.context(),
+            prompt: &mut self.prompt,
+            command: self.captcha_command.as_mut(),
+            assume_yes: self.assume_yes,
+            case: client.captcha_case(),
+        };
+        let mut solver = CaptchaSink::new(client, provider);
+        let count = notepads.len();
+        let fetch = async move {
+            if !available {
+                return Err(SaveNotepadError::ReadCaptcha(msg!("prompt.input_required")));
+            }
+            for i in 0..count {
+                // 第i个验证码在第i-2个notepad保存后获取
+                if i >= 2 && saved_rx.recv().await.is_none() {
+                    break;
+                }
+                let answer = solver.read_captcha().await?;
+                trace!("prefetched captcha {} of {}", i + 1, count);
+                if answer_tx.send(answer).await.is_err() {
+                    break;
+                }
+            }
+            Ok::<_, SaveNotepadError>(())
+        };
+        let mut sink = PrefetchedSink {
+            client,
+            answers: &mut answer_rx,
+        };
+        let store = &mut self.notepads;
+        let save = async {
+            let mut results = vec![];
+            for notepad in notepads {
+                let result = match sink.save(notepad).await {
+                    Ok(()) => replace_local_notepad(store, notepad.clone()).map_err(Into::into),
+                    Err(e) => Err(e),
+                };
+                // fetch结束后不再需要
+                let _ = saved_tx.send(());
+                results.push(result);
+            }
+            results
+        };
+        // fetch与save借用了self，不能spawn到其它任务。join在当前任务中并发poll两者，
+        // 获取验证码、运行captcha command与保存都是异步的，save等待response时fetch可以获取下一个验证码
+        let (fetched, results) = tokio::join!(fetch, save);
+        match fetched {
+            Ok(()) => results,
+            Err(e) => results
+                .into_iter()
+                .map(|r| match r {
+                    Err(SaveNotepadError::Other(no_answer))
+                        if no_answer == NO_PREFETCHED_CAPTCHA =>
+                    {
+                        Err(e.clone())
+                    }
+                    r => r,
+                })
+                .collect(),
+        }
+    }
+
+    /// 未指定timestamp时输出notepad_id的所有备份，否则用该备份覆盖notepad。
+    /// 覆盖前同样会询问并备份当前的内容
+    pub async fn restore_notepad(
+        &mut self,
+        notepad_id: &str,
+        timestamp: Option<&str>,
+    ) -> Result<(), String> {
+        let backups = self
+            .backups
+            .as_ref()
+            .ok_or("notepad backups are disabled by notepad_backups: 0")?;
+        let timestamp = match timestamp {
+            Some(timestamp) => timestamp,
+            None => {
+                let timestamps = backups.list(notepad_id)?;
+                if timestamps.is_empty() && !self.upload_options.quiet {
+                    eprintln!("{}", msg!("notepad.no_backups", notepad_id));
+                }
+                for timestamp in timestamps {
+                    writeln!(self.output, "{}", timestamp)
+                        .map_err(|e| format!("write backups error: {}", e))?;
+                }
+                return Ok(());
+            }
+        };
+        let contents = backups.read(notepad_id, timestamp)?;
+        if !self.has_logged() {
+            return Err(msg!("notepad.not_logged_in"));
+        }
+        self.check_conflict(notepad_id).await?;
+        let old_notepad = self
+            .notepads
+            .get()
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id)
+            .ok_or_else(|| format!("not found notepad_id: {}", notepad_id))?;
+        let mut new_notepad = old_notepad.clone();
+        new_notepad.set_contents(Some(contents));
+        let summary = replace_summary(old_notepad, &new_notepad);
+        if !prompt::confirm(self.prompt.as_mut(), self.assume_yes, &summary).await? {
+            eprintln!("{}", msg!("aborted"));
+            return Ok(());
+        }
+        self.backup_notepad(notepad_id)?;
+        if self.push(new_notepad).await? {
+            info!("restored notepad {} from backup {}", notepad_id, timestamp);
+        }
+        Ok(())
+    }
+
+    /// 将notepad_id中没有的words添加到notepad的最后，忽略大小写，lemma时使用单词原形比较。
+    /// 返回添加的单词数量，没有新的单词时不上传
+    pub async fn append_words(
+        &mut self,
+        notepad_id: &str,
+        words: &[String],
+        timestamp: bool,
+        lemma: bool,
+    ) -> Result<usize, String> {
+        if !self.has_logged() {
+            return Err(msg!("notepad.not_logged_in"));
+        }
+        let words = self.new_words(notepad_id, words, lemma)?;
+        if words.is_empty() {
+            debug!("all words are already in notepad {}", notepad_id);
+            return Ok(0);
+        }
+        self.check_conflict(notepad_id).await?;
+        let contents = words.join("\n");
+        let notepad = self
+            .build_uploaded_notepad(contents.as_bytes(), notepad_id, true, timestamp)
+            .await?;
+        if !self.push(notepad).await? {
+            return Err(msg!("aborted"));
+        }
+        Ok(words.len())
+    }
+
+    /// 本地notepad_id中没有的words，忽略大小写与重复的单词，lemma时使用单词原形比较
+    pub(crate) fn new_words(
+        &self,
+        notepad_id: &str,
+        words: &[String],
+        lemma: bool,
+    ) -> Result<Vec<String>, String> {
+        let mut known = self
+            .notepads
+            .get()
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id)
+            .ok_or_else(|| format!("not found notepad_id: {}", notepad_id))?
+            .get_contents()
+            .unwrap_or_default()
+            .lines()
+            .map(|line| duplicates::word_key(line, true, lemma))
+            .collect::<std::collections::HashSet<_>>();
+        Ok(words
+            .iter()
+            .filter(|w| known.insert(duplicates::word_key(w, true, lemma)))
+            .cloned()
+            .collect())
+    }
+
+    /// 输出搜索到的公开notepads。json格式时输出[`maimemo_client::NotepadSummary`]数组
+    pub async fn search_public(
+        &mut self,
+        query: &NotepadQuery,
+        format: OutputFormat,
+    ) -> Result<(), String> {
+        let client = self.backend.web("search")?;
+        if !client.has_logged() {
+            return Err(msg!("notepad.not_logged_in"));
+        }
+        let notepads = client.get_notepad_list(query).await?;
+        let out = match format {
+            OutputFormat::Json => {
+                let summaries = notepads.iter().map(Notepad::summary).collect::<Vec<_>>();
+                serde_json::to_string_pretty(&summaries).map_err(|e| msg!("error.serialize", e))?
+            }
+            OutputFormat::Text if notepads.is_empty() => {
+                if !self.upload_options.quiet {
+                    eprintln!("{}", msg!("notepad.no_public"));
+                }
+                return Ok(());
+            }
+            OutputFormat::Text => {
+                let mut table = Table::new(&["ID", "TITLE", "BRIEF"]);
+                for n in &notepads {
+                    table.add_row(vec![n.get_notepad_id(), n.get_title(), n.get_brief()]);
+                }
+                table.render(table::stdout_width())
+            }
+        };
+        writeln!(self.output, "{}", out).map_err(|e| e.to_string())
+    }
+
+    /// 获取maimemo中notepad_id的当前版本，输出与本地缓存的unified diff与updated_time是否不同。
+    ///
+    /// update_local时确认后用远程的notepad替换本地缓存，远程已删除时从缓存中移除。
+    /// 需要调用[`MaimemoApp::save`]保存
+    pub async fn diff_notepad(
+        &mut self,
+        notepad_id: &str,
+        update_local: bool,
+        format: OutputFormat,
+    ) -> Result<(), String> {
+        let remote = match &mut self.backend {
+            MaimemoBackend::Open(open_client) => open_client.get_notepad(notepad_id).await?,
+            MaimemoBackend::Web(client) => client.get_remote_notepad(notepad_id).await?,
+        };
+        let local = self
+            .notepads
+            .get()
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id);
+        let local_contents = local.and_then(Notepad::get_contents).unwrap_or_default();
+        let updated_time =
+            |n: Option<&Notepad>| n.and_then(Notepad::get_updated_time).map(str::to_string);
+        let result = NotepadDiff {
+            notepad_id: notepad_id.to_string(),
+            cached: local.is_some(),
+            deleted: remote.is_none(),
+            local_updated_time: updated_time(local),
+            remote_updated_time: updated_time(remote.as_ref()),
+            diff: remote.as_ref().and_then(|remote| {
+                diff::unified_diff(
+                    local_contents,
+                    remote.get_contents().unwrap_or_default(),
+                    &format!("local/{}", notepad_id),
+                    &format!("remote/{}", notepad_id),
+                )
+            }),
+            preview: remote.as_ref().and_then(|remote| {
+                self.render_diff(
+                    local_contents,
+                    remote.get_contents().unwrap_or_default(),
+                    self.color.stdout(),
+                    table::stdout_width(),
+                )
+            }),
+        };
+        let out = match format {
+            OutputFormat::Json => {
+                serde_json::to_string_pretty(&result).map_err(|e| msg!("error.serialize", e))?
+            }
+            OutputFormat::Text => result.to_string(),
+        };
+        writeln!(self.output, "{}", out).map_err(|e| e.to_string())?;
+        if !update_local {
+            return Ok(());
+        }
+        let summary = match (local, &remote) {
+            (Some(local), None) => msg!("confirm.remove_cached_notepad", local.get_title()),
+            (Some(local), Some(remote)) if local != remote => {
+                msg!("confirm.replace_cached_notepad", local.get_title())
+            }
+            (None, Some(remote)) => msg!("confirm.add_cached_notepad", remote.get_title()),
+            _ => {
+                debug!("local notepad {} is up to date", notepad_id);
+                return Ok(());
+            }
+        };
+        if !prompt::confirm(self.prompt.as_mut(), self.assume_yes, &summary).await? {
+            eprintln!("{}", msg!("aborted"));
+            return Ok(());
+        }
+        match remote {
+            Some(remote) => {
+                self.notepads.merge(vec![remote]);
+            }
+            None => self
+                .notepads
+                .get_mut()
+                .retain(|n| n.get_notepad_id() != notepad_id),
+        }
+        info!("updated local notepad {}", notepad_id);
+        Ok(())
+    }
+
+    /// 将公开的public_id复制为私有的notepad。创建后重新获取notepad list找到新的notepad并添加到本地，
+    /// 返回新的notepad_id。用户取消或无法找到时为None
+    pub async fn clone_notepad(
+        &mut self,
+        public_id: &str,
+        title: &str,
+    ) -> Result<Option<String>, String> {
+        let client = self.backend.web("clone")?;
+        if !client.has_logged() {
+            return Err(msg!("notepad.not_logged_in"));
+        }
+        if title.trim().is_empty() {
+            return Err(msg!("notepad.title_empty"));
+        }
+        let contents = client.get_notepad_contents(public_id).await?;
+        let brief = format!("cloned from {}", public_id);
+        let notepad = Notepad::new(title, &brief, contents.clone());
+        if !self.save_with_captcha(&notepad).await? {
+            return Ok(None);
+        }
+        let known = self
+            .notepads
+            .get()
+            .iter()
+            .map(|n| n.get_notepad_id().to_string())
+            .collect::<std::collections::HashSet<_>>();
+        let created = self
+            .backend
+            .web("clone")?
+            .get_notepad_list(&NotepadQuery::default())
+            .await?
+            .into_iter()
+            .find(|n| n.get_title() == title && !known.contains(n.get_notepad_id()));
+        match created {
+            Some(mut notepad) => {
+                let notepad_id = notepad.get_notepad_id().to_string();
+                notepad.set_contents(Some(contents));
+                self.notepads.get_mut().push(notepad);
+                Ok(Some(notepad_id))
+            }
+            None => {
+                warn!(
+                    "created notepad {} but not found it in notepad list. please use -r refresh",
+                    title
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// 通过open api创建标题为title的私有notepad并添加到本地，返回新的notepad_id
+    pub async fn create_notepad(&mut self, title: &str, contents: &str) -> Result<String, String> {
+        let open_client = self.backend.open("create")?;
+        if title.trim().is_empty() {
+            return Err(msg!("notepad.title_empty"));
+        }
+        let notepad = Notepad::new(title, "", contents.to_string());
+        let mut created = open_client.create_notepad(&notepad).await?;
+        if created.get_contents().is_none() {
+            created.set_contents(Some(contents.to_string()));
+        }
+        let notepad_id = created.get_notepad_id().to_string();
+        self.notepads.get_mut().push(created);
+        Ok(notepad_id)
+    }
+
+    /// 确认并备份后通过open api删除notepad_id，同时从本地移除。返回false表示用户取消了删除
+    pub async fn delete_notepad(&mut self, notepad_id: &str) -> Result<bool, String> {
+        self.backend.open("delete")?;
+        let notepad = self
+            .notepads
+            .get()
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id)
+            .ok_or_else(|| format!("not found notepad_id: {}", notepad_id))?;
+        let summary = msg!(
+            "confirm.delete_notepad",
+            notepad.get_title(),
+            notepad.get_contents().unwrap_or_default().lines().count()
+        );
+        if !prompt::confirm(self.prompt.as_mut(), self.assume_yes, &summary).await? {
+            eprintln!("{}", msg!("aborted"));
+            return Ok(false);
+        }
+        self.backup_notepad(notepad_id)
+            .map_err(|e| msg!("notepad.backup_error", e))?;
+        self.backend
+            .open("delete")?
+            .delete_notepad(notepad_id)
+            .await?;
+        self.notepads
+            .get_mut()
+            .retain(|n| n.get_notepad_id() != notepad_id);
+        Ok(true)
+    }
+
+    /// 在发送请求前备份notepad_id本地的内容，保存失败时备份仍然保留
+    fn backup_notepad(&self, notepad_id: &str) -> Result<(), String> {
+        let backups = match self.backups.as_ref() {
+            Some(backups) => backups,
+            None => return Ok(()),
+        };
+        let contents = self
+            .notepads
+            .get()
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id)
+            .and_then(|n| n.get_contents());
+        match contents {
+            Some(contents) => backups
+                .backup(notepad_id, contents, Local::now())
+                .map(|_| ()),
+            None => {
+                warn!("skipped backup for notepad {} without contents", notepad_id);
+                Ok(())
+            }
+        }
+    }
+
+    /// 通过open api或验证码上传notepad并更新本地的notepads，见[`push_notepad`]
+    async fn push(&mut self, notepad: Notepad) -> Result<bool, String> {
+        let notepad_id = notepad.get_notepad_id().to_string();
+        let pushed = match &mut self.backend {
+            MaimemoBackend::Open(open_client) => {
+                push_notepad(open_client, &mut self.notepads, notepad).await?
+            }
+            MaimemoBackend::Web(_) => {
+                if !self.save_with_captcha(&notepad).await? {
+                    return Ok(false);
+                }
+                replace_local_notepad(&mut self.notepads, notepad)?;
+                true
+            }
+        };
+        if !pushed {
+            return Ok(false);
+        }
+        self.update_remote_time(&notepad_id).await;
+        Ok(true)
+    }
+
+    /// 通过验证码保存notepad，失败时询问用户是否重试。返回false表示用户取消了上传
+    async fn save_with_captcha(&mut self, notepad: &Notepad) -> Result<bool, String> {
+        // 只读模式在读取验证码前失败
+        self.backend.context().check_writable("save notepad")?;
+        loop {
+            if self.captcha.is_none()
+                && self.solved_captcha.is_none()
+                && self.prompt.is_none()
+                && self.captcha_command.is_none()
+            {
+                let e = SaveNotepadError::ReadCaptcha(msg!("prompt.input_required"));
+                return Err(e.to_string());
+            }
+            let client = match &self.backend {
+                MaimemoBackend::Web(client) => client,
+                MaimemoBackend::Open(_) => {
+                    return Err(msg!("notepad.open_api_unsupported", "captcha"))
+                }
+            };
+            let provider = UserCaptcha {
+                context: client.context(),
+                prompt: &mut self.prompt,
+                command: self.captcha_command.as_mut(),
+                assume_yes: self.assume_yes,
+                case: client.captcha_case(),
+            };
+            // `--captcha`优先于复用的验证码
+            let mut sink = CaptchaSink::new(client, provider)
+                .with_answer(self.captcha.take())
+                .with_solved(self.solved_captcha.take());
+            let result = sink.save(notepad).await;
+            self.solved_captcha = sink.into_solved();
+            let e = match result {
+                Ok(()) => return Ok(true),
+                Err(SaveNotepadError::Throttled { delay, error }) => {
+                    if !wait_throttled(self.prompt.as_mut(), &error, delay).await? {
+                        return Ok(false);
+                    }
+                    continue;
+                }
+                Err(e @ SaveNotepadError::ReadCaptcha(_)) => return Err(e.to_string()),
+                Err(SaveNotepadError::WrongCaptcha(e)) | Err(SaveNotepadError::Other(e)) => e,
+            };
+            // fix decode error
+            if e.to_lowercase().contains("decode") {
+                return Err(msg!("notepad.cache_decode"));
+            }
+            debug!("upload failed. notepad: {}", notepad);
+            let retry = match self.prompt.as_mut() {
+                // 无法询问用户时直接失败
+                None => return Err(msg!("notepad.upload_error", e)),
+                Some(prompt) => prompt
+                    .confirm(&msg!("prompt.upload_retry", e))
+                    .await
+                    .map_err(|e| msg!("prompt.read_error", e))?,
+            };
+            if !retry {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// 保存后maimemo中的updated_time已改变，更新本地的避免下次上传时误报冲突
+    async fn update_remote_time(&mut self, notepad_id: &str) {
+        match self.get_notepad_info(notepad_id).await {
+            Ok(Some(info)) => {
+                if let Some(n) = self
+                    .notepads
+                    .get_mut()
+                    .iter_mut()
+                    .find(|n| n.get_notepad_id() == notepad_id)
+                {
+                    n.set_updated_time(info.get_updated_time().map(str::to_string));
+                }
+            }
+            Ok(None) => warn!("not found notepad {} after saving", notepad_id),
+            Err(e) => warn!(
+                "get updated_time of notepad {} error: {}. use -r before the next upload",
+                notepad_id, e
+            ),
+        }
+    }
+
+    /// 检查maimemo中的notepad_id是否在refresh后被修改，如在手机上编辑。
+    /// 远程的updated_time比本地缓存的新时返回冲突的error，force时不检查
+    async fn check_conflict(&mut self, notepad_id: &str) -> Result<(), String> {
+        if self.force {
+            debug!(
+                "skipped conflict check of notepad {} by --force",
+                notepad_id
+            );
+            return Ok(());
+        }
+        let local = self
+            .notepads
+            .get()
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id)
+            .ok_or_else(|| format!("not found notepad_id: {}", notepad_id))?;
+        let title = local.get_title().to_string();
+        let local_time = match local.get_updated_time() {
+            Some(t) => t.to_string(),
+            None => {
+                warn!(
+                    "not found updated_time of local notepad {}, skipped conflict check",
+                    notepad_id
+                );
+                return Ok(());
+            }
+        };
+        let remote = self
+            .get_notepad_info(notepad_id)
+            .await
+            .map_err(|e| format!("get notepad info error: {}", e))?
+            .ok_or_else(|| {
+                format!(
+                    "not found notepad {} in maimemo, it may have been deleted. use -r to refresh",
+                    notepad_id
+                )
+            })?;
+        let remote_time = remote.get_updated_time().unwrap_or_default();
+        let newer = match (
+            maimemo_client::parse_notepad_time(remote_time),
+            maimemo_client::parse_notepad_time(&local_time),
+        ) {
+            (Some(remote), Some(local)) => remote > local,
+            // 无法比较时不同即认为有冲突
+            _ => remote_time != local_time,
+        };
+        if newer {
+            return Err(format!(
+                "conflict: notepad '{}' was modified in maimemo at {} after the local copy from {}. \
+                 use --diff to compare or -r to refresh, or --force to overwrite",
+                title,
+                remote_time,
+                local_time
+            ));
+        }
+        Ok(())
+    }
+
+    /// 当notepads被更新后保存到文件
+    /// 保存notepads与cookies。两者都会尝试保存，返回第一个错误
+    pub async fn save(&mut self) -> Result<(), String> {
+        let notepads = self.notepads.flush().map(|_| ());
+        let cookies = match &mut self.backend {
+            MaimemoBackend::Web(client) => client.save().await,
+            MaimemoBackend::Open(_) => Ok(()),
+        };
+        let result = notepads.and(cookies);
+        emit_result(self.backend.context(), &result, Event::SaveSucceeded);
+        result
+    }
+
+    /// 从stdin中读取并构造出notepad。
+    ///
+    /// 如果is_appending=true则在原notepad上添加，否则清楚仅用stdin中的内容
+    ///
+    /// 如果timestamp=true则自动插入时间戳
+    async fn build_uploaded_notepad(
+        &mut self,
+        contents_read: impl io::Read,
+        notepad_id: &str,
+        is_appending: bool,
+        timestamp: bool,
+    ) -> Result<Notepad, String> {
+        trace!("Building a new notepad");
+        let mut notepad = self
+            .notepads
+            .get()
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id)
+            .ok_or(format!("not found notepad_id: {}", notepad_id))?
+            .clone();
+        // 未加载内容的notepad在追加时会丢失maimemo中原来的内容
+        let contents = notepad
+            .get_contents_mut()
+            .ok_or_else(|| format!("not found contents of notepad_id: {}", notepad_id))?;
+        if !is_appending {
+            debug!("Emptying original Notepad contents");
+            contents.clear();
+        } else if !contents.is_empty() && !contents.ends_with('\n') {
+            // 避免新内容的第一行与原来的最后一行连在一起
+            contents.push('\n');
+        }
+        let options = self.upload_options.clone();
+        if timestamp {
+            let header = build_header(
+                options.header_template.as_deref(),
+                options.timestamp_format.as_deref(),
+                Local::now(),
+            )?;
+            let s = format!("\n{}\n", header);
+            debug!("Inserting timestamp string: {}", s);
+            contents.push_str(&s);
+        }
+        // read contents
+        debug!("reading contents");
+        let mut input = String::new();
+        io::BufReader::new(contents_read)
+            .read_to_string(&mut input)
+            .map_err(|e| {
+                error!("read contents to string error: {}, contents: {}", e, input);
+                format!("read contents to string error: {}", e)
+            })?;
+        let issues = content_lint::lint(&input);
+        if !issues.is_empty() {
+            if options.fix {
+                input = content_lint::fix(&input);
+                if !options.quiet {
+                    eprintln!(
+                        "{}",
+                        msg!(
+                            "upload.fixed_lines",
+                            content_lint::count_lines(&issues),
+                            content_lint::format_issues(&issues)
+                        )
+                    );
+                }
+            } else {
+                eprintln!(
+                    "{}",
+                    msg!(
+                        "upload.lint_warning",
+                        content_lint::count_lines(&issues),
+                        content_lint::format_issues(&issues)
+                    )
+                );
+            }
+        }
+        if options.raw {
+            contents.push_str(&input);
+        } else {
+            let normalized = normalize_input(&input);
+            if !normalized.phrases.is_empty() {
+                let msg = msg!(
+                    "upload.phrases",
+                    normalized.phrases.len(),
+                    normalized.phrases.join(", ")
+                );
+                if options.strict {
+                    return Err(msg);
+                }
+                eprintln!("{}", msg!("warning", msg));
+            }
+            if !options.quiet && normalized.has_changes() {
+                eprintln!("{}", normalized.report());
+            }
+            contents.push_str(&normalized.text);
+        }
+        debug!("read contents:\n{}", contents);
+        if let Some(lines) = options.brief_lines {
+            let brief = maimemo_client::generate_brief(contents, lines);
+            if brief.is_empty() {
+                debug!("kept the brief of notepad {} without any word", notepad_id);
+            } else {
+                debug!("updating brief: {}", brief);
+                notepad.set_brief(brief);
+            }
+        }
+        Ok(notepad)
+    }
+
+    /// 按sort排序后打印最多limit个notepad的概要信息
+    pub fn list(
+        &mut self,
+        format: OutputFormat,
+        sort: NotepadSort,
+        limit: Option<usize>,
+    ) -> Result<(), String> {
+        let mut notepads = self.notepads.get().clone();
+        maimemo_client::sort_notepads(&mut notepads, sort);
+        notepads.truncate(limit.unwrap_or(notepads.len()));
+        write_notepads(&mut self.output, &notepads, format)
+    }
+
+    /// 输出在多个notepad中重复的单词。within不为空时仅输出该notepad中重复的单词
+    pub fn find_duplicates(
+        &mut self,
+        within: Option<&str>,
+        ignore_case: bool,
+        lemma: bool,
+        format: OutputFormat,
+    ) -> Result<(), String> {
+        let notepads = self.notepads.get();
+        let out = match within {
+            Some(notepad_id) => {
+                let contents = notepads
+                    .iter()
+                    .find(|n| n.get_notepad_id() == notepad_id)
+                    .ok_or_else(|| format!("not found notepad_id: {}", notepad_id))?
+                    .get_contents()
+                    .ok_or_else(|| format!("not found contents for notepad_id: {}", notepad_id))?;
+                let dups = duplicates::find_within(contents, ignore_case, lemma);
+                match format {
+                    OutputFormat::Json => serde_json::to_string_pretty(&dups)
+                        .map_err(|e| msg!("error.serialize", e))?,
+                    OutputFormat::Text => dups
+                        .iter()
+                        .map(|d| {
+                            let lines = d.lines.iter().map(|l| l.to_string());
+                            format!("{}\t{}", d.word, lines.collect::<Vec<_>>().join(","))
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                }
+            }
+            None => {
+                let dups = duplicates::find_duplicates(notepads, ignore_case, lemma);
+                match format {
+                    OutputFormat::Json => serde_json::to_string_pretty(&dups)
+                        .map_err(|e| msg!("error.serialize", e))?,
+                    OutputFormat::Text => dups
+                        .iter()
+                        .map(|d| {
+                            let titles = d.notepads.iter().map(|n| n.title.as_str());
+                            format!("{}\t{}", d.word, titles.collect::<Vec<_>>().join(", "))
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                }
+            }
+        };
+        if !out.is_empty() {
+            writeln!(self.output, "{}", out).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// 输出指定id的notepad内容。json格式时输出完整的notepad
+    ///
+    /// # Errors
+    ///
+    /// 如果notepad不存在或输出失败
+    pub fn list_contents(&mut self, notepad_id: &str, format: OutputFormat) -> Result<(), String> {
+        let notepad = self
+            .notepads
+            .get()
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id)
+            .ok_or_else(|| format!("not found notepad_id: {}", notepad_id))?;
+        write_notepad_contents(&mut self.output, notepad, format)
+    }
+}
+
+/// 回答保存notepad的验证码，优先通过command识别，失败时在终端询问用户。
+/// 没有终端时仅能使用`--captcha`或captcha_command
+struct UserCaptcha<'s, 'a> {
+    context: &'s HttpContext,
+    prompt: &'s mut Option<UserPrompt<'a>>,
+    command: Option<&'s mut CommandCaptcha>,
+    /// 为true时command失败不再询问用户
+    assume_yes: bool,
+    case: CaptchaCase,
+}
+
+impl UserCaptcha<'_, '_> {
+    /// 优先通过command识别验证码，失败时询问用户
+    async fn solve_captcha(&mut self, captcha: &Captcha) -> Result<String, String> {
+        if let Some(command) = self.command.as_mut() {
+            match command.solve(captcha).await {
+                Ok(answer) => return Ok(answer),
+                // --yes时不等待用户输入
+                Err(e) if self.prompt.is_none() || self.assume_yes => return Err(e),
+                Err(e) => warn!("{}. falling back to the prompt", e),
+            }
+        }
+        match self.prompt.as_mut() {
+            Some(prompt) => {
+                let case = self.case;
+                TerminalCaptcha { prompt, case }.solve(captcha).await
+            }
+            None => Err(msg!("prompt.input_required")),
+        }
+    }
+}
+
+impl CaptchaProvider for UserCaptcha<'_, '_> {
+    fn solve<'b>(&'b mut self, captcha: &'b Captcha) -> BoxFuture<'b, Result<String, String>> {
+        Box::pin(async move {
+            // 包装程序从文件中显示验证码，回答后删除
+            let image_path = if self.context.has_events() {
+                let path = captcha::save_temp(captcha)?;
+                self.context.emit(Event::CaptchaRequired {
+                    image_path: path.display().to_string(),
+                });
+                Some(path)
+            } else {
+                None
+            };
+            let answer = self.solve_captcha(captcha).await;
+            if let Some(Err(e)) = image_path.map(|path| path.close()) {
+                warn!("remove captcha file error: {}", e);
+            }
+            answer
+        })
+    }
+}
+
+/// 被maimemo限流时询问用户是否等待delay后重试，等待时在终端显示倒计时
+async fn wait_throttled(
+    prompt: Option<&mut UserPrompt<'_>>,
+    e: &str,
+    delay: std::time::Duration,
+) -> Result<bool, String> {
+    let prompt = match prompt {
+        Some(prompt) => prompt,
+        None => return Err(msg!("notepad.upload_error", e)),
+    };
+    let retry = prompt
+        .confirm(&msg!("prompt.throttled_retry", e, delay.as_secs()))
+        .await
+        .map_err(|e| msg!("prompt.read_error", e))?;
+    if retry {
+        prompt::countdown(Some(prompt), delay, &msg!("prompt.retrying_in")).await?;
+    }
+    Ok(retry)
+}
+
+/// 获取验证码的任务结束后，[`PrefetchedSink`]没有可用的验证码
+const NO_PREFETCHED_CAPTCHA: &str = "no prefetched captcha";
+
+/// 使用预先获取并回答的验证码保存notepad，失败时不重试，见[`MaimemoApp::save_prefetched`]
+struct PrefetchedSink<'s> {
+    client: &'s MaimemoClient,
+    answers: &'s mut tokio::sync::mpsc::Receiver<String>,
+}
+
+impl PrefetchedSink<'_> {
+    async fn save(&mut self, notepad: &Notepad) -> Result<(), SaveNotepadError> {
+        let captcha = self
+            .answers
+            .recv()
+            .await
+            .ok_or_else(|| NO_PREFETCHED_CAPTCHA.to_string())?;
+        self.client.save_notepad(notepad.clone(), captcha).await
+    }
+}
+
+/// 在终端中显示验证码图片并读取用户的输入
+struct TerminalCaptcha<'s, 'a> {
+    prompt: &'s mut UserPrompt<'a>,
+    case: CaptchaCase,
+}
+
+impl CaptchaProvider for TerminalCaptcha<'_, '_> {
+    fn solve<'b>(&'b mut self, captcha: &'b Captcha) -> BoxFuture<'b, Result<String, String>> {
+        Box::pin(async move {
+            trace!("Printing image content");
+            let img = image::load_from_memory(&captcha.bytes).map_err(|e| format!("{:?}", e))?;
+            viuer::print(
+                &img,
+                &viuer::Config {
+                    absolute_offset: false,
+                    ..viuer::Config::default()
+                },
+            )
+            .map_err(|e| format!("print captcha image error: {}", e))?;
+            debug!("Waiting for input captcha");
+            // 提示输出到终端，避免混入stdout的数据。无效的验证码不提交，直接重新输入
+            let case = self.case;
+            self.prompt
+                .read_valid_line(&msg!("prompt.captcha"), |answer| {
+                    maimemo_client::normalize_captcha(answer, case)
+                })
+                .await
+        })
+    }
+}
+
+/// 输出notepads。json格式时输出[`maimemo_client::NotepadSummary`]数组
+fn write_notepads(
+    output: &mut impl Write,
+    notepads: &[Notepad],
+    format: OutputFormat,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Text => notepads
+            .iter()
+            .try_for_each(|n| writeln!(output, "{}", n))
+            .map_err(|e| e.to_string()),
+        OutputFormat::Json => {
+            let summaries = notepads.iter().map(Notepad::summary).collect::<Vec<_>>();
+            let out =
+                serde_json::to_string_pretty(&summaries).map_err(|e| msg!("error.serialize", e))?;
+            writeln!(output, "{}", out).map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn write_notepad_contents(
+    output: &mut impl Write,
+    notepad: &Notepad,
+    format: OutputFormat,
+) -> Result<(), String> {
+    let out = match format {
+        OutputFormat::Text => notepad
+            .get_contents()
+            .ok_or_else(|| {
+                format!(
+                    "not found contents for notepad_id: {}",
+                    notepad.get_notepad_id()
+                )
+            })?
+            .to_string(),
+        OutputFormat::Json => {
+            serde_json::to_string_pretty(notepad).map_err(|e| msg!("error.serialize", e))?
+        }
+    };
+    writeln!(output, "{}", out).map_err(|e| e.to_string())
+}
+
+/// maimemo配置的获取验证码的命令
+pub(crate) fn captcha_command(config: &AppConfig) -> Result<Option<CommandCaptcha>, String> {
+    let timeout = config
+        .get_captcha_command_timeout_secs()
+        .unwrap_or(captcha::DEFAULT_TIMEOUT_SECS);
+    config
+        .get_captcha_command()
+        .map(|c| CommandCaptcha::new(c, std::time::Duration::from_secs(timeout)))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use dict::{
+        client,
+        config::{Config, LoggingConfig},
+        logging,
+        store::MergeReport,
+    };
+    use mock_server::{MockRequest, MockServer, FIXTURE_CAPTCHA};
+    use std::fs;
+    use std::sync::Mutex;
+
+    fn init_log() {
+        logging::init(1, None, None, ColorChoice::Auto, &LoggingConfig::default()).unwrap();
+    }
+
+    /// 最后一次保存notepad的请求。保存后还会请求列表更新修改时间
+    fn last_save(server: &MockServer) -> MockRequest {
+        server
+            .requests()
+            .into_iter()
+            .rev()
+            .find(|r| r.path.ends_with("/notepad/save"))
+            .unwrap()
+    }
+
+    /// 通过录制的响应代理所有请求的配置与http context
+    fn recorded_config(
+        server: &MockServer,
+        dir: &tempfile::TempDir,
+    ) -> Result<(AppConfig, Arc<HttpContext>), String> {
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let context = HttpContext::from_config(&config.http())?;
+        Ok((config.maimemo(), Arc::new(context)))
+    }
+
+    async fn mocked_maimemo_data<'a>(
+        server: &MockServer,
+        dir: &tempfile::TempDir,
+        is_local: bool,
+    ) -> Result<(MaimemoApp<'a>, Vec<Notepad>), String> {
+        init_log();
+        let (config, context) = recorded_config(server, dir)?;
+        if is_local {
+            fs::copy(
+                "tests/fixtures/maimemo-notepads-v1.json",
+                config.get_dictionary_path(),
+            )
+            .map_err(|e| format!("{:?}", e))?;
+        }
+        let prompt = UserPrompt::new(io::Cursor::new(""), io::sink());
+        let output = io::Cursor::new(Vec::new());
+        let app = MaimemoApp::new(config, context, is_local, Some(prompt), output).await?;
+        let notepads = app.notepads.get().clone();
+        Ok((app, notepads))
+    }
+
+    #[tokio::test]
+    async fn nofile_web_loading() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut app, notepads) = mocked_maimemo_data(&server, &dir, false).await?;
+        assert_eq!(notepads.len(), 2);
+        assert_eq!(notepads[0].get_contents(), Some("accommodate\nbenevolent"));
+        app.save().await?;
+        let saved = NotepadStore::load(app.notepads.get_path()).await?;
+        assert_eq!(saved.get().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn diff_remote_notepad() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        let (expired, deleted) = (
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let server = {
+            let (expired, deleted) = (expired.clone(), deleted.clone());
+            MockServer::start(move |req| {
+                if req.path.ends_with("/notepad/detail/695835") {
+                    if deleted.load(Ordering::SeqCst) {
+                        return MockResponse::text(404, "not found");
+                    }
+                    if expired.swap(false, Ordering::SeqCst) {
+                        return MockResponse::text(
+                            200,
+                            include_str!("../../tests/fixtures/maimemo-login-page.html"),
+                        );
+                    }
+                }
+                recorded_response(req)
+            })
+            .await
+        };
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut app, _) = mocked_maimemo_data(&server, &dir, false).await?;
+        let output = |app: &mut MaimemoApp| {
+            let out = String::from_utf8(app.output.buffer().to_vec()).unwrap();
+            app.output = io::BufWriter::new(Box::new(io::Cursor::new(vec![])));
+            out
+        };
+        output(&mut app);
+
+        // session过期时重新登录
+        expired.store(true, Ordering::SeqCst);
+        app.diff_notepad("695835", false, OutputFormat::Text)
+            .await?;
+        assert_eq!(
+            output(&mut app),
+            "updated_time: local 2020-10-18 10:00:00, remote 2020-10-18 10:00:00 (unchanged)\n\
+             contents are identical\n"
+        );
+        let logins = server
+            .requests()
+            .iter()
+            .filter(|r| r.path.ends_with("/auth/login"))
+            .count();
+        assert_eq!(logins, 2);
+
+        // 本地缓存是旧的版本
+        let remote = app.notepads.get()[0].clone();
+        let mut old = serde_json::to_value(&remote).unwrap();
+        old["contents"] = serde_json::json!("accommodate\nrun");
+        old["updated_time"] = serde_json::json!("2020-10-17 10:00:00");
+        app.notepads.get_mut()[0] = serde_json::from_value(old).unwrap();
+        app.diff_notepad("695835", false, OutputFormat::Text)
+            .await?;
+        let out = output(&mut app);
+        assert!(
+            out.contains("(changed)\n+")
+                && out.contains(" - run\n")
+                && out.contains(" + benevolent\n"),
+            "{}",
+            out
+        );
+        app.assume_yes = true;
+        app.diff_notepad("695835", true, OutputFormat::Json).await?;
+        let json = serde_json::from_str::<serde_json::Value>(&output(&mut app)).unwrap();
+        assert_eq!(json["local_updated_time"], "2020-10-17 10:00:00");
+        assert_eq!(json["remote_updated_time"], "2020-10-18 10:00:00");
+        let diff = json["diff"].as_str().unwrap();
+        assert!(
+            diff.starts_with("--- local/695835\n+++ remote/695835\n")
+                && diff.contains("\n-run\n")
+                && diff.contains("\n+benevolent\n"),
+            "{}",
+            diff
+        );
+        assert_eq!(app.notepads.get()[0], remote);
+
+        // 远程已删除
+        deleted.store(true, Ordering::SeqCst);
+        app.diff_notepad("695835", true, OutputFormat::Text).await?;
+        assert_eq!(output(&mut app), "notepad 695835 was deleted remotely\n");
+        assert!(app
+            .notepads
+            .get()
+            .iter()
+            .all(|n| n.get_notepad_id() != "695835"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_api_backend() -> Result<(), String> {
+        use mock_server::{maimemo_open_api, FIXTURE_API_TOKEN};
+        let server = MockServer::start(maimemo_open_api()).await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config = serde_yaml::from_str::<AppConfig>(&format!(
+            "dictionary_path: {}
+api_token: {}
+api_url: {}
+",
+            dir.path().join("maimemo.json").display(),
+            FIXTURE_API_TOKEN,
+            server.url("/notepads")
+        ))
+        .unwrap();
+        let context = Arc::new(HttpContext::new()?);
+        // 没有prompt时仍然可以上传，不需要验证码
+        let mut app = MaimemoApp::new(config, context, false, None, io::sink())
+            .await?
+            .with_assume_yes(true);
+        // 695836在获取内容时已被删除
+        assert_eq!(app.notepads.get().len(), 1);
+        app.upload_notepad(io::Cursor::new("serendipity"), "695835", true, false)
+            .await?;
+        let notepad = &app.notepads.get()[0];
+        assert!(notepad.get_contents().unwrap().contains("serendipity"));
+        assert_eq!(notepad.get_updated_time(), Some("2024-05-02T08:00:00.000Z"));
+        assert!(server
+            .requests()
+            .iter()
+            .all(|r| r.path.starts_with("/notepads")));
+
+        let e = app
+            .search_public(
+                &NotepadQuery::public("english", false, 1),
+                OutputFormat::Text,
+            )
+            .await
+            .unwrap_err();
+        assert!(e.contains("open api"), "{}", e);
+
+        let id = app.create_notepad("new", "run\nwalk").await?;
+        assert_eq!(id, "700000");
+        assert!(app.delete_notepad("695835").await?);
+        let ids = app
+            .notepads
+            .get()
+            .iter()
+            .map(|n| n.get_notepad_id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["700000"]);
+        assert_eq!(app.notepads.get()[0].get_contents(), Some("run\nwalk"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resume_failed_refresh() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse};
+        let details = |server: &MockServer| {
+            server
+                .requests()
+                .iter()
+                .filter(|r| r.path.contains("/notepad/detail/"))
+                .count()
+        };
+        let server = MockServer::start(|req| {
+            if req.path.ends_with("/notepad/detail/695836") {
+                return MockResponse::text(500, "internal error");
+            }
+            recorded_response(req)
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let e = mocked_maimemo_data(&server, &dir, false)
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            e.starts_with("get notepads failed: ")
+                && e.ends_with("fetched 1 of 2 notepads before failure"),
+            "{}",
+            e
+        );
+        // 已获取的notepad被保存
+        let (config, _) = recorded_config(&server, &dir)?;
+        let saved = NotepadStore::load(config.get_dictionary_path()).await?;
+        assert_eq!(saved.get().len(), 1);
+        assert_eq!(saved.get()[0].get_notepad_id(), "695835");
+
+        let server = MockServer::start_recorded().await;
+        let (app, notepads) = mocked_maimemo_data(&server, &dir, false).await?;
+        assert_eq!(notepads.len(), 2);
+        assert_eq!(details(&server), 1);
+        let report = app.refresh_report.as_ref().unwrap();
+        assert_eq!((report.merge.added, report.merge.unchanged), (1, 1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refresh_report() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut app, _) = mocked_maimemo_data(&server, &dir, false).await?;
+        let report = app.refresh_report.clone().unwrap();
+        assert_eq!((report.fetched, report.merge.added), (2, 2));
+        app.save().await?;
+
+        // 修改一个，删除一个并添加一个本地的notepad。updated_time不同时才会重新获取内容
+        let mut store = NotepadStore::load(app.notepads.get_path()).await?;
+        let mut extra = serde_json::to_value(&store.get()[1]).unwrap();
+        extra["notepad_id"] = serde_json::json!("1");
+        let mut changed = serde_json::to_value(&store.get()[0]).unwrap();
+        changed["contents"] = serde_json::json!("accommodate");
+        changed["updated_time"] = serde_json::json!("2020-10-17 10:00:00");
+        store.get_mut()[0] = serde_json::from_value(changed).unwrap();
+        store.get_mut()[1] = serde_json::from_value(extra).unwrap();
+        store.flush()?;
+
+        let (app, notepads) = mocked_maimemo_data(&server, &dir, false).await?;
+        let report = app.refresh_report.as_ref().unwrap();
+        assert_eq!(report.service, "maimemo");
+        assert_eq!(report.fetched, 2);
+        assert_eq!(
+            report.merge,
+            MergeReport {
+                added: 1,
+                updated: 1,
+                unchanged: 0,
+                removed: 1,
+            }
+        );
+        assert_eq!(report.path, app.notepads.get_path());
+        assert_eq!(notepads.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut app, notepads) = mocked_maimemo_data(&server, &dir, true).await?;
+        app.list(OutputFormat::Text, NotepadSort::Id, None)?;
+        let mut data = vec![];
+        notepads.iter().for_each(|n| {
+            let s = n.to_string() + "\n";
+            s.bytes().for_each(|b| data.push(b));
+        });
+        assert_eq!(data, app.output.buffer());
+
+        // 默认按updated降序，没有时间的在最后
+        let listed = |app: &mut MaimemoApp, sort, limit| {
+            app.output = io::BufWriter::new(Box::new(io::Cursor::new(vec![])));
+            app.list(OutputFormat::Json, sort, limit).unwrap();
+            serde_json::from_slice::<Vec<serde_json::Value>>(app.output.buffer())
+                .unwrap()
+                .iter()
+                .map(|n| n["notepad_id"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            listed(&mut app, NotepadSort::Updated, None),
+            vec!["695835", "695836"]
+        );
+        assert_eq!(
+            listed(&mut app, NotepadSort::Title, Some(1)),
+            vec!["695836"]
+        );
+        assert!(listed(&mut app, NotepadSort::Id, Some(0)).is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_contents() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut app, notepads) = mocked_maimemo_data(&server, &dir, true).await?;
+        let notepad_id = "695835";
+        app.list_contents(notepad_id, OutputFormat::Text)?;
+        let mut data = vec![];
+        if let Some(s) = notepads
+            .iter()
+            .find(|n| n.get_notepad_id() == notepad_id)
+            .and_then(|n| n.get_contents())
+        {
+            data.extend(s.bytes());
+        }
+        data.push(b'\n');
+        assert_eq!(data, app.output.buffer());
+
+        let e = app.list_contents("1", OutputFormat::Json).unwrap_err();
+        assert_eq!(e, "not found notepad_id: 1");
+        // 输出失败时返回错误而不是panic
+        struct Closed;
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        app.output = io::BufWriter::with_capacity(0, Box::new(Closed));
+        assert!(app.list(OutputFormat::Json, NotepadSort::Id, None).is_err());
+        assert!(app.list_contents(notepad_id, OutputFormat::Text).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn list_json_snapshot() -> Result<(), String> {
+        let notepads: Vec<Notepad> = serde_json::from_str(include_str!(
+            "../../tests/fixtures/maimemo-notepads-v1.json"
+        ))
+        .map_err(|e| format!("{:?}", e))?;
+        let mut output = vec![];
+        write_notepads(&mut output, &notepads, OutputFormat::Json)?;
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            include_str!("../../tests/fixtures/maimemo-list.json")
+        );
+        let mut output = vec![];
+        write_notepad_contents(&mut output, &notepads[0], OutputFormat::Json)?;
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            include_str!("../../tests/fixtures/maimemo-list-contents.json")
+        );
+        let mut output = vec![];
+        write_notepad_contents(&mut output, &notepads[0], OutputFormat::Text)?;
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "accommodate\nbenevolent\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn upload_headers() -> Result<(), String> {
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 8, 30, 0).unwrap();
+        assert_eq!(
+            build_header(None, None, now)?,
+            "# 2024-06-01 08:30:00 Auto insert"
+        );
+        assert_eq!(
+            build_header(Some("#{date}"), Some("%Y-%m-%d"), now)?,
+            "#2024-06-01"
+        );
+        assert_eq!(
+            build_header(Some("## words {date} ({date})"), Some("%m/%d"), now)?,
+            "## words 06/01 (06/01)"
+        );
+        assert_eq!(build_header(Some("# new"), None, now)?, "# new");
+        assert!(validate_timestamp_format("%Y-%m-%d").is_ok());
+        assert!(build_header(None, Some("%Y-%Q"), now).is_err());
+        assert!(validate_timestamp_format("%").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn maimemo_save() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (app, _) = mocked_maimemo_data(&server, &dir, false).await?;
+        let mut app = app
+            .with_captcha(Some(FIXTURE_CAPTCHA.to_string()))
+            .with_assume_yes(true);
+
+        let notepad_id = "695835";
+        let contents_read = io::Cursor::new("test words");
+        app.upload_notepad(contents_read, notepad_id, false, false)
+            .await?;
+        let notepad = &app.notepads.get()[0];
+        assert_eq!(notepad.get_contents(), Some("test words\n"));
+        let save = last_save(&server);
+        assert_eq!(save.path, "http://www.maimemo.com/notepad/save");
+        assert!(save.body_str().contains("content=test+words"));
+        assert!(save.body_str().contains("brief=youdao+words"));
+
+        // 用新内容的前两个单词更新简介
+        app.upload_options.brief_lines = Some(2);
+        let contents_read = io::Cursor::new("# new\nrun\nwalk\njump");
+        app.captcha = Some(FIXTURE_CAPTCHA.to_string());
+        app.upload_notepad(contents_read, notepad_id, false, false)
+            .await?;
+        assert_eq!(app.notepads.get()[0].get_brief(), "run、walk");
+        let save = last_save(&server);
+        assert!(
+            save.body_str().contains("brief=run%E3%80%81walk&"),
+            "{}",
+            save.body_str()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn backup_and_restore() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let backups = NotepadBackups::new(dir.path().to_str().unwrap(), 10);
+        let (app, _) = mocked_maimemo_data(&server, &dir, false).await?;
+        let mut app = app
+            .with_captcha(Some("0000".to_string()))
+            .with_assume_yes(true)
+            .with_backups(backups.clone());
+        let notepad_id = "695835";
+
+        // 保存失败时仍然保留备份。不再重试
+        app.prompt = Some(UserPrompt::new(io::Cursor::new("n\n"), io::sink()));
+        app.upload_notepad(io::Cursor::new("failed"), notepad_id, false, false)
+            .await?;
+        let timestamps = backups.list(notepad_id)?;
+        assert_eq!(timestamps.len(), 1);
+        assert_eq!(
+            backups.read(notepad_id, &timestamps[0])?,
+            "accommodate\nbenevolent"
+        );
+        assert_eq!(
+            app.notepads.get()[0].get_contents(),
+            Some("accommodate\nbenevolent")
+        );
+
+        app.captcha = Some(FIXTURE_CAPTCHA.to_string());
+        app.upload_notepad(io::Cursor::new("test words"), notepad_id, false, false)
+            .await?;
+        assert_eq!(app.notepads.get()[0].get_contents(), Some("test words\n"));
+
+        app.restore_notepad(notepad_id, None).await?;
+        let listed = String::from_utf8(app.output.buffer().to_vec()).unwrap();
+        assert_eq!(listed.lines().count(), 2);
+
+        app.captcha = Some(FIXTURE_CAPTCHA.to_string());
+        app.restore_notepad(notepad_id, Some(&timestamps[0]))
+            .await?;
+        assert_eq!(
+            app.notepads.get()[0].get_contents(),
+            Some("accommodate\nbenevolent")
+        );
+        let save = last_save(&server);
+        assert!(save.body_str().contains("content=accommodate%0Abenevolent"));
+        // 恢复前同样备份了当前的内容
+        let timestamps = backups.list(notepad_id)?;
+        assert_eq!(timestamps.len(), 3);
+        assert_eq!(backups.read(notepad_id, &timestamps[2])?, "test words\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn offline_mode() -> Result<(), String> {
+        init_log();
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let offline =
+            |context: Arc<HttpContext>| Arc::new(HttpContext::clone(&context).with_offline(true));
+        let (config, context) = recorded_config(&server, &dir)?;
+        let prompt = UserPrompt::new(io::Cursor::new(""), io::sink());
+        let e = MaimemoApp::new(config, offline(context), false, Some(prompt), io::sink())
+            .await
+            .err()
+            .unwrap();
+        assert!(e.contains(client::context::OFFLINE_MODE), "{}", e);
+        assert!(server.requests().is_empty());
+
+        // 本地的notepads仍然可以使用
+        let (config, context) = recorded_config(&server, &dir)?;
+        fs::copy(
+            "tests/fixtures/maimemo-notepads-v1.json",
+            config.get_dictionary_path(),
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        let output = io::Cursor::new(Vec::new());
+        let mut app = MaimemoApp::new(config, offline(context), true, None, output).await?;
+        app.list(OutputFormat::Text, NotepadSort::Updated, None)?;
+        let listed = String::from_utf8(app.output.buffer().to_vec()).unwrap();
+        assert!(listed.contains("695835"), "{}", listed);
+        assert!(server.requests().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_only_mode() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (config, context) = recorded_config(&server, &dir)?;
+        let context = Arc::new(HttpContext::clone(&context).with_read_only(true));
+        let prompt = UserPrompt::new(io::Cursor::new("y\n"), io::sink());
+        let output = io::Cursor::new(Vec::new());
+        // 登录与获取notepads不受只读模式影响
+        let mut app = MaimemoApp::new(config, context.clone(), false, Some(prompt), output).await?;
+        app.assume_yes = true;
+        let is_read_only = |e: String| {
+            assert_eq!(
+                error::DictError::classify(&e),
+                Some(error::DictError::ReadOnly),
+                "{}",
+                e
+            )
+        };
+        let words = vec!["serendipity".to_string()];
+        is_read_only(
+            app.try_upload_notepad("serendipity".as_bytes(), "695835", false, false)
+                .await
+                .unwrap_err(),
+        );
+        is_read_only(
+            app.try_upload_notepad("serendipity".as_bytes(), "695835", true, true)
+                .await
+                .unwrap_err(),
+        );
+        is_read_only(
+            app.append_words("695835", &words, false, false)
+                .await
+                .unwrap_err(),
+        );
+        is_read_only(app.clone_notepad("900001", "copy").await.unwrap_err());
+        // 没有获取验证码或保存notepad
+        assert!(
+            server
+                .requests()
+                .iter()
+                .all(|r| !r.path.contains("captcha") && !r.path.ends_with("/notepad/save")),
+            "{:?}",
+            server.requests()
+        );
+        // 查询不受影响
+        app.list(OutputFormat::Text, NotepadSort::Updated, None)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn append_after_last_line() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut app, _) = mocked_maimemo_data(&server, &dir, true).await?;
+        let mut notepads = app.notepads.get().clone();
+        let notepad = notepads
+            .iter_mut()
+            .find(|n| n.get_notepad_id() == "695835")
+            .unwrap();
+        notepad.set_contents(Some("hello\nworld".to_string()));
+        app.notepads.set(notepads);
+
+        let notepad = app
+            .build_uploaded_notepad("serendipity".as_bytes(), "695835", true, false)
+            .await?;
+        assert_eq!(notepad.get_contents(), Some("hello\nworld\nserendipity\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_with_captcha_command() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (app, _) = mocked_maimemo_data(&server, &dir, false).await?;
+        let command = |mode: &str| {
+            let command = format!("sh tests/fixtures/captcha-command.sh {}", mode);
+            CommandCaptcha::new(&command, std::time::Duration::from_secs(5)).map(Some)
+        };
+        // 没有终端时仍然可以通过command上传
+        let mut app = app
+            .with_assume_yes(true)
+            .with_captcha_command(command("ok")?);
+        app.prompt = None;
+        app.upload_notepad(io::Cursor::new("run"), "695835", false, false)
+            .await?;
+        assert_eq!(app.notepads.get()[0].get_contents(), Some("run\n"));
+
+        // command失败且不能询问时不上传
+        let mut app = app.with_captcha_command(command("fail")?);
+        app.solved_captcha = None;
+        let mut notepad = app.notepads.get()[0].clone();
+        notepad.set_contents(Some("walk".to_string()));
+        let e = app.push(notepad).await.unwrap_err();
+        assert!(e.contains("captcha command failed"), "{}", e);
+        assert_eq!(app.notepads.get()[0].get_contents(), Some("run\n"));
+        let saves = server
+            .requests()
+            .iter()
+            .filter(|r| r.path.ends_with("/notepad/save"))
+            .count();
+        assert_eq!(saves, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_prompt_timeout() -> Result<(), String> {
+        /// 测试结束前没有输入的终端，sender被drop后读到EOF
+        struct Idle(std::sync::mpsc::Receiver<()>);
+        impl Read for Idle {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                let _ = self.0.recv();
+                Ok(0)
+            }
+        }
+        let (_input, idle) = std::sync::mpsc::channel();
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut app, notepads) = mocked_maimemo_data(&server, &dir, false).await?;
+        let timeout = std::time::Duration::from_millis(100);
+        app.prompt = Some(UserPrompt::with_timeout(Idle(idle), io::sink(), timeout));
+        let e = app
+            .try_upload_notepad("run\n".as_bytes(), "695835", false, false)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error::DictError::classify(&e),
+            Some(error::DictError::PromptTimeout),
+            "{}",
+            e
+        );
+        // 超时后不再询问之后的notepads
+        let ids = ["695835".to_string(), "695836".to_string()];
+        let results = app.upload_notepads("run\n", &ids, false, false).await;
+        assert_eq!(results.len(), 1, "{:?}", results);
+        assert_eq!(results[0].0, "695835");
+        assert!(!server
+            .requests()
+            .iter()
+            .any(|r| r.path.ends_with("/notepad/save")));
+        // 中止后refresh的notepads仍被保存
+        app.save().await?;
+        let saved = NotepadStore::load(app.notepads.get_path()).await?;
+        assert_eq!(saved.get(), &notepads);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_multiple_notepads() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        // 拒绝第二次保存，即复用的验证码
+        let saves = Arc::new(AtomicUsize::new(0));
+        let server = {
+            let saves = saves.clone();
+            MockServer::start(move |req| {
+                if req.path.ends_with("/notepad/save") && saves.fetch_add(1, Ordering::SeqCst) == 1
+                {
+                    return MockResponse::json(
+                        200,
+                        &serde_json::json!({"valid": 0, "errorCode": "captcha_error"}),
+                    );
+                }
+                recorded_response(req)
+            })
+            .await
+        };
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (app, _) = mocked_maimemo_data(&server, &dir, false).await?;
+        let command = CommandCaptcha::new(
+            "sh tests/fixtures/captcha-command.sh ok",
+            std::time::Duration::from_secs(5),
+        )?;
+        let mut app = app
+            .with_assume_yes(true)
+            .with_captcha_command(Some(command));
+        app.prompt = None;
+        let ids = ["695835", "695836", "1"].map(String::from);
+        let results = app
+            .upload_notepads(
+                "run
+", &ids, false, false,
+            )
+            .await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], ("695835".to_string(), Ok(true)));
+        assert_eq!(results[1], ("695836".to_string(), Ok(true)));
+        let e = results[2].1.as_ref().unwrap_err();
+        assert!(e.contains("not found notepad"), "{}", e);
+        assert!(app
+            .notepads
+            .get()
+            .iter()
+            .all(|n| n.get_contents() == Some("run\n")));
+        // 第一个验证码被复用一次，被拒绝后重新获取
+        assert_eq!(saves.load(Ordering::SeqCst), 3);
+        let captchas = server
+            .requests()
+            .iter()
+            .filter(|r| r.path.contains("/captcha/image"))
+            .count();
+        assert_eq!(captchas, 2);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn upload_prefetched_captchas() -> Result<(), String> {
+        use mock_server::recorded_response;
+        use std::sync::Condvar;
+        // 保存第k个notepad时等待第k+1个验证码被获取，验证两者同时进行
+        let log = Arc::new((Mutex::new(Vec::<String>::new()), Condvar::new()));
+        let server = {
+            let log = log.clone();
+            MockServer::start(move |req| {
+                let count = |log: &[String], prefix: &str| {
+                    log.iter().filter(|l| l.starts_with(prefix)).count()
+                };
+                let (lock, fetched) = &*log;
+                if req.path.contains("/captcha/image") {
+                    let mut log = lock.lock().unwrap();
+                    let n = count(&log, "captcha") + 1;
+                    log.push(format!("captcha {}", n));
+                    fetched.notify_all();
+                } else if req.path.ends_with("/notepad/save") {
+                    let body = req.body_str();
+                    let id = body.split('&').next().unwrap_or_default().to_string();
+                    let mut log = lock.lock().unwrap();
+                    let k = count(&log, "save-start") + 1;
+                    log.push(format!("save-start {}", id));
+                    // 最后一个notepad没有下一个验证码
+                    let mut log = fetched
+                        .wait_while(log, |log| k < 3 && count(log, "captcha") <= k)
+                        .unwrap();
+                    log.push(format!("save-end {}", id));
+                }
+                recorded_response(req)
+            })
+            .await
+        };
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (app, notepads) = mocked_maimemo_data(&server, &dir, false).await?;
+        let command = CommandCaptcha::new(
+            "sh tests/fixtures/captcha-command.sh ok",
+            std::time::Duration::from_secs(5),
+        )?;
+        let mut app = app
+            .with_assume_yes(true)
+            .with_force(true)
+            .with_prefetch_captcha(true)
+            .with_captcha_command(Some(command));
+        app.prompt = None;
+        let third = notepads[0].clone().with_id("695837");
+        app.notepads.get_mut().push(third);
+
+        let ids = ["695835", "695836", "695837"].map(String::from);
+        let results = app.upload_notepads("run\n", &ids, false, false).await;
+        assert!(results.iter().all(|(_, r)| r == &Ok(true)), "{:?}", results);
+        assert!(app
+            .notepads
+            .get()
+            .iter()
+            .all(|n| n.get_contents() == Some("run\n")));
+
+        let log = log.0.lock().unwrap().clone();
+        let at = |line: &str| {
+            log.iter()
+                .position(|l| l == line)
+                .unwrap_or_else(|| panic!("not found {} in {:?}", line, log))
+        };
+        assert_eq!(log.len(), 9, "{:?}", log);
+        assert_eq!(at("captcha 1"), 0);
+        assert!(at("save-start id=695835") < at("save-start id=695836"));
+        assert!(at("save-start id=695836") < at("save-start id=695837"));
+        // 保存的同时获取下一个验证码，但最多提前一个
+        assert!(at("captcha 2") < at("save-end id=695835"), "{:?}", log);
+        assert!(at("captcha 3") < at("save-end id=695836"), "{:?}", log);
+        assert!(at("save-end id=695835") < at("captcha 3"), "{:?}", log);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_conflicts() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        // edited后notepad list中695835的updated_time变为在手机上编辑的时间
+        let edited = Arc::new(AtomicBool::new(false));
+        let server = {
+            let edited = edited.clone();
+            MockServer::start(move |req| {
+                let resp = recorded_response(req);
+                let is_mine =
+                    req.path.contains("/notepad/search?") && req.body_json()["scope"] != "ALL";
+                if !is_mine || !edited.load(Ordering::SeqCst) {
+                    return resp;
+                }
+                let mut page = serde_json::from_slice::<serde_json::Value>(&resp.body).unwrap();
+                page["notepad"][0]["updated_time"] = serde_json::json!("2026-10-16 10:00:00");
+                MockResponse::json(200, &page)
+            })
+            .await
+        };
+        let saves = || {
+            server
+                .requests()
+                .iter()
+                .filter(|r| r.path.ends_with("/notepad/save"))
+                .count()
+        };
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (app, _) = mocked_maimemo_data(&server, &dir, false).await?;
+        let mut app = app.with_assume_yes(true);
+
+        app.captcha = Some(FIXTURE_CAPTCHA.to_string());
+        app.upload_notepad(io::Cursor::new("run"), "695835", false, false)
+            .await?;
+        assert_eq!(app.notepads.get()[0].get_contents(), Some("run\n"));
+        assert_eq!(saves(), 1);
+
+        // 冲突时返回error，不上传
+        edited.store(true, Ordering::SeqCst);
+        let e = app
+            .upload_notepad(io::Cursor::new("walk"), "695835", false, false)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            e,
+            "conflict: notepad 'english' was modified in maimemo at 2026-10-16 10:00:00 \
+             after the local copy from 2020-10-18 10:00:00. \
+             use --diff to compare or -r to refresh, or --force to overwrite"
+        );
+        assert_eq!(saves(), 1);
+        assert_eq!(app.notepads.get()[0].get_contents(), Some("run\n"));
+
+        let mut app = app.with_force(true);
+        app.captcha = Some(FIXTURE_CAPTCHA.to_string());
+        app.upload_notepad(io::Cursor::new("walk"), "695835", false, false)
+            .await?;
+        assert_eq!(app.notepads.get()[0].get_contents(), Some("walk\n"));
+        assert_eq!(saves(), 2);
+        // 保存后更新了本地的updated_time
+        assert_eq!(
+            app.notepads.get()[0].get_updated_time(),
+            Some("2026-10-16 10:00:00")
+        );
+        let mut app = app.with_force(false);
+        app.check_conflict("695835").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn search_and_clone_public() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        let saved = Arc::new(AtomicBool::new(false));
+        let server = {
+            let saved = saved.clone();
+            MockServer::start(move |req| {
+                let resp = recorded_response(req);
+                if req.path.ends_with("/notepad/save")
+                    && serde_json::from_slice::<serde_json::Value>(&resp.body).unwrap()["valid"]
+                        == 1
+                {
+                    saved.store(true, Ordering::SeqCst);
+                }
+                // 创建后的notepad list中包含新的notepad
+                let is_mine =
+                    req.path.contains("/notepad/search?") && req.body_json()["scope"] != "ALL";
+                if !is_mine || !saved.load(Ordering::SeqCst) {
+                    return resp;
+                }
+                let mut page = serde_json::from_slice::<serde_json::Value>(&resp.body).unwrap();
+                page["notepad"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(serde_json::json!({
+                        "is_private": 1,
+                        "notepad_id": "700001",
+                        "title": "gre copy",
+                        "brief": "cloned from 900001",
+                        "created_time": "2026-10-16 10:00:00",
+                        "updated_time": "2026-10-16 10:00:00",
+                    }));
+                MockResponse::json(200, &page)
+            })
+            .await
+        };
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut app, notepads) = mocked_maimemo_data(&server, &dir, false).await?;
+
+        let query = NotepadQuery::public("gre", true, 5);
+        app.search_public(&query, OutputFormat::Json).await?;
+        let out = String::from_utf8(app.output.buffer().to_vec()).unwrap();
+        let found = serde_json::from_str::<Vec<serde_json::Value>>(&out).unwrap();
+        assert!(!found.is_empty() && found.len() <= 5, "{}", out);
+        assert!(found
+            .iter()
+            .all(|n| n["notepad_id"].as_str().unwrap().starts_with('9')));
+
+        // 验证码错误时不会添加
+        app.captcha = Some("0000".to_string());
+        app.prompt = Some(UserPrompt::new(io::Cursor::new("n\n"), io::sink()));
+        assert_eq!(app.clone_notepad("900001", "gre copy").await?, None);
+        assert_eq!(app.notepads.get().len(), notepads.len());
+
+        app.captcha = Some(FIXTURE_CAPTCHA.to_string());
+        assert_eq!(
+            app.clone_notepad("900001", "gre copy").await?,
+            Some("700001".to_string())
+        );
+        let save = server
+            .requests()
+            .into_iter()
+            .rfind(|r| r.path.ends_with("/notepad/save"))
+            .unwrap();
+        assert!(
+            save.body_str().contains("id=&title=gre+copy"),
+            "{}",
+            save.body_str()
+        );
+        assert!(save.body_str().contains("content=accommodate%0Abenevolent"));
+        let cloned = app.notepads.get().last().unwrap();
+        assert_eq!(cloned.get_title(), "gre copy");
+        assert_eq!(cloned.get_contents(), Some("accommodate\nbenevolent"));
+        assert!(app.clone_notepad("900001", " ").await.is_err());
+        // 创建与删除仅支持open api
+        let e = app.create_notepad("new", "run").await.unwrap_err();
+        assert!(e.contains("api_token"), "{}", e);
+        let e = app.delete_notepad("695835").await.unwrap_err();
+        assert!(e.contains("api_token"), "{}", e);
+        Ok(())
+    }
+    #[test]
+    fn replace_notepad_summary() {
+        assert_eq!(format_count(87), "87");
+        assert_eq!(format_count(1243), "1,243");
+        assert_eq!(format_count(1234567), "1,234,567");
+        let notepad = |contents: String| -> Notepad {
+            serde_json::from_value(serde_json::json!({
+                "notepad_id": "1",
+                "title": "GRE words",
+                "contents": contents,
+            }))
+            .unwrap()
+        };
+        let old = notepad("word\n".repeat(1243));
+        let new = notepad("word\n".repeat(87));
+        assert_eq!(
+            replace_summary(&old, &new),
+            "this will REPLACE notepad 'GRE words' (1,243 lines) with 87 new lines"
+        );
+    }
+}
@@ -0,0 +1,892 @@
+//! 各服务的app：加载本地数据、刷新与保存，以及它们共用的单词读取与refresh结果
+
+pub mod eudic;
+pub mod maimemo;
+pub mod runner;
+pub mod source;
+pub mod youdao;
+
+use std::path::Path;
+
+use chrono::Duration;
+use dict::{
+    client::{
+        context::HttpContext,
+        maimemo_client::Notepad,
+        metrics::{MetricsRegistry, Timings},
+        youdao_client::WordItem,
+        NotepadSink, WordLookup, WordSource,
+    },
+    clipboard::Clipboard,
+    config::AppConfig,
+    duplicates, error,
+    events::Event,
+    export::WordTemplate,
+    msg,
+    store::{MergeReport, NotepadStore, WordStorage},
+    table,
+};
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+
+use std::fs;
+use std::io::{self, Write};
+use std::str;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{format_count, OutputFormat};
+use youdao::{ListOptions, YoudaoApp};
+
+/// 补全单词的结果
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct EnrichReport {
+    enriched: usize,
+    failed: usize,
+    /// 超过limit未查询的单词数量
+    remaining: usize,
+}
+
+/// 去除items中本地已存在或重复的单词，忽略大小写，lemma时使用单词原形比较
+fn new_words(existing: &[WordItem], items: Vec<WordItem>, lemma: bool) -> Vec<WordItem> {
+    let mut seen = existing
+        .iter()
+        .map(|w| duplicates::word_key(&w.word, true, lemma))
+        .collect::<std::collections::HashSet<_>>();
+    items
+        .into_iter()
+        .filter(|w| seen.insert(duplicates::word_key(&w.word, true, lemma)))
+        .collect()
+}
+
+/// enrich时每查询完成的单词数，之后合并到storage，中断时已查询的结果不会丢失
+const ENRICH_MERGE_BATCH: usize = 50;
+
+/// 查询storage中trans或phonetic为空且未补全过的单词，补全为空的字段后合并到storage。
+///
+/// 最多查询limit个单词，同时最多concurrency个请求，rate>0时每秒最多开始rate个请求。
+/// 每查询[`ENRICH_MERGE_BATCH`]个单词合并一次。查询失败的单词不修改，再次执行时继续补全
+async fn enrich_words(
+    lookup: &dyn WordLookup,
+    storage: &mut dyn WordStorage,
+    limit: Option<usize>,
+    concurrency: usize,
+    rate: u32,
+) -> Result<EnrichReport, String> {
+    storage.check_writable("enrich")?;
+    let pending = storage
+        .words()
+        .iter()
+        .filter(|w| w.trans.is_empty() || w.phonetic.is_empty())
+        .filter(|w| !w.local.as_ref().is_some_and(|local| local.enriched))
+        .cloned()
+        .collect::<Vec<_>>();
+    let total = pending.len();
+    let pending = pending
+        .into_iter()
+        .take(limit.unwrap_or(total))
+        .collect::<Vec<_>>();
+    let mut report = EnrichReport {
+        remaining: total - pending.len(),
+        ..EnrichReport::default()
+    };
+    debug!("enriching {} of {} words", pending.len(), total);
+    let interval = Some(rate)
+        .filter(|rate| *rate > 0)
+        .map(|rate| std::time::Duration::from_secs(1) / rate);
+    let start = tokio::time::Instant::now();
+    let mut batches = stream::iter(pending.into_iter().enumerate())
+        .map(|(i, item)| async move {
+            if let Some(interval) = interval {
+                tokio::time::sleep_until(start + interval * i as u32).await;
+            }
+            let result = lookup.lookup(&item.word).await;
+            (item, result)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .chunks(ENRICH_MERGE_BATCH);
+    while let Some(results) = batches.next().await {
+        let mut items = vec![];
+        for (mut item, result) in results {
+            let definition = match result {
+                Ok(definition) => definition,
+                Err(e) => {
+                    warn!("lookup word {} error: {}", item.word, e);
+                    report.failed += 1;
+                    continue;
+                }
+            };
+            if item.trans.is_empty() {
+                item.trans = definition.translations.join("\n");
+            }
+            if item.phonetic.is_empty() {
+                if let Some(phonetic) = definition.phonetics.first() {
+                    item.phonetic = format!("[{}]", phonetic.text);
+                }
+            }
+            item.local_mut().enriched = true;
+            items.push(item);
+        }
+        report.enriched += items.len();
+        storage.merge(items)?;
+    }
+    Ok(report)
+}
+
+/// refresh的结果，在命令结束时输出，见[`write_refresh_report`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct RefreshReport {
+    service: &'static str,
+    /// 从web上获取的数量
+    fetched: usize,
+    #[serde(flatten)]
+    merge: MergeReport,
+    elapsed_secs: f64,
+    /// 保存的位置
+    path: String,
+    /// 指定`--timings`时的请求统计
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) timings: Option<Timings>,
+}
+
+impl RefreshReport {
+    fn new(service: &'static str, merge: MergeReport, started: Instant, path: &str) -> Self {
+        Self {
+            service,
+            fetched: merge.added + merge.updated + merge.unchanged,
+            merge,
+            elapsed_secs: (started.elapsed().as_millis() as f64) / 1000.0,
+            path: path.to_string(),
+            timings: None,
+        }
+    }
+}
+
+impl std::fmt::Display for RefreshReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "refreshed {}: {} fetched, {} new, {} updated, {} unchanged, {} removed in {:.1}s. saved to {}",
+            self.service,
+            format_count(self.fetched),
+            format_count(self.merge.added),
+            format_count(self.merge.updated),
+            format_count(self.merge.unchanged),
+            format_count(self.merge.removed),
+            self.elapsed_secs,
+            self.path
+        )
+    }
+}
+
+/// text时输出到stderr，quiet时不输出；json时作为一个对象输出到stdout
+fn write_refresh_report(
+    report: &RefreshReport,
+    format: OutputFormat,
+    quiet: bool,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Json => {
+            let out = serde_json::to_string(report).map_err(|e| msg!("error.serialize", e))?;
+            writeln!(stdout, "{}", out)
+        }
+        OutputFormat::Text if quiet => Ok(()),
+        OutputFormat::Text => writeln!(stderr, "{}", report),
+    }
+    .map_err(|e| format!("write refresh report error: {}", e))
+}
+
+/// save成功后输出refresh的结果
+pub(crate) fn refresh_exit_code(
+    result: Result<(), String>,
+    report: Option<&RefreshReport>,
+    format: OutputFormat,
+    quiet: bool,
+) -> i32 {
+    let code = save_exit_code(result);
+    if let (0, Some(report)) = (code, report) {
+        if let Err(e) =
+            write_refresh_report(report, format, quiet, &mut io::stdout(), &mut io::stderr())
+        {
+            eprintln!("{}", error::pretty(&e));
+            return 1;
+        }
+    }
+    code
+}
+
+/// 输出请求统计。text时以表格输出到stderr；json时如果未作为refresh结果的一部分输出(attached)，
+/// 则作为`{"timings": ..}`对象输出到stdout
+pub(crate) fn write_timings(
+    metrics: &MetricsRegistry,
+    attached: bool,
+    format: OutputFormat,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> Result<(), String> {
+    let timings = metrics.timings();
+    match format {
+        OutputFormat::Json if attached => Ok(()),
+        OutputFormat::Json => {
+            let out = serde_json::to_string(&serde_json::json!({ "timings": timings }))
+                .map_err(|e| msg!("error.serialize", e))?;
+            writeln!(stdout, "{}", out)
+        }
+        OutputFormat::Text => write!(stderr, "{}", timings.render(table::stderr_width())),
+    }
+    .map_err(|e| format!("write timings error: {}", e))
+}
+
+/// json输出且指定`--timings`时将请求统计加入refresh结果，返回是否已加入
+pub(crate) fn attach_timings(
+    report: Option<&mut RefreshReport>,
+    metrics: &Mutex<MetricsRegistry>,
+    timings: bool,
+    format: OutputFormat,
+) -> bool {
+    match (report, timings, format) {
+        (Some(report), true, OutputFormat::Json) => {
+            report.timings = Some(metrics.lock().unwrap().timings());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// result成功时输出event，否则输出error事件
+fn emit_result<T>(context: &HttpContext, result: &Result<T, String>, event: Event) {
+    match result {
+        Ok(_) => context.emit(event),
+        Err(e) => context.emit(Event::error(e)),
+    }
+}
+
+/// 从source中获取单词替换storage中的所有单词
+async fn refresh_words(
+    source: &mut dyn WordSource,
+    storage: &mut dyn WordStorage,
+) -> Result<MergeReport, String> {
+    let words = source.fetch_words().await?;
+    storage.replace(words)
+}
+
+/// 上传notepad到sink，成功后更新本地notepads。返回false表示用户取消了上传
+async fn push_notepad(
+    sink: &mut dyn NotepadSink,
+    notepads: &mut NotepadStore,
+    notepad: Notepad,
+) -> Result<bool, String> {
+    if !sink.push(&notepad).await? {
+        return Ok(false);
+    }
+    replace_local_notepad(notepads, notepad).map(|_| true)
+}
+
+/// 用上传成功的notepad替换本地相同id的notepad
+fn replace_local_notepad(notepads: &mut NotepadStore, notepad: Notepad) -> Result<(), String> {
+    let notepad_id = notepad.get_notepad_id().to_string();
+    match notepads
+        .get_mut()
+        .iter_mut()
+        .find(|n| n.get_notepad_id() == notepad_id)
+    {
+        Some(n) => {
+            *n = notepad;
+            Ok(())
+        }
+        None => {
+            warn!(
+                "Failed to update local Notepad. not found notepad_id: {}",
+                notepad_id
+            );
+            Err("save notepad successful, but Failed to update local Notepad. please use -r refresh local data".to_string())
+        }
+    }
+}
+
+/// 规范化后的stdin内容与统计
+#[derive(Debug, Default, PartialEq)]
+struct NormalizedInput {
+    text: String,
+    removed_bom: bool,
+    /// 去掉了`\r`或行尾空白的行数
+    modified_lines: usize,
+    empty_lines: usize,
+    duplicate_lines: usize,
+    /// 包含空白的行，可能是短语而不是单词
+    phrases: Vec<String>,
+}
+
+impl NormalizedInput {
+    fn has_changes(&self) -> bool {
+        self.removed_bom || self.modified_lines + self.empty_lines + self.duplicate_lines > 0
+    }
+
+    fn report(&self) -> String {
+        format!(
+            "normalized input: dropped {} empty and {} duplicate lines, modified {} lines{}",
+            self.empty_lines,
+            self.duplicate_lines,
+            self.modified_lines,
+            if self.removed_bom {
+                ", removed bom"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// 去掉bom，转换crlf，去掉行尾空白、空行与重复的行。`#`开头的注释行可以重复且不检查短语
+fn normalize_input(input: &str) -> NormalizedInput {
+    let mut out = NormalizedInput::default();
+    let input = match input.strip_prefix('\u{feff}') {
+        Some(input) => {
+            out.removed_bom = true;
+            input
+        }
+        None => input,
+    };
+    let mut lines = input.split('\n').collect::<Vec<_>>();
+    // 最后的换行符后没有内容
+    if lines.last() == Some(&"") {
+        lines.pop();
+    }
+    let mut seen = std::collections::HashSet::new();
+    for line in lines {
+        let word = line.trim_end();
+        if word.trim_start().is_empty() {
+            out.empty_lines += 1;
+            continue;
+        }
+        let is_comment = word.starts_with('#');
+        if !is_comment && !seen.insert(word) {
+            out.duplicate_lines += 1;
+            continue;
+        }
+        if word.len() != line.len() {
+            out.modified_lines += 1;
+        }
+        if !is_comment && word.trim_start().contains(char::is_whitespace) {
+            out.phrases.push(word.to_string());
+        }
+        out.text.push_str(word);
+        out.text.push('\n');
+    }
+    out
+}
+
+/// 规范化contents后每行一个单词，忽略`#`开头的注释行。非quiet时报告规范化的修改
+fn read_words(contents: &str, quiet: bool) -> Vec<String> {
+    let normalized = normalize_input(contents);
+    if !normalized.phrases.is_empty() {
+        let phrases = msg!(
+            "upload.phrases",
+            normalized.phrases.len(),
+            normalized.phrases.join(", ")
+        );
+        eprintln!("{}", msg!("warning", phrases));
+    }
+    if !quiet && normalized.has_changes() {
+        eprintln!("{}", normalized.report());
+    }
+    normalized
+        .text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// 从剪贴板中读取单词，见[`read_words`]
+pub(crate) fn read_clipboard_words(
+    clipboard: &mut dyn Clipboard,
+    quiet: bool,
+) -> Result<Vec<String>, String> {
+    let contents = clipboard.get_text()?;
+    Ok(read_words(&contents, quiet))
+}
+
+/// 读取path文件的内容，为None时读取stdin
+pub(crate) fn read_file_or_stdin(path: Option<&str>) -> Result<String, String> {
+    match path {
+        Some(path) => {
+            fs::read_to_string(path).map_err(|e| format!("read file error: {}, path: {}", e, path))
+        }
+        None => io::read_to_string(io::stdin()).map_err(|e| format!("read stdin error: {}", e)),
+    }
+}
+
+/// 按修改时间降序输出words，json时输出完整的单词
+fn list_words(
+    output: &mut impl Write,
+    words: &[WordItem],
+    offset: isize,
+    format: OutputFormat,
+) -> Result<(), String> {
+    let words = words.iter().cloned();
+    match format {
+        OutputFormat::Text => YoudaoApp::write_words(
+            output,
+            words.map(Ok),
+            &ListOptions::new(offset, &WordTemplate::default()),
+        ),
+        OutputFormat::Json => {
+            let words = YoudaoApp::select_words(words, None, None, offset);
+            let out =
+                serde_json::to_string_pretty(&words).map_err(|e| msg!("error.serialize", e))?;
+            writeln!(output, "{}", out).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// 解析相对时间：`h`小时、`d`天、`w`周与`m`月，m按30天计算
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let unit_at = s.char_indices().last().map_or(0, |(i, _)| i);
+    let (num, unit) = s.split_at(unit_at);
+    let num = num
+        .parse::<u32>()
+        .map_err(|e| format!("invalid duration {}: {}", s, e))?;
+    let hours = match unit {
+        "h" => 1,
+        "d" => 24,
+        "w" => 7 * 24,
+        "m" => 30 * 24,
+        _ => {
+            return Err(format!(
+                "invalid duration unit {}: expected one of h, d, w, m",
+                s
+            ))
+        }
+    };
+    Ok(Duration::hours(i64::from(num) * hours))
+}
+
+/// 未指定refresh时检查本地数据文件是否存在。文件不存在时如果开启了auto_refresh则返回
+/// true表示需要从web上加载
+///
+/// # Errors
+///
+/// 如果本地数据文件不存在且未开启auto_refresh
+pub(crate) fn need_refresh(
+    config: &AppConfig,
+    refresh: bool,
+    auto_refresh: bool,
+) -> Result<bool, String> {
+    let path = config.get_dictionary_path();
+    if refresh || Path::new(path).exists() {
+        return Ok(refresh);
+    }
+    if auto_refresh || config.auto_refresh() {
+        info!("not found local data {}, refreshing from web", path);
+        Ok(true)
+    } else {
+        Err(msg!("data.no_local", path))
+    }
+}
+
+/// 保存失败时输出错误并返回非0的退出码
+pub(crate) fn save_exit_code(result: Result<(), String>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", msg!("error.save", error::pretty(&e)));
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dict::{
+        client::{
+            youdao_client::{Definition, Phonetic},
+            BoxFuture,
+        },
+        store::WordStore,
+    };
+    use std::sync::Arc;
+
+    /// 内存中的单词服务
+    struct FakeSource {
+        words: Result<Vec<WordItem>, String>,
+        fetched: usize,
+    }
+
+    impl WordSource for FakeSource {
+        fn fetch_words(&mut self) -> BoxFuture<'_, Result<Vec<WordItem>, String>> {
+            self.fetched += 1;
+            let words = self.words.clone();
+            Box::pin(async move { words })
+        }
+    }
+
+    /// 记录上传的notepad，accept=false时模拟用户取消
+    struct FakeSink {
+        accept: bool,
+        pushed: Vec<Notepad>,
+    }
+
+    impl NotepadSink for FakeSink {
+        fn push<'a>(&'a mut self, notepad: &'a Notepad) -> BoxFuture<'a, Result<bool, String>> {
+            Box::pin(async move {
+                self.pushed.push(notepad.clone());
+                Ok(self.accept)
+            })
+        }
+    }
+
+    /// 查询words中的单词，记录查询过的单词
+    struct FakeLookup {
+        definitions: Vec<Definition>,
+        looked_up: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl WordLookup for FakeLookup {
+        fn lookup<'a>(&'a self, word: &'a str) -> BoxFuture<'a, Result<Definition, String>> {
+            self.looked_up.borrow_mut().push(word.to_string());
+            let result = self
+                .definitions
+                .iter()
+                .find(|d| d.word == word)
+                .cloned()
+                .ok_or_else(|| format!("not found definition of {}", word));
+            Box::pin(async move { result })
+        }
+    }
+
+    fn definition(word: &str, phonetic: Option<&str>) -> Definition {
+        Definition {
+            word: word.to_string(),
+            phonetics: phonetic
+                .map(|text| Phonetic {
+                    accent: "uk".to_string(),
+                    text: text.to_string(),
+                })
+                .into_iter()
+                .collect(),
+            translations: vec![format!("n. {}", word), "v. 测试".to_string()],
+            web_phrases: vec![],
+        }
+    }
+
+    fn fake_lookup() -> FakeLookup {
+        FakeLookup {
+            definitions: vec![
+                definition("empty", Some("ˈempti")),
+                definition("full", Some("fʊl")),
+                definition("no phonetic", None),
+                definition("phonetic only", Some("ˈfəʊ")),
+            ],
+            looked_up: Default::default(),
+        }
+    }
+
+    fn word(word: &str) -> WordItem {
+        WordItem {
+            item_id: word.to_string(),
+            book_id: "1".to_string(),
+            book_name: "GRE".to_string(),
+            word: word.to_string(),
+            trans: String::new(),
+            phonetic: String::new(),
+            modified_time: 0,
+            local: None,
+        }
+    }
+
+    fn fixture_notepads() -> NotepadStore {
+        let notepads: Vec<Notepad> = serde_json::from_str(include_str!(
+            "../../tests/fixtures/maimemo-notepads-v1.json"
+        ))
+        .unwrap();
+        NotepadStore::new("notepads.json", notepads)
+    }
+
+    fn edited(notepad: &Notepad, contents: &str) -> Notepad {
+        let mut notepad = notepad.clone();
+        notepad.set_contents(Some(contents.to_string()));
+        notepad
+    }
+
+    #[tokio::test]
+    async fn enrich_only_empty_words() -> Result<(), String> {
+        let mut full = word("full");
+        full.trans = "adj. 满的".to_string();
+        full.phonetic = "[fʊl]".to_string();
+        let mut phonetic_only = word("phonetic only");
+        phonetic_only.trans = "keep".to_string();
+        let mut storage = WordStore::new(
+            "words.json",
+            vec![
+                word("empty"),
+                full.clone(),
+                word("missing"),
+                word("no phonetic"),
+                phonetic_only,
+            ],
+        );
+        let lookup = fake_lookup();
+        let report = enrich_words(&lookup, &mut storage, None, 2, 0).await?;
+        assert_eq!(
+            report,
+            EnrichReport {
+                enriched: 3,
+                failed: 1,
+                remaining: 0,
+            }
+        );
+        let mut looked_up = lookup.looked_up.borrow().clone();
+        looked_up.sort();
+        assert_eq!(
+            looked_up,
+            vec!["empty", "missing", "no phonetic", "phonetic only"]
+        );
+
+        let words = storage.words();
+        let find = |w: &str| words.iter().find(|i| i.word == w).unwrap();
+        assert_eq!(find("empty").trans, "n. empty\nv. 测试");
+        assert_eq!(find("empty").phonetic, "[ˈempti]");
+        assert_eq!(find("full"), &full);
+        // 失败的单词不修改
+        assert_eq!(find("missing"), &word("missing"));
+        assert_eq!(find("no phonetic").phonetic, "");
+        // 仅补全为空的字段
+        assert_eq!(find("phonetic only").trans, "keep");
+        assert_eq!(find("phonetic only").phonetic, "[ˈfəʊ]");
+
+        // 查询结果中没有音标的单词不再查询，仅重试失败的单词
+        let report = enrich_words(&lookup, &mut storage, None, 2, 0).await?;
+        assert_eq!((report.enriched, report.failed), (0, 1));
+        assert_eq!(lookup.looked_up.borrow().len(), 5);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn enrich_with_limit_and_rate() -> Result<(), String> {
+        let words = ["empty", "no phonetic", "phonetic only"];
+        let mut storage = WordStore::new("words.json", words.iter().map(|w| word(w)).collect());
+        let lookup = fake_lookup();
+        let start = tokio::time::Instant::now();
+        let report = enrich_words(&lookup, &mut storage, Some(2), 4, 2).await?;
+        assert_eq!(report.enriched, 2);
+        assert_eq!(report.remaining, 1);
+        // 每秒最多2个请求
+        assert_eq!(start.elapsed(), std::time::Duration::from_millis(500));
+
+        // 已补全的单词被跳过，包括查询结果中没有音标的
+        let report = enrich_words(&lookup, &mut storage, None, 4, 0).await?;
+        assert_eq!(report.enriched, 1);
+        assert_eq!(report.remaining, 0);
+        assert_eq!(lookup.looked_up.borrow().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refresh_replaces_words() -> Result<(), String> {
+        let mut storage = WordStore::new("words.json", vec![word("old"), word("kept")]);
+        let mut source = FakeSource {
+            words: Ok(vec![word("kept"), word("new")]),
+            fetched: 0,
+        };
+        let report = refresh_words(&mut source, &mut storage).await?;
+        assert_eq!(source.fetched, 1);
+        let words = storage
+            .words()
+            .iter()
+            .map(|w| w.word.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(words, vec!["kept", "new"]);
+        assert_eq!((report.added, report.removed), (1, 1));
+
+        // 获取失败时不修改本地单词
+        source.words = Err("login error".to_string());
+        let e = refresh_words(&mut source, &mut storage).await.unwrap_err();
+        assert_eq!(e, "login error");
+        assert_eq!(storage.words().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn push_updates_local_notepad() -> Result<(), String> {
+        let mut notepads = fixture_notepads();
+        let notepad = edited(&notepads.get()[0], "new words");
+        let mut sink = FakeSink {
+            accept: true,
+            pushed: vec![],
+        };
+        assert!(push_notepad(&mut sink, &mut notepads, notepad).await?);
+        assert_eq!(sink.pushed.len(), 1);
+        assert_eq!(notepads.get()[0].get_contents(), Some("new words"));
+
+        // 用户取消时不更新本地
+        let notepad = edited(&notepads.get()[0], "canceled");
+        sink.accept = false;
+        assert!(!push_notepad(&mut sink, &mut notepads, notepad).await?);
+        assert_eq!(sink.pushed.len(), 2);
+        assert_eq!(notepads.get()[0].get_contents(), Some("new words"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn push_missing_local_notepad() {
+        let mut notepads = NotepadStore::new("notepads.json", vec![]);
+        let notepad = edited(&fixture_notepads().get()[0], "new words");
+        let mut sink = FakeSink {
+            accept: true,
+            pushed: vec![],
+        };
+        let e = push_notepad(&mut sink, &mut notepads, notepad)
+            .await
+            .unwrap_err();
+        assert!(e.contains("please use -r"), "{}", e);
+    }
+    /// 剪贴板中的文本，text为None时读写都失败
+    #[derive(Default)]
+    struct FakeClipboard {
+        text: Option<String>,
+    }
+
+    impl Clipboard for FakeClipboard {
+        fn get_text(&mut self) -> Result<String, String> {
+            self.text.clone().ok_or_else(|| "no display".to_string())
+        }
+
+        fn set_text(&mut self, text: &str) -> Result<(), String> {
+            let copied = self.text.as_mut().ok_or_else(|| "no display".to_string())?;
+            *copied = text.to_string();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn clipboard_words() {
+        // 从pdf阅读器中复制的文本：bom、crlf、行尾空白、空行与重复
+        let mut clipboard = FakeClipboard {
+            text: Some(
+                "\u{feff}ubiquitous \r\n\r\nephemeral\r\n# notes\r\nubiquitous\r\n".to_string(),
+            ),
+        };
+        assert_eq!(
+            read_clipboard_words(&mut clipboard, true).unwrap(),
+            vec!["ubiquitous", "ephemeral"]
+        );
+        assert!(read_clipboard_words(&mut FakeClipboard::default(), true).is_err());
+    }
+    #[test]
+    fn normalize_inputs() {
+        // (input, text, removed_bom, modified, empty, duplicate, phrases)
+        let cases = vec![
+            ("", "", false, 0, 0, 0, 0),
+            ("apple\nbanana\n", "apple\nbanana\n", false, 0, 0, 0, 0),
+            ("apple\nbanana", "apple\nbanana\n", false, 0, 0, 0, 0),
+            ("\u{feff}apple\n", "apple\n", true, 0, 0, 0, 0),
+            ("apple\r\nbanana\r\n", "apple\nbanana\n", false, 2, 0, 0, 0),
+            ("apple \t\nbanana\n", "apple\nbanana\n", false, 1, 0, 0, 0),
+            (
+                "apple\n\n  \r\nbanana\n",
+                "apple\nbanana\n",
+                false,
+                0,
+                2,
+                0,
+                0,
+            ),
+            (
+                "apple\nbanana\napple\napple \n",
+                "apple\nbanana\n",
+                false,
+                0,
+                0,
+                2,
+                0,
+            ),
+            ("give up\napple\n", "give up\napple\n", false, 0, 0, 0, 1),
+            (
+                "# 2020-10-17 Auto insert\n",
+                "# 2020-10-17 Auto insert\n",
+                false,
+                0,
+                0,
+                0,
+                0,
+            ),
+            // 重复的注释行保留
+            (
+                "# day 1\napple\n#\n# day 1\n#\n",
+                "# day 1\napple\n#\n# day 1\n#\n",
+                false,
+                0,
+                0,
+                0,
+                0,
+            ),
+        ];
+        for (input, text, removed_bom, modified, empty, duplicate, phrases) in cases {
+            let out = normalize_input(input);
+            assert_eq!(out.text, text, "{:?}", input);
+            assert_eq!(
+                (
+                    out.removed_bom,
+                    out.modified_lines,
+                    out.empty_lines,
+                    out.duplicate_lines,
+                    out.phrases.len()
+                ),
+                (removed_bom, modified, empty, duplicate, phrases),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    fn missing_config(dir: &tempfile::TempDir, extra: &str) -> AppConfig {
+        let path = dir.path().join("missing.json");
+        serde_yaml::from_str(&format!(
+            "username: a\npassword: b\ndictionary_path: {}\n{}",
+            path.to_str().unwrap(),
+            extra
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn missing_local_data() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config = missing_config(&dir, "");
+        let e = need_refresh(&config, false, false).unwrap_err();
+        assert!(e.contains("--refresh"), "{}", e);
+        assert!(YoudaoApp::from_file(config, Arc::new(HttpContext::new()?))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn auto_refresh_missing_local_data() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config = missing_config(&dir, "");
+        assert_eq!(need_refresh(&config, false, true), Ok(true));
+        assert_eq!(need_refresh(&config, true, false), Ok(true));
+        let config = missing_config(&dir, "auto_refresh: true\n");
+        assert_eq!(need_refresh(&config, false, false), Ok(true));
+        // 存在本地文件时不需要refresh
+        fs::write(config.get_dictionary_path(), "[]").map_err(|e| format!("{:?}", e))?;
+        assert_eq!(need_refresh(&config, false, true), Ok(false));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("24h"), Ok(Duration::hours(24)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::days(7)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::weeks(2)));
+        assert_eq!(parse_duration("1m"), Ok(Duration::days(30)));
+        for s in &["7x", "d", "", "-1d", "1.5d", "7天"] {
+            assert!(parse_duration(s).is_err(), "{}", s);
+        }
+    }
+}
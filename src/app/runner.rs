@@ -0,0 +1,491 @@
+//! `dict run`与`dict sync`的plan执行与watch循环
+
+use std::path::Path;
+
+use dict::{
+    client::context::HttpContext,
+    config::{AppConfig, Config},
+    error,
+    events::Event,
+    msg,
+    plan::{Action, Plan, Service, Step, SyncStep},
+    watch,
+};
+use tokio_util::sync::CancellationToken;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::str;
+use std::sync::Arc;
+
+use super::{
+    maimemo::{captcha_command, MaimemoApp},
+    parse_duration, save_exit_code,
+    source::SourceApp,
+    youdao::YoudaoApp,
+};
+use crate::format_age;
+
+/// `dict run --watch`与`dict sync --watch`默认的间隔
+const WATCH_INTERVAL: &str = "6h";
+
+/// 执行一次plan。watch时每隔interval重复执行，interval为None时使用[`WATCH_INTERVAL`]
+pub(crate) async fn run_or_watch_plan(
+    plan: &Plan,
+    runner: PlanRunner,
+    watch: bool,
+    interval: Option<&str>,
+    cancel: &CancellationToken,
+    quiet: bool,
+) -> i32 {
+    if !watch {
+        let mut runner = runner;
+        return run_plan(plan, &mut runner, quiet).await;
+    }
+    let interval = match parse_duration(interval.unwrap_or(WATCH_INTERVAL))
+        .and_then(|d| d.to_std().map_err(|e| e.to_string()))
+    {
+        Ok(interval) if !interval.is_zero() => interval,
+        Ok(_) => {
+            eprintln!("{}", msg!("error.plan", "watch interval must not be zero"));
+            return 1;
+        }
+        Err(e) => {
+            eprintln!("{}", msg!("error.plan", error::pretty(&e)));
+            return 1;
+        }
+    };
+    let mut runner = runner.with_watch();
+    watch_plan(plan, &mut runner, interval, cancel, quiet).await
+}
+
+/// `dict run`执行plan时共享的apps。每个服务在第一次使用时加载，之后的步骤复用其登录状态
+pub(crate) struct PlanRunner {
+    config: Config,
+    context: Arc<HttpContext>,
+    youdao: Option<YoudaoApp>,
+    maimemo: Option<MaimemoApp<'static>>,
+    /// 已加载的自定义来源，key为来源名称
+    sources: BTreeMap<String, SourceApp>,
+    assume_yes: bool,
+    /// watch时每个sync步骤上一轮处理过的单词的hash。单词没有变化时跳过该步骤
+    watermarks: Option<BTreeMap<String, u64>>,
+    /// 为Some时不上传，而是将待添加的单词写入该目录，见[`PlanRunner::stage_words`]
+    staging_dir: Option<String>,
+    /// 本轮sync步骤添加或暂存的单词数量
+    synced: usize,
+}
+
+impl PlanRunner {
+    pub(crate) fn new(config: Config, context: Arc<HttpContext>, assume_yes: bool) -> Self {
+        Self {
+            config,
+            context,
+            youdao: None,
+            maimemo: None,
+            sources: BTreeMap::new(),
+            assume_yes,
+            watermarks: None,
+            staging_dir: None,
+            synced: 0,
+        }
+    }
+
+    /// 用于`--watch`重复执行plan：单词没有变化的sync步骤被跳过。
+    /// maimemo未配置captcha_command时无法自动输入验证码，不上传而是暂存到data_dir中
+    fn with_watch(mut self) -> Self {
+        self.watermarks = Some(BTreeMap::new());
+        let automated = self
+            .config
+            .maimemo
+            .as_ref()
+            .and_then(AppConfig::get_captcha_command)
+            .is_some();
+        if !automated {
+            self.staging_dir = Some(self.config.get_data_dir().to_string());
+        }
+        self
+    }
+
+    /// 保存后释放已加载的apps，下一轮重新加载。保留watermarks
+    fn next_round(&mut self) {
+        self.youdao = None;
+        self.maimemo = None;
+        self.sources.clear();
+        self.synced = 0;
+    }
+
+    /// 第一次使用时加载，refresh时从web上加载
+    async fn youdao(&mut self, refresh: bool) -> Result<&mut YoudaoApp, String> {
+        if self.youdao.is_none() {
+            let config = self
+                .config
+                .youdao
+                .clone()
+                .ok_or("youdao is not configured")?;
+            let context = self.context.clone();
+            let app = if refresh {
+                YoudaoApp::from_web(config, context).await?
+            } else {
+                YoudaoApp::from_file(config, context).await?
+            };
+            self.youdao = Some(app);
+        }
+        Ok(self.youdao.as_mut().unwrap())
+    }
+
+    async fn maimemo(&mut self, refresh: bool) -> Result<&mut MaimemoApp<'static>, String> {
+        if self.maimemo.is_none() {
+            let config = self
+                .config
+                .maimemo
+                .clone()
+                .ok_or("maimemo is not configured")?;
+            let captcha_command = captcha_command(&config)?;
+            let app = MaimemoApp::with_stdio(config, self.context.clone(), !refresh)
+                .await?
+                .with_assume_yes(self.assume_yes)
+                .with_captcha_command(captcha_command);
+            self.maimemo = Some(app);
+        }
+        Ok(self.maimemo.as_mut().unwrap())
+    }
+
+    /// 第一次使用时从来源中重新加载
+    async fn source(&mut self, name: &str) -> Result<&mut SourceApp, String> {
+        if !self.sources.contains_key(name) {
+            let source = self.config.custom_source(name)?;
+            let app = SourceApp::new(name, &source, self.context.clone(), true).await?;
+            self.sources.insert(name.to_string(), app);
+        }
+        Ok(self.sources.get_mut(name).unwrap())
+    }
+
+    async fn run_step(&mut self, step: &Step) -> Result<(), String> {
+        match &step.action {
+            Action::Refresh(Service::Youdao) => self.youdao(true).await.map(|_| ()),
+            Action::Refresh(Service::Maimemo) => self.maimemo(true).await.map(|_| ()),
+            Action::ExportCsv(path) => self.youdao(false).await?.export_csv(path),
+            Action::Sync(sync) => {
+                let (words, from) = match &sync.source {
+                    Some(source) => {
+                        let words = self.source(source).await?.words();
+                        if words.is_empty() {
+                            return Err(format!("not found any word in custom source: {}", source));
+                        }
+                        (words, format!("custom source {}", source))
+                    }
+                    None => (
+                        self.youdao(false).await?.book_words(&sync.book)?,
+                        format!("book {}", sync.book),
+                    ),
+                };
+                let key = format!("{} -> {}", from, sync.notepad);
+                let watermark = words_watermark(&words);
+                if let Some(watermarks) = &self.watermarks {
+                    if watermarks.get(&key) == Some(&watermark) {
+                        info!("nothing new in {} since the last round, skipped", from);
+                        return Ok(());
+                    }
+                }
+                let added = match self.staging_dir.clone() {
+                    Some(dir) => self.stage_words(&dir, sync, &words).await?,
+                    None => {
+                        self.maimemo(false)
+                            .await?
+                            .append_words(&sync.notepad, &words, sync.timestamp, sync.lemma)
+                            .await?
+                    }
+                };
+                info!(
+                    "added {} of {} words in {} to notepad {}",
+                    added,
+                    words.len(),
+                    from,
+                    sync.notepad
+                );
+                self.synced += added;
+                if let Some(watermarks) = &mut self.watermarks {
+                    watermarks.insert(key, watermark);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// 将notepad中没有的words写入dir中的`pending-<notepad>.txt`并提示，返回暂存的单词数量。
+    /// 没有新的单词时删除之前暂存的文件
+    async fn stage_words(
+        &mut self,
+        dir: &str,
+        sync: &SyncStep,
+        words: &[String],
+    ) -> Result<usize, String> {
+        let pending = self
+            .maimemo(false)
+            .await?
+            .new_words(&sync.notepad, words, sync.lemma)?;
+        let path = Path::new(dir).join(format!("pending-{}.txt", sync.notepad));
+        let path_str = path.to_string_lossy().into_owned();
+        if pending.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| format!("remove {} error: {}", path_str, e))?;
+            }
+            return Ok(0);
+        }
+        fs::write(&path, pending.join("\n") + "\n")
+            .map_err(|e| format!("write {} error: {}", path_str, e))?;
+        let command = format!(
+            "dict mm -u -a{} --id {} --file {}",
+            if sync.timestamp { " -t" } else { "" },
+            sync.notepad,
+            path_str
+        );
+        warn!(
+            "staged {} words for notepad {} to {}",
+            pending.len(),
+            sync.notepad,
+            path_str
+        );
+        let message = msg!(
+            "watch.staged",
+            pending.len(),
+            sync.notepad,
+            path_str,
+            command
+        );
+        eprintln!("{}", message);
+        watch::notify_staged(&message);
+        self.context.emit(Event::UploadStaged {
+            notepad: sync.notepad.clone(),
+            path: path_str,
+            words: pending.len(),
+        });
+        Ok(pending.len())
+    }
+
+    /// 保存已加载的apps，都会尝试保存，返回第一个错误
+    async fn save(&mut self) -> Result<(), String> {
+        let youdao = match self.youdao.as_mut() {
+            Some(app) => app.save().await,
+            None => Ok(()),
+        };
+        let maimemo = match self.maimemo.as_mut() {
+            Some(app) => app.save().await,
+            None => Ok(()),
+        };
+        let sources = self
+            .sources
+            .values_mut()
+            .map(SourceApp::save)
+            .fold(Ok(()), Result::and);
+        youdao.and(maimemo).and(sources)
+    }
+}
+
+/// sync步骤中单词的hash，与顺序无关。用于watch时判断来源中是否有新的单词
+fn words_watermark(words: &[String]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut words = words.iter().collect::<Vec<_>>();
+    words.sort_unstable();
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    words.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// `dict run --watch`：每隔interval执行一次plan，直到cancel。被cancel时进行中的请求被取消，
+/// 之后与每一轮结束时一样通过[`PlanRunner::save`]保存。返回最后一轮的退出码
+async fn watch_plan(
+    plan: &Plan,
+    runner: &mut PlanRunner,
+    interval: std::time::Duration,
+    cancel: &CancellationToken,
+    quiet: bool,
+) -> i32 {
+    let mut schedule = watch::Schedule::new(interval);
+    loop {
+        let code = run_plan(plan, runner, quiet).await;
+        let round = match (code, runner.synced) {
+            (0, 0) => watch::Round::NothingNew,
+            (0, _) => watch::Round::Synced,
+            _ => watch::Round::Failed,
+        };
+        runner.next_round();
+        let delay = schedule.next_delay(round);
+        info!(
+            "watch round finished: {:?}, next round in {:?}",
+            round, delay
+        );
+        if cancel.is_cancelled() {
+            return code;
+        }
+        if !quiet {
+            eprintln!(
+                "{}",
+                msg!(
+                    "watch.next_round",
+                    format_age(delay.as_secs()),
+                    schedule.failures()
+                )
+            );
+        }
+        if !watch::sleep_or_cancel(delay, cancel).await {
+            return code;
+        }
+    }
+}
+
+/// 按顺序执行plan的步骤，失败时停止，continue_on_error的步骤除外。结束后保存已加载的数据。
+/// 返回进程退出码
+async fn run_plan(plan: &Plan, runner: &mut PlanRunner, quiet: bool) -> i32 {
+    let total = plan.steps.len();
+    let mut code = 0;
+    for (i, step) in plan.steps.iter().enumerate() {
+        if !quiet {
+            eprintln!("{}", msg!("plan.step", i + 1, total, step));
+        }
+        if let Err(e) = runner.run_step(step).await {
+            eprintln!("{}", msg!("plan.step_failed", i + 1, error::pretty(&e)));
+            if !step.continue_on_error {
+                code = 1;
+                break;
+            }
+            eprintln!("{}", msg!("plan.continue"));
+        }
+    }
+    match (code, save_exit_code(runner.save().await)) {
+        (0, saved) => saved,
+        (code, _) => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OutputFormat;
+    use dict::client::youdao_client::WordItem;
+
+    #[tokio::test]
+    async fn custom_source_app() -> Result<(), String> {
+        use mock_server::{MockResponse, MockServer};
+        let server = MockServer::start(|_| {
+            MockResponse::text(
+                200,
+                include_str!("../../tests/fixtures/custom-source-nested.json"),
+            )
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config_path = dir.path().join("dict-config.yml");
+        fs::write(
+            &config_path,
+            format!(
+                "data_dir: .
+custom_sources:
+  vocab:
+    dictionary_path: vocab.json
+    request:
+      url: {}
+      method: GET
+    mapping:
+      items: /data/words
+      word: /text
+      trans: /meaning
+      modified_time: /updated_at
+",
+                server.url("/words")
+            ),
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        let config = Config::from_yaml_file(config_path.to_str().unwrap())?;
+        let source = config.custom_source("vocab")?;
+        let context = Arc::new(HttpContext::new()?);
+
+        let mut app = SourceApp::new("vocab", &source, context.clone(), true).await?;
+        app.save()?;
+        assert_eq!(app.words(), vec!["serendipity", "ephemeral"]);
+        let mut out = vec![];
+        app.list(&mut out, 0, OutputFormat::Json)?;
+        let listed: Vec<WordItem> = serde_json::from_slice(&out).unwrap();
+        assert_eq!(listed[0].word, "ephemeral");
+        assert_eq!(listed[0].book_name, "vocab");
+
+        // 不refresh时从本地文件加载
+        let app = SourceApp::new("vocab", &source, context.clone(), false).await?;
+        assert_eq!(app.words().len(), 2);
+        assert_eq!(server.requests().len(), 1);
+
+        // plan中的来源仅在第一次使用时加载
+        let mut runner = PlanRunner::new(config, context, true);
+        runner.source("vocab").await?;
+        assert_eq!(runner.source("vocab").await?.words().len(), 2);
+        assert_eq!(server.requests().len(), 2);
+        let e = runner.source("other").await.err().unwrap();
+        assert!(e.contains("not found custom source other"), "{}", e);
+        runner.save().await
+    }
+
+    #[tokio::test]
+    async fn watch_stages_new_words() -> Result<(), String> {
+        use mock_server::{MockResponse, MockServer};
+        let server = MockServer::start(|_| {
+            MockResponse::text(
+                200,
+                include_str!("../../tests/fixtures/custom-source-nested.json"),
+            )
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        fs::copy(
+            "tests/fixtures/maimemo-notepads-v1.json",
+            dir.path().join("notepads.json"),
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        let config_path = dir.path().join("dict-config.yml");
+        fs::write(
+            &config_path,
+            format!(
+                "data_dir: .
+maimemo:
+  username: a
+  password: b
+  dictionary_path: notepads.json
+custom_sources:
+  vocab:
+    dictionary_path: vocab.json
+    request:
+      url: {}
+      method: GET
+    mapping:
+      items: /data/words
+      word: /text
+",
+                server.url("/words")
+            ),
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        let config = Config::from_yaml_file(config_path.to_str().unwrap())?;
+        let plan =
+            Plan::from_yaml("steps:\n  - sync:\n      source: vocab\n      notepad: '695835'")?;
+        let context = Arc::new(HttpContext::new()?);
+        let mut runner = PlanRunner::new(config, context, true).with_watch();
+
+        // 未配置captcha_command时不登录上传，而是暂存新的单词
+        assert_eq!(run_plan(&plan, &mut runner, true).await, 0);
+        assert_eq!(runner.synced, 2);
+        let pending = dir.path().join("pending-695835.txt");
+        assert_eq!(
+            fs::read_to_string(&pending).map_err(|e| format!("{:?}", e))?,
+            "serendipity\nephemeral\n"
+        );
+
+        // 来源中没有新的单词时跳过
+        runner.next_round();
+        fs::remove_file(&pending).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(run_plan(&plan, &mut runner, true).await, 0);
+        assert_eq!(runner.synced, 0);
+        assert!(!pending.exists());
+        assert_eq!(server.requests().len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+//! 配置文件custom_sources中的自定义来源
+
+use dict::{
+    client::{self, context::HttpContext, generic_json_client::GenericJsonClient},
+    config::CustomSourceConfig,
+    store::{open_word_storage, MergeReport, WordStorage},
+};
+
+use std::io::Write;
+use std::str;
+use std::sync::Arc;
+
+use super::{list_words, refresh_words};
+use crate::OutputFormat;
+
+/// 配置文件custom_sources中的自定义来源，本地单词与youdao使用相同的存储。仅支持读取
+pub(crate) struct SourceApp {
+    word_items: Box<dyn WordStorage>,
+    client: GenericJsonClient,
+}
+
+impl SourceApp {
+    /// 打开本地单词本，不存在时使用空的单词本。refresh时从来源中重新加载
+    pub async fn new(
+        name: &str,
+        source: &CustomSourceConfig,
+        context: Arc<HttpContext>,
+        refresh: bool,
+    ) -> Result<Self, String> {
+        let config = source.to_app_config(client::generic_json_client::REQUEST_NAME);
+        let word_items = open_word_storage(&config, true, context.is_read_only())
+            .await
+            .map_err(|e| format!("load words failed. {}", e))?;
+        let mut app = Self {
+            word_items,
+            client: GenericJsonClient::with_context(name, source, context),
+        };
+        if refresh {
+            let report = app.refresh().await?;
+            debug!("refreshed custom source {} words: {:?}", name, report);
+        }
+        Ok(app)
+    }
+
+    /// 用来源中的单词替换本地单词
+    pub async fn refresh(&mut self) -> Result<MergeReport, String> {
+        refresh_words(&mut self.client, self.word_items.as_mut()).await
+    }
+
+    pub fn list(
+        &self,
+        output: &mut impl Write,
+        offset: isize,
+        format: OutputFormat,
+    ) -> Result<(), String> {
+        list_words(output, self.word_items.words(), offset, format)
+    }
+
+    /// 所有单词，按修改时间升序
+    pub fn words(&self) -> Vec<String> {
+        self.word_items
+            .words()
+            .iter()
+            .map(|w| w.word.clone())
+            .collect()
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        self.word_items.flush().map(|_| ())
+    }
+}
@@ -0,0 +1,1411 @@
+//! youdao单词本的查询、过滤、标签与导入导出
+
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use dict::{
+    client::{
+        anki_client::{AnkiAddReport, AnkiClient},
+        context::HttpContext,
+        youdao_client::{BatchReport, Book, WordItem, YoudaoClient},
+    },
+    config::{AnkiConfig, AppConfig},
+    events::Event,
+    export::{format_anki_tsv, format_chapters, ChapterBy, WordTemplate},
+    msg,
+    prompt::UserPrompt,
+    review,
+    store::{self, open_word_storage, MergeReport, WordStorage, WordStore},
+    table::{self, Table},
+};
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs;
+use std::io::{self, Write};
+use std::str;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::{emit_result, enrich_words, new_words, parse_duration, EnrichReport, RefreshReport};
+use crate::OutputFormat;
+
+/// 查询单词并输出到output。add时将单词添加到单词本，需要时先登录
+pub(crate) async fn lookup_word(
+    client: &mut YoudaoClient,
+    word: &str,
+    add: bool,
+    format: OutputFormat,
+    output: &mut impl Write,
+) -> Result<(), String> {
+    let definition = client.lookup(word).await?;
+    match format {
+        OutputFormat::Text => write!(output, "{}", definition),
+        OutputFormat::Json => {
+            let out = serde_json::to_string_pretty(&definition)
+                .map_err(|e| msg!("error.serialize", e))?;
+            writeln!(output, "{}", out)
+        }
+    }
+    .map_err(|e| e.to_string())?;
+    if add {
+        client.login_if_needed().await?;
+        client.add_word(&definition.word).await?;
+        client.save().await?;
+        info!("added {} to youdao wordbook", definition.word);
+    }
+    Ok(())
+}
+
+/// 与[`refresh_words`]相同，但失败或被取消时将已获取的单词合并到storage中，不删除本地的单词
+async fn refresh_youdao_words(
+    client: &mut YoudaoClient,
+    storage: &mut dyn WordStorage,
+) -> Result<MergeReport, String> {
+    client.login_if_needed().await?;
+    match client.get_words_partial().await {
+        Ok(words) => storage.replace(words),
+        Err(mut e) => {
+            if !e.fetched.is_empty() && storage.check_writable("merge fetched words").is_ok() {
+                let report = storage.merge(std::mem::take(&mut e.fetched))?;
+                info!("merged partially fetched words: {:?}", report);
+            }
+            Err(e.to_string())
+        }
+    }
+}
+
+/// list时单词的过滤条件
+#[derive(Debug, Default)]
+pub(crate) struct WordFilter<'a> {
+    /// 仅有该本地标签的单词，忽略大小写
+    pub(crate) tag: Option<&'a str>,
+    /// 仅first_seen不早于该时间的单词
+    pub(crate) added_since: Option<DateTime<Utc>>,
+}
+
+impl WordFilter<'_> {
+    fn matches(&self, w: &WordItem) -> bool {
+        self.tag.is_none_or(|tag| w.has_tag(tag))
+            && self
+                .added_since
+                .is_none_or(|since| w.first_seen() as i64 >= since.timestamp_millis())
+    }
+}
+
+/// list单词的选项
+#[derive(Debug)]
+pub(crate) struct ListOptions<'a> {
+    /// 仅时间区间`[start, end]`内的单词
+    pub(crate) start: Option<DateTime<Utc>>,
+    pub(crate) end: Option<DateTime<Utc>>,
+    /// 排序后前后过滤的数量，见[`YoudaoApp::select_words`]
+    pub(crate) offset: isize,
+    pub(crate) filter: WordFilter<'a>,
+    pub(crate) template: &'a WordTemplate,
+    pub(crate) chapter_by: ChapterBy,
+}
+
+impl<'a> ListOptions<'a> {
+    /// 不过滤单词也不分章节的选项
+    pub(crate) fn new(offset: isize, template: &'a WordTemplate) -> Self {
+        Self {
+            start: None,
+            end: None,
+            offset,
+            filter: WordFilter::default(),
+            template,
+            chapter_by: ChapterBy::None,
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub(crate) struct YoudaoApp {
+    word_items: Box<dyn WordStorage>,
+    context: Arc<HttpContext>,
+    client: YoudaoClient,
+    pub(crate) output: io::BufWriter<Box<dyn Write>>,
+    /// 从web上加载时refresh的结果
+    pub(crate) refresh_report: Option<RefreshReport>,
+}
+
+impl std::ops::Drop for YoudaoApp {
+    /// 如果单词在修改后未通过[`YoudaoApp::save`]保存，则在drop时保存
+    fn drop(&mut self) {
+        match self.word_items.flush() {
+            Ok(true) => warn!("youdao words were not saved explicitly, saved late in drop"),
+            Ok(false) => {}
+            Err(e) => error!("save youdao words failed in drop: {}", e),
+        }
+    }
+}
+
+impl YoudaoApp {
+    /// 从file中构造
+    pub async fn from_file(config: AppConfig, context: Arc<HttpContext>) -> Result<Self, String> {
+        let word_items = open_word_storage(&config, false, context.is_read_only())
+            .await
+            .map_err(|e| format!("youdao load json failed. {}", e))?;
+        let client = YoudaoClient::with_context(config, context.clone())
+            .map_err(|e| format!("youdao client new failed. {}", e))?;
+        Ok(Self {
+            word_items,
+            context,
+            client,
+            output: io::BufWriter::new(Box::new(io::stdout())),
+            refresh_report: None,
+        })
+    }
+
+    /// 从youdao web上获取words构造
+    ///
+    /// # Errors
+    ///
+    /// 如果refresh失败或被取消。此时已获取的单词合并到本地单词本，与cookies一起保存
+    pub async fn from_web(config: AppConfig, context: Arc<HttpContext>) -> Result<Self, String> {
+        let mut word_items = open_word_storage(&config, true, context.is_read_only())
+            .await
+            .map_err(|e| format!("youdao open dictionary error: {}", e))?;
+        let mut client = YoudaoClient::with_context(config, context.clone())
+            .map_err(|e| format!("new youdaoclient error: {}", e))?;
+        let started = Instant::now();
+        context.emit(Event::RefreshStarted {
+            service: "youdao".to_string(),
+        });
+        let report = match refresh_youdao_words(&mut client, word_items.as_mut()).await {
+            Ok(report) => {
+                debug!("refreshed youdao words: {:?}", report);
+                RefreshReport::new("youdao", report, started, word_items.get_path())
+            }
+            Err(e) => {
+                let e = format!("youdao refresh words error: {}", e);
+                context.emit(Event::error(&e));
+                if let Err(e) = word_items.flush() {
+                    warn!("save partially fetched words error: {}", e);
+                }
+                if let Err(e) = client.save().await {
+                    warn!("save youdao cookies error: {}", e);
+                }
+                return Err(e);
+            }
+        };
+        Ok(Self {
+            word_items,
+            context,
+            client,
+            output: io::BufWriter::new(Box::new(io::stdout())),
+            refresh_report: Some(report),
+        })
+    }
+
+    /// 使用output代替stdout
+    pub fn with_output(mut self, output: Box<dyn Write>) -> Self {
+        self.output = io::BufWriter::new(output);
+        self
+    }
+
+    /// 查询单词。可以通过date和排序后前后过滤数量
+    ///
+    /// 通过时间区间`[start, end]`过虑单词并以降序排列
+    ///
+    /// 仅输出匹配filter的单词
+    pub fn list(&mut self, options: &ListOptions<'_>) {
+        let words = self
+            .word_items
+            .words()
+            .iter()
+            .filter(|w| options.filter.matches(w))
+            .cloned()
+            .map(Ok);
+        if let Err(e) = Self::write_words(&mut self.output, words, options) {
+            error!("list words error: {}", e);
+        }
+    }
+
+    /// 与[`YoudaoApp::list`]相同，但从json文件中逐个读取单词输出到output，不会加载全部单词到内存中
+    pub fn list_file(
+        path: &str,
+        output: Box<dyn Write>,
+        options: &ListOptions<'_>,
+    ) -> Result<(), String> {
+        let words = WordStore::iter_from_file(path)?.filter(|w| match w {
+            Ok(w) => options.filter.matches(w),
+            Err(_) => true,
+        });
+        let mut output = io::BufWriter::new(output);
+        Self::write_words(&mut output, words, options)
+    }
+
+    /// 修改本地所有word单词的标签与笔记并输出修改后的结果。note为空字符串时删除笔记
+    pub fn tag(
+        &mut self,
+        word: &str,
+        add: &[String],
+        remove: &[String],
+        note: Option<&str>,
+    ) -> Result<(), String> {
+        let mut items = self
+            .word_items
+            .words()
+            .iter()
+            .filter(|w| w.word.eq_ignore_ascii_case(word))
+            .cloned()
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            return Err(format!("not found word: {}", word));
+        }
+        for item in &mut items {
+            let mut local = item.local.take().unwrap_or_default();
+            for tag in add.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+                if !local.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    local.tags.push(tag.to_string());
+                }
+            }
+            local
+                .tags
+                .retain(|t| !remove.iter().any(|r| r.trim().eq_ignore_ascii_case(t)));
+            if let Some(note) = note {
+                local.note = Some(note.trim().to_string()).filter(|n| !n.is_empty());
+            }
+            item.local = Some(local);
+        }
+        let report = self.word_items.merge(items.clone())?;
+        debug!("tagged word {}: {:?}", word, report);
+        for (item, local) in items
+            .iter()
+            .filter_map(|w| w.local.as_ref().map(|l| (w, l)))
+        {
+            writeln!(
+                self.output,
+                "{}\t{}\t{}",
+                item.word,
+                local.tags.join(","),
+                local.note.as_deref().unwrap_or_default()
+            )
+            .map_err(|e| format!("writeln error: {}", e))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_words(
+        output: &mut impl Write,
+        words: impl Iterator<Item = Result<WordItem, String>>,
+        options: &ListOptions<'_>,
+    ) -> Result<(), String> {
+        let mut error = None;
+        let words = words.filter_map(|w| w.map_err(|e| error = Some(e)).ok());
+        let words = Self::select_words(words, options.start, options.end, options.offset);
+        if let Some(e) = error {
+            return Err(e);
+        }
+        let template = options.template;
+        if options.chapter_by != ChapterBy::None {
+            let chapters = format_chapters(&words, options.chapter_by, template);
+            return write!(output, "{}", chapters)
+                .map_err(|e| format!("write chapters error: {}", e));
+        }
+        for item in words {
+            if let Err(e) = writeln!(output, "{}", template.render(&item)) {
+                error!("writeln error: {}, worditem: {:?}", e, item);
+            }
+        }
+        Ok(())
+    }
+
+    /// 从`[start, end]`内的单词中不重复地随机抽取n个
+    fn sample_words(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        n: usize,
+        seed: Option<u64>,
+    ) -> Result<Vec<WordItem>, String> {
+        let words = Self::select_words(self.word_items.words().iter().cloned(), start, end, 0);
+        let mut rng = match seed {
+            Some(seed) => review::Rng::new(seed),
+            None => review::Rng::from_entropy()?,
+        };
+        Ok(review::sample(words, n, &mut rng))
+    }
+
+    /// 输出随机抽取的n个单词，detail为[`export::detail_template`]，None时仅输出单词
+    pub fn sample(
+        &mut self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        n: usize,
+        seed: Option<u64>,
+        detail: Option<&str>,
+    ) -> Result<(), String> {
+        let template = match detail {
+            Some(detail) => WordTemplate::parse(detail)?,
+            None => WordTemplate::default(),
+        };
+        for item in self.sample_words(start, end, n, seed)? {
+            writeln!(self.output, "{}", template.render(&item))
+                .map_err(|e| format!("writeln error: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// 在终端中测验随机抽取的n个单词，最后输出得分
+    pub async fn quiz(
+        &mut self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        n: usize,
+        seed: Option<u64>,
+        prompt: &mut UserPrompt<'_>,
+    ) -> Result<(), String> {
+        let words = self.sample_words(start, end, n, seed)?;
+        let score = review::quiz(&words, prompt).await?;
+        writeln!(self.output, "{}", score).map_err(|e| format!("writeln error: {}", e))
+    }
+
+    /// 输出单词本的id、名称与单词数量。remote时从youdao获取，否则从本地单词统计
+    pub async fn list_books(&mut self, remote: bool, format: OutputFormat) -> Result<(), String> {
+        let books = if remote {
+            self.login_if_needed().await?;
+            self.client.get_books().await?
+        } else {
+            let mut counts = BTreeMap::<(&str, &str), usize>::new();
+            for w in self.word_items.words() {
+                *counts.entry((&w.book_id, &w.book_name)).or_default() += 1;
+            }
+            counts
+                .into_iter()
+                .map(|((book_id, book_name), word_count)| Book {
+                    book_id: book_id.to_string(),
+                    book_name: book_name.to_string(),
+                    word_count,
+                })
+                .collect()
+        };
+        let out = match format {
+            OutputFormat::Json => {
+                serde_json::to_string_pretty(&books).map_err(|e| msg!("error.serialize", e))?
+            }
+            OutputFormat::Text => {
+                let mut table = Table::new(&["ID", "NAME", "WORDS"]);
+                for b in &books {
+                    table.add_row(vec![
+                        b.book_id.clone(),
+                        b.book_name.clone(),
+                        b.word_count.to_string(),
+                    ]);
+                }
+                table.render(table::stdout_width())
+            }
+        };
+        writeln!(self.output, "{}", out).map_err(|e| e.to_string())
+    }
+
+    /// 将`[start, end]`内单词本from中有标签tag的单词在youdao上移动到单词本to中。
+    ///
+    /// 仅移动成功的单词在本地更新为新的单词本，失败的保持不变，可以再次执行继续移动
+    pub async fn move_book(
+        &mut self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        tag: Option<&str>,
+        from: &str,
+        to: &str,
+    ) -> Result<BatchReport, String> {
+        self.login_if_needed().await?;
+        let book = self
+            .client
+            .get_books()
+            .await?
+            .into_iter()
+            .find(|b| b.book_name == to)
+            .ok_or_else(|| msg!("book.not_found", to))?;
+        let words = self
+            .word_items
+            .words()
+            .iter()
+            .filter(|w| w.book_name == from && tag.is_none_or(|tag| w.has_tag(tag)))
+            .cloned();
+        let words = Self::select_words(words, start, end, 0);
+        if words.is_empty() {
+            return Err(msg!("book.no_words", from));
+        }
+        let item_ids = words.iter().map(|w| w.item_id.clone()).collect::<Vec<_>>();
+        let report = self
+            .client
+            .move_words(&item_ids, &book.book_id, |done, total| {
+                debug!("moved {}/{} words to {}", done, total, book.book_name)
+            })
+            .await?;
+        let moved = words
+            .into_iter()
+            .filter(|w| report.succeeded.contains(&w.item_id))
+            .map(|mut w| {
+                w.book_id = book.book_id.clone();
+                w.book_name = book.book_name.clone();
+                w
+            })
+            .collect::<Vec<_>>();
+        let merged = self.word_items.merge(moved)?;
+        debug!("merged moved words: {:?}", merged);
+        Ok(report)
+    }
+
+    /// 逐个添加words到youdao单词本，需要时先登录。添加的单词在下次refresh时进入本地单词本
+    pub async fn add_words(&mut self, words: &[String]) -> Result<BatchReport, String> {
+        self.login_if_needed().await?;
+        self.client
+            .add_words(words, |done, total| {
+                debug!("added {}/{} words to youdao", done, total)
+            })
+            .await
+    }
+
+    /// 在未登录时登录youdao
+    async fn login_if_needed(&mut self) -> Result<(), String> {
+        self.client.login_if_needed().await
+    }
+
+    /// 导出单词到csv文件path
+    pub fn export_csv(&self, path: &str) -> Result<(), String> {
+        let file = fs::File::create(path)
+            .map_err(|e| format!("create file error: {}, path: {}", e, path))?;
+        store::write_csv(self.word_items.words(), io::BufWriter::new(file))?;
+        info!(
+            "exported {} words to csv: {}",
+            self.word_items.words().len(),
+            path
+        );
+        Ok(())
+    }
+
+    /// 本地单词本book中的所有单词
+    ///
+    /// # Errors
+    ///
+    /// 如果book中没有任何单词，如名称错误
+    pub fn book_words(&self, book: &str) -> Result<Vec<String>, String> {
+        let words = self
+            .word_items
+            .words()
+            .iter()
+            .filter(|w| w.book_name == book)
+            .map(|w| w.word.clone())
+            .collect::<Vec<_>>();
+        if words.is_empty() {
+            return Err(format!("not found any word in youdao book: {}", book));
+        }
+        Ok(words)
+    }
+
+    /// 导出单词到anki tsv文件path
+    pub fn export_anki(&self, path: &str, deck: Option<&str>) -> Result<(), String> {
+        let contents = format_anki_tsv(self.word_items.words(), deck);
+        fs::write(path, contents)
+            .map_err(|e| format!("write file error: {}, path: {}", e, path))?;
+        info!(
+            "exported {} words to anki tsv: {}",
+            self.word_items.words().len(),
+            path
+        );
+        Ok(())
+    }
+
+    /// 通过AnkiConnect将所有单词添加到anki deck中
+    pub async fn push_anki(
+        &self,
+        config: &AnkiConfig,
+        deck: &str,
+    ) -> Result<AnkiAddReport, String> {
+        AnkiClient::with_context(config, self.context.clone())?
+            .add_words(deck, self.word_items.words())
+            .await
+    }
+
+    /// 从csv文件path中导入单词合并到本地
+    pub fn import_csv(&mut self, path: &str) -> Result<MergeReport, String> {
+        let file =
+            fs::File::open(path).map_err(|e| format!("open file error: {}, path: {}", e, path))?;
+        let items = WordStore::import_csv(io::BufReader::new(file))?;
+        self.word_items.merge(items)
+    }
+
+    /// 从文本文件path中导入本地不存在的单词，book_name为`imported:<filename>`。
+    /// dry_run时仅输出将要添加的单词。lemma时本地已有单词原形的变形也被忽略
+    pub fn import_txt(
+        &mut self,
+        path: &str,
+        dry_run: bool,
+        lemma: bool,
+    ) -> Result<MergeReport, String> {
+        let file =
+            fs::File::open(path).map_err(|e| format!("open file error: {}, path: {}", e, path))?;
+        let filename = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path);
+        let items = store::import_txt(
+            io::BufReader::new(file),
+            &format!("imported:{}", filename),
+            Utc::now().timestamp_millis() as usize,
+        )?;
+        let items = new_words(self.word_items.words(), items, lemma);
+        if !dry_run {
+            return self.word_items.merge(items);
+        }
+        for item in &items {
+            if item.trans.is_empty() {
+                writeln!(self.output, "{}", item.word)
+            } else {
+                writeln!(self.output, "{}\t{}", item.word, item.trans)
+            }
+            .map_err(|e| format!("write words error: {}", e))?;
+        }
+        Ok(MergeReport {
+            added: items.len(),
+            ..Default::default()
+        })
+    }
+
+    /// 通过youdao补全本地单词，见[`enrich_words`]
+    pub async fn enrich(
+        &mut self,
+        limit: Option<usize>,
+        concurrency: usize,
+        rate: u32,
+    ) -> Result<EnrichReport, String> {
+        enrich_words(
+            &self.client,
+            self.word_items.as_mut(),
+            limit,
+            concurrency,
+            rate,
+        )
+        .await
+    }
+
+    /// 将json文件path中的单词迁移到当前配置的sqlite存储中
+    pub async fn migrate_store(&mut self, path: &str) -> Result<MergeReport, String> {
+        store::migrate_json_words(path, self.word_items.as_mut()).await
+    }
+
+    /// 当从web刷新单词后保存到文件
+    pub async fn save(&mut self) -> Result<(), String> {
+        let words = self.word_items.flush().map(|_| ());
+        let cookies = self.client.save().await;
+        let result = words.and(cookies);
+        emit_result(&self.context, &result, Event::SaveSucceeded);
+        result
+    }
+
+    /// 通过时间区间`[start, end]`过滤单词并按modified_time降序排列后取出offset个元素。
+    ///
+    /// 如果offset>0取出最新的offset个，如果offset<0则从后取出最旧的offset个。
+    /// 如果offset==0则不会过滤任何元素。offset!=0时仅保留offset个元素在内存中
+    ///
+    /// # Examples
+    ///
+    /// ```rust, ignore
+    /// // modified_time: [1,2,3]
+    /// let offset = -2;
+    /// select_words(words, None, None, offset); // modified_time: [2,1]
+    /// ```
+    pub(crate) fn select_words(
+        words: impl Iterator<Item = WordItem>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        offset: isize,
+    ) -> Vec<WordItem> {
+        debug!("filter offset: {}", offset);
+        let words = words.filter(|w| {
+            let date = Utc.timestamp_millis_opt(w.modified_time as i64).unwrap();
+            match (start, end) {
+                (None, None) => true,
+                (Some(start), Some(end)) => date >= start && date <= end,
+                (Some(start), None) => date >= start,
+                (None, Some(end)) => date <= end,
+            }
+        });
+        let limit = offset.unsigned_abs();
+        let mut selected = if offset > 0 {
+            // 小顶堆保留最新的limit个
+            let mut heap = BinaryHeap::with_capacity(limit + 1);
+            for w in words {
+                heap.push(Reverse((w.modified_time, w)));
+                if heap.len() > limit {
+                    heap.pop();
+                }
+            }
+            heap.into_iter().map(|Reverse((_, w))| w).collect()
+        } else if offset < 0 {
+            // 大顶堆保留最旧的limit个
+            let mut heap = BinaryHeap::with_capacity(limit + 1);
+            for w in words {
+                heap.push((w.modified_time, w));
+                if heap.len() > limit {
+                    heap.pop();
+                }
+            }
+            heap.into_iter().map(|(_, w)| w).collect()
+        } else {
+            words.collect::<Vec<_>>()
+        };
+        selected.sort_unstable_by_key(|w| Reverse(w.modified_time));
+        selected
+    }
+
+    /// 解析`--since`与`--until`：相对now的时间如`7d`，或now所在时区的日期`%Y-%m-%d`的0点
+    pub(crate) fn parse_time_bound<Tz: TimeZone>(
+        s: &str,
+        now: DateTime<Tz>,
+    ) -> Result<DateTime<Utc>, String> {
+        if let Ok(duration) = parse_duration(s) {
+            return Ok((now - duration).with_timezone(&Utc));
+        }
+        let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map_err(|e| format!("invalid date or duration {}: {}", s, e))?;
+        now.timezone()
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .ok_or_else(|| format!("invalid local date: {}", s))
+    }
+
+    /// 解析since与until。since_last_refresh时使用path的修改时间作为since
+    pub(crate) fn parse_time_range(
+        since: Option<&str>,
+        until: Option<&str>,
+        since_last_refresh: bool,
+        path: &str,
+    ) -> Result<TimeRange, String> {
+        let now = Local::now();
+        let since = if since_last_refresh {
+            let modified = fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map_err(|e| format!("read modified time error: {}, path: {}", e, path))?;
+            Some(DateTime::<Utc>::from(modified))
+        } else {
+            since.map(|s| Self::parse_time_bound(s, now)).transpose()?
+        };
+        let until = until.map(|s| Self::parse_time_bound(s, now)).transpose()?;
+        Ok((since, until))
+    }
+}
+
+/// 过滤单词的时间区间`[since, until]`
+type TimeRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{
+        attach_timings, maimemo::MaimemoApp, save_exit_code, write_refresh_report, write_timings,
+    };
+    use chrono::Duration;
+    use dict::{client, config::Config, error, export};
+    use std::sync::Mutex;
+    use std::time::Instant;
+    use store::ReadOnlyStorage;
+    use tokio_util::sync::CancellationToken;
+
+    fn word_item(i: usize) -> WordItem {
+        WordItem {
+            item_id: i.to_string(),
+            book_id: "1".to_string(),
+            book_name: "GRE".to_string(),
+            word: format!("word{}", i),
+            trans: "n. 测试".to_string(),
+            phonetic: "[test]".to_string(),
+            modified_time: i,
+            local: None,
+        }
+    }
+
+    fn times(words: &[WordItem]) -> Vec<usize> {
+        words.iter().map(|w| w.modified_time).collect()
+    }
+
+    #[tokio::test]
+    async fn lookup_and_add() -> Result<(), String> {
+        let server = mock_server::MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let context = Arc::new(HttpContext::from_config(&config.http())?);
+        let mut client = YoudaoClient::with_context(config.youdao(), context)?;
+
+        let mut output = vec![];
+        lookup_word(
+            &mut client,
+            "look up",
+            false,
+            OutputFormat::Json,
+            &mut output,
+        )
+        .await?;
+        let definition = serde_json::from_slice::<serde_json::Value>(&output).unwrap();
+        assert_eq!(definition["word"], "look up");
+        assert_eq!(definition["phonetics"], serde_json::json!([]));
+        assert!(!client.has_logged());
+
+        // add时先登录
+        let mut output = vec![];
+        lookup_word(
+            &mut client,
+            "serendipity",
+            true,
+            OutputFormat::Text,
+            &mut output,
+        )
+        .await?;
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("serendipity\n"));
+        assert!(client.has_logged());
+        let requests = server.requests();
+        assert!(requests
+            .last()
+            .unwrap()
+            .path
+            .contains("/add?word=serendipity"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn move_book() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse, MockServer};
+        let server = MockServer::start(|req| {
+            if req.path.contains("/ajax/modify?itemId=2&") {
+                return MockResponse::json(200, &serde_json::json!({"code": 500, "msg": "error"}));
+            }
+            recorded_response(req)
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let context = Arc::new(HttpContext::from_config(&config.http())?);
+        let path = dir.path().join("words.json");
+        let words = (0..4)
+            .map(|i| WordItem {
+                book_id: "0".to_string(),
+                book_name: "无标签".to_string(),
+                ..word_item(i)
+            })
+            .chain(Some(word_item(4)))
+            .collect();
+        let stdout = SharedOutput::default();
+        let output = |app: &mut YoudaoApp| {
+            app.output.flush().unwrap();
+            String::from_utf8(std::mem::take(&mut *stdout.0.lock().unwrap())).unwrap()
+        };
+        let mut app = YoudaoApp {
+            word_items: Box::new(WordStore::new(path.to_str().unwrap(), words)),
+            client: YoudaoClient::with_context(config.youdao(), context.clone())?,
+            context,
+            output: io::BufWriter::new(Box::new(stdout.clone())),
+            refresh_report: None,
+        };
+
+        app.list_books(false, OutputFormat::Json).await?;
+        let books = serde_json::from_str::<Vec<Book>>(&output(&mut app)).unwrap();
+        assert_eq!(
+            books
+                .iter()
+                .map(|b| (b.book_name.as_str(), b.word_count))
+                .collect::<Vec<_>>(),
+            vec![("无标签", 4), ("GRE", 1)]
+        );
+        assert!(app
+            .move_book(None, None, None, "无标签", "TOEFL")
+            .await
+            .is_err());
+
+        // item 0被since过滤，item 2在youdao上移动失败
+        let start = Utc.timestamp_millis_opt(1).unwrap();
+        let report = app
+            .move_book(Some(start), None, None, "无标签", "GRE")
+            .await?;
+        assert_eq!(report.succeeded, vec!["3", "1"]);
+        assert_eq!(report.failed.len(), 1);
+        let books = app
+            .word_items
+            .words()
+            .iter()
+            .map(|w| (w.item_id.as_str(), w.book_name.as_str()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            books.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("0", "无标签"),
+                ("1", "GRE"),
+                ("2", "无标签"),
+                ("3", "GRE"),
+                ("4", "GRE")
+            ]
+        );
+
+        app.list_books(true, OutputFormat::Text).await?;
+        assert!(output(&mut app).contains("无标签"));
+        Ok(())
+    }
+
+    /// 记录所有事件
+    #[derive(Debug, Default)]
+    struct RecordedEvents(std::sync::Mutex<Vec<Event>>);
+
+    impl dict::events::EventSink for RecordedEvents {
+        fn emit(&self, event: &Event) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_events() -> Result<(), String> {
+        let server = mock_server::MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let events = Arc::new(RecordedEvents::default());
+        let context =
+            Arc::new(HttpContext::from_config(&config.http())?.with_events(events.clone()));
+
+        let mut app = YoudaoApp::from_web(config.youdao(), context.clone()).await?;
+        app.save().await?;
+        let words = app.word_items.words().len();
+        let mut app = MaimemoApp::new(config.maimemo(), context, false, None, io::sink()).await?;
+        app.save().await?;
+        let ids = app
+            .notepads
+            .get()
+            .iter()
+            .map(|n| n.get_notepad_id().to_string())
+            .collect::<Vec<_>>();
+
+        let mut expected = vec![
+            Event::RefreshStarted {
+                service: "youdao".to_string(),
+            },
+            Event::PageFetched {
+                offset: 0,
+                count: words,
+            },
+            Event::SaveSucceeded,
+            Event::RefreshStarted {
+                service: "maimemo".to_string(),
+            },
+        ];
+        expected.extend(ids.into_iter().map(|id| Event::NotepadFetched { id }));
+        expected.push(Event::SaveSucceeded);
+        assert_eq!(*events.0.lock().unwrap(), expected);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn cancel_refresh() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse, MockServer};
+        use std::sync::mpsc;
+        let cancel = CancellationToken::new();
+        let (requested_tx, mut requested) = tokio::sync::mpsc::unbounded_channel();
+        let (release, released) = mpsc::channel::<()>();
+        let released = Mutex::new(released);
+        // 1500个单词分为两页，第二页的请求阻塞到测试取消之后
+        let server = MockServer::start(move |req| {
+            let query = match req.path.split_once("/wordbook/webapi/words?") {
+                Some((_, query)) => query,
+                None => return recorded_response(req),
+            };
+            let items = match query {
+                // 单词数量
+                "limit=1&offset=0" => vec![],
+                "limit=1000&offset=0" => (0..1000)
+                    .map(
+                        |i| serde_json::json!({"itemId": i.to_string(), "word": format!("w{}", i)}),
+                    )
+                    .collect(),
+                _ => {
+                    requested_tx.send(()).unwrap();
+                    // 测试结束后release被drop
+                    let _ = released.lock().unwrap().recv();
+                    return MockResponse::text(500, "too late");
+                }
+            };
+            MockResponse::json(
+                200,
+                &serde_json::json!({"code": 0, "msg": "SUCCESS",
+                    "data": {"total": 1500, "itemList": items}}),
+            )
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let context = HttpContext::from_config(&config.http())?.with_cancel(cancel.clone());
+        let config = config.youdao();
+        let dictionary_path = config.get_dictionary_path().to_string();
+
+        let cancel_requested = async {
+            requested.recv().await.expect("second page requested");
+            cancel.cancel();
+        };
+        let (result, _) = tokio::join!(
+            YoudaoApp::from_web(config, Arc::new(context)),
+            cancel_requested
+        );
+        let e = result.err().expect("cancelled");
+        drop(release);
+        assert_eq!(
+            error::DictError::classify(&e),
+            Some(error::DictError::Cancelled)
+        );
+        // 第一页的单词与登录的cookies已保存
+        assert_eq!(WordStore::load(&dictionary_path).await?.get().len(), 1000);
+        assert!(dir.path().join("youdao-cookies.json").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refresh_report() -> Result<(), String> {
+        let server = mock_server::MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config_path = server.write_fixture_config(dir.path());
+        let mut config = Config::from_yaml_file(&config_path)?;
+        let context = Arc::new(HttpContext::from_config(&config.http())?);
+        let metrics = context.metrics();
+        let mut app = YoudaoApp::from_web(config.youdao(), context.clone()).await?;
+        app.save().await?;
+        let dictionary_path = app.word_items.get_path().to_string();
+
+        // 修改一个，删除一个并添加一个本地的单词
+        let mut store = WordStore::load(&dictionary_path).await?;
+        let words = store.get_mut();
+        words[0].trans = "changed".to_string();
+        words[1] = word_item(1);
+        store.flush()?;
+
+        let config = Config::from_yaml_file(&config_path)?.youdao();
+        let app = YoudaoApp::from_web(config, context).await?;
+        let report = app.refresh_report.clone().unwrap();
+        assert_eq!(report.service, "youdao");
+        assert_eq!(report.fetched, 3);
+        assert_eq!(
+            report.merge,
+            MergeReport {
+                added: 1,
+                updated: 1,
+                unchanged: 1,
+                removed: 1,
+            }
+        );
+        assert_eq!(report.path, dictionary_path);
+
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        write_refresh_report(&report, OutputFormat::Json, true, &mut stdout, &mut stderr)?;
+        let json = serde_json::from_slice::<serde_json::Value>(&stdout).unwrap();
+        assert_eq!(json["fetched"], 3);
+        assert_eq!(json["removed"], 1);
+        assert!(stderr.is_empty());
+
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        write_refresh_report(&report, OutputFormat::Text, false, &mut stdout, &mut stderr)?;
+        let text = String::from_utf8(stderr).unwrap();
+        assert!(
+            text.starts_with(
+                "refreshed youdao: 3 fetched, 1 new, 1 updated, 1 unchanged, 1 removed in "
+            ),
+            "{}",
+            text
+        );
+        assert!(stdout.is_empty());
+        let mut stderr = vec![];
+        write_refresh_report(&report, OutputFormat::Text, true, &mut stdout, &mut stderr)?;
+        assert!(stderr.is_empty());
+
+        // --timings
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        write_timings(
+            &metrics.lock().unwrap(),
+            false,
+            OutputFormat::Text,
+            &mut stdout,
+            &mut stderr,
+        )?;
+        let text = String::from_utf8(stderr).unwrap();
+        assert!(text.starts_with("REQUEST\tCOUNT"), "{}", text);
+        assert!(text.contains("\nget-words\t"), "{}", text);
+        let mut report = report;
+        assert!(attach_timings(
+            Some(&mut report),
+            &metrics,
+            true,
+            OutputFormat::Json
+        ));
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        write_refresh_report(&report, OutputFormat::Json, true, &mut stdout, &mut stderr)?;
+        let json = serde_json::from_slice::<serde_json::Value>(&stdout).unwrap();
+        assert!(json["timings"]["total"]["count"].as_u64().unwrap() > 1);
+        assert!(json["timings"]["total"]["bytes"].as_u64().unwrap() > 0);
+        // 已作为refresh结果输出
+        let mut stdout = vec![];
+        write_timings(
+            &metrics.lock().unwrap(),
+            true,
+            OutputFormat::Json,
+            &mut stdout,
+            &mut stderr,
+        )?;
+        assert!(stdout.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_only_local_store() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let is_read_only =
+            |e: String| assert!(e.contains(client::context::READ_ONLY_MODE), "{}", e);
+        let words = vec!["hard".to_string()];
+        let path = dir.path().join("words.json");
+        let mut app = temp_app(path.to_str().unwrap())?;
+        let storage = std::mem::replace(&mut app.word_items, Box::new(WordStore::new("", vec![])));
+        app.word_items = Box::new(ReadOnlyStorage::new(storage));
+        is_read_only(app.tag("word1", &words, &[], None).unwrap_err());
+        let csv = dir.path().join("words.csv");
+        fs::write(&csv, "word\nserendipity\n").map_err(|e| format!("{:?}", e))?;
+        is_read_only(app.import_csv(csv.to_str().unwrap()).unwrap_err());
+        let txt = dir.path().join("words.txt");
+        fs::write(&txt, "serendipity\n").map_err(|e| format!("{:?}", e))?;
+        let txt = txt.to_str().unwrap();
+        is_read_only(app.import_txt(txt, false, false).unwrap_err());
+        assert_eq!(app.import_txt(txt, true, false)?.added, 1);
+        is_read_only(app.enrich(None, 1, 0).await.unwrap_err());
+        is_read_only(app.migrate_store("words.json").await.unwrap_err());
+        assert_eq!(app.word_items.words().len(), 1);
+        Ok(())
+    }
+
+    fn temp_app(dictionary_path: &str) -> Result<YoudaoApp, String> {
+        temp_app_with(dictionary_path, vec![word_item(1)], Box::new(io::sink()))
+    }
+
+    fn temp_app_with(
+        dictionary_path: &str,
+        words: Vec<WordItem>,
+        output: Box<dyn Write>,
+    ) -> Result<YoudaoApp, String> {
+        let config = serde_yaml::from_str::<AppConfig>(&format!(
+            "username: a\npassword: b\ndictionary_path: {}\n",
+            dictionary_path
+        ))
+        .map_err(|e| format!("{:?}", e))?;
+        Ok(YoudaoApp {
+            word_items: Box::new(WordStore::new(dictionary_path, words)),
+            context: Arc::new(HttpContext::new()?),
+            client: YoudaoClient::new(config)?,
+            output: io::BufWriter::new(output),
+            refresh_report: None,
+        })
+    }
+
+    /// 可以在app外读取内容的stdout
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn sample_words() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("words.json");
+        let stdout = SharedOutput::default();
+        let words = (1..=5).map(word_item).collect();
+        let mut app = temp_app_with(path.to_str().unwrap(), words, Box::new(stdout.clone()))?;
+        let output = |app: &mut YoudaoApp| {
+            app.output.flush().unwrap();
+            String::from_utf8(std::mem::take(&mut *stdout.0.lock().unwrap())).unwrap()
+        };
+        app.sample(None, None, 2, Some(3), None)?;
+        let first = output(&mut app);
+        assert_eq!(first.lines().count(), 2);
+        app.sample(None, None, 2, Some(3), None)?;
+        assert_eq!(output(&mut app), first);
+
+        // 仅从时间范围内抽取
+        let start = Utc.timestamp_millis_opt(4).unwrap();
+        app.sample(Some(start), None, 10, None, Some(export::DETAIL_TEMPLATE))?;
+        let mut lines = output(&mut app)
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                "word4\t[test]\tn. 测试\t1970-01-01\t",
+                "word5\t[test]\tn. 测试\t1970-01-01\t"
+            ]
+        );
+
+        let mut answers = UserPrompt::new("\ny\n".as_bytes(), io::sink());
+        app.quiz(Some(start), None, 1, Some(1), &mut answers)
+            .await?;
+        assert_eq!(output(&mut app), "known 1/1 (100%)\n");
+        Ok(())
+    }
+
+    #[test]
+    fn list_only_outputs_words() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("words.json");
+        let stdout = SharedOutput::default();
+        let words = vec![2, 1, 3].into_iter().map(word_item).collect();
+        let mut app = temp_app_with(path.to_str().unwrap(), words, Box::new(stdout.clone()))?;
+        app.list(&ListOptions::new(0, &WordTemplate::default()));
+        app.output.flush().map_err(|e| format!("{:?}", e))?;
+        let out = String::from_utf8(std::mem::take(&mut *stdout.0.lock().unwrap())).unwrap();
+        assert_eq!(out, "word3\nword2\nword1\n");
+
+        let template = WordTemplate::parse("{word}\\t{trans_first_line}\\t{book}")?;
+        app.list(&ListOptions::new(1, &template));
+        app.output.flush().map_err(|e| format!("{:?}", e))?;
+        let out = String::from_utf8(std::mem::take(&mut *stdout.0.lock().unwrap())).unwrap();
+        assert_eq!(out, "word3\tn. 测试\tGRE\n");
+
+        app.list(&ListOptions {
+            chapter_by: ChapterBy::Book,
+            ..ListOptions::new(0, &WordTemplate::default())
+        });
+        app.output.flush().map_err(|e| format!("{:?}", e))?;
+        let out = String::from_utf8(std::mem::take(&mut *stdout.0.lock().unwrap())).unwrap();
+        assert_eq!(out, "#GRE\nword3\nword2\nword1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn tag_and_filter_words() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("words.json");
+        let path = path.to_str().unwrap();
+        let stdout = SharedOutput::default();
+        let words = vec![1, 2, 3].into_iter().map(word_item).collect();
+        let mut app = temp_app_with(path, words, Box::new(stdout.clone()))?;
+        app.tag(
+            "WORD1",
+            &["gre".to_string()],
+            &[],
+            Some("from Economist article"),
+        )?;
+        app.tag(
+            "word3",
+            &["GRE".to_string(), "toefl".to_string()],
+            &[],
+            None,
+        )?;
+        app.tag("word3", &["gre".to_string()], &["toefl".to_string()], None)?;
+        assert!(app.tag("missing", &["gre".to_string()], &[], None).is_err());
+        app.output.flush().map_err(|e| format!("{:?}", e))?;
+        let out = String::from_utf8(std::mem::take(&mut *stdout.0.lock().unwrap())).unwrap();
+        assert_eq!(
+            out,
+            "word1\tgre\tfrom Economist article\nword3\tGRE,toefl\t\nword3\tGRE\t\n"
+        );
+
+        app.list(&ListOptions {
+            filter: WordFilter {
+                tag: Some("gre"),
+                ..Default::default()
+            },
+            ..ListOptions::new(0, &WordTemplate::default())
+        });
+        app.output.flush().map_err(|e| format!("{:?}", e))?;
+        let out = String::from_utf8(std::mem::take(&mut *stdout.0.lock().unwrap())).unwrap();
+        assert_eq!(out, "word3\nword1\n");
+
+        // 从文件中读取时同样过滤
+        app.word_items.flush()?;
+        let tagged = WordStore::iter_from_file(path)?
+            .filter_map(Result::ok)
+            .filter(|w| w.has_tag("Gre"))
+            .map(|w| w.word)
+            .collect::<Vec<_>>();
+        assert_eq!(tagged, vec!["word1", "word3"]);
+
+        // 空笔记删除笔记
+        app.tag("word1", &[], &["gre".to_string()], Some(""))?;
+        let word1 = &app.word_items.words()[0];
+        assert_eq!(word1.local, Some(Default::default()));
+        Ok(())
+    }
+
+    #[test]
+    fn filter_added_since() {
+        let mut word = word_item(5);
+        let filter = WordFilter {
+            added_since: Some(Utc.timestamp_millis_opt(3).unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&word));
+        // 之后修改的单词仍按first_seen过滤
+        word.local_mut().first_seen = Some(2);
+        assert!(!filter.matches(&word));
+        assert!(WordFilter::default().matches(&word));
+    }
+
+    #[cfg(feature = "lemma")]
+    #[test]
+    fn new_words_by_lemma() {
+        let item = |word: &str| WordItem {
+            word: word.to_string(),
+            ..word_item(1)
+        };
+        let items = || {
+            ["Running", "ran", "children", "child", "单词"]
+                .map(item)
+                .to_vec()
+        };
+        let words = |items: Vec<WordItem>| items.into_iter().map(|w| w.word).collect::<Vec<_>>();
+        assert_eq!(
+            words(new_words(&[item("run")], items(), true)),
+            vec!["children", "单词"]
+        );
+        assert_eq!(words(new_words(&[item("run")], items(), false)).len(), 5);
+    }
+
+    #[test]
+    fn import_txt_skips_existing() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let txt = dir.path().join("notes.md");
+        fs::write(&txt, "- Word1: existing\n- new — 新\n- NEW\n- other\n")
+            .map_err(|e| format!("{:?}", e))?;
+        let txt = txt.to_str().unwrap();
+        let stdout = SharedOutput::default();
+        let path = dir.path().join("words.json");
+        let mut app = temp_app_with(
+            path.to_str().unwrap(),
+            vec![word_item(1)],
+            Box::new(stdout.clone()),
+        )?;
+
+        let report = app.import_txt(txt, true, false)?;
+        assert_eq!(report.added, 2);
+        app.output.flush().map_err(|e| format!("{:?}", e))?;
+        let out = String::from_utf8(stdout.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(out, "new\t新\nother\n");
+        assert_eq!(app.word_items.words().len(), 1);
+
+        let report = app.import_txt(txt, false, false)?;
+        assert_eq!(report.added, 2);
+        let words = app.word_items.words();
+        assert_eq!(words.len(), 3);
+        assert!(words[1..]
+            .iter()
+            .all(|w| w.book_name == "imported:notes.md"));
+        // 再次导入时全部已存在
+        assert_eq!(app.import_txt(txt, false, false)?.added, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn save_before_exit() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("words.json");
+        let mut app = temp_app(path.to_str().unwrap())?;
+        assert_eq!(save_exit_code(app.save().await), 0);
+        // 返回前已经写入文件
+        let words = WordStore::load(path.to_str().unwrap()).await?;
+        assert_eq!(words.get(), &vec![word_item(1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn save_late_in_drop() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("words.json");
+        drop(temp_app(path.to_str().unwrap())?);
+        let words = WordStore::load(path.to_str().unwrap()).await?;
+        assert_eq!(words.get(), &vec![word_item(1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_save_exit_code() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("missing").join("words.json");
+        let mut app = temp_app(path.to_str().unwrap())?;
+        assert!(app.save().await.is_err());
+        assert_eq!(save_exit_code(app.save().await), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn select_words_offset() {
+        let all = || vec![2, 1, 3].into_iter().map(word_item);
+        let selected = YoudaoApp::select_words(all(), None, None, 0);
+        assert_eq!(times(&selected), vec![3, 2, 1]);
+        let selected = YoudaoApp::select_words(all(), None, None, 2);
+        assert_eq!(times(&selected), vec![3, 2]);
+        let selected = YoudaoApp::select_words(all(), None, None, -2);
+        assert_eq!(times(&selected), vec![2, 1]);
+        // offset超过单词数量
+        let selected = YoudaoApp::select_words(all(), None, None, 10);
+        assert_eq!(times(&selected), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn select_words_date() {
+        let day = 24 * 60 * 60 * 1000;
+        let all = vec![day, 2 * day, 3 * day].into_iter().map(word_item);
+        let now = Utc::now();
+        let start = YoudaoApp::parse_time_bound("1970-01-02", now).ok();
+        let end = YoudaoApp::parse_time_bound("1970-01-03", now).ok();
+        let selected = YoudaoApp::select_words(all, start, end, 0);
+        assert_eq!(times(&selected), vec![2 * day, day]);
+    }
+
+    #[test]
+    fn relative_time_bound() {
+        let now = Local::now();
+        let since = YoudaoApp::parse_time_bound("7d", now).unwrap();
+        assert_eq!(now.with_timezone(&Utc) - since, Duration::days(7));
+        assert!(YoudaoApp::parse_time_bound("2024-13-01", now).is_err());
+        // 日期为now所在时区的0点
+        let east8 = chrono::FixedOffset::east_opt(8 * 60 * 60).unwrap();
+        let date = YoudaoApp::parse_time_bound("2024-05-01", now.with_timezone(&east8)).unwrap();
+        assert_eq!(date.to_rfc3339(), "2024-04-30T16:00:00+00:00");
+        assert!(YoudaoApp::parse_time_bound("7x", now).is_err());
+    }
+
+    /// 生成100k个单词的文件测试流式读取的耗时。`cargo test -- --ignored`运行
+    #[test]
+    #[ignore]
+    fn bench_list_file_100k() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("words.json");
+        let path = path.to_str().unwrap();
+        let count = 100_000;
+        dict::config::save_json(&(0..count).map(word_item).collect::<Vec<_>>(), path)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let now = Instant::now();
+        let words = WordStore::iter_from_file(path)?.filter_map(Result::ok);
+        let selected = YoudaoApp::select_words(words, None, None, 10);
+        println!("streamed {} words in {:?}", count, now.elapsed());
+        assert_eq!(selected.len(), 10);
+        assert_eq!(selected[0].modified_time, count - 1);
+
+        let now = Instant::now();
+        let mut output = vec![];
+        let words = WordStore::iter_from_file(path)?;
+        YoudaoApp::write_words(
+            &mut output,
+            words,
+            &ListOptions::new(0, &WordTemplate::default()),
+        )?;
+        println!("listed {} words in {:?}", count, now.elapsed());
+        assert_eq!(output.iter().filter(|b| **b == b'\n').count(), count);
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! maimemo notepad覆盖前的本地备份。
+//!
+//! 备份保存在`<data_dir>/backups/<notepad_id>/<timestamp>.txt`中，每个notepad仅保留最近的keep个
+use chrono::{DateTime, Local};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 备份目录名，相对于data_dir
+pub const BACKUP_DIR: &str = "backups";
+
+/// 每个notepad默认保留的备份数量
+pub const DEFAULT_KEEP: usize = 10;
+
+/// 备份文件名的时间戳格式，按文件名排序即按时间排序
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+const BACKUP_EXT: &str = "txt";
+
+#[derive(Debug, Clone)]
+pub struct NotepadBackups {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl NotepadBackups {
+    /// 在data_dir中保存备份。keep=0时不备份
+    pub fn new(data_dir: &str, keep: usize) -> Self {
+        Self {
+            dir: Path::new(data_dir).join(BACKUP_DIR),
+            keep,
+        }
+    }
+
+    /// 写入notepad_id的备份并删除超出keep的旧备份。返回备份的时间戳，keep=0时为None
+    pub fn backup(
+        &self,
+        notepad_id: &str,
+        contents: &str,
+        now: DateTime<Local>,
+    ) -> Result<Option<String>, String> {
+        if self.keep == 0 {
+            return Ok(None);
+        }
+        let dir = self.notepad_dir(notepad_id)?;
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("create backup dir error: {}, path: {:?}", e, dir))?;
+        // 同一毫秒内的多次备份添加递增的序号，避免覆盖。旧的备份被删除后也不重用序号
+        let base = now.format(TIMESTAMP_FORMAT).to_string();
+        let timestamp = match self
+            .list(notepad_id)?
+            .iter()
+            .map(|t| sort_key(t))
+            .filter(|(b, _)| *b == base)
+            .map(|(_, i)| i + 1)
+            .max()
+        {
+            Some(i) => format!("{}-{}", base, i),
+            None => base,
+        };
+        let path = dir.join(format!("{}.{}", timestamp, BACKUP_EXT));
+        fs::write(&path, contents)
+            .map_err(|e| format!("write backup error: {}, path: {:?}", e, path))?;
+        info!("backed up notepad {} to {:?}", notepad_id, path);
+        self.prune(notepad_id)?;
+        Ok(Some(timestamp))
+    }
+
+    /// notepad_id所有备份的时间戳，从旧到新
+    pub fn list(&self, notepad_id: &str) -> Result<Vec<String>, String> {
+        let dir = self.notepad_dir(notepad_id)?;
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut timestamps = fs::read_dir(&dir)
+            .map_err(|e| format!("read backup dir error: {}, path: {:?}", e, dir))?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != BACKUP_EXT {
+                    return None;
+                }
+                path.file_stem()?.to_str().map(str::to_string)
+            })
+            .collect::<Vec<_>>();
+        timestamps.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+        Ok(timestamps)
+    }
+
+    /// 读取notepad_id在timestamp的备份内容
+    pub fn read(&self, notepad_id: &str, timestamp: &str) -> Result<String, String> {
+        check_name(timestamp)?;
+        let path = self
+            .notepad_dir(notepad_id)?
+            .join(format!("{}.{}", timestamp, BACKUP_EXT));
+        fs::read_to_string(&path).map_err(|e| {
+            format!(
+                "read backup {} of notepad {} error: {}, path: {:?}",
+                timestamp, notepad_id, e, path
+            )
+        })
+    }
+
+    fn prune(&self, notepad_id: &str) -> Result<(), String> {
+        let timestamps = self.list(notepad_id)?;
+        let dir = self.notepad_dir(notepad_id)?;
+        for timestamp in &timestamps[..timestamps.len().saturating_sub(self.keep)] {
+            let path = dir.join(format!("{}.{}", timestamp, BACKUP_EXT));
+            fs::remove_file(&path)
+                .map_err(|e| format!("remove old backup error: {}, path: {:?}", e, path))?;
+            debug!("removed old backup: {:?}", path);
+        }
+        Ok(())
+    }
+
+    fn notepad_dir(&self, notepad_id: &str) -> Result<PathBuf, String> {
+        check_name(notepad_id)?;
+        Ok(self.dir.join(notepad_id))
+    }
+}
+
+/// 按时间戳及同一毫秒内的序号排序，避免`-10`排在`-2`前
+fn sort_key(timestamp: &str) -> (&str, usize) {
+    // 时间戳本身以毫秒结尾，不能解析为序号
+    match timestamp.rsplit_once('-') {
+        Some((base, i)) => match i.parse() {
+            Ok(i) => (base, i),
+            Err(_) => (timestamp, 0),
+        },
+        None => (timestamp, 0),
+    }
+}
+
+/// 避免notepad_id或timestamp中的路径访问备份目录外的文件
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("invalid backup name: {:?}", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn rotate_backups() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let backups = NotepadBackups::new(dir.path().to_str().unwrap(), 3);
+        let start = Local.with_ymd_and_hms(2026, 10, 16, 8, 30, 0).unwrap();
+        for i in 0..5 {
+            let now = start + chrono::Duration::seconds(i);
+            backups.backup("695835", &format!("words {}", i), now)?;
+        }
+        let timestamps = backups.list("695835")?;
+        assert_eq!(
+            timestamps,
+            vec![
+                "20261016-083002.000",
+                "20261016-083003.000",
+                "20261016-083004.000"
+            ]
+        );
+        assert_eq!(backups.read("695835", &timestamps[2])?, "words 4");
+        assert!(backups.list("1")?.is_empty());
+        assert!(backups.read("695835", "../695835").is_err());
+
+        // 相同时间的备份不会被覆盖
+        let same = backups.backup("2", "a", start)?.unwrap();
+        let next = backups.backup("2", "b", start)?.unwrap();
+        assert_eq!(next, format!("{}-1", same));
+        assert_eq!(backups.list("2")?, vec![same.clone(), next]);
+
+        // 序号按数字排序，prune时删除最旧的备份
+        for _ in 0..10 {
+            backups.backup("2", "c", start)?;
+        }
+        let last = backups.backup("2", "d", start)?.unwrap();
+        assert_eq!(last, format!("{}-12", same));
+        assert_eq!(
+            backups.list("2")?,
+            vec![
+                format!("{}-10", same),
+                format!("{}-11", same),
+                format!("{}-12", same)
+            ]
+        );
+        assert_eq!(backups.read("2", &last)?, "d");
+
+        let disabled = NotepadBackups::new(dir.path().to_str().unwrap(), 0);
+        assert_eq!(disabled.backup("1", "words", start)?, None);
+        assert!(!dir.path().join(BACKUP_DIR).join("1").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+//! 本地缓存文件的查询与清理：单词本、notepads与cookie文件
+use crate::config::{AppConfig, Config};
+use crate::store;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::time::SystemTime;
+
+/// youdao的服务名，与子命令名一致
+pub const SERVICE_YOUDAO: &str = "yd";
+
+/// maimemo的服务名，与子命令名一致
+pub const SERVICE_MAIMEMO: &str = "mm";
+
+/// eudic的服务名，与子命令名一致
+pub const SERVICE_EUDIC: &str = "eu";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+    Dictionary,
+    Cookies,
+}
+
+/// 一个缓存文件的状态。文件不存在时size等字段为None
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEntry {
+    pub service: &'static str,
+    pub kind: CacheKind,
+    pub path: String,
+    pub exists: bool,
+    pub size: Option<u64>,
+    pub items: Option<usize>,
+    pub modified: Option<String>,
+    pub age_secs: Option<u64>,
+}
+
+impl CacheEntry {
+    /// 读取path的文件状态
+    pub fn new(service: &'static str, kind: CacheKind, path: &str) -> Self {
+        let metadata = fs::metadata(path).ok();
+        let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+        let items = metadata.as_ref().and_then(|_| match kind {
+            CacheKind::Dictionary => store::store_info(path).ok().map(|info| info.items),
+            // cookie_store每行保存一个cookie
+            CacheKind::Cookies => fs::read_to_string(path)
+                .ok()
+                .map(|s| s.lines().filter(|l| !l.trim().is_empty()).count()),
+        });
+        Self {
+            service,
+            kind,
+            path: path.to_string(),
+            exists: metadata.is_some(),
+            size: metadata.as_ref().map(|m| m.len()),
+            items,
+            modified: modified.map(|t| DateTime::<Utc>::from(t).to_rfc3339()),
+            age_secs: modified
+                .and_then(|t| SystemTime::now().duration_since(t).ok())
+                .map(|d| d.as_secs()),
+        }
+    }
+}
+
+/// config中所有服务的缓存文件
+pub fn cache_entries(config: &Config) -> Vec<CacheEntry> {
+    let mut entries = vec![];
+    let services = [
+        (SERVICE_YOUDAO, config.youdao.as_ref()),
+        (SERVICE_MAIMEMO, config.maimemo.as_ref()),
+        (SERVICE_EUDIC, config.eudic.as_ref()),
+    ];
+    for (service, app) in services.iter() {
+        if let Some(app) = app {
+            entries.extend(app_entries(service, app));
+        }
+    }
+    entries
+}
+
+fn app_entries(service: &'static str, app: &AppConfig) -> Vec<CacheEntry> {
+    let mut entries = vec![CacheEntry::new(
+        service,
+        CacheKind::Dictionary,
+        app.get_dictionary_path(),
+    )];
+    if let Some(path) = app.get_cookie_path() {
+        entries.push(CacheEntry::new(service, CacheKind::Cookies, path));
+    }
+    entries
+}
+
+/// 通过service与类型过滤entries。service为None时选择所有服务，
+/// cookies与dictionaries都为false时选择所有类型
+pub fn select_entries(
+    entries: Vec<CacheEntry>,
+    service: Option<&str>,
+    cookies: bool,
+    dictionaries: bool,
+) -> Vec<CacheEntry> {
+    let all_kinds = !cookies && !dictionaries;
+    entries
+        .into_iter()
+        .filter(|e| service.is_none() || service == Some(e.service))
+        .filter(|e| {
+            all_kinds
+                || match e.kind {
+                    CacheKind::Cookies => cookies,
+                    CacheKind::Dictionary => dictionaries,
+                }
+        })
+        .collect()
+}
+
+/// 删除entries中存在的文件，返回被删除的路径
+pub fn clear_entries(entries: &[CacheEntry]) -> Result<Vec<String>, String> {
+    let mut removed = vec![];
+    for entry in entries.iter().filter(|e| e.exists) {
+        fs::remove_file(&entry.path)
+            .map_err(|e| format!("remove file error: {}, path: {}", e, entry.path))?;
+        info!("removed cache file: {}", entry.path);
+        removed.push(entry.path.clone());
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn temp_config(dir: &Path) -> Result<Config, String> {
+        let config_path = dir.join("dict-config.yml");
+        fs::write(
+            &config_path,
+            "data_dir: .
+youdao:
+  username: a
+  password: b
+  cookie_path: youdao-cookies.json
+  dictionary_path: youdao.json
+maimemo:
+  username: a
+  password: b
+  cookie_path: maimemo-cookies.json
+  dictionary_path: maimemo.json
+",
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        for name in &[
+            "youdao-cookies.json",
+            "maimemo-cookies.json",
+            "maimemo.json",
+        ] {
+            fs::write(dir.join(name), "{\"a\": 1}\n{\"b\": 2}\n")
+                .map_err(|e| format!("{:?}", e))?;
+        }
+        fs::copy(
+            "tests/fixtures/youdao-words-v1.json",
+            dir.join("youdao.json"),
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        Config::from_yaml_file(config_path.to_str().unwrap())
+    }
+
+    #[test]
+    fn status_entries() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config = temp_config(dir.path())?;
+        let entries = cache_entries(&config);
+        assert_eq!(entries.len(), 4);
+        let youdao = &entries[0];
+        assert_eq!(
+            (youdao.service, youdao.kind),
+            (SERVICE_YOUDAO, CacheKind::Dictionary)
+        );
+        assert!(youdao.exists);
+        assert_eq!(youdao.items, Some(3));
+        assert_eq!(entries[1].items, Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn clear_selected_entries() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config = temp_config(dir.path())?;
+
+        let selected = select_entries(cache_entries(&config), Some(SERVICE_MAIMEMO), true, false);
+        assert_eq!(clear_entries(&selected)?.len(), 1);
+        assert!(!dir.path().join("maimemo-cookies.json").exists());
+        assert!(dir.path().join("maimemo.json").exists());
+        assert!(dir.path().join("youdao-cookies.json").exists());
+
+        let selected = select_entries(cache_entries(&config), None, false, true);
+        assert_eq!(clear_entries(&selected)?.len(), 2);
+        assert!(!dir.path().join("maimemo.json").exists());
+        assert!(!dir.path().join("youdao.json").exists());
+        assert!(dir.path().join("youdao-cookies.json").exists());
+
+        // 已删除的文件被忽略
+        let selected = select_entries(cache_entries(&config), None, false, false);
+        assert_eq!(
+            clear_entries(&selected)?,
+            vec![dir
+                .path()
+                .join("youdao-cookies.json")
+                .to_str()
+                .unwrap()
+                .to_string()]
+        );
+        Ok(())
+    }
+}
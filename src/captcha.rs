@@ -0,0 +1,147 @@
+//! 通过外部命令识别maimemo验证码，如本地的OCR脚本或发送到手机的通知。
+//!
+//! 验证码图片被写入临时文件，文件路径作为命令的最后一个参数，命令的stdout去除空白后作为答案
+use crate::api::{BoxFuture, Captcha, CaptchaProvider};
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tempfile::TempPath;
+use tokio::process::Command;
+
+/// 未配置`captcha_command_timeout_secs`时等待命令的秒数
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// 错误信息中包含的stderr最大字节数
+const STDERR_SNIPPET_LEN: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct CommandCaptcha {
+    command: String,
+    timeout: Duration,
+}
+
+impl CommandCaptcha {
+    /// command通过`sh -c`执行，可以包含引号与管道等
+    ///
+    /// # Errors
+    ///
+    /// 如果command为空
+    pub fn new(command: &str, timeout: Duration) -> Result<Self, String> {
+        let command = command.trim();
+        if command.is_empty() {
+            return Err("captcha command is empty".to_string());
+        }
+        Ok(Self {
+            command: command.to_string(),
+            timeout,
+        })
+    }
+
+    async fn run(&self, path: &Path) -> Result<String, String> {
+        // 路径作为位置参数传入，避免被shell解释
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", self.command))
+            .arg("dict-captcha")
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // 超时后drop时结束进程
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("run captcha command {} error: {}", self.command, e))?;
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| {
+                format!(
+                    "captcha command timed out after {}s",
+                    self.timeout.as_secs_f64()
+                )
+            })?
+            .map_err(|e| format!("wait captcha command error: {}", e))?;
+        if !output.status.success() {
+            let stderr = &output.stderr[..output.stderr.len().min(STDERR_SNIPPET_LEN)];
+            return Err(format!(
+                "captcha command failed with {}. stderr: {}",
+                output.status,
+                String::from_utf8_lossy(stderr).trim()
+            ));
+        }
+        let answer = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if answer.is_empty() {
+            return Err("captcha command printed nothing".to_string());
+        }
+        Ok(answer)
+    }
+}
+
+impl CaptchaProvider for CommandCaptcha {
+    fn solve<'a>(&'a mut self, captcha: &'a Captcha) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let path = save_temp(captcha)?;
+            debug!("running captcha command {} with {:?}", self.command, path);
+            let answer = self.run(&path).await;
+            if let Err(e) = path.close() {
+                warn!("remove captcha file error: {}", e);
+            }
+            answer
+        })
+    }
+}
+
+/// 将验证码图片写入临时文件，文件名随机且仅当前用户可读，drop时删除
+pub fn save_temp(captcha: &Captcha) -> Result<TempPath, String> {
+    let mut file = tempfile::Builder::new()
+        .prefix("dict-captcha-")
+        .suffix(".png")
+        .tempfile()
+        .map_err(|e| format!("create captcha file error: {}", e))?;
+    file.write_all(&captcha.bytes)
+        .map_err(|e| format!("write captcha error: {}, path: {:?}", e, file.path()))?;
+    Ok(file.into_temp_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "tests/fixtures/captcha-command.sh";
+
+    fn captcha() -> Captcha {
+        Captcha {
+            bytes: include_bytes!("../tests/fixtures/maimemo-captcha.png").to_vec(),
+            content_type: Some("image/png".to_string()),
+        }
+    }
+
+    async fn solve(mode: &str, timeout_ms: u64) -> Result<String, String> {
+        let command = format!("sh {} {}", FIXTURE, mode);
+        let mut provider = CommandCaptcha::new(&command, Duration::from_millis(timeout_ms))?;
+        provider.solve(&captcha()).await
+    }
+
+    #[tokio::test]
+    async fn command_answers() -> Result<(), String> {
+        assert_eq!(solve("ok", 5000).await?, "1234");
+
+        let e = solve("fail", 5000).await.unwrap_err();
+        assert!(e.contains("exit status: 3"), "{}", e);
+        assert!(e.contains("no answer"), "{}", e);
+        let e = solve("empty", 5000).await.unwrap_err();
+        assert!(e.contains("printed nothing"), "{}", e);
+        let e = solve("sleep", 200).await.unwrap_err();
+        assert!(e.contains("timed out"), "{}", e);
+
+        // 通过shell执行，参数可以包含引号
+        let command = format!("sh '{}' \"ok\"", FIXTURE);
+        let mut quoted = CommandCaptcha::new(&command, Duration::from_secs(5))?;
+        assert_eq!(quoted.solve(&captcha()).await?, "1234");
+
+        assert!(CommandCaptcha::new(" ", Duration::from_secs(1)).is_err());
+        let mut missing = CommandCaptcha::new("dict-no-such-command", Duration::from_secs(1))?;
+        assert!(missing.solve(&captcha()).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use crate::client::context::HttpContext;
+use crate::client::youdao_client::WordItem;
+use crate::config::AnkiConfig;
+use crate::error::DictError;
+use crate::export::{anki_tags, escape_html};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::Arc;
+
+/// AnkiConnect api版本
+const ANKI_CONNECT_VERSION: u8 = 6;
+
+/// 离线检查、录制与统计中AnkiConnect请求的名称
+const REQ_NAME: &str = "anki";
+
+/// 本地AnkiConnect默认地址
+pub const DEFAULT_ANKI_CONNECT_URL: &str = "http://127.0.0.1:8765";
+
+#[derive(Debug, Serialize)]
+struct AnkiRequest<'a, P> {
+    action: &'a str,
+    version: u8,
+    params: P,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnkiResponse<T> {
+    result: Option<T>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct NotesParams<'a> {
+    notes: &'a [AnkiNote],
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AnkiNoteFields {
+    #[serde(rename = "Front")]
+    pub front: String,
+    #[serde(rename = "Back")]
+    pub back: String,
+}
+
+/// 一个Basic类型的anki note
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AnkiNote {
+    #[serde(rename = "deckName")]
+    pub deck_name: String,
+    #[serde(rename = "modelName")]
+    pub model_name: String,
+    pub fields: AnkiNoteFields,
+    pub tags: Vec<String>,
+}
+
+impl AnkiNote {
+    /// 用word构造一个Basic note。front为单词，back为音标与释义
+    pub fn from_word(deck: &str, word: &WordItem) -> Self {
+        let trans = escape_html(&word.trans.replace("\r\n", "\n")).replace('\n', "<br>");
+        let back = if word.phonetic.is_empty() {
+            trans
+        } else {
+            format!("{}<br>{}", escape_html(&word.phonetic), trans)
+        };
+        Self {
+            deck_name: deck.to_string(),
+            model_name: "Basic".to_string(),
+            fields: AnkiNoteFields {
+                front: escape_html(&word.word),
+                back,
+            },
+            tags: anki_tags(&word.book_name)
+                .split(' ')
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// 添加notes的结果
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AnkiAddReport {
+    pub added: usize,
+    pub duplicates: usize,
+}
+
+/// 通过AnkiConnect访问本地anki
+pub struct AnkiClient {
+    context: Arc<HttpContext>,
+    url: String,
+}
+
+impl AnkiClient {
+    pub fn new(config: &AnkiConfig) -> Result<Self, String> {
+        Self::with_context(config, Arc::new(HttpContext::new()?))
+    }
+
+    /// 使用共享的http context创建client
+    pub fn with_context(config: &AnkiConfig, context: Arc<HttpContext>) -> Result<Self, String> {
+        Ok(Self {
+            context,
+            url: config
+                .get_url()
+                .unwrap_or(DEFAULT_ANKI_CONNECT_URL)
+                .to_string(),
+        })
+    }
+
+    /// 获取所有deck名称
+    pub async fn deck_names(&self) -> Result<Vec<String>, String> {
+        self.invoke("deckNames", serde_json::json!({})).await
+    }
+
+    /// 将words添加到deck中，跳过anki认为重复的note
+    ///
+    /// # Errors
+    ///
+    /// 如果deck不存在或无法连接AnkiConnect
+    pub async fn add_words(&self, deck: &str, words: &[WordItem]) -> Result<AnkiAddReport, String> {
+        self.context.check_writable("anki add notes")?;
+        if !self.deck_names().await?.iter().any(|d| d == deck) {
+            return Err(format!("anki deck not found: {}", deck));
+        }
+        let notes = words
+            .iter()
+            .map(|w| AnkiNote::from_word(deck, w))
+            .collect::<Vec<_>>();
+        let can_add: Vec<bool> = self
+            .invoke("canAddNotes", NotesParams { notes: &notes })
+            .await?;
+        let notes = notes
+            .into_iter()
+            .zip(can_add)
+            .filter(|(note, can_add)| {
+                if !can_add {
+                    debug!("skipped duplicate anki note: {}", note.fields.front);
+                }
+                *can_add
+            })
+            .map(|(note, _)| note)
+            .collect::<Vec<_>>();
+        let mut report = AnkiAddReport {
+            added: 0,
+            duplicates: words.len() - notes.len(),
+        };
+        if notes.is_empty() {
+            return Ok(report);
+        }
+        let ids: Vec<Option<u64>> = self
+            .invoke("addNotes", NotesParams { notes: &notes })
+            .await?;
+        report.added = ids.iter().filter(|id| id.is_some()).count();
+        report.duplicates += ids.len() - report.added;
+        debug!("added anki notes: {:?}", report);
+        Ok(report)
+    }
+
+    /// 发送一个AnkiConnect action
+    async fn invoke<P: Serialize, T: DeserializeOwned>(
+        &self,
+        action: &str,
+        params: P,
+    ) -> Result<T, String> {
+        debug!("invoking anki connect action: {}", action);
+        let req = AnkiRequest {
+            action,
+            version: ANKI_CONNECT_VERSION,
+            params,
+        };
+        let resp = super::send_unconfigured(&self.context, REQ_NAME, Method::POST, &self.url, |b| {
+            b.json(&req)
+        })
+        .await
+        .map_err(|e| {
+            if DictError::classify(&e) == Some(DictError::Network) {
+                format!(
+                    "unable to connect to AnkiConnect at {}. Is anki running with AnkiConnect installed? {}",
+                    self.url, e
+                )
+            } else {
+                e
+            }
+        })?
+        .json::<AnkiResponse<T>>()
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        match resp {
+            AnkiResponse { error: Some(e), .. } => {
+                error!("anki connect action {} error: {}", action, e);
+                if e.contains("deck was not found") {
+                    Err(format!("anki deck not found: {}", e))
+                } else {
+                    Err(format!("anki connect error: {}", e))
+                }
+            }
+            AnkiResponse {
+                result: Some(result),
+                ..
+            } => Ok(result),
+            _ => Err(format!("anki connect returned empty result for {}", action)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::{MockResponse, MockServer};
+
+    fn word(word: &str) -> WordItem {
+        WordItem {
+            item_id: word.to_string(),
+            book_id: "1".to_string(),
+            book_name: "GRE 3000".to_string(),
+            word: word.to_string(),
+            trans: "n. 测试\nv. <测>".to_string(),
+            phonetic: "[test]".to_string(),
+            modified_time: 0,
+            local: None,
+        }
+    }
+
+    fn mocked_anki_connect() -> impl Fn(&mock_server::MockRequest) -> MockResponse {
+        |req| {
+            let body = req.body_json();
+            let result = match body["action"].as_str().unwrap() {
+                "deckNames" => serde_json::json!(["Default", "English"]),
+                // 单词dup已经存在
+                "canAddNotes" => serde_json::json!(body["params"]["notes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|n| n["fields"]["Front"] != "dup")
+                    .collect::<Vec<_>>()),
+                "addNotes" => serde_json::json!(body["params"]["notes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, _)| i + 1)
+                    .collect::<Vec<_>>()),
+                _ => {
+                    return MockResponse::json(
+                        200,
+                        &serde_json::json!({"result": null, "error": "unsupported action"}),
+                    )
+                }
+            };
+            MockResponse::json(200, &serde_json::json!({ "result": result, "error": null }))
+        }
+    }
+
+    #[test]
+    fn note_from_word() {
+        let note = AnkiNote::from_word("English", &word("test"));
+        assert_eq!(note.fields.front, "test");
+        assert_eq!(note.fields.back, "[test]<br>n. 测试<br>v. &lt;测&gt;");
+        assert_eq!(note.tags, vec!["GRE_3000", "youdao"]);
+        assert_eq!(note.model_name, "Basic");
+    }
+
+    #[tokio::test]
+    async fn add_words() -> Result<(), String> {
+        let server = MockServer::start(mocked_anki_connect()).await;
+        let client = AnkiClient::new(&AnkiConfig::with_url(&server.url("/")))?;
+        let report = client
+            .add_words("English", &[word("a"), word("dup"), word("b")])
+            .await?;
+        assert_eq!(
+            report,
+            AnkiAddReport {
+                added: 2,
+                duplicates: 1
+            }
+        );
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let added = requests[2].body_json();
+        assert_eq!(added["action"], "addNotes");
+        assert_eq!(added["version"], 6);
+        assert_eq!(added["params"]["notes"].as_array().unwrap().len(), 2);
+        assert_eq!(added["params"]["notes"][0]["deckName"], "English");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_words_all_duplicated() -> Result<(), String> {
+        let server = MockServer::start(mocked_anki_connect()).await;
+        let client = AnkiClient::new(&AnkiConfig::with_url(&server.url("/")))?;
+        let report = client.add_words("English", &[word("dup")]).await?;
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.added, 0);
+        // 没有可以添加的note时不会发送addNotes
+        assert_eq!(server.requests().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deck_not_found() -> Result<(), String> {
+        let server = MockServer::start(mocked_anki_connect()).await;
+        let client = AnkiClient::new(&AnkiConfig::with_url(&server.url("/")))?;
+        let e = client.add_words("Missing", &[word("a")]).await.unwrap_err();
+        assert!(e.contains("deck not found"), "{}", e);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn offline_blocks_requests() -> Result<(), String> {
+        let server = MockServer::start(mocked_anki_connect()).await;
+        let context = Arc::new(HttpContext::new()?.with_offline(true));
+        let client = AnkiClient::with_context(&AnkiConfig::with_url(&server.url("/")), context)?;
+        let e = client.add_words("English", &[word("a")]).await.unwrap_err();
+        assert_eq!(DictError::classify(&e), Some(DictError::Offline), "{}", e);
+        assert!(server.requests().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn connection_refused() -> Result<(), String> {
+        // 绑定后立即释放端口，保证没有server监听
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = AnkiClient::new(&AnkiConfig::with_url(&format!("http://{}", addr)))?;
+        let e = client.deck_names().await.unwrap_err();
+        assert!(e.contains("unable to connect to AnkiConnect"), "{}", e);
+        Ok(())
+    }
+}
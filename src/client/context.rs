@@ -0,0 +1,667 @@
+use crate::client::metrics::MetricsRegistry;
+use crate::client::record::Recorder;
+use crate::config::{HttpConfig, TlsConfig};
+use crate::error::{CANCELLED, NETWORK_ERROR};
+use crate::events::{Event, EventSink};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT},
+    Certificate, Client, ClientBuilder, Method, NoProxy, Proxy, Request, RequestBuilder, Response,
+};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// 未配置http.user_agent时所有请求使用的User-Agent。
+/// youdao会更快地拒绝reqwest等非浏览器的UA
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/86.0.4240.75 Safari/537.36";
+
+/// 离线模式下发送请求的错误
+pub const OFFLINE_MODE: &str =
+    "offline mode: network access is disabled by --offline or DICT_OFFLINE";
+
+/// 只读模式下修改数据的错误
+pub const READ_ONLY_MODE: &str =
+    "read-only mode: modifications are disabled by --read-only or read_only in config";
+
+/// 只读模式下阻止operation的错误，包含[`READ_ONLY_MODE`]
+pub fn read_only_error(operation: &str) -> String {
+    warn!("blocked {} in read-only mode", operation);
+    format!("{}. operation: {}", READ_ONLY_MODE, operation)
+}
+
+/// reqwest的错误。连接失败、超时或发送失败时添加[`NETWORK_ERROR`]标记，
+/// 由[`crate::error::DictError::classify`]识别为网络错误
+pub fn request_error(e: reqwest::Error) -> String {
+    if e.is_connect() || e.is_timeout() || e.is_request() {
+        format!("{}: {:?}", NETWORK_ERROR, e)
+    } else {
+        format!("{:?}", e)
+    }
+}
+
+/// 通过[`HttpContext::with_cancel`]取消后发送请求的错误。错误中包含[`CANCELLED`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(CANCELLED)
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl From<Cancelled> for String {
+    fn from(e: Cancelled) -> Self {
+        e.to_string()
+    }
+}
+
+/// 所有client共享的http上下文。
+///
+/// 包含一个共享连接池的[`Client`]与全局的headers、timeout设置，在main中创建一次后通过`Arc`
+/// 传递给各个client
+#[derive(Debug, Clone)]
+pub struct HttpContext {
+    client: Client,
+    /// client发送请求时添加的http配置中的headers与User-Agent
+    default_headers: HeaderMap,
+    /// 录制或回放响应，见[`Recorder`]
+    recorder: Option<Arc<Recorder>>,
+    /// 禁止所有网络请求，回放录制的响应除外
+    offline: bool,
+    /// 禁止修改远程与本地的数据，见[`HttpContext::check_writable`]
+    read_only: bool,
+    /// 输出机器可读的事件，见[`crate::events`]
+    events: Option<Arc<dyn EventSink>>,
+    /// 取消后不再发送请求，进行中的请求立即返回[`Cancelled`]
+    cancel: CancellationToken,
+    /// 所有clients请求的统计，见[`crate::client::metrics`]
+    metrics: Arc<Mutex<MetricsRegistry>>,
+}
+
+impl HttpContext {
+    /// 使用默认配置创建
+    pub fn new() -> Result<Self, String> {
+        Self::from_config(&HttpConfig::default())
+    }
+
+    /// 通过config创建。不会自动处理cookie与redirect，由各个client自行处理
+    pub fn from_config(config: &HttpConfig) -> Result<Self, String> {
+        let mut headers = HeaderMap::new();
+        if let Some(config_headers) = config.get_headers() {
+            for (name, val) in config_headers {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| format!("invalid header name {}: {}", name, e))?;
+                let val = HeaderValue::from_str(val)
+                    .map_err(|e| format!("invalid header value {}: {}", val, e))?;
+                headers.insert(name, val);
+            }
+        }
+        if let Some(user_agent) = Self::user_agent(config) {
+            let val = HeaderValue::from_str(user_agent)
+                .map_err(|e| format!("invalid user agent {}: {}", user_agent, e))?;
+            headers.insert(USER_AGENT, val);
+        }
+        let mut builder = Client::builder()
+            .cookie_store(false)
+            .redirect(reqwest::redirect::Policy::none())
+            .default_headers(headers.clone());
+        if let Some(secs) = config.get_timeout_secs() {
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+        // 未配置proxy时reqwest使用系统代理环境变量
+        if let Some(proxy) = Self::build_proxy(config)? {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(tls) = config.get_tls() {
+            builder = Self::apply_tls(builder, tls)?;
+        }
+        let client = builder.build().map_err(|e| format!("{:?}", e))?;
+        Ok(Self {
+            client,
+            default_headers: headers,
+            recorder: None,
+            offline: false,
+            read_only: false,
+            events: None,
+            cancel: CancellationToken::new(),
+            metrics: Arc::default(),
+        })
+    }
+
+    /// 全局的User-Agent。未配置时为[`DEFAULT_USER_AGENT`]，配置为空字符串时为None
+    pub fn user_agent(config: &HttpConfig) -> Option<&str> {
+        match config.get_user_agent() {
+            Some(user_agent) if user_agent.trim().is_empty() => None,
+            Some(user_agent) => Some(user_agent),
+            None => Some(DEFAULT_USER_AGENT),
+        }
+    }
+
+    fn build_proxy(config: &HttpConfig) -> Result<Option<Proxy>, String> {
+        let url = match config.get_proxy() {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        debug!("using http proxy: {}", url);
+        let proxy =
+            Proxy::all(url).map_err(|e| format!("invalid http.proxy setting {}: {}", url, e))?;
+        let no_proxy = config
+            .get_no_proxy()
+            .and_then(|hosts| NoProxy::from_string(&hosts.join(",")));
+        Ok(Some(proxy.no_proxy(no_proxy)))
+    }
+
+    fn apply_tls(mut builder: ClientBuilder, tls: &TlsConfig) -> Result<ClientBuilder, String> {
+        if let Some(path) = tls.get_extra_ca_path() {
+            let pem = std::fs::read(path)
+                .map_err(|e| format!("read http.tls.extra_ca_path {} error: {}", path, e))?;
+            let cert = Certificate::from_pem(&pem)
+                .map_err(|e| format!("invalid http.tls.extra_ca_path {}: {}", path, e))?;
+            debug!("added extra root certificate: {}", path);
+            builder = builder.add_root_certificate(cert);
+        }
+        if tls.danger_accept_invalid_certs() {
+            warn!(
+                "tls certificate verification is disabled by http.tls.danger_accept_invalid_certs"
+            );
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder)
+    }
+
+    /// 通过recorder录制或回放所有请求的响应
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
+
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_deref()
+    }
+
+    /// offline时所有通过[`crate::client::send_request`]发送的请求都返回[`OFFLINE_MODE`]错误
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// read_only时所有修改数据的操作都返回[`READ_ONLY_MODE`]错误，查询与refresh不受影响
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 在修改数据前检查，包括发送验证码等修改前的准备请求。operation为请求名称或操作的描述
+    ///
+    /// # Errors
+    ///
+    /// 如果是只读模式
+    pub fn check_writable(&self, operation: &str) -> Result<(), String> {
+        if self.read_only {
+            return Err(read_only_error(operation));
+        }
+        Ok(())
+    }
+
+    /// 将clients与apps中的事件输出到sink
+    pub fn with_events(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.events = Some(sink);
+        self
+    }
+
+    pub fn has_events(&self) -> bool {
+        self.events.is_some()
+    }
+
+    /// 通过token取消所有clients的请求，如在ctrl-c时
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 在两次请求之间检查是否已被取消
+    pub fn check_cancelled(&self) -> Result<(), Cancelled> {
+        if self.cancel.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 请求统计记录到metrics中，用于在创建context前获取共享的registry
+    pub fn with_metrics(mut self, metrics: Arc<Mutex<MetricsRegistry>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// 共享的请求统计，可以在请求进行中读取
+    pub fn metrics(&self) -> Arc<Mutex<MetricsRegistry>> {
+        self.metrics.clone()
+    }
+
+    pub fn record_request(&self, req_name: &str, elapsed: Duration, error: bool) {
+        self.metrics
+            .lock()
+            .unwrap()
+            .record_request(req_name, elapsed, error);
+    }
+
+    /// 发送请求，取消时不等待响应。已取消时不发送
+    pub async fn send(&self, req_builder: RequestBuilder) -> Result<Response, String> {
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => Err(Cancelled.into()),
+            resp = req_builder.send() => resp.map_err(request_error),
+        }
+    }
+
+    /// 未设置sink时不做任何操作
+    pub fn emit(&self, event: Event) {
+        if let Some(sink) = &self.events {
+            sink.emit(&event);
+        }
+    }
+
+    /// 检查是否可以发送req_name请求。回放时不访问网络，离线模式下仍然可以发送
+    ///
+    /// # Errors
+    ///
+    /// 如果是离线模式
+    pub fn check_online(&self, req_name: &str) -> Result<(), String> {
+        let replaying = self.recorder().is_some_and(Recorder::is_replay);
+        if self.offline && !replaying {
+            warn!("blocked request {} in offline mode", req_name);
+            return Err(format!("{}. request: {}", OFFLINE_MODE, req_name));
+        }
+        Ok(())
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// 创建一个请求。请求中设置的headers会覆盖全局的headers
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// 同client发送时，将req中没有的默认headers添加到req中
+    pub fn fill_default_headers(&self, req: &mut Request) {
+        for (name, val) in &self.default_headers {
+            req.headers_mut().entry(name).or_insert_with(|| val.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{
+        maimemo_client::MaimemoClient, save_cookie_store, youdao_client::YoudaoClient,
+    };
+    use crate::config::AppConfig;
+    use cookie_store::CookieStore;
+    use mock_server::{MockResponse, MockServer};
+
+    /// 保存已登录的cookies并生成请求mock server的config
+    fn app_config(
+        dir: &tempfile::TempDir,
+        name: &str,
+        login_url: &str,
+        cookies: &[&str],
+        requests: &[(&str, String)],
+    ) -> AppConfig {
+        let cookie_path = dir.path().join(format!("{}-cookies.json", name));
+        let mut cookie_store = CookieStore::default();
+        let url = reqwest::Url::parse(login_url).unwrap();
+        for cookie in cookies {
+            let cookie = cookie::Cookie::parse(format!("{}=1; Max-Age=86400", cookie)).unwrap();
+            cookie_store.insert_raw(&cookie, &url).unwrap();
+        }
+        save_cookie_store(cookie_path.to_str().unwrap(), &cookie_store, None).unwrap();
+        let mut yaml = format!(
+            "username: a\npassword: b\ncookie_path: {}\ndictionary_path: {}.json\nrequests:\n",
+            cookie_path.to_str().unwrap(),
+            name
+        );
+        for (req_name, url) in requests {
+            yaml.push_str(&format!(
+                "  {}:\n    url: {}\n    method: GET\n    headers:\n      accept: '*/*'\n",
+                req_name, url
+            ));
+        }
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    #[tokio::test]
+    async fn clients_share_context() -> Result<(), String> {
+        let server = MockServer::start(|req| {
+            if req.path.starts_with("/words") {
+                MockResponse::json(
+                    200,
+                    &serde_json::json!({"code": 0, "msg": "", "data": {"total": 3, "itemList": []}}),
+                )
+            } else {
+                MockResponse::text(200, "<html><div id=\"content\">word</div></html>")
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let context = Arc::new(HttpContext::from_config(
+            &HttpConfig::default().with_user_agent("dict-test"),
+        )?);
+
+        let config = app_config(
+            &dir,
+            "youdao",
+            "https://youdao.com/",
+            &["OUTFOX_SEARCH_USER_ID", "DICT_PERS"],
+            &[("get-words", server.url("/words"))],
+        );
+        let youdao = YoudaoClient::with_context(config, context.clone())?;
+        assert_eq!(youdao.get_words_total().await?, 3);
+
+        let config = app_config(
+            &dir,
+            "maimemo",
+            "https://www.maimemo.com/",
+            &["userToken"],
+            &[("notepad-detail", server.url("/notepad/"))],
+        );
+        let maimemo = MaimemoClient::with_context(config, context)?;
+        assert_eq!(maimemo.get_notepad_contents("1").await?, "word");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        for req in requests {
+            assert_eq!(req.header("user-agent"), Some("dict-test"));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn offline_blocks_requests() -> Result<(), String> {
+        let server = MockServer::start(|_| MockResponse::text(200, "word")).await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let context = Arc::new(HttpContext::new()?.with_offline(true));
+        let config = app_config(
+            &dir,
+            "youdao",
+            "https://youdao.com/",
+            &[],
+            &[("login", server.url("/login"))],
+        );
+        let mut youdao = YoudaoClient::with_context(config, context)?;
+        let e = youdao.login().await.unwrap_err().to_string();
+        assert!(e.starts_with(OFFLINE_MODE), "{}", e);
+        assert!(server.requests().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_only_blocks_modifications() -> Result<(), String> {
+        use crate::client::{
+            anki_client::AnkiClient,
+            eudic_client::{EudicClient, StudyList},
+            maimemo_client::Notepad,
+            maimemo_open_client::MaimemoOpenClient,
+        };
+        use crate::config::AnkiConfig;
+
+        let server = MockServer::start(|_| {
+            MockResponse::text(200, "<html><div id=\"content\">word</div></html>")
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let context = Arc::new(HttpContext::new()?.with_read_only(true));
+        let assert_read_only = |e: String| assert!(e.starts_with(READ_ONLY_MODE), "{}", e);
+
+        let config = app_config(
+            &dir,
+            "youdao",
+            "https://youdao.com/",
+            &["OUTFOX_SEARCH_USER_ID", "DICT_PERS"],
+            &[
+                ("add-word", server.url("/add")),
+                ("delete-word", server.url("/delete")),
+                ("update-word-book", server.url("/move")),
+            ],
+        );
+        let youdao = YoudaoClient::with_context(config, context.clone())?;
+        let words = ["a".to_string()];
+        assert_read_only(youdao.add_word("a").await.unwrap_err());
+        assert_read_only(youdao.delete_word("a").await.unwrap_err());
+        assert_read_only(youdao.update_word_book("1", "2").await.unwrap_err());
+        assert_read_only(youdao.add_words(&words, |_, _| {}).await.unwrap_err());
+        assert_read_only(youdao.delete_words(&words, |_, _| {}).await.unwrap_err());
+        assert_read_only(youdao.move_words(&words, "2", |_, _| {}).await.unwrap_err());
+
+        let config = app_config(
+            &dir,
+            "maimemo",
+            "https://www.maimemo.com/",
+            &["userToken"],
+            &[
+                ("notepad-detail", server.url("/notepad/")),
+                ("service-captcha", server.url("/captcha")),
+                ("notepad-save", server.url("/save")),
+            ],
+        );
+        let maimemo = MaimemoClient::with_context(config, context.clone())?;
+        let notepad = Notepad::new("new", "brief", "word".to_string());
+        assert_read_only(maimemo.refresh_captcha().await.unwrap_err().to_string());
+        assert_read_only(
+            maimemo
+                .save_notepad(notepad.clone(), "1234".to_string())
+                .await
+                .unwrap_err()
+                .to_string(),
+        );
+
+        let config = serde_yaml::from_str(&format!(
+            "dictionary_path: maimemo.json\napi_token: t\napi_url: {}",
+            server.url("/notepads")
+        ))
+        .unwrap();
+        let open = MaimemoOpenClient::with_context(config, context.clone())?;
+        assert_read_only(open.create_notepad(&notepad).await.unwrap_err());
+        assert_read_only(open.update_notepad(&notepad).await.unwrap_err());
+        assert_read_only(open.delete_notepad("1").await.unwrap_err());
+
+        let config = serde_yaml::from_str(&format!(
+            "dictionary_path: eudic.json\ntoken: t\napi_url: {}",
+            server.url("/studylist")
+        ))
+        .unwrap();
+        let eudic = EudicClient::with_context(config, context.clone())?;
+        let list = StudyList {
+            id: "0".to_string(),
+            language: "en".to_string(),
+            name: "default".to_string(),
+        };
+        assert_read_only(eudic.add_words(&list, &words).await.unwrap_err());
+        assert_read_only(eudic.delete_words(&list, &words).await.unwrap_err());
+
+        let anki = AnkiClient::with_context(&AnkiConfig::with_url(&server.url("/")), context)?;
+        assert_read_only(anki.add_words("Default", &[]).await.unwrap_err());
+
+        assert!(server.requests().is_empty());
+        // 查询不受影响
+        assert_eq!(maimemo.get_notepad_contents("1").await?, "word");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn default_user_agent() -> Result<(), String> {
+        let server = MockServer::start(|_| {
+            MockResponse::text(200, "<html><div id=\"content\">word</div></html>")
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let context = Arc::new(HttpContext::new()?);
+        let config = app_config(
+            &dir,
+            "maimemo",
+            "https://www.maimemo.com/",
+            &["userToken"],
+            &[("notepad-detail", server.url("/notepad/"))],
+        );
+        MaimemoClient::with_context(config, context.clone())?
+            .get_notepad_contents("1")
+            .await?;
+
+        // 服务的user_agent覆盖默认的
+        let config = app_config(
+            &dir,
+            "maimemo",
+            "https://www.maimemo.com/",
+            &["userToken"],
+            &[("notepad-detail", server.url("/notepad/"))],
+        )
+        .with_user_agent("dict-maimemo");
+        MaimemoClient::with_context(config, context)?
+            .get_notepad_contents("1")
+            .await?;
+
+        let agents = server
+            .requests()
+            .iter()
+            .map(|req| req.header("user-agent").map(str::to_string))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            agents,
+            vec![
+                Some(DEFAULT_USER_AGENT.to_string()),
+                Some("dict-maimemo".to_string())
+            ]
+        );
+
+        let context = HttpContext::from_config(&HttpConfig::default().with_user_agent(""))?;
+        context
+            .request(Method::GET, &server.url("/"))
+            .send()
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        assert_eq!(server.requests().pop().unwrap().header("user-agent"), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn connect_error_is_network() -> Result<(), String> {
+        // 绑定后关闭的端口拒绝连接
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.local_addr())
+            .map_err(|e| format!("{:?}", e))?;
+        let context = HttpContext::new()?;
+        let e = context
+            .send(context.request(Method::GET, &format!("http://{}/", addr)))
+            .await
+            .unwrap_err();
+        assert!(e.starts_with(NETWORK_ERROR), "{}", e);
+        assert_eq!(
+            crate::error::DictError::classify(&e),
+            Some(crate::error::DictError::Network)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancelled() -> Result<(), String> {
+        let cancel = CancellationToken::new();
+        let context = HttpContext::new()?.with_cancel(cancel.clone());
+        assert_eq!(context.check_cancelled(), Ok(()));
+        cancel.cancel();
+        assert_eq!(context.check_cancelled(), Err(Cancelled));
+        let e = context
+            .send(context.request(Method::GET, "http://127.0.0.1:9/"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            crate::error::DictError::classify(&e),
+            Some(crate::error::DictError::Cancelled)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_through_proxy() -> Result<(), String> {
+        let proxy = MockServer::start(|_| MockResponse::text(200, "proxied")).await;
+        let server = MockServer::start(|_| MockResponse::text(200, "direct")).await;
+        let context = HttpContext::from_config(
+            &HttpConfig::default().with_proxy(&proxy.url(""), &[&server.addr().ip().to_string()]),
+        )?;
+
+        let resp = context
+            .request(Method::GET, "http://dict.test/words?limit=1")
+            .send()
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        assert_eq!(resp.text().await.unwrap(), "proxied");
+        // 通过proxy时使用absolute-form
+        assert_eq!(proxy.requests()[0].path, "http://dict.test/words?limit=1");
+
+        let resp = context
+            .request(Method::GET, &server.url("/words"))
+            .send()
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        assert_eq!(resp.text().await.unwrap(), "direct");
+        assert_eq!(proxy.requests().len(), 1);
+        Ok(())
+    }
+
+    async fn get_tls(server: &MockServer, tls: TlsConfig) -> Result<String, String> {
+        let context = HttpContext::from_config(&HttpConfig::default().with_tls(tls))?;
+        let url = format!("https://localhost:{}/", server.addr().port());
+        let resp = context
+            .request(Method::GET, &url)
+            .send()
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        resp.text().await.map_err(|e| format!("{:?}", e))
+    }
+
+    #[tokio::test]
+    async fn tls_options() -> Result<(), String> {
+        let server = MockServer::start_tls(
+            include_bytes!("../../tests/fixtures/tls/cert.pem"),
+            include_bytes!("../../tests/fixtures/tls/key.pem"),
+            |_| MockResponse::text(200, "secure"),
+        )
+        .await;
+        // 默认不信任自签名证书
+        assert!(get_tls(&server, TlsConfig::default()).await.is_err());
+
+        let tls = TlsConfig::default().with_extra_ca_path("tests/fixtures/tls/cert.pem");
+        assert_eq!(get_tls(&server, tls).await?, "secure");
+
+        let tls = TlsConfig::default().with_danger_accept_invalid_certs(true);
+        assert_eq!(get_tls(&server, tls).await?, "secure");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_extra_ca() {
+        let tls = TlsConfig::default().with_extra_ca_path("tests/fixtures/not-found.pem");
+        let e = HttpContext::from_config(&HttpConfig::default().with_tls(tls)).unwrap_err();
+        assert!(e.contains("http.tls.extra_ca_path"), "{}", e);
+    }
+
+    #[test]
+    fn invalid_proxy() {
+        let config = HttpConfig::default().with_proxy("not a url", &[]);
+        let e = HttpContext::from_config(&config).unwrap_err();
+        assert!(e.contains("http.proxy"), "{}", e);
+    }
+}
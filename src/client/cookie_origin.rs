@@ -0,0 +1,131 @@
+//! 登录cookies的来源，便于排查youdao黑名单等问题。
+//!
+//! 记录保存在`<cookie_path>.origin.json`中：cookies由登录产生、从浏览器导入，或是在没有记录时
+//! 由之前的运行保留，以及产生的时间。之前的运行保留的cookies在`dict auth status`时才被记录
+use crate::config::save_json;
+use serde::{Deserialize, Serialize};
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieSource {
+    /// 通过client登录
+    Login,
+    /// 通过`dict auth import`从浏览器导出的cookies中导入
+    Import,
+    /// 没有记录的已保存的cookies，如旧版本保存的
+    Previous,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CookieOrigin {
+    pub source: CookieSource,
+    /// cookies产生的unix时间戳秒数。Previous为首次发现时cookie文件的修改时间
+    pub at: i64,
+    /// 导入cookies的浏览器，如firefox
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser: Option<String>,
+}
+
+impl CookieOrigin {
+    pub fn login(now: i64) -> Self {
+        Self {
+            source: CookieSource::Login,
+            at: now,
+            browser: None,
+        }
+    }
+
+    pub fn import(browser: Option<&str>, now: i64) -> Self {
+        Self {
+            source: CookieSource::Import,
+            at: now,
+            browser: browser.map(str::to_string),
+        }
+    }
+
+    /// cookie_path对应的记录文件
+    pub fn path(cookie_path: &str) -> String {
+        format!("{}.origin.json", cookie_path)
+    }
+
+    /// 从cookie_path对应的记录文件中加载。不存在或无法解析时为None
+    pub fn load(cookie_path: &str) -> Option<Self> {
+        let path = Self::path(cookie_path);
+        let contents = std::fs::read_to_string(&path).ok()?;
+        serde_json::from_str(&contents)
+            .map_err(|e| warn!("ignored invalid cookie origin: {}, path: {}", e, path))
+            .ok()
+    }
+
+    pub fn save(&self, cookie_path: &str) -> Result<(), String> {
+        let path = Self::path(cookie_path);
+        save_json(self, &path)
+            .map_err(|e| format!("save cookie origin error: {}, path: {}", e, path))
+    }
+
+    /// 保存失败时仅记录日志，不影响登录与导入
+    pub fn save_or_warn(&self, cookie_path: &str) {
+        if let Err(e) = self.save(cookie_path) {
+            warn!("{}", e);
+        }
+    }
+
+    /// 同[`CookieOrigin::load`]，但非空的cookie文件没有来源记录时记录为[`CookieSource::Previous`]，
+    /// 时间为文件的修改时间
+    pub fn load_or_note_previous(cookie_path: &str) -> Option<Self> {
+        if std::path::Path::new(&Self::path(cookie_path)).exists() {
+            return Self::load(cookie_path);
+        }
+        let modified = match std::fs::metadata(cookie_path) {
+            Ok(meta) if meta.len() > 0 => meta.modified().ok(),
+            _ => return None,
+        };
+        let at = modified
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or_else(|| chrono::Utc::now().timestamp(), |d| d.as_secs() as i64);
+        debug!(
+            "recording cookies of {} as carried over from a previous run",
+            cookie_path
+        );
+        let origin = Self {
+            source: CookieSource::Previous,
+            at,
+            browser: None,
+        };
+        origin.save_or_warn(cookie_path);
+        Some(origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_previous_cookies() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let cookie_path = dir.path().join("cookies.json");
+        let cookie_path = cookie_path.to_str().unwrap();
+
+        // 不存在或空的cookie文件没有来源
+        assert_eq!(CookieOrigin::load_or_note_previous(cookie_path), None);
+        std::fs::write(cookie_path, "").unwrap();
+        assert_eq!(CookieOrigin::load_or_note_previous(cookie_path), None);
+        assert_eq!(CookieOrigin::load(cookie_path), None);
+
+        std::fs::write(cookie_path, "[]").unwrap();
+        let origin = CookieOrigin::load_or_note_previous(cookie_path).unwrap();
+        assert_eq!(origin.source, CookieSource::Previous);
+        assert!((chrono::Utc::now().timestamp() - origin.at).abs() < 60);
+        assert_eq!(CookieOrigin::load(cookie_path), Some(origin));
+
+        // 已有的记录不会被覆盖
+        CookieOrigin::import(Some("firefox"), 1000).save(cookie_path)?;
+        assert_eq!(
+            CookieOrigin::load_or_note_previous(cookie_path),
+            Some(CookieOrigin::import(Some("firefox"), 1000))
+        );
+        Ok(())
+    }
+}
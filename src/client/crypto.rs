@@ -0,0 +1,78 @@
+//! cookie文件的加密。
+//!
+//! 格式：`MAGIC | salt(16) | nonce(12) | ciphertext`。key通过Argon2id从passphrase与salt中派生，
+//! 使用ChaCha20-Poly1305加密
+use argon2::Argon2;
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key, KeyInit, Nonce};
+
+/// 加密文件的magic header
+pub const MAGIC: &[u8] = b"DICTENC1";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// data是否为加密格式
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// 使用passphrase加密，每次加密使用随机的salt与nonce
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    getrandom::getrandom(&mut salt).map_err(|e| format!("generate salt error: {}", e))?;
+    getrandom::getrandom(&mut nonce).map_err(|e| format!("generate nonce error: {}", e))?;
+    let cipher = build_cipher(passphrase, &salt)?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|e| format!("encrypt error: {}", e))?;
+
+    let mut data = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// 解密[`encrypt`]的数据
+///
+/// # Errors
+///
+/// 如果data不是加密格式，或passphrase错误、数据被修改
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    if !is_encrypted(data) || data.len() < MAGIC.len() + SALT_LEN + NONCE_LEN {
+        return Err("not an encrypted cookie file".to_string());
+    }
+    let (salt, rest) = data[MAGIC.len()..].split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    build_cipher(passphrase, salt)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "wrong cookie passphrase or corrupted cookie file".to_string())
+}
+
+fn build_cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("derive key error: {}", e))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> Result<(), String> {
+        let data = encrypt(b"{\"a\": 1}", "secret")?;
+        assert!(is_encrypted(&data));
+        assert_eq!(decrypt(&data, "secret")?, b"{\"a\": 1}");
+        assert_eq!(
+            decrypt(&data, "wrong").unwrap_err(),
+            "wrong cookie passphrase or corrupted cookie file"
+        );
+        assert!(decrypt(b"{\"a\": 1}", "secret").is_err());
+        Ok(())
+    }
+}
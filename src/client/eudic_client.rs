@@ -0,0 +1,398 @@
+//! 欧路词典生词本open api，通过token授权。见<https://my.eudic.net/OpenAPI/doc_api_study>
+use crate::client::{context::HttpContext, youdao_client::WordItem, *};
+use crate::config::*;
+use cookie_store::CookieStore;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// 默认的生词本api地址
+pub const DEFAULT_API_URL: &str = "https://api.frdic.com/api/open/v1/studylist";
+
+/// 当前仅支持英语生词本
+const LANGUAGE: &str = "en";
+
+/// 分页获取单词时每页的数量
+const PAGE_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ResponseResult<T> {
+    data: T,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// 生词本
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StudyList {
+    pub id: String,
+    pub language: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct EudicWord {
+    word: String,
+    #[serde(default)]
+    exp: String,
+    add_time: Option<String>,
+}
+
+/// 添加与删除单词请求的json
+#[derive(Serialize, Debug)]
+pub struct WordsBody<'a> {
+    id: &'a str,
+    language: &'a str,
+    words: &'a [String],
+}
+
+impl<'a> WordsBody<'a> {
+    pub fn new(list: &'a StudyList, words: &'a [String]) -> Self {
+        Self {
+            id: &list.id,
+            language: &list.language,
+            words,
+        }
+    }
+}
+
+/// 未配置的请求使用默认的api，配置文件中的请求优先
+///
+/// # Errors
+///
+/// 如果未配置token
+pub fn with_default_requests(config: AppConfig) -> Result<AppConfig, String> {
+    let token = config
+        .get_token()
+        .filter(|t| !t.is_empty())
+        .ok_or("not found eudic token in config")?
+        .to_string();
+    let url = config
+        .get_api_url()
+        .unwrap_or(DEFAULT_API_URL)
+        .trim_end_matches('/')
+        .to_string();
+    let headers = [
+        ("authorization", token.as_str()),
+        ("accept", "application/json"),
+        ("content-type", "application/json"),
+    ]
+    .iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect::<HashMap<_, _>>();
+    let words_url = format!("{}/words", url);
+    Ok(config
+        .with_default_request(
+            "get-study-lists",
+            RequestConfig::new(&format!("{}/category", url), "GET", headers.clone()),
+        )
+        .with_default_request(
+            "get-words",
+            RequestConfig::new(&words_url, "GET", headers.clone()),
+        )
+        .with_default_request(
+            "add-words",
+            RequestConfig::new(&words_url, "POST", headers.clone()).with_ok_status(&[200, 201]),
+        )
+        .with_default_request(
+            "delete-words",
+            RequestConfig::new(&words_url, "DELETE", headers).with_ok_status(&[200, 204]),
+        ))
+}
+
+pub struct EudicClient {
+    context: Arc<HttpContext>,
+    config: AppConfig,
+    /// open api不使用cookie
+    cookie_store: CookieStore,
+}
+
+impl EudicClient {
+    pub fn new(config: AppConfig) -> Result<Self, String> {
+        Self::with_context(config, Arc::new(HttpContext::new()?))
+    }
+
+    /// 使用共享的http context创建client。未配置的请求使用默认的api，见[`with_default_requests`]
+    ///
+    /// # Errors
+    ///
+    /// 如果未配置token
+    pub fn with_context(config: AppConfig, context: Arc<HttpContext>) -> Result<Self, String> {
+        let config = with_default_requests(config)?;
+        Ok(Self {
+            context,
+            config,
+            cookie_store: CookieStore::default(),
+        })
+    }
+
+    /// 获取所有英语生词本
+    pub async fn get_study_lists(&self) -> Result<Vec<StudyList>, String> {
+        let resp = send_request_nobody(
+            &self.config,
+            &self.context,
+            &self.cookie_store,
+            "get-study-lists",
+            |url| format!("{}?language={}", url, LANGUAGE),
+        )
+        .await?;
+        let body = read_body(&self.config, "get-study-lists", resp).await?;
+        let result = serde_json::from_slice::<ResponseResult<Vec<StudyList>>>(&body)
+            .map_err(|e| format!("parse study lists error: {:?}", e))?;
+        debug!("found {} study lists", result.data.len());
+        Ok(result.data)
+    }
+
+    /// 获取config中study_list名称对应的生词本，未配置时使用第一个生词本
+    pub async fn find_study_list(&self) -> Result<StudyList, String> {
+        let lists = self.get_study_lists().await?;
+        match self.config.get_study_list() {
+            Some(name) => lists
+                .into_iter()
+                .find(|l| l.name == name)
+                .ok_or_else(|| format!("not found eudic study list: {}", name)),
+            None => lists
+                .into_iter()
+                .next()
+                .ok_or_else(|| "not found any eudic study list".to_string()),
+        }
+    }
+
+    /// 分页获取生词本中的所有单词
+    pub async fn get_words(&self, list: &StudyList) -> Result<Vec<WordItem>, String> {
+        let mut items = vec![];
+        for page in 0.. {
+            let resp = send_request_nobody(
+                &self.config,
+                &self.context,
+                &self.cookie_store,
+                "get-words",
+                |url| {
+                    format!(
+                        "{}/{}?language={}&page={}&page_size={}",
+                        url, list.id, list.language, page, PAGE_SIZE
+                    )
+                },
+            )
+            .await?;
+            let body = read_body(&self.config, "get-words", resp).await?;
+            let words = serde_json::from_slice::<ResponseResult<Vec<EudicWord>>>(&body)
+                .map_err(|e| format!("parse words error: {:?}, page: {}", e, page))?
+                .data;
+            trace!("found {} words in page {}", words.len(), page);
+            let last = words.len() < PAGE_SIZE;
+            items.extend(words.into_iter().map(|w| w.into_item(list)));
+            if last {
+                break;
+            }
+        }
+        debug!("found {} words in study list: {}", items.len(), list.name);
+        Ok(items)
+    }
+
+    /// 添加单词到生词本
+    pub async fn add_words(&self, list: &StudyList, words: &[String]) -> Result<(), String> {
+        self.send_words("add-words", list, words).await
+    }
+
+    /// 从生词本中删除单词
+    pub async fn delete_words(&self, list: &StudyList, words: &[String]) -> Result<(), String> {
+        self.send_words("delete-words", list, words).await
+    }
+
+    async fn send_words(
+        &self,
+        req_name: &str,
+        list: &StudyList,
+        words: &[String],
+    ) -> Result<(), String> {
+        self.context.check_writable(req_name)?;
+        if words.is_empty() {
+            debug!("skipped {} for empty words", req_name);
+            return Ok(());
+        }
+        let body = WordsBody::new(list, words);
+        send_request(
+            &self.config,
+            &self.context,
+            &self.cookie_store,
+            req_name,
+            |url| url.to_string(),
+            Some(&body),
+        )
+        .await?;
+        debug!(
+            "{} {} words in study list: {}",
+            req_name,
+            words.len(),
+            list.name
+        );
+        Ok(())
+    }
+}
+
+impl WordSource for EudicClient {
+    fn fetch_words(&mut self) -> BoxFuture<'_, Result<Vec<WordItem>, String>> {
+        Box::pin(async move {
+            let list = self.find_study_list().await?;
+            self.get_words(&list).await
+        })
+    }
+}
+
+impl EudicWord {
+    fn into_item(self, list: &StudyList) -> WordItem {
+        let modified_time = self
+            .add_time
+            .as_deref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.timestamp_millis().max(0) as usize)
+            .unwrap_or_default();
+        WordItem {
+            item_id: self.word.clone(),
+            book_id: list.id.clone(),
+            book_name: list.name.clone(),
+            word: self.word,
+            trans: self.exp,
+            phonetic: String::new(),
+            modified_time,
+            local: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::{MockRequest, MockResponse, MockServer};
+
+    fn eudic_config(url: &str, study_list: Option<&str>) -> AppConfig {
+        let mut yaml = format!(
+            "dictionary_path: eudic.json
+token: NIS test-token
+api_url: {}
+",
+            url
+        );
+        if let Some(name) = study_list {
+            yaml.push_str(&format!("study_list: {}\n", name));
+        }
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn client(server: &MockServer, study_list: Option<&str>) -> Result<EudicClient, String> {
+        EudicClient::new(eudic_config(&server.url("/studylist"), study_list))
+    }
+
+    fn mocked_eudic() -> impl Fn(&MockRequest) -> MockResponse {
+        |req| {
+            if req.header("authorization") != Some("NIS test-token") {
+                return MockResponse::new(401);
+            }
+            let path = req.path.as_str();
+            match (req.method.as_str(), path) {
+                ("GET", "/studylist/category?language=en") => MockResponse::json(
+                    200,
+                    &serde_json::json!({"data": [
+                        {"id": "0", "language": "en", "name": "我的生词本"},
+                        {"id": "1", "language": "en", "name": "GRE"},
+                    ], "message": ""}),
+                ),
+                ("GET", p) if p.starts_with("/studylist/words/1?") => {
+                    // 第一页满页，第二页剩余1个
+                    let count = if p.contains("page=0") { PAGE_SIZE } else { 1 };
+                    let words = (0..count)
+                        .map(|i| {
+                            serde_json::json!({
+                                "word": format!("{}-{}", if p.contains("page=0") { "a" } else { "b" }, i),
+                                "exp": "n. 测试",
+                                "add_time": "2021-03-08T05:25:21Z",
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    MockResponse::json(200, &serde_json::json!({ "data": words }))
+                }
+                ("POST", "/studylist/words") => {
+                    MockResponse::json(201, &serde_json::json!({"message": "单词添加成功"}))
+                }
+                ("DELETE", "/studylist/words") => MockResponse::new(204),
+                _ => MockResponse::new(404),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn study_lists() -> Result<(), String> {
+        let server = MockServer::start(mocked_eudic()).await;
+        let lists = client(&server, None)?.get_study_lists().await?;
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[1].name, "GRE");
+
+        assert_eq!(client(&server, None)?.find_study_list().await?.id, "0");
+        assert_eq!(
+            client(&server, Some("GRE"))?.find_study_list().await?.id,
+            "1"
+        );
+        let e = client(&server, Some("missing"))?
+            .find_study_list()
+            .await
+            .unwrap_err();
+        assert!(e.contains("missing"), "{}", e);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn paged_words() -> Result<(), String> {
+        let server = MockServer::start(mocked_eudic()).await;
+        let client = client(&server, Some("GRE"))?;
+        let list = client.find_study_list().await?;
+        let words = client.get_words(&list).await?;
+        assert_eq!(words.len(), PAGE_SIZE + 1);
+        let last = words.last().unwrap();
+        assert_eq!(last.word, "b-0");
+        assert_eq!(last.item_id, "b-0");
+        assert_eq!(last.book_name, "GRE");
+        assert_eq!(last.trans, "n. 测试");
+        assert_eq!(last.modified_time, 1615181121000);
+        // categories + 2 pages
+        assert_eq!(server.requests().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_and_delete_words() -> Result<(), String> {
+        let server = MockServer::start(mocked_eudic()).await;
+        let client = client(&server, None)?;
+        let list = client.find_study_list().await?;
+        let words = vec!["hello".to_string(), "world".to_string()];
+        client.add_words(&list, &words).await?;
+        client.delete_words(&list, &words[..1]).await?;
+        // 没有单词时不发送请求
+        client.add_words(&list, &[]).await?;
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(
+            requests[1].body_json(),
+            serde_json::json!({"id": "0", "language": "en", "words": ["hello", "world"]})
+        );
+        assert_eq!(requests[2].method, "DELETE");
+        assert_eq!(
+            requests[2].body_json()["words"],
+            serde_json::json!(["hello"])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unauthorized() -> Result<(), String> {
+        let server = MockServer::start(|_| MockResponse::new(401)).await;
+        let e = client(&server, None)?.get_study_lists().await.unwrap_err();
+        assert!(e.contains("401"), "{}", e);
+
+        let config = serde_yaml::from_str("dictionary_path: eudic.json").unwrap();
+        let e = EudicClient::new(config).err().unwrap();
+        assert!(e.contains("token"), "{}", e);
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+//! 通过配置的http请求与字段映射从任意json接口获取单词，见[`CustomSourceConfig`]
+use crate::client::{context::HttpContext, youdao_client::WordItem, *};
+use crate::config::*;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// 获取单词的请求名称
+pub const REQUEST_NAME: &str = "custom-words";
+
+/// 映射错误中json示例的最大字符数
+const MAX_SAMPLE_CHARS: usize = 200;
+
+pub struct GenericJsonClient {
+    name: String,
+    context: Arc<HttpContext>,
+    config: AppConfig,
+    book_name: String,
+    mapping: FieldMapping,
+}
+
+impl GenericJsonClient {
+    pub fn new(name: &str, source: &CustomSourceConfig) -> Result<Self, String> {
+        Ok(Self::with_context(
+            name,
+            source,
+            Arc::new(HttpContext::new()?),
+        ))
+    }
+
+    /// 使用共享的http context创建名称为name的来源的client
+    pub fn with_context(
+        name: &str,
+        source: &CustomSourceConfig,
+        context: Arc<HttpContext>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            context,
+            config: source.to_app_config(REQUEST_NAME),
+            book_name: source.get_book_name().unwrap_or(name).to_string(),
+            mapping: source.get_mapping().clone(),
+        }
+    }
+
+    pub fn context(&self) -> &HttpContext {
+        &self.context
+    }
+
+    /// 请求配置的url并按mapping读取所有单词。不使用cookie，授权通过请求配置的headers
+    pub async fn get_words(&self) -> Result<Vec<WordItem>, String> {
+        let resp = send_request_without_cookies(
+            &self.config,
+            &self.context,
+            REQUEST_NAME,
+            |url| url.to_string(),
+            None::<&str>,
+        )
+        .await?;
+        let resp = check_status(&self.config, REQUEST_NAME, resp).await?;
+        let body = read_body(&self.config, REQUEST_NAME, resp).await?;
+        let value = serde_json::from_slice::<Value>(&body).map_err(|e| {
+            format!(
+                "custom source {}: parse json error: {}. body: {}",
+                self.name,
+                e,
+                sample(&String::from_utf8_lossy(&body))
+            )
+        })?;
+        let items = map_words(&self.mapping, &value, &self.name, &self.book_name)
+            .map_err(|e| format!("custom source {}: {}", self.name, e))?;
+        debug!("found {} words in custom source {}", items.len(), self.name);
+        Ok(items)
+    }
+}
+
+impl WordSource for GenericJsonClient {
+    fn fetch_words(&mut self) -> BoxFuture<'_, Result<Vec<WordItem>, String>> {
+        Box::pin(self.get_words())
+    }
+}
+
+/// 按mapping将response json转换为单词，item_id为单词，book_id为来源名称。
+/// 重复的单词仅保留第一个
+///
+/// # Errors
+///
+/// 如果pointer不存在或值的类型不对，包含失败的pointer与json示例
+pub fn map_words(
+    mapping: &FieldMapping,
+    value: &Value,
+    source: &str,
+    book_name: &str,
+) -> Result<Vec<WordItem>, String> {
+    let items = value
+        .pointer(&mapping.items)
+        .ok_or_else(|| mapping_error("items", &mapping.items, "not found", value))?
+        .as_array()
+        .ok_or_else(|| mapping_error("items", &mapping.items, "not an array", value))?;
+    let mut words = Vec::with_capacity(items.len());
+    let mut item_ids = HashSet::new();
+    for item in items {
+        let word = field_str(item, "word", Some(&mapping.word))?
+            .filter(|w| !w.trim().is_empty())
+            .ok_or_else(|| mapping_error("word", &mapping.word, "empty", item))?;
+        if !item_ids.insert(word.clone()) {
+            debug!(
+                "skipped duplicate word {} in custom source {}",
+                word, source
+            );
+            continue;
+        }
+        words.push(WordItem {
+            item_id: word.clone(),
+            book_id: source.to_string(),
+            book_name: book_name.to_string(),
+            word,
+            trans: field_str(item, "trans", mapping.trans.as_deref())?.unwrap_or_default(),
+            phonetic: field_str(item, "phonetic", mapping.phonetic.as_deref())?.unwrap_or_default(),
+            modified_time: field_time(item, mapping.modified_time.as_deref())?,
+            local: None,
+        });
+    }
+    Ok(words)
+}
+
+/// 字符串字段，字符串数组以换行连接。pointer未配置、不存在或为null时返回None
+fn field_str(item: &Value, field: &str, pointer: Option<&str>) -> Result<Option<String>, String> {
+    let pointer = match pointer {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    match item.pointer(pointer) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.trim().to_string())),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|v| v.as_str().map(str::trim))
+            .collect::<Option<Vec<_>>>()
+            .map(|lines| Some(lines.join("\n")))
+            .ok_or_else(|| mapping_error(field, pointer, "not an array of strings", item)),
+        Some(_) => Err(mapping_error(field, pointer, "not a string", item)),
+    }
+}
+
+/// 毫秒的时间戳或RFC 3339格式的时间，未配置或不存在时为0
+fn field_time(item: &Value, pointer: Option<&str>) -> Result<usize, String> {
+    let pointer = match pointer {
+        Some(p) => p,
+        None => return Ok(0),
+    };
+    match item.pointer(pointer) {
+        None | Some(Value::Null) => Ok(0),
+        Some(Value::Number(n)) => n
+            .as_u64()
+            .map(|n| n as usize)
+            .ok_or_else(|| mapping_error("modified_time", pointer, "not a timestamp", item)),
+        Some(Value::String(s)) => DateTime::parse_from_rfc3339(s)
+            .map(|t| t.timestamp_millis().max(0) as usize)
+            .map_err(|_| mapping_error("modified_time", pointer, "not a RFC 3339 time", item)),
+        Some(_) => Err(mapping_error(
+            "modified_time",
+            pointer,
+            "not a timestamp",
+            item,
+        )),
+    }
+}
+
+fn mapping_error(field: &str, pointer: &str, reason: &str, value: &Value) -> String {
+    format!(
+        "mapping {} pointer `{}` {} in json: {}",
+        field,
+        pointer,
+        reason,
+        sample(&value.to_string())
+    )
+}
+
+/// 最多[`MAX_SAMPLE_CHARS`]个字符的json示例
+fn sample(json: &str) -> String {
+    match json.char_indices().nth(MAX_SAMPLE_CHARS) {
+        Some((i, _)) => format!("{}...", &json[..i]),
+        None => json.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::{MockResponse, MockServer};
+
+    fn source_config(url: &str, mapping: &str) -> CustomSourceConfig {
+        serde_yaml::from_str(&format!(
+            "dictionary_path: vocab.json
+request:
+  url: {}
+  method: GET
+  headers:
+    authorization: Bearer test-token
+mapping:
+{}",
+            url, mapping
+        ))
+        .unwrap()
+    }
+
+    fn mapping(yaml: &str) -> FieldMapping {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn map_nested_payload() -> Result<(), String> {
+        let value: Value = serde_json::from_str(include_str!(
+            "../../tests/fixtures/custom-source-nested.json"
+        ))
+        .unwrap();
+        let mapping = mapping(
+            "items: /data/words
+word: /text
+trans: /meaning
+phonetic: /pron/us
+modified_time: /updated_at",
+        );
+        let words = map_words(&mapping, &value, "vocab", "GRE")?;
+        assert_eq!(words.len(), 2);
+        let first = &words[0];
+        assert_eq!(
+            (
+                first.item_id.as_str(),
+                first.book_id.as_str(),
+                first.book_name.as_str()
+            ),
+            ("serendipity", "vocab", "GRE")
+        );
+        assert_eq!(first.trans, "n. 意外发现珍奇事物的本领\nn. 机缘凑巧");
+        assert_eq!(first.phonetic, "[ˌserənˈdɪpəti]");
+        assert_eq!(first.modified_time, 1714557600000);
+        // 不存在的phonetic为空，时区被转换
+        assert_eq!(words[1].phonetic, "");
+        assert_eq!(words[1].modified_time, 1714609800000);
+        Ok(())
+    }
+
+    #[test]
+    fn map_flat_payload() -> Result<(), String> {
+        let value: Value =
+            serde_json::from_str(include_str!("../../tests/fixtures/custom-source-flat.json"))
+                .unwrap();
+        let mapping = mapping("word: /w\ntrans: /def\nmodified_time: /ts");
+        let words = map_words(&mapping, &value, "flat", "flat")?;
+        assert_eq!(
+            words
+                .iter()
+                .map(|w| (w.word.as_str(), w.trans.as_str(), w.modified_time))
+                .collect::<Vec<_>>(),
+            vec![
+                ("ubiquitous", "adj. 无处不在的", 1714557600000),
+                ("meticulous", "adj. 一丝不苟的", 1714644000000),
+                ("candid", "", 0),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn skip_duplicate_words() -> Result<(), String> {
+        let value = serde_json::json!([
+            {"w": "candid", "def": "adj. 坦率的"},
+            {"w": "ubiquitous"},
+            {"w": "candid", "def": "adj. 直言的"},
+        ]);
+        let words = map_words(&mapping("word: /w\ntrans: /def"), &value, "flat", "flat")?;
+        assert_eq!(
+            words
+                .iter()
+                .map(|w| (w.item_id.as_str(), w.trans.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("candid", "adj. 坦率的"), ("ubiquitous", "")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mapping_errors() {
+        let value: Value = serde_json::from_str(include_str!(
+            "../../tests/fixtures/custom-source-nested.json"
+        ))
+        .unwrap();
+        let err = |yaml: &str| map_words(&mapping(yaml), &value, "vocab", "vocab").unwrap_err();
+
+        let e = err("items: /data/list\nword: /text");
+        assert!(
+            e.starts_with("mapping items pointer `/data/list` not found in json: {\"code\":0,"),
+            "{}",
+            e
+        );
+        // 过长的json被截断
+        assert!(e.ends_with("..."), "{}", e);
+        let e = err("items: /data\nword: /text");
+        assert!(e.contains("`/data` not an array"), "{}", e);
+        let e = err("items: /data/words\nword: /txt");
+        assert!(
+            e.starts_with("mapping word pointer `/txt` empty in json: {"),
+            "{}",
+            e
+        );
+        let e = err("items: /data/words\nword: /text\ntrans: /pron");
+        assert!(e.contains("trans pointer `/pron` not a string"), "{}", e);
+        let e = err("items: /data/words\nword: /text\nmodified_time: /text");
+        assert!(
+            e.contains("modified_time pointer `/text` not a RFC 3339 time in json: {\"meaning\""),
+            "{}",
+            e
+        );
+        assert!(serde_yaml::from_str::<FieldMapping>("word: /w\nwords: /x").is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_from_server() -> Result<(), String> {
+        let server = MockServer::start(|req| {
+            if req.header("authorization") != Some("Bearer test-token") {
+                return MockResponse::new(401);
+            }
+            match req.path.as_str() {
+                "/words" => MockResponse::text(
+                    200,
+                    include_str!("../../tests/fixtures/custom-source-flat.json"),
+                ),
+                _ => MockResponse::text(200, "<html>not json</html>"),
+            }
+        })
+        .await;
+        let mut client = GenericJsonClient::new(
+            "flat",
+            &source_config(&server.url("/words"), "  word: /w\n  trans: /def"),
+        )?;
+        let words = client.fetch_words().await?;
+        assert_eq!(words.len(), 3);
+        assert_eq!(words[0].book_name, "flat");
+        assert_eq!(server.requests()[0].method, "GET");
+        assert_eq!(server.requests()[0].header("cookie"), None);
+
+        let client =
+            GenericJsonClient::new("html", &source_config(&server.url("/html"), "  word: /w"))?;
+        let e = client.get_words().await.unwrap_err();
+        assert!(
+            e.starts_with("custom source html: parse json error")
+                && e.ends_with("<html>not json</html>"),
+            "{}",
+            e
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! 宽松的反序列化，避免服务端修改字段类型或返回个别错误的数据时丢失整个列表
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::Value;
+use std::str::FromStr;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString<T> {
+    Number(T),
+    String(String),
+}
+
+/// 接受数字或数字字符串如`"1"`。null与空字符串时为默认值
+pub fn number_or_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr + Default,
+    T::Err: std::fmt::Display,
+{
+    match Option::<NumberOrString<T>>::deserialize(deserializer)? {
+        Some(NumberOrString::Number(n)) => Ok(n),
+        Some(NumberOrString::String(s)) if s.trim().is_empty() => Ok(T::default()),
+        Some(NumberOrString::String(s)) => s
+            .trim()
+            .parse()
+            .map_err(|e| serde::de::Error::custom(format!("invalid number {:?}: {}", s, e))),
+        None => Ok(T::default()),
+    }
+}
+
+/// null时为默认值
+pub fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
+
+/// 逐个解析values，跳过解析失败的元素并输出warn日志。返回解析成功的元素与跳过的数量
+pub fn from_values<T: DeserializeOwned>(values: Vec<Value>, kind: &str) -> (Vec<T>, usize) {
+    let total = values.len();
+    let items = values
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, value)| match serde_json::from_value::<T>(value) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                warn!("skipped invalid {} at index {}: {}", kind, i, e);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let skipped = total - items.len();
+    if skipped > 0 {
+        warn!("skipped {} of {} invalid {}", skipped, total, kind);
+    }
+    (items, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{maimemo_client::Notepad, youdao_client::WordItem};
+
+    #[test]
+    fn malformed_notepads() {
+        let values = serde_json::from_str::<Vec<Value>>(
+            r#"[
+                {"is_private": "1", "notepad_id": "1", "title": "a", "brief": null, "tags": ["new"]},
+                {"is_private": 0, "notepad_id": "2", "title": "b", "created_time": "2021-03-08 13:25:21"},
+                {"is_private": "", "notepad_id": "3"},
+                {"is_private": "yes", "notepad_id": "4"},
+                {"title": "missing id"}
+            ]"#,
+        )
+        .unwrap();
+        let (notepads, skipped) = from_values::<Notepad>(values, "notepads");
+        assert_eq!(skipped, 2);
+        let ids = notepads
+            .iter()
+            .map(|n| n.get_notepad_id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        let private = notepads
+            .iter()
+            .map(|n| serde_json::to_value(n).unwrap()["is_private"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(private, vec![1, 0, 0]);
+        assert_eq!(notepads[0].get_title(), "a");
+        assert_eq!(notepads[2].get_title(), "");
+    }
+
+    #[test]
+    fn malformed_word_items() {
+        let values = serde_json::from_str::<Vec<Value>>(
+            r#"[
+                {"itemId": "1", "word": "a", "modifiedTime": "1615181121000", "trans": null},
+                {"itemId": "2", "word": "b", "modifiedTime": 1615181121000, "phonetic": null, "star": 1},
+                {"itemId": "3", "word": "c"},
+                {"itemId": "4", "word": "d", "modifiedTime": "today"},
+                {"itemId": "5", "word": null}
+            ]"#,
+        )
+        .unwrap();
+        let (items, skipped) = from_values::<WordItem>(values, "words");
+        assert_eq!(skipped, 2);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].modified_time, 1615181121000);
+        assert_eq!(items[0].trans, "");
+        assert_eq!(items[1].modified_time, 1615181121000);
+        assert_eq!(items[2].modified_time, 0);
+    }
+}
@@ -0,0 +1,95 @@
+//! 登录尝试的记录，避免频繁登录被youdao加入黑名单。
+//!
+//! 记录保存在`<cookie_path>.login.json`中，在多次运行间共享。每个window内最多尝试登录一次，
+//! 连续失败后window按失败次数指数增长
+use crate::config::save_json;
+use serde::{Deserialize, Serialize};
+
+/// 未配置`login_interval_secs`时两次登录的最小间隔
+pub const DEFAULT_LOGIN_INTERVAL_SECS: u64 = 10 * 60;
+
+/// 失败后window最多增长到interval的`2^MAX_BACKOFF_SHIFT`倍
+const MAX_BACKOFF_SHIFT: u32 = 6;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginState {
+    /// 上次尝试登录的unix时间戳秒数
+    pub last_attempt: Option<i64>,
+    /// 连续失败的次数，登录成功后清零
+    #[serde(default)]
+    pub failures: u32,
+}
+
+impl LoginState {
+    /// cookie_path对应的记录文件
+    pub fn path(cookie_path: &str) -> String {
+        format!("{}.login.json", cookie_path)
+    }
+
+    /// 从path中加载。不存在或无法解析时为空的记录
+    pub fn load(path: &str) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("ignored invalid login state: {}, path: {}", e, path);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        save_json(self, path).map_err(|e| format!("save login state error: {}, path: {}", e, path))
+    }
+
+    /// 在now时距离下次可以登录的秒数，可以登录时为None
+    pub fn wait_secs(&self, now: i64, interval_secs: u64) -> Option<u64> {
+        let last = self.last_attempt?;
+        let window = interval_secs.saturating_mul(1 << self.failures.min(MAX_BACKOFF_SHIFT));
+        let elapsed = now.saturating_sub(last).max(0) as u64;
+        if elapsed < window {
+            Some(window - elapsed)
+        } else {
+            None
+        }
+    }
+
+    /// 记录在now开始的一次登录
+    pub fn attempt(&mut self, now: i64) {
+        self.last_attempt = Some(now);
+    }
+
+    /// 记录登录的结果，失败时增加下次的window
+    pub fn finish(&mut self, succeeded: bool) {
+        self.failures = if succeeded {
+            0
+        } else {
+            self.failures.saturating_add(1)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_after_failures() {
+        let mut state = LoginState::default();
+        assert_eq!(state.wait_secs(1000, 600), None);
+        state.attempt(1000);
+        assert_eq!(state.wait_secs(1100, 600), Some(500));
+        assert_eq!(state.wait_secs(1600, 600), None);
+
+        state.finish(false);
+        state.finish(false);
+        assert_eq!(state.wait_secs(1600, 600), Some(1800));
+        assert_eq!(state.wait_secs(3400, 600), None);
+        // 最多增长到2^6倍
+        state.failures = 100;
+        assert_eq!(state.wait_secs(1000, 600), Some(600 * 64));
+        state.finish(true);
+        assert_eq!(state.failures, 0);
+        // 时钟回拨时等待整个window
+        assert_eq!(state.wait_secs(900, 600), Some(600));
+    }
+}
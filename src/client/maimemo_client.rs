@@ -1,29 +1,99 @@
-use crate::client::*;
+use crate::client::{context::HttpContext, cookie_origin::CookieOrigin, *};
 use crate::config::*;
-use chrono::Local;
+use crate::error::{LOGIN_FAILED, NOT_LOGGED_IN, STATUS_ERROR, WRONG_CAPTCHA};
+use crate::events::Event;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
 use cookie_store::CookieStore;
-use reqwest::Client;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
 
 /// notepad包含必要的header info和内容detail
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Notepad {
+    #[serde(default, deserialize_with = "lenient::number_or_string")]
     is_private: u8,
     notepad_id: String,
+    #[serde(default, deserialize_with = "lenient::null_as_default")]
     title: String,
+    #[serde(default, deserialize_with = "lenient::null_as_default")]
     brief: String,
+    #[serde(default)]
     created_time: Option<String>,
+    #[serde(default)]
     updated_time: Option<String>,
+    #[serde(default)]
     contents: Option<String>,
 }
 
 impl Notepad {
+    /// 未保存到maimemo的私有notepad，notepad_id为空
+    pub fn new(title: &str, brief: &str, contents: String) -> Self {
+        Self {
+            is_private: 1,
+            notepad_id: String::new(),
+            title: title.to_string(),
+            brief: brief.to_string(),
+            created_time: None,
+            updated_time: None,
+            contents: Some(contents),
+        }
+    }
+
+    /// 已保存在maimemo中的notepad_id，如从open api获取的notepad
+    pub fn with_id(mut self, notepad_id: &str) -> Self {
+        self.notepad_id = notepad_id.to_string();
+        self
+    }
+
+    pub fn with_private(mut self, is_private: bool) -> Self {
+        self.is_private = is_private as u8;
+        self
+    }
+
+    pub fn with_times(
+        mut self,
+        created_time: Option<String>,
+        updated_time: Option<String>,
+    ) -> Self {
+        self.created_time = created_time;
+        self.updated_time = updated_time;
+        self
+    }
+
     pub fn get_notepad_id(&self) -> &str {
         &self.notepad_id
     }
 
+    pub fn is_private(&self) -> bool {
+        self.is_private != 0
+    }
+
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn get_brief(&self) -> &str {
+        &self.brief
+    }
+
+    pub fn get_updated_time(&self) -> Option<&str> {
+        self.updated_time.as_deref()
+    }
+
+    pub fn set_updated_time(&mut self, updated_time: Option<String>) {
+        self.updated_time = updated_time;
+    }
+
+    pub fn set_brief(&mut self, brief: String) {
+        self.brief = brief;
+    }
+
     pub fn set_contents(&mut self, contents: Option<String>) {
         self.contents = contents;
     }
@@ -35,42 +105,576 @@ impl Notepad {
     pub fn get_contents_mut(&mut self) -> Option<&mut String> {
         self.contents.as_mut()
     }
+
+    /// 不包含contents的概要信息
+    pub fn summary(&self) -> NotepadSummary<'_> {
+        NotepadSummary {
+            notepad_id: &self.notepad_id,
+            title: &self.title,
+            brief: &self.brief,
+            is_private: self.is_private,
+            created_time: self.created_time.as_deref(),
+            updated_time: self.updated_time.as_deref(),
+            content_length: self.contents.as_ref().map_or(0, |c| c.len()),
+        }
+    }
+}
+
+/// notepad的概要信息，用于json输出。仅包含稳定的字段
+#[derive(Debug, Serialize)]
+pub struct NotepadSummary<'a> {
+    notepad_id: &'a str,
+    title: &'a str,
+    brief: &'a str,
+    is_private: u8,
+    created_time: Option<&'a str>,
+    updated_time: Option<&'a str>,
+    /// contents的字节长度
+    content_length: usize,
 }
 
 impl fmt::Display for Notepad {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut temp = self.clone();
         // 仅输出第一行 与 total length
-        let contents = temp.contents.as_mut().unwrap();
-        let total_len = contents.len();
-        contents.drain(contents.find("\n").unwrap_or(total_len)..);
-        contents.push_str("... total length: ");
-        contents.push_str(&total_len.to_string());
+        if let Some(contents) = temp.contents.as_mut() {
+            let total_len = contents.len();
+            contents.drain(contents.find("\n").unwrap_or(total_len)..);
+            contents.push_str("... total length: ");
+            contents.push_str(&total_len.to_string());
+        }
         write!(f, "{}", serde_json::to_string_pretty(&temp).unwrap())
     }
 }
 
+/// notepad list的排序方式。时间降序，与maimemo app中相同；title与id升序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotepadSort {
+    Updated,
+    Created,
+    Title,
+    Id,
+}
+
+impl FromStr for NotepadSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "updated" => Ok(NotepadSort::Updated),
+            "created" => Ok(NotepadSort::Created),
+            "title" => Ok(NotepadSort::Title),
+            "id" => Ok(NotepadSort::Id),
+            _ => Err(format!("unsupported notepad sort: {}", s)),
+        }
+    }
+}
+
+/// 解析maimemo中的时间如`2020-10-18 10:00:00`，也支持rfc3339与`%Y-%m-%d`。
+/// rfc3339转换为utc时间
+pub fn parse_notepad_time(s: &str) -> Option<NaiveDateTime> {
+    let s = s.trim();
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+        .or_else(|| DateTime::parse_from_rfc3339(s).ok().map(|t| t.naive_utc()))
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// 数字id按数值比较
+fn cmp_notepad_ids(a: &Notepad, b: &Notepad) -> Ordering {
+    match (a.notepad_id.parse::<u64>(), b.notepad_id.parse::<u64>()) {
+        (Ok(x), Ok(y)) => x.cmp(&y),
+        _ => a.notepad_id.cmp(&b.notepad_id),
+    }
+}
+
+/// 按sort排序notepads，相同时按id升序。
+///
+/// 没有时间的notepad排在最后。有无法解析的时间时输出警告并按字符串比较所有时间
+pub fn sort_notepads(notepads: &mut [Notepad], sort: NotepadSort) {
+    let time = |n: &Notepad| match sort {
+        NotepadSort::Created => n.created_time.clone(),
+        _ => n.updated_time.clone(),
+    };
+    match sort {
+        NotepadSort::Title => notepads.sort_by(|a, b| {
+            a.title
+                .to_lowercase()
+                .cmp(&b.title.to_lowercase())
+                .then_with(|| cmp_notepad_ids(a, b))
+        }),
+        NotepadSort::Id => notepads.sort_by(cmp_notepad_ids),
+        NotepadSort::Updated | NotepadSort::Created => {
+            let invalid = notepads
+                .iter()
+                .filter_map(time)
+                .filter(|t| parse_notepad_time(t).is_none())
+                .collect::<Vec<_>>();
+            if invalid.is_empty() {
+                notepads.sort_by(|a, b| {
+                    let key = |n| {
+                        let t = time(n).and_then(|t| parse_notepad_time(&t));
+                        (t.is_none(), Reverse(t))
+                    };
+                    key(a).cmp(&key(b)).then_with(|| cmp_notepad_ids(a, b))
+                });
+            } else {
+                warn!(
+                    "unexpected notepad time format: {}. sorting by string",
+                    invalid.join(", ")
+                );
+                notepads.sort_by(|a, b| {
+                    let key = |n| {
+                        let t = time(n);
+                        (t.is_none(), Reverse(t))
+                    };
+                    key(a).cmp(&key(b)).then_with(|| cmp_notepad_ids(a, b))
+                });
+            }
+        }
+    }
+}
+
+/// 获取notepads内容时失败。fetched为失败前已获取内容的notepads，可以保存后在下次refresh时继续
+#[derive(Debug)]
+pub struct PartialNotepadsError {
+    pub fetched: Vec<Notepad>,
+    /// notepad list中的数量，获取list失败时为0
+    pub total: usize,
+    pub error: String,
+}
+
+impl fmt::Display for PartialNotepadsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}. fetched {} of {} notepads before failure",
+            self.error,
+            self.fetched.len(),
+            self.total
+        )
+    }
+}
+
+impl std::error::Error for PartialNotepadsError {}
+
+/// 获取验证码或保存notepad失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveNotepadError {
+    /// 被maimemo限流，delay后可以重试
+    Throttled {
+        delay: Duration,
+        error: String,
+    },
+    /// 获取或回答验证码失败，见[`CaptchaSink`]
+    ReadCaptcha(String),
+    /// maimemo拒绝了验证码，需要获取新的验证码
+    WrongCaptcha(String),
+    Other(String),
+}
+
+impl fmt::Display for SaveNotepadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Throttled { error, .. } | Self::WrongCaptcha(error) | Self::Other(error) => {
+                write!(f, "{}", error)
+            }
+            Self::ReadCaptcha(error) => write!(f, "read captcha error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SaveNotepadError {}
+
+impl From<String> for SaveNotepadError {
+    fn from(error: String) -> Self {
+        Self::Other(error)
+    }
+}
+
+impl From<ReadBodyError> for SaveNotepadError {
+    fn from(e: ReadBodyError) -> Self {
+        Self::Other(e.to_string())
+    }
+}
+
+impl From<SaveNotepadError> for String {
+    fn from(e: SaveNotepadError) -> Self {
+        e.to_string()
+    }
+}
+
+/// 获取notepad内容失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotepadContentsError {
+    /// maimemo返回404，notepad已被删除
+    NotFound {
+        notepad_id: String,
+    },
+    /// 返回了登录页面，见[`SESSION_EXPIRED`]
+    SessionExpired,
+    Other(String),
+}
+
+impl fmt::Display for NotepadContentsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { notepad_id } => write!(f, "not found notepad {}", notepad_id),
+            Self::SessionExpired => write!(f, "{}", SESSION_EXPIRED),
+            Self::Other(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for NotepadContentsError {}
+
+impl From<String> for NotepadContentsError {
+    fn from(error: String) -> Self {
+        Self::Other(error)
+    }
+}
+
+impl From<ReadBodyError> for NotepadContentsError {
+    fn from(e: ReadBodyError) -> Self {
+        Self::Other(e.to_string())
+    }
+}
+
+impl From<NotepadContentsError> for String {
+    fn from(e: NotepadContentsError) -> Self {
+        e.to_string()
+    }
+}
+
+/// 刷新得到的验证码
+#[derive(Debug, Clone, PartialEq)]
+pub struct Captcha {
+    pub bytes: Vec<u8>,
+    /// response header content-type
+    pub content_type: Option<String>,
+}
+
+impl Captcha {
+    /// 检查content-type是否为图片，避免将错误页面当作图片解码
+    ///
+    /// # Errors
+    ///
+    /// 如果不是图片，error中包含body的前[`ERROR_BODY_SNIPPET_LEN`]个字节
+    pub fn ensure_image(&self) -> Result<(), String> {
+        match &self.content_type {
+            Some(t) if t.trim().to_lowercase().starts_with("image/") => Ok(()),
+            t => Err(format!(
+                "captcha refresh failed, maybe the session expired. content-type: {}, body: {}",
+                t.as_deref().unwrap_or("none"),
+                String::from_utf8_lossy(
+                    &self.bytes[..self.bytes.len().min(ERROR_BODY_SNIPPET_LEN)]
+                )
+            )),
+        }
+    }
+}
+
+/// notepad搜索的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum NotepadScope {
+    /// 自己的notepads
+    Mine,
+    /// 包括其他用户公开的notepads
+    All,
+}
+
+/// 搜索notepads的参数。默认为自己的所有notepads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotepadQuery {
+    pub scope: NotepadScope,
+    pub keyword: Option<String>,
+    /// 仅搜索maimemo推荐的notepads
+    pub recommend: bool,
+    /// 最多获取的数量，为None时获取全部
+    pub limit: Option<usize>,
+}
+
+impl Default for NotepadQuery {
+    fn default() -> Self {
+        Self {
+            scope: NotepadScope::Mine,
+            keyword: None,
+            recommend: false,
+            limit: None,
+        }
+    }
+}
+
+impl NotepadQuery {
+    /// 通过keyword搜索公开的notepads
+    pub fn public(keyword: &str, recommend: bool, limit: usize) -> Self {
+        Self {
+            scope: NotepadScope::All,
+            keyword: Some(keyword.to_string()),
+            recommend,
+            limit: Some(limit),
+        }
+    }
+}
+
+/// notepad-search每次请求的数量
+pub const NOTEPAD_PAGE_SIZE: usize = 30;
+
+/// maimemo notepad简介的最大字符数
+pub const MAX_BRIEF_CHARS: usize = 100;
+
+/// 生成简介时默认使用的单词行数
+pub const DEFAULT_BRIEF_LINES: usize = 5;
+
+/// 简介中单词间的分隔
+const BRIEF_SEPARATOR: &str = "、";
+
+/// 从contents前lines个非空且不以`#`开头的行生成简介，超过[`MAX_BRIEF_CHARS`]个字符时截断并以省略号结尾
+pub fn generate_brief(contents: &str, lines: usize) -> String {
+    let brief = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .take(lines)
+        .collect::<Vec<_>>()
+        .join(BRIEF_SEPARATOR);
+    if brief.chars().count() <= MAX_BRIEF_CHARS {
+        return brief;
+    }
+    let mut truncated = brief.chars().take(MAX_BRIEF_CHARS - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// 去除验证码答案两端的空白并按case转换大小写
+///
+/// # Errors
+///
+/// 如果答案为空或包含ascii字母数字以外的字符
+pub fn normalize_captcha(answer: &str, case: CaptchaCase) -> Result<String, String> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Err("captcha is empty".to_string());
+    }
+    if let Some(c) = answer.chars().find(|c| !c.is_ascii_alphanumeric()) {
+        return Err(format!(
+            "invalid character {:?} in captcha, only letters and digits are allowed",
+            c
+        ));
+    }
+    Ok(match case {
+        CaptchaCase::Keep => answer.to_string(),
+        CaptchaCase::Lower => answer.to_ascii_lowercase(),
+        CaptchaCase::Upper => answer.to_ascii_uppercase(),
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ResponseResult {
     error: String,
     valid: i32,
     total: usize,
-    notepad: Option<Vec<Notepad>>,
+    /// 逐个解析，见[`lenient::from_values`]
+    notepad: Option<Vec<serde_json::Value>>,
+}
+
+/// 登录后需要的cookies: `(domain, name)`
+pub const LOGIN_COOKIES: &[(&str, &str)] = &[("www.maimemo.com", "userToken")];
+
+/// 服务端的session已过期，需要重新登录。cookie仍存在时maimemo返回登录页面而不是notepad
+pub const SESSION_EXPIRED: &str = "maimemo session expired";
+
+/// 登录页面中的元素
+const LOGIN_FORM_SELECTOR: &str = "form[action*=\"login\"], input[type=\"password\"]";
+
+/// session过期时错误页面中的提示
+const SESSION_EXPIRED_MARKERS: &[&str] = &["请先登录", "登录已过期", "登录超时"];
+
+/// 保存notepad时表示请求过于频繁的errorCode
+const THROTTLING_ERROR_CODES: &[&str] = &["too_frequent", "captcha_too_frequent"];
+
+/// 被限流且没有Retry-After与`captcha_min_interval_secs`时的等待秒数
+const DEFAULT_THROTTLE_WAIT_SECS: u64 = 30;
+
+/// 缓存的notepad内容与获取时的updated_time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedContents {
+    updated_time: String,
+    contents: String,
+}
+
+/// notepad内容的缓存，按notepad_id保存。listed的updated_time改变时缓存失效
+#[derive(Debug, Default)]
+struct ContentCache {
+    /// 为空时仅缓存在内存中
+    path: Option<String>,
+    entries: BTreeMap<String, CachedContents>,
+    dirty: bool,
+}
+
+impl ContentCache {
+    /// 从path中加载缓存。文件不存在或无法解析时使用空的缓存
+    fn load(path: Option<&str>) -> Self {
+        let entries = path
+            .filter(|p| std::path::Path::new(p).exists())
+            .and_then(|p| {
+                std::fs::read(p)
+                    .map_err(|e| e.to_string())
+                    .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))
+                    .map_err(|e| warn!("ignored notepad content cache {}: {}", p, e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path: path.map(str::to_string),
+            entries,
+            dirty: false,
+        }
+    }
+
+    /// notepad的updated_time与缓存时相同时返回缓存的内容
+    fn get(&self, notepad: &Notepad) -> Option<&str> {
+        let updated_time = notepad.updated_time.as_deref()?;
+        self.entries
+            .get(&notepad.notepad_id)
+            .filter(|c| c.updated_time == updated_time)
+            .map(|c| c.contents.as_str())
+    }
+
+    /// 缓存notepad的内容。没有updated_time或内容时无法判断是否失效，不缓存
+    fn insert(&mut self, notepad: &Notepad) {
+        if let (Some(updated_time), Some(contents)) = (&notepad.updated_time, &notepad.contents) {
+            self.entries.insert(
+                notepad.notepad_id.clone(),
+                CachedContents {
+                    updated_time: updated_time.clone(),
+                    contents: contents.clone(),
+                },
+            );
+            self.dirty = true;
+        }
+    }
+
+    /// 移除不在notepads中的缓存，如已被删除的notepad
+    fn retain(&mut self, notepads: &[Notepad]) {
+        let len = self.entries.len();
+        self.entries
+            .retain(|id, _| notepads.iter().any(|n| n.notepad_id == *id));
+        self.dirty |= self.entries.len() != len;
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        if let (true, Some(path)) = (self.dirty, self.path.as_deref()) {
+            let data = serde_json::to_vec(&self.entries)
+                .map_err(|e| format!("serialize notepad content cache error: {}", e))?;
+            std::fs::write(path, data)
+                .map_err(|e| format!("write notepad content cache error: {}, path: {}", e, path))?;
+            debug!("saved {} notepad contents to {}", self.entries.len(), path);
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// 清空内存与文件中的缓存
+    fn clear(&mut self) -> Result<(), String> {
+        self.entries.clear();
+        self.dirty = false;
+        match self.path.as_deref() {
+            Some(path) if std::path::Path::new(path).exists() => std::fs::remove_file(path)
+                .map_err(|e| format!("remove notepad content cache error: {}, path: {}", e, path)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 登录请求的form
+#[derive(Debug, Serialize)]
+pub struct LoginForm<'a> {
+    email: &'a str,
+    password: &'a str,
+}
+
+impl<'a> LoginForm<'a> {
+    pub fn new(email: &'a str, password: &'a str) -> Self {
+        Self { email, password }
+    }
+}
+
+/// 搜索一页notepads请求的json
+#[derive(Debug, Serialize)]
+pub struct SearchPayload<'a> {
+    keyword: Option<&'a str>,
+    scope: NotepadScope,
+    recommend: bool,
+    offset: usize,
+    limit: usize,
+    total: i64,
+}
+
+impl<'a> SearchPayload<'a> {
+    pub fn new(query: &'a NotepadQuery, offset: usize, limit: usize) -> Self {
+        Self {
+            keyword: query.keyword.as_deref(),
+            scope: query.scope,
+            recommend: query.recommend,
+            offset,
+            limit,
+            total: -1,
+        }
+    }
+}
+
+/// 保存notepad请求的form。固定顺序的字段，保证日志与录制的请求稳定
+#[derive(Debug, Serialize)]
+pub struct SaveForm<'a> {
+    id: &'a str,
+    title: &'a str,
+    brief: &'a str,
+    content: &'a str,
+    is_private: bool,
+    captcha: &'a str,
+}
+
+impl<'a> SaveForm<'a> {
+    /// 没有内容的notepad的content为空
+    pub fn new(notepad: &'a Notepad, captcha: &'a str) -> Self {
+        Self {
+            id: &notepad.notepad_id,
+            title: &notepad.title,
+            brief: &notepad.brief,
+            content: notepad.contents.as_deref().unwrap_or_default(),
+            is_private: notepad.is_private == 1,
+            captcha,
+        }
+    }
 }
 
 /// maimemo提供一些访问操作。
 pub struct MaimemoClient {
-    client: Client,
+    context: Arc<HttpContext>,
     config: AppConfig,
     cookie_store: CookieStore,
+    /// cookie store被修改且未保存
+    cookies_dirty: bool,
     user_token_name: String,
+    /// 上次刷新验证码的时间
+    last_captcha: Mutex<Option<Instant>>,
+    /// notepad内容缓存，None表示已禁用
+    content_cache: Option<ContentCache>,
 }
 
 impl std::ops::Drop for MaimemoClient {
-    /// 在退出时保存cookie store
+    /// 如果cookie store在修改后未通过[`MaimemoClient::save`]保存，则在drop时保存
     fn drop(&mut self) {
-        if let Some(path) = self.config.get_cookie_path() {
-            if let Err(e) = save_cookie_store(path, &self.cookie_store) {
+        if self.cookies_dirty {
+            warn!("maimemo cookies were not saved explicitly, saving late in drop");
+            if let Err(e) = self.save_cookies() {
                 error!("save cookie store failed: {}", e);
             }
         }
@@ -80,15 +684,68 @@ impl std::ops::Drop for MaimemoClient {
 impl MaimemoClient {
     /// 用config构造一个client。如果config.cookie_path存在则加载，否则使用in memory的cookie store。
     pub fn new(config: AppConfig) -> Result<Self, String> {
-        let cookie_store = build_cookie_store(config.get_cookie_path())?;
+        Self::with_context(config, Arc::new(HttpContext::new()?))
+    }
+
+    /// 使用共享的http context创建client
+    pub fn with_context(config: AppConfig, context: Arc<HttpContext>) -> Result<Self, String> {
+        let passphrase = config.get_cookie_passphrase()?;
+        let cookie_store = build_cookie_store(config.get_cookie_path(), passphrase.as_deref())?;
+        warn_expiring_cookies(
+            "maimemo",
+            &login_cookie_status(&cookie_store, LOGIN_COOKIES),
+        );
         Ok(Self {
-            client: build_general_client()?,
+            context,
+            cookie_store,
+            cookies_dirty: false,
+            user_token_name: LOGIN_COOKIES[0].1.to_string(),
+            last_captcha: Mutex::new(None),
+            content_cache: Some(ContentCache::load(config.get_content_cache_path())),
             config,
-            cookie_store: cookie_store,
-            user_token_name: "userToken".to_string(),
         })
     }
 
+    /// 是否使用notepad内容缓存，默认开启。禁用时[`MaimemoClient::get_notepads`]总是请求所有内容
+    pub fn with_content_cache(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.content_cache = None;
+        } else if self.content_cache.is_none() {
+            self.content_cache = Some(ContentCache::load(self.config.get_content_cache_path()));
+        }
+        self
+    }
+
+    pub fn context(&self) -> &HttpContext {
+        &self.context
+    }
+
+    /// 清空notepad内容缓存，包括content_cache_path文件
+    pub fn clear_cache(&mut self) -> Result<(), String> {
+        match self.content_cache.as_mut() {
+            Some(cache) => cache.clear(),
+            None => Ok(()),
+        }
+    }
+
+    /// 保存cookie store到cookie_path，内容缓存到content_cache_path。未配置路径时不做任何操作
+    pub async fn save(&mut self) -> Result<(), String> {
+        self.save_cookies()?;
+        match self.content_cache.as_mut() {
+            Some(cache) => cache.save(),
+            None => Ok(()),
+        }
+    }
+
+    fn save_cookies(&mut self) -> Result<(), String> {
+        if let Some(path) = self.config.get_cookie_path() {
+            let passphrase = self.config.get_cookie_passphrase()?;
+            save_cookie_store(path, &self.cookie_store, passphrase.as_deref())?;
+        }
+        self.cookies_dirty = false;
+        Ok(())
+    }
+
     pub fn get_user_token_val(&self) -> Option<&str> {
         self.cookie_store
             .get("www.maimemo.com", "/", &self.user_token_name)
@@ -103,13 +760,10 @@ impl MaimemoClient {
     pub async fn login(&mut self) -> Result<(), String> {
         let req_name = "login";
 
-        let form = [
-            ("email", self.config.get_username()),
-            ("password", self.config.get_password()),
-        ];
+        let form = LoginForm::new(self.config.get_username(), self.config.get_password());
         let resp = send_request(
             &self.config,
-            &self.client,
+            &self.context,
             &self.cookie_store,
             req_name,
             |url| url.to_string(),
@@ -119,56 +773,216 @@ impl MaimemoClient {
         // login failed
         // Check if the user token exists
         update_set_cookies(&mut self.cookie_store, &resp);
+        self.cookies_dirty = true;
         if !self.has_logged() {
             error!(
                 "update cookie store failed. not found cookie: [{}] in cookie_store",
                 self.user_token_name
             );
-            Err("login failed. not found cookie store".to_string())
+            Err(format!("{}. not found cookie store", LOGIN_FAILED))
         } else {
             debug!("login successful");
+            if let Some(path) = self.config.get_cookie_path() {
+                CookieOrigin::login(chrono::Utc::now().timestamp()).save_or_warn(path);
+            }
             Ok(())
         }
     }
 
     /// 提供完整的notepad list调用get_notepad_list与get_notepad_contents
+    ///
+    /// 获取内容时如果session已过期，则重新登录后重试一次
     pub async fn get_notepads(&mut self) -> Result<Vec<Notepad>, String> {
-        let mut notepads = self.get_notepad_list().await?;
-        for notepad in &mut notepads {
-            let contents = self.get_notepad_contents(notepad.get_notepad_id()).await?;
-            notepad.set_contents(Some(contents));
+        self.get_notepads_cached(&[])
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// 与[`MaimemoClient::get_notepads`]相同，但cached或client的内容缓存中有内容且updated_time
+    /// 未改变的notepad直接使用缓存的内容，不再请求。
+    ///
+    /// # Errors
+    ///
+    /// 失败时返回已获取内容的notepads
+    pub async fn get_notepads_cached(
+        &mut self,
+        cached: &[Notepad],
+    ) -> Result<Vec<Notepad>, PartialNotepadsError> {
+        let mut notepads = self
+            .get_notepad_list(&NotepadQuery::default())
+            .await
+            .map_err(|error| PartialNotepadsError {
+                fetched: vec![],
+                total: 0,
+                error,
+            })?;
+        let total = notepads.len();
+        let (mut relogged, mut reused) = (false, 0);
+        for i in 0..total {
+            let notepad = &notepads[i];
+            let cached_contents = cached
+                .iter()
+                .find(|c| c.notepad_id == notepad.notepad_id)
+                .filter(|c| c.updated_time.is_some() && c.updated_time == notepad.updated_time)
+                .and_then(|c| c.contents.clone())
+                .or_else(|| {
+                    self.content_cache
+                        .as_ref()
+                        .and_then(|cache| cache.get(notepad))
+                        .map(str::to_string)
+                });
+            if let Some(contents) = cached_contents {
+                trace!("using cached contents of notepad {}", notepad.notepad_id);
+                notepads[i].set_contents(Some(contents));
+                reused += 1;
+                self.context.emit(Event::NotepadFetched {
+                    id: notepads[i].notepad_id.clone(),
+                });
+                continue;
+            }
+            let id = notepad.notepad_id.clone();
+            let result = match self.get_notepad_contents(&id).await {
+                Err(e @ NotepadContentsError::SessionExpired) if !relogged => {
+                    warn!("{} while getting notepad contents, logging in again", e);
+                    relogged = true;
+                    match self.login().await {
+                        Ok(_) => self.get_notepad_contents(&id).await.map_err(String::from),
+                        Err(e) => Err(e),
+                    }
+                }
+                r => r.map_err(String::from),
+            };
+            match result {
+                Ok(contents) => {
+                    notepads[i].set_contents(Some(contents));
+                    if let Some(cache) = self.content_cache.as_mut() {
+                        cache.insert(&notepads[i]);
+                    }
+                    self.context.emit(Event::NotepadFetched { id });
+                }
+                Err(error) => {
+                    notepads.truncate(i);
+                    return Err(PartialNotepadsError {
+                        fetched: notepads,
+                        total,
+                        error,
+                    });
+                }
+            }
+        }
+        if let Some(cache) = self.content_cache.as_mut() {
+            cache.retain(&notepads);
         }
+        debug!("got {} notepads, {} contents from cache", total, reused);
         Ok(notepads)
     }
 
-    /// 获取notepad list
-    pub async fn get_notepad_list(&mut self) -> Result<Vec<Notepad>, String> {
+    /// 获取maimemo中notepad_id的元数据与内容，notepad已被删除时返回None。
+    ///
+    /// 未登录时先登录，session过期时重新登录后重试一次
+    pub async fn get_remote_notepad(
+        &mut self,
+        notepad_id: &str,
+    ) -> Result<Option<Notepad>, String> {
         if !self.has_logged() {
-            return Err("not logged in".to_string());
+            self.login().await?;
         }
+        let contents = match self.get_notepad_contents(notepad_id).await {
+            Err(e @ NotepadContentsError::SessionExpired) => {
+                warn!("{} while getting notepad contents, logging in again", e);
+                self.login().await?;
+                self.get_notepad_contents(notepad_id).await
+            }
+            r => r,
+        };
+        let contents = match contents {
+            Err(e @ NotepadContentsError::NotFound { .. }) => {
+                debug!("{}", e);
+                return Ok(None);
+            }
+            r => r?,
+        };
+        Ok(self.get_notepad_info(notepad_id).await?.map(|mut n| {
+            n.set_contents(Some(contents));
+            n
+        }))
+    }
+
+    /// 从notepad list中获取notepad_id的元数据如updated_time，不包含内容。不存在时返回None
+    pub async fn get_notepad_info(&mut self, notepad_id: &str) -> Result<Option<Notepad>, String> {
+        let notepad = self
+            .get_notepad_list(&NotepadQuery::default())
+            .await?
+            .into_iter()
+            .find(|n| n.notepad_id == notepad_id);
+        if notepad.is_none() {
+            debug!("notepad {} is not in the notepad list", notepad_id);
+        }
+        Ok(notepad)
+    }
+
+    /// 获取query对应的notepad list，不包含内容。
+    ///
+    /// 每次请求[`NOTEPAD_PAGE_SIZE`]个，直到获取了total或query.limit个
+    pub async fn get_notepad_list(&mut self, query: &NotepadQuery) -> Result<Vec<Notepad>, String> {
+        if !self.has_logged() {
+            return Err(NOT_LOGGED_IN.to_string());
+        }
+        let mut notepads = vec![];
+        let limit = query.limit.unwrap_or(usize::MAX);
+        let mut offset = 0;
+        while notepads.len() < limit {
+            let page_size = NOTEPAD_PAGE_SIZE.min(limit - notepads.len());
+            let (page, len, total) = self.search_notepads(query, offset, page_size).await?;
+            notepads.extend(page);
+            offset += len;
+            if len == 0 || offset >= total {
+                break;
+            }
+        }
+        notepads.truncate(limit);
+        debug!(
+            "got notepad list. query: {:?}, len: {}",
+            query,
+            notepads.len()
+        );
+        Ok(notepads)
+    }
+
+    /// 获取一页notepads，返回解析成功的notepads、该页的数量与total
+    async fn search_notepads(
+        &self,
+        query: &NotepadQuery,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Notepad>, usize, usize), String> {
         let req_name = "notepad-search";
         // ?token={user_token}
         let url_handler = |url: &str| {
             let user_token = self.get_user_token_val().expect("not found user token");
             url.to_string() + user_token
         };
-        let payload = serde_json::json!({"keyword":null,"scope":"MINE","recommend":false,"offset":0,"limit":30,"total":-1});
+        let payload = SearchPayload::new(query, offset, limit);
         let resp = send_request(
             &self.config,
-            &self.client,
+            &self.context,
             &self.cookie_store,
             req_name,
             url_handler,
             Some(&payload),
         )
         .await?;
-        let result = resp
-            .json::<ResponseResult>()
-            .await
-            .map_err(|e| format!("{:?}", e))?;
+        let body = read_body(&self.config, req_name, resp).await?;
+        let result =
+            serde_json::from_slice::<ResponseResult>(&body).map_err(|e| format!("{:?}", e))?;
         if let Some(notepad) = result.notepad {
-            debug!("got notepad list. len: {}", notepad.len());
-            Ok(notepad)
+            let len = notepad.len();
+            let (notepad, skipped) = lenient::from_values::<Notepad>(notepad, "notepads");
+            debug!(
+                "got notepad page. offset: {}, len: {}, skipped: {}, total: {}",
+                offset, len, skipped, result.total
+            );
+            Ok((notepad, len, result.total))
         } else {
             error!("get notepad failed: {:?}", result);
             Err("get notepad failed".to_string())
@@ -176,74 +990,148 @@ impl MaimemoClient {
     }
 
     /// 获取notepad中单词文本
-    pub async fn get_notepad_contents(&self, notepad_id: &str) -> Result<String, String> {
+    pub async fn get_notepad_contents(
+        &self,
+        notepad_id: &str,
+    ) -> Result<String, NotepadContentsError> {
         if !self.has_logged() {
-            return Err("not logged in".to_string());
+            return Err(NOT_LOGGED_IN.to_string().into());
         }
         let req_name = "notepad-detail";
         let url_handler = |url: &str| url.to_string() + notepad_id;
-        let resp = send_request_nobody(
+        let resp = send_request_unchecked(
             &self.config,
-            &self.client,
+            &self.context,
             &self.cookie_store,
             req_name,
             url_handler,
+            None::<&str>,
         )
         .await?;
-        Self::parse_notepad_text(&resp.text().await.map_err(|e| format!("{:?}", e))?)
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Err(NotepadContentsError::NotFound {
+                notepad_id: notepad_id.to_string(),
+            });
+        }
+        let resp = check_status(&self.config, req_name, resp).await?;
+        let body = read_body(&self.config, req_name, resp).await?;
+        Self::parse_notepad_text(&String::from_utf8_lossy(&body))
     }
 
     /// 刷新下载notepad对应的captcha返回文件全路径。
-    pub async fn refresh_captcha(&self) -> Result<Vec<u8>, String> {
+    ///
+    /// 配置了`captcha_min_interval_secs`时，距上次刷新不足该间隔则先等待
+    pub async fn refresh_captcha(&self) -> Result<Captcha, SaveNotepadError> {
+        // 验证码仅用于保存notepad
+        self.context.check_writable("service-captcha")?;
         if !self.has_logged() {
-            return Err("not logged in".to_string());
+            return Err(NOT_LOGGED_IN.to_string().into());
         }
+        self.wait_captcha_interval().await;
         let req_name = "service-captcha";
-        let url_handler = |url: &str| url.to_owned() + &Local::now().timestamp_nanos().to_string();
+        let url_handler = |url: &str| {
+            url.to_owned()
+                + &Local::now()
+                    .timestamp_nanos_opt()
+                    .unwrap_or_default()
+                    .to_string()
+        };
         let resp = send_request_nobody(
             &self.config,
-            &self.client,
+            &self.context,
             &self.cookie_store,
             req_name,
             url_handler,
         )
         .await
-        .map_err(|e| format!("{:?}", e))?;
-        let contents = resp
-            .bytes()
-            .await
-            .map(|body| body.to_vec())
-            .map_err(|e| format!("{:?}", e))?;
-        Ok(contents)
+        .map_err(|e| self.save_error(format!("{:?}", e)))?;
+        let content_type = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let bytes = read_body(&self.config, req_name, resp).await?;
+        trace!(
+            "got captcha {} bytes with content-type: {:?}",
+            bytes.len(),
+            content_type
+        );
+        Ok(Captcha {
+            bytes,
+            content_type,
+        })
+    }
+
+    /// 提交验证码前的大小写转换，见[`normalize_captcha`]
+    pub fn captcha_case(&self) -> CaptchaCase {
+        self.config.get_captcha_case()
+    }
+
+    /// 配置了`captcha_min_interval_secs`时，距上次调用不足该间隔则先等待
+    async fn wait_captcha_interval(&self) {
+        let secs = match self.config.get_captcha_min_interval_secs() {
+            Some(secs) => secs,
+            None => return,
+        };
+        let last = *self.last_captcha.lock().unwrap();
+        if let Some(last) = last {
+            let next = last + Duration::from_secs(secs);
+            if next > Instant::now() {
+                debug!(
+                    "waiting {:?} for captcha min interval",
+                    next - Instant::now()
+                );
+                tokio::time::sleep_until(next).await;
+            }
+        }
+        *self.last_captcha.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// 429或表示限流的errorCode时为[`SaveNotepadError::Throttled`]
+    ///
+    /// 优先等待429 Retry-After，否则使用`captcha_min_interval_secs`或默认值
+    fn save_error(&self, e: String) -> SaveNotepadError {
+        let throttled = e.contains(&format!(
+            "{}{}",
+            STATUS_ERROR,
+            StatusCode::TOO_MANY_REQUESTS
+        )) || THROTTLING_ERROR_CODES.iter().any(|code| e.contains(code));
+        if !throttled {
+            return SaveNotepadError::Other(e);
+        }
+        let min = Duration::from_secs(
+            self.config
+                .get_captcha_min_interval_secs()
+                .unwrap_or(DEFAULT_THROTTLE_WAIT_SECS),
+        );
+        SaveNotepadError::Throttled {
+            delay: parse_retry_after(&e).unwrap_or(min),
+            error: e,
+        }
     }
 
     /// 保存notepad
     ///
     /// 注意：maimemo要求先获取验证码，再保存。并且要求是同一机器发送的。在win host浏览器刷新验证码，
     /// 但在wsl2 保存则不会生效，很可能是对比的发送的数据包是否来自同一机器
-    pub async fn save_notepad(&self, notepad: Notepad, captcha: String) -> Result<(), String> {
+    ///
+    /// notepad_id为空时创建一个新的notepad，见[`Notepad::new`]
+    pub async fn save_notepad(
+        &self,
+        notepad: Notepad,
+        captcha: String,
+    ) -> Result<(), SaveNotepadError> {
+        self.context.check_writable("notepad-save")?;
         if !self.has_logged() {
-            return Err("not logged in".to_string());
+            return Err(NOT_LOGGED_IN.to_string().into());
         }
         let req_name = "notepad-save";
         if notepad.contents.is_none() {
-            return Err("notepad contents is none".to_string());
-        }
-        // form
-        let mut form = std::collections::HashMap::new();
-        form.insert("id".to_string(), notepad.notepad_id);
-        form.insert("title".to_string(), notepad.title);
-        form.insert("brief".to_string(), notepad.brief);
-        form.insert("content".to_string(), notepad.contents.unwrap());
-        form.insert(
-            "is_private".to_string(),
-            (notepad.is_private == 1).to_string(),
-        );
-        form.insert("captcha".to_string(), captcha);
-        let form = form
-            .iter()
-            .map(|(key, val)| (key.as_str(), val.as_str()))
-            .collect::<Vec<_>>();
+            return Err("notepad contents is none".to_string().into());
+        }
+        // 不发送无效的验证码，避免浪费一次验证码
+        let captcha = normalize_captcha(&captcha, self.config.get_captcha_case())?;
+        let form = SaveForm::new(&notepad, &captcha);
 
         #[derive(Debug, Serialize, Deserialize)]
         struct RespResult {
@@ -251,112 +1139,801 @@ impl MaimemoClient {
             #[serde(rename = "errorCode")]
             error: Option<String>,
         }
-        let result: RespResult = send_request(
+        let resp = send_request(
             &self.config,
-            &self.client,
+            &self.context,
             &self.cookie_store,
             req_name,
             |url| url.to_string(),
             Some(&form),
         )
-        .await?
-        .json::<RespResult>()
         .await
-        .map_err(|e| format!("{:?}", e))?;
-        
+        .map_err(|e| self.save_error(e))?;
+        let body = read_body(&self.config, req_name, resp).await?;
+        let result = serde_json::from_slice::<RespResult>(&body).map_err(|e| format!("{:?}", e))?;
+
         if let Some(e) = &result.error {
             error!("save notepad failed: {:?}", result);
-            return Err(format!("save notepad failed: {}", e));
+            let msg = format!("save notepad failed: {}", e);
+            if e == WRONG_CAPTCHA {
+                return Err(SaveNotepadError::WrongCaptcha(msg));
+            }
+            return Err(self.save_error(msg));
         }
         debug!("save_notepad successful");
         Ok(())
     }
 
     /// 从response html body中取出单词文本
-    fn parse_notepad_text(html: &str) -> Result<String, String> {
+    ///
+    /// # Errors
+    ///
+    /// 如果html为登录页面或session过期的错误页面，返回[`NotepadContentsError::SessionExpired`]
+    fn parse_notepad_text(html: &str) -> Result<String, NotepadContentsError> {
         if html.is_empty() {
-            return Err("html is empty".to_string());
+            return Err("html is empty".to_string().into());
         }
         debug!("parsing notepad html");
         let id = "#content";
         let id_selector = Selector::parse(id).map_err(|e| format!("{:?}", e))?;
         let document = Html::parse_document(html);
-        document
-            .select(&id_selector)
-            .next()
-            .map(|e| e.inner_html())
-            .ok_or_else(|| {
-                error!("not found element {} in html: \n{}", id, html);
-                format!("not found element {} in html", id)
-            })
+        if let Some(e) = document.select(&id_selector).next() {
+            return Ok(e.inner_html());
+        }
+        let login_selector =
+            Selector::parse(LOGIN_FORM_SELECTOR).map_err(|e| format!("{:?}", e))?;
+        if document.select(&login_selector).next().is_some()
+            || SESSION_EXPIRED_MARKERS.iter().any(|m| html.contains(m))
+        {
+            debug!("found login page or session expired marker in notepad html");
+            return Err(NotepadContentsError::SessionExpired);
+        }
+        error!(
+            "not found element {} in html: \n{}",
+            id,
+            html.chars()
+                .take(ERROR_BODY_SNIPPET_LEN)
+                .collect::<String>()
+        );
+        Err(format!("not found element {} in html", id).into())
+    }
+}
+
+/// 通过验证码上传notepad到maimemo，验证码由provider回答
+pub struct CaptchaSink<'c, P> {
+    client: &'c MaimemoClient,
+    provider: P,
+    /// 下次上传使用的验证码，如`--captcha`，优先于复用的验证码
+    answer: Option<String>,
+    /// 上次被maimemo接受的验证码，上传多个notepads时先尝试复用
+    solved: Option<String>,
+}
+
+impl<'c, P: CaptchaProvider> CaptchaSink<'c, P> {
+    pub fn new(client: &'c MaimemoClient, provider: P) -> Self {
+        Self {
+            client,
+            provider,
+            answer: None,
+            solved: None,
+        }
+    }
+
+    pub fn with_answer(mut self, answer: Option<String>) -> Self {
+        self.answer = answer;
+        self
+    }
+
+    pub fn with_solved(mut self, solved: Option<String>) -> Self {
+        self.solved = solved;
+        self
+    }
+
+    /// 上次被接受的验证码，用于之后的上传
+    pub fn into_solved(self) -> Option<String> {
+        self.solved
+    }
+
+    /// 使用answer或获取新的验证码交给provider回答。被限流以外的失败为[`SaveNotepadError::ReadCaptcha`]
+    pub async fn read_captcha(&mut self) -> Result<String, SaveNotepadError> {
+        if let Some(answer) = self.answer.take() {
+            debug!("using the given captcha answer");
+            return Ok(answer);
+        }
+        trace!("loading captcha from maimemo service");
+        let captcha = match self.client.refresh_captcha().await {
+            Ok(captcha) => captcha,
+            Err(SaveNotepadError::Other(e)) => return Err(SaveNotepadError::ReadCaptcha(e)),
+            Err(e) => return Err(e),
+        };
+        captcha
+            .ensure_image()
+            .map_err(SaveNotepadError::ReadCaptcha)?;
+        self.provider
+            .solve(&captcha)
+            .await
+            .map_err(SaveNotepadError::ReadCaptcha)
+    }
+
+    /// 保存notepad。复用的验证码被拒绝时重新获取，其它错误不重试
+    pub async fn save(&mut self, notepad: &Notepad) -> Result<(), SaveNotepadError> {
+        loop {
+            let reused = self.solved.take().filter(|_| self.answer.is_none());
+            let is_reused = reused.is_some();
+            let captcha = match reused {
+                Some(captcha) => captcha,
+                None => self.read_captcha().await?,
+            };
+            match self
+                .client
+                .save_notepad(notepad.clone(), captcha.clone())
+                .await
+            {
+                Ok(()) => {
+                    self.solved = Some(captcha);
+                    return Ok(());
+                }
+                Err(SaveNotepadError::WrongCaptcha(_)) if is_reused => {
+                    debug!("the reused captcha was rejected. solving a new one");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<P: CaptchaProvider> NotepadSink for CaptchaSink<'_, P> {
+    /// 见[`CaptchaSink::save`]
+    fn push<'a>(&'a mut self, notepad: &'a Notepad) -> BoxFuture<'a, Result<bool, String>> {
+        Box::pin(async move { self.save(notepad).await.map(|_| true).map_err(String::from) })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    const CONFIG_PATH: &str = "config.yml";
+    use mock_server::{
+        recorded_response, MockResponse, MockServer, FIXTURE_CAPTCHA, PUBLIC_NOTEPADS_TOTAL,
+    };
+    use std::sync::atomic::Ordering;
+
+    fn temp_client(dir: &tempfile::TempDir) -> Result<(MaimemoClient, std::path::PathBuf), String> {
+        let cookie_path = dir.path().join("cookies.json");
+        let config = serde_yaml::from_str::<AppConfig>(&format!(
+            "username: a\npassword: b\ncookie_path: {}\ndictionary_path: notepads.json\n",
+            cookie_path.to_str().unwrap()
+        ))
+        .map_err(|e| format!("{:?}", e))?;
+        let mut client = MaimemoClient::new(config)?;
+        let url = reqwest::Url::parse("https://www.maimemo.com/").unwrap();
+        let cookie = cookie::Cookie::parse("userToken=token; Max-Age=86400").unwrap();
+        client
+            .cookie_store
+            .insert_raw(&cookie, &url)
+            .map_err(|e| format!("{:?}", e))?;
+        client.cookies_dirty = true;
+        Ok((client, cookie_path))
+    }
+
+    #[tokio::test]
+    async fn save_cookies_explicitly() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (mut client, cookie_path) = temp_client(&dir)?;
+        client.save().await?;
+        let contents = std::fs::read_to_string(&cookie_path).map_err(|e| format!("{:?}", e))?;
+        assert!(contents.contains("userToken"));
+
+        // 已保存后drop时不会再写入
+        std::fs::remove_file(&cookie_path).map_err(|e| format!("{:?}", e))?;
+        drop(client);
+        assert!(!cookie_path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn save_cookies_late_in_drop() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let (client, cookie_path) = temp_client(&dir)?;
+        std::fs::remove_file(&cookie_path).map_err(|e| format!("{:?}", e))?;
+        drop(client);
+        let contents = std::fs::read_to_string(&cookie_path).map_err(|e| format!("{:?}", e))?;
+        assert!(contents.contains("userToken"));
+        Ok(())
+    }
+    /// 通过录制的响应创建client，cookie等文件保存在dir中
+    fn recorded_client(
+        server: &MockServer,
+        dir: &tempfile::TempDir,
+    ) -> Result<MaimemoClient, String> {
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let context = HttpContext::from_config(&config.http())?;
+        MaimemoClient::with_context(config.maimemo(), Arc::new(context))
+    }
+
+    async fn logged_client(
+        server: &MockServer,
+        dir: &tempfile::TempDir,
+    ) -> Result<MaimemoClient, String> {
+        let mut client = recorded_client(server, dir)?;
+        client.login().await?;
+        Ok(client)
+    }
+
     #[tokio::test]
     async fn try_login() -> Result<(), String> {
-        init_log();
-        let config = Config::from_yaml_file(CONFIG_PATH).unwrap();
-        let mut client = MaimemoClient::new(config.maimemo.unwrap())?;
-        client.login().await.map_err(|e| format!("{:?}", e))?;
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = recorded_client(&server, &dir)?;
+        assert!(!client.has_logged());
+        client.login().await?;
+        assert_eq!(client.get_user_token_val(), Some("fixture-token"));
+        let login = &server.requests()[0];
+        assert_eq!(login.path, "http://www.maimemo.com/auth/login");
+        assert!(login.body_str().contains("password=fixture.123456"));
         Ok(())
     }
 
     #[tokio::test]
     async fn get_notepad_list() -> Result<(), String> {
-        init_log();
-        let config = Config::from_yaml_file(CONFIG_PATH).unwrap();
-        let mut client = MaimemoClient::new(config.maimemo.unwrap())?;
-        if !client.has_logged() {
-            client.login().await?;
-        }
-        let notepads = client.get_notepad_list().await?;
-        assert!(notepads.len() > 0);
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = recorded_client(&server, &dir)?;
+        assert_eq!(
+            client
+                .get_notepad_list(&NotepadQuery::default())
+                .await
+                .unwrap_err(),
+            "not logged in"
+        );
+        client.login().await?;
+        let notepads = client.get_notepad_list(&NotepadQuery::default()).await?;
+        assert_eq!(notepads.len(), 2);
+        assert_eq!(notepads[0].get_title(), "english");
+        assert_eq!(notepads[0].get_contents(), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn search_public_notepads() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = logged_client(&server, &dir).await?;
+        let notepads = client
+            .get_notepad_list(&NotepadQuery::public("GRE 3000", false, 35))
+            .await?;
+        assert_eq!(notepads.len(), 35);
+        assert_eq!(notepads[0].get_notepad_id(), "900001");
+        assert_eq!(notepads[0].get_brief(), "GRE 3000词 第1单元");
+        assert_eq!(notepads[1].get_brief(), "");
+        assert_eq!(notepads[1].is_private, 0);
+        let pages = server
+            .requests()
+            .into_iter()
+            .filter(|r| r.path.contains("/notepad/search"))
+            .map(|r| r.body_json())
+            .collect::<Vec<_>>();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0]["keyword"], "GRE 3000");
+        assert_eq!(pages[0]["scope"], "ALL");
+        assert_eq!(
+            (&pages[0]["offset"], &pages[0]["limit"]),
+            (&0.into(), &30.into())
+        );
+        assert_eq!(
+            (&pages[1]["offset"], &pages[1]["limit"]),
+            (&30.into(), &5.into())
+        );
+
+        // 不限制数量时获取total个
+        let query = NotepadQuery {
+            limit: None,
+            ..NotepadQuery::public("GRE 3000", false, 0)
+        };
+        assert_eq!(
+            client.get_notepad_list(&query).await?.len(),
+            PUBLIC_NOTEPADS_TOTAL
+        );
+        let recommended = client
+            .get_notepad_list(&NotepadQuery::public("GRE", true, 30))
+            .await?;
+        assert_eq!(recommended.len(), 1);
+        assert_eq!(recommended[0].get_title(), "GRE 3000 recommended");
         Ok(())
     }
 
     #[tokio::test]
     async fn get_notepad_contents() -> Result<(), String> {
-        init_log();
-        let config = Config::from_yaml_file(CONFIG_PATH).unwrap();
-        let mut client = MaimemoClient::new(config.maimemo.unwrap())?;
-        if !client.has_logged() {
-            client.login().await?;
-        }
-        let notepads = client.get_notepad_list().await?;
-        // for notepad in notepads {
-            let contents = client.get_notepad_contents(&notepads[0].notepad_id).await?;
-            assert!(contents.len() > 0);
-            assert!(contents.contains("\n"));
-        // }
+        // 已删除的notepad返回404
+        let server = MockServer::start(|req| {
+            if req.path.ends_with("/notepad/detail/404") {
+                return MockResponse::text(404, "not found");
+            }
+            recorded_response(req)
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = logged_client(&server, &dir).await?;
+        let notepads = client.get_notepad_list(&NotepadQuery::default()).await?;
+        let contents = client.get_notepad_contents(&notepads[0].notepad_id).await?;
+        assert_eq!(contents, "accommodate\nbenevolent");
+        assert_eq!(
+            client.get_notepad_contents("404").await,
+            Err(NotepadContentsError::NotFound {
+                notepad_id: "404".to_string()
+            })
+        );
+
+        let notepads = client.get_notepads().await?;
+        assert_eq!(notepads[1].get_contents(), Some(""));
+        Ok(())
+    }
+
+    #[test]
+    fn captcha_answers() {
+        assert_eq!(
+            normalize_captcha(" aB12\n", CaptchaCase::Keep).as_deref(),
+            Ok("aB12")
+        );
+        assert_eq!(
+            normalize_captcha("aB12", CaptchaCase::Lower).as_deref(),
+            Ok("ab12")
+        );
+        assert_eq!(
+            normalize_captcha("\taB12 ", CaptchaCase::Upper).as_deref(),
+            Ok("AB12")
+        );
+        assert_eq!(
+            normalize_captcha(" \r\n", CaptchaCase::Keep),
+            Err("captcha is empty".to_string())
+        );
+        for invalid in &["ab 12", "ab-12", "验证码", "１２"] {
+            let e = normalize_captcha(invalid, CaptchaCase::Keep).unwrap_err();
+            assert!(e.contains("invalid character"), "{}", e);
+        }
+    }
+
+    #[test]
+    fn brief_from_contents() {
+        let contents = "# 2026-10-16 Auto insert\n\n  accommodate \n容纳\n# chapter\nbenevolent\n仁慈的 kind\nrun\nwalk";
+        assert_eq!(
+            generate_brief(contents, 4),
+            "accommodate、容纳、benevolent、仁慈的 kind"
+        );
+        assert_eq!(generate_brief(contents, 1), "accommodate");
+        assert_eq!(generate_brief("# only header\n", 5), "");
+
+        // 按字符截断，不会截断在中文字符的中间
+        let long = "很长的一行单词".repeat(30);
+        let brief = generate_brief(&long, 5);
+        assert_eq!(brief.chars().count(), MAX_BRIEF_CHARS);
+        assert!(brief.starts_with("很长的一行单词很长"));
+        assert!(brief.ends_with('…'));
+        let brief = generate_brief(&format!("{}\nrun", "a".repeat(MAX_BRIEF_CHARS)), 1);
+        assert_eq!(brief, "a".repeat(MAX_BRIEF_CHARS));
+    }
+
+    #[test]
+    fn parse_notepad_pages() {
+        let detail = include_str!("../../tests/fixtures/maimemo-notepad-detail.html");
+        assert_eq!(
+            MaimemoClient::parse_notepad_text(detail).unwrap(),
+            "accommodate\nbenevolent"
+        );
+        for page in [
+            include_str!("../../tests/fixtures/maimemo-login-page.html"),
+            include_str!("../../tests/fixtures/maimemo-error-page.html"),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                MaimemoClient::parse_notepad_text(page).unwrap_err(),
+                NotepadContentsError::SessionExpired
+            );
+        }
+        let unknown = format!("<html><body>{}</body></html>", "x".repeat(2000));
+        let e = MaimemoClient::parse_notepad_text(&unknown)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("#content"), "{}", e);
+    }
+
+    #[tokio::test]
+    async fn relogin_when_session_expired() -> Result<(), String> {
+        // 前expired_times次获取notepad内容时返回登录页面
+        let recorded_server = |expired_times: usize| {
+            let expired = std::sync::atomic::AtomicUsize::new(expired_times);
+            MockServer::start(move |req| {
+                if req
+                    .path
+                    .starts_with("http://www.maimemo.com/notepad/detail/")
+                    && expired
+                        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                        .is_ok()
+                {
+                    return MockResponse::text(
+                        200,
+                        include_str!("../../tests/fixtures/maimemo-login-page.html"),
+                    );
+                }
+                recorded_response(req)
+            })
+        };
+        let logins = |server: &MockServer| {
+            server
+                .requests()
+                .iter()
+                .filter(|r| r.path.ends_with("/auth/login"))
+                .count()
+        };
+
+        let server = recorded_server(1).await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = logged_client(&server, &dir).await?;
+        let notepads = client.get_notepads().await?;
+        assert_eq!(notepads[0].get_contents(), Some("accommodate\nbenevolent"));
+        assert_eq!(logins(&server), 2);
+
+        // 仅重试一次
+        let server = recorded_server(2).await;
+        let mut client = logged_client(&server, &dir).await?;
+        assert_eq!(
+            client.get_notepads().await.unwrap_err(),
+            format!(
+                "{}. fetched 0 of 2 notepads before failure",
+                SESSION_EXPIRED
+            )
+        );
+        assert_eq!(logins(&server), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn sort_notepads_by() {
+        let notepads = |times: &[(&str, Option<&str>, &str)]| {
+            times
+                .iter()
+                .map(|(id, updated, title)| {
+                    serde_json::from_value::<Notepad>(serde_json::json!({
+                        "notepad_id": id,
+                        "title": title,
+                        "created_time": "2020-10-17 10:00:00",
+                        "updated_time": updated,
+                    }))
+                    .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let sorted = |mut notepads: Vec<Notepad>, sort| {
+            sort_notepads(&mut notepads, sort);
+            notepads
+                .iter()
+                .map(|n| n.notepad_id.clone())
+                .collect::<Vec<_>>()
+        };
+        // 混合的时间格式与缺少的时间
+        let mixed = notepads(&[
+            ("10", Some("2020-10-18 10:00:00"), "english"),
+            ("9", None, "Empty"),
+            ("8", Some("2021-01-02T03:04:05+08:00"), "gre"),
+            ("11", Some("2020-10-18"), "cet4"),
+            ("7", Some("2020-10-18 10:00:00"), "abc"),
+        ]);
+        let cases = vec![
+            (NotepadSort::Updated, vec!["8", "7", "10", "11", "9"]),
+            (NotepadSort::Created, vec!["7", "8", "9", "10", "11"]),
+            (NotepadSort::Title, vec!["7", "11", "9", "10", "8"]),
+            (NotepadSort::Id, vec!["7", "8", "9", "10", "11"]),
+        ];
+        for (sort, expected) in cases {
+            assert_eq!(sorted(mixed.clone(), sort), expected, "{:?}", sort);
+        }
+        // 无法解析时按字符串比较
+        let unexpected = notepads(&[
+            ("1", Some("2020/10/18"), ""),
+            ("2", Some("2021-01-02 03:04:05"), ""),
+            ("3", None, ""),
+        ]);
+        assert_eq!(
+            sorted(unexpected, NotepadSort::Updated),
+            vec!["2", "1", "3"]
+        );
+
+        assert_eq!(
+            parse_notepad_time(" 2021-01-02T03:04:05+08:00"),
+            parse_notepad_time("2021-01-01 19:04:05")
+        );
+        assert_eq!(parse_notepad_time("18/10/2020"), None);
+        assert_eq!("title".parse::<NotepadSort>(), Ok(NotepadSort::Title));
+        assert!("size".parse::<NotepadSort>().is_err());
+    }
+
+    #[tokio::test]
+    async fn resume_notepads() -> Result<(), String> {
+        let details = |server: &MockServer| {
+            server
+                .requests()
+                .into_iter()
+                .filter(|r| r.path.contains("/notepad/detail/"))
+                .map(|r| r.path.rsplit('/').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        // 获取第二个notepad的内容时失败
+        let server = MockServer::start(|req| {
+            if req.path.ends_with("/notepad/detail/695836") {
+                return MockResponse::text(500, "internal error");
+            }
+            recorded_response(req)
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = logged_client(&server, &dir).await?;
+        let e = client.get_notepads_cached(&[]).await.unwrap_err();
+        assert_eq!((e.fetched.len(), e.total), (1, 2));
+        assert_eq!(e.fetched[0].get_contents(), Some("accommodate\nbenevolent"));
+        assert!(
+            e.to_string()
+                .ends_with("fetched 1 of 2 notepads before failure"),
+            "{}",
+            e
+        );
+        assert_eq!(details(&server), vec!["695835", "695836"]);
+
+        // 仅请求未缓存的notepad
+        let server = MockServer::start_recorded().await;
+        let mut client = logged_client(&server, &dir).await?;
+        let notepads = client.get_notepads_cached(&e.fetched).await.unwrap();
+        assert_eq!(notepads.len(), 2);
+        assert_eq!(notepads[0], e.fetched[0]);
+        assert_eq!(notepads[1].get_contents(), Some(""));
+        assert_eq!(details(&server), vec!["695836"]);
+
+        // updated_time改变时重新请求
+        let mut cached = notepads.clone();
+        cached[0].updated_time = Some("2020-10-17 10:00:00".to_string());
+        cached[1].updated_time = None;
+        let server = MockServer::start_recorded().await;
+        let mut client = logged_client(&server, &dir).await?;
+        assert_eq!(client.get_notepads_cached(&cached).await.unwrap(), notepads);
+        assert_eq!(details(&server), vec!["695835", "695836"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn content_cache() -> Result<(), String> {
+        let details = |server: &MockServer| {
+            server
+                .requests()
+                .iter()
+                .filter(|r| r.path.contains("/notepad/detail/"))
+                .count()
+        };
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let cache_path = dir.path().join("contents.json");
+        let cached_client = |server: &MockServer| -> Result<MaimemoClient, String> {
+            let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+            let context = HttpContext::from_config(&config.http())?;
+            let config = config
+                .maimemo()
+                .with_content_cache_path(cache_path.to_str().unwrap());
+            MaimemoClient::with_context(config, Arc::new(context))
+        };
+        let server = MockServer::start_recorded().await;
+        let mut client = cached_client(&server)?;
+        client.login().await?;
+        let notepads = client.get_notepads().await?;
+        assert_eq!(details(&server), 2);
+        // 第二次获取时命中缓存
+        assert_eq!(client.get_notepads().await?, notepads);
+        assert_eq!(details(&server), 2);
+        client.save().await?;
+        assert!(cache_path.exists());
+
+        // 从文件中加载缓存
+        let server = MockServer::start_recorded().await;
+        let mut client = cached_client(&server)?;
+        client.login().await?;
+        assert_eq!(client.get_notepads().await?, notepads);
+        assert_eq!(details(&server), 0);
+
+        client.clear_cache()?;
+        assert!(!cache_path.exists());
+        client.get_notepads().await?;
+        assert_eq!(details(&server), 2);
+
+        // 禁用时总是请求
+        let server = MockServer::start_recorded().await;
+        let mut client = cached_client(&server)?.with_content_cache(false);
+        client.login().await?;
+        client.get_notepads().await?;
+        client.get_notepads().await?;
+        assert_eq!(details(&server), 4);
         Ok(())
     }
 
     #[allow(dead_code)]
     fn init_log() {
-        pretty_env_logger::formatted_builder()
-            // .format(|buf, record| writeln!(buf, "{}: {}", record.level(), record.args()))
-            .filter_module("dict", log::LevelFilter::Trace)
-            .init();
+        crate::logging::init(
+            2,
+            None,
+            None,
+            crate::color::ColorChoice::Auto,
+            &crate::config::LoggingConfig::default(),
+        )
+        .unwrap();
     }
 
     #[tokio::test]
     async fn refresh_captcha() -> Result<(), String> {
-        let config = Config::from_yaml_file(CONFIG_PATH).unwrap();
-        let mut client = MaimemoClient::new(config.maimemo.unwrap())?;
-        if !client.has_logged() {
-            client.login().await?;
-        }
-        let data = client.refresh_captcha().await?;
-        assert!(data.len() > 0);
-        // assert!(path.is_file());
-        // let new_file = std::fs::read(path).map_err(|e| format!("{:?}", e))?;
-        // if let Ok(old_file) = old_file {
-        //     assert_ne!(old_file, new_file);
-        // }
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let client = logged_client(&server, &dir).await?;
+        let captcha = client.refresh_captcha().await?;
+        assert_eq!(
+            captcha.bytes,
+            include_bytes!("../../tests/fixtures/maimemo-captcha.png").to_vec()
+        );
+        assert_eq!(captcha.content_type.as_deref(), Some("image/png"));
+        captcha.ensure_image()?;
+
+        // 返回错误页面而不是图片
+        let server = MockServer::start(|req| {
+            if req
+                .path
+                .starts_with("http://www.maimemo.com/service/captcha/")
+            {
+                return MockResponse::text(
+                    200,
+                    include_str!("../../tests/fixtures/maimemo-error-page.html"),
+                );
+            }
+            recorded_response(req)
+        })
+        .await;
+        let client = logged_client(&server, &dir).await?;
+        let e = client.refresh_captcha().await?.ensure_image().unwrap_err();
+        assert!(e.contains("captcha refresh failed"), "{}", e);
+        assert!(e.contains("text/html"), "{}", e);
+        assert!(e.contains("登录已过期"), "{}", e);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn captcha_throttling() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = server.write_fixture_config(dir.path());
+        let contents = std::fs::read_to_string(&path).map_err(|e| format!("{:?}", e))?;
+        std::fs::write(
+            &path,
+            contents.replace("maimemo:\n", "maimemo:\n    captcha_min_interval_secs: 1\n"),
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        let mut config = Config::from_yaml_file(&path)?;
+        let context = HttpContext::from_config(&config.http())?;
+        let mut client = MaimemoClient::with_context(config.maimemo(), Arc::new(context))?;
+        client.login().await?;
+
+        client.refresh_captcha().await?;
+
+        let delay = |e: &str| match client.save_error(e.to_string()) {
+            SaveNotepadError::Throttled { delay, .. } => Some(delay),
+            _ => None,
+        };
+        let e = "Response code error: 429 Too Many Requests, retry after: 30s, body: ";
+        assert_eq!(delay(e), Some(Duration::from_secs(30)));
+        let e = "Response code error: 429 Too Many Requests, body: ";
+        assert_eq!(delay(e), Some(Duration::from_secs(1)));
+        assert_eq!(
+            delay("save notepad failed: too_frequent"),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(delay("save notepad failed: captcha_error"), None);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn captcha_min_interval() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let client = |interval: &str| {
+            let config = serde_yaml::from_str::<AppConfig>(&format!(
+                "username: a\npassword: b\ncookie_path: {}\ndictionary_path: notepads.json\n{}",
+                dir.path().join("cookies.json").to_str().unwrap(),
+                interval
+            ))
+            .map_err(|e| format!("{:?}", e))?;
+            MaimemoClient::new(config)
+        };
+        // 未配置时不等待
+        let start = Instant::now();
+        let unlimited = client("")?;
+        unlimited.wait_captcha_interval().await;
+        unlimited.wait_captcha_interval().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        let limited = client("captcha_min_interval_secs: 1\n")?;
+        limited.wait_captcha_interval().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limited.wait_captcha_interval().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn save_notepad() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = logged_client(&server, &dir).await?;
+        let mut notepad = client
+            .get_notepad_list(&NotepadQuery::default())
+            .await?
+            .remove(0);
+        notepad.set_contents(Some("test words".to_string()));
+
+        let e = client
+            .save_notepad(notepad.clone(), "0000".to_string())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&e, SaveNotepadError::WrongCaptcha(e) if e.contains(WRONG_CAPTCHA)),
+            "{}",
+            e
+        );
+        client
+            .save_notepad(notepad, FIXTURE_CAPTCHA.to_string())
+            .await?;
+        let save = server.requests().pop().unwrap();
+        assert!(save.body_str().contains("content=test+words"));
+        let keys = save
+            .body_str()
+            .split('&')
+            .map(|kv| kv.split('=').next().unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec!["id", "title", "brief", "content", "is_private", "captcha"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn captcha_sink() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = logged_client(&server, &dir).await?;
+        let mut notepad = client
+            .get_notepad_list(&NotepadQuery::default())
+            .await?
+            .remove(0);
+        notepad.set_contents(Some("test words".to_string()));
+
+        let solved = std::cell::Cell::new(0);
+        let provider = |_: &Captcha| {
+            solved.set(solved.get() + 1);
+            Ok(FIXTURE_CAPTCHA.to_string())
+        };
+        // 复用的验证码被拒绝时重新获取
+        let mut sink = CaptchaSink::new(&client, provider).with_solved(Some("0000".to_string()));
+        assert!(sink.push(&notepad).await?);
+        assert_eq!(solved.get(), 1);
+        // 之后复用被接受的验证码
+        assert!(sink.push(&notepad).await?);
+        assert_eq!(solved.get(), 1);
+        assert_eq!(sink.into_solved().as_deref(), Some(FIXTURE_CAPTCHA));
+
+        // 给定的验证码被拒绝时不重试
+        let mut sink = CaptchaSink::new(&client, provider)
+            .with_answer(Some("0000".to_string()))
+            .with_solved(Some(FIXTURE_CAPTCHA.to_string()));
+        let e = sink.push(&notepad).await.unwrap_err();
+        assert!(e.contains(WRONG_CAPTCHA), "{}", e);
+        assert_eq!(solved.get(), 1);
+
+        let cancel = |_: &Captcha| Err("canceled".to_string());
+        let e = CaptchaSink::new(&client, cancel)
+            .push(&notepad)
+            .await
+            .unwrap_err();
+        assert_eq!(e, "read captcha error: canceled");
         Ok(())
     }
 }
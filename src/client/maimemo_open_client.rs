@@ -0,0 +1,487 @@
+//! 墨墨背单词开放平台的云词本api，通过token授权，不需要登录与验证码。
+//! 见<https://open.maimemo.com>
+use crate::client::{context::HttpContext, maimemo_client::Notepad, *};
+use crate::config::*;
+use crate::events::Event;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// 默认的云词本api地址
+pub const DEFAULT_API_URL: &str = "https://open.maimemo.com/open/api/v1/notepads";
+
+/// 分页获取云词本列表时每页的数量
+const PAGE_SIZE: usize = 100;
+
+/// 公开的云词本状态，其它状态如`UNPUBLISHED`视为私有
+const STATUS_PUBLISHED: &str = "PUBLISHED";
+
+const STATUS_UNPUBLISHED: &str = "UNPUBLISHED";
+
+/// 创建云词本时使用的标签，open api要求至少一个标签
+const DEFAULT_TAG: &str = "其他";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ResponseResult<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<ApiError>,
+    data: Option<T>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiError {
+    #[serde(default)]
+    pub code: String,
+    #[serde(default)]
+    pub msg: String,
+}
+
+/// open api中的云词本。列表中不包含content
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenNotepad {
+    pub id: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub brief: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub created_time: Option<String>,
+    #[serde(default)]
+    pub updated_time: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct NotepadsData {
+    notepads: Vec<OpenNotepad>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct NotepadData {
+    notepad: OpenNotepad,
+}
+
+/// 创建与更新云词本的请求body
+#[derive(Serialize, Debug)]
+struct NotepadBody<'a> {
+    notepad: NotepadFields<'a>,
+}
+
+#[derive(Serialize, Debug)]
+struct NotepadFields<'a> {
+    status: &'a str,
+    content: &'a str,
+    title: &'a str,
+    brief: &'a str,
+    tags: &'a [String],
+}
+
+impl<T> ResponseResult<T> {
+    /// success为false或没有data时返回errors
+    fn into_data(self, req_name: &str) -> Result<T, String> {
+        match self.data {
+            Some(data) if self.success => Ok(data),
+            _ => Err(format!(
+                "maimemo open api {} error: {}",
+                req_name,
+                self.errors
+                    .iter()
+                    .map(|e| format!("{} {}", e.code, e.msg))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+impl From<OpenNotepad> for Notepad {
+    fn from(n: OpenNotepad) -> Self {
+        let mut notepad = Notepad::new(&n.title, &n.brief, String::new())
+            .with_id(&n.id)
+            .with_private(n.status != STATUS_PUBLISHED)
+            .with_times(n.created_time, n.updated_time);
+        notepad.set_contents(n.content);
+        notepad
+    }
+}
+
+/// 未配置的请求使用默认的api，配置文件中的请求优先
+///
+/// # Errors
+///
+/// 如果未配置api_token
+pub fn with_default_requests(config: AppConfig) -> Result<AppConfig, String> {
+    let token = config
+        .get_api_token()
+        .ok_or("not found maimemo api_token in config")?;
+    let authorization = format!("Bearer {}", token);
+    let url = config
+        .get_api_url()
+        .unwrap_or(DEFAULT_API_URL)
+        .trim_end_matches('/')
+        .to_string();
+    let headers = [
+        ("authorization", authorization.as_str()),
+        ("accept", "application/json"),
+        ("content-type", "application/json"),
+    ]
+    .iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect::<HashMap<_, _>>();
+    Ok(config
+        .with_default_request(
+            "open-list-notepads",
+            RequestConfig::new(&url, "GET", headers.clone()),
+        )
+        .with_default_request(
+            "open-get-notepad",
+            RequestConfig::new(&url, "GET", headers.clone()),
+        )
+        .with_default_request(
+            "open-create-notepad",
+            RequestConfig::new(&url, "POST", headers.clone()).with_ok_status(&[200, 201]),
+        )
+        .with_default_request(
+            "open-update-notepad",
+            RequestConfig::new(&url, "POST", headers.clone()),
+        )
+        .with_default_request(
+            "open-delete-notepad",
+            RequestConfig::new(&url, "DELETE", headers).with_ok_status(&[200, 204]),
+        ))
+}
+
+/// 通过maimemo open api管理云词本，与[`MaimemoClient`](maimemo_client::MaimemoClient)
+/// 不同，不使用cookies
+pub struct MaimemoOpenClient {
+    context: Arc<HttpContext>,
+    config: AppConfig,
+}
+
+impl MaimemoOpenClient {
+    pub fn new(config: AppConfig) -> Result<Self, String> {
+        Self::with_context(config, Arc::new(HttpContext::new()?))
+    }
+
+    /// 使用共享的http context创建client。未配置的请求使用默认的api，见[`with_default_requests`]
+    ///
+    /// # Errors
+    ///
+    /// 如果未配置api_token
+    pub fn with_context(config: AppConfig, context: Arc<HttpContext>) -> Result<Self, String> {
+        let config = with_default_requests(config)?;
+        Ok(Self { context, config })
+    }
+
+    pub fn context(&self) -> &HttpContext {
+        &self.context
+    }
+
+    /// 分页获取所有云词本，不包含内容
+    pub async fn list_notepads(&self) -> Result<Vec<Notepad>, String> {
+        let mut notepads = vec![];
+        for offset in (0..).step_by(PAGE_SIZE) {
+            let resp = self
+                .send(
+                    "open-list-notepads",
+                    |url| format!("{}?limit={}&offset={}", url, PAGE_SIZE, offset),
+                    None,
+                )
+                .await?;
+            let body = read_body(&self.config, "open-list-notepads", resp).await?;
+            let page = serde_json::from_slice::<ResponseResult<NotepadsData>>(&body)
+                .map_err(|e| format!("parse notepads error: {:?}, offset: {}", e, offset))?
+                .into_data("open-list-notepads")?
+                .notepads;
+            trace!("found {} notepads at offset {}", page.len(), offset);
+            let last = page.len() < PAGE_SIZE;
+            notepads.extend(page.into_iter().map(Notepad::from));
+            if last {
+                break;
+            }
+        }
+        debug!("found {} notepads", notepads.len());
+        Ok(notepads)
+    }
+
+    /// 获取notepad_id的元数据与内容，不存在时返回None
+    pub async fn get_notepad(&self, notepad_id: &str) -> Result<Option<Notepad>, String> {
+        Ok(self.get_open_notepad(notepad_id).await?.map(Notepad::from))
+    }
+
+    /// 获取所有云词本及其内容
+    pub async fn get_notepads(&self) -> Result<Vec<Notepad>, String> {
+        let mut notepads = vec![];
+        for info in self.list_notepads().await? {
+            let notepad_id = info.get_notepad_id().to_string();
+            match self.get_notepad(&notepad_id).await? {
+                Some(notepad) => {
+                    self.context.emit(Event::NotepadFetched {
+                        id: notepad_id.clone(),
+                    });
+                    notepads.push(notepad);
+                }
+                None => warn!("notepad {} was deleted while fetching", notepad_id),
+            }
+        }
+        Ok(notepads)
+    }
+
+    /// 创建私有的云词本，返回maimemo中的notepad
+    pub async fn create_notepad(&self, notepad: &Notepad) -> Result<Notepad, String> {
+        self.context.check_writable("open-create-notepad")?;
+        let tags = [DEFAULT_TAG.to_string()];
+        let body = NotepadBody {
+            notepad: NotepadFields {
+                status: STATUS_UNPUBLISHED,
+                content: notepad.get_contents().unwrap_or_default(),
+                title: notepad.get_title(),
+                brief: notepad.get_brief(),
+                tags: &tags,
+            },
+        };
+        self.send_notepad("open-create-notepad", |url| url.to_string(), &body)
+            .await
+    }
+
+    /// 用notepad的标题、简介与内容更新maimemo中相同id的云词本。状态与标签不变，
+    /// 返回更新后的notepad
+    pub async fn update_notepad(&self, notepad: &Notepad) -> Result<Notepad, String> {
+        self.context.check_writable("open-update-notepad")?;
+        let notepad_id = notepad.get_notepad_id();
+        let remote = self
+            .get_open_notepad(notepad_id)
+            .await?
+            .ok_or_else(|| format!("not found notepad {} in maimemo", notepad_id))?;
+        let body = NotepadBody {
+            notepad: NotepadFields {
+                status: &remote.status,
+                content: notepad.get_contents().unwrap_or_default(),
+                title: notepad.get_title(),
+                brief: notepad.get_brief(),
+                tags: &remote.tags,
+            },
+        };
+        self.send_notepad(
+            "open-update-notepad",
+            |url| format!("{}/{}", url, notepad_id),
+            &body,
+        )
+        .await
+    }
+
+    pub async fn delete_notepad(&self, notepad_id: &str) -> Result<(), String> {
+        self.context.check_writable("open-delete-notepad")?;
+        self.send(
+            "open-delete-notepad",
+            |url| format!("{}/{}", url, notepad_id),
+            None,
+        )
+        .await?;
+        debug!("deleted notepad {}", notepad_id);
+        Ok(())
+    }
+
+    async fn get_open_notepad(&self, notepad_id: &str) -> Result<Option<OpenNotepad>, String> {
+        let resp = send_request_without_cookies(
+            &self.config,
+            &self.context,
+            "open-get-notepad",
+            |url| format!("{}/{}", url, notepad_id),
+            None::<&str>,
+        )
+        .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            debug!("notepad {} is not found", notepad_id);
+            return Ok(None);
+        }
+        let resp = check_status(&self.config, "open-get-notepad", resp).await?;
+        let body = read_body(&self.config, "open-get-notepad", resp).await?;
+        serde_json::from_slice::<ResponseResult<NotepadData>>(&body)
+            .map_err(|e| format!("parse notepad {} error: {:?}", notepad_id, e))?
+            .into_data("open-get-notepad")
+            .map(|data| Some(data.notepad))
+    }
+
+    /// 不使用cookies发送请求，response.status不在`ok_status`中时返回error
+    async fn send<U: FnOnce(&str) -> String>(
+        &self,
+        req_name: &str,
+        url_handler: U,
+        body: Option<&NotepadBody<'_>>,
+    ) -> Result<reqwest::Response, String> {
+        let resp =
+            send_request_without_cookies(&self.config, &self.context, req_name, url_handler, body)
+                .await?;
+        check_status(&self.config, req_name, resp).await
+    }
+
+    async fn send_notepad<U: FnOnce(&str) -> String>(
+        &self,
+        req_name: &str,
+        url_handler: U,
+        body: &NotepadBody<'_>,
+    ) -> Result<Notepad, String> {
+        let resp = self.send(req_name, url_handler, Some(body)).await?;
+        let body = read_body(&self.config, req_name, resp).await?;
+        let notepad = serde_json::from_slice::<ResponseResult<NotepadData>>(&body)
+            .map_err(|e| format!("parse {} response error: {:?}", req_name, e))?
+            .into_data(req_name)?
+            .notepad;
+        debug!("{} notepad {} successful", req_name, notepad.id);
+        Ok(notepad.into())
+    }
+}
+
+impl NotepadSink for MaimemoOpenClient {
+    /// 不需要验证码，不会询问用户
+    fn push<'a>(&'a mut self, notepad: &'a Notepad) -> BoxFuture<'a, Result<bool, String>> {
+        Box::pin(async move { self.update_notepad(notepad).await.map(|_| true) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::{maimemo_open_api, MockResponse, MockServer, FIXTURE_API_TOKEN};
+
+    fn open_config(url: &str) -> AppConfig {
+        serde_yaml::from_str(&format!(
+            "dictionary_path: maimemo.json
+api_token: {}
+api_url: {}
+",
+            FIXTURE_API_TOKEN, url
+        ))
+        .unwrap()
+    }
+
+    fn client(server: &MockServer) -> Result<MaimemoOpenClient, String> {
+        MaimemoOpenClient::new(open_config(&server.url("/notepads")))
+    }
+
+    #[test]
+    fn deserialize_fixtures() {
+        let list = serde_json::from_str::<ResponseResult<NotepadsData>>(include_str!(
+            "../../tests/fixtures/maimemo-open-notepads.json"
+        ))
+        .unwrap()
+        .into_data("list")
+        .unwrap();
+        assert_eq!(list.notepads.len(), 2);
+        assert_eq!(list.notepads[0].content, None);
+        assert_eq!(list.notepads[1].tags, vec!["考研".to_string()]);
+
+        let notepad: Notepad = serde_json::from_str::<ResponseResult<NotepadData>>(include_str!(
+            "../../tests/fixtures/maimemo-open-notepad.json"
+        ))
+        .unwrap()
+        .into_data("get")
+        .unwrap()
+        .notepad
+        .into();
+        assert_eq!(notepad.get_notepad_id(), "695835");
+        assert_eq!(notepad.get_title(), "english");
+        assert!(notepad.is_private());
+        assert_eq!(notepad.get_contents(), Some("accommodate\nbenevolent"));
+        assert_eq!(notepad.get_updated_time(), Some("2024-05-01T10:00:00.000Z"));
+
+        let e = serde_json::from_str::<ResponseResult<NotepadData>>(
+            r#"{"success":false,"errors":[{"code":"auth_failed","msg":"invalid token","info":""}]}"#,
+        )
+        .unwrap()
+        .into_data("get")
+        .unwrap_err();
+        assert!(e.contains("auth_failed invalid token"), "{}", e);
+    }
+
+    #[tokio::test]
+    async fn list_and_get() -> Result<(), String> {
+        let server = MockServer::start(maimemo_open_api()).await;
+        let client = client(&server)?;
+        let notepads = client.list_notepads().await?;
+        assert_eq!(notepads.len(), 2);
+        assert!(!notepads[1].is_private());
+
+        assert!(client.get_notepad("1").await?.is_none());
+        // 695836在列表中但获取时已不存在，被跳过
+        let notepads = client.get_notepads().await?;
+        assert_eq!(notepads.len(), 1);
+        assert_eq!(notepads[0].get_contents(), Some("accommodate\nbenevolent"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_flow() -> Result<(), String> {
+        let server = MockServer::start(maimemo_open_api()).await;
+        let mut client = client(&server)?;
+        let mut notepad = client.get_notepad("695835").await?.unwrap();
+        notepad.set_contents(Some("accommodate\nbenevolent\nserendipity".to_string()));
+        assert!(client.push(&notepad).await?);
+
+        let requests = server.requests();
+        // get, get before update, update
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|r| r.header("cookie").is_none()));
+        let update = &requests[2];
+        assert_eq!(update.method, "POST");
+        assert_eq!(update.path, "/notepads/695835");
+        // 状态与标签保持maimemo中的值
+        assert_eq!(
+            update.body_json(),
+            serde_json::json!({"notepad": {
+                "status": "UNPUBLISHED",
+                "content": "accommodate\nbenevolent\nserendipity",
+                "title": "english",
+                "brief": "youdao words",
+                "tags": ["英语"],
+            }})
+        );
+        let updated = client.get_notepad("695835").await?.unwrap();
+        assert!(updated.get_contents().unwrap().ends_with("serendipity"));
+        assert_eq!(updated.get_updated_time(), Some("2024-05-02T08:00:00.000Z"));
+
+        let e = client
+            .update_notepad(&notepad.clone().with_id("1"))
+            .await
+            .unwrap_err();
+        assert!(e.contains("not found notepad 1"), "{}", e);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_and_delete() -> Result<(), String> {
+        let server = MockServer::start(maimemo_open_api()).await;
+        let client = client(&server)?;
+        let created = client
+            .create_notepad(&Notepad::new("new", "brief", "run\nwalk".to_string()))
+            .await?;
+        assert_eq!(created.get_notepad_id(), "700000");
+        assert!(created.is_private());
+        assert_eq!(
+            server.requests()[0].body_json()["notepad"]["tags"],
+            serde_json::json!([DEFAULT_TAG])
+        );
+        client.delete_notepad("695835").await?;
+        assert_eq!(server.requests()[1].method, "DELETE");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unauthorized() -> Result<(), String> {
+        let server = MockServer::start(|_| MockResponse::new(401)).await;
+        let e = client(&server)?.list_notepads().await.unwrap_err();
+        assert!(e.contains("401"), "{}", e);
+
+        let config = serde_yaml::from_str("dictionary_path: maimemo.json").unwrap();
+        let e = MaimemoOpenClient::new(config).err().unwrap();
+        assert!(e.contains("api_token"), "{}", e);
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+//! 按请求名称统计的请求次数、耗时与接收的字节数，用于`--timings`与GUI等包装程序。
+//!
+//! [`HttpContext`](super::context::HttpContext)中共享一个[`MetricsRegistry`]，
+//! 由[`send_request`](super::send_request)与[`read_body`](super::read_body)更新
+use crate::table::Table;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// 一个请求名称的统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Entry {
+    count: usize,
+    /// 失败的次数，包括status不在ok_status中的
+    errors: usize,
+    elapsed: Duration,
+    bytes: u64,
+}
+
+/// 请求的统计，按请求名称保存
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    entries: BTreeMap<String, Entry>,
+}
+
+/// 一个请求名称的统计结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequestTimings {
+    pub name: String,
+    pub count: usize,
+    pub errors: usize,
+    pub total_ms: u64,
+    pub avg_ms: u64,
+    /// 通过[`read_body`](super::read_body)读取的body字节数
+    pub bytes: u64,
+}
+
+/// 所有请求的统计结果，requests按名称排序
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Timings {
+    pub requests: Vec<RequestTimings>,
+    pub total: RequestTimings,
+}
+
+impl MetricsRegistry {
+    /// 记录一次请求，elapsed为收到response或失败时的耗时
+    pub fn record_request(&mut self, name: &str, elapsed: Duration, error: bool) {
+        let entry = self.entries.entry(name.to_string()).or_default();
+        entry.count += 1;
+        entry.elapsed += elapsed;
+        if error {
+            entry.errors += 1;
+        }
+    }
+
+    /// 记录name的response body的字节数
+    pub fn record_bytes(&mut self, name: &str, bytes: usize) {
+        self.entries.entry(name.to_string()).or_default().bytes += bytes as u64;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 当前的统计结果
+    pub fn timings(&self) -> Timings {
+        let mut total = Entry::default();
+        let requests = self
+            .entries
+            .iter()
+            .map(|(name, entry)| {
+                total.count += entry.count;
+                total.errors += entry.errors;
+                total.elapsed += entry.elapsed;
+                total.bytes += entry.bytes;
+                entry.timings(name)
+            })
+            .collect();
+        Timings {
+            requests,
+            total: total.timings("total"),
+        }
+    }
+}
+
+impl Entry {
+    fn timings(&self, name: &str) -> RequestTimings {
+        let total_ms = self.elapsed.as_millis() as u64;
+        RequestTimings {
+            name: name.to_string(),
+            count: self.count,
+            errors: self.errors,
+            total_ms,
+            avg_ms: total_ms.checked_div(self.count as u64).unwrap_or_default(),
+            bytes: self.bytes,
+        }
+    }
+}
+
+impl Timings {
+    /// 每个请求一行的表格，最后一行为总计。width见[`Table::render`]
+    pub fn render(&self, width: Option<usize>) -> String {
+        let mut table = Table::new(&["REQUEST", "COUNT", "ERRORS", "TOTAL", "AVG", "BYTES"]);
+        for t in self.requests.iter().chain(std::iter::once(&self.total)) {
+            table.add_row(vec![
+                t.name.clone(),
+                t.count.to_string(),
+                t.errors.to_string(),
+                format!("{}ms", t.total_ms),
+                format!("{}ms", t.avg_ms),
+                t.bytes.to_string(),
+            ]);
+        }
+        table.render(width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_totals() {
+        let mut registry = MetricsRegistry::default();
+        assert!(registry.is_empty());
+        registry.record_request("login", Duration::from_millis(30), false);
+        registry.record_request("word-list", Duration::from_millis(100), false);
+        registry.record_request("word-list", Duration::from_millis(300), false);
+        registry.record_request("word-list", Duration::from_millis(5), true);
+        registry.record_bytes("word-list", 1000);
+        registry.record_bytes("word-list", 24);
+
+        let timings = registry.timings();
+        assert_eq!(
+            timings.requests[1],
+            RequestTimings {
+                name: "word-list".to_string(),
+                count: 3,
+                errors: 1,
+                total_ms: 405,
+                avg_ms: 135,
+                bytes: 1024,
+            }
+        );
+        assert_eq!(timings.requests[0].name, "login");
+        assert_eq!(timings.total.count, 4);
+        assert_eq!(timings.total.total_ms, 435);
+        assert_eq!(timings.total.avg_ms, 108);
+        assert_eq!(timings.total.bytes, 1024);
+        assert_eq!(
+            timings.render(None),
+            "REQUEST\tCOUNT\tERRORS\tTOTAL\tAVG\tBYTES\n\
+             login\t1\t0\t30ms\t30ms\t0\n\
+             word-list\t3\t1\t405ms\t135ms\t1024\n\
+             total\t4\t1\t435ms\t108ms\t1024"
+        );
+    }
+}
@@ -1,70 +1,255 @@
+pub mod anki_client;
+pub mod context;
+pub mod cookie_origin;
+pub mod crypto;
+pub mod eudic_client;
+pub mod generic_json_client;
+pub mod lenient;
+pub mod login_state;
 pub mod maimemo_client;
+pub mod maimemo_open_client;
+pub mod metrics;
+pub mod record;
 pub mod youdao_client;
 
 use crate::config::*;
+use crate::error::{REQUEST_CONFIG_MISSING, STATUS_ERROR};
+use crate::lock;
+use crate::redact;
+use chrono::{DateTime, Utc};
+use context::HttpContext;
 use cookie_store::CookieStore;
-use reqwest::{header::*, Client, Method, RequestBuilder};
+use maimemo_client::{Captcha, Notepad};
+use metrics::MetricsRegistry;
+use reqwest::{header::*, Client, Method, RequestBuilder, StatusCode, Url};
 use serde::Serialize;
-use std::collections::HashMap;
-use std::{fs, io};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use youdao_client::{Definition, WordItem};
 
-/// cookie store持久化
-pub fn save_cookie_store(path: &str, cookie_store: &CookieStore) -> Result<(), String> {
+/// cookie store持久化。写入时持有文件的排它锁
+///
+/// passphrase不为空时加密保存，见[`crypto`]
+pub fn save_cookie_store(
+    path: &str,
+    cookie_store: &CookieStore,
+    passphrase: Option<&str>,
+) -> Result<(), String> {
     info!("Saving cookies to path {}", path);
+    let mut data = vec![];
+    cookie_store
+        .save_json(&mut data)
+        .map_err(|e| format!("{:?}", e))?;
+    if let Some(passphrase) = passphrase {
+        data = crypto::encrypt(&data, passphrase)?;
+    }
+    // 获取锁后再清空文件，避免破坏其它进程正在读取的内容
     let mut file = fs::OpenOptions::new()
         .create(true)
         .write(true)
+        .truncate(false)
         .open(path)
         .map_err(|e| format!("{:?}", e))?;
-    cookie_store
-        .save_json(&mut file)
+    lock::lock_exclusive(&file, path)?;
+    file.set_len(0)
+        .and_then(|_| file.write_all(&data))
         .map_err(|e| format!("{:?}", e))?;
     debug!("saved cookie store");
     Ok(())
 }
 
 /// 从path中创建一个cs, 如果path is none,则使用内存上的cs
-pub fn build_cookie_store(cookie_path: Option<&str>) -> Result<CookieStore, String> {
-    let cookie_store = if let Some(cookie_path) = cookie_path {
+///
+/// 如果文件无法解析，则将文件移动到`<path>.corrupt-<timestamp>`并使用一个空的cs。
+/// 读取时持有文件的共享锁。加载后过期的cookies被清除
+///
+/// 加密的文件通过magic header识别并使用passphrase解密，明文文件仍可加载，在下次保存时被加密
+pub fn build_cookie_store(
+    cookie_path: Option<&str>,
+    passphrase: Option<&str>,
+) -> Result<CookieStore, String> {
+    let mut cookie_store = if let Some(cookie_path) = cookie_path {
         // let path = fs::canonicalize(path).map_err(|e| format!("path {} error: {:?}", path, e))?;
         // let path_str = path.to_str().unwrap().to_string();
         debug!("opening cookie store from path: {}", cookie_path);
-        let file = fs::OpenOptions::new()
+        let mut file = fs::OpenOptions::new()
             .create(true)
+            .truncate(false)
             .write(true)
             .read(true)
             .open(cookie_path)
             .map_err(|e| format!("path {} error: {:?}", cookie_path, e))?;
+        lock::lock_shared(&file, cookie_path)?;
+        let mut data = vec![];
+        file.read_to_end(&mut data)
+            .map_err(|e| format!("read cookie file {} error: {}", cookie_path, e))?;
+        if crypto::is_encrypted(&data) {
+            let passphrase = passphrase.ok_or_else(|| {
+                format!(
+                    "cookie file {} is encrypted but no cookie passphrase is configured",
+                    cookie_path
+                )
+            })?;
+            data = crypto::decrypt(&data, passphrase)
+                .map_err(|e| format!("{}: {}", e, cookie_path))?;
+        }
         // fix cookie store file loading error:
         //  DEBUG dict::client > opening cookie store from path: /home/navyd/.dict/youdao-cookies.json
         //  thread 'main' panicked at 'new youdaoclient error: Error("expected value", line: 1, column: 1)', src/main.rs:404:58
-        match CookieStore::load_json(io::BufReader::new(file)).map_err(|e| format!("{:?}", e)) {
-            Err(e) if e.contains("line: 1, column: 1") => {
-                warn!("Reinitializing cookie store. loading cookie file error: {}", e);
+        // 新创建的空文件不是损坏的文件
+        let is_empty = data.is_empty();
+        match CookieStore::load_json(&data[..]).map_err(|e| format!("{:?}", e)) {
+            Err(e) if is_empty => {
+                warn!(
+                    "Reinitializing cookie store. loading cookie file error: {}",
+                    e
+                );
                 CookieStore::default()
-            },
-            o => o?
+            }
+            Err(e) => {
+                let corrupt_path = format!(
+                    "{}.corrupt-{}",
+                    cookie_path,
+                    chrono::Utc::now().format("%Y%m%d%H%M%S")
+                );
+                warn!(
+                    "Reinitializing cookie store. moved corrupt cookie file to {}. loading error: {}",
+                    corrupt_path, e
+                );
+                fs::rename(cookie_path, &corrupt_path).map_err(|e| {
+                    format!(
+                        "move corrupt cookie file {} to {} error: {}",
+                        cookie_path, corrupt_path, e
+                    )
+                })?;
+                CookieStore::default()
+            }
+            Ok(cookie_store) => cookie_store,
         }
     } else {
         debug!("not found cookie store path. cookie store used in memory");
         CookieStore::default()
     };
-    cookie_store
-        .iter_unexpired()
-        .for_each(|c| debug!("loaded unexpirted cookie: [{}={}]", c.name(), c.value()));
+    let purged = purge_expired_cookies(&mut cookie_store);
+    if purged > 0 {
+        debug!("purged {} expired cookies", purged);
+    }
+    cookie_store.iter_unexpired().for_each(|c| {
+        debug!(
+            "loaded unexpirted cookie: [{}={}]",
+            c.name(),
+            redact::mask(c.value())
+        )
+    });
     Ok(cookie_store)
 }
 
-/// 一个不使用cookie store，重定向的client
+/// 从cookie store中删除所有过期的cookies，返回删除的数量
+pub fn purge_expired_cookies(cookie_store: &mut CookieStore) -> usize {
+    let expired = cookie_store
+        .iter_any()
+        .filter(|c| c.is_expired())
+        .filter_map(|c| {
+            c.domain.as_cow().map(|domain| {
+                (
+                    domain.to_string(),
+                    c.path.as_ref().to_string(),
+                    c.name().to_string(),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    expired
+        .iter()
+        .filter(|(domain, path, name)| cookie_store.remove(domain, path, name).is_some())
+        .count()
+}
+
+/// 登录cookie即将过期的提示时间：24h
+pub const COOKIE_EXPIRY_WARNING_SECS: i64 = 24 * 60 * 60;
+
+/// 一个登录需要的cookie的状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CookieStatus {
+    pub domain: String,
+    pub name: String,
+    pub present: bool,
+    /// 过期时间rfc3339。session cookie为None
+    pub expires: Option<String>,
+    pub expires_in_secs: Option<i64>,
+}
+
+impl CookieStatus {
+    /// 是否存在且在secs内过期
+    pub fn expires_within(&self, secs: i64) -> bool {
+        self.present && self.expires_in_secs.is_some_and(|s| s < secs)
+    }
+}
+
+/// 查询cookie store中`(domain, name)`登录cookies的状态。path为`/`
+pub fn login_cookie_status(
+    cookie_store: &CookieStore,
+    login_cookies: &[(&str, &str)],
+) -> Vec<CookieStatus> {
+    let now = Utc::now().timestamp();
+    login_cookies
+        .iter()
+        .map(|(domain, name)| {
+            let cookie = cookie_store.get(domain, "/", name);
+            // cookie_store未导出CookieExpiration，从转换后的raw cookie中读取Expires
+            let expires_at = cookie.and_then(|c| {
+                cookie::Cookie::from(c.clone())
+                    .expires()
+                    .and_then(|e| e.datetime())
+                    .map(|t| t.unix_timestamp())
+            });
+            CookieStatus {
+                domain: domain.to_string(),
+                name: name.to_string(),
+                present: cookie.is_some(),
+                expires: expires_at
+                    .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0))
+                    .map(|t| t.to_rfc3339()),
+                expires_in_secs: expires_at.map(|secs| secs - now),
+            }
+        })
+        .collect()
+}
+
+/// 登录cookie在[`COOKIE_EXPIRY_WARNING_SECS`]内过期时提示重新登录
+pub fn warn_expiring_cookies(service: &str, statuses: &[CookieStatus]) {
+    for status in statuses
+        .iter()
+        .filter(|s| s.expires_within(COOKIE_EXPIRY_WARNING_SECS))
+    {
+        warn!(
+            "{} login cookie {} expires at {}, please login again to refresh it",
+            service,
+            status.name,
+            status.expires.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+/// 一个不使用cookie store，重定向的client。多个client间共享连接池时应使用[`HttpContext`]
 pub fn build_general_client() -> Result<Client, String> {
-    Client::builder()
-        .cookie_store(false)
-        .redirect(reqwest::redirect::Policy::none())
-        .build()
-        .map_err(|e| format!("{:?}", e))
+    HttpContext::new().map(|context| context.client().clone())
 }
 
-/// 通过content-type解析body到request builder中
+/// 通过content-type解析body到request builder中。content-type忽略大小写与参数如charset
+///
+/// 当前支持：
+///
+/// - application/x-www-form-urlencoded
+/// - application/json
+/// - text/plain: body为字符串时直接使用，否则使用json字符串
+/// - multipart/form-data: body必须是一个map，非字符串的值使用json字符串
 fn fill_body<T: Serialize + ?Sized>(
     req_builder: RequestBuilder,
     content_type: &str,
@@ -72,24 +257,95 @@ fn fill_body<T: Serialize + ?Sized>(
 ) -> Result<RequestBuilder, String> {
     // parse body with content-type
     debug!("Parsing body of content type: {}", content_type);
-    if content_type.contains("application/x-www-form-urlencoded") {
-        serde_urlencoded::to_string(body)
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    match mime.as_str() {
+        "application/x-www-form-urlencoded" => serde_urlencoded::to_string(body)
             .map(|body| {
-                trace!("Url encoded the body: {}", body);
+                trace!("Url encoded the body: {}", redact::redact_form(&body));
                 req_builder.body(body)
             })
-            .map_err(|e| format!("{:?}", e))
-    } else if content_type.contains("application/json") {
-        // req_builder.body(body.into())
-        serde_json::to_vec(body)
+            .map_err(|e| format!("{:?}", e)),
+        "application/json" => serde_json::to_vec(body)
             .map(|body| {
-                trace!("jsoned body: {:?}", body);
+                if log_enabled!(log::Level::Trace) {
+                    let redacted = serde_json::from_slice(&body)
+                        .map(|v| redact::redact_json(&v).to_string())
+                        .unwrap_or_default();
+                    trace!("jsoned body: {}", redacted);
+                }
                 req_builder.body(body)
             })
-            .map_err(|e| format!("{:?}", e))
-    } else {
-        todo!("unsupported content type: {:?}", content_type)
+            .map_err(|e| format!("{:?}", e)),
+        "text/plain" => {
+            let body = match serde_json::to_value(body).map_err(|e| format!("{:?}", e))? {
+                serde_json::Value::String(s) => s,
+                v => v.to_string(),
+            };
+            trace!("text body of {} bytes", body.len());
+            Ok(req_builder.body(body))
+        }
+        "multipart/form-data" => fill_multipart(req_builder, body),
+        _ => Err(format!("unsupported content type: {}", content_type)),
+    }
+}
+
+/// 将map body作为multipart form填充。配置中的content-type没有boundary，会被替换
+fn fill_multipart<T: Serialize + ?Sized>(
+    req_builder: RequestBuilder,
+    body: &T,
+) -> Result<RequestBuilder, String> {
+    let fields = match serde_json::to_value(body).map_err(|e| format!("{:?}", e))? {
+        serde_json::Value::Object(fields) => fields,
+        v => return Err(format!("multipart body must be a map: {}", v)),
+    };
+    let mut form = reqwest::multipart::Form::new();
+    for (name, val) in fields {
+        let val = match val {
+            serde_json::Value::String(s) => s,
+            v => v.to_string(),
+        };
+        trace!(
+            "multipart field: {}={}",
+            name,
+            redact::redact_pair(&name, &val)
+        );
+        form = form.text(name, val);
     }
+    let (client, req) = req_builder.build_split();
+    let mut req = req.map_err(|e| format!("{:?}", e))?;
+    req.headers_mut().remove(CONTENT_TYPE);
+    Ok(RequestBuilder::from_parts(client, req).multipart(form))
+}
+
+/// 请求时在其它cookies前发送的cookies。youdao部分接口要求DICT_SESS在DICT_LOGIN之前
+const PRIORITY_COOKIES: &[&str] = &["DICT_SESS", "DICT_LOGIN"];
+
+/// [`PRIORITY_COOKIES`]在前，其余按名称排序，不依赖store的遍历顺序
+fn sort_request_cookies(pairs: &mut [(&str, &str)]) {
+    pairs.sort_by_key(|(name, _)| {
+        let priority = PRIORITY_COOKIES.iter().position(|p| p == name);
+        (priority.unwrap_or(PRIORITY_COOKIES.len()), *name)
+    });
+}
+
+/// 用`; `连接排序后的cookies，见[`sort_request_cookies`]。没有cookie时返回None
+fn join_request_cookies(mut pairs: Vec<(&str, &str)>) -> Option<String> {
+    if pairs.is_empty() {
+        return None;
+    }
+    sort_request_cookies(&mut pairs);
+    Some(
+        pairs
+            .iter()
+            .map(|(name, val)| format!("{}={}", name, val))
+            .collect::<Vec<_>>()
+            .join("; "),
+    )
 }
 
 /// 将cookie store中对应的url中的cookies填充requst builder
@@ -97,81 +353,402 @@ pub fn fill_request_cookies(
     cookie_store: &cookie_store::CookieStore,
     req_builder: RequestBuilder,
     req_url: &str,
+) -> RequestBuilder {
+    fill_request_cookies_from(&[cookie_store], req_builder, req_url, None)
+}
+
+/// 选择cookies时使用的url。cookie_domain不为空时替换url的host
+pub fn cookie_url(req_url: &str, cookie_domain: Option<&str>) -> Result<Url, String> {
+    let mut url = Url::parse(req_url).map_err(|e| format!("parse url {} error: {}", req_url, e))?;
+    if let Some(domain) = cookie_domain {
+        url.set_host(Some(domain))
+            .map_err(|e| format!("invalid cookie_domain {}: {}", domain, e))?;
+    }
+    Ok(url)
+}
+
+/// 多个cookie stores中发送到url的cookies，按发送的顺序。同名cookie以后面的store为准
+fn select_request_cookies<'a>(
+    cookie_stores: &[&'a CookieStore],
+    url: &Url,
+) -> Vec<(&'a str, &'a str)> {
+    let mut pairs: Vec<(&str, &str)> = vec![];
+    for cookie_store in cookie_stores {
+        for (name, value) in cookie_store.get_request_values(url) {
+            match pairs.iter_mut().find(|(n, _)| *n == name) {
+                Some(pair) => pair.1 = value,
+                None => pairs.push((name, value)),
+            }
+        }
+    }
+    sort_request_cookies(&mut pairs);
+    pairs
+}
+
+/// 将多个cookie stores中对应url的cookies填充request builder。同名cookie以后面的store为准
+///
+/// cookie_domain见[`cookie_url`]。日志中输出发送与被domain/path规则过滤的cookie名
+fn fill_request_cookies_from(
+    cookie_stores: &[&CookieStore],
+    req_builder: RequestBuilder,
+    req_url: &str,
+    cookie_domain: Option<&str>,
 ) -> RequestBuilder {
     debug!("filling reqeust cookies");
-    let url = &reqwest::Url::parse(req_url).unwrap();
-    let delimiter = "; ";
-    let mut cookies = "".to_string();
-    for c in cookie_store.get_request_cookies(url) {
-        cookies = cookies + c.name() + "=" + c.value() + delimiter;
-    }
-    if cookies.is_empty() {
-        debug!("No cookies found for url: {}", url);
-        return req_builder;
-    }
-    let start = cookies.len() - delimiter.len();
-    cookies.drain(start..cookies.len());
-    debug!("found reqeust cookie str: {}", cookies);
+    let url = match cookie_url(req_url, cookie_domain) {
+        Ok(url) => url,
+        Err(e) => {
+            warn!("skipped request cookies: {}", e);
+            return req_builder;
+        }
+    };
+    let pairs = select_request_cookies(cookie_stores, &url);
+    let filtered = cookie_stores
+        .iter()
+        .flat_map(|s| s.iter_unexpired())
+        .map(|c| c.name())
+        .filter(|name| pairs.iter().all(|(n, _)| n != name))
+        .collect::<BTreeSet<_>>();
+    debug!(
+        "request cookies for {}: attached [{}], filtered out by domain/path [{}]",
+        url,
+        pairs.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", "),
+        filtered.into_iter().collect::<Vec<_>>().join(", ")
+    );
+    let cookies = match join_request_cookies(pairs) {
+        Some(cookies) => cookies,
+        None => {
+            debug!("No cookies found for url: {}", url);
+            return req_builder;
+        }
+    };
+    debug!(
+        "found reqeust cookie str: {}",
+        redact::redact_cookies(&cookies)
+    );
     match HeaderValue::from_str(&cookies) {
         Ok(v) => req_builder.header(reqwest::header::COOKIE, v),
         Err(e) => {
             warn!(
-                "skiped unable to request cookie: {}. error: {:?}",
-                cookies, e
+                "skipped invalid request cookie: {}. error: {:?}",
+                redact::redact_cookies(&cookies),
+                e
             );
             req_builder
         }
     }
 }
 
+/// 一个请求会发送的cookies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RequestCookies {
+    pub request: String,
+    pub url: String,
+    pub cookie_domain: Option<String>,
+    /// 发送的cookie名，与请求时的顺序相同
+    pub cookies: Vec<String>,
+}
+
+/// config中每个请求会从cookie_store中发送的cookies，按请求名排序。url无法解析的请求没有cookies
+pub fn request_cookies(config: &AppConfig, cookie_store: &CookieStore) -> Vec<RequestCookies> {
+    let mut requests = config
+        .get_requests()
+        .map(|reqs| {
+            reqs.iter()
+                .map(|(name, req)| {
+                    let url = cookie_url(req.get_url(), req.get_cookie_domain());
+                    let pairs = url
+                        .map(|url| select_request_cookies(&[cookie_store], &url))
+                        .unwrap_or_default();
+                    RequestCookies {
+                        request: name.clone(),
+                        url: req.get_url().to_string(),
+                        cookie_domain: req.get_cookie_domain().map(str::to_string),
+                        cookies: pairs.iter().map(|(name, _)| name.to_string()).collect(),
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    requests.sort_by(|a, b| a.request.cmp(&b.request));
+    requests
+}
+
+/// 重定向过程中每个response的`set-cookie`s与对应的url，保存在最后一个response的extensions中
+#[derive(Debug, Default)]
+struct RedirectCookies(Vec<(Vec<HeaderValue>, Url)>);
+
 /// 从response中获取`set-cookie`s更新到cookie_store中。如果出现cookie无法解析或store无法插入则跳过
+///
+/// 跟随重定向时，重定向过程中的`set-cookie`s先于最后一个response更新
 pub fn update_set_cookies(cookie_store: &mut cookie_store::CookieStore, resp: &reqwest::Response) {
-    let set_cookies = resp
-        .headers()
-        .iter()
-        .filter(|(name, _)| *name == reqwest::header::SET_COOKIE)
-        .map(|(_, v)| v.to_str().unwrap())
-        .collect::<Vec<_>>();
-    debug!("Updating response cookies to cookie_store");
-    for cookie_str in set_cookies {
-        debug!("inserting set-cookie: {}", cookie_str);
-        if let Err(e) = cookie::Cookie::parse(cookie_str).map(|raw_cookie| {
-            if let Err(e) = cookie_store.insert_raw(&raw_cookie, resp.url()) {
-                debug!("unable to store Set-Cookie: {:?}", e);
+    if let Some(RedirectCookies(hops)) = resp.extensions().get::<RedirectCookies>() {
+        for (values, url) in hops {
+            insert_set_cookies(cookie_store, values.iter(), url);
+        }
+    }
+    insert_set_cookies(
+        cookie_store,
+        resp.headers().get_all(SET_COOKIE).iter(),
+        resp.url(),
+    );
+}
+
+/// 导入的浏览器session cookie的有效时间：30天。cookie store不会保存session cookie
+pub const IMPORTED_SESSION_COOKIE_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// 将浏览器导出的Netscape格式cookies.txt中的cookies插入cookie store，返回插入的数量
+///
+/// 每行为tab分隔的`domain include_subdomains path secure expires name value`，domain带
+/// `#HttpOnly_`前缀时为http only的cookie，其它`#`开头的行为注释。expires为0时为session cookie，
+/// 在[`IMPORTED_SESSION_COOKIE_SECS`]后过期。已过期的cookies被忽略
+pub fn import_cookies_txt(cookie_store: &mut CookieStore, contents: &str) -> Result<usize, String> {
+    let now = Utc::now().timestamp();
+    let mut imported = 0;
+    for (i, line) in contents.lines().enumerate() {
+        let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.trim_end_matches('\r').split('\t').collect::<Vec<_>>();
+        if fields.len() != 7 {
+            return Err(format!(
+                "invalid cookies.txt line {}: expected 7 tab separated fields, found {}",
+                i + 1,
+                fields.len()
+            ));
+        }
+        let (domain, subdomains, path, secure, expires, name, value) = (
+            fields[0], fields[1], fields[2], fields[3], fields[4], fields[5], fields[6],
+        );
+        let expires = expires.parse::<i64>().map_err(|e| {
+            format!(
+                "invalid cookies.txt line {}: expires {}: {}",
+                i + 1,
+                expires,
+                e
+            )
+        })?;
+        if expires > 0 && expires <= now {
+            debug!("skipped expired cookie {} of {}", name, domain);
+            continue;
+        }
+        let host = domain.trim_start_matches('.');
+        let secure = secure.eq_ignore_ascii_case("TRUE");
+        let mut cookie_str = format!("{}={}; Path={}", name, value, path);
+        // 不包含子域名时为host only的cookie
+        if subdomains.eq_ignore_ascii_case("TRUE") {
+            cookie_str.push_str(&format!("; Domain={}", host));
+        }
+        let max_age = if expires > 0 {
+            expires - now
+        } else {
+            IMPORTED_SESSION_COOKIE_SECS
+        };
+        cookie_str.push_str(&format!("; Max-Age={}", max_age));
+        if secure {
+            cookie_str.push_str("; Secure");
+        }
+        if http_only {
+            cookie_str.push_str("; HttpOnly");
+        }
+        let url = format!(
+            "{}://{}{}",
+            if secure { "https" } else { "http" },
+            host,
+            path
+        );
+        let url = Url::parse(&url)
+            .map_err(|e| format!("invalid cookies.txt line {}: {}: {}", i + 1, url, e))?;
+        let cookie = cookie::Cookie::parse(cookie_str)
+            .map_err(|e| format!("invalid cookies.txt line {}: {}", i + 1, e))?;
+        cookie_store
+            .insert_raw(&cookie, &url)
+            .map_err(|e| format!("invalid cookies.txt line {}: {:?}", i + 1, e))?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+/// 非ascii的`set-cookie`被lossy转换，同一个cookie出现多次时仅保留最后一个。
+/// 日志中仅输出插入与替换的cookie名
+fn insert_set_cookies<'a>(
+    cookie_store: &mut CookieStore,
+    values: impl Iterator<Item = &'a HeaderValue>,
+    url: &Url,
+) {
+    let mut cookies = Vec::<cookie::Cookie>::new();
+    for val in values {
+        let cookie_str = match val.to_str() {
+            Ok(s) => s.to_string(),
+            Err(_) => {
+                warn!("non-ascii Set-Cookie from {}, converted lossily", url);
+                String::from_utf8_lossy(val.as_bytes()).into_owned()
             }
-        }) {
-            debug!("parse Set-Cookie val error {:?}", e);
+        };
+        let raw_cookie = match cookie::Cookie::parse(cookie_str.clone())
+            .or_else(|e| cookie::Cookie::parse_encoded(cookie_str).map_err(|_| e))
+        {
+            Ok(raw_cookie) => raw_cookie,
+            Err(e) => {
+                debug!("parse Set-Cookie val error {:?}", e);
+                continue;
+            }
+        };
+        cookies.retain(|c| {
+            (c.name(), c.domain(), c.path())
+                != (raw_cookie.name(), raw_cookie.domain(), raw_cookie.path())
+        });
+        cookies.push(raw_cookie);
+    }
+    let existing = cookie_store
+        .matches(url)
+        .iter()
+        .map(|c| c.name().to_string())
+        .collect::<HashSet<_>>();
+    let (mut inserted, mut replaced) = (vec![], vec![]);
+    for raw_cookie in &cookies {
+        match cookie_store.insert_raw(raw_cookie, url) {
+            Ok(_) if existing.contains(raw_cookie.name()) => replaced.push(raw_cookie.name()),
+            Ok(_) => inserted.push(raw_cookie.name()),
+            Err(e) => debug!("unable to store Set-Cookie {}: {:?}", raw_cookie.name(), e),
         }
     }
+    if !cookies.is_empty() {
+        debug!(
+            "updated cookies from {}: inserted [{}], replaced [{}]",
+            url,
+            inserted.join(", "),
+            replaced.join(", ")
+        );
+    }
 }
 
-/// 将headers内容填充至req_builder中
+/// 将headers内容填充至req_builder中。header name忽略大小写，如`Content-Type`
 ///
-/// 如果header中存在不合法的key,val被跳过
+/// 如果headers中存在不合法的key或val，返回包含所有不合法headers的error
 pub fn fill_headers(
     req_builder: RequestBuilder,
     headers: &HashMap<String, String>,
-) -> RequestBuilder {
+) -> Result<RequestBuilder, String> {
     let mut req_headers = HeaderMap::new();
+    let mut invalid_headers = vec![];
     for (key, val) in headers {
-        trace!("filling request header: {}={}", key, val);
-        let name = if let Ok(name) = HeaderName::from_lowercase(key.to_lowercase().as_bytes()) {
-            name
-        } else {
-            warn!("skip invalid headername: {}", key);
-            continue;
+        trace!(
+            "filling request header: {}={}",
+            key,
+            redact::redact_header(key, val)
+        );
+        let name = match HeaderName::from_lowercase(key.to_lowercase().as_bytes()) {
+            Ok(name) => name,
+            Err(_) => {
+                invalid_headers.push(format!("invalid name: {:?}", key));
+                continue;
+            }
         };
-        let val = if let Ok(name) = HeaderValue::from_str(val) {
-            name
-        } else {
-            warn!("skip invalid header value: {}", val);
-            continue;
+        let val = match HeaderValue::from_str(val) {
+            Ok(val) => val,
+            Err(_) => {
+                invalid_headers.push(format!("invalid value of {}: {:?}", key, val));
+                continue;
+            }
         };
         if let Some(old) = req_headers.insert(name, val) {
-            debug!("replace old header: {}={}", key, old.to_str().unwrap());
+            debug!(
+                "replace old header: {}={}",
+                key,
+                redact::redact_header(key, old.to_str().unwrap_or_default())
+            );
         }
     }
-    req_builder.headers(req_headers)
+    if !invalid_headers.is_empty() {
+        // 保证error信息稳定
+        invalid_headers.sort();
+        return Err(format!(
+            "invalid request headers: {}",
+            invalid_headers.join(", ")
+        ));
+    }
+    Ok(req_builder.headers(req_headers))
+}
+
+/// trait中异步方法的返回值
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// 提供单词的服务，如youdao单词本与eudic生词本
+pub trait WordSource {
+    /// 获取服务中的所有单词，需要时先登录
+    fn fetch_words(&mut self) -> BoxFuture<'_, Result<Vec<WordItem>, String>>;
+}
+
+/// 可以查询单词释义的服务
+pub trait WordLookup {
+    fn lookup<'a>(&'a self, word: &'a str) -> BoxFuture<'a, Result<Definition, String>>;
+}
+
+/// 可以上传notepad的服务
+pub trait NotepadSink {
+    /// 用notepad的内容替换服务中相同id的notepad。返回false表示用户取消了上传
+    fn push<'a>(&'a mut self, notepad: &'a Notepad) -> BoxFuture<'a, Result<bool, String>>;
+}
+
+/// 显示maimemo的验证码图片并返回用户输入的答案，由嵌入的应用实现。
+///
+/// 同步的闭包`FnMut(&Captcha) -> Result<String, String>`也实现了该trait：
+///
+/// ```no_run
+/// use dict::api::{self, Captcha, Config, HttpContext};
+/// use std::sync::Arc;
+///
+/// # async fn run() -> Result<(), String> {
+/// let mut config = Config::from_yaml_file("dict-config.yml")?;
+/// let context = Arc::new(HttpContext::new()?);
+/// let ask_user = |captcha: &Captcha| -> Result<String, String> {
+///     // 在应用中显示captcha.bytes并等待用户输入
+///     Ok("1234".to_string())
+/// };
+/// api::upload_notepad(config.maimemo(), context, "695835", "accommodate\nbenevolent", ask_user)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait CaptchaProvider {
+    /// captcha已经检查为图片。返回error时取消上传
+    fn solve<'a>(&'a mut self, captcha: &'a Captcha) -> BoxFuture<'a, Result<String, String>>;
+}
+
+impl<F> CaptchaProvider for F
+where
+    F: FnMut(&Captcha) -> Result<String, String>,
+{
+    fn solve<'a>(&'a mut self, captcha: &'a Captcha) -> BoxFuture<'a, Result<String, String>> {
+        let answer = self(captcha);
+        Box::pin(async move { answer })
+    }
+}
+
+/// 请求req_name实际发送的User-Agent，优先级：请求配置的headers、app的user_agent、
+/// http.user_agent。返回None表示不会发送User-Agent
+pub fn request_user_agent<'a>(
+    http: &'a HttpConfig,
+    config: &'a AppConfig,
+    req_name: &str,
+) -> Option<&'a str> {
+    let configured = get_request_config(config, req_name)
+        .and_then(|req| req.get_headers())
+        .and_then(|headers| {
+            headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(USER_AGENT.as_str()))
+                .map(|(_, v)| v.as_str())
+        });
+    configured
+        .or_else(|| config.get_user_agent())
+        .or_else(|| HttpContext::user_agent(http))
+        .filter(|ua| !ua.trim().is_empty())
 }
 
 /// 通过req_name从Config中获取一个request config
@@ -185,14 +762,14 @@ pub fn get_request_config<'a>(config: &'a AppConfig, req_name: &str) -> Option<&
 
 pub async fn send_request_nobody<U: FnOnce(&str) -> String>(
     config: &AppConfig,
-    client: &Client,
+    context: &HttpContext,
     cookie_store: &CookieStore,
     req_name: &str,
     url_handler: U,
 ) -> Result<reqwest::Response, String> {
     send_request(
         config,
-        client,
+        context,
         cookie_store,
         req_name,
         url_handler,
@@ -212,18 +789,135 @@ pub async fn send_request_nobody<U: FnOnce(&str) -> String>(
 /// - json
 /// - form
 ///
-/// 如果response.status!=200 || != 302则返回error
+/// 如果request config中配置了`follow_redirects`，则跟随301/302/307/308重定向直到
+/// `max_redirects`次。重定向过程中的set-cookie用于后续的重定向请求，并与最后一个response的
+/// set-cookie一起由调用者通过[`update_set_cookies`]更新到cookie_store中
+///
+/// 如果response.status不在request config的`ok_status`中(默认200, 302)则返回error，
+/// error中包含body的前[`ERROR_BODY_SNIPPET_LEN`]个字节
+///
+/// context中存在[`record::Recorder`]时，每个response(包括重定向)被录制到文件，
+/// 或从录制的文件中回放而不发送请求
 ///
+/// 请求的次数与耗时记录到context的[`metrics::MetricsRegistry`]中
 pub async fn send_request<T: Serialize + ?Sized, U: FnOnce(&str) -> String>(
     config: &AppConfig,
-    client: &Client,
+    context: &HttpContext,
     cookie_store: &CookieStore,
     req_name: &str,
     url_handler: U,
     body: Option<&T>,
+) -> Result<reqwest::Response, String> {
+    let resp =
+        send_request_unchecked(config, context, cookie_store, req_name, url_handler, body).await?;
+    check_status(config, req_name, resp).await
+}
+
+/// 同[`send_request`]，但不检查response.status，由调用者处理如404等status后通过[`check_status`]检查
+pub async fn send_request_unchecked<T: Serialize + ?Sized, U: FnOnce(&str) -> String>(
+    config: &AppConfig,
+    context: &HttpContext,
+    cookie_store: &CookieStore,
+    req_name: &str,
+    url_handler: U,
+    body: Option<&T>,
+) -> Result<reqwest::Response, String> {
+    send_recorded_request(
+        config,
+        context,
+        Some(cookie_store),
+        req_name,
+        url_handler,
+        body,
+    )
+    .await
+}
+
+/// 同[`send_request_unchecked`]，但不发送cookies，用于通过token授权的api。
+/// 重定向时仍然使用重定向过程中收到的cookies
+pub async fn send_request_without_cookies<T: Serialize + ?Sized, U: FnOnce(&str) -> String>(
+    config: &AppConfig,
+    context: &HttpContext,
+    req_name: &str,
+    url_handler: U,
+    body: Option<&T>,
+) -> Result<reqwest::Response, String> {
+    send_recorded_request(config, context, None, req_name, url_handler, body).await
+}
+
+/// 检查离线与取消后发送请求，记录请求的统计。
+/// response中带有context的metrics，用于[`read_body`]记录读取的字节数
+async fn send_recorded_request<T: Serialize + ?Sized, U: FnOnce(&str) -> String>(
+    config: &AppConfig,
+    context: &HttpContext,
+    cookie_store: Option<&CookieStore>,
+    req_name: &str,
+    url_handler: U,
+    body: Option<&T>,
+) -> Result<reqwest::Response, String> {
+    context.check_online(req_name)?;
+    context.check_cancelled()?;
+    let started = Instant::now();
+    let result =
+        send_configured_request(config, context, cookie_store, req_name, url_handler, body).await;
+    let failed = result
+        .as_ref()
+        .map_or(true, |resp| !is_ok_status(config, req_name, resp.status()));
+    context.record_request(req_name, started.elapsed(), failed);
+    result.map(|mut resp| {
+        resp.extensions_mut().insert(context.metrics());
+        resp
+    })
+}
+
+/// 发送没有request config的请求，如AnkiConnect与doctor的检查。与[`send_recorded_request`]相同
+/// 检查离线与取消、录制或回放并记录请求的统计，非2xx的status记录为失败。
+/// fill用于设置请求的headers与body
+pub async fn send_unconfigured(
+    context: &HttpContext,
+    req_name: &str,
+    method: Method,
+    url: &str,
+    fill: impl FnOnce(RequestBuilder) -> RequestBuilder,
+) -> Result<reqwest::Response, String> {
+    context.check_online(req_name)?;
+    context.check_cancelled()?;
+    let started = Instant::now();
+    let req_builder = fill(context.request(method.clone(), url));
+    let result = match context.recorder() {
+        Some(recorder) if recorder.is_replay() => recorder.load(req_name, &method, url),
+        Some(recorder) => match context.send(req_builder).await {
+            Ok(resp) => recorder.save(req_name, &method, resp).await,
+            Err(e) => Err(e),
+        },
+        None => context.send(req_builder).await,
+    };
+    let failed = result
+        .as_ref()
+        .map_or(true, |resp| !resp.status().is_success());
+    context.record_request(req_name, started.elapsed(), failed);
+    result.map(|mut resp| {
+        resp.extensions_mut().insert(context.metrics());
+        resp
+    })
+}
+
+/// status是否在req_name对应request config的`ok_status`中
+fn is_ok_status(config: &AppConfig, req_name: &str, status: StatusCode) -> bool {
+    get_request_config(config, req_name)
+        .is_some_and(|c| c.get_ok_status().contains(&status.as_u16()))
+}
+
+async fn send_configured_request<T: Serialize + ?Sized, U: FnOnce(&str) -> String>(
+    config: &AppConfig,
+    context: &HttpContext,
+    cookie_store: Option<&CookieStore>,
+    req_name: &str,
+    url_handler: U,
+    body: Option<&T>,
 ) -> Result<reqwest::Response, String> {
     let req_config = get_request_config(config, req_name)
-        .ok_or(format!("not found req config with req_name: {}", req_name))?;
+        .ok_or(format!("{}{}", REQUEST_CONFIG_MISSING, req_name))?;
     debug!("sending request: {}", req_name);
 
     let url = req_config.get_url();
@@ -235,43 +929,1185 @@ pub async fn send_request<T: Serialize + ?Sized, U: FnOnce(&str) -> String>(
         Method::from_bytes(req_config.get_method().as_bytes()).map_err(|e| format!("{:?}", e))?;
     debug!("found the configured method: {}", method);
 
-    let mut req_builder = client.request(method, &url);
-
     let headers = req_config
         .get_headers()
         .ok_or(format!("not found any headers in req url: {}", url))?;
-    debug!("Fill in the request from the configured headers");
-    req_builder = fill_headers(req_builder, headers);
 
-    req_builder = fill_request_cookies(cookie_store, req_builder, &url);
+    let (mut method, mut url, mut body) = (method, url, body);
+    // 重定向过程中收到的cookies
+    let mut redirect_cookies = CookieStore::default();
+    let mut redirect_set_cookies = RedirectCookies::default();
+    let mut redirects = 0;
+    let resp = loop {
+        // cookie_domain仅用于配置的url，重定向后使用实际的url
+        let cookie_domain = req_config.get_cookie_domain().filter(|_| redirects == 0);
+        let cookie_stores = cookie_store
+            .into_iter()
+            .chain([&redirect_cookies])
+            .collect::<Vec<_>>();
+        let req_builder = fill_request(
+            context.request(method.clone(), &url),
+            config,
+            headers,
+            &cookie_stores,
+            &url,
+            cookie_domain,
+            body,
+        )?;
 
-    if let Some(body) = body {
-        let content_type = req_config
-            .get_headers()
-            .and_then(|headers| {
-                headers
-                    .iter()
-                    .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
-                    .map(|(_, v)| v)
-            })
-            .ok_or_else(|| {
-                format!(
-                    "not found content-type in request headers: {:?}",
-                    req_config.get_headers()
-                )
-            })?;
-        req_builder = fill_body(req_builder, content_type, body)?;
-        // req_builder = req_builder.form(body);
-    }
+        trace!("sending request: {} {}", method, url);
+        let resp = match context.recorder() {
+            Some(recorder) if recorder.is_replay() => recorder.load(req_name, &method, &url)?,
+            Some(recorder) => {
+                let resp = context.send(req_builder).await?;
+                recorder.save(req_name, &method, resp).await?
+            }
+            None => context.send(req_builder).await?,
+        };
+        if !req_config.follow_redirects() {
+            break resp;
+        }
+        let location = match redirect_location(&resp)? {
+            Some(location) => location,
+            None if redirect_set_cookies.0.is_empty() => break resp,
+            None => {
+                let mut resp = resp;
+                resp.extensions_mut().insert(redirect_set_cookies);
+                break resp;
+            }
+        };
+        redirects += 1;
+        if redirects > req_config.get_max_redirects() {
+            return Err(format!(
+                "too many redirects: {} for request: {}",
+                redirects - 1,
+                req_name
+            ));
+        }
+        debug!(
+            "following redirect {} with status {} to: {}",
+            redirects,
+            resp.status(),
+            location
+        );
+        update_set_cookies(&mut redirect_cookies, &resp);
+        let values = resp
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        if !values.is_empty() {
+            redirect_set_cookies.0.push((values, resp.url().clone()));
+        }
+        // 301,302仅对GET,HEAD保持method，其它method改为GET并丢弃body
+        if matches!(
+            resp.status(),
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND
+        ) && method != Method::GET
+            && method != Method::HEAD
+        {
+            method = Method::GET;
+            body = None;
+        }
+        url = location.to_string();
+    };
+    Ok(resp)
+}
 
-    trace!("sending request: {:?}", req_builder);
-    let resp = req_builder.send().await.map_err(|e| format!("{:?}", e))?;
+/// response.status不在req_name对应request config的`ok_status`中时返回error，
+/// error中包含body的前[`ERROR_BODY_SNIPPET_LEN`]个字节
+pub async fn check_status(
+    config: &AppConfig,
+    req_name: &str,
+    resp: reqwest::Response,
+) -> Result<reqwest::Response, String> {
     let status = resp.status();
-    if status.as_u16() == 200 || status.as_u16() == 302 {
+    if is_ok_status(config, req_name, status) {
         Ok(resp)
     } else {
-        let msg = format!("Response code error: {}", status);
-        debug!("Response code error: {}. resp: {:?}", status, resp);
-        Err(msg)
+        debug!(
+            "Response code error: {}. resp: {}",
+            status,
+            debug_response(&resp)
+        );
+        // 429时error中包含Retry-After的秒数，见[`parse_retry_after`]
+        let retry_after = resp
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|_| status == StatusCode::TOO_MANY_REQUESTS)
+            .map(|secs| format!(", {}{}s", RETRY_AFTER_PREFIX, secs))
+            .unwrap_or_default();
+        // 仅读取error需要的部分body
+        let mut resp = resp;
+        let mut body = vec![];
+        while body.len() < ERROR_BODY_SNIPPET_LEN {
+            match resp.chunk().await.map_err(context::request_error)? {
+                Some(chunk) => body.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        let snippet = &body[..body.len().min(ERROR_BODY_SNIPPET_LEN)];
+        Err(format!(
+            "{}{}{}, body: {}",
+            STATUS_ERROR,
+            status,
+            retry_after,
+            String::from_utf8_lossy(snippet)
+        ))
+    }
+}
+
+/// 用app的User-Agent、请求配置的headers、cookie_stores中的cookies与body填充req_builder，不发送。
+/// 见[`send_request`]与[`preview_request`]
+fn fill_request<T: Serialize + ?Sized>(
+    req_builder: RequestBuilder,
+    config: &AppConfig,
+    headers: &HashMap<String, String>,
+    cookie_stores: &[&CookieStore],
+    url: &str,
+    cookie_domain: Option<&str>,
+    body: Option<&T>,
+) -> Result<RequestBuilder, String> {
+    let mut req_builder = req_builder;
+    // 服务的User-Agent覆盖全局的，但会被请求配置的headers覆盖
+    if let Some(user_agent) = config.get_user_agent() {
+        req_builder = req_builder.header(USER_AGENT, user_agent);
+    }
+    debug!("Fill in the request from the configured headers");
+    req_builder = fill_headers(req_builder, headers)?;
+    req_builder = fill_request_cookies_from(cookie_stores, req_builder, url, cookie_domain);
+
+    if let Some(body) = body {
+        let content_type = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+            .map(|(_, v)| v)
+            .ok_or_else(|| format!("not found content-type in request headers: {:?}", headers))?;
+        req_builder = fill_body(req_builder, content_type, body)?;
+    }
+    Ok(req_builder)
+}
+
+/// 不发送时请求的内容，见[`preview_request`]。敏感的值被隐藏，见[`redact`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RequestPreview {
+    pub request: String,
+    pub method: String,
+    /// 配置的url，url_handler添加的部分如token不存在
+    pub url: String,
+    /// 实际发送的headers，包括http配置中的headers与User-Agent，按名称排序
+    pub headers: BTreeMap<String, String>,
+    /// 发送的cookie名，与请求时的顺序相同
+    pub cookies: Vec<String>,
+    pub body: Option<String>,
+}
+
+/// 同[`send_request`]构造req_name的第一个请求但不发送。不会跟随重定向。
+///
+/// headers按reqwest发送时的规则合并：请求的headers覆盖http配置中的headers与User-Agent，
+/// 见[`HttpContext::fill_default_headers`]
+pub fn preview_request<T: Serialize + ?Sized>(
+    config: &AppConfig,
+    context: &HttpContext,
+    cookie_store: &CookieStore,
+    req_name: &str,
+    body: Option<&T>,
+) -> Result<RequestPreview, String> {
+    let req_config = get_request_config(config, req_name)
+        .ok_or(format!("{}{}", REQUEST_CONFIG_MISSING, req_name))?;
+    let url = req_config.get_url();
+    let method =
+        Method::from_bytes(req_config.get_method().as_bytes()).map_err(|e| format!("{:?}", e))?;
+    let headers = req_config
+        .get_headers()
+        .ok_or(format!("not found any headers in req url: {}", url))?;
+    let mut req = fill_request(
+        context.request(method.clone(), url),
+        config,
+        headers,
+        &[cookie_store],
+        url,
+        req_config.get_cookie_domain(),
+        body,
+    )?
+    .build()
+    .map_err(|e| format!("build request {} error: {}", req_name, e))?;
+    context.fill_default_headers(&mut req);
+
+    let headers = req
+        .headers()
+        .iter()
+        .map(|(name, val)| {
+            let val =
+                redact::redact_header(name.as_str(), &String::from_utf8_lossy(val.as_bytes()));
+            (name.to_string(), val)
+        })
+        .collect();
+
+    let cookies = cookie_url(url, req_config.get_cookie_domain())
+        .map(|url| select_request_cookies(&[cookie_store], &url))
+        .unwrap_or_default()
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+
+    let is_form = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+    let body = req.body().map(|body| match body.as_bytes() {
+        Some(bytes) if is_form => redact::redact_form(&String::from_utf8_lossy(bytes)),
+        Some(bytes) => redact::redact_body(&String::from_utf8_lossy(bytes)),
+        // multipart
+        None => "<streamed body>".to_string(),
+    });
+    Ok(RequestPreview {
+        request: req_name.to_string(),
+        method: method.to_string(),
+        url: url.to_string(),
+        headers,
+        cookies,
+        body,
+    })
+}
+
+/// status错误时error中包含的body最大字节数
+pub const ERROR_BODY_SNIPPET_LEN: usize = 500;
+
+const RETRY_AFTER_PREFIX: &str = "retry after: ";
+
+/// 从[`send_request`]的error中取出429 Retry-After的秒数
+pub fn parse_retry_after(e: &str) -> Option<Duration> {
+    let (_, rest) = e.split_once(RETRY_AFTER_PREFIX)?;
+    rest.split('s')
+        .next()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// response body超出`max_response_bytes`时error的前缀
+pub const RESPONSE_TOO_LARGE: &str = "response body too large";
+
+/// 读取response body失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadBodyError {
+    /// body超出max字节。content_length为response header中的长度，没有时在读取超出时停止
+    TooLarge {
+        content_length: Option<u64>,
+        max: usize,
+        url: String,
+    },
+    Other(String),
+}
+
+impl fmt::Display for ReadBodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge {
+                content_length: Some(len),
+                max,
+                url,
+            } => write!(
+                f,
+                "{}: content-length {} exceeds {} bytes, url: {}",
+                RESPONSE_TOO_LARGE, len, max, url
+            ),
+            Self::TooLarge {
+                content_length: None,
+                max,
+                url,
+            } => write!(
+                f,
+                "{}: read more than {} bytes, url: {}",
+                RESPONSE_TOO_LARGE, max, url
+            ),
+            Self::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadBodyError {}
+
+impl From<ReadBodyError> for String {
+    fn from(e: ReadBodyError) -> Self {
+        e.to_string()
+    }
+}
+
+/// 按req_name对应request config的`max_response_bytes`读取response body，见[`read_limited_body`]。
+/// 读取的字节数记录到发送请求的context的[`metrics::MetricsRegistry`]中
+pub async fn read_body(
+    config: &AppConfig,
+    req_name: &str,
+    resp: reqwest::Response,
+) -> Result<Vec<u8>, ReadBodyError> {
+    let max = get_request_config(config, req_name)
+        .map(RequestConfig::get_max_response_bytes)
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+    let metrics = resp
+        .extensions()
+        .get::<Arc<Mutex<MetricsRegistry>>>()
+        .cloned();
+    let body = read_limited_body(resp, max).await?;
+    if let Some(metrics) = metrics {
+        metrics.lock().unwrap().record_bytes(req_name, body.len());
+    }
+    Ok(body)
+}
+
+/// 分块读取response body，超出max字节时停止读取并返回[`ReadBodyError::TooLarge`]。
+///
+/// content-length超出max时不读取body
+pub async fn read_limited_body(
+    mut resp: reqwest::Response,
+    max: usize,
+) -> Result<Vec<u8>, ReadBodyError> {
+    let too_large = |content_length, resp: &reqwest::Response| ReadBodyError::TooLarge {
+        content_length,
+        max,
+        url: resp.url().to_string(),
+    };
+    if let Some(len) = resp.content_length().filter(|len| *len > max as u64) {
+        return Err(too_large(Some(len), &resp));
+    }
+    let mut body = vec![];
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| ReadBodyError::Other(context::request_error(e)))?
+    {
+        if body.len() + chunk.len() > max {
+            return Err(too_large(None, &resp));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// 用于日志的response debug字符串，set-cookie等敏感的值被隐藏，见[`redact`]
+pub fn debug_response(resp: &reqwest::Response) -> String {
+    let headers = resp
+        .headers()
+        .iter()
+        .map(|(name, val)| {
+            let val =
+                redact::redact_header(name.as_str(), &String::from_utf8_lossy(val.as_bytes()));
+            format!("{}: {:?}", name, val)
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "Response {{ url: {}, status: {}, headers: {{{}}} }}",
+        resp.url(),
+        resp.status(),
+        headers.join(", ")
+    )
+}
+
+/// 如果response是301/302/307/308重定向，返回解析后的location url。相对的location相对于response url解析
+fn redirect_location(resp: &reqwest::Response) -> Result<Option<Url>, String> {
+    match resp.status() {
+        StatusCode::MOVED_PERMANENTLY
+        | StatusCode::FOUND
+        | StatusCode::TEMPORARY_REDIRECT
+        | StatusCode::PERMANENT_REDIRECT => {}
+        _ => return Ok(None),
+    }
+    let location = resp
+        .headers()
+        .get(LOCATION)
+        .ok_or_else(|| {
+            format!(
+                "not found location in redirect response: {}",
+                debug_response(resp)
+            )
+        })?
+        .to_str()
+        .map_err(|e| format!("invalid location header: {:?}", e))?;
+    resp.url()
+        .join(location)
+        .map(Some)
+        .map_err(|e| format!("invalid redirect location {}: {}", location, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::{MockRequest, MockResponse, MockServer};
+
+    fn app_config(url: &str, extra: &str) -> AppConfig {
+        serde_yaml::from_str(&format!(
+            "username: a
+password: b
+dictionary_path: words.json
+requests:
+  test:
+    url: {}
+    method: GET
+    headers:
+      accept: '*/*'
+{}",
+            url, extra
+        ))
+        .unwrap()
+    }
+
+    /// 捕获dict的日志，返回捕获的日志行。全局logger只能设置一次，多个测试共享
+    fn capture_logs() -> &'static Mutex<Vec<String>> {
+        struct Capture;
+        static LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        static INIT: std::sync::Once = std::sync::Once::new();
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.target().starts_with("dict")
+            }
+
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    LOGS.lock().unwrap().push(record.args().to_string());
+                }
+            }
+
+            fn flush(&self) {}
+        }
+        INIT.call_once(|| {
+            log::set_logger(&Capture).unwrap();
+            log::set_max_level(log::LevelFilter::Debug);
+        });
+        &LOGS
+    }
+
+    #[test]
+    fn invalid_request_cookie_is_redacted() {
+        let logs = capture_logs();
+        let url = Url::parse("https://dict.test/").unwrap();
+        let mut cookie_store = CookieStore::default();
+        let cookie = cookie::Cookie::parse("token=secret\x01token; Path=/").unwrap();
+        cookie_store.insert_raw(&cookie, &url).unwrap();
+        let req = Client::new().get(url.as_str());
+        let req = fill_request_cookies_from(&[&cookie_store], req, url.as_str(), None)
+            .build()
+            .unwrap();
+        assert!(req.headers().get(COOKIE).is_none());
+        let logs = logs.lock().unwrap();
+        assert!(
+            logs.iter()
+                .any(|l| l.starts_with("skipped invalid request cookie: token=")),
+            "{:?}",
+            logs
+        );
+        assert!(logs.iter().all(|l| !l.contains("secret")), "{:?}", logs);
+    }
+
+    fn redirect_server() -> impl Fn(&MockRequest) -> MockResponse {
+        |req| match req.path.as_str() {
+            "/start" => MockResponse::new(302)
+                .header("location", "/a/first")
+                .header("set-cookie", "hop=1; Path=/"),
+            // 相对路径
+            "/a/first" => MockResponse::new(307).header("location", "second"),
+            "/a/second" => MockResponse::text(200, req.header("cookie").unwrap_or("")),
+            _ => MockResponse::new(404),
+        }
+    }
+
+    #[tokio::test]
+    async fn follow_redirects() -> Result<(), String> {
+        let server = MockServer::start(redirect_server()).await;
+        let config = app_config(
+            &server.url("/start"),
+            "    follow_redirects: true\n    max_redirects: 2\n",
+        );
+        let context = HttpContext::new()?;
+        let mut cookie_store = CookieStore::default();
+        let url = Url::parse(&server.url("/")).unwrap();
+        let cookie = cookie::Cookie::parse("token=a; Path=/").unwrap();
+        cookie_store.insert_raw(&cookie, &url).unwrap();
+
+        let resp = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await?;
+        assert_eq!(resp.url().path(), "/a/second");
+        // 重定向中的set-cookie与最后一个response一起更新到cookie_store
+        update_set_cookies(&mut cookie_store, &resp);
+        assert_eq!(
+            cookie_store
+                .get(url.host_str().unwrap(), "/", "hop")
+                .unwrap()
+                .value(),
+            "1"
+        );
+        // 重定向中的set-cookie会被带到后续请求
+        assert_eq!(resp.text().await.unwrap(), "hop=1; token=a");
+        let paths = server
+            .requests()
+            .into_iter()
+            .map(|r| r.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/start", "/a/first", "/a/second"]);
+        Ok(())
+    }
+
+    fn build_body<T: Serialize + ?Sized>(content_type: &str, body: &T) -> Result<Vec<u8>, String> {
+        let req = fill_body(Client::new().post("http://dict.test/"), content_type, body)?
+            .build()
+            .map_err(|e| format!("{:?}", e))?;
+        Ok(req.body().and_then(|b| b.as_bytes()).unwrap().to_vec())
+    }
+
+    #[test]
+    fn fill_body_content_types() -> Result<(), String> {
+        let form = [("a", "1"), ("b", "x y")];
+        assert_eq!(
+            build_body("application/x-www-form-urlencoded", &form)?,
+            b"a=1&b=x+y"
+        );
+        assert_eq!(
+            build_body(
+                "Application/JSON; charset=utf-8",
+                &serde_json::json!({"a": 1})
+            )?,
+            br#"{"a":1}"#
+        );
+        assert_eq!(build_body("text/plain", "raw text")?, b"raw text");
+        assert_eq!(build_body("text/plain", &[1, 2])?, b"[1,2]");
+
+        let e = build_body("application/xml", "<a/>").unwrap_err();
+        assert_eq!(e, "unsupported content type: application/xml");
+        let e = fill_body(
+            Client::new().post("http://dict.test/"),
+            "multipart/form-data",
+            "a",
+        )
+        .unwrap_err();
+        assert!(e.contains("must be a map"), "{}", e);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fill_multipart_body() -> Result<(), String> {
+        let server = MockServer::start(|_| MockResponse::new(200)).await;
+        let req_builder = Client::new()
+            .post(server.url("/"))
+            .header(CONTENT_TYPE, "multipart/form-data");
+        let body = serde_json::json!({"title": "words", "id": 1});
+        fill_body(req_builder, "multipart/form-data", &body)?
+            .send()
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let req = &server.requests()[0];
+        let content_type = req.header("content-type").unwrap();
+        assert!(
+            content_type.starts_with("multipart/form-data; boundary="),
+            "{}",
+            content_type
+        );
+        assert_eq!(
+            req.headers
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+                .count(),
+            1
+        );
+        let body = req.body_str();
+        assert!(body.contains("name=\"title\"\r\n\r\nwords\r\n"), "{}", body);
+        assert!(body.contains("name=\"id\"\r\n\r\n1\r\n"), "{}", body);
+        Ok(())
+    }
+
+    fn insert_cookies(cookie_store: &mut CookieStore, count: usize) {
+        let url = Url::parse("https://dict.test/").unwrap();
+        for i in 0..count {
+            let cookie =
+                cookie::Cookie::parse(format!("c{}={}; Max-Age=86400", i, "v".repeat(64))).unwrap();
+            cookie_store.insert_raw(&cookie, &url).unwrap();
+        }
+    }
+
+    #[test]
+    fn request_cookies_order() {
+        assert_eq!(join_request_cookies(vec![]), None);
+        assert_eq!(
+            join_request_cookies(vec![("a", "1")]).as_deref(),
+            Some("a=1")
+        );
+        assert_eq!(
+            join_request_cookies(vec![
+                ("OUTFOX_SEARCH_USER_ID", "1"),
+                ("DICT_LOGIN", "2"),
+                ("DICT_PERS", "3"),
+                ("DICT_SESS", "4"),
+                ("_ntes_nnid", "5"),
+            ])
+            .as_deref(),
+            Some("DICT_SESS=4; DICT_LOGIN=2; DICT_PERS=3; OUTFOX_SEARCH_USER_ID=1; _ntes_nnid=5")
+        );
+
+        // 与store中的插入顺序无关
+        let url = Url::parse("https://dict.youdao.com/").unwrap();
+        let mut cookie_store = CookieStore::default();
+        for cookie in &["DICT_LOGIN=2", "b=1", "DICT_SESS=1", "a=1"] {
+            let cookie = cookie::Cookie::parse(*cookie).unwrap();
+            cookie_store.insert_raw(&cookie, &url).unwrap();
+        }
+        let req = fill_request_cookies(&cookie_store, Client::new().get(url.clone()), url.as_str())
+            .build()
+            .unwrap();
+        assert_eq!(
+            req.headers().get(COOKIE).unwrap(),
+            "DICT_SESS=1; DICT_LOGIN=2; a=1; b=1"
+        );
+        let req = fill_request_cookies(
+            &CookieStore::default(),
+            Client::new().get(url.clone()),
+            url.as_str(),
+        )
+        .build()
+        .unwrap();
+        assert!(req.headers().get(COOKIE).is_none());
+    }
+
+    #[test]
+    fn cookie_domain_isolation() {
+        let mut cookie_store = CookieStore::default();
+        for (url, cookie) in &[
+            ("https://dict.youdao.com/", "DICT_PERS=1; Domain=youdao.com"),
+            ("https://www.maimemo.com/", "userToken=2"),
+        ] {
+            let cookie = cookie::Cookie::parse(*cookie).unwrap();
+            cookie_store
+                .insert_raw(&cookie, &Url::parse(url).unwrap())
+                .unwrap();
+        }
+        let config = app_config(
+            "https://dict.youdao.com/words",
+            "  mirror:
+    url: https://mirror.test/words
+    method: GET
+    cookie_domain: dict.youdao.com
+  other:
+    url: https://mirror.test/other
+    method: GET
+  maimemo:
+    url: https://www.maimemo.com/notepad
+    method: GET
+  invalid:
+    url: not a url
+    method: GET
+",
+        );
+        let mapping = request_cookies(&config, &cookie_store)
+            .into_iter()
+            .map(|r| (r.request, r.cookies))
+            .collect::<Vec<_>>();
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            mapping,
+            vec![
+                ("invalid".to_string(), names(&[])),
+                ("maimemo".to_string(), names(&["userToken"])),
+                ("mirror".to_string(), names(&["DICT_PERS"])),
+                ("other".to_string(), names(&[])),
+                ("test".to_string(), names(&["DICT_PERS"])),
+            ]
+        );
+
+        let cookie_header = |url: &str, cookie_domain| {
+            let req = Client::new().get(url);
+            let req = fill_request_cookies_from(&[&cookie_store], req, url, cookie_domain)
+                .build()
+                .unwrap();
+            req.headers()
+                .get(COOKIE)
+                .map(|v| v.to_str().unwrap().to_string())
+        };
+        assert_eq!(
+            cookie_header("https://mirror.test/words", Some("dict.youdao.com")).as_deref(),
+            Some("DICT_PERS=1")
+        );
+        assert_eq!(cookie_header("https://mirror.test/words", None), None);
+        assert_eq!(
+            cookie_header("https://www.maimemo.com/", Some("www.maimemo.com")).as_deref(),
+            Some("userToken=2")
+        );
+        assert!(cookie_url("https://mirror.test/", Some("bad domain")).is_err());
+    }
+
+    #[test]
+    fn non_ascii_set_cookies() {
+        let mut cookie_store = CookieStore::default();
+        let url = Url::parse("https://www.maimemo.com/auth/login").unwrap();
+        cookie_store
+            .insert_raw(&cookie::Cookie::parse("PHPSESSID=old").unwrap(), &url)
+            .unwrap();
+        let values = [
+            HeaderValue::from_bytes(b"userToken=1; Path=/; Comment=\xE4\xB8\xAD\xE6\x96\x87")
+                .unwrap(),
+            HeaderValue::from_static("PHPSESSID=first; Path=/"),
+            HeaderValue::from_static("PHPSESSID=new; Path=/"),
+            HeaderValue::from_static("invalid"),
+        ];
+        assert!(values[0].to_str().is_err());
+        insert_set_cookies(&mut cookie_store, values.iter(), &url);
+        let value = |name| {
+            cookie_store
+                .get("www.maimemo.com", "/", name)
+                .map(|c| c.value())
+        };
+        assert_eq!(value("userToken"), Some("1"));
+        assert_eq!(value("PHPSESSID"), Some("new"));
+        assert_eq!(cookie_store.iter_any().count(), 3);
+    }
+
+    #[test]
+    fn purge_expired() {
+        let mut cookie_store = CookieStore::default();
+        let url = Url::parse("https://dict.test/").unwrap();
+        for cookie in &["a=1; Max-Age=1", "b=1; Max-Age=86400"] {
+            let cookie = cookie::Cookie::parse(*cookie).unwrap();
+            cookie_store.insert_raw(&cookie, &url).unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        assert_eq!(purge_expired_cookies(&mut cookie_store), 1);
+        assert_eq!(cookie_store.iter_any().count(), 1);
+        assert!(cookie_store.contains("dict.test", "/", "b"));
+    }
+
+    #[test]
+    fn login_cookies_expiry() {
+        let mut cookie_store = CookieStore::default();
+        let url = Url::parse("https://dict.test/").unwrap();
+        for cookie in &[
+            "soon=1; Max-Age=3600",
+            "later=1; Max-Age=172800",
+            "session=1",
+        ] {
+            let cookie = cookie::Cookie::parse(*cookie).unwrap();
+            cookie_store.insert_raw(&cookie, &url).unwrap();
+        }
+        let statuses = login_cookie_status(
+            &cookie_store,
+            &[
+                ("dict.test", "soon"),
+                ("dict.test", "later"),
+                ("dict.test", "session"),
+                ("dict.test", "missing"),
+            ],
+        );
+        let expiring = statuses
+            .iter()
+            .filter(|s| s.expires_within(COOKIE_EXPIRY_WARNING_SECS))
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(expiring, vec!["soon"]);
+        assert!(statuses[0].expires_in_secs.unwrap() <= 3600);
+        assert!(statuses[0].expires.is_some());
+        assert_eq!((statuses[2].present, &statuses[2].expires), (true, &None));
+        assert!(!statuses[3].present);
+    }
+
+    #[test]
+    fn save_smaller_cookie_store() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("cookies.json");
+        let path = path.to_str().unwrap();
+
+        let mut cookie_store = CookieStore::default();
+        insert_cookies(&mut cookie_store, 20);
+        save_cookie_store(path, &cookie_store, None)?;
+
+        let mut cookie_store = CookieStore::default();
+        insert_cookies(&mut cookie_store, 1);
+        save_cookie_store(path, &cookie_store, None)?;
+
+        let cookie_store = build_cookie_store(Some(path), None)?;
+        assert_eq!(cookie_store.iter_unexpired().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_cookie_store_saves() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("cookies.json");
+        let path = path.to_str().unwrap().to_string();
+
+        // 多个进程同时保存与加载时不会读取到写入一半的文件
+        let handles = (1..=8)
+            .map(|n| {
+                let path = path.clone();
+                std::thread::spawn(move || -> Result<(), String> {
+                    let mut cookie_store = CookieStore::default();
+                    insert_cookies(&mut cookie_store, n * 10);
+                    for _ in 0..10 {
+                        save_cookie_store(&path, &cookie_store, None)?;
+                        let loaded = build_cookie_store(Some(&path), None)?;
+                        assert_eq!(loaded.iter_unexpired().count() % 10, 0);
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        let corrupted = fs::read_dir(dir.path())
+            .map_err(|e| format!("{:?}", e))?
+            .filter_map(Result::ok)
+            .filter(|e| e.file_name().to_string_lossy().contains(".corrupt-"))
+            .count();
+        assert_eq!(corrupted, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn encrypted_cookie_store() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("cookies.json");
+        let path = path.to_str().unwrap();
+
+        let mut cookie_store = CookieStore::default();
+        insert_cookies(&mut cookie_store, 2);
+        save_cookie_store(path, &cookie_store, Some("secret"))?;
+        let data = fs::read(path).map_err(|e| format!("{:?}", e))?;
+        assert!(crypto::is_encrypted(&data));
+        assert!(!String::from_utf8_lossy(&data).contains("c0"));
+
+        let cookie_store = build_cookie_store(Some(path), Some("secret"))?;
+        assert_eq!(cookie_store.iter_unexpired().count(), 2);
+
+        // 错误的passphrase不会被当作损坏的文件移走
+        let e = build_cookie_store(Some(path), Some("wrong")).unwrap_err();
+        assert!(e.starts_with("wrong cookie passphrase"), "{}", e);
+        let e = build_cookie_store(Some(path), None).unwrap_err();
+        assert!(e.contains("no cookie passphrase"), "{}", e);
+        assert_eq!(fs::read(path).map_err(|e| format!("{:?}", e))?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn upgrade_plaintext_cookie_store() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("cookies.json");
+        let path = path.to_str().unwrap();
+
+        let mut cookie_store = CookieStore::default();
+        insert_cookies(&mut cookie_store, 1);
+        save_cookie_store(path, &cookie_store, None)?;
+
+        let cookie_store = build_cookie_store(Some(path), Some("secret"))?;
+        assert_eq!(cookie_store.iter_unexpired().count(), 1);
+        save_cookie_store(path, &cookie_store, Some("secret"))?;
+        assert!(crypto::is_encrypted(
+            &fs::read(path).map_err(|e| format!("{:?}", e))?
+        ));
+        let cookie_store = build_cookie_store(Some(path), Some("secret"))?;
+        assert_eq!(cookie_store.iter_unexpired().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn move_corrupt_cookie_file() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("cookies.json");
+        fs::write(&path, "{\"raw_cookie\": tru").map_err(|e| format!("{:?}", e))?;
+
+        let cookie_store = build_cookie_store(path.to_str(), None)?;
+        assert_eq!(cookie_store.iter_unexpired().count(), 0);
+        let names = fs::read_dir(dir.path())
+            .map_err(|e| format!("{:?}", e))?
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 1);
+        assert!(names[0].starts_with("cookies.json.corrupt-"), "{:?}", names);
+        Ok(())
+    }
+
+    #[test]
+    fn fill_mixed_case_headers() -> Result<(), String> {
+        let headers = [("Content-Type", "text/plain"), ("User-Agent", "dict")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        let req = fill_headers(Client::new().get("http://dict.test/"), &headers)?
+            .build()
+            .map_err(|e| format!("{:?}", e))?;
+        assert_eq!(req.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(req.headers()[USER_AGENT], "dict");
+
+        let headers = [
+            ("Content-Type", "text/plain"),
+            ("Bad Header", "a"),
+            ("accept", "a\nb"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<_, _>>();
+        let e = fill_headers(Client::new().get("http://dict.test/"), &headers).unwrap_err();
+        assert_eq!(
+            e,
+            r#"invalid request headers: invalid name: "Bad Header", invalid value of accept: "a\nb""#
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ok_status_allowlist() -> Result<(), String> {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/created" => MockResponse::new(201),
+            "/throttled" => MockResponse::new(429).header("retry-after", "30"),
+            _ => MockResponse::new(403)
+                .header("set-cookie", "token=secret; Path=/")
+                .body("x".repeat(600).as_bytes()),
+        })
+        .await;
+        let context = HttpContext::new()?;
+        let cookie_store = CookieStore::default();
+
+        let config = app_config(&server.url("/created"), "");
+        let e = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await
+            .unwrap_err();
+        assert!(e.starts_with("Response code error: 201"), "{}", e);
+
+        let config = app_config(&server.url("/created"), "    ok_status: [200, 201]\n");
+        let resp = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await?;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        // error中包含body的前500个字节
+        let config = app_config(&server.url("/forbidden"), "");
+        let e = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            e,
+            format!(
+                "Response code error: 403 Forbidden, body: {}",
+                "x".repeat(500)
+            )
+        );
+        assert_eq!(parse_retry_after(&e), None);
+
+        let config = app_config(&server.url("/throttled"), "");
+        let e = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(parse_retry_after(&e), Some(Duration::from_secs(30)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn redact_set_cookie() -> Result<(), String> {
+        let server = MockServer::start(|_| {
+            MockResponse::new(200).header("set-cookie", "token=secret; Path=/")
+        })
+        .await;
+        let resp = HttpContext::new()?
+            .request(Method::GET, &server.url("/"))
+            .send()
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let debug = debug_response(&resp);
+        assert!(
+            debug.contains("set-cookie: \"token=se***(6); Path=/\""),
+            "{}",
+            debug
+        );
+        assert!(!debug.contains("secret"), "{}", debug);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn redirects_limit() -> Result<(), String> {
+        let server = MockServer::start(redirect_server()).await;
+        let context = HttpContext::new()?;
+        let cookie_store = CookieStore::default();
+
+        let config = app_config(
+            &server.url("/start"),
+            "    follow_redirects: true\n    max_redirects: 1\n",
+        );
+        let e = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await
+            .unwrap_err();
+        assert!(e.contains("too many redirects"), "{}", e);
+
+        // 默认不跟随重定向，如登录时
+        let config = app_config(&server.url("/start"), "");
+        let resp = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await?;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn response_size_limit() -> Result<(), String> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // content-length超出时不读取body
+        let server = MockServer::start(|_| MockResponse::text(200, &"x".repeat(2048))).await;
+        let context = HttpContext::new()?;
+        let cookie_store = CookieStore::default();
+        let config = app_config(&server.url("/"), "    max_response_bytes: 1024\n");
+        let resp = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await?;
+        let e = read_body(&config, "test", resp).await.unwrap_err();
+        assert_eq!(
+            e,
+            ReadBodyError::TooLarge {
+                content_length: Some(2048),
+                max: 1024,
+                url: server.url("/"),
+            }
+        );
+        assert!(e
+            .to_string()
+            .starts_with(&format!("{}: content-length 2048", RESPONSE_TOO_LARGE)));
+        let config = app_config(&server.url("/"), "");
+        let resp = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await?;
+        assert_eq!(read_body(&config, "test", resp).await?.len(), 2048);
+
+        // 没有content-length时持续发送body直到连接被关闭
+        const CHUNK: usize = 64 * 1024;
+        const TOTAL: usize = 64 * 1024 * 1024;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let addr = listener.local_addr().map_err(|e| format!("{:?}", e))?;
+        let written = Arc::new(AtomicUsize::new(0));
+        let server = tokio::spawn({
+            let written = written.clone();
+            async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let head = "HTTP/1.1 200 OK\r\nconnection: close\r\n\r\n";
+                stream.write_all(head.as_bytes()).await.unwrap();
+                let chunk = vec![b'x'; CHUNK];
+                while written.load(Ordering::SeqCst) < TOTAL {
+                    if stream.write_all(&chunk).await.is_err() {
+                        break;
+                    }
+                    written.fetch_add(CHUNK, Ordering::SeqCst);
+                }
+            }
+        });
+        let config = app_config(
+            &format!("http://{}/", addr),
+            "    max_response_bytes: 100000\n",
+        );
+        let resp = send_request_nobody(&config, &context, &cookie_store, "test", |u| u.to_string())
+            .await?;
+        let e = read_body(&config, "test", resp).await.unwrap_err();
+        assert!(
+            matches!(
+                e,
+                ReadBodyError::TooLarge {
+                    content_length: None,
+                    max: 100000,
+                    ..
+                }
+            ),
+            "{}",
+            e
+        );
+        server.await.map_err(|e| format!("{:?}", e))?;
+        assert!(written.load(Ordering::SeqCst) < TOTAL);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_request_metrics() -> Result<(), String> {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/a" => MockResponse::text(200, "hello"),
+            _ => MockResponse::text(200, "hello world"),
+        })
+        .await;
+        let config = app_config(&server.url("/a"), "");
+        let context = HttpContext::new()?;
+        let cookie_store = CookieStore::default();
+        for path in ["/a", "/b"] {
+            let resp = send_request_nobody(&config, &context, &cookie_store, "test", |u| {
+                u.replace("/a", path)
+            })
+            .await?;
+            read_body(&config, "test", resp).await?;
+        }
+
+        let timings = context.metrics().lock().unwrap().timings();
+        assert_eq!(timings.requests.len(), 1);
+        let test = &timings.requests[0];
+        assert_eq!(
+            (test.name.as_str(), test.count, test.errors, test.bytes),
+            ("test", 2, 0, 16)
+        );
+        assert_eq!(timings.total.count, 2);
+        assert_eq!(timings.total.bytes, 16);
+        Ok(())
+    }
+
+    #[test]
+    fn preview_configured_request() -> Result<(), String> {
+        let http: HttpConfig = serde_yaml::from_str(
+            "user_agent: dict-test
+headers:
+  Accept: text/html
+  X-Trace: 'on'
+",
+        )
+        .unwrap();
+        let config = app_config(
+            "http://www.maimemo.com/auth/login",
+            "      content-type: application/x-www-form-urlencoded\n",
+        );
+        let context = HttpContext::from_config(&http)?;
+        let mut cookie_store = CookieStore::default();
+        let url = Url::parse("http://www.maimemo.com/").unwrap();
+        for cookie in ["userToken=abcdefgh; Path=/", "other=1; Path=/other"] {
+            let cookie = cookie::Cookie::parse(cookie).unwrap();
+            cookie_store.insert_raw(&cookie, &url).unwrap();
+        }
+        let form = [("email", "a@b.com"), ("password", "secret")];
+
+        let preview = preview_request(&config, &context, &cookie_store, "test", Some(&form))?;
+        let headers = preview
+            .headers
+            .iter()
+            .map(|(k, v)| format!("{}: {}", k, v))
+            .collect::<Vec<_>>();
+        // 请求配置的accept覆盖http配置的，cookie与password被隐藏
+        assert_eq!(
+            headers,
+            vec![
+                "accept: */*",
+                "content-type: application/x-www-form-urlencoded",
+                "cookie: userToken=ab***(8)",
+                "user-agent: dict-test",
+                "x-trace: on",
+            ]
+        );
+        assert_eq!(preview.method, "GET");
+        assert_eq!(preview.url, "http://www.maimemo.com/auth/login");
+        assert_eq!(preview.cookies, vec!["userToken"]);
+        assert_eq!(
+            preview.body.as_deref(),
+            Some("email=a%40b.com&password=se***(6)")
+        );
+
+        let preview = preview_request(&config, &context, &cookie_store, "test", None::<&str>)?;
+        assert_eq!(preview.body, None);
+        assert!(
+            preview_request(&config, &context, &cookie_store, "x", None::<&str>)
+                .unwrap_err()
+                .starts_with(REQUEST_CONFIG_MISSING)
+        );
+        Ok(())
     }
 }
@@ -0,0 +1,381 @@
+//! 录制与回放请求的响应，用于调试服务端的变化与生成测试fixtures。
+//!
+//! 录制时每个请求的最终响应被写入目录中编号的文件：`{n}-{req_name}.json`保存url、status与
+//! headers，`{n}-{req_name}.body`保存body。cookie、token与password的值会被隐藏，见[`redact`]。
+//! 回放时通过req_name与url的hash查找响应，不访问网络
+use crate::{hash, redact};
+use reqwest::{Method, ResponseBuilderExt, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// 一个录制的响应，body保存在同名的`.body`文件中
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordedResponse {
+    pub req_name: String,
+    pub method: String,
+    /// 隐藏了敏感query参数的url
+    pub url: String,
+    /// 用于回放时查找的method与url的hash
+    pub key: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record {
+        dir: PathBuf,
+        /// 下一个文件的编号
+        seq: AtomicUsize,
+    },
+    Replay {
+        entries: Vec<(RecordedResponse, PathBuf)>,
+        /// 已回放的entries索引
+        replayed: Mutex<HashSet<usize>>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Recorder {
+    mode: Mode,
+}
+
+impl Recorder {
+    /// 录制响应到dir，dir不存在时被创建。已有文件时从最大的编号后继续
+    pub fn record(dir: &str) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("create dir error: {}, path: {}", e, dir))?;
+        let next = read_entries(dir)?
+            .iter()
+            .filter_map(|path| file_seq(path))
+            .max()
+            .map_or(1, |n| n + 1);
+        Ok(Self {
+            mode: Mode::Record {
+                dir: PathBuf::from(dir),
+                seq: AtomicUsize::new(next),
+            },
+        })
+    }
+
+    /// 从dir中回放录制的响应
+    pub fn replay(dir: &str) -> Result<Self, String> {
+        let entries = read_entries(dir)?
+            .into_iter()
+            .map(|path| {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| format!("read file error: {}, path: {:?}", e, path))?;
+                let meta = serde_json::from_str::<RecordedResponse>(&contents).map_err(|e| {
+                    format!("parse recorded response error: {}, path: {:?}", e, path)
+                })?;
+                Ok((meta, path.with_extension("body")))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        debug!("loaded {} recorded responses from {}", entries.len(), dir);
+        Ok(Self {
+            mode: Mode::Replay {
+                entries,
+                replayed: Mutex::new(HashSet::new()),
+            },
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self.mode, Mode::Replay { .. })
+    }
+
+    /// 录制模式时保存resp并返回一个相同的response，否则直接返回resp
+    pub async fn save(
+        &self,
+        req_name: &str,
+        method: &Method,
+        resp: reqwest::Response,
+    ) -> Result<reqwest::Response, String> {
+        let (dir, seq) = match &self.mode {
+            Mode::Record { dir, seq } => (dir, seq),
+            Mode::Replay { .. } => return Ok(resp),
+        };
+        let url = resp.url().clone();
+        let status = resp.status().as_u16();
+        let headers = resp
+            .headers()
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.as_str().to_string(),
+                    String::from_utf8_lossy(v.as_bytes()).to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|e| format!("read response body error: {:?}", e))?
+            .to_vec();
+        let recorded = RecordedResponse {
+            req_name: req_name.to_string(),
+            method: method.to_string(),
+            url: redact_url(url.as_str()),
+            key: request_key(method, url.as_str()),
+            status,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.clone(), redact::redact_header(k, v)))
+                .collect(),
+        };
+        let path = dir.join(format!(
+            "{:03}-{}.json",
+            seq.fetch_add(1, Ordering::SeqCst),
+            req_name
+        ));
+        let meta = serde_json::to_string_pretty(&recorded).map_err(|e| e.to_string())?;
+        fs::write(&path, meta + "\n")
+            .and_then(|_| fs::write(path.with_extension("body"), redact_body(&body)))
+            .map_err(|e| format!("write recorded response error: {}, path: {:?}", e, path))?;
+        debug!("recorded response of {} to {:?}", req_name, path);
+        build_response(status, &headers, url, body)
+    }
+
+    /// 回放req_name对应的响应。相同method与url的响应按顺序使用，用完后重复使用最后一个
+    ///
+    /// # Errors
+    ///
+    /// 如果不是回放模式或没有录制相同method与url的响应
+    pub fn load(
+        &self,
+        req_name: &str,
+        method: &Method,
+        url: &str,
+    ) -> Result<reqwest::Response, String> {
+        let (entries, replayed) = match &self.mode {
+            Mode::Replay { entries, replayed } => (entries, replayed),
+            Mode::Record { .. } => return Err("recorder is not in replay mode".to_string()),
+        };
+        let key = request_key(method, url);
+        let mut replayed = replayed.lock().unwrap();
+        let same = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, (meta, _))| meta.req_name == req_name && meta.key == key)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let i = same
+            .iter()
+            .find(|i| !replayed.contains(i))
+            .or_else(|| same.last())
+            .copied()
+            .ok_or_else(|| format!("no recording for {} {}", method, redact_url(url)))?;
+        replayed.insert(i);
+        let (meta, body_path) = &entries[i];
+        debug!("replaying {} from {:?}", req_name, body_path);
+        let body = fs::read(body_path)
+            .map_err(|e| format!("read file error: {}, path: {:?}", e, body_path))?;
+        let url = Url::parse(url).map_err(|e| format!("invalid url {}: {}", url, e))?;
+        build_response(meta.status, &meta.headers, url, body)
+    }
+}
+
+fn build_response(
+    status: u16,
+    headers: &[(String, String)],
+    url: Url,
+    body: Vec<u8>,
+) -> Result<reqwest::Response, String> {
+    let mut builder = http::Response::builder().status(status).url(url);
+    for (k, v) in headers {
+        // body已被解压
+        if k.eq_ignore_ascii_case("content-encoding") || k.eq_ignore_ascii_case("content-length") {
+            continue;
+        }
+        builder = builder.header(k.as_str(), v.as_str());
+    }
+    builder
+        .body(body)
+        .map(reqwest::Response::from)
+        .map_err(|e| format!("build response error: {}", e))
+}
+
+/// dir中所有录制的`.json`文件，按文件名排序
+fn read_entries(dir: &str) -> Result<Vec<PathBuf>, String> {
+    let mut paths = fs::read_dir(dir)
+        .map_err(|e| format!("read dir error: {}, path: {}", e, dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths)
+}
+
+/// 文件名前的编号
+fn file_seq(path: &Path) -> Option<usize> {
+    path.file_name()?.to_str()?.split('-').next()?.parse().ok()
+}
+
+/// 隐藏url query中敏感参数的值
+pub fn redact_url(url: &str) -> String {
+    match url.split_once('?') {
+        Some((path, query)) => format!("{}?{}", path, redact::redact_form(query)),
+        None => url.to_string(),
+    }
+}
+
+/// json body中敏感的值被隐藏，其它body不变
+fn redact_body(body: &[u8]) -> Vec<u8> {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(value) => redact::redact_json(&value).to_string().into_bytes(),
+        Err(_) => body.to_vec(),
+    }
+}
+
+/// method与url的hash。url中敏感参数的值被忽略，保证回放时不同的token也可以匹配
+fn request_key(method: &Method, url: &str) -> String {
+    let url = match url.split_once('?') {
+        Some((path, query)) => {
+            let query = query
+                .split('&')
+                .map(|pair| match pair.split_once('=') {
+                    Some((k, _)) if redact::is_secret_key(k) => format!("{}=", k),
+                    _ => pair.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            format!("{}?{}", path, query)
+        }
+        None => url.to_string(),
+    };
+    let hash = hash::fnv1a(format!("{} {}", method, url).as_bytes());
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::context::HttpContext;
+    use crate::client::send_request_nobody;
+    use crate::config::AppConfig;
+    use cookie_store::CookieStore;
+    use mock_server::{MockResponse, MockServer};
+
+    fn words_config(server: &MockServer) -> AppConfig {
+        let yaml = format!(
+            "dictionary_path: words.json
+requests:
+  get-words:
+    url: {}
+    method: GET
+    headers:
+      accept: '*/*'
+",
+            server.url("/words")
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    async fn mocked_words() -> MockServer {
+        MockServer::start(|req| {
+            let page = req.path.rsplit('=').next().unwrap_or_default().to_string();
+            MockResponse::json(
+                200,
+                &serde_json::json!({"token": "secret-token", "words": [format!("page-{}", page)]}),
+            )
+            .header("set-cookie", "userToken=secret-cookie; Path=/")
+        })
+        .await
+    }
+
+    async fn get_words(
+        config: &AppConfig,
+        context: &HttpContext,
+        page: usize,
+    ) -> Result<serde_json::Value, String> {
+        send_request_nobody(
+            config,
+            context,
+            &CookieStore::default(),
+            "get-words",
+            |url| format!("{}?token=secret-token&page={}", url, page),
+        )
+        .await?
+        .json()
+        .await
+        .map_err(|e| format!("{:?}", e))
+    }
+
+    #[tokio::test]
+    async fn record_and_replay() -> Result<(), String> {
+        let server = mocked_words().await;
+        let config = words_config(&server);
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let dir = dir.path().to_str().unwrap();
+
+        let context = HttpContext::new()?.with_recorder(Recorder::record(dir)?);
+        for page in 0..2 {
+            let words = get_words(&config, &context, page).await?;
+            // 录制时调用者获取原始的response
+            assert_eq!(words["token"], "secret-token");
+        }
+        assert_eq!(server.requests().len(), 2);
+
+        let meta = fs::read_to_string(format!("{}/002-get-words.json", dir)).unwrap();
+        let body = fs::read_to_string(format!("{}/002-get-words.body", dir)).unwrap();
+        for recorded in [&meta, &body].iter() {
+            assert!(!recorded.contains("secret-"), "{}", recorded);
+        }
+        let meta = serde_json::from_str::<RecordedResponse>(&meta).unwrap();
+        assert_eq!(meta.status, 200);
+        assert!(meta.url.ends_with("&page=1"), "{}", meta.url);
+        assert!(meta
+            .headers
+            .iter()
+            .any(|(k, v)| k == "set-cookie" && v.starts_with("userToken=")));
+
+        // 按url查找，不依赖请求的顺序
+        let context = HttpContext::new()?.with_recorder(Recorder::replay(dir)?);
+        assert_eq!(
+            get_words(&config, &context, 1).await?["words"],
+            serde_json::json!(["page-1"])
+        );
+        assert_eq!(
+            get_words(&config, &context, 0).await?["words"],
+            serde_json::json!(["page-0"])
+        );
+        assert_eq!(server.requests().len(), 2);
+
+        // 继续录制时不覆盖已有的文件
+        let context = HttpContext::new()?.with_recorder(Recorder::record(dir)?);
+        get_words(&config, &context, 2).await?;
+        assert!(Path::new(&format!("{}/003-get-words.body", dir)).exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replay_fallback() -> Result<(), String> {
+        let server = mocked_words().await;
+        let config = words_config(&server);
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let dir = dir.path().to_str().unwrap();
+        let context = HttpContext::new()?.with_recorder(Recorder::record(dir)?);
+        get_words(&config, &context, 0).await?;
+
+        let context = HttpContext::new()?.with_recorder(Recorder::replay(dir)?);
+        // 相同url可以重复回放
+        get_words(&config, &context, 0).await?;
+        get_words(&config, &context, 0).await?;
+        // 未录制的url不使用其它url的response
+        let e = get_words(&config, &context, 5).await.unwrap_err();
+        assert!(e.contains("no recording for GET http://"), "{}", e);
+        assert!(
+            e.ends_with("&page=5") && !e.contains("secret-token"),
+            "{}",
+            e
+        );
+        assert_eq!(server.requests().len(), 1);
+
+        let e = Recorder::replay(&format!("{}/missing", dir)).unwrap_err();
+        assert!(e.contains("missing"), "{}", e);
+        Ok(())
+    }
+}
@@ -1,8 +1,17 @@
+use crate::client::{context::HttpContext, cookie_origin::CookieOrigin, login_state::*, *};
 use crate::config::*;
-use crate::client::*;
+use crate::error::{
+    DictError, LOGIN_CAPTCHA_REQUIRED, LOGIN_FAILED, LOGIN_RATE_LIMITED, NOT_LOGGED_IN,
+    WRONG_CREDENTIALS,
+};
+use crate::events::Event;
+use crate::redact;
 use cookie_store::CookieStore;
-use reqwest::{self, Client};
+use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ResponseResult<T> {
@@ -17,32 +26,596 @@ struct Page<T> {
     #[serde(rename = "itemList")]
     item_list: Vec<T>,
 }
+
+/// 获取单词本时失败或被取消。fetched为之前已获取的页中的单词，可以合并到本地后在下次refresh时继续
+#[derive(Debug)]
+pub struct PartialWordsError {
+    pub fetched: Vec<WordItem>,
+    /// 单词本中的数量，获取数量失败时为0
+    pub total: usize,
+    pub error: String,
+}
+
+impl fmt::Display for PartialWordsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}. fetched {} of {} words before failure",
+            self.error,
+            self.fetched.len(),
+            self.total
+        )
+    }
+}
+
+impl std::error::Error for PartialWordsError {}
+
 #[derive(Serialize, Deserialize, Debug, Ord, PartialOrd, Eq, PartialEq, Clone)]
 pub struct WordItem {
     #[serde(rename = "itemId")]
     pub item_id: String,
-    #[serde(rename = "bookId")]
+    #[serde(
+        rename = "bookId",
+        default,
+        deserialize_with = "lenient::null_as_default"
+    )]
     pub book_id: String,
-    #[serde(rename = "bookName")]
+    #[serde(
+        rename = "bookName",
+        default,
+        deserialize_with = "lenient::null_as_default"
+    )]
     pub book_name: String,
     pub word: String,
+    #[serde(default, deserialize_with = "lenient::null_as_default")]
     pub trans: String,
+    #[serde(default, deserialize_with = "lenient::null_as_default")]
     pub phonetic: String,
-    #[serde(rename = "modifiedTime")]
+    #[serde(
+        rename = "modifiedTime",
+        default,
+        deserialize_with = "lenient::number_or_string"
+    )]
     pub modified_time: usize,
+    /// 仅保存在本地的标签、笔记与历史，youdao中没有该字段。为默认值时不保存
+    #[serde(default, skip_serializing_if = "LocalMeta::is_empty")]
+    pub local: Option<LocalMeta>,
+}
+
+/// 单词在本地的标签、笔记与历史，refresh时被保留
+#[derive(Serialize, Deserialize, Debug, Default, Ord, PartialOrd, Eq, PartialEq, Clone)]
+pub struct LocalMeta {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// 第一次合并到本地时的modified_time，仅在之后modified_time改变时保存。见[`WordItem::first_seen`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<usize>,
+    /// 最后一次从youdao refresh时单词仍存在的时间，毫秒
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen_in_remote: Option<usize>,
+    /// 已通过lookup补全过，查询结果中没有音标等字段时再次enrich也不会补全
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub enriched: bool,
+}
+
+impl LocalMeta {
+    /// local为None或没有任何值
+    fn is_empty(local: &Option<LocalMeta>) -> bool {
+        local
+            .as_ref()
+            .is_none_or(|local| *local == LocalMeta::default())
+    }
+}
+
+impl WordItem {
+    /// 单词第一次进入单词本的时间，毫秒。没有记录时为modified_time
+    pub fn first_seen(&self) -> usize {
+        self.local
+            .as_ref()
+            .and_then(|local| local.first_seen)
+            .unwrap_or(self.modified_time)
+    }
+
+    pub fn last_seen_in_remote(&self) -> Option<usize> {
+        self.local
+            .as_ref()
+            .and_then(|local| local.last_seen_in_remote)
+    }
+
+    /// 获取local，不存在时创建
+    pub fn local_mut(&mut self) -> &mut LocalMeta {
+        self.local.get_or_insert_with(LocalMeta::default)
+    }
+
+    /// 是否有标签tag，忽略大小写
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.local
+            .as_ref()
+            .is_some_and(|local| local.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+    }
+}
+
+/// 登录后需要的cookies: `(domain, name)`
+pub const LOGIN_COOKIES: &[(&str, &str)] = &[
+    ("youdao.com", "OUTFOX_SEARCH_USER_ID"),
+    ("youdao.com", "DICT_PERS"),
+];
+
+/// 默认的单词查询api，不需要登录
+pub const DEFAULT_LOOKUP_URL: &str = "https://dict.youdao.com/jsonapi";
+
+/// 默认的添加单词到单词本api
+pub const DEFAULT_ADD_WORD_URL: &str = "https://dict.youdao.com/wordbook/webapi/v2/ajax/add";
+
+/// 默认的从单词本删除单词api
+pub const DEFAULT_DELETE_WORD_URL: &str = "https://dict.youdao.com/wordbook/webapi/v2/ajax/delete";
+
+/// 批量添加或删除时两次请求的默认间隔毫秒数。youdao每秒仅允许少量修改
+pub const DEFAULT_BATCH_DELAY_MILLIS: u64 = 500;
+
+/// 批量操作中每个单词失败后的默认重试次数
+pub const DEFAULT_BATCH_RETRIES: usize = 2;
+
+/// 默认的获取所有单词本api
+pub const DEFAULT_GET_BOOKS_URL: &str = "https://dict.youdao.com/wordbook/webapi/books";
+
+/// 默认的修改单词所在单词本api
+pub const DEFAULT_UPDATE_WORD_BOOK_URL: &str =
+    "https://dict.youdao.com/wordbook/webapi/v2/ajax/modify";
+
+/// 登录失败页面中的滑块验证码，由网易易盾提供
+const LOGIN_CAPTCHA_SELECTOR: &str =
+    ".yidun, .yidun_slider, script[src*=\"cstaticdun\"], input[name=\"captcha\"]";
+
+/// 登录失败页面中的错误码与提示
+const LOGIN_ERROR_CODE_SELECTOR: &str = "input[name=\"error_code\"], input[name=\"errorCode\"]";
+
+const LOGIN_ERROR_MSG_SELECTOR: &str = "#errorMsg, .login-error, .error-msg";
+
+/// 已知的登录错误码：420用户不存在，460密码错误
+const WRONG_CREDENTIALS_CODES: &[i64] = &[420, 460];
+
+/// 已知的登录错误码：412登录过于频繁
+const LOGIN_RATE_LIMITED_CODES: &[i64] = &[412];
+
+/// 没有错误码时通过提示中的关键字识别
+const WRONG_CREDENTIALS_KEYWORDS: &[&str] =
+    &["密码错误", "用户名或密码", "帐号不存在", "账号不存在"];
+
+const LOGIN_RATE_LIMITED_KEYWORDS: &[&str] = &["频繁", "稍后再试", "次数过多"];
+
+/// youdao拒绝登录时响应中可以识别的原因。登录失败时响应仍为200
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginFailure {
+    /// 用户名或密码错误，包含页面中的提示
+    WrongCredentials(String),
+    /// 需要在浏览器中完成滑块验证码
+    CaptchaRequired,
+    /// 登录过于频繁，包含页面中的提示
+    RateLimited(String),
+}
+
+impl LoginFailure {
+    /// 从登录响应的html或json中识别失败的原因，无法识别时返回None
+    pub fn parse(body: &str) -> Option<Self> {
+        if let Ok(value) = serde_json::from_str::<Value>(body) {
+            let code = ["code", "error_code", "errorCode"]
+                .iter()
+                .find_map(|k| value[*k].as_i64());
+            let msg = ["msg", "message", "error"]
+                .iter()
+                .find_map(|k| value[*k].as_str())
+                .unwrap_or_default();
+            return Self::classify(code, msg);
+        }
+        let html = Html::parse_document(body);
+        let select = |selector: &str| {
+            Selector::parse(selector)
+                .map_err(|e| warn!("invalid selector {}: {:?}", selector, e))
+                .ok()
+        };
+        if select(LOGIN_CAPTCHA_SELECTOR).is_some_and(|s| html.select(&s).next().is_some()) {
+            return Some(Self::CaptchaRequired);
+        }
+        let code = select(LOGIN_ERROR_CODE_SELECTOR).and_then(|s| {
+            html.select(&s)
+                .find_map(|e| e.value().attr("value")?.trim().parse::<i64>().ok())
+        });
+        let msg = select(LOGIN_ERROR_MSG_SELECTOR)
+            .and_then(|s| {
+                html.select(&s)
+                    .map(|e| e.text().collect::<String>().trim().to_string())
+                    .find(|t| !t.is_empty())
+            })
+            .unwrap_or_default();
+        Self::classify(code, &msg)
+    }
+
+    fn classify(code: Option<i64>, msg: &str) -> Option<Self> {
+        let msg = msg.trim().to_string();
+        let has = |keywords: &[&str]| keywords.iter().any(|k| msg.contains(k));
+        match code {
+            Some(code) if WRONG_CREDENTIALS_CODES.contains(&code) => {
+                Some(Self::WrongCredentials(msg))
+            }
+            Some(code) if LOGIN_RATE_LIMITED_CODES.contains(&code) => Some(Self::RateLimited(msg)),
+            _ if has(WRONG_CREDENTIALS_KEYWORDS) => Some(Self::WrongCredentials(msg)),
+            _ if has(LOGIN_RATE_LIMITED_KEYWORDS) => Some(Self::RateLimited(msg)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LoginFailure {
+    /// 包含[`DictError`](crate::error::DictError)识别的标记
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (marker, msg) = match self {
+            Self::WrongCredentials(msg) => (WRONG_CREDENTIALS, msg.as_str()),
+            Self::CaptchaRequired => (LOGIN_CAPTCHA_REQUIRED, ""),
+            Self::RateLimited(msg) => (LOGIN_RATE_LIMITED, msg.as_str()),
+        };
+        write!(f, "{}: {}", LOGIN_FAILED, marker)?;
+        if !msg.is_empty() {
+            write!(f, ". {}", msg)?;
+        }
+        Ok(())
+    }
+}
+
+impl LoginFailure {
+    /// 对应的[`DictError`]，用于输出提示
+    pub fn kind(&self) -> DictError {
+        match self {
+            Self::WrongCredentials(_) => DictError::WrongCredentials,
+            Self::CaptchaRequired => DictError::LoginCaptcha,
+            Self::RateLimited(_) => DictError::LoginRateLimited,
+        }
+    }
+}
+
+/// [`YoudaoClient::login`]的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginError {
+    /// youdao拒绝了登录，响应中可以识别原因
+    Rejected(LoginFailure),
+    /// 在`login_interval_secs`内已经尝试过登录，没有发送请求。failures为之前连续失败的次数
+    TooSoon {
+        wait_secs: u64,
+        failures: u32,
+    },
+    Other(String),
+}
+
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected(failure) => write!(f, "{}", failure),
+            Self::TooSoon {
+                wait_secs,
+                failures,
+            } => {
+                write!(f, "refused to login youdao again within {}s", wait_secs)?;
+                // 上次登录成功时仍在间隔内，没有失败的次数
+                if *failures > 0 {
+                    write!(f, " after {} failed attempts", failures)?;
+                }
+                write!(f, ". reuse the saved cookies or use --force-login")
+            }
+            Self::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoginError {}
+
+impl From<String> for LoginError {
+    fn from(e: String) -> Self {
+        Self::Other(e)
+    }
+}
+
+impl From<ReadBodyError> for LoginError {
+    fn from(e: ReadBodyError) -> Self {
+        Self::Other(e.to_string())
+    }
+}
+
+impl From<LoginError> for String {
+    fn from(e: LoginError) -> Self {
+        e.to_string()
+    }
+}
+
+/// add-word与delete-word请求的参数
+fn word_query(word: &str) -> Vec<(&'static str, String)> {
+    vec![("word", word.to_string()), ("lan", "en".to_string())]
+}
+
+/// update-word-book请求的参数
+fn book_query(item_id: &str, book_id: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("itemId", item_id.to_string()),
+        ("bookId", book_id.to_string()),
+    ]
+}
+
+/// youdao中的单词本
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Book {
+    #[serde(rename = "bookId")]
+    pub book_id: String,
+    #[serde(rename = "bookName")]
+    pub book_name: String,
+    /// 单词本中的单词数量
+    #[serde(
+        rename = "wordNum",
+        default,
+        deserialize_with = "lenient::number_or_string"
+    )]
+    pub word_count: usize,
+}
+
+/// 批量修改单词本的结果
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct BatchReport {
+    pub succeeded: Vec<String>,
+    /// 重试后仍失败的item与最后一次的错误
+    pub failed: Vec<(String, String)>,
+}
+
+/// 单词本修改请求失败的原因，仅[`ModifyError::Transient`]会被重试
+#[derive(Debug)]
+enum ModifyError {
+    /// 网络错误或http 5xx与429
+    Transient(String),
+    Failed(String),
+}
+
+impl From<String> for ModifyError {
+    fn from(e: String) -> Self {
+        Self::Failed(e)
+    }
+}
+
+impl From<ReadBodyError> for ModifyError {
+    fn from(e: ReadBodyError) -> Self {
+        match e {
+            ReadBodyError::TooLarge { .. } => Self::Failed(e.to_string()),
+            // 读取body时连接中断
+            ReadBodyError::Other(e) => Self::Transient(e),
+        }
+    }
+}
+
+impl From<ModifyError> for String {
+    fn from(e: ModifyError) -> Self {
+        match e {
+            ModifyError::Transient(e) | ModifyError::Failed(e) => e,
+        }
+    }
+}
+
+/// 音标，accent为uk或us，没有区分时为空
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Phonetic {
+    pub accent: String,
+    pub text: String,
+}
+
+/// 网络短语
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WebPhrase {
+    pub phrase: String,
+    pub translations: Vec<String>,
+}
+
+/// 单词查询结果。短语通常没有音标
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub word: String,
+    pub phonetics: Vec<Phonetic>,
+    pub translations: Vec<String>,
+    pub web_phrases: Vec<WebPhrase>,
+}
+
+impl Definition {
+    /// 从jsonapi的响应中解析，忽略缺失的部分。没有任何释义时返回None
+    pub fn from_json(query: &str, value: &Value) -> Option<Self> {
+        let ec = &value["ec"]["word"][0];
+        let word = ec["return-phrase"]["l"]["i"]
+            .as_str()
+            .unwrap_or(query)
+            .to_string();
+        let phonetics = [("uk", "ukphone"), ("us", "usphone"), ("", "phone")]
+            .iter()
+            .filter_map(|(accent, key)| {
+                ec[*key]
+                    .as_str()
+                    .filter(|s| !s.is_empty())
+                    .map(|text| Phonetic {
+                        accent: accent.to_string(),
+                        text: text.to_string(),
+                    })
+            })
+            .collect::<Vec<_>>();
+        let mut translations = ec["trs"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|trs| trs["tr"].as_array().into_iter().flatten())
+            .flat_map(|tr| json_strings(&tr["l"]["i"]))
+            .collect::<Vec<_>>();
+        let mut web_phrases = vec![];
+        for item in value["web_trans"]["web-translation"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let phrase = match item["key"].as_str() {
+                Some(phrase) => phrase,
+                None => continue,
+            };
+            let trans = item["trans"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|t| t["value"].as_str().map(str::to_string))
+                .collect::<Vec<_>>();
+            if phrase.eq_ignore_ascii_case(&word) {
+                // 没有ec释义时使用网络释义
+                if translations.is_empty() {
+                    translations = trans;
+                }
+            } else {
+                web_phrases.push(WebPhrase {
+                    phrase: phrase.to_string(),
+                    translations: trans,
+                });
+            }
+        }
+        if translations.is_empty() && web_phrases.is_empty() {
+            return None;
+        }
+        Some(Self {
+            word,
+            phonetics,
+            translations,
+            web_phrases,
+        })
+    }
+}
+
+/// 字符串或字符串数组
+fn json_strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+impl WordLookup for YoudaoClient {
+    fn lookup<'a>(&'a self, word: &'a str) -> BoxFuture<'a, Result<Definition, String>> {
+        Box::pin(YoudaoClient::lookup(self, word))
+    }
+}
+
+impl fmt::Display for Definition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.word)?;
+        if !self.phonetics.is_empty() {
+            let phonetics = self
+                .phonetics
+                .iter()
+                .map(|p| format!("{} [{}]", p.accent, p.text).trim().to_string())
+                .collect::<Vec<_>>();
+            writeln!(f, "  {}", phonetics.join("  "))?;
+        }
+        for trans in &self.translations {
+            writeln!(f, "  {}", trans)?;
+        }
+        if !self.web_phrases.is_empty() {
+            writeln!(f, "web phrases:")?;
+            for phrase in &self.web_phrases {
+                writeln!(f, "  {}: {}", phrase.phrase, phrase.translations.join("；"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 未配置lookup与单词本修改等请求时使用默认的api，配置文件中的请求优先
+pub fn with_default_requests(config: AppConfig) -> AppConfig {
+    let headers = [("accept", "application/json, text/plain, */*")]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<_, _>>();
+    config
+        .with_default_request(
+            "lookup",
+            RequestConfig::new(DEFAULT_LOOKUP_URL, "GET", headers.clone()),
+        )
+        .with_default_request(
+            "add-word",
+            RequestConfig::new(DEFAULT_ADD_WORD_URL, "GET", headers.clone()),
+        )
+        .with_default_request(
+            "delete-word",
+            RequestConfig::new(DEFAULT_DELETE_WORD_URL, "GET", headers.clone()),
+        )
+        .with_default_request(
+            "get-books",
+            RequestConfig::new(DEFAULT_GET_BOOKS_URL, "GET", headers.clone()),
+        )
+        .with_default_request(
+            "update-word-book",
+            RequestConfig::new(DEFAULT_UPDATE_WORD_BOOK_URL, "GET", headers),
+        )
+}
+
+/// 登录请求的form，字段与网页登录时相同
+#[derive(Debug, Serialize)]
+pub struct LoginForm<'a> {
+    username: &'a str,
+    password: &'a str,
+    /// 保存cookie
+    savelogin: u8,
+    /// 由savelogin决定
+    cf: u8,
+    app: &'static str,
+    tp: &'static str,
+    fr: &'static str,
+    ru: &'static str,
+    product: &'static str,
+    #[serde(rename = "type")]
+    type_: &'static str,
+    um: &'static str,
+    /// 同意登录
+    #[serde(rename = "agreePrRule")]
+    agree_pr_rule: &'static str,
+}
+
+impl<'a> LoginForm<'a> {
+    pub fn new(username: &'a str, password: &'a str) -> Self {
+        let savelogin = true;
+        Self {
+            username,
+            password,
+            savelogin: savelogin as u8,
+            cf: if savelogin { 7 } else { 3 },
+            app: "web",
+            tp: "urstoken",
+            fr: "1",
+            ru: "http://dict.youdao.com/wordbook/wordlist?keyfrom=dict2.index#/",
+            product: "DICT",
+            type_: "1",
+            um: "true",
+            agree_pr_rule: "1",
+        }
+    }
 }
 
 pub struct YoudaoClient {
-    client: Client,
+    context: Arc<HttpContext>,
     config: AppConfig,
     cookie_store: CookieStore,
+    /// cookie store被修改且未保存
+    cookies_dirty: bool,
+    /// 未配置cookie_path时仅在内存中记录
+    login_state: LoginState,
 }
 
 impl std::ops::Drop for YoudaoClient {
-    /// 在退出时保存cookie store
+    /// 如果cookie store在修改后未通过[`YoudaoClient::save`]保存，则在drop时保存
     fn drop(&mut self) {
-        if let Some(path) = self.config.get_cookie_path() {
-            if let Err(e) = save_cookie_store(path, &self.cookie_store) {
+        if self.cookies_dirty {
+            warn!("youdao cookies were not saved explicitly, saving late in drop");
+            if let Err(e) = self.save_cookies() {
                 error!("save cookie store failed: {}", e);
             }
         }
@@ -56,42 +629,100 @@ impl YoudaoClient {
     ///
     /// 如果Client无法创建
     pub fn new(config: AppConfig) -> Result<Self, String> {
-        let cookie_store = build_cookie_store(config.get_cookie_path())?;
+        Self::with_context(config, Arc::new(HttpContext::new()?))
+    }
+
+    /// 使用共享的http context创建client。未配置lookup与单词本修改等请求时使用默认的api
+    pub fn with_context(config: AppConfig, context: Arc<HttpContext>) -> Result<Self, String> {
+        let passphrase = config.get_cookie_passphrase()?;
+        let cookie_store = build_cookie_store(config.get_cookie_path(), passphrase.as_deref())?;
+        warn_expiring_cookies("youdao", &login_cookie_status(&cookie_store, LOGIN_COOKIES));
+        let config = with_default_requests(config);
+        let login_state = config
+            .get_cookie_path()
+            .map(|path| LoginState::load(&LoginState::path(path)))
+            .unwrap_or_default();
         Ok(Self {
-            client: build_general_client()?,
+            context,
             config,
             cookie_store,
+            cookies_dirty: false,
+            login_state,
         })
     }
 
+    /// 保存cookie store到cookie_path。未配置cookie_path时不做任何操作
+    pub async fn save(&mut self) -> Result<(), String> {
+        self.save_cookies()
+    }
+
+    fn save_cookies(&mut self) -> Result<(), String> {
+        if let Some(path) = self.config.get_cookie_path() {
+            let passphrase = self.config.get_cookie_passphrase()?;
+            save_cookie_store(path, &self.cookie_store, passphrase.as_deref())?;
+        }
+        self.cookies_dirty = false;
+        Ok(())
+    }
+
+    /// 未登录时通过[`YoudaoClient::login`]登录
+    pub async fn login_if_needed(&mut self) -> Result<(), String> {
+        if !self.has_logged() {
+            debug!("Signing in");
+            self.login()
+                .await
+                .map_err(|e| format!("youdao login error: {}", e))?;
+        }
+        Ok(())
+    }
+
     /// 使用username, password登录youdao. password必须是通过youdao网页端加密过的(hex_md5)，不能是明文密码
-    pub async fn login(&mut self) -> Result<(), String> {
+    ///
+    /// 在`login_interval_secs`内已经尝试过登录时直接返回error，避免被加入黑名单，见[`LoginState`]。
+    /// 配置了force_login时忽略
+    pub async fn login(&mut self) -> Result<(), LoginError> {
+        self.context.check_online("login")?;
+        let now = Utc::now().timestamp();
+        let interval = self
+            .config
+            .get_login_interval_secs()
+            .unwrap_or(DEFAULT_LOGIN_INTERVAL_SECS);
+        if let Some(wait) = self.login_state.wait_secs(now, interval) {
+            if !self.config.is_force_login() {
+                return Err(LoginError::TooSoon {
+                    wait_secs: wait,
+                    failures: self.login_state.failures,
+                });
+            }
+            warn!("forced youdao login within {}s of the last attempt", wait);
+        }
+        self.login_state.attempt(now);
+        self.save_login_state();
+        let result = self.try_login().await;
+        self.login_state.finish(result.is_ok());
+        self.save_login_state();
+        if let (Ok(_), Some(path)) = (&result, self.config.get_cookie_path()) {
+            CookieOrigin::login(now).save_or_warn(path);
+        }
+        result
+    }
+
+    /// 保存失败时仅记录日志，不影响登录
+    fn save_login_state(&self) {
+        if let Some(path) = self.config.get_cookie_path() {
+            if let Err(e) = self.login_state.save(&LoginState::path(path)) {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    async fn try_login(&mut self) -> Result<(), LoginError> {
         self.prapre_login().await?;
         let req_name = "login";
-        let savelogin = true;
-        let form = [
-            ("username", self.config.get_username()),
-            ("password", self.config.get_password()),
-            // 保存cookie
-            ("savelogin", &(savelogin as i8).to_string()),
-            // 由savelogin决定
-            ("cf", &if savelogin { 7 } else { 3 }.to_string()),
-            ("app", "web"),
-            ("tp", "urstoken"),
-            ("fr", "1"),
-            (
-                "ru",
-                "http://dict.youdao.com/wordbook/wordlist?keyfrom=dict2.index#/",
-            ),
-            ("product", "DICT"),
-            ("type", "1"),
-            ("um", "true"),
-            // 同意登录
-            ("agreePrRule", "1"),
-        ];
+        let form = LoginForm::new(self.config.get_username(), self.config.get_password());
         let resp = send_request(
             &self.config,
-            &self.client,
+            &self.context,
             &self.cookie_store,
             req_name,
             |url| url.to_string(),
@@ -99,49 +730,63 @@ impl YoudaoClient {
         )
         .await?;
         update_set_cookies(&mut self.cookie_store, &resp);
+        self.cookies_dirty = true;
         // 多次登录后可能引起无法登录的问题
-        if resp
+        let has_set_cookie = resp
             .headers()
             .iter()
-            .find(|(k, _)| k.as_str().eq_ignore_ascii_case("set-cookie"))
-            .is_none()
-        {
-            let error = format!("not found set-cookie in login resp: {:?}", resp);
-            let body = resp.text().await.map_err(|e| format!("{:?}", e))?;
-            error!("{}, body: {}", error, body);
-            Err("Frequent login may have been added to youdao blacklist, not found any set-cookie in login resp".to_string())
-        } else if !self.has_logged() {
-            let error = format!("Unable to find login related cookie. resp: {:?}", resp);
-            error!(
-                "{}, cookie store: {:?}, body: {:?}",
-                error,
-                self.cookie_store,
-                resp.text().await.map_err(|e| format!("{:?}", e))?
-            );
-            Err("login failed. not found login cookies".to_string())
+            .any(|(k, _)| k.as_str().eq_ignore_ascii_case("set-cookie"));
+        if has_set_cookie && self.has_logged() {
+            return Ok(());
+        }
+        let error = if has_set_cookie {
+            let cookie_names = self
+                .cookie_store
+                .iter_unexpired()
+                .map(|c| c.name().to_string())
+                .collect::<Vec<_>>();
+            format!(
+                "Unable to find login related cookie. cookies: {:?}, resp: {}",
+                cookie_names,
+                debug_response(&resp)
+            )
+        } else {
+            format!(
+                "not found set-cookie in login resp: {}",
+                debug_response(&resp)
+            )
+        };
+        let body = read_body(&self.config, req_name, resp).await?;
+        let body = String::from_utf8_lossy(&body);
+        error!("{}", error);
+        trace!("login resp body: {}", redact::redact_body(&body));
+        if let Some(failure) = LoginFailure::parse(&body) {
+            return Err(LoginError::Rejected(failure));
+        }
+        if has_set_cookie {
+            Err(format!("{}. not found login cookies", LOGIN_FAILED).into())
         } else {
-            Ok(())
+            Err("Frequent login may have been added to youdao blacklist, not found any set-cookie in login resp".to_string().into())
         }
     }
 
     /// 获取单词数量
     pub async fn get_words_total(&self) -> Result<usize, String> {
         if !self.has_logged() {
-            return Err("not logged in".to_string());
+            return Err(NOT_LOGGED_IN.to_string());
         }
         let req_name = "get-words";
         let (limit, offset) = (1, 0);
         let resp = send_request_nobody(
             &self.config,
-            &self.client,
+            &self.context,
             &self.cookie_store,
             req_name,
             |url| format!("{}?limit={}&offset={}", url, limit, offset),
         )
         .await?;
-        let result = resp
-            .json::<ResponseResult<Page<WordItem>>>()
-            .await
+        let body = read_body(&self.config, req_name, resp).await?;
+        let result = serde_json::from_slice::<ResponseResult<Page<WordItem>>>(&body)
             .map_err(|e| format!("{:?}", e))?;
         Ok(result.data.total)
     }
@@ -152,56 +797,296 @@ impl YoudaoClient {
     ///
     /// 如果用户未登录
     pub async fn get_words(&mut self) -> Result<Vec<WordItem>, String> {
+        self.get_words_partial().await.map_err(|e| e.error)
+    }
+
+    /// 与[`YoudaoClient::get_words`]相同，但保留失败前已获取的单词。
+    /// 通过[`HttpContext::with_cancel`]取消时在下一页的请求前失败
+    ///
+    /// # Errors
+    ///
+    /// 失败或取消时返回已获取的页中的单词
+    pub async fn get_words_partial(&mut self) -> Result<Vec<WordItem>, PartialWordsError> {
+        let partial = |fetched, total, error| PartialWordsError {
+            fetched,
+            total,
+            error,
+        };
         if !self.has_logged() {
-            return Err("not logged in".to_string());
+            return Err(partial(vec![], 0, NOT_LOGGED_IN.to_string()));
         }
         debug!("getting words total");
-        let total = self.get_words_total().await?;
+        let total = self
+            .get_words_total()
+            .await
+            .map_err(|e| partial(vec![], 0, e))?;
         debug!("got words total: {}", total);
         let mut words = vec![];
-        let req_name = "get-words";
+        let mut skipped = 0;
         let limit = 1000;
         let numbers = (total as f64 / limit as f64).ceil() as usize;
         for number in 0..numbers {
             let offset = limit * number;
-            // let querys = ;
-            debug!("Getting words with limit: {}, offset: {}", limit, offset);
-            let resp = send_request_nobody(
-                &self.config,
-                &self.client,
-                &self.cookie_store,
-                req_name,
-                |url| format!("{}?limit={}&offset={}", url, limit, offset),
-            )
-            .await?;
-            let result = resp
-                .json::<ResponseResult<Page<WordItem>>>()
-                .await
-                .map_err(|e| format!("{:?}", e))?;
-            let items = result.data.item_list;
+            let result = match self.get_words_page(limit, offset).await {
+                Ok(result) => result,
+                Err(e) => return Err(partial(words, total, e)),
+            };
+            let (items, page_skipped) =
+                lenient::from_values::<WordItem>(result.data.item_list, "youdao words");
             debug!(
                 "got youdao page words. code: {}, msg: {}, item size: {}",
                 result.code,
                 result.msg,
                 items.len()
             );
+            skipped += page_skipped;
+            self.context.emit(Event::PageFetched {
+                offset,
+                count: items.len(),
+            });
             items.into_iter().for_each(|item| words.push(item));
         }
-        debug!("got all words size: {}", words.len());
-        if words.len() == total {
+        debug!("got all words size: {}, skipped: {}", words.len(), skipped);
+        // 跳过的单词也计入total
+        if words.len() + skipped == total {
             Ok(words)
         } else {
-            Err(format!("The number of words obtained is not the same as the total number! len: {}, total: {}", words.len(), total))
+            let e = format!("The number of words obtained is not the same as the total number! len: {}, total: {}", words.len(), total);
+            Err(partial(words, total, e))
+        }
+    }
+
+    /// 获取从offset开始的limit个单词
+    async fn get_words_page(
+        &self,
+        limit: usize,
+        offset: usize,
+    ) -> Result<ResponseResult<Page<Value>>, String> {
+        let req_name = "get-words";
+        debug!("Getting words with limit: {}, offset: {}", limit, offset);
+        let resp = send_request_nobody(
+            &self.config,
+            &self.context,
+            &self.cookie_store,
+            req_name,
+            |url| format!("{}?limit={}&offset={}", url, limit, offset),
+        )
+        .await?;
+        let body = read_body(&self.config, req_name, resp).await?;
+        serde_json::from_slice::<ResponseResult<Page<Value>>>(&body).map_err(|e| format!("{:?}", e))
+    }
+
+    /// 查询单词的音标与释义，不需要登录
+    pub async fn lookup(&self, word: &str) -> Result<Definition, String> {
+        let query = serde_urlencoded::to_string([("q", word)]).map_err(|e| format!("{:?}", e))?;
+        let resp = send_request_nobody(
+            &self.config,
+            &self.context,
+            &self.cookie_store,
+            "lookup",
+            |url| format!("{}?{}", url, query),
+        )
+        .await?;
+        let body = read_body(&self.config, "lookup", resp).await?;
+        let value = serde_json::from_slice::<Value>(&body)
+            .map_err(|e| format!("parse lookup response error: {:?}", e))?;
+        Definition::from_json(word, &value)
+            .ok_or_else(|| format!("not found definition of {}", word))
+    }
+
+    /// 添加单词到单词本
+    pub async fn add_word(&self, word: &str) -> Result<(), String> {
+        self.send_modify("add-word", word, &word_query(word))
+            .await?;
+        debug!("added word {} to youdao wordbook", word);
+        Ok(())
+    }
+
+    /// 从单词本中删除单词
+    pub async fn delete_word(&self, word: &str) -> Result<(), String> {
+        self.send_modify("delete-word", word, &word_query(word))
+            .await?;
+        debug!("deleted word {} from youdao wordbook", word);
+        Ok(())
+    }
+
+    /// 将单词item_id移动到单词本book_id中
+    pub async fn update_word_book(&self, item_id: &str, book_id: &str) -> Result<(), String> {
+        self.send_modify("update-word-book", item_id, &book_query(item_id, book_id))
+            .await?;
+        debug!("moved word item {} to book {}", item_id, book_id);
+        Ok(())
+    }
+
+    /// 获取youdao中所有的单词本
+    pub async fn get_books(&self) -> Result<Vec<Book>, String> {
+        if !self.has_logged() {
+            return Err(NOT_LOGGED_IN.to_string());
+        }
+        let req_name = "get-books";
+        let resp = send_request_nobody(
+            &self.config,
+            &self.context,
+            &self.cookie_store,
+            req_name,
+            |url| url.to_string(),
+        )
+        .await?;
+        let body = read_body(&self.config, req_name, resp).await?;
+        let result = serde_json::from_slice::<ResponseResult<Vec<Book>>>(&body)
+            .map_err(|e| format!("parse {} response error: {:?}", req_name, e))?;
+        if result.code != 0 {
+            return Err(format!("get books failed: {}", result.msg));
         }
+        Ok(result.data)
+    }
+
+    /// 逐个添加words到单词本，见[`YoudaoClient::run_batch`]
+    pub async fn add_words(
+        &self,
+        words: &[String],
+        on_progress: impl FnMut(usize, usize),
+    ) -> Result<BatchReport, String> {
+        self.run_batch("add-word", words, word_query, on_progress)
+            .await
+    }
+
+    /// 逐个从单词本删除words，见[`YoudaoClient::run_batch`]
+    pub async fn delete_words(
+        &self,
+        words: &[String],
+        on_progress: impl FnMut(usize, usize),
+    ) -> Result<BatchReport, String> {
+        self.run_batch("delete-word", words, word_query, on_progress)
+            .await
+    }
+
+    /// 逐个将item_ids移动到单词本book_id中，报告中为item_id。见[`YoudaoClient::run_batch`]
+    pub async fn move_words(
+        &self,
+        item_ids: &[String],
+        book_id: &str,
+        on_progress: impl FnMut(usize, usize),
+    ) -> Result<BatchReport, String> {
+        let query = |item_id: &str| book_query(item_id, book_id);
+        self.run_batch("update-word-book", item_ids, query, on_progress)
+            .await
+    }
+
+    /// 按顺序对每个item发送req_name请求，参数由query生成。两次请求间等待`batch_delay_millis`，
+    /// 网络错误或被限流等暂时的失败时最多重试`batch_retries`次。每个item完成后调用`on_progress(完成数量, 总数)`
+    ///
+    /// # Errors
+    ///
+    /// 如果未登录。单个item的失败记录在[`BatchReport::failed`]中
+    async fn run_batch(
+        &self,
+        req_name: &str,
+        items: &[String],
+        query: impl Fn(&str) -> Vec<(&'static str, String)>,
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> Result<BatchReport, String> {
+        self.context.check_writable(req_name)?;
+        if !self.has_logged() {
+            return Err(NOT_LOGGED_IN.to_string());
+        }
+        let delay = std::time::Duration::from_millis(
+            self.config
+                .get_batch_delay_millis()
+                .unwrap_or(DEFAULT_BATCH_DELAY_MILLIS),
+        );
+        let retries = self
+            .config
+            .get_batch_retries()
+            .unwrap_or(DEFAULT_BATCH_RETRIES);
+        let mut report = BatchReport::default();
+        let mut sent = false;
+        for (i, item) in items.iter().enumerate() {
+            let query = query(item);
+            let mut attempt = 0;
+            let result = loop {
+                if sent {
+                    tokio::time::sleep(delay).await;
+                }
+                sent = true;
+                match self.send_modify(req_name, item, &query).await {
+                    Err(ModifyError::Transient(e)) if attempt < retries => {
+                        attempt += 1;
+                        warn!("{}, retrying {}/{}", e, attempt, retries);
+                    }
+                    r => break r.map_err(String::from),
+                }
+            };
+            match result {
+                Ok(()) => report.succeeded.push(item.clone()),
+                Err(e) => report.failed.push((item.clone(), e)),
+            }
+            on_progress(i + 1, items.len());
+        }
+        info!(
+            "{} {} items, {} failed",
+            req_name,
+            report.succeeded.len(),
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    /// 发送单词本修改请求如add-word，响应的code不为0时失败。item用于错误信息
+    async fn send_modify(
+        &self,
+        req_name: &str,
+        item: &str,
+        query: &[(&str, String)],
+    ) -> Result<(), ModifyError> {
+        self.context.check_writable(req_name)?;
+        if !self.has_logged() {
+            return Err(NOT_LOGGED_IN.to_string().into());
+        }
+        #[derive(Deserialize, Debug)]
+        struct ModifyResult {
+            code: i32,
+            #[serde(default)]
+            msg: String,
+        }
+        let query = serde_urlencoded::to_string(query).map_err(|e| format!("{:?}", e))?;
+        let resp = send_request_unchecked(
+            &self.config,
+            &self.context,
+            &self.cookie_store,
+            req_name,
+            |url| format!("{}?{}", url, query),
+            None::<&str>,
+        )
+        .await
+        .map_err(|e| match DictError::classify(&e) {
+            Some(DictError::Network) => ModifyError::Transient(e),
+            _ => ModifyError::Failed(e),
+        })?;
+        let status = resp.status();
+        let resp = check_status(&self.config, req_name, resp)
+            .await
+            .map_err(|e| {
+                if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+                    ModifyError::Transient(e)
+                } else {
+                    ModifyError::Failed(e)
+                }
+            })?;
+        let body = read_body(&self.config, req_name, resp).await?;
+        let result = serde_json::from_slice::<ModifyResult>(&body)
+            .map_err(|e| format!("parse {} response error: {:?}", req_name, e))?;
+        if result.code != 0 {
+            return Err(format!("{} {} failed: {}", req_name, item, result.msg).into());
+        }
+        Ok(())
     }
 
     /// 从cookie_store中查询是否存在登录的cookie
     pub fn has_logged(&self) -> bool {
-        let domain = "youdao.com";
-        self.cookie_store
-            .get(domain, "/", "OUTFOX_SEARCH_USER_ID")
-            .is_some()
-            && self.cookie_store.get(domain, "/", "DICT_PERS").is_some()
+        LOGIN_COOKIES
+            .iter()
+            .all(|(domain, name)| self.cookie_store.get(domain, "/", name).is_some())
     }
 
     /// 获取youdao set-cookie: outfox_search_user_id，保证后续登录有效
@@ -210,51 +1095,437 @@ impl YoudaoClient {
         debug!("sending request with req name: {}", req_name);
         let resp = send_request_nobody(
             &self.config,
-            &self.client,
+            &self.context,
             &self.cookie_store,
             req_name,
             |url| url.to_string(),
         )
         .await?;
         update_set_cookies(&mut self.cookie_store, &resp);
+        self.cookies_dirty = true;
         Ok(())
     }
 }
 
+impl WordSource for YoudaoClient {
+    fn fetch_words(&mut self) -> BoxFuture<'_, Result<Vec<WordItem>, String>> {
+        Box::pin(async move {
+            self.login_if_needed().await?;
+            self.get_words().await
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    const CONFIG_PATH: &'static str = "config.yml";
+    use mock_server::MockServer;
+
+    /// 通过录制的响应创建client，cookie等文件保存在dir中
+    fn recorded_client(
+        server: &MockServer,
+        dir: &tempfile::TempDir,
+    ) -> Result<YoudaoClient, String> {
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let context = HttpContext::from_config(&config.http())?;
+        YoudaoClient::with_context(config.youdao(), Arc::new(context))
+    }
 
     #[tokio::test]
     async fn login_test() -> Result<(), String> {
-        // init_log();
-        let config = Config::from_yaml_file(CONFIG_PATH).map_err(|e| format!("{:?}", e))?;
-        let mut client = YoudaoClient::new(config.youdao.unwrap())?;
-        if !client.has_logged() {
-            client.login().await?;
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = recorded_client(&server, &dir)?;
+        assert!(!client.has_logged());
+        client.login().await?;
+        assert!(client.has_logged());
+        client.save().await?;
+        let cookie_path = dir.path().join("youdao-cookies.json");
+        let origin = CookieOrigin::load(cookie_path.to_str().unwrap()).unwrap();
+        assert_eq!(origin.source, cookie_origin::CookieSource::Login);
+
+        // 保存的cookies可以在下次使用
+        let client = recorded_client(&server, &dir)?;
+        assert!(client.has_logged());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn login_interval() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let state_path = LoginState::path(dir.path().join("youdao-cookies.json").to_str().unwrap());
+        let logins = || {
+            server
+                .requests()
+                .iter()
+                .filter(|r| r.path.contains("/login/acc/login"))
+                .count()
+        };
+        recorded_client(&server, &dir)?.login().await?;
+        let state = LoginState::load(&state_path);
+        assert!(state.last_attempt.is_some());
+        assert_eq!(state.failures, 0);
+
+        // 在window内的其它运行中拒绝再次登录
+        let e = recorded_client(&server, &dir)?.login().await.unwrap_err();
+        assert!(
+            matches!(e, LoginError::TooSoon { failures: 0, .. }),
+            "{}",
+            e
+        );
+        let e = e.to_string();
+        assert!(e.contains("--force-login"), "{}", e);
+        assert!(!e.contains("failed attempts"), "{}", e);
+        assert_eq!(logins(), 1);
+
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let context = Arc::new(HttpContext::from_config(&config.http())?);
+        let config = config.youdao().with_force_login(true);
+        YoudaoClient::with_context(config, context)?.login().await?;
+        assert_eq!(logins(), 2);
+
+        // 超过window后可以登录。连续失败后window增长
+        let now = Utc::now().timestamp();
+        let failed = |failures, ago: i64| LoginState {
+            last_attempt: Some(now - ago),
+            failures,
+        };
+        failed(0, 601).save(&state_path)?;
+        recorded_client(&server, &dir)?.login().await?;
+        failed(2, 601).save(&state_path)?;
+        let e = recorded_client(&server, &dir)?.login().await.unwrap_err();
+        assert!(e.to_string().contains("after 2 failed attempts"), "{}", e);
+        assert_eq!(logins(), 3);
+        failed(2, 2401).save(&state_path)?;
+        recorded_client(&server, &dir)?.login().await?;
+        assert_eq!(LoginState::load(&state_path).failures, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_login_failures() {
+        assert_eq!(
+            LoginFailure::parse(include_str!(
+                "../../tests/fixtures/youdao-login-wrong-password.html"
+            )),
+            Some(LoginFailure::WrongCredentials(
+                "用户名或密码错误，请重新输入".to_string()
+            ))
+        );
+        assert_eq!(
+            LoginFailure::parse(include_str!(
+                "../../tests/fixtures/youdao-login-captcha.html"
+            )),
+            Some(LoginFailure::CaptchaRequired)
+        );
+        assert_eq!(
+            LoginFailure::parse(include_str!(
+                "../../tests/fixtures/youdao-login-rate-limited.html"
+            )),
+            Some(LoginFailure::RateLimited(
+                "您的登录操作过于频繁，请稍后再试".to_string()
+            ))
+        );
+        assert_eq!(
+            LoginFailure::parse(r#"{"code": 460, "msg": "wrong password"}"#),
+            Some(LoginFailure::WrongCredentials("wrong password".to_string()))
+        );
+        assert_eq!(LoginFailure::parse("<html></html>"), None);
+        assert_eq!(LoginFailure::parse("login failed"), None);
+    }
+
+    #[tokio::test]
+    async fn typed_login_failures() -> Result<(), String> {
+        use crate::error::DictError;
+        use mock_server::{recorded_response, MockResponse};
+        let pages = [
+            (
+                include_str!("../../tests/fixtures/youdao-login-wrong-password.html"),
+                DictError::WrongCredentials,
+            ),
+            (
+                include_str!("../../tests/fixtures/youdao-login-captcha.html"),
+                DictError::LoginCaptcha,
+            ),
+            (
+                include_str!("../../tests/fixtures/youdao-login-rate-limited.html"),
+                DictError::LoginRateLimited,
+            ),
+        ];
+        for (page, expected) in pages {
+            let server = MockServer::start(move |req| {
+                if req.path.ends_with("/login/acc/login") {
+                    MockResponse::text(200, page)
+                } else {
+                    recorded_response(req)
+                }
+            })
+            .await;
+            let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+            let e = recorded_client(&server, &dir)?.login().await.unwrap_err();
+            match &e {
+                LoginError::Rejected(failure) => assert_eq!(failure.kind(), expected),
+                e => panic!("unexpected login error: {}", e),
+            }
+            // 转为String后仍然可以识别
+            assert_eq!(DictError::classify(&e.to_string()), Some(expected), "{}", e);
         }
+        // 无法识别时仍为原来的错误
+        let server = MockServer::start(|req| {
+            if req.path.ends_with("/login/acc/login") {
+                MockResponse::text(200, "login failed")
+            } else {
+                recorded_response(req)
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let e = recorded_client(&server, &dir)?.login().await.unwrap_err();
+        assert!(
+            matches!(&e, LoginError::Other(e) if e.contains("blacklist")),
+            "{}",
+            e
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lookup_test() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let client = recorded_client(&server, &dir)?;
+
+        let def = client.lookup("serendipity").await?;
+        assert_eq!(def.word, "serendipity");
+        assert_eq!(def.phonetics.len(), 2);
+        assert_eq!(def.phonetics[1].accent, "us");
+        assert_eq!(
+            def.translations,
+            vec!["n. 意外发现珍奇事物的本领；有意外发现珍宝的运气"]
+        );
+        assert_eq!(def.web_phrases.len(), 2);
+        assert_eq!(
+            def.web_phrases[1].translations,
+            vec!["偶然性原理", "机缘原理"]
+        );
+        assert_eq!(
+            def.to_string(),
+            "serendipity
+  uk [ˌserənˈdɪpəti]  us [ˌserənˈdɪpəti]
+  n. 意外发现珍奇事物的本领；有意外发现珍宝的运气
+web phrases:
+  Serendipity Apartment: 意外公寓
+  serendipity principle: 偶然性原理；机缘原理
+"
+        );
+
+        // 短语没有音标
+        let def = client.lookup("look up").await?;
+        assert!(def.phonetics.is_empty());
+        assert_eq!(def.translations, vec!["查阅；向上看；好转", "拜访"]);
+        assert_eq!(def.web_phrases[0].phrase, "look up to");
+        assert!(server
+            .requests()
+            .last()
+            .unwrap()
+            .path
+            .ends_with("?q=look+up"));
+
+        let e = client.lookup("qwzxq").await.unwrap_err();
+        assert_eq!(e, "not found definition of qwzxq");
+
+        // 仅有网络释义
+        let value = serde_json::json!({"web_trans": {"web-translation": [
+            {"key": "Serendipity", "trans": [{"value": "机缘凑巧"}]}
+        ]}});
+        let def = Definition::from_json("serendipity", &value).unwrap();
+        assert_eq!(def.translations, vec!["机缘凑巧"]);
+        assert!(def.web_phrases.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_word_test() -> Result<(), String> {
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = recorded_client(&server, &dir)?;
+        assert_eq!(
+            client.add_word("serendipity").await.unwrap_err(),
+            "not logged in"
+        );
+        client.login().await?;
+        client.add_word("serendipity").await?;
+        assert!(server
+            .requests()
+            .last()
+            .unwrap()
+            .path
+            .ends_with("/add?word=serendipity&lan=en"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn batch_words() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        // 从第二个开始每三个修改请求中有一个被限流，"bad"与删除"walk"总是失败
+        let count = AtomicUsize::new(0);
+        let server = MockServer::start(move |req| {
+            if !req.path.contains("/wordbook/webapi/v2/ajax/") {
+                return recorded_response(req);
+            }
+            let n = count.fetch_add(1, Ordering::SeqCst);
+            if n % 3 == 1 {
+                return MockResponse::text(429, "busy");
+            }
+            if req.path.contains("word=bad&") || req.path.contains("delete?word=walk&") {
+                return MockResponse::json(200, &serde_json::json!({"code": 1, "msg": "invalid"}));
+            }
+            recorded_response(req)
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = server.write_fixture_config(dir.path());
+        let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let contents = contents.replacen(
+            "youdao:\n",
+            "youdao:\n    batch_delay_millis: 10\n    batch_retries: 1\n",
+            1,
+        );
+        std::fs::write(&path, contents).map_err(|e| e.to_string())?;
+        let mut config = Config::from_yaml_file(&path)?;
+        let context = HttpContext::from_config(&config.http())?;
+        let mut client = YoudaoClient::with_context(config.youdao(), Arc::new(context))?;
+        let words = ["run", "walk", "bad", "swim"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>();
+        assert!(client.add_words(&words, |_, _| {}).await.is_err());
+        client.login().await?;
+
+        let mut progress = vec![];
+        let report = client
+            .add_words(&words, |done, total| progress.push((done, total)))
+            .await?;
+        assert_eq!(report.succeeded, vec!["run", "walk", "swim"]);
+        assert_eq!(
+            report.failed,
+            vec![(
+                "bad".to_string(),
+                "add-word bad failed: invalid".to_string()
+            )]
+        );
+        assert_eq!(progress, vec![(1, 4), (2, 4), (3, 4), (4, 4)]);
+        let sent = server
+            .requests()
+            .into_iter()
+            .filter_map(|r| {
+                r.path
+                    .split_once("/ajax/add?word=")
+                    .map(|(_, q)| q.to_string())
+            })
+            .map(|q| q.split('&').next().unwrap().to_string())
+            .collect::<Vec<_>>();
+        // walk与swim被限流后重试成功，bad的失败不是暂时的，不重试
+        assert_eq!(sent, vec!["run", "walk", "walk", "bad", "swim", "swim"]);
+
+        let report = client.delete_words(&words[..2], |_, _| {}).await?;
+        assert_eq!(report.succeeded, vec!["run"]);
+        assert_eq!(
+            report.failed,
+            vec![(
+                "walk".to_string(),
+                "delete-word walk failed: invalid".to_string()
+            )]
+        );
+        assert!(server
+            .requests()
+            .iter()
+            .any(|r| r.path.ends_with("/ajax/delete?word=run&lan=en")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn books() -> Result<(), String> {
+        use mock_server::{recorded_response, MockResponse};
+        let server = MockServer::start(|req| {
+            if req.path.contains("/ajax/modify?itemId=bad&") {
+                return MockResponse::json(200, &serde_json::json!({"code": 500, "msg": "error"}));
+            }
+            recorded_response(req)
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = recorded_client(&server, &dir)?;
+        assert!(client.get_books().await.is_err());
+        client.login().await?;
+
+        let books = client.get_books().await?;
+        assert_eq!(
+            books
+                .iter()
+                .map(|b| b.book_name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["无标签", "GRE"]
+        );
+        assert_eq!(books[1].book_id, "1");
+        assert_eq!(books[0].word_count, 2);
+
+        client.update_word_book("a", "1").await?;
+        assert!(server
+            .requests()
+            .iter()
+            .any(|r| r.path.ends_with("/ajax/modify?itemId=a&bookId=1")));
+
+        let items = ["b", "bad", "c"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>();
+        let report = client.move_words(&items, "1", |_, _| {}).await?;
+        assert_eq!(report.succeeded, vec!["b", "c"]);
+        assert_eq!(report.failed[0].0, "bad");
         Ok(())
     }
 
     #[tokio::test]
     async fn get_words_test() -> Result<(), String> {
-        // init_log();
-        let config = Config::from_yaml_file(CONFIG_PATH).map_err(|e| format!("{:?}", e))?;
-        let mut client = YoudaoClient::new(config.youdao.unwrap())?;
-        if !client.has_logged() {
-            client.login().await?;
-        }
+        let server = MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut client = recorded_client(&server, &dir)?;
+        assert_eq!(client.get_words().await.unwrap_err(), "not logged in");
+        client.login().await?;
         let words = client.get_words().await?;
-        assert!(words.len() > 0);
+        assert_eq!(
+            words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>(),
+            vec!["accommodate", "benevolent", "R&D"]
+        );
+        assert_eq!(words[0].book_name, "GRE 3000");
+        let paths = server
+            .requests()
+            .into_iter()
+            .filter(|r| r.path.contains("/webapi/words"))
+            .map(|r| r.path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "http://dict.youdao.com/wordbook/webapi/words?limit=1&offset=0",
+                "http://dict.youdao.com/wordbook/webapi/words?limit=1000&offset=0",
+            ]
+        );
         Ok(())
     }
 
     #[allow(dead_code)]
     fn init_log() {
-        pretty_env_logger::formatted_builder()
-            // .format(|buf, record| writeln!(buf, "{}: {}", record.level(), record.args()))
-            .filter_module("youdao_dict_export::youdao_client", log::LevelFilter::Trace)
-            .init();
+        crate::logging::init(
+            2,
+            None,
+            None,
+            crate::color::ColorChoice::Auto,
+            &crate::config::LoggingConfig::default(),
+        )
+        .unwrap();
     }
 }
@@ -0,0 +1,93 @@
+//! 系统剪贴板。需要启用`clipboard` feature，否则所有操作都返回error
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// 读写剪贴板中的文本
+pub trait Clipboard {
+    fn get_text(&mut self) -> Result<String, String>;
+
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+}
+
+/// 系统剪贴板，每次操作时连接，没有图形界面时返回error
+#[derive(Debug, Default)]
+pub struct SystemClipboard;
+
+#[cfg(feature = "clipboard")]
+impl Clipboard for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        arboard::Clipboard::new()
+            .and_then(|mut c| c.get_text())
+            .map_err(|e| format!("read clipboard error: {}", e))
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        arboard::Clipboard::new()
+            .and_then(|mut c| c.set_text(text))
+            .map_err(|e| format!("write clipboard error: {}", e))
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+impl Clipboard for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        Err(unsupported())
+    }
+
+    fn set_text(&mut self, _text: &str) -> Result<(), String> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn unsupported() -> String {
+    "clipboard is not supported. please build with `--features clipboard`".to_string()
+}
+
+/// 写入inner的同时保存一份副本，用于将输出复制到剪贴板
+pub struct Tee<W> {
+    inner: W,
+    copy: Arc<Mutex<Vec<u8>>>,
+}
+
+impl<W: Write> Tee<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            copy: Arc::default(),
+        }
+    }
+
+    /// 已写入内容的副本，在Tee移动后仍然可以读取
+    pub fn copy(&self) -> Arc<Mutex<Vec<u8>>> {
+        self.copy.clone()
+    }
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.copy.lock().unwrap().extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tee_copies_output() {
+        let mut out = vec![];
+        let mut tee = Tee::new(&mut out);
+        let copy = tee.copy();
+        write!(tee, "run\nwalk\n").unwrap();
+        drop(tee);
+        assert_eq!(out, b"run\nwalk\n");
+        assert_eq!(*copy.lock().unwrap(), out);
+    }
+}
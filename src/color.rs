@@ -0,0 +1,90 @@
+//! 终端颜色输出设置
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+/// 禁用颜色的环境变量，见<https://no-color.org>
+pub const NO_COLOR_ENV: &str = "NO_COLOR";
+
+pub const RED: &str = "\x1b[31m";
+pub const GREEN: &str = "\x1b[32m";
+pub const YELLOW: &str = "\x1b[33m";
+pub const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// 以ansi颜色color输出s
+pub fn paint(color: &str, s: &str) -> String {
+    format!("{}{}{}", color, s, RESET)
+}
+
+/// 是否输出颜色。在main中解析一次后传递给logger与各个输出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// 未设置NO_COLOR且输出为终端时使用颜色
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unsupported color choice: {}", s)),
+        }
+    }
+}
+
+impl ColorChoice {
+    /// 命令行参数优先，auto时非空的NO_COLOR禁用颜色，否则仅在终端中使用颜色
+    pub fn resolve(self, no_color: Option<&str>, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => no_color.is_none_or(|s| s.is_empty()) && is_terminal,
+        }
+    }
+
+    /// stdout是否使用颜色
+    pub fn stdout(self) -> bool {
+        self.resolve(
+            std::env::var(NO_COLOR_ENV).ok().as_deref(),
+            io::stdout().is_terminal(),
+        )
+    }
+
+    /// stderr是否使用颜色
+    pub fn stderr(self) -> bool {
+        self.resolve(
+            std::env::var(NO_COLOR_ENV).ok().as_deref(),
+            io::stderr().is_terminal(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        // 参数优先于NO_COLOR与终端检测
+        assert!(ColorChoice::Always.resolve(Some("1"), false));
+        assert!(!ColorChoice::Never.resolve(None, true));
+        // NO_COLOR优先于终端检测
+        assert!(!ColorChoice::Auto.resolve(Some("1"), true));
+        // 空的NO_COLOR被忽略
+        assert!(ColorChoice::Auto.resolve(Some(""), true));
+        assert!(ColorChoice::Auto.resolve(None, true));
+        assert!(!ColorChoice::Auto.resolve(None, false));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("never".parse::<ColorChoice>(), Ok(ColorChoice::Never));
+        assert!("yes".parse::<ColorChoice>().is_err());
+    }
+}
@@ -1,26 +1,261 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::io;
+use std::path::Path;
 use tokio::fs as afs;
 
+/// 默认的配置文件名，位于$HOME中
+pub const CONFIG_FILENAME: &str = "dict-config.yml";
+
+/// 配置文件路径的环境变量，`--config-path`优先
+pub const CONFIG_PATH_ENV: &str = "DICT_CONFIG_PATH";
+
+/// 无法确定配置文件路径，tried为依次尝试过的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigPathError {
+    pub tried: Vec<String>,
+}
+
+impl fmt::Display for ConfigPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "not found config path. tried: {}. use --config-path or --no-config",
+            self.tried.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ConfigPathError {}
+
+/// 依次使用flag、[`CONFIG_PATH_ENV`]与`$HOME/`[`CONFIG_FILENAME`]作为配置文件路径。
+///
+/// 仅在前两者都不存在时才读取$HOME，env用于读取环境变量
+///
+/// # Errors
+///
+/// 如果都不存在，返回尝试过的位置
+pub fn resolve_config_path(
+    flag: Option<&str>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<String, ConfigPathError> {
+    if let Some(path) = flag {
+        return Ok(path.to_string());
+    }
+    let non_empty = |key: &str| env(key).filter(|v| !v.is_empty());
+    if let Some(path) = non_empty(CONFIG_PATH_ENV) {
+        return Ok(path);
+    }
+    match non_empty("HOME") {
+        Some(home) => Ok(join_path(Path::new(&home), CONFIG_FILENAME)),
+        None => Err(ConfigPathError {
+            tried: vec![
+                "--config-path".to_string(),
+                format!("${}", CONFIG_PATH_ENV),
+                format!("$HOME/{} ($HOME is not set)", CONFIG_FILENAME),
+            ],
+        }),
+    }
+}
+
 /// 一个对应.yml文件的配置struct
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Config {
+    /// 数据目录。配置后dictionary_path与cookie_path为相对路径时相对于该目录，
+    /// 该目录为相对路径时相对于配置文件所在目录。
+    /// 未配置时相对路径仍相对于当前目录
+    pub data_dir: Option<String>,
     pub maimemo: Option<AppConfig>,
     pub youdao: Option<AppConfig>,
+    pub eudic: Option<AppConfig>,
+    pub anki: Option<AnkiConfig>,
+    pub http: Option<HttpConfig>,
+    pub logging: Option<LoggingConfig>,
+    /// 所有app的默认存储配置，app中的store配置优先
+    pub store: Option<StoreConfig>,
+    /// 只读模式，禁止上传notepad、添加或删除单词等修改数据的操作，同`--read-only`
+    pub read_only: Option<bool>,
+    /// 通过http json接口获取单词的自定义来源，key为来源名称，用于`dict src <name>`
+    pub custom_sources: Option<BTreeMap<String, CustomSourceConfig>>,
 }
 
 impl Config {
     /// 从path yaml中加载配置。
     ///
+    /// 支持merge key `<<`与顶层的[`INCLUDE_KEY`]，见[`load_layered_yaml`]。
+    /// include的文件中的相对路径同样相对于data_dir
+    ///
     /// # Errors
     ///
     /// 如果path不存在或其它问题，yaml解析失败返回error
     pub fn from_yaml_file(path: &str) -> Result<Config, String> {
         let contents = std::fs::read_to_string(path)
             .map_err(|e| format!("read to string error: {}, path: {}", e, path))?;
-        serde_yaml::from_str::<Config>(&contents)
-            .map_err(|e| format!("serde parse yaml error: {}. contents: {}", e, contents))
+        let mut config = match load_layered_yaml(path, &contents)? {
+            Some(value) => serde_yaml::from_value::<Config>(value)
+                .map_err(|e| format!("serde parse yaml error: {}. path: {}", e, path))?,
+            // 直接解析原文件，错误中包含行号。不输出可能包含密码的配置内容
+            None => serde_yaml::from_str::<Config>(&contents)
+                .map_err(|e| format!("serde parse yaml error: {}. path: {}", e, path))?,
+        };
+        // 未配置data_dir时保持旧的行为，相对路径相对于当前目录
+        let data_dir = match config.data_dir.as_deref() {
+            Some(dir) => {
+                let config_dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+                let data_dir = normalize_path(&join_path(config_dir, dir))
+                    .to_str()
+                    .unwrap_or(dir)
+                    .to_string();
+                config.resolve_paths(&data_dir);
+                if let Some(file) = config.logging.as_mut().and_then(|l| l.file.as_mut()) {
+                    *file = join_path(Path::new(&data_dir), file);
+                }
+                data_dir
+            }
+            None => String::new(),
+        };
+        config.validate_paths()?;
+        config.validate_credentials()?;
+        config.inherit_store();
+        config.data_dir = Some(data_dir);
+        Ok(config)
+    }
+
+    /// 不读取配置文件时的最小配置，仅用于lookup等不需要登录的命令。数据目录为当前目录
+    pub fn minimal() -> Config {
+        let youdao = AppConfig {
+            dictionary_path: "youdao-dictionary.json".to_string(),
+            ..Default::default()
+        };
+        Config {
+            data_dir: Some(String::new()),
+            youdao: Some(youdao),
+            ..Default::default()
+        }
+    }
+
+    /// 将所有app中的相对路径解析为相对于data_dir的路径
+    pub fn resolve_paths(&mut self, data_dir: &str) {
+        for app in self
+            .maimemo
+            .iter_mut()
+            .chain(self.youdao.iter_mut())
+            .chain(self.eudic.iter_mut())
+        {
+            app.resolve_paths(data_dir);
+        }
+        for source in self.custom_sources.iter_mut().flat_map(|s| s.values_mut()) {
+            source.dictionary_path = join_path(Path::new(data_dir), &source.dictionary_path);
+        }
+    }
+
+    /// 检查不同的app没有使用相同的cookie_path或dictionary_path，避免同时运行时互相覆盖。
+    /// 比较前去掉路径中的`.`与`..`，如`./words.json`与`words.json`是相同的路径
+    ///
+    /// # Errors
+    ///
+    /// 如果两个app的路径相同
+    pub fn validate_paths(&self) -> Result<(), String> {
+        let apps = [
+            ("maimemo", self.maimemo.as_ref()),
+            ("youdao", self.youdao.as_ref()),
+            ("eudic", self.eudic.as_ref()),
+        ];
+        let mut used = HashMap::new();
+        for (name, app) in apps.iter().filter_map(|(n, a)| a.map(|a| (*n, a))) {
+            let paths = std::iter::once(("dictionary_path", app.get_dictionary_path()))
+                .chain(app.get_cookie_path().map(|p| ("cookie_path", p)))
+                .chain(
+                    app.get_content_cache_path()
+                        .map(|p| ("content_cache_path", p)),
+                );
+            for (kind, path) in paths {
+                check_path(&mut used, name.to_string(), kind, path)?;
+            }
+        }
+        for (name, source) in self.custom_sources.iter().flatten() {
+            let name = format!("custom source {}", name);
+            check_path(&mut used, name, "dictionary_path", &source.dictionary_path)?;
+        }
+        Ok(())
+    }
+
+    /// 检查app的登录信息：youdao需要username与password，maimemo未配置api_token时同样需要，
+    /// eudic需要token
+    ///
+    /// # Errors
+    ///
+    /// 如果缺少需要的字段或字段为空
+    pub fn validate_credentials(&self) -> Result<(), String> {
+        let mut required = vec![];
+        if let Some(app) = &self.youdao {
+            required.extend([
+                ("youdao", "username", app.get_username()),
+                ("youdao", "password", app.get_password()),
+            ]);
+        }
+        if let Some(app) = self
+            .maimemo
+            .as_ref()
+            .filter(|a| a.get_api_token().is_none())
+        {
+            required.extend([
+                ("maimemo", "username", app.get_username()),
+                ("maimemo", "password", app.get_password()),
+            ]);
+        }
+        if let Some(app) = &self.eudic {
+            required.push(("eudic", "token", app.get_token().unwrap_or_default()));
+        }
+        match required.iter().find(|(_, _, value)| value.is_empty()) {
+            Some((app, field, _)) => Err(format!("missing field `{}` in {} config", field, app)),
+            None => Ok(()),
+        }
+    }
+
+    /// 使用profile独立的登录状态：所有app的cookie_path改为`<cookie_path>.<profile>`，
+    /// 多个profile可以同时运行，不会互相覆盖cookies
+    ///
+    /// # Errors
+    ///
+    /// 如果profile为空或包含路径分隔符
+    pub fn use_profile(&mut self, profile: &str) -> Result<(), String> {
+        if profile.is_empty() || profile.contains(['/', '\\']) {
+            return Err(format!("invalid profile name: {:?}", profile));
+        }
+        for app in self
+            .maimemo
+            .iter_mut()
+            .chain(self.youdao.iter_mut())
+            .chain(self.eudic.iter_mut())
+        {
+            if let Some(path) = app.cookie_path.as_mut() {
+                *path = format!("{}.{}", path, profile);
+            }
+        }
+        Ok(())
+    }
+
+    /// 未配置store的app使用全局的store配置
+    pub fn inherit_store(&mut self) {
+        let store = match self.store.as_ref() {
+            Some(store) => store,
+            None => return,
+        };
+        for app in self
+            .maimemo
+            .iter_mut()
+            .chain(self.youdao.iter_mut())
+            .chain(self.eudic.iter_mut())
+        {
+            app.store.get_or_insert_with(|| store.clone());
+        }
+    }
+
+    /// 解析后的数据目录。为空字符串时表示当前目录
+    pub fn get_data_dir(&self) -> &str {
+        self.data_dir.as_deref().unwrap_or_default()
     }
 
     pub fn get_maimemo(&self) -> &AppConfig {
@@ -28,7 +263,7 @@ impl Config {
     }
 
     pub fn get_youdao(&self) -> &AppConfig {
-        &self.youdao.as_ref().unwrap()
+        self.youdao.as_ref().unwrap()
     }
 
     pub fn maimemo(&mut self) -> AppConfig {
@@ -38,6 +273,214 @@ impl Config {
     pub fn youdao(&mut self) -> AppConfig {
         self.youdao.take().unwrap()
     }
+
+    /// # Errors
+    ///
+    /// 如果配置文件中没有eudic
+    pub fn eudic(&mut self) -> Result<AppConfig, String> {
+        self.eudic
+            .take()
+            .ok_or_else(|| "eudic is not configured".to_string())
+    }
+
+    /// 如果未配置anki则使用默认配置
+    pub fn anki(&mut self) -> AnkiConfig {
+        self.anki.take().unwrap_or_default()
+    }
+
+    /// 如果未配置http则使用默认配置
+    pub fn http(&mut self) -> HttpConfig {
+        self.http.take().unwrap_or_default()
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only.unwrap_or(false)
+    }
+
+    /// 名称为name的自定义来源，使用全局的store配置
+    ///
+    /// # Errors
+    ///
+    /// 如果未配置该来源，返回已配置的来源名称
+    pub fn custom_source(&self, name: &str) -> Result<CustomSourceConfig, String> {
+        let sources = self.custom_sources.as_ref();
+        let mut source = sources.and_then(|s| s.get(name)).cloned().ok_or_else(|| {
+            format!(
+                "not found custom source {} in config. configured: [{}]",
+                name,
+                sources
+                    .map(|s| s.keys().cloned().collect::<Vec<_>>().join(", "))
+                    .unwrap_or_default()
+            )
+        })?;
+        source.store = self.store.clone();
+        Ok(source)
+    }
+
+    /// 如果未配置logging则使用默认配置
+    pub fn logging(&mut self) -> LoggingConfig {
+        self.logging.take().unwrap_or_default()
+    }
+}
+
+/// 所有client共享的http配置
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// 所有请求默认的User-Agent。未配置时使用内置的浏览器UA，为空字符串时不发送
+    user_agent: Option<String>,
+    timeout_secs: Option<u64>,
+    /// 添加到所有请求中的headers，请求配置中的headers优先
+    headers: Option<HashMap<String, String>>,
+    /// 代理地址，支持http, https与socks5。未配置时使用HTTPS_PROXY, HTTP_PROXY与NO_PROXY环境变量
+    proxy: Option<String>,
+    /// 不使用proxy的host列表，仅在配置了proxy时有效
+    no_proxy: Option<Vec<String>>,
+    /// 仅用于调试的tls设置，如通过mitmproxy抓包
+    tls: Option<TlsConfig>,
+}
+
+impl HttpConfig {
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    pub fn get_user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    pub fn get_timeout_secs(&self) -> Option<u64> {
+        self.timeout_secs
+    }
+
+    pub fn get_headers(&self) -> Option<&HashMap<String, String>> {
+        self.headers.as_ref()
+    }
+
+    pub fn with_proxy(mut self, proxy: &str, no_proxy: &[&str]) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self.no_proxy = Some(no_proxy.iter().map(|s| s.to_string()).collect());
+        self
+    }
+
+    pub fn get_proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    pub fn get_no_proxy(&self) -> Option<&[String]> {
+        self.no_proxy.as_deref()
+    }
+
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    pub fn get_tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref()
+    }
+}
+
+/// 本次运行的tls设置，不会被保存到cookies或单词本中
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// 额外信任的pem格式CA证书路径
+    extra_ca_path: Option<String>,
+    /// 不验证服务器证书。非常危险，仅用于调试
+    #[serde(default)]
+    danger_accept_invalid_certs: bool,
+}
+
+impl TlsConfig {
+    pub fn with_extra_ca_path(mut self, path: &str) -> Self {
+        self.extra_ca_path = Some(path.to_string());
+        self
+    }
+
+    pub fn with_danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.danger_accept_invalid_certs = accept;
+        self
+    }
+
+    pub fn get_extra_ca_path(&self) -> Option<&str> {
+        self.extra_ca_path.as_deref()
+    }
+
+    pub fn danger_accept_invalid_certs(&self) -> bool {
+        self.danger_accept_invalid_certs
+    }
+}
+
+/// 日志文件配置。stderr的日志由命令行参数`-v`控制
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// 日志文件路径。相对路径时相对于data_dir
+    file: Option<String>,
+    /// 日志文件的级别，默认info
+    file_level: Option<String>,
+    /// 单个日志文件的最大MB，默认10
+    max_size_mb: Option<u64>,
+    /// 保留的日志文件数量，包括当前文件，默认5
+    max_files: Option<usize>,
+}
+
+impl LoggingConfig {
+    pub fn with_file(mut self, path: &str) -> Self {
+        self.file = Some(path.to_string());
+        self
+    }
+
+    pub fn get_file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    pub fn get_file_level(&self) -> Option<&str> {
+        self.file_level.as_deref()
+    }
+
+    pub fn get_max_size_mb(&self) -> u64 {
+        self.max_size_mb.unwrap_or(10)
+    }
+
+    pub fn get_max_files(&self) -> usize {
+        self.max_files.unwrap_or(5)
+    }
+}
+
+/// json文件存储的配置
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StoreConfig {
+    /// 是否输出格式化的json，默认true。关闭时写入紧凑的json
+    pretty: Option<bool>,
+}
+
+impl StoreConfig {
+    pub fn with_pretty(mut self, pretty: bool) -> Self {
+        self.pretty = Some(pretty);
+        self
+    }
+
+    pub fn is_pretty(&self) -> bool {
+        self.pretty.unwrap_or(true)
+    }
+}
+
+/// 本地AnkiConnect配置
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AnkiConfig {
+    url: Option<String>,
+}
+
+impl AnkiConfig {
+    pub fn with_url(url: &str) -> Self {
+        Self {
+            url: Some(url.to_string()),
+        }
+    }
+
+    pub fn get_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,15 +490,80 @@ pub struct Youdao {
     cookie_path: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
+    /// eudic使用token授权时不需要
+    #[serde(default)]
     username: String,
+    #[serde(default)]
     password: String,
     cookie_path: Option<String>,
     dictionary_path: String,
+    /// 本地存储类型：json或sqlite。为空时通过dictionary_path后缀判断
+    storage: Option<String>,
     requests: Option<HashMap<String, RequestConfig>>,
+    /// 是否加密保存cookie文件
+    cookie_encryption: Option<bool>,
+    /// 加密cookie文件的passphrase。为空时从环境变量[`COOKIE_PASSPHRASE_ENV`]中读取
+    cookie_passphrase: Option<String>,
+    /// 本地没有数据文件时自动从web上加载
+    auto_refresh: Option<bool>,
+    /// maimemo upload时插入时间戳的strftime格式
+    timestamp_format: Option<String>,
+    /// maimemo upload时插入的header模板，`{date}`替换为时间戳
+    header_template: Option<String>,
+    /// maimemo upload时是否从新的内容生成notepad的简介
+    update_brief: Option<bool>,
+    /// 生成简介时使用的单词行数
+    brief_lines: Option<usize>,
+    /// eudic open api的授权token
+    token: Option<String>,
+    /// eudic生词本名称，为空时使用第一个生词本
+    study_list: Option<String>,
+    /// eudic或maimemo open api地址，为空时使用默认地址
+    api_url: Option<String>,
+    /// maimemo开放平台api的授权token。配置时使用open api，不再登录与输入验证码
+    api_token: Option<String>,
+    /// maimemo两次刷新验证码的最小间隔秒数，同时作为被限流且没有Retry-After时的等待时间
+    captcha_min_interval_secs: Option<u64>,
+    /// json文件存储的配置，未配置时使用全局的store配置
+    store: Option<StoreConfig>,
+    /// 该服务所有请求的User-Agent，覆盖http.user_agent。请求配置的headers优先
+    user_agent: Option<String>,
+    /// maimemo覆盖notepad前每个notepad保留的备份数量，0表示不备份
+    notepad_backups: Option<usize>,
+    /// 识别maimemo验证码的命令，通过`sh -c`执行，验证码图片路径作为最后一个参数，stdout为答案。失败时询问用户
+    captcha_command: Option<String>,
+    /// 等待captcha_command的秒数
+    captcha_command_timeout_secs: Option<u64>,
+    /// 提交前转换maimemo验证码答案的大小写：keep, lower或upper
+    captcha_case: Option<CaptchaCase>,
+    /// youdao两次尝试登录的最小间隔秒数，登录失败后指数增长
+    login_interval_secs: Option<u64>,
+    /// 忽略login_interval_secs，由`--force-login`设置
+    #[serde(skip)]
+    force_login: bool,
+    /// youdao批量添加或删除单词时两次请求的间隔毫秒数
+    batch_delay_millis: Option<u64>,
+    /// youdao批量操作中每个单词因网络错误或被限流失败后的重试次数
+    batch_retries: Option<usize>,
+    /// maimemo notepad内容缓存文件，按updated_time复用已下载的内容。为空时仅缓存在内存中
+    content_cache_path: Option<String>,
+}
+
+/// 验证码答案的大小写转换。maimemo的验证码不区分大小写
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptchaCase {
+    #[default]
+    Keep,
+    Lower,
+    Upper,
 }
 
+/// cookie passphrase的环境变量
+pub const COOKIE_PASSPHRASE_ENV: &str = "DICT_COOKIE_PASSPHRASE";
+
 impl AppConfig {
     pub fn get_username(&self) -> &str {
         &self.username
@@ -66,30 +574,494 @@ impl AppConfig {
     }
 
     pub fn get_cookie_path(&self) -> Option<&str> {
-        self.cookie_path.as_ref().map(|s| s.as_str())
+        self.cookie_path.as_deref()
     }
 
     pub fn get_dictionary_path(&self) -> &str {
         &self.dictionary_path
     }
 
+    pub fn get_storage(&self) -> Option<&str> {
+        self.storage.as_deref()
+    }
+
+    /// 开启cookie_encryption时返回passphrase，优先使用config中的passphrase
+    ///
+    /// # Errors
+    ///
+    /// 如果开启了cookie_encryption但config与环境变量中都没有passphrase
+    pub fn get_cookie_passphrase(&self) -> Result<Option<String>, String> {
+        if !self.cookie_encryption.unwrap_or(false) {
+            return Ok(None);
+        }
+        self.cookie_passphrase
+            .clone()
+            .or_else(|| std::env::var(COOKIE_PASSPHRASE_ENV).ok())
+            .filter(|p| !p.is_empty())
+            .map(Some)
+            .ok_or_else(|| {
+                format!(
+                    "cookie_encryption is enabled but no cookie_passphrase or {} is set",
+                    COOKIE_PASSPHRASE_ENV
+                )
+            })
+    }
+
+    pub fn auto_refresh(&self) -> bool {
+        self.auto_refresh.unwrap_or(false)
+    }
+
+    pub fn get_timestamp_format(&self) -> Option<&str> {
+        self.timestamp_format.as_deref()
+    }
+
+    pub fn get_header_template(&self) -> Option<&str> {
+        self.header_template.as_deref()
+    }
+
+    pub fn is_update_brief(&self) -> bool {
+        self.update_brief.unwrap_or(false)
+    }
+
+    pub fn get_brief_lines(&self) -> Option<usize> {
+        self.brief_lines
+    }
+
+    pub fn get_token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn get_study_list(&self) -> Option<&str> {
+        self.study_list.as_deref()
+    }
+
+    pub fn get_api_url(&self) -> Option<&str> {
+        self.api_url.as_deref()
+    }
+
+    /// 空的api_token视为未配置
+    pub fn get_api_token(&self) -> Option<&str> {
+        self.api_token.as_deref().filter(|t| !t.trim().is_empty())
+    }
+
+    pub fn get_captcha_min_interval_secs(&self) -> Option<u64> {
+        self.captcha_min_interval_secs
+    }
+
+    pub fn get_notepad_backups(&self) -> Option<usize> {
+        self.notepad_backups
+    }
+
+    pub fn get_captcha_command(&self) -> Option<&str> {
+        self.captcha_command.as_deref()
+    }
+
+    pub fn get_captcha_command_timeout_secs(&self) -> Option<u64> {
+        self.captcha_command_timeout_secs
+    }
+
+    pub fn get_captcha_case(&self) -> CaptchaCase {
+        self.captcha_case.unwrap_or_default()
+    }
+
+    pub fn get_login_interval_secs(&self) -> Option<u64> {
+        self.login_interval_secs
+    }
+
+    pub fn get_batch_delay_millis(&self) -> Option<u64> {
+        self.batch_delay_millis
+    }
+
+    pub fn get_batch_retries(&self) -> Option<usize> {
+        self.batch_retries
+    }
+
+    pub fn get_content_cache_path(&self) -> Option<&str> {
+        self.content_cache_path.as_deref()
+    }
+
+    pub fn with_content_cache_path(mut self, path: &str) -> Self {
+        self.content_cache_path = Some(path.to_string());
+        self
+    }
+
+    pub fn is_force_login(&self) -> bool {
+        self.force_login
+    }
+
+    pub fn with_force_login(mut self, force_login: bool) -> Self {
+        self.force_login = force_login;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    pub fn get_user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    /// 是否以格式化的json保存单词本
+    pub fn is_pretty_store(&self) -> bool {
+        self.store.clone().unwrap_or_default().is_pretty()
+    }
+
     pub fn get_requests(&self) -> Option<&HashMap<String, RequestConfig>> {
         self.requests.as_ref()
     }
+
+    /// 如果未配置req_name则使用默认的请求配置，配置文件中的请求优先
+    pub fn with_default_request(mut self, req_name: &str, req: RequestConfig) -> Self {
+        self.requests
+            .get_or_insert_with(HashMap::new)
+            .entry(req_name.to_string())
+            .or_insert(req);
+        self
+    }
+
+    /// 将相对的dictionary_path、cookie_path与content_cache_path解析为相对于data_dir的路径
+    pub fn resolve_paths(&mut self, data_dir: &str) {
+        let data_dir = Path::new(data_dir);
+        self.dictionary_path = join_path(data_dir, &self.dictionary_path);
+        for path in self
+            .cookie_path
+            .iter_mut()
+            .chain(self.content_cache_path.iter_mut())
+        {
+            *path = join_path(data_dir, path);
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// 配置文件顶层引用其它配置文件的key，值为一个路径或路径列表
+pub const INCLUDE_KEY: &str = "include";
+
+/// yaml merge key，如`<<: *defaults`
+const MERGE_KEY: &str = "<<";
+
+/// 加载path及其include的文件，合并为一个yaml。path与include中都没有include与merge key时返回None，
+/// 由调用者直接解析contents。
+///
+/// include的路径相对于引用它的文件所在的目录，可以嵌套。include的文件按顺序深度合并，后面的文件优先，
+/// 引用它们的文件最优先。mapping被递归合并，其它的值包括列表被替换
+///
+/// # Errors
+///
+/// 如果include的文件不存在、无法解析或循环引用，error中包含引用的链
+fn load_layered_yaml(path: &str, contents: &str) -> Result<Option<serde_yaml::Value>, String> {
+    let value = serde_yaml::from_str::<serde_yaml::Value>(contents)
+        .map_err(|e| format!("serde parse yaml error: {}. path: {}", e, path))?;
+    let has_include = value
+        .as_mapping()
+        .is_some_and(|m| m.contains_key(&INCLUDE_KEY.into()));
+    if !has_include && !has_merge_key(&value) {
+        return Ok(None);
+    }
+    let mut chain = vec![];
+    load_included(Path::new(path), value, &mut chain).map(Some)
+}
+
+/// 合并path的value与其include的文件，chain为正在加载的文件
+fn load_included(
+    path: &Path,
+    mut value: serde_yaml::Value,
+    chain: &mut Vec<(String, std::path::PathBuf)>,
+) -> Result<serde_yaml::Value, String> {
+    // 文件已被读取，canonicalize不会失败
+    let canonical = path
+        .canonicalize()
+        .unwrap_or_else(|_| normalize_path(path.to_str().unwrap_or_default()));
+    chain.push((path.display().to_string(), canonical));
+    apply_merge_keys(&mut value);
+    let includes = match value.as_mapping_mut() {
+        Some(mapping) => mapping.remove(&INCLUDE_KEY.into()),
+        None => None,
+    };
+    let includes = match includes {
+        None => vec![],
+        Some(serde_yaml::Value::String(include)) => vec![include],
+        Some(serde_yaml::Value::Sequence(includes)) => includes
+            .into_iter()
+            .map(|v| match v {
+                serde_yaml::Value::String(include) => Ok(include),
+                v => Err(format!(
+                    "invalid {} {:?} in {}. expected a path",
+                    INCLUDE_KEY,
+                    v,
+                    format_chain(chain)
+                )),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(v) => {
+            return Err(format!(
+                "invalid {} {:?} in {}. expected a path or a list of paths",
+                INCLUDE_KEY,
+                v,
+                format_chain(chain)
+            ))
+        }
+    };
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut merged = serde_yaml::Value::Mapping(Default::default());
+    for include in includes {
+        let include_path = dir.join(&include);
+        let display = include_path.display().to_string();
+        let contents = std::fs::read_to_string(&include_path).map_err(|e| {
+            format!(
+                "read included config error: {}, path: {}. included by: {}",
+                e,
+                display,
+                format_chain(chain)
+            )
+        })?;
+        let canonical = include_path.canonicalize().unwrap_or_default();
+        if chain.iter().any(|(_, p)| *p == canonical) {
+            return Err(format!(
+                "cyclic config include: {} -> {}",
+                format_chain(chain),
+                display
+            ));
+        }
+        let included = serde_yaml::from_str::<serde_yaml::Value>(&contents).map_err(|e| {
+            format!(
+                "serde parse yaml error: {}. path: {}. included by: {}",
+                e,
+                display,
+                format_chain(chain)
+            )
+        })?;
+        let included = load_included(&include_path, included, chain)?;
+        merge_yaml(&mut merged, included);
+    }
+    merge_yaml(&mut merged, value);
+    chain.pop();
+    Ok(merged)
+}
+
+/// 用`a -> b -> c`的形式输出include的链
+fn format_chain(chain: &[(String, std::path::PathBuf)]) -> String {
+    chain
+        .iter()
+        .map(|(path, _)| path.as_str())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// 将overlay深度合并到base中，overlay优先。仅合并mapping
+fn merge_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    match (base, overlay) {
+        (serde_yaml::Value::Mapping(base), serde_yaml::Value::Mapping(overlay)) => {
+            for (key, val) in overlay {
+                match base.get_mut(&key) {
+                    Some(old) => merge_yaml(old, val),
+                    None => {
+                        base.insert(key, val);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn has_merge_key(value: &serde_yaml::Value) -> bool {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => mapping
+            .iter()
+            .any(|(k, v)| k.as_str() == Some(MERGE_KEY) || has_merge_key(v)),
+        serde_yaml::Value::Sequence(items) => items.iter().any(has_merge_key),
+        _ => false,
+    }
+}
+
+/// 展开所有的merge key。mapping中已有的key优先，`<<`为列表时前面的mapping优先
+fn apply_merge_keys(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (_, val) in mapping.iter_mut() {
+                apply_merge_keys(val);
+            }
+            let sources = match mapping.remove(&MERGE_KEY.into()) {
+                Some(serde_yaml::Value::Sequence(sources)) => sources,
+                Some(source) => vec![source],
+                None => return,
+            };
+            for source in sources {
+                if let serde_yaml::Value::Mapping(source) = source {
+                    for (key, val) in source {
+                        if !mapping.contains_key(&key) {
+                            mapping.insert(key, val);
+                        }
+                    }
+                }
+            }
+        }
+        serde_yaml::Value::Sequence(items) => items.iter_mut().for_each(apply_merge_keys),
+        _ => {}
+    }
+}
+
+/// 检查path未被其它app使用，name与kind用于错误信息
+fn check_path<'a>(
+    used: &mut HashMap<std::path::PathBuf, (String, &'a str)>,
+    name: String,
+    kind: &'a str,
+    path: &str,
+) -> Result<(), String> {
+    match used.insert(normalize_path(path), (name.clone(), kind)) {
+        Some((other, other_kind)) => Err(format!(
+            "{} {} and {} {} are the same path: {}",
+            other, other_kind, name, kind, path
+        )),
+        None => Ok(()),
+    }
+}
+
+/// path为相对路径时拼接到dir后
+fn join_path(dir: &Path, path: &str) -> String {
+    dir.join(path).to_str().unwrap_or(path).to_string()
+}
+
+/// 不访问文件系统，去掉path中的`.`并与之前的目录抵消`..`
+fn normalize_path(path: &str) -> std::path::PathBuf {
+    use std::path::Component;
+    let mut normalized = std::path::PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// 通过一个http请求获取json格式单词的来源，如个人的单词服务。
+///
+/// ```yaml
+/// custom_sources:
+///   vocab:
+///     dictionary_path: vocab.json
+///     request:
+///       url: https://example.com/api/words
+///       method: GET
+///       headers:
+///         authorization: Bearer xxx
+///     mapping:
+///       items: /data/words
+///       word: /text
+///       trans: /meaning
+///       modified_time: /updated_at
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomSourceConfig {
+    /// 本地单词本路径，与youdao使用相同的存储
+    dictionary_path: String,
+    /// 单词所在的单词本名称，默认为来源名称
+    book_name: Option<String>,
+    request: RequestConfig,
+    mapping: FieldMapping,
+    /// 使用全局的store配置
+    #[serde(skip)]
+    store: Option<StoreConfig>,
+}
+
+/// 从response json中读取单词的字段，值为json pointer如`/data/words`，见
+/// [RFC 6901](https://tools.ietf.org/html/rfc6901)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldMapping {
+    /// 单词数组在response中的位置，为空时response就是单词数组
+    #[serde(default)]
+    pub items: String,
+    /// 以下的字段相对于单词数组中的每个元素
+    pub word: String,
+    pub trans: Option<String>,
+    pub phonetic: Option<String>,
+    /// 毫秒的时间戳或RFC 3339格式的时间。未配置时为0
+    pub modified_time: Option<String>,
+}
+
+impl CustomSourceConfig {
+    pub fn get_dictionary_path(&self) -> &str {
+        &self.dictionary_path
+    }
+
+    pub fn get_book_name(&self) -> Option<&str> {
+        self.book_name.as_deref()
+    }
+
+    pub fn get_mapping(&self) -> &FieldMapping {
+        &self.mapping
+    }
+
+    /// 请求名称为req_name的app配置，用于发送请求与打开本地单词本。request可以不配置headers
+    pub fn to_app_config(&self, req_name: &str) -> AppConfig {
+        let mut request = self.request.clone();
+        request.headers.get_or_insert_with(HashMap::new);
+        AppConfig {
+            dictionary_path: self.dictionary_path.clone(),
+            store: self.store.clone(),
+            ..Default::default()
+        }
+        .with_default_request(req_name, request)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RequestConfig {
     url: String,
     method: String,
     headers: Option<HashMap<String, String>>,
+    /// 是否跟随301/302/307/308重定向。默认不跟随，如登录时需要获取302的set-cookie
+    follow_redirects: Option<bool>,
+    /// 最大重定向次数，默认为[`DEFAULT_MAX_REDIRECTS`]
+    max_redirects: Option<usize>,
+    /// 允许的response status，默认为[`DEFAULT_OK_STATUS`]
+    ok_status: Option<Vec<u16>>,
+    /// response body的最大字节数，默认为[`DEFAULT_MAX_RESPONSE_BYTES`]
+    max_response_bytes: Option<usize>,
+    /// 使用该域名的cookies而不是url的host，如通过镜像地址请求youdao。仅用于第一个请求，不用于重定向
+    cookie_domain: Option<String>,
 }
 
 impl RequestConfig {
+    pub fn new(url: &str, method: &str, headers: HashMap<String, String>) -> Self {
+        Self {
+            url: url.to_string(),
+            method: method.to_string(),
+            headers: Some(headers),
+            follow_redirects: None,
+            max_redirects: None,
+            ok_status: None,
+            max_response_bytes: None,
+            cookie_domain: None,
+        }
+    }
+
+    pub fn with_ok_status(mut self, ok_status: &[u16]) -> Self {
+        self.ok_status = Some(ok_status.to_vec());
+        self
+    }
+
     pub fn get_url(&self) -> &str {
         &self.url
     }
 
+    pub fn get_cookie_domain(&self) -> Option<&str> {
+        self.cookie_domain.as_deref()
+    }
+
     pub fn get_method(&self) -> &str {
         &self.method
     }
@@ -97,8 +1069,34 @@ impl RequestConfig {
     pub fn get_headers(&self) -> Option<&HashMap<String, String>> {
         self.headers.as_ref()
     }
+
+    pub fn follow_redirects(&self) -> bool {
+        self.follow_redirects.unwrap_or(false)
+    }
+
+    pub fn get_max_redirects(&self) -> usize {
+        self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS)
+    }
+
+    pub fn get_ok_status(&self) -> &[u16] {
+        self.ok_status.as_deref().unwrap_or(&DEFAULT_OK_STATUS)
+    }
+
+    pub fn get_max_response_bytes(&self) -> usize {
+        self.max_response_bytes
+            .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES)
+    }
 }
 
+/// 默认允许的response status
+pub const DEFAULT_OK_STATUS: [u16; 2] = [200, 302];
+
+/// 跟随重定向时默认的最大次数
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// 默认的response body最大字节数
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
+
 /// 保存到json文件
 pub fn save_json<T: ?Sized + serde::ser::Serialize>(data: &T, path: &str) -> io::Result<()> {
     let contents = serde_json::to_string(data)?;
@@ -121,18 +1119,373 @@ pub async fn load_from_json_file<T: serde::de::DeserializeOwned>(path: &str) ->
 mod tests {
     use super::*;
 
+    #[test]
+    fn config_path_without_home() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        // 指定flag时不读取$HOME
+        let no_home = env(&[]);
+        assert_eq!(
+            resolve_config_path(Some("a.yml"), no_home),
+            Ok("a.yml".to_string())
+        );
+        assert_eq!(
+            resolve_config_path(None, env(&[(CONFIG_PATH_ENV, "b.yml"), ("HOME", "/root")])),
+            Ok("b.yml".to_string())
+        );
+        assert_eq!(
+            resolve_config_path(None, env(&[(CONFIG_PATH_ENV, ""), ("HOME", "/root")])),
+            Ok("/root/dict-config.yml".to_string())
+        );
+        let e = resolve_config_path(None, env(&[("HOME", "")])).unwrap_err();
+        assert_eq!(e.tried.len(), 3);
+        assert!(e.to_string().contains("$HOME is not set"), "{}", e);
+
+        let mut config = Config::minimal();
+        assert_eq!(config.get_data_dir(), "");
+        assert_eq!(
+            config.youdao().get_dictionary_path(),
+            "youdao-dictionary.json"
+        );
+        assert!(config.maimemo.is_none());
+    }
+
+    #[test]
+    fn resolve_relative_paths() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config_path = dir.path().join("dict-config.yml");
+        std::fs::write(
+            &config_path,
+            "data_dir: data
+youdao:
+  username: a
+  password: b
+  cookie_path: youdao-cookies.json
+  dictionary_path: /tmp/youdao.json
+maimemo:
+  username: a
+  password: b
+  dictionary_path: maimemo.json
+",
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        let config = Config::from_yaml_file(config_path.to_str().unwrap())?;
+        let data_dir = dir.path().join("data");
+        assert_eq!(config.get_data_dir(), data_dir.to_str().unwrap());
+        let youdao = config.get_youdao();
+        assert_eq!(youdao.get_dictionary_path(), "/tmp/youdao.json");
+        assert_eq!(
+            youdao.get_cookie_path(),
+            data_dir.join("youdao-cookies.json").to_str()
+        );
+        assert_eq!(
+            config.get_maimemo().get_dictionary_path(),
+            data_dir.join("maimemo.json").to_str().unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn missing_credentials() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config_path = dir.path().join("dict-config.yml");
+        let config_path = config_path.to_str().unwrap();
+        let load = |yaml: &str| {
+            std::fs::write(config_path, yaml).unwrap();
+            Config::from_yaml_file(config_path)
+        };
+        let e = load("youdao:\n  username: a\n  dictionary_path: y.json\n").unwrap_err();
+        assert_eq!(e, "missing field `password` in youdao config");
+        let e = load("maimemo:\n  username: ''\n  password: b\n  dictionary_path: m.json\n")
+            .unwrap_err();
+        assert_eq!(e, "missing field `username` in maimemo config");
+        // 使用open api时不需要密码
+        load("maimemo:\n  api_token: t\n  dictionary_path: m.json\n")?;
+        let e = load("eudic:\n  dictionary_path: e.json\n").unwrap_err();
+        assert_eq!(e, "missing field `token` in eudic config");
+
+        let mut config = load("eudic:\n  token: t\n  dictionary_path: e.json\n")?;
+        assert!(config.eudic().is_ok());
+        assert_eq!(config.eudic().unwrap_err(), "eudic is not configured");
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error_without_contents() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("dict-config.yml");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "youdao:\n  password: secret\n  username: [a]\n")
+            .map_err(|e| format!("{:?}", e))?;
+        let e = Config::from_yaml_file(path).unwrap_err();
+        assert!(!e.contains("secret"), "{}", e);
+        assert!(
+            e.contains("line 3") && e.ends_with(&format!("path: {}", path)),
+            "{}",
+            e
+        );
+
+        // include合并后解析的错误同样不包含内容
+        let base = dir.path().join("base.yml");
+        std::fs::write(&base, "youdao:\n  password: secret\n").map_err(|e| format!("{:?}", e))?;
+        std::fs::write(path, "include: base.yml\nyoudao:\n  username: [a]\n")
+            .map_err(|e| format!("{:?}", e))?;
+        let e = Config::from_yaml_file(path).unwrap_err();
+        assert!(!e.contains("secret"), "{}", e);
+        assert!(e.ends_with(&format!("path: {}", path)), "{}", e);
+        Ok(())
+    }
+
+    #[test]
+    fn profile_cookie_paths() -> Result<(), String> {
+        let mut config = serde_yaml::from_str::<Config>(
+            "youdao:
+  cookie_path: /tmp/youdao-cookies.json
+  dictionary_path: youdao.json
+maimemo:
+  dictionary_path: maimemo.json
+",
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        config.use_profile("work")?;
+        assert_eq!(
+            config.get_youdao().get_cookie_path(),
+            Some("/tmp/youdao-cookies.json.work")
+        );
+        // 没有cookie_path时不保存cookies
+        assert_eq!(config.get_maimemo().get_cookie_path(), None);
+        assert_eq!(
+            config.use_profile("../work").unwrap_err(),
+            "invalid profile name: \"../work\""
+        );
+        assert!(config.use_profile("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn shared_paths() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let config_path = dir.path().join("dict-config.yml");
+        let config_path = config_path.to_str().unwrap();
+        let write = |maimemo_cookie_path: &str| {
+            std::fs::write(
+                config_path,
+                format!(
+                    "youdao:
+  username: a
+  password: b
+  cookie_path: cookies.json
+  dictionary_path: youdao.json
+maimemo:
+  username: a
+  password: b
+  cookie_path: {}
+  dictionary_path: maimemo.json
+",
+                    maimemo_cookie_path
+                ),
+            )
+            .map_err(|e| format!("{:?}", e))
+        };
+        write("maimemo-cookies.json")?;
+        Config::from_yaml_file(config_path)?;
+
+        write("cookies.json")?;
+        let e = Config::from_yaml_file(config_path).unwrap_err();
+        assert!(
+            e.starts_with("maimemo cookie_path and youdao cookie_path are the same path"),
+            "{}",
+            e
+        );
+
+        // cookie与单词本也不能相同
+        write("youdao.json")?;
+        let e = Config::from_yaml_file(config_path).unwrap_err();
+        assert!(e.contains("dictionary_path"), "{}", e);
+
+        // 写法不同的相同路径
+        write("./data/../cookies.json")?;
+        let e = Config::from_yaml_file(config_path).unwrap_err();
+        assert!(e.contains("are the same path"), "{}", e);
+
+        // 自定义来源的单词本
+        std::fs::write(
+            config_path,
+            "youdao:
+  dictionary_path: youdao.json
+custom_sources:
+  vocab:
+    dictionary_path: ./youdao.json
+    request: {url: 'http://localhost/words', method: GET}
+    mapping: {word: /w}
+",
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        let e = Config::from_yaml_file(config_path).unwrap_err();
+        assert!(
+            e.starts_with(
+                "youdao dictionary_path and custom source vocab dictionary_path are the same path"
+            ),
+            "{}",
+            e
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn inherit_store_config() {
+        let mut config = serde_yaml::from_str::<Config>(
+            "store:
+  pretty: false
+youdao:
+  dictionary_path: youdao.json
+maimemo:
+  dictionary_path: maimemo.json
+  store:
+    pretty: true
+",
+        )
+        .unwrap();
+        config.inherit_store();
+        assert!(!config.get_youdao().is_pretty_store());
+        assert!(config.get_maimemo().is_pretty_store());
+
+        let config = serde_yaml::from_str::<AppConfig>("dictionary_path: a.json").unwrap();
+        assert!(config.is_pretty_store());
+    }
+
     #[test]
     fn load_maimemo_from_file() -> Result<(), String> {
-        let path = "config.yml";
+        let path = "tests/fixtures/dict-config.yml";
         let config = Config::from_yaml_file(path)?;
         let maimemo = config.get_maimemo();
-        assert_eq!(maimemo.get_username(), "dhjnavyd@gmail.com");
-        assert!(maimemo.get_password().len() > 0);
+        assert_eq!(maimemo.get_username(), "fixture@gmail.com");
+        assert!(!maimemo.get_password().is_empty());
+        // 未配置data_dir时相对路径不变
+        assert_eq!(config.get_data_dir(), "");
         assert_eq!("maimemo-dictionary.json", maimemo.get_dictionary_path());
         assert_eq!(Some("maimemo-cookies.json"), maimemo.get_cookie_path());
-        if let Some(requests) = maimemo.get_requests() {
-            assert_eq!(requests.len(), 5);
-        }
+        assert_eq!(maimemo.get_requests().map(|r| r.len()), Some(5));
+        Ok(())
+    }
+
+    #[test]
+    fn load_layered_includes() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let write = |name: &str, contents: &str| {
+            std::fs::write(dir.path().join(name), contents).map_err(|e| format!("{:?}", e))
+        };
+        write(
+            "requests-maimemo.yml",
+            "defaults: &form
+  method: POST
+  headers: {content-type: application/x-www-form-urlencoded}
+maimemo:
+  username: template
+  dictionary_path: maimemo.json
+  requests:
+    login:
+      <<: *form
+      url: http://www.maimemo.com/auth/login
+    notepad-save:
+      <<: *form
+      url: http://www.maimemo.com/notepad/save
+",
+        )?;
+        write(
+            "secrets.yml",
+            "maimemo:
+  username: a@b.com
+  password: secret
+",
+        )?;
+        write(
+            "dict-config.yml",
+            "data_dir: .
+include: [requests-maimemo.yml, secrets.yml]
+maimemo:
+  password: override
+  requests:
+    login:
+      url: http://localhost/login
+",
+        )?;
+        let config = Config::from_yaml_file(dir.path().join("dict-config.yml").to_str().unwrap())?;
+        let maimemo = config.get_maimemo();
+        // 后面的文件优先，引用的文件最优先
+        assert_eq!(maimemo.get_username(), "a@b.com");
+        assert_eq!(maimemo.get_password(), "override");
+        assert_eq!(
+            maimemo.get_dictionary_path(),
+            dir.path().join("maimemo.json").to_str().unwrap()
+        );
+        let requests = maimemo.get_requests().unwrap();
+        assert_eq!(requests.len(), 2);
+        let login = &requests["login"];
+        assert_eq!(login.get_url(), "http://localhost/login");
+        assert_eq!(login.get_method(), "POST");
+        let save = &requests["notepad-save"];
+        assert_eq!(save.get_url(), "http://www.maimemo.com/notepad/save");
+        assert_eq!(
+            save.get_headers().and_then(|h| h.get("content-type")),
+            Some(&"application/x-www-form-urlencoded".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reject_bad_includes() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let write = |name: &str, contents: &str| {
+            std::fs::write(path(name), contents).map_err(|e| format!("{:?}", e))
+        };
+        write(
+            "a.yml",
+            "include: b.yml
+",
+        )?;
+        write(
+            "b.yml",
+            "include: [./a.yml]
+",
+        )?;
+        let e = Config::from_yaml_file(&path("a.yml")).unwrap_err();
+        assert_eq!(
+            e,
+            format!(
+                "cyclic config include: {} -> {} -> {}",
+                path("a.yml"),
+                path("b.yml"),
+                path("./a.yml")
+            )
+        );
+
+        write(
+            "b.yml",
+            "include: missing.yml
+",
+        )?;
+        let e = Config::from_yaml_file(&path("a.yml")).unwrap_err();
+        assert!(
+            e.contains(&format!("path: {}.", path("missing.yml"))),
+            "{}",
+            e
+        );
+        assert!(
+            e.ends_with(&format!(
+                "included by: {} -> {}",
+                path("a.yml"),
+                path("b.yml")
+            )),
+            "{}",
+            e
+        );
         Ok(())
     }
 }
@@ -0,0 +1,210 @@
+//! 上传前检查notepad内容中会被maimemo删除的字符。
+//!
+//! 每个规则检查并修复一行，新的规则添加到[`RULES`]中即可。修复按表中的顺序应用所有规则，
+//! 修复后的内容不会再被任何规则检查出问题
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// 一个检查规则
+pub struct Rule {
+    pub name: &'static str,
+    /// 输出警告时的说明
+    pub message: &'static str,
+    check: fn(&str) -> bool,
+    fix: fn(&str) -> String,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "tab",
+        message: "tabs",
+        check: |line| line.contains('\t'),
+        fix: |line| line.replace('\t', " "),
+    },
+    Rule {
+        name: "full-width-space",
+        message: "full-width spaces",
+        check: |line| line.contains('\u{3000}'),
+        fix: |line| line.replace('\u{3000}', " "),
+    },
+    Rule {
+        name: "control",
+        message: "control characters",
+        check: |line| line.chars().any(is_control),
+        fix: |line| line.chars().filter(|c| !is_control(*c)).collect(),
+    },
+    Rule {
+        name: "emoji",
+        message: "emoji",
+        check: |line| line.chars().any(is_emoji),
+        fix: |line| line.chars().filter(|c| !is_emoji(*c)).collect(),
+    },
+    Rule {
+        name: "unnormalized",
+        message: "unicode not in NFC form",
+        check: |line| !is_nfc(line),
+        fix: |line| line.nfc().collect(),
+    },
+];
+
+/// tab由单独的规则处理
+fn is_control(c: char) -> bool {
+    c.is_control() && c != '\t'
+}
+
+/// emoji所在的unicode区块，以及组合emoji时使用的ZWJ与变体选择符。
+/// 不包括释义中常用的☆、✓等杂项符号与装饰符号区块
+const EMOJI_RANGES: &[(char, char)] = &[
+    ('\u{200d}', '\u{200d}'),
+    ('\u{fe0f}', '\u{fe0f}'),
+    ('\u{1f000}', '\u{1faff}'),
+];
+
+fn is_emoji(c: char) -> bool {
+    EMOJI_RANGES
+        .iter()
+        .any(|(start, end)| (*start..=*end).contains(&c))
+}
+
+/// 一行中违反一个规则的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// 行号，从1开始
+    pub line: usize,
+    pub rule: &'static str,
+}
+
+/// 检查contents中的每一行，按行号与[`RULES`]的顺序返回问题
+pub fn lint(contents: &str) -> Vec<Issue> {
+    contents
+        .lines()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            RULES
+                .iter()
+                .filter(move |rule| (rule.check)(line))
+                .map(move |rule| Issue {
+                    line: i + 1,
+                    rule: rule.name,
+                })
+        })
+        .collect()
+}
+
+/// 对每一行应用所有规则的修复。行尾统一为`\n`，保留最后的换行
+pub fn fix(contents: &str) -> String {
+    let mut fixed = contents
+        .lines()
+        .map(|line| {
+            RULES
+                .iter()
+                .fold(line.to_string(), |line, rule| (rule.fix)(&line))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if contents.ends_with('\n') {
+        fixed.push('\n');
+    }
+    fixed
+}
+
+/// 有问题的行数
+pub fn count_lines(issues: &[Issue]) -> usize {
+    let mut lines = issues.iter().map(|i| i.line).collect::<Vec<_>>();
+    lines.dedup();
+    lines.len()
+}
+
+/// 每个规则一行，如`lines 1, 3 contain tabs`或`line 1 contains tabs`
+pub fn format_issues(issues: &[Issue]) -> String {
+    RULES
+        .iter()
+        .filter_map(|rule| {
+            let lines = issues
+                .iter()
+                .filter(|i| i.rule == rule.name)
+                .map(|i| i.line.to_string())
+                .collect::<Vec<_>>();
+            match lines.len() {
+                0 => None,
+                1 => Some(format!("line {} contains {}", lines[0], rule.message)),
+                _ => Some(format!(
+                    "lines {} contain {}",
+                    lines.join(", "),
+                    rule.message
+                )),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules() {
+        // (rule, line, fixed)
+        let cases = vec![
+            ("tab", "run\tto run", "run to run"),
+            ("full-width-space", "run\u{3000}跑", "run 跑"),
+            ("control", "run\u{7}\u{1b}", "run"),
+            ("control", "a\u{85}b", "ab"),
+            ("emoji", "run 🏃", "run "),
+            ("emoji", "❤\u{fe0f} love", "❤ love"),
+            ("emoji", "👨\u{200d}👩 family", " family"),
+            ("unnormalized", "cafe\u{301}", "café"),
+        ];
+        for (name, line, fixed) in cases {
+            let rule = RULES.iter().find(|r| r.name == name).unwrap();
+            assert!((rule.check)(line), "{}: {:?}", name, line);
+            assert_eq!((rule.fix)(line), fixed, "{}", name);
+            assert!(!(rule.check)(fixed), "{}: {:?}", name, fixed);
+            // 其它规则不会检查出问题
+            let others = lint(line)
+                .into_iter()
+                .filter(|i| i.rule != name)
+                .collect::<Vec<_>>();
+            assert!(others.is_empty(), "{}: {:?}", name, others);
+        }
+        for line in &[
+            "accommodate",
+            "容纳；使适应",
+            "café",
+            "naïve — résumé",
+            "[əˈkɒmədeɪt]",
+            "☆ 常用 ✓",
+            "★★★ run ➜ ran",
+        ] {
+            assert!(lint(line).is_empty(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn lint_and_fix() {
+        let contents = "run\t🏃\r\n\nwalk\u{3000}\ncafe\u{301}\n";
+        let issues = lint(contents);
+        assert_eq!(
+            issues.iter().map(|i| (i.line, i.rule)).collect::<Vec<_>>(),
+            vec![
+                (1, "tab"),
+                (1, "emoji"),
+                (3, "full-width-space"),
+                (4, "unnormalized"),
+            ]
+        );
+        assert_eq!(count_lines(&issues), 3);
+        assert_eq!(
+            format_issues(&issues),
+            "line 1 contains tabs\nline 3 contains full-width spaces\nline 1 contains emoji\nline 4 contains unicode not in NFC form"
+        );
+        let issues = lint("run\t\nwalk\t");
+        assert_eq!(format_issues(&issues), "lines 1, 2 contain tabs");
+        let fixed = fix(contents);
+        assert_eq!(fixed, "run \n\nwalk \ncafé\n");
+        assert!(lint(&fixed).is_empty());
+        // 修复是幂等的
+        assert_eq!(fix(&fixed), fixed);
+        assert_eq!(fix("run"), "run");
+    }
+}
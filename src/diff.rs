@@ -0,0 +1,209 @@
+//! 输出文本之间的unified diff与终端中的摘要diff
+use crate::color::{paint, CYAN, GREEN, RED};
+use crate::table;
+use similar::udiff::UnifiedHunkHeader;
+use similar::{ChangeTag, TextDiff};
+
+/// diff中修改前后保留的上下文行数
+pub const CONTEXT_LINES: usize = 3;
+
+/// 摘要diff默认最多输出的hunk数量
+pub const DEFAULT_MAX_HUNKS: usize = 5;
+
+/// [`render_diff`]的输出设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffView {
+    /// 最多输出的hunk数量，None时输出全部
+    pub max_hunks: Option<usize>,
+    /// 是否使用颜色区分添加与删除的行
+    pub color: bool,
+}
+
+impl Default for DiffView {
+    fn default() -> Self {
+        Self {
+            max_hunks: Some(DEFAULT_MAX_HUNKS),
+            color: false,
+        }
+    }
+}
+
+/// 按行比较old与new，输出以`--- old_name`与`+++ new_name`开头的unified diff。相同时返回None
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let diff = TextDiff::from_lines(old, new);
+    let mut out = diff
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_name, new_name)
+        .to_string();
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// 在终端中输出的摘要diff：添加与删除的行数，之后为最多`view.max_hunks`个hunk。
+/// 每行前为对齐的旧行号与新行号，超过width的行被截断。相同时返回空字符串
+pub fn render_diff<'a>(
+    diff: &TextDiff<'a, 'a, 'a, str>,
+    width: Option<usize>,
+    view: DiffView,
+) -> String {
+    let paint = |color: &str, s: &str| {
+        if view.color {
+            paint(color, s)
+        } else {
+            s.to_string()
+        }
+    };
+    let (mut added, mut removed) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    let groups = diff.grouped_ops(CONTEXT_LINES);
+    if groups.is_empty() {
+        return String::new();
+    }
+    let mut lines = vec![crate::msg!(
+        "diff.summary",
+        paint(GREEN, &format!("+{}", added)),
+        paint(RED, &format!("-{}", removed)),
+        groups.len()
+    )];
+    let digits = diff
+        .old_slices()
+        .len()
+        .max(diff.new_slices().len())
+        .to_string()
+        .len();
+    let number = |i: Option<usize>| i.map_or_else(String::new, |i| (i + 1).to_string());
+    let shown = view.max_hunks.unwrap_or(groups.len()).min(groups.len());
+    for group in &groups[..shown] {
+        lines.push(paint(CYAN, &UnifiedHunkHeader::new(group).to_string()));
+        for change in group.iter().flat_map(|op| diff.iter_changes(op)) {
+            let (sign, color) = match change.tag() {
+                ChangeTag::Insert => ('+', Some(GREEN)),
+                ChangeTag::Delete => ('-', Some(RED)),
+                ChangeTag::Equal => (' ', None),
+            };
+            let line = format!(
+                "{:>w$} {:>w$} {} {}",
+                number(change.old_index()),
+                number(change.new_index()),
+                sign,
+                change.value().trim_end_matches(&['\r', '\n'][..]),
+                w = digits
+            );
+            let line = match width {
+                Some(width) => table::truncate(&line, width),
+                None => line,
+            };
+            lines.push(match color {
+                Some(color) => paint(color, &line),
+                None => line,
+            });
+        }
+    }
+    if shown < groups.len() {
+        lines.push(crate::msg!("diff.more_hunks", groups.len() - shown));
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "local", "remote"), None);
+        assert_eq!(
+            unified_diff("a\nb\nc\n", "a\nc\nd\n", "local", "remote").unwrap(),
+            "--- local\n+++ remote\n@@ -1,3 +1,3 @@\n a\n-b\n c\n+d\n"
+        );
+        let diff = unified_diff("a", "", "local", "remote").unwrap();
+        assert!(diff.contains("\n-a\n"), "{}", diff);
+        assert!(diff.ends_with('\n'), "{}", diff);
+    }
+
+    const OLD: &str = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+    const NEW: &str = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+
+    #[test]
+    fn render_plain() {
+        let diff = TextDiff::from_lines(OLD, NEW);
+        let full = DiffView {
+            max_hunks: None,
+            color: false,
+        };
+        assert_eq!(
+            render_diff(&diff, None, full),
+            "+2 -1 lines in 2 hunk(s)
+@@ -1,5 +1,5 @@
+ 1  1   a
+ 2    - b
+    2 + B
+ 3  3   c
+ 4  4   d
+ 5  5   e
+@@ -10,3 +10,4 @@
+10 10   j
+11 11   k
+12 12   l
+   13 + m
+"
+        );
+        let out = render_diff(
+            &diff,
+            Some(8),
+            DiffView {
+                max_hunks: Some(1),
+                color: false,
+            },
+        );
+        // 仅截断diff的行
+        assert_eq!(
+            out,
+            "+2 -1 lines in 2 hunk(s)
+@@ -1,5 +1,5 @@
+ 1  1  …
+ 2    -…
+    2 +…
+ 3  3  …
+ 4  4  …
+ 5  5  …
+... 1 more hunk(s). use --diff-full to show all
+"
+        );
+        assert!(render_diff(&TextDiff::from_lines(OLD, OLD), None, full).is_empty());
+    }
+
+    #[test]
+    fn render_color() {
+        let diff = TextDiff::from_lines("a\nb\n", "a\nc\n");
+        let out = render_diff(
+            &diff,
+            None,
+            DiffView {
+                max_hunks: None,
+                color: true,
+            },
+        );
+        assert_eq!(
+            out,
+            "\x1b[32m+1\x1b[0m \x1b[31m-1\x1b[0m lines in 1 hunk(s)
+\x1b[36m@@ -1,2 +1,2 @@\x1b[0m
+1 1   a
+\x1b[31m2   - b\x1b[0m
+\x1b[32m  2 + c\x1b[0m
+"
+        );
+    }
+}
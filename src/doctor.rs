@@ -0,0 +1,563 @@
+//! `dict doctor`的检查项。
+//!
+//! 每个检查返回一个或多个[`Check`]。配置、路径与cookies的检查不访问网络，
+//! hosts与登录状态的检查使用共享的[`HttpContext`]
+use crate::client::{
+    self,
+    context::HttpContext,
+    crypto, eudic_client,
+    maimemo_client::{self, MaimemoClient, NotepadQuery},
+    maimemo_open_client::{self, MaimemoOpenClient},
+    youdao_client::{self, YoudaoClient},
+    COOKIE_EXPIRY_WARNING_SECS,
+};
+use crate::config::{AppConfig, Config};
+use crate::error::DictError;
+use cookie_store::CookieStore;
+use reqwest::{Method, Url};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 检查host可访问时每个请求的超时秒数
+pub const HOST_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// 一个检查项的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Pass, detail)
+    }
+
+    pub fn warn(name: &str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warn, detail)
+    }
+
+    pub fn fail(name: &str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Fail, detail)
+    }
+
+    fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// 加载并验证配置文件
+pub fn check_config_file(path: &str) -> (Check, Option<Config>) {
+    match Config::from_yaml_file(path) {
+        Ok(config) => (Check::pass("config", path), Some(config)),
+        Err(e) => (Check::fail("config", e), None),
+    }
+}
+
+fn apps(config: &Config) -> impl Iterator<Item = (&'static str, &AppConfig)> {
+    vec![
+        ("youdao", config.youdao.as_ref()),
+        ("maimemo", config.maimemo.as_ref()),
+        ("eudic", config.eudic.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(name, app)| app.map(|app| (name, app)))
+}
+
+/// 检查所有app的dictionary_path与cookie_path是否可写
+pub fn check_paths(config: &Config) -> Vec<Check> {
+    let mut checks = vec![];
+    for (name, app) in apps(config) {
+        checks.push(check_writable(
+            &format!("{} dictionary_path", name),
+            app.get_dictionary_path(),
+        ));
+        if let Some(path) = app.get_cookie_path() {
+            checks.push(check_writable(&format!("{} cookie_path", name), path));
+        }
+    }
+    checks
+}
+
+/// 已存在的文件以append打开，不存在时在所在目录中创建并删除一个临时文件
+fn check_writable(name: &str, path: &str) -> Check {
+    let p = Path::new(path);
+    if p.is_dir() {
+        return Check::fail(name, format!("{} is a directory", path));
+    }
+    if p.exists() {
+        return match fs::OpenOptions::new().append(true).open(p) {
+            Ok(_) => Check::pass(name, path),
+            Err(e) => Check::fail(name, format!("{} is not writable: {}", path, e)),
+        };
+    }
+    let dir = p
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    if !dir.is_dir() {
+        return Check::fail(name, format!("directory {:?} of {} not found", dir, path));
+    }
+    let probe = dir.join(format!(".dict-doctor-{}", std::process::id()));
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Check::pass(name, format!("{} (not created yet)", path))
+        }
+        Err(e) => Check::fail(name, format!("directory {:?} is not writable: {}", dir, e)),
+    }
+}
+
+/// 检查youdao与maimemo的cookie文件能否解析，且包含未过期的登录cookies。
+/// maimemo使用open api时不检查
+pub fn check_cookies(config: &Config) -> Vec<Check> {
+    [
+        (
+            "youdao",
+            config.youdao.as_ref(),
+            youdao_client::LOGIN_COOKIES,
+        ),
+        (
+            "maimemo",
+            config
+                .maimemo
+                .as_ref()
+                .filter(|app| app.get_api_token().is_none()),
+            maimemo_client::LOGIN_COOKIES,
+        ),
+    ]
+    .iter()
+    .filter_map(|(service, app, login_cookies)| {
+        app.map(|app| check_login_cookies(service, app, login_cookies))
+    })
+    .collect()
+}
+
+/// maimemo使用的后端：配置api_token时为open api，否则为网页登录与验证码
+pub fn check_maimemo_backend(app: &AppConfig) -> Check {
+    let name = "maimemo backend";
+    match app.get_api_token() {
+        Some(_) => Check::pass(
+            name,
+            format!(
+                "open api (api_token): {}",
+                app.get_api_url()
+                    .unwrap_or(maimemo_open_client::DEFAULT_API_URL)
+            ),
+        ),
+        None => Check::pass(name, "web (cookies and captcha)"),
+    }
+}
+
+/// 不同于[`client::build_cookie_store`]，不会创建或移动cookie文件
+fn check_login_cookies(service: &str, app: &AppConfig, login_cookies: &[(&str, &str)]) -> Check {
+    let name = format!("{} cookies", service);
+    let path = match app.get_cookie_path() {
+        Some(path) => path,
+        None => return Check::warn(&name, "no cookie_path, cookies are only kept in memory"),
+    };
+    if !Path::new(path).exists() {
+        return Check::warn(&name, format!("{} not found, not logged in yet", path));
+    }
+    let mut data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) => return Check::fail(&name, format!("read {} error: {}", path, e)),
+    };
+    if crypto::is_encrypted(&data) {
+        let decrypted = app.get_cookie_passphrase().and_then(|passphrase| {
+            let passphrase = passphrase
+                .ok_or_else(|| "encrypted but no cookie passphrase is configured".to_string())?;
+            crypto::decrypt(&data, &passphrase)
+        });
+        data = match decrypted {
+            Ok(data) => data,
+            Err(e) => return Check::fail(&name, format!("{}: {}", e, path)),
+        };
+    }
+    if data.is_empty() {
+        return Check::warn(&name, format!("{} is empty, not logged in yet", path));
+    }
+    let cookie_store = match CookieStore::load_json(&data[..]) {
+        Ok(cookie_store) => cookie_store,
+        Err(e) => return Check::fail(&name, format!("parse {} error: {:?}", path, e)),
+    };
+    let statuses = client::login_cookie_status(&cookie_store, login_cookies);
+    let missing = statuses
+        .iter()
+        .filter(|s| !s.present)
+        .map(|s| s.name.as_str())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Check::warn(
+            &name,
+            format!(
+                "missing or expired login cookies: {}, please login again",
+                missing.join(", ")
+            ),
+        );
+    }
+    let expires = statuses
+        .iter()
+        .filter_map(|s| s.expires.as_deref().map(|e| (s, e)))
+        .min_by_key(|(s, _)| s.expires_in_secs);
+    match expires {
+        Some((s, expires)) if s.expires_within(COOKIE_EXPIRY_WARNING_SECS) => Check::warn(
+            &name,
+            format!(
+                "login cookie {} expires at {}, please login again",
+                s.name, expires
+            ),
+        ),
+        Some((_, expires)) => Check::pass(&name, format!("logged in until {}", expires)),
+        None => Check::pass(&name, "logged in, session cookies"),
+    }
+}
+
+/// 配置的请求与未配置时默认api的host，如`http://dict.youdao.com/`。按service与host排序去重
+pub fn configured_hosts(config: &Config) -> Vec<(&'static str, String)> {
+    let mut hosts = BTreeSet::new();
+    for (service, app) in apps(config) {
+        let requests = app.get_requests();
+        let mut urls = requests
+            .map(|reqs| reqs.values().map(|r| r.get_url()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let defaults = match service {
+            "youdao" => vec![
+                ("lookup", youdao_client::DEFAULT_LOOKUP_URL),
+                ("add-word", youdao_client::DEFAULT_ADD_WORD_URL),
+                ("delete-word", youdao_client::DEFAULT_DELETE_WORD_URL),
+                ("get-books", youdao_client::DEFAULT_GET_BOOKS_URL),
+                (
+                    "update-word-book",
+                    youdao_client::DEFAULT_UPDATE_WORD_BOOK_URL,
+                ),
+            ],
+            _ => vec![],
+        };
+        urls.extend(
+            defaults
+                .into_iter()
+                .filter(|(name, _)| requests.is_none_or(|reqs| !reqs.contains_key(*name)))
+                .map(|(_, url)| url),
+        );
+        if service == "eudic" {
+            urls.push(app.get_api_url().unwrap_or(eudic_client::DEFAULT_API_URL));
+        }
+        if service == "maimemo" && app.get_api_token().is_some() {
+            urls.push(
+                app.get_api_url()
+                    .unwrap_or(maimemo_open_client::DEFAULT_API_URL),
+            );
+        }
+        for url in urls {
+            match Url::parse(url) {
+                Ok(url) if url.has_host() => {
+                    hosts.insert((service, url.origin().ascii_serialization() + "/"));
+                }
+                _ => warn!("skipped invalid {} url: {}", service, url),
+            }
+        }
+    }
+    hosts.into_iter().collect()
+}
+
+/// 通过HEAD请求检查host的dns与连接。任何http响应都表示可访问
+pub async fn check_host(context: &HttpContext, service: &str, host: &str) -> Check {
+    let name = format!("{} {}", service, host);
+    let resp = client::send_unconfigured(context, "doctor", Method::HEAD, host, |b| {
+        b.timeout(Duration::from_secs(HOST_TIMEOUT_SECS))
+    })
+    .await;
+    match resp {
+        Ok(resp) => Check::pass(&name, format!("reachable, status {}", resp.status())),
+        Err(e) if DictError::classify(&e) == Some(DictError::Offline) => {
+            Check::warn(&name, "skipped in offline mode")
+        }
+        Err(e) => Check::fail(&name, format!("unreachable: {}", e)),
+    }
+}
+
+/// 使用保存的cookies获取单词数量验证youdao的登录状态，不会登录
+pub async fn check_youdao_session(config: AppConfig, context: Arc<HttpContext>) -> Check {
+    let name = "youdao session";
+    if context.is_offline() {
+        return Check::warn(name, "skipped in offline mode");
+    }
+    let client = match YoudaoClient::with_context(config, context) {
+        Ok(client) => client,
+        Err(e) => return Check::fail(name, e),
+    };
+    if !client.has_logged() {
+        return Check::warn(name, "not logged in");
+    }
+    match client.get_words_total().await {
+        Ok(total) => Check::pass(name, format!("logged in, {} words", total)),
+        Err(e) => Check::fail(name, e),
+    }
+}
+
+/// 使用保存的cookies获取一个notepad验证maimemo的登录状态，不会登录。
+/// 配置api_token时获取notepad list验证token
+pub async fn check_maimemo_session(config: AppConfig, context: Arc<HttpContext>) -> Check {
+    let name = "maimemo session";
+    if context.is_offline() {
+        return Check::warn(name, "skipped in offline mode");
+    }
+    if config.get_api_token().is_some() {
+        let result = match MaimemoOpenClient::with_context(config, context) {
+            Ok(client) => client.list_notepads().await,
+            Err(e) => Err(e),
+        };
+        return match result {
+            Ok(notepads) => Check::pass(
+                name,
+                format!("api_token is valid, {} notepads", notepads.len()),
+            ),
+            Err(e) => Check::fail(name, e),
+        };
+    }
+    let mut client = match MaimemoClient::with_context(config, context) {
+        Ok(client) => client,
+        Err(e) => return Check::fail(name, e),
+    };
+    if !client.has_logged() {
+        return Check::warn(name, "not logged in");
+    }
+    let query = NotepadQuery {
+        limit: Some(1),
+        ..Default::default()
+    };
+    match client.get_notepad_list(&query).await {
+        Ok(_) => Check::pass(name, "logged in"),
+        Err(e) => Check::fail(name, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::{MockResponse, MockServer};
+
+    fn statuses(checks: &[Check]) -> Vec<(&str, CheckStatus)> {
+        checks.iter().map(|c| (c.name.as_str(), c.status)).collect()
+    }
+
+    fn write_cookies(path: &Path, cookies: &[&str]) {
+        let mut cookie_store = CookieStore::default();
+        for (url, cookie) in cookies.iter().filter_map(|c| c.split_once(' ')) {
+            let url = Url::parse(url).unwrap();
+            let cookie = cookie::Cookie::parse(cookie).unwrap();
+            cookie_store.insert_raw(&cookie, &url).unwrap();
+        }
+        let mut data = vec![];
+        cookie_store.save_json(&mut data).unwrap();
+        fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn config_and_paths() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("dict-config.yml");
+        let path = path.to_str().unwrap();
+        fs::write(path, "youdao:\n  password: secret\n  - broken").unwrap();
+        let (check, config) = check_config_file(path);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(!check.detail.contains("secret"), "{}", check.detail);
+        assert!(config.is_none());
+
+        fs::write(
+            path,
+            "data_dir: .
+youdao:
+  username: a
+  password: b
+  cookie_path: youdao-cookies.json
+  dictionary_path: youdao.json
+maimemo:
+  username: a
+  password: b
+  dictionary_path: missing/maimemo.json
+",
+        )
+        .unwrap();
+        let (check, config) = check_config_file(path);
+        assert_eq!(check.status, CheckStatus::Pass);
+        let config = config.unwrap();
+        fs::write(dir.path().join("youdao.json"), "[]").unwrap();
+        assert_eq!(
+            statuses(&check_paths(&config)),
+            vec![
+                ("youdao dictionary_path", CheckStatus::Pass),
+                ("youdao cookie_path", CheckStatus::Pass),
+                ("maimemo dictionary_path", CheckStatus::Fail),
+            ]
+        );
+        // 检查时不会留下文件
+        assert!(!dir.path().join("youdao-cookies.json").exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn login_cookies() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("dict-config.yml");
+        fs::write(
+            &path,
+            "data_dir: .
+youdao:
+  username: a
+  password: b
+  cookie_path: youdao-cookies.json
+  dictionary_path: youdao.json
+maimemo:
+  username: a
+  password: b
+  cookie_path: maimemo-cookies.json
+  dictionary_path: maimemo.json
+",
+        )
+        .unwrap();
+        let config = Config::from_yaml_file(path.to_str().unwrap())?;
+        let check = |config: &Config| {
+            statuses(&check_cookies(config))
+                .iter()
+                .map(|c| c.1)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(check(&config), vec![CheckStatus::Warn, CheckStatus::Warn]);
+
+        let youdao = dir.path().join("youdao-cookies.json");
+        let maimemo = dir.path().join("maimemo-cookies.json");
+        write_cookies(
+            &youdao,
+            &[
+                "https://youdao.com/ DICT_PERS=1; Domain=youdao.com; Max-Age=172800",
+                "https://youdao.com/ OUTFOX_SEARCH_USER_ID=1; Domain=youdao.com; Max-Age=3600",
+            ],
+        );
+        fs::write(&maimemo, "{broken").unwrap();
+        let checks = check_cookies(&config);
+        assert_eq!(
+            statuses(&checks),
+            vec![
+                ("youdao cookies", CheckStatus::Warn),
+                ("maimemo cookies", CheckStatus::Fail),
+            ]
+        );
+        assert!(checks[0]
+            .detail
+            .contains("OUTFOX_SEARCH_USER_ID expires at"));
+        // 损坏的文件不会被移动
+        assert!(maimemo.exists());
+
+        write_cookies(
+            &youdao,
+            &["https://youdao.com/ DICT_PERS=1; Domain=youdao.com; Max-Age=172800"],
+        );
+        write_cookies(
+            &maimemo,
+            &["https://www.maimemo.com/ userToken=1; Max-Age=172800"],
+        );
+        let checks = check_cookies(&config);
+        assert_eq!(check(&config), vec![CheckStatus::Warn, CheckStatus::Pass]);
+        assert!(
+            checks[0].detail.contains("OUTFOX_SEARCH_USER_ID"),
+            "{}",
+            checks[0].detail
+        );
+        assert!(checks[1].detail.starts_with("logged in until"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hosts_and_sessions() -> Result<(), String> {
+        let server = MockServer::start(|req| {
+            if req.method == "HEAD" {
+                MockResponse::new(404)
+            } else {
+                mock_server::recorded_response(req)
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = server.write_fixture_config(dir.path());
+        let config = || Config::from_yaml_file(&path).unwrap();
+        let hosts = configured_hosts(&config());
+        assert_eq!(
+            hosts,
+            vec![
+                ("maimemo", "http://www.maimemo.com/".to_string()),
+                ("youdao", "http://account.youdao.com/".to_string()),
+                ("youdao", "http://dict.youdao.com/".to_string()),
+                ("youdao", "http://logindict.youdao.com/".to_string()),
+            ]
+        );
+        let context = Arc::new(HttpContext::from_config(&config().http())?);
+        let check = check_host(&context, hosts[0].0, &hosts[0].1).await;
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.detail);
+        assert_eq!(check.detail, "reachable, status 404 Not Found");
+
+        let check = check_youdao_session(config().youdao(), context.clone()).await;
+        assert_eq!(check.status, CheckStatus::Warn);
+        let mut client = YoudaoClient::with_context(config().youdao(), context.clone())?;
+        client.login().await?;
+        client.save().await?;
+        let check = check_youdao_session(config().youdao(), context.clone()).await;
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.detail);
+
+        let offline = Arc::new(HttpContext::from_config(&config().http())?.with_offline(true));
+        let check = check_host(&offline, hosts[0].0, &hosts[0].1).await;
+        assert_eq!(check.status, CheckStatus::Warn);
+        let check = check_maimemo_session(config().maimemo(), offline).await;
+        assert_eq!(check.status, CheckStatus::Warn);
+        Ok(())
+    }
+
+    #[test]
+    fn maimemo_open_api() {
+        let config: Config = serde_yaml::from_str(
+            "
+maimemo:
+  cookie_path: maimemo-cookies.json
+  dictionary_path: maimemo.json
+  api_token: test-token
+",
+        )
+        .unwrap();
+        assert_eq!(
+            check_maimemo_backend(config.maimemo.as_ref().unwrap()).detail,
+            format!(
+                "open api (api_token): {}",
+                maimemo_open_client::DEFAULT_API_URL
+            )
+        );
+        // 不需要cookies
+        assert!(check_cookies(&config).is_empty());
+        assert_eq!(
+            configured_hosts(&config),
+            vec![("maimemo", "https://open.maimemo.com/".to_string())]
+        );
+    }
+}
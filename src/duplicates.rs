@@ -0,0 +1,220 @@
+//! maimemo notepads中重复的单词。
+//!
+//! notepad内容每行一个单词，忽略空行与`#`开头的章节行
+use crate::client::maimemo_client::Notepad;
+use crate::lemma::normalize_word;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// 在多个notepad中出现的单词
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Duplicate {
+    /// 第一次出现时的写法
+    pub word: String,
+    pub notepads: Vec<NotepadRef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NotepadRef {
+    pub notepad_id: String,
+    pub title: String,
+}
+
+/// 在一个notepad中多次出现的单词
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WithinDuplicate {
+    pub word: String,
+    /// 出现的行号，从1开始
+    pub lines: Vec<usize>,
+}
+
+/// notepad内容中的单词与行号，忽略空行与`#`开头的章节行
+pub fn notepad_words(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, word)| !word.is_empty() && !word.starts_with('#'))
+}
+
+/// 比较单词时使用的key。lemma时使用单词原形比较，同时忽略大小写
+pub fn word_key(word: &str, ignore_case: bool, lemma: bool) -> String {
+    let word = word.trim();
+    if lemma {
+        normalize_word(word).to_lowercase()
+    } else if ignore_case {
+        word.to_lowercase()
+    } else {
+        word.to_string()
+    }
+}
+
+/// 找出在多个notepad中出现的单词，按单词排序。没有内容的notepad被忽略
+pub fn find_duplicates(notepads: &[Notepad], ignore_case: bool, lemma: bool) -> Vec<Duplicate> {
+    let mut words = BTreeMap::<String, Duplicate>::new();
+    for notepad in notepads {
+        let contents = match notepad.get_contents() {
+            Some(contents) => contents,
+            None => {
+                debug!(
+                    "skipped notepad {} without contents",
+                    notepad.get_notepad_id()
+                );
+                continue;
+            }
+        };
+        let mut seen = HashSet::new();
+        for (_, word) in notepad_words(contents) {
+            let key = word_key(word, ignore_case, lemma);
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            words
+                .entry(key)
+                .or_insert_with(|| Duplicate {
+                    word: word.to_string(),
+                    notepads: vec![],
+                })
+                .notepads
+                .push(NotepadRef {
+                    notepad_id: notepad.get_notepad_id().to_string(),
+                    title: notepad.get_title().to_string(),
+                });
+        }
+    }
+    words
+        .into_values()
+        .filter(|d| d.notepads.len() > 1)
+        .collect()
+}
+
+/// 找出在contents中多次出现的单词，按第一次出现的顺序
+pub fn find_within(contents: &str, ignore_case: bool, lemma: bool) -> Vec<WithinDuplicate> {
+    let mut words = Vec::<WithinDuplicate>::new();
+    let mut indexes = BTreeMap::new();
+    for (line, word) in notepad_words(contents) {
+        let i = *indexes
+            .entry(word_key(word, ignore_case, lemma))
+            .or_insert_with(|| {
+                words.push(WithinDuplicate {
+                    word: word.to_string(),
+                    lines: vec![],
+                });
+                words.len() - 1
+            });
+        words[i].lines.push(line);
+    }
+    words.retain(|d| d.lines.len() > 1);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notepad(id: &str, title: &str, contents: Option<&str>) -> Notepad {
+        serde_json::from_value(serde_json::json!({
+            "notepad_id": id,
+            "title": title,
+            "contents": contents,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn across_notepads() {
+        let notepads = vec![
+            notepad(
+                "1",
+                "GRE",
+                Some("# chapter 1\nApple\nrun\nrun\n\nbenevolent"),
+            ),
+            notepad("2", "TOEFL", Some("# Apple\napple\nrun\n")),
+            notepad("3", "empty", None),
+            notepad("4", "news", Some("  benevolent  \nrun")),
+        ];
+        let words = |dups: Vec<Duplicate>| {
+            dups.into_iter()
+                .map(|d| {
+                    let ids = d.notepads.into_iter().map(|n| n.notepad_id);
+                    (d.word, ids.collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            words(find_duplicates(&notepads, false, false)),
+            vec![
+                (
+                    "benevolent".to_string(),
+                    vec!["1".to_string(), "4".to_string()]
+                ),
+                (
+                    "run".to_string(),
+                    vec!["1".to_string(), "2".to_string(), "4".to_string()]
+                ),
+            ]
+        );
+        let ignored = find_duplicates(&notepads, true, false);
+        assert_eq!(ignored[0].word, "Apple");
+        assert_eq!(ignored[0].notepads[1].title, "TOEFL");
+        assert_eq!(ignored.len(), 3);
+    }
+
+    #[test]
+    fn within_notepad() {
+        let contents = "# run\nrun\nApple\n\nrun\napple\n# run\nrun";
+        assert_eq!(
+            find_within(contents, false, false),
+            vec![WithinDuplicate {
+                word: "run".to_string(),
+                lines: vec![2, 5, 8],
+            }]
+        );
+        let dups = find_within(contents, true, false);
+        assert_eq!(dups.len(), 2);
+        assert_eq!(dups[1].word, "Apple");
+        assert_eq!(dups[1].lines, vec![3, 6]);
+        assert!(find_within("a\nb", true, false).is_empty());
+    }
+
+    #[cfg(feature = "lemma")]
+    #[test]
+    fn lemma_duplicates() {
+        let notepads = vec![
+            notepad(
+                "1",
+                "kindle",
+                Some(
+                    "running
+Children
+单词",
+                ),
+            ),
+            notepad(
+                "2",
+                "youdao",
+                Some(
+                    "run
+child
+单词
+runner",
+                ),
+            ),
+        ];
+        let dups = find_duplicates(&notepads, false, true);
+        assert_eq!(
+            dups.iter().map(|d| d.word.as_str()).collect::<Vec<_>>(),
+            vec!["Children", "running", "单词"]
+        );
+        let dups = find_within(
+            "ran
+Run
+runs
+runner",
+            false,
+            true,
+        );
+        assert_eq!(dups.len(), 1);
+        assert_eq!(dups[0].lines, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,347 @@
+//! 将clients返回的错误字符串识别为常见的错误，输出用户可读的信息与提示
+//!
+//! clients的错误仍为`String`，错误的标记定义在这里并由产生错误的地方使用，保证能被[`DictError::classify`]识别
+use crate::client::{
+    context::{OFFLINE_MODE, READ_ONLY_MODE},
+    RESPONSE_TOO_LARGE,
+};
+use crate::msg;
+use crate::redact;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 未登录或登录过期时的错误
+pub const NOT_LOGGED_IN: &str = "not logged in";
+
+/// 登录失败时错误的标记
+pub const LOGIN_FAILED: &str = "login failed";
+
+/// 服务拒绝了用户名或密码时错误的标记
+pub const WRONG_CREDENTIALS: &str = "wrong username or password";
+
+/// 需要在浏览器中完成验证码才能登录时错误的标记
+pub const LOGIN_CAPTCHA_REQUIRED: &str = "login requires captcha";
+
+/// 登录过于频繁被拒绝时错误的标记
+pub const LOGIN_RATE_LIMITED: &str = "too many login attempts";
+
+/// maimemo拒绝了保存notepad时的验证码时的errorCode
+pub const WRONG_CAPTCHA: &str = "captcha_error";
+
+/// 配置中没有请求时的错误前缀，之后为请求名称
+pub const REQUEST_CONFIG_MISSING: &str = "not found req config with req_name: ";
+
+/// response status不在`ok_status`中时的错误前缀，之后为status
+pub const STATUS_ERROR: &str = "Response code error: ";
+
+/// 通过ctrl-c取消操作时的错误
+pub const CANCELLED: &str = "cancelled by ctrl-c";
+
+/// 用户没有在`--prompt-timeout`内输入时错误的标记，见[`crate::prompt::PromptTimeout`]
+pub const PROMPT_TIMED_OUT: &str = "prompt timed out";
+
+/// 连接失败或超时等网络错误的标记，由[`crate::client::context::request_error`]添加
+pub const NETWORK_ERROR: &str = "network error";
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// 设置[`pretty`]是否输出完整的错误
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// 可以识别的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DictError {
+    NotLoggedIn,
+    LoginFailed,
+    WrongCredentials,
+    LoginCaptcha,
+    LoginRateLimited,
+    WrongCaptcha,
+    ConfigMissing { req_name: String },
+    Offline,
+    ReadOnly,
+    Network,
+    HttpStatus { status: String },
+    ResponseTooLarge,
+    Cancelled,
+    PromptTimeout,
+}
+
+impl DictError {
+    /// 从错误字符串中识别错误。无法识别时返回None
+    pub fn classify(e: &str) -> Option<Self> {
+        if e.contains(CANCELLED) {
+            return Some(Self::Cancelled);
+        }
+        if e.contains(PROMPT_TIMED_OUT) {
+            return Some(Self::PromptTimeout);
+        }
+        if e.contains(OFFLINE_MODE) {
+            return Some(Self::Offline);
+        }
+        if e.contains(READ_ONLY_MODE) {
+            return Some(Self::ReadOnly);
+        }
+        if e.contains(NETWORK_ERROR) {
+            return Some(Self::Network);
+        }
+        if let Some((_, rest)) = e.split_once(REQUEST_CONFIG_MISSING) {
+            let req_name = rest.split_whitespace().next().unwrap_or_default();
+            return Some(Self::ConfigMissing {
+                req_name: req_name.to_string(),
+            });
+        }
+        if e.contains(RESPONSE_TOO_LARGE) {
+            return Some(Self::ResponseTooLarge);
+        }
+        if let Some((_, rest)) = e.split_once(STATUS_ERROR) {
+            let status = rest.split([',', '.']).next().unwrap_or_default();
+            return Some(Self::HttpStatus {
+                status: status.trim().to_string(),
+            });
+        }
+        if e.contains(WRONG_CREDENTIALS) {
+            return Some(Self::WrongCredentials);
+        }
+        if e.contains(LOGIN_CAPTCHA_REQUIRED) {
+            return Some(Self::LoginCaptcha);
+        }
+        if e.contains(LOGIN_RATE_LIMITED) {
+            return Some(Self::LoginRateLimited);
+        }
+        if e.contains(WRONG_CAPTCHA) {
+            return Some(Self::WrongCaptcha);
+        }
+        if e.contains(LOGIN_FAILED) {
+            return Some(Self::LoginFailed);
+        }
+        if e.contains(NOT_LOGGED_IN) {
+            return Some(Self::NotLoggedIn);
+        }
+        None
+    }
+
+    /// 机器可读的名称，如[`crate::events::Event::Error`]中的kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NotLoggedIn => "not_logged_in",
+            Self::LoginFailed => "login_failed",
+            Self::WrongCredentials => "wrong_credentials",
+            Self::LoginCaptcha => "login_captcha",
+            Self::LoginRateLimited => "login_rate_limited",
+            Self::WrongCaptcha => "wrong_captcha",
+            Self::ConfigMissing { .. } => "config_missing",
+            Self::Offline => "offline",
+            Self::ReadOnly => "read_only",
+            Self::Network => "network",
+            Self::HttpStatus { .. } => "http_status",
+            Self::ResponseTooLarge => "response_too_large",
+            Self::Cancelled => "cancelled",
+            Self::PromptTimeout => "prompt_timeout",
+        }
+    }
+
+    /// 一行用户可读的信息
+    pub fn message(&self) -> String {
+        match self {
+            Self::NotLoggedIn => msg!("dict_error.not_logged_in"),
+            Self::LoginFailed => msg!("dict_error.login_failed"),
+            Self::WrongCredentials => msg!("dict_error.wrong_credentials"),
+            Self::LoginCaptcha => msg!("dict_error.login_captcha"),
+            Self::LoginRateLimited => msg!("dict_error.login_rate_limited"),
+            Self::WrongCaptcha => msg!("dict_error.wrong_captcha"),
+            Self::ConfigMissing { req_name } => msg!("dict_error.config_missing", req_name),
+            Self::Offline => msg!("dict_error.offline"),
+            Self::ReadOnly => msg!("dict_error.read_only"),
+            Self::Network => msg!("dict_error.network"),
+            Self::HttpStatus { status } => msg!("dict_error.http_status", status),
+            Self::ResponseTooLarge => msg!("dict_error.response_too_large"),
+            Self::Cancelled => msg!("dict_error.cancelled"),
+            Self::PromptTimeout => msg!("dict_error.prompt_timeout"),
+        }
+    }
+
+    /// 用户可以采取的操作
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::NotLoggedIn => Some(msg!("hint.relogin")),
+            Self::LoginFailed => Some(msg!("hint.check_credentials")),
+            Self::WrongCredentials => Some(msg!("hint.md5_password")),
+            Self::LoginCaptcha => Some(msg!("hint.browser_captcha")),
+            Self::LoginRateLimited => Some(msg!("hint.login_later")),
+            Self::WrongCaptcha => Some(msg!("hint.captcha_again")),
+            Self::ConfigMissing { req_name } => Some(msg!("hint.add_request", req_name)),
+            Self::Offline => Some(msg!("hint.disable_offline")),
+            Self::ReadOnly => Some(msg!("hint.disable_read_only")),
+            Self::Network => Some(msg!("hint.check_network")),
+            Self::HttpStatus { status } if status.starts_with("429") => {
+                Some(msg!("hint.rate_limited"))
+            }
+            Self::HttpStatus { .. } => None,
+            Self::ResponseTooLarge => Some(msg!("hint.max_response_bytes")),
+            Self::Cancelled => Some(msg!("hint.run_again")),
+            Self::PromptTimeout => Some(msg!("hint.prompt_timeout")),
+        }
+    }
+}
+
+/// 输出错误e：可以识别时为信息与提示，verbose时再输出完整的错误；否则原样输出。
+/// 完整的错误中敏感的值同日志一样被隐藏，见[`redact::redact_text`]
+pub fn render(e: &str, verbose: bool) -> String {
+    let err = match DictError::classify(e) {
+        Some(err) => err,
+        None => return redact::redact_text(e),
+    };
+    let mut out = err.message();
+    if let Some(hint) = err.hint() {
+        out.push('\n');
+        out.push_str(&msg!("hint", hint));
+    }
+    if verbose {
+        out.push('\n');
+        out.push_str(&msg!("caused_by", redact::redact_text(e)));
+    }
+    out
+}
+
+/// 使用[`set_verbose`]设置的verbose输出错误，见[`render`]
+pub fn pretty(e: &str) -> Pretty<'_> {
+    Pretty(e)
+}
+
+pub struct Pretty<'a>(&'a str);
+
+impl fmt::Display for Pretty<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&render(self.0, VERBOSE.load(Ordering::Relaxed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每个variant的一个例子，增加variant时这里无法编译
+    fn examples() -> Vec<(DictError, &'static str)> {
+        let errors = vec![
+            (DictError::NotLoggedIn, "not logged in"),
+            (
+                DictError::LoginFailed,
+                "youdao login error: login failed. not found login cookies",
+            ),
+            (
+                DictError::WrongCredentials,
+                "youdao login error: login failed: wrong username or password. 用户名或密码错误",
+            ),
+            (
+                DictError::LoginCaptcha,
+                "youdao login error: login failed: login requires captcha",
+            ),
+            (
+                DictError::LoginRateLimited,
+                "youdao login error: login failed: too many login attempts. 请稍后再试",
+            ),
+            (
+                DictError::WrongCaptcha,
+                "upload error: save notepad failed: captcha_error",
+            ),
+            (
+                DictError::ConfigMissing {
+                    req_name: "notepad-save".to_string(),
+                },
+                "not found req config with req_name: notepad-save",
+            ),
+            (
+                DictError::Offline,
+                "offline mode: network access is disabled by --offline or DICT_OFFLINE. request: lookup",
+            ),
+            (
+                DictError::ReadOnly,
+                "upload error: read-only mode: modifications are disabled by --read-only or read_only in config. operation: notepad-save",
+            ),
+            (
+                DictError::Network,
+                "network error: reqwest::Error { kind: Request, url: Url { .. }, source: hyper::Error(Connect, ..) }",
+            ),
+            (
+                DictError::HttpStatus {
+                    status: "429 Too Many Requests".to_string(),
+                },
+                "Response code error: 429 Too Many Requests, retry after: 3s, body: busy",
+            ),
+            (
+                DictError::ResponseTooLarge,
+                "response body too large: 2048 > 1024 bytes",
+            ),
+            (
+                DictError::Cancelled,
+                "youdao refresh words error: cancelled by ctrl-c",
+            ),
+            (
+                DictError::PromptTimeout,
+                "read captcha error: prompt timed out after 30s",
+            ),
+        ];
+        for (err, _) in &errors {
+            match err {
+                DictError::NotLoggedIn
+                | DictError::LoginFailed
+                | DictError::WrongCredentials
+                | DictError::LoginCaptcha
+                | DictError::LoginRateLimited
+                | DictError::WrongCaptcha
+                | DictError::ConfigMissing { .. }
+                | DictError::Offline
+                | DictError::ReadOnly
+                | DictError::Network
+                | DictError::HttpStatus { .. }
+                | DictError::ResponseTooLarge
+                | DictError::Cancelled
+                | DictError::PromptTimeout => {}
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn all_variants() {
+        for (err, e) in examples() {
+            assert_eq!(DictError::classify(e).as_ref(), Some(&err), "{}", e);
+            assert!(!err.message().is_empty(), "{:?}", err);
+            assert!(!err.kind().is_empty(), "{:?}", err);
+            if !matches!(err, DictError::HttpStatus { .. }) {
+                assert!(err.hint().is_some_and(|h| !h.is_empty()), "{:?}", err);
+            }
+        }
+        let status = DictError::HttpStatus {
+            status: "500 Internal Server Error".to_string(),
+        };
+        assert_eq!(status.hint(), None);
+        assert_eq!(DictError::classify("parse error: eof"), None);
+    }
+
+    #[test]
+    fn render_errors() {
+        let e = "maimemo upload error: not logged in";
+        assert_eq!(
+            render(e, false),
+            "not logged in\nhint: run with -r to re-login"
+        );
+        assert_eq!(
+            render(e, true),
+            format!(
+                "not logged in\nhint: run with -r to re-login\ncaused by: {}",
+                e
+            )
+        );
+        assert_eq!(render("parse error: eof", true), "parse error: eof");
+        // 无法识别的错误同样隐藏敏感的值
+        assert_eq!(
+            render("parse error near password: hunter22", false),
+            "parse error near password: hu***(8)"
+        );
+        assert!(render("not logged in. token=abcdef", true).ends_with("token=ab***(6)"));
+    }
+}
@@ -0,0 +1,196 @@
+//! 长时间操作中机器可读的事件，用于GUI等包装程序。
+//!
+//! 事件通过[`HttpContext::with_events`](crate::client::context::HttpContext::with_events)
+//! 设置的[`EventSink`]输出，人类可读的输出仍然在终端中。[`JsonLinesSink`]每行输出一个json：
+//!
+//! ```text
+//! {"version":1,"event":"page_fetched","offset":0,"count":1000}
+//! ```
+use crate::{error::DictError, redact};
+use serde::Serialize;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+
+/// 事件格式的版本，字段不兼容地修改时增加
+pub const EVENTS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// 开始从service获取全部数据
+    RefreshStarted { service: String },
+    /// 获取了youdao单词本的一页
+    PageFetched { offset: usize, count: usize },
+    /// 获取了maimemo notepad的内容，包括使用缓存的
+    NotepadFetched { id: String },
+    /// 需要用户输入验证码，图片已写入image_path
+    CaptchaRequired { image_path: String },
+    /// 本地数据已保存
+    SaveSucceeded,
+    /// `dict run --watch`无法自动输入验证码，待添加到notepad的words个单词已写入path
+    UploadStaged {
+        notepad: String,
+        path: String,
+        words: usize,
+    },
+    /// kind为[`DictError::kind`]，无法识别时为`other`。message中敏感的值已被隐藏
+    Error { kind: String, message: String },
+}
+
+impl Event {
+    pub fn error(e: &str) -> Self {
+        Self::Error {
+            kind: DictError::classify(e)
+                .map_or("other", |err| err.kind())
+                .to_string(),
+            message: redact::redact_text(e),
+        }
+    }
+}
+
+/// 带版本的事件，即实际输出的json
+#[derive(Debug, Serialize)]
+pub struct EventRecord<'a> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub event: &'a Event,
+}
+
+impl<'a> EventRecord<'a> {
+    pub fn new(event: &'a Event) -> Self {
+        Self {
+            version: EVENTS_VERSION,
+            event,
+        }
+    }
+}
+
+/// 接收事件。输出失败时不影响正在进行的操作
+pub trait EventSink: fmt::Debug + Send + Sync {
+    fn emit(&self, event: &Event);
+}
+
+/// 每行输出一个[`EventRecord`]的json，每个事件后flush
+pub struct JsonLinesSink {
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLinesSink {
+    pub fn new(output: impl Write + Send + 'static) -> Self {
+        Self {
+            output: Mutex::new(Box::new(output)),
+        }
+    }
+
+    /// 创建或清空path
+    pub fn create(path: &str) -> Result<Self, String> {
+        File::create(path)
+            .map(Self::new)
+            .map_err(|e| format!("create events file error: {}, path: {}", e, path))
+    }
+
+    /// 输出到已打开的文件描述符fd，如由父进程传入的管道。通过`/dev/fd`打开，不会关闭fd
+    #[cfg(unix)]
+    pub fn from_fd(fd: i32) -> Result<Self, String> {
+        if fd <= 2 {
+            return Err(format!("invalid events fd: {}. 0-2 are stdio", fd));
+        }
+        let path = format!("/dev/fd/{}", fd);
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map(Self::new)
+            .map_err(|e| format!("open events fd {} error: {}", fd, e))
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_fd(fd: i32) -> Result<Self, String> {
+        Err(format!(
+            "events fd {} is only supported on unix. please use --events-file",
+            fd
+        ))
+    }
+}
+
+impl fmt::Debug for JsonLinesSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLinesSink").finish_non_exhaustive()
+    }
+}
+
+impl EventSink for JsonLinesSink {
+    fn emit(&self, event: &Event) {
+        let line = match serde_json::to_string(&EventRecord::new(event)) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("serialize event {:?} error: {}", event, e);
+                return;
+            }
+        };
+        let mut output = self.output.lock().unwrap();
+        if let Err(e) = writeln!(output, "{}", line).and_then(|_| output.flush()) {
+            warn!("write event error: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_lines() {
+        let out = Shared::default();
+        let sink = JsonLinesSink::new(out.clone());
+        sink.emit(&Event::RefreshStarted {
+            service: "youdao".to_string(),
+        });
+        sink.emit(&Event::PageFetched {
+            offset: 1000,
+            count: 3,
+        });
+        sink.emit(&Event::SaveSucceeded);
+        sink.emit(&Event::error("maimemo upload error: not logged in"));
+        let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            vec![
+                r#"{"version":1,"event":"refresh_started","service":"youdao"}"#,
+                r#"{"version":1,"event":"page_fetched","offset":1000,"count":3}"#,
+                r#"{"version":1,"event":"save_succeeded"}"#,
+                r#"{"version":1,"event":"error","kind":"not_logged_in","message":"maimemo upload error: not logged in"}"#,
+            ]
+        );
+        assert_eq!(
+            Event::error("parse error"),
+            Event::Error {
+                kind: "other".to_string(),
+                message: "parse error".to_string()
+            }
+        );
+        // 包装程序可能记录事件，不能输出cookie与token
+        match Event::error("request error: token=abc123 cookie: JSESSIONID=s3cr3t") {
+            Event::Error { message, .. } => {
+                assert!(!message.contains("abc123"), "{}", message);
+                assert!(!message.contains("s3cr3t"), "{}", message);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use crate::client::youdao_client::WordItem;
+use crate::trans::{join_senses, parse_trans, parts_of_speech};
+use chrono::{TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// anki导入时默认添加的tag
+const ANKI_TAG: &str = "youdao";
+
+/// 将words格式化为anki可导入的tsv文本。
+///
+/// 每行字段依次为：word, trans, phonetic, tags, pos。trans中的换行被转换为`<br>`，
+/// 所有字段都会被html转义。tags由book_name（空格替换为`_`）与`youdao`组成，
+/// pos为从trans中识别的词性，见[`parts_of_speech`]
+///
+/// 如果deck不为空则添加`#deck:`头让anki导入到指定deck
+pub fn format_anki_tsv(words: &[WordItem], deck: Option<&str>) -> String {
+    let mut out = String::new();
+    out.push_str("#separator:tab\n");
+    out.push_str("#html:true\n");
+    if let Some(deck) = deck {
+        out.push_str("#deck:");
+        out.push_str(&sanitize_field(deck));
+        out.push('\n');
+    }
+    out.push_str("#tags column:4\n");
+    for word in words {
+        let back = escape_html(&word.trans.replace("\r\n", "\n"))
+            .split('\n')
+            .collect::<Vec<_>>()
+            .join("<br>");
+        let fields = [
+            escape_html(&word.word),
+            back,
+            escape_html(&word.phonetic),
+            anki_tags(&word.book_name),
+            escape_html(&parts_of_speech(&parse_trans(&word.trans))),
+        ];
+        let line = fields
+            .iter()
+            .map(|f| sanitize_field(f))
+            .collect::<Vec<_>>()
+            .join("\t");
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// 由book_name生成tags，空格分隔
+pub fn anki_tags(book_name: &str) -> String {
+    let book_tag = book_name.split_whitespace().collect::<Vec<_>>().join("_");
+    if book_tag.is_empty() {
+        ANKI_TAG.to_string()
+    } else {
+        format!("{} {}", book_tag, ANKI_TAG)
+    }
+}
+
+/// 转义html特殊字符
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// [`WordTemplate`]中可用的占位符
+pub const TEMPLATE_PLACEHOLDERS: &[&str] = &[
+    "word",
+    "trans",
+    "trans_first_line",
+    "phonetic",
+    "book",
+    "date",
+    "item_id",
+    "first_seen",
+    "last_seen",
+    "pos",
+    "first_sense",
+    "senses",
+];
+
+/// 默认模板，每行仅输出单词
+pub const DEFAULT_TEMPLATE: &str = "{word}";
+
+/// `--detail`时输出单词的模板，仅输出第一个释义
+pub const DETAIL_TEMPLATE: &str =
+    "{word}\\t{phonetic}\\t{first_sense}\\t{first_seen}\\t{last_seen}";
+
+/// `--detail --all-senses`时输出单词的模板
+pub const DETAIL_ALL_SENSES_TEMPLATE: &str =
+    "{word}\\t{phonetic}\\t{senses}\\t{first_seen}\\t{last_seen}";
+
+/// `--detail`时使用的模板，all_senses时输出所有释义
+pub fn detail_template(all_senses: bool) -> &'static str {
+    if all_senses {
+        DETAIL_ALL_SENSES_TEMPLATE
+    } else {
+        DETAIL_TEMPLATE
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Placeholder(&'static str),
+}
+
+/// 输出单词的模板，如`{word}\t{trans_first_line}`。
+///
+/// `{name}`替换为[`TEMPLATE_PLACEHOLDERS`]中对应的字段，支持`\t`, `\n`, `\\`, `\{`, `\}`转义。
+/// date为modified_time的utc日期`%Y-%m-%d`，first_seen与last_seen同样为日期，见[`WordItem::first_seen`]。
+/// pos、first_sense与senses为从trans中解析的词性与释义，见[`crate::trans::parse_trans`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl Default for WordTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TEMPLATE).expect("default template")
+    }
+}
+
+impl WordTemplate {
+    /// 解析模板，未知的占位符或转义返回error
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some(c @ ('\\' | '{' | '}')) => text.push(c),
+                    Some(c) => {
+                        return Err(format!(
+                            "unknown escape `\\{}` in template: {}",
+                            c, template
+                        ))
+                    }
+                    None => return Err(format!("trailing `\\` in template: {}", template)),
+                },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed `{{` in template: {}", template)),
+                        }
+                    }
+                    let placeholder = TEMPLATE_PLACEHOLDERS
+                        .iter()
+                        .find(|p| **p == name)
+                        .ok_or_else(|| {
+                            format!(
+                                "unknown placeholder {{{}}} in template, valid placeholders: {}",
+                                name,
+                                TEMPLATE_PLACEHOLDERS
+                                    .iter()
+                                    .map(|p| format!("{{{}}}", p))
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            )
+                        })?;
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(TemplatePart::Placeholder(placeholder));
+                }
+                '}' => return Err(format!("unmatched `}}` in template: {}", template)),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+        Ok(Self { parts })
+    }
+
+    /// 用word的字段替换占位符，不包含结尾的换行
+    pub fn render(&self, word: &WordItem) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => out.push_str(text),
+                TemplatePart::Placeholder(name) => match *name {
+                    "word" => out.push_str(&word.word),
+                    "trans" => out.push_str(&word.trans),
+                    "trans_first_line" => out.push_str(word.trans.lines().next().unwrap_or("")),
+                    "pos" => out.push_str(&parts_of_speech(&parse_trans(&word.trans))),
+                    "first_sense" => {
+                        if let Some(sense) = parse_trans(&word.trans).first() {
+                            out.push_str(&sense.to_string());
+                        }
+                    }
+                    "senses" => out.push_str(&join_senses(&parse_trans(&word.trans), true)),
+                    "phonetic" => out.push_str(&word.phonetic),
+                    "book" => out.push_str(&word.book_name),
+                    "date" => push_date(&mut out, Some(word.modified_time)),
+                    "item_id" => out.push_str(&word.item_id),
+                    "first_seen" => push_date(&mut out, Some(word.first_seen())),
+                    "last_seen" => push_date(&mut out, word.last_seen_in_remote()),
+                    name => unreachable!("unknown placeholder: {}", name),
+                },
+            }
+        }
+        out
+    }
+}
+
+/// 输出毫秒时间戳millis的utc日期`%Y-%m-%d`，None时不输出
+fn push_date(out: &mut String, millis: Option<usize>) {
+    if let Some(date) = millis.and_then(|t| Utc.timestamp_millis_opt(t as i64).single()) {
+        out.push_str(&date.format("%Y-%m-%d").to_string());
+    }
+}
+
+/// 没有单词本的单词所在章节
+pub const UNKNOWN_CHAPTER: &str = "unknown";
+
+/// 将单词分组为maimemo notepad中`#`开头的章节
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChapterBy {
+    /// 不分组
+    None,
+    /// 按modified_time的utc月份`%Y-%m`
+    Month,
+    /// 按单词本名称
+    Book,
+}
+
+impl FromStr for ChapterBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(ChapterBy::None),
+            "month" => Ok(ChapterBy::Month),
+            "book" => Ok(ChapterBy::Book),
+            _ => Err(format!("unsupported chapter by: {}", s)),
+        }
+    }
+}
+
+/// 章节的排序键与名称。月份按时间升序，单词本按名称忽略大小写的字母序，没有单词本的章节在最后
+fn chapter_of(word: &WordItem, by: ChapterBy) -> ((bool, String), String) {
+    match by {
+        ChapterBy::None => ((false, String::new()), String::new()),
+        ChapterBy::Month => {
+            let month = Utc
+                .timestamp_millis_opt(word.modified_time as i64)
+                .single()
+                .map(|date| date.format("%Y-%m").to_string());
+            match month {
+                Some(month) => ((false, month.clone()), month),
+                None => ((true, String::new()), UNKNOWN_CHAPTER.to_string()),
+            }
+        }
+        ChapterBy::Book => {
+            let book = word.book_name.trim();
+            if book.is_empty() {
+                ((true, String::new()), UNKNOWN_CHAPTER.to_string())
+            } else {
+                ((false, book.to_lowercase()), book.to_string())
+            }
+        }
+    }
+}
+
+/// 将words按by分组为章节，每个单词使用template输出一行。
+///
+/// 章节头为`#name`，章节之间空一行，章节内保持words中的顺序。by为[`ChapterBy::None`]时不输出章节头
+pub fn format_chapters(words: &[WordItem], by: ChapterBy, template: &WordTemplate) -> String {
+    let mut chapters = BTreeMap::<_, (String, Vec<&WordItem>)>::new();
+    for word in words {
+        let (key, name) = chapter_of(word, by);
+        chapters
+            .entry(key)
+            .or_insert_with(|| (name, vec![]))
+            .1
+            .push(word);
+    }
+    let mut out = String::new();
+    for (name, words) in chapters.values() {
+        if by != ChapterBy::None {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push('#');
+            out.push_str(name);
+            out.push('\n');
+        }
+        for word in words {
+            out.push_str(&template.render(word));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// tsv字段中不能出现tab与换行
+fn sanitize_field(s: &str) -> String {
+    s.replace('\t', " ").replace('\r', "").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_words() -> Vec<WordItem> {
+        vec![
+            WordItem {
+                item_id: "1".to_string(),
+                book_id: "1".to_string(),
+                book_name: "GRE 3000".to_string(),
+                word: "accommodate".to_string(),
+                trans: "vt. 容纳；使适应\nvi. 适应".to_string(),
+                phonetic: "[əˈkɒmədeɪt]".to_string(),
+                modified_time: 1602900865000,
+                local: None,
+            },
+            WordItem {
+                item_id: "2".to_string(),
+                book_id: "0".to_string(),
+                book_name: "".to_string(),
+                word: "R&D".to_string(),
+                trans: "abbr. <研究与开发>\t\"research\"".to_string(),
+                phonetic: "".to_string(),
+                modified_time: 1602900865000,
+                local: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn anki_tsv() {
+        let tsv = format_anki_tsv(&fixture_words(), None);
+        assert_eq!(
+            tsv,
+            "#separator:tab\n\
+             #html:true\n\
+             #tags column:4\n\
+             accommodate\tvt. 容纳；使适应<br>vi. 适应\t[əˈkɒmədeɪt]\tGRE_3000 youdao\tvt. vi.\n\
+             R&amp;D\tabbr. &lt;研究与开发&gt; &quot;research&quot;\t\tyoudao\tabbr.\n"
+        );
+    }
+
+    #[test]
+    fn anki_tsv_with_deck() {
+        let tsv = format_anki_tsv(&[], Some("English\tWords"));
+        assert_eq!(
+            tsv,
+            "#separator:tab\n#html:true\n#deck:English Words\n#tags column:4\n"
+        );
+    }
+
+    #[test]
+    fn word_template() -> Result<(), String> {
+        let words = fixture_words();
+        let template = WordTemplate::parse("{word}\\t{trans_first_line}")?;
+        assert_eq!(template.render(&words[0]), "accommodate\tvt. 容纳；使适应");
+        assert_eq!(WordTemplate::default().render(&words[1]), "R&D");
+
+        let template = WordTemplate::parse("{item_id}:{book} {date}\\n\\\\\\{word\\}{phonetic}")?;
+        assert_eq!(
+            template.render(&words[0]),
+            "1:GRE 3000 2020-10-17\n\\{word}[əˈkɒmədeɪt]"
+        );
+        assert_eq!(
+            WordTemplate::parse("{trans}")?.render(&words[0]),
+            words[0].trans
+        );
+
+        let template = WordTemplate::parse("{pos}|{first_sense}|{senses}")?;
+        assert_eq!(
+            template.render(&words[0]),
+            "vt. vi.|vt. 容纳；使适应|vt. 容纳；使适应; vi. 适应"
+        );
+        let detail = WordTemplate::parse(detail_template(false))?;
+        assert_eq!(
+            detail.render(&words[0]),
+            "accommodate\t[əˈkɒmədeɪt]\tvt. 容纳；使适应\t2020-10-17\t"
+        );
+        let detail = WordTemplate::parse(detail_template(true))?;
+        assert_eq!(
+            detail.render(&words[0]),
+            "accommodate\t[əˈkɒmədeɪt]\tvt. 容纳；使适应; vi. 适应\t2020-10-17\t"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_word_template() {
+        let e = WordTemplate::parse("{word}\\t{tran}").unwrap_err();
+        assert!(e.contains("unknown placeholder {tran}"), "{}", e);
+        assert!(
+            e.contains(
+                "{word}, {trans}, {trans_first_line}, {phonetic}, {book}, {date}, {item_id}"
+            ),
+            "{}",
+            e
+        );
+        assert!(WordTemplate::parse("{word").is_err());
+        assert!(WordTemplate::parse("word}").is_err());
+        assert!(WordTemplate::parse("\\r").is_err());
+        assert!(WordTemplate::parse("word\\").is_err());
+    }
+
+    #[test]
+    fn chapters() -> Result<(), String> {
+        let mut words = fixture_words();
+        let word = |word: &str, book: &str, modified_time| {
+            let mut item = words[0].clone();
+            item.word = word.to_string();
+            item.book_name = book.to_string();
+            item.modified_time = modified_time;
+            item
+        };
+        // 2024-05-02, 2024-04-30, 2024-05-01
+        let more = vec![
+            word("abandon", "gre", 1714608000000),
+            word("ability", "CET4", 1714435200000),
+            word("able", "GRE 3000", 1714521600000),
+        ];
+        words.extend(more);
+        let template = WordTemplate::default();
+        assert_eq!(
+            format_chapters(&words, ChapterBy::Month, &template),
+            "#2020-10\naccommodate\nR&D\n\n#2024-04\nability\n\n#2024-05\nabandon\nable\n"
+        );
+        assert_eq!(
+            format_chapters(&words, ChapterBy::Book, &template),
+            "#CET4\nability\n\n#gre\nabandon\n\n#GRE 3000\naccommodate\nable\n\n#unknown\nR&D\n"
+        );
+        assert_eq!(
+            format_chapters(&words, ChapterBy::None, &template),
+            "accommodate\nR&D\nabandon\nability\nable\n"
+        );
+        // 模板应用于章节中的每个单词
+        let template = WordTemplate::parse("{word}\\t{date}")?;
+        assert_eq!(
+            format_chapters(&words[2..], ChapterBy::Book, &template),
+            "#CET4\nability\t2024-04-30\n\n#gre\nabandon\t2024-05-02\n\n#GRE 3000\nable\t2024-05-01\n"
+        );
+        assert_eq!(format_chapters(&[], ChapterBy::Month, &template), "");
+        assert_eq!("book".parse::<ChapterBy>()?, ChapterBy::Book);
+        assert!("year".parse::<ChapterBy>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn escape_html_chars() {
+        assert_eq!(escape_html("a<b>&'\""), "a&lt;b&gt;&amp;&#39;&quot;");
+        assert_eq!(escape_html("适应"), "适应");
+    }
+}
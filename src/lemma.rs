@@ -0,0 +1,109 @@
+//! 英文单词的原形，用于比较不同来源的单词时忽略变形，如kindle中的`running`与youdao中的`run`。
+//!
+//! 使用内置的小型词形表`data/lemmas-en.txt`，需要使用`--features lemma`构建。用于：
+//!
+//! - `dict mm --find-duplicates --lemma`：notepad间或notepad内重复的单词
+//! - plan中sync步骤的`lemma: true`：将youdao单词追加到maimemo notepad时忽略已有单词的变形
+//! - `dict yd --import-txt <file> --lemma`：导入kindle等导出的单词列表时忽略本地已有单词的变形
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+#[cfg(feature = "lemma")]
+const LEMMAS: &str = include_str!("../data/lemmas-en.txt");
+#[cfg(not(feature = "lemma"))]
+const LEMMAS: &str = "";
+
+/// 解析`原形: 变形...`格式的词形表，key为小写的变形与原形
+fn parse_table(s: &str) -> HashMap<String, String> {
+    let mut table = HashMap::new();
+    for line in s.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (lemma, forms) = match line.split_once(':') {
+            Some(v) => v,
+            None => {
+                warn!("skipped invalid lemma line: {}", line);
+                continue;
+            }
+        };
+        let lemma = lemma.trim().to_lowercase();
+        for form in forms.split_whitespace() {
+            table
+                .entry(form.to_lowercase())
+                .or_insert_with(|| lemma.clone());
+        }
+    }
+    table
+}
+
+fn table() -> &'static HashMap<String, String> {
+    static TABLE: OnceLock<HashMap<String, String>> = OnceLock::new();
+    TABLE.get_or_init(|| parse_table(LEMMAS))
+}
+
+/// 当前构建是否支持lemma，不支持时返回error
+pub fn check_supported() -> Result<(), String> {
+    if cfg!(feature = "lemma") {
+        Ok(())
+    } else {
+        Err("lemma is not supported. please build with `--features lemma`".to_string())
+    }
+}
+
+/// 单词的原形，不区分大小写，返回小写的原形。词形表中没有的单词原样返回，如非英文的单词
+///
+/// ```
+/// # if cfg!(feature = "lemma") {
+/// use dict::lemma::normalize_word;
+/// assert_eq!(normalize_word("Running"), "run");
+/// assert_eq!(normalize_word("serendipity"), "serendipity");
+/// # }
+/// ```
+pub fn normalize_word(word: &str) -> String {
+    table()
+        .get(&word.trim().to_lowercase())
+        .cloned()
+        .unwrap_or_else(|| word.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lemma_table() {
+        let table = parse_table("# comment\n\nrun: runs Ran\ninvalid\nchild: children runs\n");
+        assert_eq!(table.get("ran").map(String::as_str), Some("run"));
+        // 重复的变形使用第一个原形
+        assert_eq!(table.get("runs").map(String::as_str), Some("run"));
+        assert_eq!(table.get("children").map(String::as_str), Some("child"));
+        assert_eq!(table.len(), 3);
+    }
+
+    #[cfg(feature = "lemma")]
+    #[test]
+    fn common_inflections() {
+        for (word, lemma) in [
+            ("running", "run"),
+            ("ran", "run"),
+            ("Went", "go"),
+            ("studies", "study"),
+            ("stopped", "stop"),
+            ("children", "child"),
+            ("MICE", "mouse"),
+            ("better", "good"),
+            ("accommodating", "accommodate"),
+            ("run", "run"),
+        ] {
+            assert_eq!(normalize_word(word), lemma, "{}", word);
+        }
+    }
+
+    #[test]
+    fn unknown_words_unchanged() {
+        for word in ["serendipity", "Serendipity", "单词", "café", "Straße", ""] {
+            assert_eq!(normalize_word(word), word);
+        }
+    }
+}
@@ -1,6 +1,35 @@
+//! youdao单词本、maimemo notepads与eudic生词本的同步工具。
+//!
+//! 嵌入其它应用时使用[`api`]中的函数
+pub mod api;
+pub mod backup;
+pub mod cache;
+pub mod captcha;
+pub mod clipboard;
+pub mod color;
 pub mod config;
+pub mod content_lint;
 pub mod client;
+pub mod diff;
+pub mod doctor;
+pub mod duplicates;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod hash;
+pub mod lemma;
+pub mod lock;
+pub mod logging;
+pub mod msg;
+pub mod output;
+pub mod plan;
+pub mod prompt;
+pub mod redact;
+pub mod review;
+pub mod store;
+pub mod table;
+pub mod trans;
+pub mod watch;
 
-pub extern crate pretty_env_logger;
 #[macro_use]
 pub extern crate log;
\ No newline at end of file
@@ -0,0 +1,117 @@
+//! 多个dict进程间的文件advisory lock。读取时使用共享锁，写入时使用排它锁
+//!
+//! 锁在文件关闭时自动释放
+use fs2::FileExt;
+use std::fs::File;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 等待文件锁的默认最大时间
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 重试获取锁的间隔
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 获取共享锁，在[`LOCK_TIMEOUT`]内未获取到时返回error
+pub fn lock_shared(file: &File, path: &str) -> Result<(), String> {
+    lock_with_timeout(file, path, false, LOCK_TIMEOUT)
+}
+
+/// 获取排它锁，在[`LOCK_TIMEOUT`]内未获取到时返回error
+pub fn lock_exclusive(file: &File, path: &str) -> Result<(), String> {
+    lock_with_timeout(file, path, true, LOCK_TIMEOUT)
+}
+
+/// 在timeout内重试获取文件锁
+pub fn lock_with_timeout(
+    file: &File,
+    path: &str,
+    exclusive: bool,
+    timeout: Duration,
+) -> Result<(), String> {
+    let start = Instant::now();
+    loop {
+        let res = if exclusive {
+            FileExt::try_lock_exclusive(file)
+        } else {
+            FileExt::try_lock_shared(file)
+        };
+        match res {
+            Ok(()) => {
+                trace!("locked file exclusive: {}, path: {}", exclusive, path);
+                return Ok(());
+            }
+            Err(e) if is_contended(&e) => {
+                if start.elapsed() >= timeout {
+                    return Err(format!(
+                        "another dict process holds the lock on {}, waited {:?}",
+                        path, timeout
+                    ));
+                }
+                thread::sleep(LOCK_RETRY_INTERVAL);
+            }
+            Err(e) => return Err(format!("lock file error: {}, path: {}", e, path)),
+        }
+    }
+}
+
+fn is_contended(e: &io::Error) -> bool {
+    e.kind() == fs2::lock_contended_error().kind()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn exclusive_lock_timeout() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("words.json");
+        let path = path.to_str().unwrap().to_string();
+        File::create(&path).map_err(|e| format!("{:?}", e))?;
+
+        let (locked_tx, locked_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let holder_path = path.clone();
+        let holder = thread::spawn(move || {
+            let file = File::open(&holder_path).unwrap();
+            lock_exclusive(&file, &holder_path).unwrap();
+            locked_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        });
+        locked_rx.recv().unwrap();
+
+        let file = File::open(&path).map_err(|e| format!("{:?}", e))?;
+        let timeout = Duration::from_millis(100);
+        let e = lock_with_timeout(&file, &path, false, timeout).unwrap_err();
+        assert!(
+            e.starts_with(&format!("another dict process holds the lock on {}", path)),
+            "{}",
+            e
+        );
+
+        // 释放后可以获取到锁
+        release_tx.send(()).unwrap();
+        holder.join().unwrap();
+        lock_with_timeout(&file, &path, true, timeout)
+    }
+
+    #[test]
+    fn shared_locks() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("cookies.json");
+        let path = path.to_str().unwrap();
+        File::create(path).map_err(|e| format!("{:?}", e))?;
+
+        let a = File::open(path).map_err(|e| format!("{:?}", e))?;
+        let b = File::open(path).map_err(|e| format!("{:?}", e))?;
+        let timeout = Duration::from_millis(100);
+        lock_with_timeout(&a, path, false, timeout)?;
+        lock_with_timeout(&b, path, false, timeout)?;
+        let c = File::open(path).map_err(|e| format!("{:?}", e))?;
+        assert!(lock_with_timeout(&c, path, true, timeout).is_err());
+        Ok(())
+    }
+}
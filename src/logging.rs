@@ -0,0 +1,316 @@
+//! 日志初始化：stderr使用pretty_env_logger格式，可选同时写入按大小滚动的日志文件
+use crate::color::ColorChoice;
+use crate::config::LoggingConfig;
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use pretty_env_logger::env_logger::{self, WriteStyle};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// 仅输出该模块下的日志
+const LOG_MODULE: &str = "dict";
+
+/// 按大小滚动的日志文件。
+///
+/// 当前文件为`path`，滚动后的文件依次为`path.1`, `path.2`...，`path.1`为最新的。
+/// 包括当前文件最多保留max_files个文件
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: &str, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files: max_files.max(1),
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    /// 关闭当前文件并依次重命名，超出max_files的文件被删除
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 1 {
+            // 仅保留当前文件时直接清空
+            self.file.set_len(0)?;
+            self.size = 0;
+            return Ok(());
+        }
+        let oldest = self.rotated_path(self.max_files - 1);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for n in (1..self.max_files - 1).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // 非空文件写入后超出大小时先滚动
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// 同时输出到stderr与文件的logger
+struct DictLogger {
+    stderr: Option<env_logger::Logger>,
+    file: Option<(LevelFilter, Mutex<RotatingFile>)>,
+}
+
+impl DictLogger {
+    fn file_enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with(LOG_MODULE)
+            && self
+                .file
+                .as_ref()
+                .is_some_and(|(level, _)| metadata.level() <= *level)
+    }
+}
+
+impl Log for DictLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.as_ref().is_some_and(|l| l.enabled(metadata)) || self.file_enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(stderr) = &self.stderr {
+            stderr.log(record);
+        }
+        if self.file_enabled(record.metadata()) {
+            if let Some((_, file)) = &self.file {
+                let line = format_record(record);
+                if let Ok(mut file) = file.lock() {
+                    // 日志写入失败时无法再记录日志
+                    let _ = file.write_all(line.as_bytes());
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some((_, file)) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+fn format_record(record: &Record) -> String {
+    format!(
+        "{} {:<5} {} > {}\n",
+        Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+/// 通过`-v`的次数、`--log-filter`与`RUST_LOG`得到stderr的env_logger filter。
+///
+/// log_filter优先，其次为verbose，都未设置时使用rust_log。返回None时不输出到stderr
+pub fn filter_spec(
+    verbose: u8,
+    log_filter: Option<&str>,
+    rust_log: Option<&str>,
+) -> Option<String> {
+    if let Some(filter) = log_filter {
+        return Some(filter.to_string());
+    }
+    match verbose {
+        0 => rust_log.filter(|s| !s.is_empty()).map(|s| s.to_string()),
+        1 => Some(format!("{}=debug", LOG_MODULE)),
+        2 => Some(format!("{}=trace", LOG_MODULE)),
+        _ => Some(format!("{}=trace,reqwest=trace,hyper=trace", LOG_MODULE)),
+    }
+}
+
+/// 初始化全局logger。stderr的日志filter见[`filter_spec`]，颜色由color决定，
+/// log_file优先于config中的logging.file。
+///
+/// 多次调用时仅第一次生效，之后的调用直接返回
+///
+/// # Errors
+///
+/// 如果日志文件无法打开或file_level不合法
+pub fn init(
+    verbose: u8,
+    log_filter: Option<&str>,
+    log_file: Option<&str>,
+    color: ColorChoice,
+    config: &LoggingConfig,
+) -> Result<(), String> {
+    let rust_log = std::env::var("RUST_LOG").ok();
+    let write_style = if color.stderr() {
+        WriteStyle::Always
+    } else {
+        WriteStyle::Never
+    };
+    let stderr = filter_spec(verbose, log_filter, rust_log.as_deref()).map(|spec| {
+        pretty_env_logger::formatted_builder()
+            .parse_filters(&spec)
+            .write_style(write_style)
+            .build()
+    });
+    let file = match log_file.or_else(|| config.get_file()) {
+        Some(path) => {
+            let level = match config.get_file_level() {
+                Some(level) => LevelFilter::from_str(level)
+                    .map_err(|_| format!("invalid logging.file_level: {}", level))?,
+                None => LevelFilter::Info,
+            };
+            let file = RotatingFile::open(
+                path,
+                config.get_max_size_mb() * 1024 * 1024,
+                config.get_max_files(),
+            )
+            .map_err(|e| format!("open log file error: {}, path: {}", e, path))?;
+            Some((level, Mutex::new(file)))
+        }
+        None => None,
+    };
+    let max_level = [
+        stderr.as_ref().map(|l| l.filter()),
+        file.as_ref().map(|(level, _)| *level),
+    ]
+    .iter()
+    .flatten()
+    .max()
+    .copied()
+    .unwrap_or(LevelFilter::Off);
+    if log::set_boxed_logger(Box::new(DictLogger { stderr, file })).is_err() {
+        debug!("logger has already been initialized");
+        return Ok(());
+    }
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &PathBuf) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn verbose_filter_spec() {
+        assert_eq!(filter_spec(0, None, None), None);
+        assert_eq!(filter_spec(1, None, None).as_deref(), Some("dict=debug"));
+        assert_eq!(filter_spec(2, None, None).as_deref(), Some("dict=trace"));
+        assert_eq!(
+            filter_spec(3, None, None).as_deref(),
+            Some("dict=trace,reqwest=trace,hyper=trace")
+        );
+        assert_eq!(filter_spec(5, None, None), filter_spec(3, None, None));
+    }
+
+    #[test]
+    fn log_filter_precedence() {
+        // --log-filter覆盖-v与RUST_LOG
+        let spec = filter_spec(2, Some("dict::client=trace"), Some("info"));
+        assert_eq!(spec.as_deref(), Some("dict::client=trace"));
+        // -v覆盖RUST_LOG
+        assert_eq!(
+            filter_spec(1, None, Some("info")).as_deref(),
+            Some("dict=debug")
+        );
+        assert_eq!(filter_spec(0, None, Some("info")).as_deref(), Some("info"));
+        assert_eq!(filter_spec(0, None, Some("")), None);
+    }
+
+    #[test]
+    fn init_twice() {
+        let config = LoggingConfig::default();
+        assert!(init(1, None, None, ColorChoice::Never, &config).is_ok());
+        assert!(init(2, None, None, ColorChoice::Never, &config).is_ok());
+    }
+
+    #[test]
+    fn rotate_by_size() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("logs").join("dict.log");
+        let mut file = RotatingFile::open(path.to_str().unwrap(), 10, 3)?;
+        for line in &["aaaaaa\n", "bbbbbb\n", "cccccc\n", "dddddd\n"] {
+            file.write_all(line.as_bytes())?;
+        }
+        file.flush()?;
+
+        assert_eq!(read(&path), "dddddd\n");
+        assert_eq!(read(&file.rotated_path(1)), "cccccc\n");
+        assert_eq!(read(&file.rotated_path(2)), "bbbbbb\n");
+        // 超出max_files的文件被删除
+        assert!(!file.rotated_path(3).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn append_existing_file() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("dict.log");
+        fs::write(&path, "old\n")?;
+        let mut file = RotatingFile::open(path.to_str().unwrap(), 8, 2)?;
+        file.write_all(b"new\n")?;
+        assert_eq!(read(&path), "old\nnew\n");
+
+        file.write_all(b"next\n")?;
+        assert_eq!(read(&path), "next\n");
+        assert_eq!(read(&file.rotated_path(1)), "old\nnew\n");
+        Ok(())
+    }
+
+    #[test]
+    fn keep_single_file() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("dict.log");
+        let mut file = RotatingFile::open(path.to_str().unwrap(), 4, 1)?;
+        file.write_all(b"abc\n")?;
+        file.write_all(b"def\n")?;
+        assert_eq!(read(&path), "def\n");
+        assert!(!file.rotated_path(1).exists());
+        Ok(())
+    }
+}
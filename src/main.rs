@@ -1,21 +1,63 @@
 // https://users.rust-lang.org/t/cargo-build-shows-unresolved-import/45445/7
 use std::path::Path;
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Local, Utc};
+use cookie_store::CookieStore;
 use dict::{
+    backup::{self, NotepadBackups},
+    cache::{self, CacheEntry, CacheKind},
     client::{
-        maimemo_client::{MaimemoClient, Notepad},
-        youdao_client::{WordItem, YoudaoClient},
+        self,
+        context::HttpContext,
+        cookie_origin::{CookieOrigin, CookieSource},
+        eudic_client,
+        maimemo_client::{self, Notepad, NotepadQuery, NotepadSort},
+        maimemo_open_client,
+        metrics::MetricsRegistry,
+        record::Recorder,
+        youdao_client::{self, YoudaoClient},
+        CookieStatus, RequestPreview,
     },
-    config::{load_from_json_file, save_json, AppConfig, Config},
+    clipboard::{Clipboard, SystemClipboard, Tee},
+    color::{self, ColorChoice},
+    config::{self, AppConfig, Config, HttpConfig, LoggingConfig},
+    diff,
+    doctor::{self, Check, CheckStatus},
+    error,
+    events::JsonLinesSink,
+    export::{self, ChapterBy, WordTemplate},
+    lemma, logging, msg,
+    msg::Lang,
+    output,
+    plan::{Plan, SyncStep},
+    prompt::{self, UserPrompt},
+    store,
+    table::{self, Table},
 };
 use structopt::StructOpt;
+use tokio_util::sync::CancellationToken;
 #[macro_use]
 extern crate log;
 
-use std::fs;
-use std::io::{self, prelude::*, Write};
+pub mod app;
+
+use app::{
+    attach_timings,
+    eudic::EudicApp,
+    maimemo::{
+        captcha_command, validate_timestamp_format, MaimemoApp, UploadOptions, DEFAULT_SEARCH_LIMIT,
+    },
+    need_refresh, read_clipboard_words, read_file_or_stdin, refresh_exit_code,
+    runner::{run_or_watch_plan, PlanRunner},
+    save_exit_code,
+    source::SourceApp,
+    write_timings,
+    youdao::{lookup_word, ListOptions, WordFilter, YoudaoApp},
+};
+
+use std::io::{self, Write};
 use std::str;
+use std::sync::{Arc, Mutex};
 
 /// 典型用法
 /// 
@@ -24,18 +66,146 @@ use std::str;
 /// ```
 #[derive(StructOpt, Debug)]
 struct AppOpt {
-    /// 是否输出debug日志
+    /// 输出日志到stderr：-v为debug，-vv为trace，-vvv同时输出reqwest与hyper的trace日志。
+    /// 未指定时使用RUST_LOG环境变量
+    #[structopt(short, long, parse(from_occurrences))]
+    verbose: u8,
+
+    /// 仅在stdout输出数据，在stderr输出错误，不输出进度等提示信息
+    #[structopt(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// 本地没有单词本或notepads文件时自动从web上加载，同配置中的auto_refresh
+    #[structopt(long)]
+    auto_refresh: bool,
+
+    /// 不询问直接执行覆盖notepad、删除文件等操作。没有终端时必须指定
     #[structopt(short, long)]
-    verbose: bool,
+    yes: bool,
 
-    /// config配置文件路径。如果为空则默认从$HOME/dict-config.yml文件加载
+    /// stderr日志的env_logger filter，如`dict::client=trace`，覆盖-v与RUST_LOG
+    #[structopt(long)]
+    log_filter: Option<String>,
+
+    /// 同时将日志写入文件，优先于配置中的logging.file
+    #[structopt(long)]
+    log_file: Option<String>,
+
+    /// config配置文件路径。如果为空则从$DICT_CONFIG_PATH或$HOME/dict-config.yml文件加载
     #[structopt(long)]
     config_path: Option<String>,
 
+    /// 不加载配置文件，使用默认配置。仅支持lookup、store info与cache path等不需要登录的命令
+    #[structopt(long, conflicts_with = "config-path")]
+    no_config: bool,
+
+    /// 是否输出颜色：auto时仅在终端中且未设置NO_COLOR环境变量时输出
+    #[structopt(long, default_value = "auto", possible_values = &["auto", "always", "never"])]
+    color: ColorChoice,
+
+    /// 同`--color never`
+    #[structopt(long)]
+    no_color: bool,
+
+    /// 列表超过终端高度时不通过$PAGER(默认`less -FRX`)分页。stdout不是终端时不会分页
+    #[structopt(long)]
+    no_pager: bool,
+
+    /// 提示与错误信息的语言：en或zh-CN。未指定时使用$DICT_LANG或系统locale，默认为en
+    #[structopt(long, possible_values = &["en", "zh-CN"])]
+    lang: Option<Lang>,
+
+    /// 输出格式：text或json
+    #[structopt(long, default_value = "text", possible_values = &["text", "json"])]
+    format: OutputFormat,
+
+    /// 将每个请求的响应录制到目录中，cookie、token与password被隐藏。用于调试服务端的变化
+    #[structopt(long, env = "DICT_RECORD_DIR", conflicts_with = "replay")]
+    record: Option<String>,
+
+    /// 从--record录制的目录中回放响应，不访问网络
+    #[structopt(long)]
+    replay: Option<String>,
+
+    /// 使用独立的登录状态，cookies保存到`<cookie_path>.<profile>`。同`DICT_PROFILE`
+    #[structopt(long, env = "DICT_PROFILE")]
+    profile: Option<String>,
+
+    /// 离线模式，禁止登录、refresh等所有网络请求，仅使用本地数据。同`DICT_OFFLINE=1`。
+    /// 不包括本机的AnkiConnect
+    #[structopt(long)]
+    offline: bool,
+
+    /// 只读模式，禁止上传notepad、添加或删除单词、导入等修改远程或本地数据的操作，
+    /// 查询、refresh与导出不受影响。同配置中的read_only
+    #[structopt(long)]
+    read_only: bool,
+
+    /// 忽略youdao两次登录的最小间隔login_interval_secs，可能被加入黑名单
+    #[structopt(long)]
+    force_login: bool,
+
+    /// 将refresh、验证码与保存等事件以一行一个的json输出到已打开的文件描述符，用于GUI等包装程序
+    #[structopt(long, conflicts_with = "events-file")]
+    events_fd: Option<i32>,
+
+    /// 同events_fd，但输出到文件，已存在时覆盖
+    #[structopt(long)]
+    events_file: Option<String>,
+
+    /// 命令结束时在stderr输出每个请求的次数、耗时与接收的字节数。
+    /// `--format json`时作为refresh结果的timings字段或单独的一个对象输出到stdout
+    #[structopt(long)]
+    timings: bool,
+
+    /// 验证码、确认等终端输入最多等待的秒数，超时后中止当前操作，已获取的数据仍被保存。默认一直等待
+    #[structopt(long)]
+    prompt_timeout: Option<u64>,
+
     #[structopt(subcommand)]
     sub_cmd: Option<SubCommand>,
 }
+
+impl AppOpt {
+    /// 解析后的颜色设置，--no-color优先
+    fn color(&self) -> ColorChoice {
+        if self.no_color {
+            ColorChoice::Never
+        } else {
+            self.color
+        }
+    }
+
+    /// --offline或环境变量[`OFFLINE_ENV`]为非空且不是`0`、`false`时
+    fn offline(&self, env: Option<&str>) -> bool {
+        self.offline
+            || env.is_some_and(|v| !matches!(v.trim().to_lowercase().as_str(), "" | "0" | "false"))
+    }
+}
+
+/// 开启离线模式的环境变量，见`--offline`
+const OFFLINE_ENV: &str = "DICT_OFFLINE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unsupported output format: {}", s)),
+        }
+    }
+}
 #[derive(StructOpt, Debug)]
+// 仅在启动时解析一次
+#[allow(clippy::large_enum_variant)]
 enum SubCommand {
     /// youdao
     Yd {
@@ -47,17 +217,147 @@ enum SubCommand {
         #[structopt(short, long)]
         list: bool,
 
-        /// 过滤开始时间。格式支持`"%Y-%m-%d`。默认1970-01-01.
-        #[structopt(short, long)]
-        start_date: Option<String>,
+        /// 过滤开始时间。支持日期`%Y-%m-%d`即当天本地时间的0点，或相对现在的时间如`24h`, `7d`,
+        /// `2w`, `1m`。别名`--start-date`的日期同样使用本地时间，而不是之前的UTC。默认1970-01-01
+        #[structopt(short = "s", long, alias = "start-date")]
+        since: Option<String>,
 
-        /// 过滤终止时间。格式支持`"%Y-%m-%d`。默认`today`
-        #[structopt(short, long)]
-        end_date: Option<String>,
+        /// 过滤终止时间，格式同since，别名`--end-date`的日期同样使用本地时间。默认`today`
+        #[structopt(short = "e", long, alias = "end-date")]
+        until: Option<String>,
+
+        /// 使用单词本文件的修改时间即上次refresh的时间作为since
+        #[structopt(long, conflicts_with = "since")]
+        since_last_refresh: bool,
 
         /// 在输出前过滤单词数量。offset>0表示顺序输出的单词数量；offset<0表示从最后开始过滤的；offset=0表示不过滤
         #[structopt(long, default_value = "0")]
         offset: isize,
+
+        /// 导出本地单词到csv文件
+        #[structopt(long)]
+        export_csv: Option<String>,
+
+        /// 从csv文件中导入单词并合并到本地
+        #[structopt(long)]
+        import_csv: Option<String>,
+
+        /// 从文本或markdown文件中导入单词，每行一个单词。`—`或`:`后的注释作为释义。
+        /// 本地已存在的单词被忽略
+        #[structopt(long)]
+        import_txt: Option<String>,
+
+        /// import_txt时仅输出将要添加的单词，不修改本地单词本
+        #[structopt(long, requires("import-txt"))]
+        dry_run: bool,
+
+        /// import_txt时使用单词原形判断本地是否已存在，如本地已有`run`时忽略`running`
+        #[structopt(long, requires("import-txt"))]
+        lemma: bool,
+
+        /// 导出本地单词到anki可导入的tsv文件
+        #[structopt(long)]
+        export_anki: Option<String>,
+
+        /// 将指定json文件中的单词迁移到配置的sqlite存储中
+        #[structopt(long)]
+        migrate_store: Option<String>,
+
+        /// 通过AnkiConnect将本地单词添加到anki中。需要指定deck
+        #[structopt(long, requires("deck"))]
+        anki_connect: bool,
+
+        /// 在export_anki与anki_connect时指定anki deck
+        #[structopt(long)]
+        deck: Option<String>,
+
+        /// 通过youdao查询并补全本地trans或phonetic为空的单词。失败的单词不修改，可以再次执行继续补全
+        #[structopt(long)]
+        enrich: bool,
+
+        /// enrich时最多查询的单词数量
+        #[structopt(long, requires("enrich"))]
+        limit: Option<usize>,
+
+        /// enrich时同时查询的数量
+        #[structopt(long, default_value = "4")]
+        enrich_concurrency: usize,
+
+        /// enrich时每秒最多查询的数量，0表示不限制
+        #[structopt(long, default_value = "5")]
+        enrich_rate: u32,
+
+        /// list时仅显示有该本地标签的单词，忽略大小写
+        #[structopt(long, requires("list"))]
+        filter_tag: Option<String>,
+
+        /// list时仅显示first_seen即第一次进入单词本的时间不早于该时间的单词，格式同since。
+        /// 与since不同，不受之后修改单词的影响
+        #[structopt(long, requires("list"))]
+        added_since: Option<String>,
+
+        /// list时每个单词的输出模板，默认`{word}`。可用占位符：{word}, {trans}, {trans_first_line},
+        /// {phonetic}, {book}, {date}, {item_id}, {first_seen}, {last_seen}，
+        /// 从trans中解析的{pos}, {first_sense}, {senses}，支持`\t`与`\n`转义
+        #[structopt(long, requires("list"))]
+        template: Option<String>,
+
+        /// list时将单词分组为maimemo的`#`章节，可以直接用于`dict mm --upload`。
+        /// 默认none不分组。month按月份升序，book按单词本名称字母序，章节内保持list的顺序。template应用于章节中的每个单词
+        #[structopt(long, requires("list"), possible_values = &["none", "month", "book"])]
+        chapter_by: Option<ChapterBy>,
+
+        /// 从since与until过滤后的单词中随机输出N个
+        #[structopt(long, conflicts_with = "quiz")]
+        sample: Option<usize>,
+
+        /// 随机抽取N个单词在终端中测验：按Enter显示释义，回答是否认识，最后输出得分
+        #[structopt(long)]
+        quiz: Option<usize>,
+
+        /// sample与quiz的随机数种子，相同的种子与单词得到相同的结果
+        #[structopt(long)]
+        seed: Option<u64>,
+
+        /// list与sample时同时输出音标、第一个释义、first_seen与last_seen日期
+        #[structopt(long, conflicts_with = "template")]
+        detail: bool,
+
+        /// detail时输出所有释义而不是仅第一个
+        #[structopt(long, requires("detail"))]
+        all_senses: bool,
+
+        /// 显示单词本与单词数量。默认从本地单词统计，与refresh同时使用时从youdao获取
+        #[structopt(long)]
+        list_books: bool,
+
+        /// 将since与until、filter_tag过滤后单词本from中的单词移动到单词本to中，
+        /// 仅更新youdao上移动成功的本地单词
+        #[structopt(long, requires_all = &["from", "to"])]
+        move_book: bool,
+
+        /// move_book时的源单词本名称
+        #[structopt(long, requires("move-book"))]
+        from: Option<String>,
+
+        /// move_book时的目标单词本名称
+        #[structopt(long, requires("move-book"))]
+        to: Option<String>,
+
+        /// list时同时将输出复制到剪贴板，失败时返回error。需要`clipboard` feature
+        #[structopt(long, requires("list"))]
+        clip: bool,
+
+        /// 从剪贴板中读取单词，每行一个，添加到youdao单词本。需要时先登录
+        #[structopt(long, requires("from-clipboard"))]
+        add: bool,
+
+        /// add时的单词来源，目前仅支持剪贴板
+        #[structopt(long, requires("add"))]
+        from_clipboard: bool,
+
+        #[structopt(subcommand)]
+        cmd: Option<YdCmd>,
     },
     /// maimemo
     Mm {
@@ -70,14 +370,18 @@ enum SubCommand {
         #[structopt(short, long)]
         refresh: bool,
 
-        /// 可用于在list与upload时指定notepad
-        #[structopt(long = "id")]
-        notepad_id: Option<String>,
+        /// 可用于在list与upload时指定notepad。upload时可以指定多次，将相同的内容上传到每个notepad
+        #[structopt(long = "id", number_of_values = 1)]
+        notepad_id: Vec<String>,
 
         /// 从stdin中上传内容到maimemo
         #[structopt(short, long)]
         upload: bool,
 
+        /// upload与create时从文件而不是stdin中读取内容
+        #[structopt(long)]
+        file: Option<String>,
+
         /// 在upload时自动插入时间戳
         #[structopt(short, long, required_if("upload", "true"))]
         timestamp: bool,
@@ -85,463 +389,1515 @@ enum SubCommand {
         /// 在upload时在之前的基础上增加而不是覆盖
         #[structopt(short, long, required_if("upload", "true"))]
         appending: bool,
+
+        /// upload时使用已获取的验证码，不再刷新与读取验证码。用于没有终端时
+        #[structopt(long)]
+        captcha: Option<String>,
+
+        /// 插入时间戳的strftime格式，默认`%Y-%m-%d %H:%M:%S`
+        #[structopt(long)]
+        timestamp_format: Option<String>,
+
+        /// 插入的header模板，`{date}`替换为时间戳，默认`# {date} Auto insert`
+        #[structopt(long)]
+        header_template: Option<String>,
+
+        /// upload时不规范化stdin中的内容，原样上传
+        #[structopt(long)]
+        raw: bool,
+
+        /// upload时stdin中有像短语的行则报错
+        #[structopt(long, conflicts_with = "raw")]
+        strict: bool,
+
+        /// upload时修复会被maimemo删除的字符：tab替换为空格，移除控制字符与emoji，NFC规范化
+        #[structopt(long)]
+        fix: bool,
+
+        /// upload时用新内容的前几个单词更新notepad的简介，同配置中的update_brief
+        #[structopt(long)]
+        update_brief: bool,
+
+        /// 列出id指定notepad的本地备份。指定backup时用该备份覆盖notepad
+        #[structopt(long, requires("notepad-id"), conflicts_with = "upload")]
+        restore: bool,
+
+        /// restore时使用的备份时间戳
+        #[structopt(long, requires("restore"))]
+        backup: Option<String>,
+
+        /// 输出在多个本地notepad中出现的单词与所在的notepads。忽略`#`开头的行
+        #[structopt(long, conflicts_with_all = &["upload", "restore"])]
+        find_duplicates: bool,
+
+        /// find_duplicates时仅查找id指定notepad中重复的单词
+        #[structopt(long, requires_all = &["find-duplicates", "notepad-id"])]
+        within: bool,
+
+        /// find_duplicates时忽略大小写
+        #[structopt(long, requires("find-duplicates"))]
+        ignore_case: bool,
+
+        /// find_duplicates时使用单词原形比较，如`running`与`run`重复。同时忽略大小写
+        #[structopt(long, requires("find-duplicates"))]
+        lemma: bool,
+
+        /// 通过关键字搜索公开的notepads，输出id、标题与简介
+        #[structopt(long, conflicts_with_all = &["upload", "restore", "find-duplicates"])]
+        search_public: Option<String>,
+
+        /// search_public时仅搜索maimemo推荐的notepads
+        #[structopt(long, requires("search-public"))]
+        recommend: bool,
+
+        /// list与search_public时最多输出的数量。search_public默认30，list默认不限制
+        #[structopt(long)]
+        limit: Option<usize>,
+
+        /// list时notepads的排序：updated与created按时间降序，title与id升序。默认updated
+        #[structopt(long, possible_values = &["updated", "created", "title", "id"])]
+        sort: Option<NotepadSort>,
+
+        /// 将id指定的公开notepad复制为一个新的私有notepad，需要输入验证码
+        #[structopt(
+            long = "clone",
+            requires_all = &["notepad-id", "title"],
+            conflicts_with_all = &["upload", "restore", "find-duplicates", "search-public"]
+        )]
+        clone_notepad: bool,
+
+        /// clone与create时新notepad的标题
+        #[structopt(long)]
+        title: Option<String>,
+
+        /// 通过open api创建标题为title的私有notepad，内容从stdin或file中读取，输出新的id。
+        /// 需要配置api_token
+        #[structopt(
+            long,
+            requires("title"),
+            conflicts_with_all = &["upload", "restore", "find-duplicates", "search-public", "clone-notepad"]
+        )]
+        create: bool,
+
+        /// 通过open api删除id指定的notepad，删除前确认并备份本地的内容。需要配置api_token
+        #[structopt(
+            long,
+            requires("notepad-id"),
+            conflicts_with_all = &["upload", "restore", "find-duplicates", "search-public", "clone-notepad", "create"]
+        )]
+        delete: bool,
+
+        /// 获取maimemo中id指定notepad的当前内容，与本地缓存比较并输出unified diff
+        #[structopt(
+            long,
+            requires("notepad-id"),
+            conflicts_with_all = &["upload", "restore", "find-duplicates", "search-public", "clone-notepad", "create", "delete"]
+        )]
+        diff: bool,
+
+        /// diff时确认后用远程的notepad替换本地缓存，远程已删除时从缓存中移除
+        #[structopt(long, requires("diff"))]
+        update_local: bool,
+
+        /// diff与upload覆盖notepad前的预览最多输出的修改处(hunk)数量，默认5
+        #[structopt(long, conflicts_with = "diff-full")]
+        diff_max_hunks: Option<usize>,
+
+        /// diff与预览时输出全部的修改
+        #[structopt(long)]
+        diff_full: bool,
+
+        /// upload与restore时不检查maimemo中的notepad是否在refresh后被修改，直接覆盖
+        #[structopt(long)]
+        force: bool,
+
+        /// upload到多个notepads时先确认所有的覆盖，之后连续回答所有验证码。
+        /// 保存一个notepad的同时获取下一个验证码
+        #[structopt(long, requires("upload"))]
+        prefetch_captcha: bool,
+    },
+    /// eudic 欧路词典生词本
+    Eu {
+        /// 从eudic生词本中重新加载单词数据
+        #[structopt(short, long)]
+        refresh: bool,
+
+        /// 显示单词
+        #[structopt(short, long)]
+        list: bool,
+
+        /// 在输出前过滤单词数量，同yd的offset
+        #[structopt(long, default_value = "0")]
+        offset: isize,
+
+        /// 从stdin中读取单词添加到生词本，每行一个单词
+        #[structopt(short, long)]
+        add: bool,
+
+        /// 从stdin中读取单词并从生词本中删除
+        #[structopt(short, long, conflicts_with = "add")]
+        delete: bool,
+    },
+    /// 配置文件custom_sources中通过http json接口获取单词的自定义来源
+    Src {
+        /// 来源名称
+        name: String,
+
+        /// 从来源中重新加载单词数据
+        #[structopt(short, long)]
+        refresh: bool,
+
+        /// 显示单词
+        #[structopt(short, long)]
+        list: bool,
+
+        /// 在输出前过滤单词数量，同yd的offset
+        #[structopt(long, default_value = "0")]
+        offset: isize,
+    },
+    /// 通过youdao查询单词的音标与释义，不需要登录
+    Lookup {
+        /// 单词或短语
+        #[structopt(required = true)]
+        words: Vec<String>,
+
+        /// 同时添加到youdao单词本，需要时先登录
+        #[structopt(short, long)]
+        add: bool,
+
+        /// 同时将输出复制到剪贴板，失败时返回error。需要`clipboard` feature
+        #[structopt(long)]
+        clip: bool,
+    },
+    /// 本地store文件
+    Store {
+        #[structopt(subcommand)]
+        cmd: StoreCmd,
+    },
+    /// 本地缓存文件：单词本、notepads与cookies
+    Cache {
+        #[structopt(subcommand)]
+        cmd: CacheCmd,
+    },
+    /// 登录状态
+    Auth {
+        #[structopt(subcommand)]
+        cmd: AuthCmd,
+    },
+    /// 配置文件
+    Config {
+        #[structopt(subcommand)]
+        cmd: ConfigCmd,
+    },
+    /// 调试配置中的请求
+    Debug {
+        #[structopt(subcommand)]
+        cmd: DebugCmd,
+    },
+    /// 诊断配置、本地文件、登录cookies与网络，逐项输出pass/warn/fail
+    Doctor {
+        /// 同时使用保存的cookies请求各个服务，验证登录状态。不会重新登录
+        #[structopt(long)]
+        login: bool,
+    },
+    /// 按顺序执行plan文件中的步骤，如refresh、export_csv与sync。每个服务仅登录一次
+    Run {
+        /// plan文件路径，相对路径相对于该文件所在的目录
+        plan: String,
+
+        /// 仅输出解析后的步骤，不执行
+        #[structopt(long)]
+        dry_run: bool,
+
+        /// 持续运行，每隔interval执行一次plan，ctrl-c时保存后退出。连续失败时增加间隔。
+        /// maimemo未配置captcha_command时不上传，而是将待添加的单词写入data_dir中的
+        /// `pending-<notepad>.txt`并提示，使用`--features notify`构建时同时发送桌面通知
+        #[structopt(long, conflicts_with = "dry-run")]
+        watch: bool,
+
+        /// watch时执行的间隔，格式同yd的since如`6h`、`1d`。默认6h
+        #[structopt(long, requires("watch"))]
+        interval: Option<String>,
+    },
+    /// 将youdao单词本或自定义来源中的新单词添加到maimemo notepad，同只有一个sync步骤的plan。
+    /// 执行前refresh来源与maimemo
+    Sync {
+        /// youdao单词本名称
+        #[structopt(long, required_unless = "source", conflicts_with = "source")]
+        book: Option<String>,
+
+        /// 配置文件custom_sources中的来源名称
+        #[structopt(long)]
+        source: Option<String>,
+
+        /// maimemo notepad_id
+        #[structopt(long)]
+        notepad: String,
+
+        /// 在添加的单词前插入时间戳，同`mm -u -t`
+        #[structopt(short, long)]
+        timestamp: bool,
+
+        /// 使用单词原形判断notepad中是否已有该单词
+        #[structopt(long)]
+        lemma: bool,
+
+        /// 同`dict run --watch`，没有新的单词时跳过
+        #[structopt(long)]
+        watch: bool,
+
+        /// watch时执行的间隔，默认6h
+        #[structopt(long, requires("watch"))]
+        interval: Option<String>,
     },
 }
 
-pub struct MaimemoApp<'a> {
-    notepads: Vec<Notepad>,
-    dictionary_path: String,
-    is_updated: bool,
-    client: MaimemoClient,
-    input: io::BufReader<Box<dyn Read + 'a>>,
-    output: io::BufWriter<Box<dyn Write + 'a>>,
+#[derive(StructOpt, Debug)]
+enum YdCmd {
+    /// 修改本地单词的标签与笔记，refresh时被保留。不会同步到youdao
+    Tag {
+        /// 单词，忽略大小写。在多个单词本中时全部修改
+        word: String,
+
+        /// 添加标签，可以指定多次
+        #[structopt(long)]
+        add: Vec<String>,
+
+        /// 删除标签，可以指定多次
+        #[structopt(long)]
+        remove: Vec<String>,
+
+        /// 设置笔记，为空时删除笔记
+        #[structopt(long)]
+        note: Option<String>,
+    },
 }
 
-impl<'a> std::ops::Drop for MaimemoApp<'a> {
-    /// 当self被更新后保存notepads数据
-    fn drop(&mut self) {
-        if !self.is_updated {
-            return;
-        }
-        if let Err(e) = save_json(&self.notepads, &self.dictionary_path) {
-            error!("notepads persistence failed. {}", e);
-        } else {
-            info!(
-                "notepads persistence successful path: {}",
-                self.dictionary_path
-            );
-        }
-    }
+#[derive(StructOpt, Debug)]
+enum ConfigCmd {
+    /// 检查配置。如登录请求是否会发送User-Agent
+    Check,
 }
 
-impl<'a> MaimemoApp<'a> {
-    pub async fn new(
-        config: AppConfig,
-        is_local: bool,
-        input: impl io::Read + 'a,
-        output: impl io::Write + 'a,
-    ) -> MaimemoApp<'a> {
-        let dictionary_path = config.get_dictionary_path().to_string();
-        let mut client = MaimemoClient::new(config)
-            .unwrap_or_else(|e| panic!("new maimemo client failed: {}", e));
+#[derive(StructOpt, Debug)]
+enum DebugCmd {
+    /// 输出一个请求实际发送的method、url、headers、cookies与body，不发送请求。
+    /// 包括内置的默认请求、http配置中的headers与保存的cookies，敏感的值被隐藏
+    Request {
+        #[structopt(long, possible_values = &["yd", "mm", "eu"])]
+        service: String,
 
-        let notepads = if is_local {
-            load_from_json_file(&dictionary_path)
-                .await
-                .unwrap_or_else(|e| {
-                    panic!(
-                        "load maimemo dictionary error: {}, dictionary_path: {}",
-                        e, dictionary_path
-                    )
-                })
-        } else {
-            // load from web
-            debug!("Signing in");
-            client
-                .login()
-                .await
-                .unwrap_or_else(|e| panic!("maimemo client login failed: {}", e));
-            client
-                .get_notepads()
-                .await
-                .unwrap_or_else(|e| panic!("get notepads failed: {}", e))
-        };
-        Self {
-            client,
-            dictionary_path,
-            notepads,
-            is_updated: !is_local,
-            input: io::BufReader::new(Box::new(input)),
-            output: io::BufWriter::new(Box::new(output)),
-        }
-    }
-    /// 从web maimemo上加载notepads
-    pub async fn with_stdio(config: AppConfig, is_local: bool) -> MaimemoApp<'a> {
-        // 修复在stdin使用管道线时无法使用用户输入问题
-        let path = "/dev/tty";
-        let file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(path)
-            .unwrap_or_else(|e| panic!("open file error: {}, path: {}", e, path));
-        MaimemoApp::new(config, is_local, file, io::stdout()).await
-    }
-
-    pub async fn with_stdio_local(config: AppConfig) -> MaimemoApp<'a> {
-        Self::with_stdio(config, true).await
-    }
-
-    pub async fn with_stdio_web(config: AppConfig) -> MaimemoApp<'a> {
-        Self::with_stdio(config, false).await
-    }
-
-    /// 从stdin将指定notepad_id的内容更新到maimemo web上。当保存成功后更新
-    /// notepads，并在self drop退出时保存
-    ///
-    /// 如果is_appending=true则在原notepad上添加，否则清楚仅用stdin中的内容
-    ///
-    /// 如果timestamp=true则自动插入时间戳
-    ///
-    /// # Errors
-    ///
-    /// 如果client未登录。
-    pub async fn upload_notepad(
-        &mut self,
-        contents_read: impl io::Read,
-        notepad_id: &str,
-        is_appending: bool,
-        timestamp: bool,
-    ) {
-        if !self.client.has_logged() {
-            panic!("Not logged in. please use -r refresh");
+        /// 请求名称，如notepad-save
+        #[structopt(long)]
+        name: String,
+
+        /// 同时输出示例body序列化后的内容
+        #[structopt(long)]
+        body_sample: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum AuthCmd {
+    /// 显示各个服务保存的登录cookies、过期时间与来源
+    Status,
+    /// 从浏览器导出的Netscape格式cookies.txt导入登录cookies，并记录为从浏览器导入
+    Import {
+        #[structopt(long, possible_values = &["yd", "mm"])]
+        service: String,
+
+        /// 导出cookies的浏览器，如firefox。在`auth status`中显示
+        #[structopt(long)]
+        browser: Option<String>,
+
+        /// cookies.txt文件路径
+        file: String,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum CacheCmd {
+    /// 显示缓存文件的路径、大小、数量与修改时间
+    Status,
+    /// 删除缓存文件。默认删除所有服务的所有文件
+    Clear {
+        /// 仅删除指定服务的文件
+        #[structopt(long, possible_values = &["yd", "mm", "eu"])]
+        service: Option<String>,
+
+        /// 仅删除cookie文件
+        #[structopt(long)]
+        cookies: bool,
+
+        /// 仅删除单词本与notepads文件
+        #[structopt(long)]
+        dictionaries: bool,
+    },
+    /// 输出解析后的数据目录
+    Path,
+}
+
+#[derive(StructOpt, Debug)]
+enum StoreCmd {
+    /// 显示store文件的版本、单词数量与保存时间
+    Info {
+        /// store文件路径
+        path: String,
+    },
+}
+
+async fn run_cache(
+    cmd: CacheCmd,
+    config: &Config,
+    format: OutputFormat,
+    quiet: bool,
+    yes: bool,
+) -> Result<(), String> {
+    let data_dir = absolute_path(config.get_data_dir());
+    match cmd {
+        CacheCmd::Path => println!("{}", data_dir),
+        CacheCmd::Status => {
+            let entries = cache::cache_entries(config);
+            let out = match format {
+                OutputFormat::Json => serde_json::to_string_pretty(&serde_json::json!({
+                    "data_dir": data_dir,
+                    "entries": entries,
+                }))
+                .map_err(|e| msg!("error.serialize", e))?,
+                OutputFormat::Text => {
+                    format_cache_status(&data_dir, &entries, table::stdout_width())
+                }
+            };
+            println!("{}", out);
         }
-        let new_notepad = self
-            .build_uploaded_notepad(contents_read, notepad_id, is_appending, timestamp)
-            .await
-            .unwrap_or_else(|e| panic!("build notepad error: {}", e));
-        loop {
-            let captcha = self
-                .read_captcha()
-                .await
-                .unwrap_or_else(|e| panic!("read captcha error: {}", e));
-            // save notepad
-            if let Err(e) = self
-                .client
-                .save_notepad(new_notepad.clone(), captcha.clone())
-                .await
-            {
-                // fix decode error
-                if e.to_lowercase().contains("decode") {
-                    panic!("读取mm缓存文件错误，请用-r修复");
+        CacheCmd::Clear {
+            service,
+            cookies,
+            dictionaries,
+        } => {
+            let entries = cache::select_entries(
+                cache::cache_entries(config),
+                service.as_deref(),
+                cookies,
+                dictionaries,
+            );
+            let entries = entries.into_iter().filter(|e| e.exists).collect::<Vec<_>>();
+            if entries.is_empty() {
+                if !quiet {
+                    eprintln!("{}", msg!("cache.nothing_to_remove"));
                 }
-                debug!(
-                    "upload failed. notepad: {}, captcha: {}",
-                    new_notepad, captcha
-                );
-                print!("upload error: {}. \nDo you want to try again [y]:", e);
-                let line = self
-                    .read_line()
-                    .unwrap_or_else(|e| panic!("read user input error: {}", e));
-                if line == "y" {
-                    debug!("exiting with input: {}", line);
-                    return;
+                return Ok(());
+            }
+            // 需要确认时总是列出要删除的文件
+            if !quiet || !yes {
+                for entry in &entries {
+                    eprintln!("{}", entry.path);
                 }
-            } else {
-                break;
-            }
-        }
-        if self
-            .notepads
-            .iter_mut()
-            .find(|n| n.get_notepad_id() == notepad_id)
-            .map(|n| *n = new_notepad)
-            .is_none()
-        {
-            warn!(
-                "Failed to update local Notepad. not found notepad_id: {}",
-                notepad_id
-            );
-            panic!("save notepad successful, but Failed to update local Notepad. please use -r refresh local data")
-        } else {
-            self.is_updated = true;
-            debug!("upload notepad successful for notepad_id: {}", notepad_id);
-        }
-    }
-
-    fn read_line(&mut self) -> Result<String, String> {
-        trace!("reading a line");
-        let mut line = String::new();
-        match self.input.read_line(&mut line) {
-            Ok(0) => {
-                debug!("read has reached EOF. line: {}", line);
             }
-            Ok(size) => {
-                debug!("read {} bytes. line: {}", size, line);
+            let mut prompt = UserPrompt::open().ok();
+            let summary = msg!("confirm.delete_files", format_count(entries.len()));
+            if !prompt::confirm(prompt.as_mut(), yes, &summary).await? {
+                if !quiet {
+                    eprintln!("{}", msg!("aborted"));
+                }
+                return Ok(());
             }
-            Err(e) => {
-                error!("read line: {}, error: {}", e, line);
-                return Err(e.to_string());
+            let removed = cache::clear_entries(&entries)?;
+            if !quiet {
+                eprintln!("{}", msg!("cache.removed", removed.len()));
             }
         }
-        if line.is_empty() {
-            error!("read line is empty");
-            Err("read line is empty".to_string())
-        } else {
-            Ok(line)
-        }
-    }
-
-    async fn read_captcha(&mut self) -> Result<String, String> {
-        trace!("loading captcha from maimemo service");
-        let captcha_contents = self.client.refresh_captcha().await?;
-        // Display captcha on the terminal
-        trace!("Printing image content");
-        let img = image::load_from_memory(&captcha_contents).map_err(|e| format!("{:?}", e))?;
-        viuer::print(
-            &img,
-            &viuer::Config {
-                absolute_offset: false,
-                ..viuer::Config::default()
-            },
-        )
-        .expect("Image printing failed.");
-        debug!("Waiting for input captcha");
-        println!("please enter captcha: ");
-        // read captcha on stdin
-        self.read_line()
-    }
-
-    /// 从stdin中读取并构造出notepad。
-    ///
-    /// 如果is_appending=true则在原notepad上添加，否则清楚仅用stdin中的内容
-    ///
-    /// 如果timestamp=true则自动插入时间戳
-    async fn build_uploaded_notepad(
-        &mut self,
-        contents_read: impl io::Read,
-        notepad_id: &str,
-        is_appending: bool,
-        timestamp: bool,
-    ) -> Result<Notepad, String> {
-        trace!("Building a new notepad");
-        let mut notepad = self
-            .notepads
-            .iter()
-            .find(|n| n.get_notepad_id() == notepad_id)
-            .ok_or(format!("not found notepad_id: {}", notepad_id))?
-            .clone();
-        let contents = notepad.get_contents_mut().unwrap();
-        if !is_appending {
-            debug!("Emptying original Notepad contents");
-            contents.clear();
-        }
-        if timestamp {
-            let timestr = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-            let s = format!("\n# {} Auto insert\n", timestr);
-            debug!("Inserting timestamp string: {}", s);
-            contents.push_str(&s);
-        }
-        // read contents
-        debug!("reading contents");
-        io::BufReader::new(contents_read)
-            .read_to_string(contents)
-            .map_err(|e| {
-                error!(
-                    "read contents to string error: {}, contents: {}",
-                    e, contents
-                );
-                format!("read contents to string error: {}", e)
-            })?;
-        debug!("read contents:\n{}", contents);
-        Ok(notepad)
     }
+    Ok(())
+}
 
-    /// 打印所有notepad概要信息
-    pub fn list(&mut self) {
-        for n in &self.notepads {
-            writeln!(self.output, "{}", n).unwrap_or_else(|e| panic!("write notepad error: {}", e))
+fn run_auth(
+    cmd: AuthCmd,
+    config: &Config,
+    format: OutputFormat,
+    quiet: bool,
+    color: ColorChoice,
+) -> Result<(), String> {
+    match cmd {
+        AuthCmd::Status => {
+            let stores = service_cookie_stores(config)?;
+            let out = match format {
+                OutputFormat::Json => serde_json::to_string_pretty(
+                    &stores
+                        .iter()
+                        .map(|(service, app, login_cookies, cookie_store)| {
+                            serde_json::json!({
+                                "service": service,
+                                "origin": app.get_cookie_path().and_then(CookieOrigin::load_or_note_previous),
+                                "cookies": client::login_cookie_status(cookie_store, login_cookies),
+                                "requests": client::request_cookies(app, cookie_store),
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .map_err(|e| msg!("error.serialize", e))?,
+                OutputFormat::Text => {
+                    let services = stores
+                        .iter()
+                        .map(|(service, app, login_cookies, cookie_store)| {
+                            (
+                                *service,
+                                app.get_cookie_path().and_then(CookieOrigin::load_or_note_previous),
+                                client::login_cookie_status(cookie_store, login_cookies),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    format_auth_status(
+                        &services,
+                        Utc::now().timestamp(),
+                        table::stdout_width(),
+                        color.stdout(),
+                    )
+                }
+            };
+            println!("{}", out);
+        }
+        AuthCmd::Import {
+            service,
+            browser,
+            file,
+        } => {
+            let imported = import_cookies(config, &service, &file, browser.as_deref())?;
+            if !quiet {
+                eprintln!("{}", msg!("auth.imported", imported, service));
+            }
         }
     }
+    Ok(())
+}
 
-    /// 输出指定id的notepad内容
-    pub fn list_contents(&mut self, notepad_id: &str) {
-        let contents = self
-            .notepads
-            .iter()
-            .find(|n| n.get_notepad_id() == notepad_id)
-            .unwrap_or_else(|| panic!("not found notepad for notepad_id: {}", notepad_id))
-            .get_contents()
-            .unwrap_or_else(|| panic!("not found contents for notepad_id: {}", notepad_id));
-        writeln!(self.output, "{}", contents)
-            .unwrap_or_else(|e| panic!("write notepad contents error: {}", e));
+/// 将cookies.txt文件中的cookies导入到service的cookie文件，并记录为从browser导入。返回导入的数量
+fn import_cookies(
+    config: &Config,
+    service: &str,
+    file: &str,
+    browser: Option<&str>,
+) -> Result<usize, String> {
+    let app = match service {
+        cache::SERVICE_YOUDAO => config.youdao.as_ref(),
+        cache::SERVICE_MAIMEMO => config.maimemo.as_ref(),
+        _ => None,
     }
+    .ok_or_else(|| format!("{} is not configured", service))?;
+    let cookie_path = app
+        .get_cookie_path()
+        .ok_or_else(|| format!("cookie_path of {} is not configured", service))?;
+    let contents = std::fs::read_to_string(file)
+        .map_err(|e| format!("read cookies error: {}, path: {}", e, file))?;
+    let passphrase = app.get_cookie_passphrase()?;
+    let mut cookie_store = client::build_cookie_store(Some(cookie_path), passphrase.as_deref())?;
+    let imported = client::import_cookies_txt(&mut cookie_store, &contents)?;
+    client::save_cookie_store(cookie_path, &cookie_store, passphrase.as_deref())?;
+    CookieOrigin::import(browser, Utc::now().timestamp()).save(cookie_path)?;
+    Ok(imported)
 }
 
-#[allow(dead_code)]
-struct YoudaoApp {
-    word_items: Vec<WordItem>,
-    is_local: bool,
-    dictionary_path: String,
-    client: YoudaoClient,
-    output: io::BufWriter<Box<dyn Write>>,
+/// 服务名、配置、登录需要的cookies与保存的cookie store
+type ServiceCookies<'a> = (
+    &'static str,
+    &'a AppConfig,
+    &'static [(&'static str, &'static str)],
+    CookieStore,
+);
+
+/// 从保存的cookie文件中读取各个服务的cookies。不存在的cookie文件不会被创建
+fn service_cookie_stores(config: &Config) -> Result<Vec<ServiceCookies<'_>>, String> {
+    let services = [
+        (
+            cache::SERVICE_YOUDAO,
+            config.youdao.as_ref(),
+            youdao_client::LOGIN_COOKIES,
+        ),
+        (
+            cache::SERVICE_MAIMEMO,
+            config.maimemo.as_ref(),
+            maimemo_client::LOGIN_COOKIES,
+        ),
+    ];
+    let mut stores = vec![];
+    for (service, app, login_cookies) in services.iter() {
+        let app = match app {
+            Some(app) => app,
+            None => continue,
+        };
+        let cookie_path = app.get_cookie_path().filter(|p| Path::new(p).exists());
+        let passphrase = app.get_cookie_passphrase()?;
+        let cookie_store = client::build_cookie_store(cookie_path, passphrase.as_deref())?;
+        stores.push((*service, *app, *login_cookies, cookie_store));
+    }
+    Ok(stores)
 }
 
-impl std::ops::Drop for YoudaoApp {
-    /// 退出时更新
-    fn drop(&mut self) {
-        if self.is_local {
-            return;
-        }
-        if let Err(e) = save_json(&self.word_items, &self.dictionary_path) {
-            error!("word items persistence failed. {}", e);
-        } else {
-            info!(
-                "word items persistence successful path: {}",
-                self.dictionary_path
-            );
+fn run_config(
+    cmd: ConfigCmd,
+    config: &Config,
+    http: &HttpConfig,
+    format: OutputFormat,
+) -> Result<(), String> {
+    match cmd {
+        ConfigCmd::Check => {
+            let warnings = check_config(config, http);
+            let out = match format {
+                OutputFormat::Json => {
+                    serde_json::to_string_pretty(&serde_json::json!({ "warnings": warnings }))
+                        .map_err(|e| msg!("error.serialize", e))?
+                }
+                OutputFormat::Text if warnings.is_empty() => msg!("config.ok"),
+                OutputFormat::Text => warnings
+                    .iter()
+                    .map(|w| msg!("warning", w))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            println!("{}", out);
         }
     }
+    Ok(())
 }
 
-impl YoudaoApp {
-    /// 从file中构造
-    pub async fn from_file(config: AppConfig) -> Self {
-        let dictionary_path = config.get_dictionary_path().to_string();
-        let client =
-            YoudaoClient::new(config).unwrap_or_else(|e| panic!("youdao client new failed. {}", e));
-        let word_items = load_from_json_file(&dictionary_path)
-            .await
-            .unwrap_or_else(|e| panic!("youdao load json failed. {}", e));
-        Self {
-            word_items,
-            is_local: true,
-            client,
-            dictionary_path,
-            output: io::BufWriter::new(Box::new(io::stdout())),
-        }
-    }
-
-    /// 从youdao web上获取words构造
-    pub async fn from_web(config: AppConfig) -> Self {
-        let dictionary_path = config.get_dictionary_path().to_string();
-        let mut client =
-            YoudaoClient::new(config).unwrap_or_else(|e| panic!("new youdaoclient error: {}", e));
-        if !client.has_logged() {
-            debug!("Signing in");
-            if let Err(e) = client.login().await {
-                error!("youdao login error: {}", e);
-                panic!("youdao login error: {}", e);
-            }
-        }
-        let word_items = match client.get_words().await {
-            Ok(w) => w,
-            Err(e) => {
-                error!("youdao get words error: {}", e);
-                panic!("youdao get words error: {}", e);
-            }
-        };
-        Self {
-            word_items,
-            is_local: false,
-            dictionary_path,
-            client,
-            output: io::BufWriter::new(Box::new(io::stdout())),
-        }
-    }
-
-    /// 查询单词。可以通过date和排序后前后过滤数量
-    ///
-    /// 通过时间区间`[start_date, end_date]`过虑单词并以升序排列。时间格式：`"%Y-%m-%d`
-    ///
-    /// - [Remove an element from a vector](https://stackoverflow.com/a/40310140)
-    pub fn list(&mut self, start_date: Option<&str>, end_date: Option<&str>, offset: isize) {
-        let start = Self::parse_date(start_date);
-        let end = Self::parse_date(end_date);
-        let mut words = self
-            .word_items
-            .iter()
-            .filter(|w| {
-                let date = Utc.timestamp_millis(w.modified_time as i64);
-                match (start, end) {
-                    (None, None) => true,
-                    (Some(start), Some(end)) => date >= start && date <= end,
-                    (Some(start), None) => date >= start,
-                    (None, Some(end)) => date <= end,
-                }
-            })
-            .collect::<Vec<_>>();
-        words.sort_unstable_by(|a, b| b.modified_time.cmp(&a.modified_time));
-        Self::filter_offset(&mut words, offset);
-        for item in words {
-            if let Err(e) = writeln!(self.output, "{}", item.word) {
-                error!("writeln error: {}, worditem: {:?}", e, item);
-            }
-        }
-    }
-
-    /// 取出offset个元素。如果offset<0，则从后取出offset个。如果offset==0则不会过滤任何元素
-    ///
-    /// # Examples
-    ///
-    /// ```rust, ignore
-    /// let mut words = vec![1,2,3];
-    /// let offset = -2;
-    /// filter_offset(&mut words, offset); // words: [2,3]
-    /// ```
-    fn filter_offset<T>(words: &mut Vec<&T>, offset: isize) {
-        debug!("filter offset: {}", offset);
-        if offset > 0 {
-            let mut count = words.len() - offset as usize;
-            while count > 0 {
-                count -= 1;
-                words.pop();
-            }
-        } else if offset < 0 {
-            let start = (words.len() as isize + offset) as usize;
-            words.drain(0..start);
-            // replace
-        }
-    }
-
-    /// 解析时间格式
-    fn parse_date(date: Option<&str>) -> Option<DateTime<Utc>> {
-        date.and_then(|date| {
-            // 不能用date，只能用datetime
-            let (suffix, format) = (" 00:00:00", "%Y-%m-%d %H:%M:%S");
-            match Utc.datetime_from_str(&(date.to_string() + suffix), format) {
-                Ok(date) => Some(date),
-                Err(e) => {
-                    panic!("parse date {} error: {}", date, e);
-                }
-            }
-        })
+fn run_debug(
+    cmd: DebugCmd,
+    config: &Config,
+    context: &HttpContext,
+    format: OutputFormat,
+) -> Result<(), String> {
+    match cmd {
+        DebugCmd::Request {
+            service,
+            name,
+            body_sample,
+        } => {
+            let preview = preview_service_request(config, context, &service, &name, body_sample)?;
+            let out = match format {
+                OutputFormat::Json => serde_json::to_string_pretty(&preview)
+                    .map_err(|e| msg!("error.serialize", e))?,
+                OutputFormat::Text => format_request_preview(&preview),
+            };
+            println!("{}", out);
+        }
     }
+    Ok(())
 }
 
-use std::env;
-#[tokio::main]
-async fn main() {
-    let opt: AppOpt = AppOpt::from_args();
-    // debug log
-    if opt.verbose {
-        pretty_env_logger::formatted_builder()
-            // .format(|buf, record| writeln!(buf, "{}: {}", record.level(), record.args()))
-            .filter_module("dict", log::LevelFilter::Debug)
-            .init();
-    }
-    let config_path = opt
-        .config_path
-        .as_ref()
-        .map(|s| s.to_owned())
-        // default $HOME/filename
-        .unwrap_or_else(|| {
-            let filename = "dict-config.yml";
-            let home = env::var("HOME").expect("not found $HOME var");
-            let path = Path::new(&home).join(filename);
-            path.to_str().unwrap().to_string()
-        });
-    let mut config = if let Ok(config) = Config::from_yaml_file(&config_path) {
-        config
-    } else {
-        eprintln!("not found config file in path: {}", config_path);
-        return;
+/// 构造service中req_name的请求但不发送，见[`client::preview_request`]。
+///
+/// 使用与client相同的内置默认请求。使用保存的cookies，不存在的cookie文件不会被创建
+fn preview_service_request(
+    config: &Config,
+    context: &HttpContext,
+    service: &str,
+    req_name: &str,
+    body_sample: bool,
+) -> Result<RequestPreview, String> {
+    let app = match service {
+        cache::SERVICE_YOUDAO => config
+            .youdao
+            .clone()
+            .map(youdao_client::with_default_requests),
+        // 配置了api_token时使用open api
+        cache::SERVICE_MAIMEMO => match config.maimemo.clone() {
+            Some(app) if app.get_api_token().is_some() => {
+                Some(maimemo_open_client::with_default_requests(app)?)
+            }
+            app => app,
+        },
+        _ => config
+            .eudic
+            .clone()
+            .map(eudic_client::with_default_requests)
+            .transpose()?,
+    }
+    .ok_or_else(|| format!("not found service {} in config", service))?;
+    let cookie_path = app.get_cookie_path().filter(|p| Path::new(p).exists());
+    let passphrase = app.get_cookie_passphrase()?;
+    let cookie_store = client::build_cookie_store(cookie_path, passphrase.as_deref())?;
+    if !body_sample {
+        return client::preview_request(&app, context, &cookie_store, req_name, None::<&str>);
+    }
+    // `--body-sample`使用clients发送时的body结构与示例的值。未知的请求使用一个示例字段
+    const EMAIL: &str = "user@example.com";
+    let words = ["hello", "world"].map(String::from);
+    let notepad = Notepad::new("sample", "", "hello\nworld\n".to_string()).with_id("695835");
+    let list = eudic_client::StudyList {
+        id: "0".to_string(),
+        language: "en".to_string(),
+        name: "sample".to_string(),
+    };
+    let query = NotepadQuery::default();
+    let store = &cookie_store;
+    match (service, req_name) {
+        (cache::SERVICE_YOUDAO, "login") => {
+            let form = youdao_client::LoginForm::new(EMAIL, "5f4dcc3b5aa765d61d8327deb882cf99");
+            client::preview_request(&app, context, store, req_name, Some(&form))
+        }
+        (cache::SERVICE_MAIMEMO, "login") => {
+            let form = maimemo_client::LoginForm::new(EMAIL, "password");
+            client::preview_request(&app, context, store, req_name, Some(&form))
+        }
+        (cache::SERVICE_MAIMEMO, "notepad-search") => {
+            let payload = maimemo_client::SearchPayload::new(&query, 0, 10);
+            client::preview_request(&app, context, store, req_name, Some(&payload))
+        }
+        (cache::SERVICE_MAIMEMO, "notepad-save") => {
+            let form = maimemo_client::SaveForm::new(&notepad, "abcd");
+            client::preview_request(&app, context, store, req_name, Some(&form))
+        }
+        (cache::SERVICE_EUDIC, "add-words" | "delete-words") => {
+            let body = eudic_client::WordsBody::new(&list, &words);
+            client::preview_request(&app, context, store, req_name, Some(&body))
+        }
+        _ => {
+            let body = serde_json::json!({ "sample": "value" });
+            client::preview_request(&app, context, store, req_name, Some(&body))
+        }
+    }
+}
+
+/// 以`method url`、headers、cookies与body的顺序输出，类似http请求报文
+fn format_request_preview(preview: &RequestPreview) -> String {
+    let mut out = format!("{} {}\n", preview.method, preview.url);
+    for (name, val) in &preview.headers {
+        out.push_str(&format!("{}: {}\n", name, val));
+    }
+    let cookies = if preview.cookies.is_empty() {
+        "none".to_string()
+    } else {
+        preview.cookies.join(", ")
+    };
+    out.push_str(&format!("\n# cookies: {}", cookies));
+    if let Some(body) = &preview.body {
+        out.push_str(&format!("\n\n{}", body));
+    }
+    out
+}
+
+/// 检查配置中可能的问题，返回警告信息。路径冲突等错误在加载配置时已被拒绝
+fn check_config(config: &Config, http: &HttpConfig) -> Vec<String> {
+    let services = [
+        ("youdao", config.youdao.as_ref()),
+        ("maimemo", config.maimemo.as_ref()),
+    ];
+    let mut warnings = vec![];
+    for (name, app) in services.iter() {
+        let app = match app {
+            Some(app) => app,
+            None => continue,
+        };
+        if client::request_user_agent(http, app, "login").is_none() {
+            warnings.push(format!(
+                "no User-Agent would be sent on {} login request. set http.user_agent, {}.user_agent or the request headers",
+                name, name
+            ));
+        }
+    }
+    warnings
+}
+
+/// 执行doctor的所有检查。配置文件无法加载时不再执行其它检查
+async fn doctor_checks(
+    config_path: Result<String, String>,
+    login: bool,
+    offline: bool,
+) -> Vec<Check> {
+    let (check, config) = match config_path {
+        Ok(path) => doctor::check_config_file(&path),
+        Err(e) => (Check::fail("config", e), None),
+    };
+    let mut checks = vec![check];
+    let mut config = match config {
+        Some(config) => config,
+        None => return checks,
+    };
+    let http = config.http();
+    checks.extend(
+        check_config(&config, &http)
+            .into_iter()
+            .map(|w| Check::warn("config", w)),
+    );
+    checks.extend(doctor::check_paths(&config));
+    checks.extend(doctor::check_cookies(&config));
+    if let Some(app) = config.maimemo.as_ref() {
+        checks.push(doctor::check_maimemo_backend(app));
+    }
+    let context = match HttpContext::from_config(&http) {
+        Ok(context) => Arc::new(context.with_offline(offline)),
+        Err(e) => {
+            checks.push(Check::fail("http config", e));
+            return checks;
+        }
+    };
+    for (service, host) in doctor::configured_hosts(&config) {
+        checks.push(doctor::check_host(&context, service, &host).await);
+    }
+    if login {
+        if config.youdao.is_some() {
+            checks.push(doctor::check_youdao_session(config.youdao(), context.clone()).await);
+        }
+        if config.maimemo.is_some() {
+            checks.push(doctor::check_maimemo_session(config.maimemo(), context).await);
+        }
+    }
+    checks
+}
+
+/// 以表格输出doctor的检查结果，最后一行为统计。width见[`Table::render`]，color时警告与失败的行带颜色
+fn format_doctor(checks: &[Check], width: Option<usize>, color: bool) -> String {
+    let mut table = Table::new(&["status", "check", "detail"]).with_color(color);
+    for c in checks {
+        let (status, row_color) = match c.status {
+            CheckStatus::Pass => ("pass", None),
+            CheckStatus::Warn => ("warn", Some(color::YELLOW)),
+            CheckStatus::Fail => ("FAIL", Some(color::RED)),
+        };
+        table.add_colored_row(vec![status, &c.name, &c.detail], row_color);
+    }
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    format!(
+        "{}\n{} passed, {} warnings, {} failed",
+        table.render(width),
+        count(CheckStatus::Pass),
+        count(CheckStatus::Warn),
+        count(CheckStatus::Fail)
+    )
+}
+
+/// 以表格输出登录状态，每行一个cookie，按服务与cookie名排序。表格后每行一个服务在now时的cookies来源。
+/// width见[`Table::render`]，color时缺少的cookie带颜色
+fn format_auth_status(
+    services: &[(&str, Option<CookieOrigin>, Vec<CookieStatus>)],
+    now: i64,
+    width: Option<usize>,
+    color: bool,
+) -> String {
+    let mut services = services.iter().collect::<Vec<_>>();
+    services.sort_by_key(|(service, _, _)| *service);
+    let mut table = Table::new(&["service", "cookie", "status"]).with_color(color);
+    for (service, _, cookies) in &services {
+        let mut cookies = cookies.iter().collect::<Vec<_>>();
+        cookies.sort_by(|a, b| a.name.cmp(&b.name));
+        for c in cookies {
+            let detail = match (c.present, &c.expires, c.expires_in_secs) {
+                (false, _, _) => "missing".to_string(),
+                (true, Some(expires), Some(secs)) if secs > 0 => {
+                    format!("expires {} (in {})", expires, format_age(secs as u64))
+                }
+                (true, Some(expires), _) => format!("expires {}", expires),
+                (true, None, _) => "session".to_string(),
+            };
+            table.add_colored_row(
+                vec![service.to_string(), c.name.clone(), detail],
+                Some(color::RED).filter(|_| !c.present),
+            );
+        }
+    }
+    let origins = services
+        .iter()
+        .map(|(service, origin, _)| {
+            format!(
+                "{} session: {}",
+                service,
+                describe_origin(origin.as_ref(), now)
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{}\n{}", table.render(width), origins.join("\n"))
+}
+
+/// cookies来源与在now时的存在时间，如`imported from firefox 43d ago (2026-09-03T00:00:00+00:00)`
+fn describe_origin(origin: Option<&CookieOrigin>, now: i64) -> String {
+    let origin = match origin {
+        Some(origin) => origin,
+        None => return "unknown origin".to_string(),
+    };
+    let ago = format_age(now.saturating_sub(origin.at).max(0) as u64);
+    let source = match (origin.source, origin.browser.as_deref()) {
+        (CookieSource::Login, _) => format!("logged in {} ago", ago),
+        (CookieSource::Import, Some(browser)) => format!("imported from {} {} ago", browser, ago),
+        (CookieSource::Import, None) => format!("imported {} ago", ago),
+        (CookieSource::Previous, _) => {
+            format!("carried over from a previous run, saved {} ago", ago)
+        }
+    };
+    match DateTime::<Utc>::from_timestamp(origin.at, 0) {
+        Some(at) => format!("{} ({})", source, at.to_rfc3339()),
+        None => source,
+    }
+}
+
+/// 以表格输出缓存状态，每行一个文件。width见[`Table::render`]，
+/// 为None时输出TSV，不包含表格前的数据目录
+fn format_cache_status(data_dir: &str, entries: &[CacheEntry], width: Option<usize>) -> String {
+    let mut table = Table::new(&["service", "kind", "path", "detail"]);
+    for e in entries {
+        let kind = match e.kind {
+            CacheKind::Dictionary => "dictionary",
+            CacheKind::Cookies => "cookies",
+        };
+        let detail = if e.exists {
+            format!(
+                "{}, {} items, {} ago",
+                format_size(e.size.unwrap_or_default()),
+                e.items.map_or("?".to_string(), |n| n.to_string()),
+                e.age_secs.map_or("?".to_string(), format_age)
+            )
+        } else {
+            "not found".to_string()
+        };
+        table.add_row(vec![
+            e.service.to_string(),
+            kind.to_string(),
+            e.path.clone(),
+            detail,
+        ]);
+    }
+    match width {
+        Some(_) => format!("data dir: {}\n{}", data_dir, table.render(width)),
+        None => table.render(width),
+    }
+}
+
+/// 用output替换app的输出，返回原来的输出。替换前flush原来的输出，
+/// 原来的输出为分页输出时在drop时等待分页程序退出，见[`output::PagedOutput`]
+fn swap_output<'a>(
+    current: &mut io::BufWriter<Box<dyn Write + 'a>>,
+    output: Box<dyn Write + 'a>,
+) -> io::BufWriter<Box<dyn Write + 'a>> {
+    if let Err(e) = current.flush() {
+        warn!("flush output error: {}", e);
+    }
+    std::mem::replace(current, io::BufWriter::new(output))
+}
+
+/// 使用千位分隔符，如`1,243`
+fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / 60 / 60),
+        s => format!("{}d", s / 24 / 60 / 60),
+    }
+}
+
+/// 相对路径转换为相对于当前目录的绝对路径
+fn absolute_path(path: &str) -> String {
+    env::current_dir()
+        .map(|dir| dir.join(path))
+        .ok()
+        .and_then(|p| p.to_str().map(|s| s.trim_end_matches('/').to_string()))
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// 输出store文件信息
+fn print_store_info(path: &str, output: &mut impl Write) -> Result<(), String> {
+    let info = store::store_info(path)?;
+    writeln!(
+        output,
+        "path: {}\nversion: {}\nitems: {}\nsaved_at: {}",
+        info.path,
+        info.version,
+        info.items,
+        info.saved_at.as_deref().unwrap_or("-")
+    )
+    .map_err(|e| format!("writeln error: {}", e))
+}
+
+use std::env;
+#[tokio::main]
+async fn main() {
+    let opt: AppOpt = AppOpt::from_args();
+    let cancel = CancellationToken::new();
+    tokio::spawn(cancel_on_ctrl_c(cancel.clone()));
+    let code = run(opt, cancel.clone()).await;
+    let code = if cancel.is_cancelled() {
+        EXIT_INTERRUPTED
+    } else {
+        code
+    };
+    // 询问超时后读取终端的blocking线程仍在等待输入，直接退出而不等待它结束
+    std::process::exit(code);
+}
+
+/// 本地没有单词本或notepads文件时的退出码
+const EXIT_NO_LOCAL_DATA: i32 = 4;
+
+/// 通过ctrl-c中断时的退出码
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// 第一次ctrl-c时取消进行中的请求，run保存已获取的数据与cookies后正常返回。
+/// 第二次ctrl-c时立即退出，如在等待用户输入时
+async fn cancel_on_ctrl_c(cancel: CancellationToken) {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("listen for ctrl-c error: {}", e);
+        return;
+    }
+    eprintln!("{}", msg!("interrupted"));
+    cancel.cancel();
+    if tokio::signal::ctrl_c().await.is_ok() {
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+}
+
+/// 不需要配置文件与登录的命令，可以使用`--no-config`
+fn supports_no_config(cmd: Option<&SubCommand>) -> bool {
+    matches!(
+        cmd,
+        Some(SubCommand::Lookup { add: false, .. })
+            | Some(SubCommand::Store { .. })
+            | Some(SubCommand::Cache {
+                cmd: CacheCmd::Path
+            })
+    )
+}
+
+/// 执行命令并返回进程退出码
+async fn run(opt: AppOpt, cancel: CancellationToken) -> i32 {
+    let metrics = Arc::<Mutex<MetricsRegistry>>::default();
+    let timings = opt.timings;
+    let format = opt.format;
+    // json输出时统计已作为refresh结果的一部分输出
+    let mut attached = false;
+    let code = run_command(opt, cancel, metrics.clone(), &mut attached).await;
+    if timings {
+        let metrics = metrics.lock().unwrap();
+        let (mut stdout, mut stderr) = (io::stdout(), io::stderr());
+        if let Err(e) = write_timings(&metrics, attached, format, &mut stdout, &mut stderr) {
+            eprintln!("{}", error::pretty(&e));
+        }
+    }
+    code
+}
+
+/// 请求统计加入refresh结果时attached为true，见[`attach_timings`]
+async fn run_command(
+    opt: AppOpt,
+    cancel: CancellationToken,
+    metrics: Arc<Mutex<MetricsRegistry>>,
+    attached: &mut bool,
+) -> i32 {
+    msg::set_lang(Lang::resolve(opt.lang, |k| env::var(k).ok()));
+    error::set_verbose(opt.verbose > 0);
+    prompt::set_timeout(opt.prompt_timeout.map(std::time::Duration::from_secs));
+    let color = opt.color();
+    // 不需要配置文件的命令
+    if let Some(SubCommand::Store {
+        cmd: StoreCmd::Info { path },
+    }) = &opt.sub_cmd
+    {
+        if let Err(e) = logging::init(
+            opt.verbose,
+            opt.log_filter.as_deref(),
+            opt.log_file.as_deref(),
+            color,
+            &LoggingConfig::default(),
+        ) {
+            eprintln!("{}", msg!("error.logging", error::pretty(&e)));
+            return 1;
+        }
+        if let Err(e) = print_store_info(path, &mut io::stdout()) {
+            eprintln!("{}", msg!("error.store_info", error::pretty(&e)));
+            return 1;
+        }
+        return 0;
+    }
+    if let (Some(SubCommand::Doctor { login }), false) = (&opt.sub_cmd, opt.no_config) {
+        if let Err(e) = logging::init(
+            opt.verbose,
+            opt.log_filter.as_deref(),
+            opt.log_file.as_deref(),
+            color,
+            &LoggingConfig::default(),
+        ) {
+            eprintln!("{}", msg!("error.logging", error::pretty(&e)));
+            return 1;
+        }
+        let config_path =
+            config::resolve_config_path(opt.config_path.as_deref(), |k| env::var(k).ok())
+                .map_err(|e| e.to_string());
+        let offline = opt.offline(env::var(OFFLINE_ENV).ok().as_deref());
+        let checks = doctor_checks(config_path, *login, offline).await;
+        let out = match opt.format {
+            OutputFormat::Json => match serde_json::to_string_pretty(&checks) {
+                Ok(out) => out,
+                Err(e) => {
+                    eprintln!("{}", msg!("error.serialize", e));
+                    return 1;
+                }
+            },
+            OutputFormat::Text => format_doctor(&checks, table::stdout_width(), color.stdout()),
+        };
+        println!("{}", out);
+        return if checks.iter().any(|c| c.status == CheckStatus::Fail) {
+            1
+        } else {
+            0
+        };
+    }
+    let (config_path, mut config) = if opt.no_config {
+        if !supports_no_config(opt.sub_cmd.as_ref()) {
+            eprintln!("{}", msg!("config.no_config_unsupported"));
+            return 1;
+        }
+        (String::new(), Config::minimal())
+    } else {
+        let config_path =
+            match config::resolve_config_path(opt.config_path.as_deref(), |k| env::var(k).ok()) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 1;
+                }
+            };
+        match Config::from_yaml_file(&config_path) {
+            Ok(config) => (config_path, config),
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    msg!("error.load_config", error::pretty(&e), config_path)
+                );
+                return 1;
+            }
+        }
+    };
+    if let Some(profile) = opt.profile.as_deref() {
+        if let Err(e) = config.use_profile(profile) {
+            eprintln!("{}", msg!("error.config", error::pretty(&e)));
+            return 1;
+        }
+    }
+    if let Err(e) = logging::init(
+        opt.verbose,
+        opt.log_filter.as_deref(),
+        opt.log_file.as_deref(),
+        color,
+        &config.logging(),
+    ) {
+        eprintln!("{}", msg!("error.logging", error::pretty(&e)));
+        return 1;
+    }
+    let http_config = config.http();
+    if http_config
+        .get_tls()
+        .is_some_and(|tls| tls.danger_accept_invalid_certs())
+    {
+        eprintln!("{}", msg!("config.tls_insecure"));
+    }
+    // 所有client共享连接池与全局http配置
+    let context = match HttpContext::from_config(&http_config) {
+        Ok(context) => context,
+        Err(e) => {
+            eprintln!("{}", msg!("error.http_config", error::pretty(&e)));
+            return 1;
+        }
+    };
+    let recorder = match (&opt.record, &opt.replay) {
+        // env中的DICT_RECORD_DIR不受conflicts_with检查
+        (Some(_), Some(_)) => Err(msg!("record.replay_conflict")),
+        (None, Some(dir)) => Recorder::replay(dir).map(Some),
+        (Some(dir), None) => Recorder::record(dir).map(Some),
+        (None, None) => Ok(None),
+    };
+    let context = context
+        .with_offline(opt.offline(env::var(OFFLINE_ENV).ok().as_deref()))
+        .with_read_only(opt.read_only || config.read_only())
+        .with_cancel(cancel.clone())
+        .with_metrics(metrics.clone());
+    let events = match (opt.events_fd, &opt.events_file) {
+        (Some(fd), _) => JsonLinesSink::from_fd(fd).map(Some),
+        (None, Some(path)) => JsonLinesSink::create(path).map(Some),
+        (None, None) => Ok(None),
+    };
+    let context = match events {
+        Ok(Some(sink)) => context.with_events(Arc::new(sink)),
+        Ok(None) => context,
+        Err(e) => {
+            eprintln!("{}", msg!("error.events", error::pretty(&e)));
+            return 1;
+        }
+    };
+    let context = match recorder {
+        Ok(Some(recorder)) => Arc::new(context.with_recorder(recorder)),
+        Ok(None) => Arc::new(context),
+        Err(e) => {
+            eprintln!("{}", msg!("error.record", error::pretty(&e)));
+            return 1;
+        }
     };
     match opt.sub_cmd {
+        Some(SubCommand::Cache { cmd }) => {
+            if let Err(e) = run_cache(cmd, &config, opt.format, opt.quiet, opt.yes).await {
+                eprintln!("{}", msg!("error.cache", error::pretty(&e)));
+                return 1;
+            }
+            0
+        }
+        Some(SubCommand::Auth { cmd }) => {
+            if let Err(e) = run_auth(cmd, &config, opt.format, opt.quiet, color) {
+                eprintln!("{}", msg!("error.auth", error::pretty(&e)));
+                return 1;
+            }
+            0
+        }
+        Some(SubCommand::Config { cmd }) => {
+            if let Err(e) = run_config(cmd, &config, &http_config, opt.format) {
+                eprintln!("{}", msg!("error.config", error::pretty(&e)));
+                return 1;
+            }
+            0
+        }
+        Some(SubCommand::Debug { cmd }) => {
+            if let Err(e) = run_debug(cmd, &config, &context, opt.format) {
+                eprintln!("{}", msg!("error.debug", error::pretty(&e)));
+                return 1;
+            }
+            0
+        }
         Some(SubCommand::Yd {
             list,
             refresh,
-            end_date,
-            start_date,
+            until,
+            since,
+            since_last_refresh,
             offset,
+            export_csv,
+            import_csv,
+            import_txt,
+            dry_run,
+            lemma,
+            export_anki,
+            anki_connect,
+            deck,
+            migrate_store,
+            enrich,
+            limit,
+            enrich_concurrency,
+            enrich_rate,
+            filter_tag,
+            added_since,
+            template,
+            chapter_by,
+            sample,
+            quiz,
+            seed,
+            detail,
+            all_senses,
+            list_books,
+            move_book,
+            from,
+            to,
+            clip,
+            add,
+            from_clipboard,
+            cmd,
         }) => {
-            let config = config.youdao();
-            let mut app = if refresh {
-                YoudaoApp::from_web(config).await
+            if lemma {
+                if let Err(e) = lemma::check_supported() {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            }
+            let chapter_by = chapter_by.unwrap_or(ChapterBy::None);
+            // 在访问网络前检查模板
+            let template = template
+                .as_deref()
+                .or_else(|| detail.then(|| export::detail_template(all_senses)));
+            let template = match template.map(WordTemplate::parse).transpose() {
+                Ok(template) => template.unwrap_or_default(),
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            };
+            let anki_config = config.anki();
+            let config = config.youdao().with_force_login(opt.force_login);
+            if let Some(YdCmd::Tag {
+                word,
+                add,
+                remove,
+                note,
+            }) = cmd
+            {
+                let mut app = match YoudaoApp::from_file(config, context).await {
+                    Ok(app) => app,
+                    Err(e) => {
+                        eprintln!("{}", error::pretty(&e));
+                        return 1;
+                    }
+                };
+                if let Err(e) = app.tag(&word, &add, &remove, note.as_deref()) {
+                    eprintln!("{}", msg!("error.tag_word", error::pretty(&e)));
+                    return 1;
+                }
+                return save_exit_code(app.save().await);
+            }
+            let refresh = match need_refresh(&config, refresh, opt.auto_refresh) {
+                Ok(refresh) => refresh,
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return EXIT_NO_LOCAL_DATA;
+                }
+            };
+            if migrate_store.is_some() && !store::is_sqlite_storage(&config) {
+                eprintln!("{}", msg!("config.migrate_requires_sqlite"));
+                return 1;
+            }
+            // 在refresh前读取单词本的修改时间
+            let (since, until) = match YoudaoApp::parse_time_range(
+                since.as_deref(),
+                until.as_deref(),
+                since_last_refresh,
+                config.get_dictionary_path(),
+            ) {
+                Ok(range) => range,
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            };
+            let added_since = match added_since
+                .map(|s| YoudaoApp::parse_time_bound(&s, Local::now()))
+                .transpose()
+            {
+                Ok(added_since) => added_since,
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            };
+            let list_options = ListOptions {
+                start: since,
+                end: until,
+                offset,
+                filter: WordFilter {
+                    tag: filter_tag.as_deref(),
+                    added_since,
+                },
+                template: &template,
+                chapter_by,
+            };
+            let only_list = !refresh
+                && import_csv.is_none()
+                && import_txt.is_none()
+                && export_csv.is_none()
+                && export_anki.is_none()
+                && migrate_store.is_none()
+                && !anki_connect
+                && !enrich
+                && !move_book
+                && !add;
+            if list && only_list && !clip && !store::is_sqlite_storage(&config) {
+                // 仅查询时从文件中流式读取单词
+                if let Err(e) = YoudaoApp::list_file(
+                    config.get_dictionary_path(),
+                    output::stdout(!opt.no_pager),
+                    &list_options,
+                ) {
+                    eprintln!("{}", msg!("error.list_words", error::pretty(&e)));
+                    return 1;
+                }
+                return 0;
+            }
+            let app = if refresh {
+                YoudaoApp::from_web(config, context).await
             } else {
-                YoudaoApp::from_file(config).await
+                YoudaoApp::from_file(config, context).await
             };
-            if list {
-                app.list(start_date.as_deref(), end_date.as_deref(), offset);
+            let mut app = match app {
+                Ok(app) => app,
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            };
+            // 某个操作失败时继续执行其它操作并保存，最后返回非0的退出码
+            let mut failed = false;
+            if let Some(path) = migrate_store {
+                match app.migrate_store(&path).await {
+                    Ok(report) => info!("migrated store from {}: {:?}", path, report),
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.migrate_store", error::pretty(&e)));
+                        failed = true;
+                    }
+                }
+            }
+            if let Some(path) = import_csv {
+                match app.import_csv(&path) {
+                    Ok(report) => info!("imported csv {}: {:?}", path, report),
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.import_csv", error::pretty(&e)));
+                        failed = true;
+                    }
+                }
+            }
+            if let Some(path) = import_txt {
+                match app.import_txt(&path, dry_run, lemma) {
+                    Ok(report) if dry_run => {
+                        info!("would import {} words from {}", report.added, path)
+                    }
+                    Ok(report) => info!("imported txt {}: {:?}", path, report),
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.import_txt", error::pretty(&e)));
+                        failed = true;
+                    }
+                }
+            }
+            if enrich {
+                match app.enrich(limit, enrich_concurrency, enrich_rate).await {
+                    Ok(report) => info!("enriched words: {:?}", report),
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.enrich", error::pretty(&e)));
+                        failed = true;
+                    }
+                }
+            }
+            if let Some(path) = export_csv {
+                if let Err(e) = app.export_csv(&path) {
+                    eprintln!("{}", msg!("error.export_csv", error::pretty(&e)));
+                    failed = true;
+                }
+            }
+            if let Some(path) = export_anki {
+                if let Err(e) = app.export_anki(&path, deck.as_deref()) {
+                    eprintln!("{}", msg!("error.export_anki", error::pretty(&e)));
+                    failed = true;
+                }
+            }
+            if anki_connect {
+                let deck = deck.as_deref().unwrap_or_default();
+                match app.push_anki(&anki_config, deck).await {
+                    Ok(report) => info!("pushed words to anki deck {}: {:?}", deck, report),
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.push_anki", error::pretty(&e)));
+                        failed = true;
+                    }
+                }
+            }
+            if let (true, Some(from), Some(to)) = (move_book, &from, &to) {
+                match app
+                    .move_book(since, until, filter_tag.as_deref(), from, to)
+                    .await
+                {
+                    Ok(report) => {
+                        for (item_id, e) in &report.failed {
+                            eprintln!("{}", msg!("book.move_failed", item_id, to, e));
+                        }
+                        info!("moved words from {} to {}: {:?}", from, to, report);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.move_book", error::pretty(&e)));
+                        failed = true;
+                    }
+                }
+            }
+            if add && from_clipboard {
+                match read_clipboard_words(&mut SystemClipboard, opt.quiet) {
+                    Ok(words) => match app.add_words(&words).await {
+                        Ok(report) => {
+                            for (word, e) in &report.failed {
+                                eprintln!("{}", msg!("youdao.add_failed", word, e));
+                            }
+                            info!("added words to youdao: {:?}", report);
+                        }
+                        Err(e) => {
+                            eprintln!("{}", msg!("error.youdao_add", error::pretty(&e)));
+                            failed = true;
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.youdao_add", error::pretty(&e)));
+                        failed = true;
+                    }
+                }
+            }
+            if list_books {
+                if let Err(e) = app.list_books(refresh, opt.format).await {
+                    eprintln!("{}", msg!("error.list_books", error::pretty(&e)));
+                    failed = true;
+                }
+            }
+            if list && clip {
+                let tee = Tee::new(io::stdout());
+                let copy = tee.copy();
+                app = app.with_output(Box::new(tee));
+                app.list(&list_options);
+                let text = app
+                    .output
+                    .flush()
+                    .map_err(|e| e.to_string())
+                    .map(|_| String::from_utf8_lossy(&copy.lock().unwrap()).into_owned())
+                    .and_then(|text| SystemClipboard.set_text(&text));
+                if let Err(e) = text {
+                    eprintln!("{}", msg!("error.clipboard", error::pretty(&e)));
+                    return 1;
+                }
+            } else if list {
+                swap_output(&mut app.output, output::stdout(!opt.no_pager));
+                app.list(&list_options);
+                swap_output(&mut app.output, Box::new(io::stdout()));
+            }
+            if let Some(n) = sample {
+                if let Err(e) = app.sample(
+                    since,
+                    until,
+                    n,
+                    seed,
+                    detail.then(|| export::detail_template(all_senses)),
+                ) {
+                    eprintln!("{}", msg!("error.sample", error::pretty(&e)));
+                    failed = true;
+                }
+            }
+            if let Some(n) = quiz {
+                let result = match UserPrompt::open() {
+                    Ok(mut prompt) => app.quiz(since, until, n, seed, &mut prompt).await,
+                    Err(e) => Err(e.to_string()),
+                };
+                if let Err(e) = result {
+                    eprintln!("{}", msg!("error.quiz", error::pretty(&e)));
+                    failed = true;
+                }
+            }
+            let result = app.save().await;
+            *attached = attach_timings(
+                app.refresh_report.as_mut(),
+                &metrics,
+                opt.timings,
+                opt.format,
+            );
+            match refresh_exit_code(result, app.refresh_report.as_ref(), opt.format, opt.quiet) {
+                0 if failed => 1,
+                code => code,
             }
         }
         Some(SubCommand::Mm {
@@ -549,118 +1905,771 @@ async fn main() {
             notepad_id,
             timestamp,
             upload,
+            file,
             refresh,
             appending,
+            captcha,
+            raw,
+            strict,
+            fix,
+            update_brief,
+            timestamp_format,
+            header_template,
+            restore,
+            backup,
+            find_duplicates,
+            within,
+            ignore_case,
+            lemma,
+            search_public,
+            recommend,
+            limit,
+            sort,
+            clone_notepad,
+            title,
+            create,
+            delete,
+            diff,
+            update_local,
+            diff_max_hunks,
+            diff_full,
+            force,
+            prefetch_captcha,
         }) => {
+            if lemma {
+                if let Err(e) = lemma::check_supported() {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            }
+            if notepad_id.len() > 1 && !upload {
+                eprintln!("{}", msg!("notepad.multiple_ids"));
+                return 1;
+            }
+            let data_dir = config.get_data_dir().to_string();
             let config = config.maimemo();
-            let mut app = MaimemoApp::with_stdio(config, !refresh).await;
+            let backups = NotepadBackups::new(
+                &data_dir,
+                config.get_notepad_backups().unwrap_or(backup::DEFAULT_KEEP),
+            );
+            let captcha_command = match captcha_command(&config) {
+                Ok(command) => command,
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            };
+            let timestamp_format =
+                timestamp_format.or_else(|| config.get_timestamp_format().map(str::to_string));
+            let header_template =
+                header_template.or_else(|| config.get_header_template().map(str::to_string));
+            let brief_lines = if update_brief || config.is_update_brief() {
+                Some(
+                    config
+                        .get_brief_lines()
+                        .unwrap_or(maimemo_client::DEFAULT_BRIEF_LINES),
+                )
+            } else {
+                None
+            };
+            // 在登录等网络请求前检查
+            if let Some(Err(e)) = timestamp_format.as_deref().map(validate_timestamp_format) {
+                eprintln!("{}", error::pretty(&e));
+                return 1;
+            }
+            let refresh = match need_refresh(&config, refresh, opt.auto_refresh) {
+                Ok(refresh) => refresh,
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return EXIT_NO_LOCAL_DATA;
+                }
+            };
+            let mut app = match MaimemoApp::with_stdio(config, context, !refresh).await {
+                Ok(app) => app
+                    .with_captcha(captcha)
+                    .with_assume_yes(opt.yes)
+                    .with_force(force)
+                    .with_prefetch_captcha(prefetch_captcha)
+                    .with_upload_options(UploadOptions {
+                        raw,
+                        strict,
+                        fix,
+                        quiet: opt.quiet,
+                        timestamp_format,
+                        header_template,
+                        brief_lines,
+                    })
+                    .with_backups(backups)
+                    .with_captcha_command(captcha_command)
+                    .with_diff_view(
+                        color,
+                        Some(diff_max_hunks.unwrap_or(diff::DEFAULT_MAX_HUNKS))
+                            .filter(|_| !diff_full),
+                    ),
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            };
+            let mut upload_failed = false;
             if list {
-                if let Some(notepad_id) = notepad_id {
-                    app.list_contents(&notepad_id);
-                } else {
-                    app.list()
+                swap_output(&mut app.output, output::stdout(!opt.no_pager));
+                let listed = match notepad_id.first() {
+                    Some(notepad_id) => app.list_contents(notepad_id, opt.format),
+                    None => app.list(opt.format, sort.unwrap_or(NotepadSort::Updated), limit),
+                };
+                let listed = listed.and_then(|_| app.output.flush().map_err(|e| e.to_string()));
+                swap_output(&mut app.output, Box::new(io::stdout()));
+                if let Err(e) = listed {
+                    eprintln!("{}", msg!("error.list_notepads", error::pretty(&e)));
+                    return 1;
+                }
+            } else if upload {
+                let contents = match read_file_or_stdin(file.as_deref()) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        eprintln!("{}", error::pretty(&e));
+                        return 1;
+                    }
+                };
+                let results = app
+                    .upload_notepads(&contents, &notepad_id, appending, timestamp)
+                    .await;
+                upload_failed = results.iter().any(|(_, r)| r.is_err());
+                if results.len() > 1 || upload_failed {
+                    for (id, result) in &results {
+                        let line = match result {
+                            Ok(_) if opt.quiet => continue,
+                            Ok(true) => msg!("notepad.uploaded", id),
+                            Ok(false) => msg!("notepad.upload_skipped", id),
+                            Err(e) => msg!("notepad.upload_failed", id, error::pretty(e)),
+                        };
+                        eprintln!("{}", line);
+                    }
+                }
+            } else if restore {
+                let notepad_id = notepad_id.first().cloned().unwrap_or_default();
+                if let Err(e) = app.restore_notepad(&notepad_id, backup.as_deref()).await {
+                    eprintln!("{}", msg!("error.restore_notepad", error::pretty(&e)));
+                    return 1;
+                }
+            } else if let Some(keyword) = search_public {
+                let query = NotepadQuery::public(
+                    &keyword,
+                    recommend,
+                    limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+                );
+                if let Err(e) = app.search_public(&query, opt.format).await {
+                    eprintln!("{}", msg!("error.search_public", error::pretty(&e)));
+                    return 1;
+                }
+            } else if clone_notepad {
+                let (notepad_id, title) = (
+                    notepad_id.first().cloned().unwrap_or_default(),
+                    title.unwrap_or_default(),
+                );
+                match app.clone_notepad(&notepad_id, &title).await {
+                    Ok(Some(id)) => info!("cloned notepad {} to {}", notepad_id, id),
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.clone_notepad", error::pretty(&e)));
+                        return 1;
+                    }
+                }
+            } else if create {
+                let title = title.unwrap_or_default();
+                let created = match read_file_or_stdin(file.as_deref()) {
+                    Ok(contents) => app.create_notepad(&title, &contents).await,
+                    Err(e) => Err(e),
+                };
+                match created {
+                    Ok(id) => println!("{}", id),
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.create_notepad", error::pretty(&e)));
+                        return 1;
+                    }
+                }
+            } else if delete {
+                let notepad_id = notepad_id.first().cloned().unwrap_or_default();
+                match app.delete_notepad(&notepad_id).await {
+                    Ok(true) => info!("deleted notepad {}", notepad_id),
+                    Ok(false) => {}
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.delete_notepad", error::pretty(&e)));
+                        return 1;
+                    }
+                }
+            } else if diff {
+                let notepad_id = notepad_id.first().cloned().unwrap_or_default();
+                if let Err(e) = app
+                    .diff_notepad(&notepad_id, update_local, opt.format)
+                    .await
+                {
+                    eprintln!("{}", msg!("error.diff_notepad", error::pretty(&e)));
+                    return 1;
+                }
+            } else if find_duplicates {
+                let within = notepad_id.first().map(String::as_str).filter(|_| within);
+                if let Err(e) = app.find_duplicates(within, ignore_case, lemma, opt.format) {
+                    eprintln!("{}", msg!("error.find_duplicates", error::pretty(&e)));
+                    return 1;
                 }
-                return;
             }
-
-            if upload {
-                if let Some(notepad_id) = notepad_id {
-                    app.upload_notepad(io::stdin(), &notepad_id, appending, timestamp)
-                        .await;
+            let result = app.save().await;
+            *attached = attach_timings(
+                app.refresh_report.as_mut(),
+                &metrics,
+                opt.timings,
+                opt.format,
+            );
+            match refresh_exit_code(result, app.refresh_report.as_ref(), opt.format, opt.quiet) {
+                0 if upload_failed => 1,
+                code => code,
+            }
+        }
+        Some(SubCommand::Eu {
+            refresh,
+            list,
+            offset,
+            add,
+            delete,
+        }) => {
+            let config = match config.eudic() {
+                Ok(config) => config,
+                Err(_) => {
+                    eprintln!("{}", msg!("config.no_eudic", config_path));
+                    return 1;
+                }
+            };
+            // add与delete后会重新加载，此时不需要本地单词本
+            let refresh = match need_refresh(&config, refresh || add || delete, opt.auto_refresh) {
+                Ok(refresh) => refresh,
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return EXIT_NO_LOCAL_DATA;
+                }
+            };
+            let prompt = if delete && !opt.yes {
+                UserPrompt::open()
+                    .map_err(|e| debug!("no prompt for confirmation: {}", e))
+                    .ok()
+            } else {
+                None
+            };
+            let mut app = match EudicApp::new(
+                config,
+                context,
+                refresh && !add && !delete,
+                prompt,
+                io::stdout(),
+            )
+            .await
+            {
+                Ok(app) => app.with_assume_yes(opt.yes).with_quiet(opt.quiet),
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return 1;
+                }
+            };
+            if add {
+                match app.add(io::stdin()).await {
+                    Ok(n) => info!("added {} words to eudic", n),
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.eudic_add", error::pretty(&e)));
+                        return 1;
+                    }
+                }
+            } else if delete {
+                match app.delete(io::stdin()).await {
+                    Ok(n) => info!("deleted {} words from eudic", n),
+                    Err(e) => {
+                        eprintln!("{}", msg!("error.eudic_delete", error::pretty(&e)));
+                        return 1;
+                    }
                 }
-
-                return;
             }
+            if list {
+                swap_output(&mut app.output, output::stdout(!opt.no_pager));
+                let listed = app.list(offset, opt.format);
+                swap_output(&mut app.output, Box::new(io::stdout()));
+                if let Err(e) = listed {
+                    eprintln!("{}", msg!("error.list_words", error::pretty(&e)));
+                    return 1;
+                }
+            }
+            save_exit_code(app.save())
+        }
+        Some(SubCommand::Src {
+            name,
+            refresh,
+            list,
+            offset,
+        }) => {
+            let source = match config.custom_source(&name) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("{}", msg!("error.custom_source", name, e));
+                    return 1;
+                }
+            };
+            let config = source.to_app_config(client::generic_json_client::REQUEST_NAME);
+            let refresh = match need_refresh(&config, refresh, opt.auto_refresh) {
+                Ok(refresh) => refresh,
+                Err(e) => {
+                    eprintln!("{}", error::pretty(&e));
+                    return EXIT_NO_LOCAL_DATA;
+                }
+            };
+            let mut app = match SourceApp::new(&name, &source, context, refresh).await {
+                Ok(app) => app,
+                Err(e) => {
+                    eprintln!("{}", msg!("error.custom_source", name, error::pretty(&e)));
+                    return 1;
+                }
+            };
+            if list {
+                let mut stdout = output::stdout(!opt.no_pager);
+                if let Err(e) = app.list(&mut stdout, offset, opt.format) {
+                    eprintln!("{}", msg!("error.list_words", error::pretty(&e)));
+                    return 1;
+                }
+            }
+            save_exit_code(app.save())
+        }
+        Some(SubCommand::Lookup { words, add, clip }) => {
+            // 仅查询时不需要youdao的登录配置
+            let config = match config.youdao.take() {
+                Some(config) => config,
+                None if !add => Config::minimal().youdao(),
+                None => {
+                    eprintln!("{}", msg!("config.no_youdao", config_path));
+                    return 1;
+                }
+            };
+            let config = config.with_force_login(opt.force_login);
+            let mut client = match YoudaoClient::with_context(config, context) {
+                Ok(client) => client,
+                Err(e) => {
+                    eprintln!("{}", msg!("error.youdao_client", error::pretty(&e)));
+                    return 1;
+                }
+            };
+            let mut tee = Tee::new(io::stdout());
+            if let Err(e) =
+                lookup_word(&mut client, &words.join(" "), add, opt.format, &mut tee).await
+            {
+                eprintln!("{}", msg!("error.lookup", error::pretty(&e)));
+                return 1;
+            }
+            if clip {
+                let text = String::from_utf8_lossy(&tee.copy().lock().unwrap()).into_owned();
+                if let Err(e) = SystemClipboard.set_text(&text) {
+                    eprintln!("{}", msg!("error.clipboard", error::pretty(&e)));
+                    return 1;
+                }
+            }
+            0
+        }
+        Some(SubCommand::Run {
+            plan,
+            dry_run,
+            watch,
+            interval,
+        }) => {
+            let plan = match Plan::load(&plan).and_then(|p| p.check_config(&config).map(|_| p)) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    eprintln!("{}", msg!("error.plan", error::pretty(&e)));
+                    return 1;
+                }
+            };
+            if dry_run {
+                for (i, step) in plan.steps.iter().enumerate() {
+                    println!("{}. {}", i + 1, step);
+                }
+                return 0;
+            }
+            let runner = PlanRunner::new(config, context, opt.yes);
+            let interval = interval.as_deref();
+            run_or_watch_plan(&plan, runner, watch, interval, &cancel, opt.quiet).await
+        }
+        Some(SubCommand::Sync {
+            book,
+            source,
+            notepad,
+            timestamp,
+            lemma,
+            watch,
+            interval,
+        }) => {
+            let step = SyncStep {
+                book: book.unwrap_or_default(),
+                source,
+                notepad,
+                timestamp,
+                lemma,
+            };
+            let plan = match Plan::sync(step).and_then(|p| p.check_config(&config).map(|_| p)) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    eprintln!("{}", msg!("error.plan", error::pretty(&e)));
+                    return 1;
+                }
+            };
+            let runner = PlanRunner::new(config, context, opt.yes);
+            let interval = interval.as_deref();
+            run_or_watch_plan(&plan, runner, watch, interval, &cancel, opt.quiet).await
         }
         cmd => panic!("unsupported command: {:?}", cmd),
-    };
+    }
 }
 
 #[cfg(test)]
-mod maimemo_tests {
+mod cache_tests {
     use super::*;
-    const CONFIG_PATH: &'static str = "config.yml";
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(10), "10 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_age(59), "59s");
+        assert_eq!(format_age(2 * 60 * 60), "2h");
+        assert_eq!(format_age(3 * 24 * 60 * 60 + 1), "3d");
+    }
 
-    fn init_log() {
-        pretty_env_logger::formatted_builder()
-        .filter_module("dict", log::LevelFilter::Debug)
-        .init();
+    #[test]
+    fn cache_status_tsv_without_data_dir() {
+        let entries = [CacheEntry {
+            service: "youdao",
+            kind: CacheKind::Cookies,
+            path: "/data/youdao-cookies.json".to_string(),
+            exists: false,
+            size: None,
+            items: None,
+            modified: None,
+            age_secs: None,
+        }];
+        let tsv = format_cache_status("/data", &entries, None);
+        assert!(!tsv.contains("data dir"));
+        assert!(tsv.contains("youdao\tcookies\t/data/youdao-cookies.json\tnot found"));
+        let aligned = format_cache_status("/data", &entries, Some(80));
+        assert!(aligned.starts_with("data dir: /data\n"));
     }
 
-    async fn mocked_maimemo_data<'a>(
-        is_local: bool,
-    ) -> Result<(MaimemoApp<'a>, Vec<Notepad>), String> {
-        init_log();
-        let config = Config::from_yaml_file(CONFIG_PATH)?;
-        let notepads =
-            load_from_json_file::<Vec<Notepad>>(config.get_maimemo().get_dictionary_path()).await?;
-        let (input, output) = (io::Cursor::new(""), io::Cursor::new(Vec::new()));
-        Ok((
-            MaimemoApp::new(config.maimemo.unwrap(), is_local, input, output).await,
-            notepads,
-        ))
+    #[test]
+    fn auth_status_output() {
+        let status = |name: &str, present, expires_in_secs: Option<i64>| CookieStatus {
+            domain: "youdao.com".to_string(),
+            name: name.to_string(),
+            present,
+            expires: expires_in_secs.map(|_| "2026-10-17T00:00:00+00:00".to_string()),
+            expires_in_secs,
+        };
+        let now = 1_790_000_000;
+        let services = vec![
+            (
+                "yd",
+                Some(CookieOrigin::import(
+                    Some("firefox"),
+                    now - 43 * 24 * 60 * 60,
+                )),
+                vec![
+                    status("OUTFOX_SEARCH_USER_ID", true, None),
+                    status("DICT_PERS", true, Some(2 * 60 * 60)),
+                ],
+            ),
+            ("mm", None, vec![status("userToken", false, None)]),
+        ];
+        assert_eq!(
+            format_auth_status(&services, now, Some(80), false),
+            "service  cookie                 status
+mm       userToken              missing
+yd       DICT_PERS              expires 2026-10-17T00:00:00+00:00 (in 2h)
+yd       OUTFOX_SEARCH_USER_ID  session
+mm session: unknown origin
+yd session: imported from firefox 43d ago (2026-08-09T14:13:20+00:00)"
+        );
+        // 管道中输出tab分隔的文本
+        assert_eq!(
+            format_auth_status(&services, now, None, false)
+                .lines()
+                .nth(3),
+            Some("yd\tOUTFOX_SEARCH_USER_ID\tsession")
+        );
+        assert_eq!(
+            describe_origin(Some(&CookieOrigin::login(now - 90)), now),
+            "logged in 1m ago (2026-09-21T14:11:50+00:00)"
+        );
     }
 
     #[tokio::test]
-    async fn nofile_web_loading() -> Result<(), String> {
-        init_log();
-        let config = Config::from_yaml_file(CONFIG_PATH)?;
-        let remove_file_if_exists = |path: &str| -> Result<(), String> {
-            let path = std::path::Path::new(path);
-            if path.exists() {
-                debug!("removing file: {}", path.canonicalize().unwrap().to_str().unwrap());
-                fs::remove_file(path).map_err(|e| format!("{:?}", e))?;
-            }
-            Ok(())
-        };
-        remove_file_if_exists(config.get_maimemo().get_cookie_path().unwrap())?;
-        remove_file_if_exists(config.get_maimemo().get_dictionary_path())?;
-        MaimemoApp::with_stdio_web(config.maimemo.unwrap()).await;
+    async fn import_browser_cookies() -> Result<(), String> {
+        let server = mock_server::MockServer::start_recorded().await;
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut config = Config::from_yaml_file(&server.write_fixture_config(dir.path()))?;
+        let cookies = dir.path().join("cookies.txt");
+        let expires = Utc::now().timestamp() + 86400;
+        std::fs::write(
+            &cookies,
+            format!(
+                "# Netscape HTTP Cookie File\n\n\
+                 .youdao.com\tTRUE\t/\tFALSE\t0\tOUTFOX_SEARCH_USER_ID\tfixture-user-id\n\
+                 #HttpOnly_.youdao.com\tTRUE\t/\tFALSE\t{}\tDICT_PERS\tfixture-pers\n\
+                 .youdao.com\tTRUE\t/\tFALSE\t1000\tEXPIRED\tx\n",
+                expires
+            ),
+        )
+        .unwrap();
+        let imported = import_cookies(
+            &config,
+            cache::SERVICE_YOUDAO,
+            cookies.to_str().unwrap(),
+            Some("firefox"),
+        )?;
+        assert_eq!(imported, 2);
+        let cookie_path = config.get_youdao().get_cookie_path().unwrap().to_string();
+        let origin = CookieOrigin::load(&cookie_path).unwrap();
+        assert_eq!(origin.source, CookieSource::Import);
+        assert_eq!(origin.browser.as_deref(), Some("firefox"));
+
+        let e = import_cookies(&config, cache::SERVICE_YOUDAO, "not-found.txt", None).unwrap_err();
+        assert!(e.contains("not-found.txt"), "{}", e);
+        std::fs::write(&cookies, "youdao.com\tFALSE\t/\n").unwrap();
+        let e = import_cookies(
+            &config,
+            cache::SERVICE_YOUDAO,
+            cookies.to_str().unwrap(),
+            None,
+        )
+        .unwrap_err();
+        assert!(e.contains("line 1"), "{}", e);
+
+        // 导入的cookies可以直接使用，不需要登录
+        let context = Arc::new(HttpContext::from_config(&config.http())?);
+        let mut client = YoudaoClient::with_context(config.get_youdao().clone(), context)?;
+        assert!(client.has_logged());
+        client.get_words().await?;
+        assert!(server
+            .requests()
+            .iter()
+            .all(|r| !r.path.contains("/login/acc/login")));
+        assert_eq!(CookieOrigin::load(&cookie_path), Some(origin));
+
+        // 没有来源记录的cookies仅在auth status时被记录为之前保留的
+        std::fs::remove_file(CookieOrigin::path(&cookie_path)).unwrap();
+        YoudaoClient::new(config.get_youdao().clone())?;
+        assert_eq!(CookieOrigin::load(&cookie_path), None);
+        run_auth(
+            AuthCmd::Status,
+            &config,
+            OutputFormat::Json,
+            true,
+            ColorChoice::Never,
+        )?;
+        assert_eq!(
+            CookieOrigin::load(&cookie_path).map(|o| o.source),
+            Some(CookieSource::Previous)
+        );
         Ok(())
     }
 
-    #[tokio::test]
-    async fn list() -> Result<(), String> {
-        let (mut app, notepads) = mocked_maimemo_data(true).await?;
-        app.list();
-        let mut data = vec![];
-        notepads.iter().for_each(|n| {
-            let s = n.to_string() + "\n";
-            s.bytes().for_each(|b| data.push(b));
-        });
-        assert_eq!(data, app.output.buffer());
+    #[test]
+    fn request_preview_output() -> Result<(), String> {
+        // 删除lookup的配置，使用内置的默认请求
+        let contents = include_str!("../tests/fixtures/dict-config.yml")
+            .replace("        lookup:", "        unused-lookup:");
+        let config: Config = serde_yaml::from_str(&contents).map_err(|e| e.to_string())?;
+        let http = HttpConfig::default().with_user_agent("dict-test");
+        let context = HttpContext::from_config(&http)?;
+        let preview = preview_service_request(&config, &context, "mm", "notepad-save", true)?;
+        assert_eq!(
+            format_request_preview(&preview),
+            "POST http://www.maimemo.com/notepad/save
+accept: application/json, text/javascript, */*; q=0.01
+content-type: application/x-www-form-urlencoded; charset=UTF-8
+user-agent: dict-test
+
+# cookies: none
+
+id=695835&title=sample&brief=&content=hello%0Aworld%0A&is_private=true&captcha=abcd"
+        );
+        let preview = preview_service_request(&config, &context, "yd", "lookup", false)?;
+        assert_eq!(
+            format_request_preview(&preview),
+            "GET https://dict.youdao.com/jsonapi
+accept: application/json, text/plain, */*
+user-agent: dict-test
+
+# cookies: none"
+        );
         Ok(())
     }
 
     #[tokio::test]
-    async fn list_contents() -> Result<(), String> {
-        let (mut app, notepads) = mocked_maimemo_data(true).await?;
-        let notepad_id = "695835";
-        app.list_contents(notepad_id);
-        let mut data = vec![];
-        notepads
-            .iter()
-            .find(|n| n.get_notepad_id() == notepad_id)
-            .and_then(|n| n.get_contents())
-            .map(|s| s.bytes().for_each(|b| data.push(b)));
-        data.push('\n' as u8);
-        assert_eq!(data, app.output.buffer());
-        Ok(())
+    async fn doctor_output() {
+        let checks = doctor_checks(Err("not found config file".to_string()), true, true).await;
+        assert_eq!(checks, vec![Check::fail("config", "not found config file")]);
+
+        let checks = vec![
+            Check::pass("config", "dict-config.yml"),
+            Check::warn("youdao cookies", "not logged in yet"),
+            Check::fail("maimemo dictionary_path", "directory not found"),
+        ];
+        assert_eq!(
+            format_doctor(&checks, Some(80), false),
+            "status  check                    detail
+pass    config                   dict-config.yml
+warn    youdao cookies           not logged in yet
+FAIL    maimemo dictionary_path  directory not found
+1 passed, 1 warnings, 1 failed"
+        );
+    }
+
+    #[test]
+    fn check_user_agent() {
+        let config = serde_yaml::from_str::<Config>(
+            "youdao:
+  dictionary_path: youdao.json
+  requests:
+    login:
+      url: https://dict.test/login
+      method: POST
+      headers:
+        User-Agent: ''
+maimemo:
+  dictionary_path: maimemo.json
+",
+        )
+        .unwrap();
+        // 默认使用内置的UA，请求配置中为空时不发送
+        let warnings = check_config(&config, &HttpConfig::default());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("youdao login"), "{}", warnings[0]);
+
+        let http = HttpConfig::default().with_user_agent("");
+        assert_eq!(check_config(&config, &http).len(), 2);
     }
 
     #[tokio::test]
-    async fn maimemo_save() -> Result<(), String> {
-        pretty_env_logger::formatted_builder()
-            .filter_module("dict", log::LevelFilter::Trace)
-            .init();
-        let config = Config::from_yaml_file(CONFIG_PATH)?;
-        let (input, output) = (io::stdin(), io::Cursor::new(Vec::new()));
-        let mut app = MaimemoApp::new(config.maimemo.unwrap(), true, input, output).await;
-
-        let notepad_id = "695835";
-        let contents_read = io::Cursor::new("test words");
-        app.upload_notepad(contents_read, notepad_id, false, false)
-            .await;
+    async fn no_config_commands() {
+        let opt =
+            |args: &[&str]| AppOpt::from_iter(std::iter::once("dict").chain(args.iter().copied()));
+        assert!(supports_no_config(opt(&["lookup", "run"]).sub_cmd.as_ref()));
+        assert!(!supports_no_config(
+            opt(&["lookup", "-a", "run"]).sub_cmd.as_ref()
+        ));
+        assert!(!supports_no_config(
+            opt(&["cache", "status"]).sub_cmd.as_ref()
+        ));
+        // 不需要$HOME与配置文件
+        assert_eq!(
+            run(
+                opt(&["--no-config", "cache", "path"]),
+                CancellationToken::new()
+            )
+            .await,
+            0
+        );
+        assert_eq!(
+            run(opt(&["--no-config", "yd", "-l"]), CancellationToken::new()).await,
+            1
+        );
+        assert!(AppOpt::from_iter_safe(&[
+            "dict",
+            "--no-config",
+            "--config-path",
+            "a.yml",
+            "cache",
+            "path"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn sync_command_args() {
+        let opt = |args: &[&str]| {
+            AppOpt::from_iter_safe(std::iter::once("dict").chain(args.iter().copied()))
+        };
+        let cmd = opt(&["sync", "--book", "GRE", "--notepad", "695835", "--watch"])
+            .unwrap()
+            .sub_cmd;
+        assert!(matches!(
+            cmd,
+            Some(SubCommand::Sync { book: Some(book), watch: true, interval: None, .. })
+                if book == "GRE"
+        ));
+        // book与source必须且只能指定一个
+        assert!(opt(&["sync", "--notepad", "695835"]).is_err());
+        assert!(opt(&[
+            "sync",
+            "--book",
+            "GRE",
+            "--source",
+            "vocab",
+            "--notepad",
+            "1"
+        ])
+        .is_err());
+        assert!(opt(&[
+            "sync",
+            "--source",
+            "vocab",
+            "--notepad",
+            "1",
+            "--interval",
+            "1h"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn date_alias_args() {
+        let opt = AppOpt::from_iter(&[
+            "dict",
+            "yd",
+            "--start-date",
+            "2024-05-01",
+            "--end-date",
+            "2024-05-02",
+        ]);
+        let (since, until) = match opt.sub_cmd {
+            Some(SubCommand::Yd {
+                since: Some(since),
+                until: Some(until),
+                ..
+            }) => (since, until),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        };
+        // 旧的别名与--since/--until相同，日期为now所在时区的0点
+        let east8 = chrono::FixedOffset::east_opt(8 * 60 * 60).unwrap();
+        let now = Utc::now().with_timezone(&east8);
+        let bound = |s: &str| YoudaoApp::parse_time_bound(s, now).unwrap().to_rfc3339();
+        assert_eq!(bound(&since), "2024-04-30T16:00:00+00:00");
+        assert_eq!(bound(&until), "2024-05-01T16:00:00+00:00");
+    }
+
+    #[test]
+    fn offline_env() {
+        let opt = AppOpt::from_iter(&["dict", "cache", "path"]);
+        assert!(!opt.offline(None));
+        assert!(!opt.offline(Some("0")));
+        assert!(!opt.offline(Some(" false")));
+        assert!(opt.offline(Some("1")));
+        assert!(AppOpt::from_iter(&["dict", "--offline", "cache", "path"]).offline(Some("0")));
+    }
+}
+
+#[cfg(test)]
+mod store_tests {
+    use super::*;
+
+    #[test]
+    fn store_info_output() -> Result<(), String> {
+        let mut output = vec![];
+        print_store_info("tests/fixtures/youdao-words-v1.json", &mut output)?;
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "path: tests/fixtures/youdao-words-v1.json\nversion: 1\nitems: 3\nsaved_at: -\n"
+        );
         Ok(())
     }
 }
@@ -0,0 +1,638 @@
+//! CLI输出的消息目录。
+//!
+//! 语言由`--lang`、`DICT_LANG`或系统locale(`LC_ALL`、`LC_MESSAGES`、`LANG`)依次决定，
+//! 默认为英文。消息中的`{}`按顺序被参数替换，使用[`msg!`](crate::msg!)获取
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 指定语言的环境变量，优先于系统locale
+pub const LANG_ENV: &str = "DICT_LANG";
+
+/// 系统locale的环境变量，按优先级排序
+pub const LOCALE_ENVS: [&str; 3] = ["LC_ALL", "LC_MESSAGES", "LANG"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    ZhCn,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    /// 支持`en`、`zh-CN`与locale格式如`zh_CN.UTF-8`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lang = s
+            .split(['.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+            .replace('_', "-");
+        match lang.as_str() {
+            "en" | "c" | "posix" => Ok(Lang::En),
+            "zh" | "zh-cn" | "zh-hans" | "zh-sg" => Ok(Lang::ZhCn),
+            l if l.starts_with("en-") => Ok(Lang::En),
+            _ => Err(format!("unsupported language: {}", s)),
+        }
+    }
+}
+
+impl Lang {
+    /// 参数优先，其次为`DICT_LANG`，最后为系统locale。空的或不支持的环境变量被忽略，
+    /// 都不可用时为英文
+    pub fn resolve(opt: Option<Lang>, env: impl Fn(&str) -> Option<String>) -> Lang {
+        opt.or_else(|| {
+            std::iter::once(LANG_ENV)
+                .chain(LOCALE_ENVS)
+                .filter_map(|k| env(k).filter(|v| !v.is_empty()))
+                .find_map(|v| v.parse().ok())
+        })
+        .unwrap_or(Lang::En)
+    }
+
+    fn messages(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => EN,
+            Lang::ZhCn => ZH_CN,
+        }
+    }
+}
+
+/// 当前的语言，在main中设置一次
+static LANG: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_lang(lang: Lang) {
+    LANG.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match LANG.load(Ordering::Relaxed) {
+        1 => Lang::ZhCn,
+        _ => Lang::En,
+    }
+}
+
+/// 当前语言中key的消息。缺失时使用英文，仍不存在时返回key
+pub fn get(key: &'static str) -> &'static str {
+    lookup(lang(), key)
+        .or_else(|| lookup(Lang::En, key))
+        .unwrap_or(key)
+}
+
+fn lookup(lang: Lang, key: &str) -> Option<&'static str> {
+    lang.messages()
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| *v)
+}
+
+/// 替换template中的`{}`与`{n}`：`{}`按顺序使用参数，`{n}`使用第n个参数以调整语序。
+/// 缺少的参数保留原样
+pub fn format(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let placeholder = rest[start..]
+            .find('}')
+            .map(|end| &rest[start..start + end + 1])
+            .filter(|p| p[1..p.len() - 1].bytes().all(|b| b.is_ascii_digit()));
+        let placeholder = match placeholder {
+            Some(p) => p,
+            None => {
+                out.push('{');
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        let index = match &placeholder[1..placeholder.len() - 1] {
+            "" => {
+                next += 1;
+                next - 1
+            }
+            i => i.parse().unwrap_or(usize::MAX),
+        };
+        match args.get(index) {
+            Some(arg) => out.push_str(&arg.to_string()),
+            None => out.push_str(placeholder),
+        }
+        rest = &rest[start + placeholder.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 英文目录中是否有key，用于[`msg!`](crate::msg!)在编译时检查key
+#[doc(hidden)]
+pub const fn has_key(key: &str) -> bool {
+    let mut i = 0;
+    while i < EN.len() {
+        if str_eq(EN[i].0, key) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// 格式化当前语言中的消息。key不在目录中时编译失败
+///
+/// ```
+/// assert_eq!(dict::msg!("cache.removed", 2), "removed 2 files");
+/// ```
+///
+/// ```compile_fail
+/// dict::msg!("no.such.key");
+/// ```
+#[macro_export]
+macro_rules! msg {
+    (@check $key:literal) => {
+        const _: () = assert!(
+            $crate::msg::has_key($key),
+            concat!("missing message: ", $key)
+        );
+    };
+    ($key:literal) => {{
+        $crate::msg!(@check $key);
+        $crate::msg::get($key).to_string()
+    }};
+    ($key:literal, $($arg:expr),+ $(,)?) => {{
+        $crate::msg!(@check $key);
+        $crate::msg::format($crate::msg::get($key), &[$(&$arg as &dyn ::std::fmt::Display),+])
+    }};
+}
+
+static EN: &[(&str, &str)] = &[
+    ("aborted", "aborted"),
+    ("warning", "warning: {}"),
+    (
+        "interrupted",
+        "interrupted, saving the fetched data. press ctrl-c again to exit immediately",
+    ),
+    ("hint", "hint: {}"),
+    ("caused_by", "caused by: {}"),
+    ("dict_error.not_logged_in", "not logged in"),
+    ("dict_error.login_failed", "login failed"),
+    ("dict_error.wrong_credentials", "the username or password was rejected"),
+    ("dict_error.login_captcha", "login requires completing a captcha"),
+    ("dict_error.login_rate_limited", "too many login attempts"),
+    ("dict_error.wrong_captcha", "the captcha was wrong or expired"),
+    ("dict_error.config_missing", "request '{}' is not configured"),
+    ("dict_error.offline", "network access is disabled in offline mode"),
+    ("dict_error.read_only", "modifications are disabled in read-only mode"),
+    ("dict_error.network", "could not connect to the server"),
+    ("dict_error.http_status", "the server responded with {}"),
+    ("dict_error.response_too_large", "the server response is too large"),
+    ("dict_error.cancelled", "interrupted by ctrl-c"),
+    ("dict_error.prompt_timeout", "no input was given before the prompt timed out"),
+    ("hint.relogin", "run with -r to re-login"),
+    ("hint.check_credentials", "check username and password in the config, or run `dict doctor`"),
+    ("hint.md5_password", "password in the config must be the md5 hash of the password, not the plain text"),
+    (
+        "hint.browser_captcha",
+        "youdao requires completing a captcha in the browser. login there, export cookies.txt and run `dict auth import --service yd <file>`",
+    ),
+    ("hint.login_later", "wait a while before logging in again. frequent logins may get the account blacklisted"),
+    ("hint.captcha_again", "upload again and enter the new captcha, or pass it with --captcha"),
+    ("hint.add_request", "add a '{}' entry under the service's requests or upgrade to built-in defaults"),
+    ("hint.disable_offline", "run without --offline and unset DICT_OFFLINE"),
+    ("hint.disable_read_only", "run without --read-only and remove read_only from the config"),
+    ("hint.check_network", "check the network or http.proxy, or use --offline with local data"),
+    ("hint.rate_limited", "the server is throttling requests, try again later"),
+    ("hint.max_response_bytes", "raise max_response_bytes of the request in the config"),
+    ("hint.run_again", "the fetched data was saved. run the command again to continue"),
+    ("hint.prompt_timeout", "run the command again or increase --prompt-timeout"),
+    ("prompt.try_again", "{}, please try again"),
+    ("prompt.confirm_required", "interactive confirmation required; use --yes. {}"),
+    ("prompt.input_required", "interactive input required; use --captcha/--yes"),
+    ("prompt.captcha", "please enter captcha: "),
+    ("prompt.retrying_in", "retrying in"),
+    ("prompt.upload_retry", "upload error: {}. \nDo you want to try again?"),
+    ("prompt.throttled_retry", "upload throttled by maimemo: {}. \nWait {}s and try again?"),
+    ("prompt.read_error", "read user input error: {}"),
+    ("confirm.delete_files", "this will DELETE {} files"),
+    ("confirm.replace_notepad", "this will REPLACE notepad '{}' ({} lines) with {} new lines"),
+    ("confirm.remove_cached_notepad", "this will REMOVE notepad '{}' from the local cache"),
+    ("confirm.replace_cached_notepad", "this will REPLACE the cached notepad '{}' with the remote version"),
+    ("confirm.add_cached_notepad", "this will ADD notepad '{}' to the local cache"),
+    ("confirm.delete_notepad", "this will DELETE notepad '{}' ({} lines) from maimemo"),
+    ("confirm.delete_eudic_words", "this will DELETE {} words from eudic study list '{}'"),
+    ("quiz.known", "known?"),
+    ("quiz.score", "known {}/{} ({}%)"),
+    ("book.not_found", "not found youdao book: {}"),
+    ("book.no_words", "no words in book '{}' to move"),
+    ("book.move_failed", "move {} to book '{}' failed: {}"),
+    ("youdao.add_failed", "add {} to youdao failed: {}"),
+    ("cache.nothing_to_remove", "no cache files to remove"),
+    ("cache.removed", "removed {} files"),
+    ("auth.imported", "imported {} cookies for {}"),
+    ("config.ok", "config ok"),
+    ("config.no_config_unsupported", "--no-config only supports lookup without --add, store info and cache path"),
+    ("config.tls_insecure", "WARNING: tls certificate verification is disabled (http.tls.danger_accept_invalid_certs). Only use it for debugging, e.g. with mitmproxy"),
+    ("config.no_eudic", "not found eudic config in: {}"),
+    ("config.no_youdao", "not found youdao config in: {}"),
+    ("config.migrate_requires_sqlite", "migrate store requires a sqlite dictionary_path or `storage: sqlite`"),
+    ("data.no_local", "no local data yet in {}, run with -r/--refresh or --auto-refresh"),
+    ("notepad.not_logged_in", "Not logged in. please use -r refresh"),
+    ("notepad.no_backups", "not found any backup of notepad: {}"),
+    ("notepad.no_public", "not found any public notepad"),
+    (
+        "notepad.open_api_unsupported",
+        "{} is not supported by the maimemo open api. remove api_token to use the web login",
+    ),
+    (
+        "notepad.open_api_required",
+        "{} requires the maimemo open api. please set api_token in the maimemo config",
+    ),
+    ("notepad.cache_decode", "failed to read the maimemo cache file, run with -r to fix it"),
+    ("notepad.multiple_ids", "multiple --id are only supported with --upload"),
+    ("notepad.title_empty", "notepad title is empty"),
+    ("notepad.build_error", "build notepad error: {}"),
+    ("notepad.backup_error", "backup notepad error: {}"),
+    ("notepad.upload_error", "upload error: {}"),
+    ("notepad.uploaded", "{}: uploaded"),
+    ("notepad.upload_skipped", "{}: skipped"),
+    ("notepad.upload_failed", "{}: failed: {}"),
+    ("upload.fixed_lines", "fixed {} line(s):\n{}"),
+    ("upload.lint_warning", "warning: found characters maimemo strips in {} line(s), use --fix to normalize them:\n{}"),
+    ("upload.phrases", "{} lines look like phrases: {}"),
+    ("error.save", "save error: {}"),
+    ("error.logging", "logging error: {}"),
+    ("error.store_info", "store info error: {}"),
+    ("error.serialize", "serialize error: {}"),
+    ("error.load_config", "load config error: {}, path: {}"),
+    ("error.http_config", "http config error: {}"),
+    ("error.record", "record error: {}"),
+    ("record.replay_conflict", "--record or DICT_RECORD_DIR cannot be used with --replay"),
+    ("error.cache", "cache error: {}"),
+    ("error.auth", "auth error: {}"),
+    ("error.config", "config error: {}"),
+    ("error.debug", "debug error: {}"),
+    ("error.tag_word", "tag word error: {}"),
+    ("error.list_words", "list words error: {}"),
+    ("error.migrate_store", "migrate store error: {}"),
+    ("error.import_csv", "import csv error: {}"),
+    ("error.import_txt", "import txt error: {}"),
+    ("error.enrich", "enrich words error: {}"),
+    ("error.export_csv", "export csv error: {}"),
+    ("error.plan", "plan error: {}"),
+    ("diff.summary", "{} {} lines in {} hunk(s)"),
+    ("diff.more_hunks", "... {} more hunk(s). use --diff-full to show all"),
+    ("plan.step", "[{}/{}] {}"),
+    ("plan.step_failed", "step {} failed: {}"),
+    ("plan.continue", "continuing by continue_on_error"),
+    (
+        "watch.staged",
+        "{} new words for notepad {} are staged to {} because captcha_command is not configured. upload them with: {}",
+    ),
+    ("watch.next_round", "next round in {} ({} consecutive failures)"),
+    ("error.export_anki", "export anki error: {}"),
+    ("error.push_anki", "push anki error: {}"),
+    ("error.restore_notepad", "restore notepad error: {}"),
+    ("error.search_public", "search public notepads error: {}"),
+    ("error.clone_notepad", "clone notepad error: {}"),
+    ("error.create_notepad", "create notepad error: {}"),
+    ("error.delete_notepad", "delete notepad error: {}"),
+    ("error.diff_notepad", "diff notepad error: {}"),
+    ("error.find_duplicates", "find duplicates error: {}"),
+    ("error.list_notepads", "list notepads error: {}"),
+    ("error.eudic_add", "eudic add words error: {}"),
+    ("error.eudic_delete", "eudic delete words error: {}"),
+    ("error.custom_source", "custom source {} error: {}"),
+    ("error.youdao_client", "youdao client new failed. {}"),
+    ("error.lookup", "lookup error: {}"),
+    ("error.youdao_add", "add words to youdao error: {}"),
+    ("error.events", "open events output error: {}"),
+    ("error.clipboard", "clipboard error: {}"),
+    ("error.sample", "sample words error: {}"),
+    ("error.quiz", "quiz error: {}"),
+    ("error.list_books", "list books error: {}"),
+    ("error.move_book", "move book error: {}"),
+];
+
+static ZH_CN: &[(&str, &str)] = &[
+    ("aborted", "已取消"),
+    ("warning", "警告：{}"),
+    (
+        "interrupted",
+        "已中断，正在保存已获取的数据。再次按ctrl-c立即退出",
+    ),
+    ("hint", "提示：{}"),
+    ("caused_by", "原因：{}"),
+    ("dict_error.not_logged_in", "未登录"),
+    ("dict_error.login_failed", "登录失败"),
+    ("dict_error.wrong_credentials", "用户名或密码错误"),
+    ("dict_error.login_captcha", "登录需要完成验证码"),
+    ("dict_error.login_rate_limited", "登录尝试过多"),
+    ("dict_error.wrong_captcha", "验证码错误或已过期"),
+    ("dict_error.config_missing", "没有配置请求'{}'"),
+    ("dict_error.offline", "离线模式下禁止访问网络"),
+    ("dict_error.read_only", "只读模式下禁止修改"),
+    ("dict_error.network", "无法连接到服务器"),
+    ("dict_error.http_status", "服务器响应{}"),
+    ("dict_error.response_too_large", "服务器的响应过大"),
+    ("dict_error.cancelled", "已通过ctrl-c中断"),
+    ("dict_error.prompt_timeout", "提示在超时前没有输入"),
+    ("hint.relogin", "使用-r重新登录"),
+    (
+        "hint.check_credentials",
+        "检查配置中的username与password，或运行`dict doctor`",
+    ),
+    (
+        "hint.md5_password",
+        "配置中的password必须是密码的md5值，不能是明文",
+    ),
+    (
+        "hint.browser_captcha",
+        "youdao要求在浏览器中完成验证码。在浏览器中登录后导出cookies.txt，使用`dict auth import --service yd <file>`导入",
+    ),
+    (
+        "hint.login_later",
+        "请等待一段时间后再登录，频繁登录可能导致帐号被加入黑名单",
+    ),
+    (
+        "hint.captcha_again",
+        "重新上传并输入新的验证码，或通过--captcha指定",
+    ),
+    (
+        "hint.add_request",
+        "在服务的requests中添加'{}'，或升级使用内置的默认配置",
+    ),
+    ("hint.disable_offline", "不使用--offline并取消DICT_OFFLINE"),
+    (
+        "hint.disable_read_only",
+        "不使用--read-only并删除配置中的read_only",
+    ),
+    (
+        "hint.check_network",
+        "检查网络或http.proxy，或使用--offline查看本地数据",
+    ),
+    ("hint.rate_limited", "服务器限制了请求频率，请稍后再试"),
+    (
+        "hint.max_response_bytes",
+        "在配置中增大请求的max_response_bytes",
+    ),
+    ("hint.run_again", "已获取的数据已保存，再次执行命令继续"),
+    ("hint.prompt_timeout", "再次执行命令或增加--prompt-timeout"),
+    ("prompt.try_again", "{}，请重新输入"),
+    ("prompt.confirm_required", "需要交互确认，请使用--yes。{}"),
+    (
+        "prompt.input_required",
+        "需要交互输入，请使用--captcha/--yes",
+    ),
+    ("prompt.captcha", "请输入验证码："),
+    ("prompt.retrying_in", "重试倒计时"),
+    ("prompt.upload_retry", "上传错误：{}。\n是否重试？"),
+    ("prompt.read_error", "读取用户输入错误：{}"),
+    (
+        "prompt.throttled_retry",
+        "上传被maimemo限流：{}。\n是否等待{}秒后重试？",
+    ),
+    ("confirm.delete_files", "将删除{}个文件"),
+    (
+        "confirm.replace_notepad",
+        "将用{2}行新内容替换notepad '{0}'（{1}行）",
+    ),
+    (
+        "confirm.remove_cached_notepad",
+        "将从本地缓存中移除notepad '{}'",
+    ),
+    (
+        "confirm.replace_cached_notepad",
+        "将用远程版本替换本地缓存的notepad '{}'",
+    ),
+    ("confirm.add_cached_notepad", "将添加notepad '{}'到本地缓存"),
+    (
+        "confirm.delete_notepad",
+        "将从maimemo中删除notepad '{}'（{}行）",
+    ),
+    (
+        "confirm.delete_eudic_words",
+        "将从eudic生词本'{1}'中删除{0}个单词",
+    ),
+    ("quiz.known", "认识吗？"),
+    ("quiz.score", "认识{}/{}（{}%）"),
+    ("book.not_found", "没有找到youdao单词本：{}"),
+    ("book.no_words", "单词本'{}'中没有要移动的单词"),
+    ("book.move_failed", "移动{}到单词本'{}'失败：{}"),
+    ("youdao.add_failed", "添加{}到youdao失败：{}"),
+    ("cache.nothing_to_remove", "没有可删除的缓存文件"),
+    ("cache.removed", "已删除{}个文件"),
+    ("auth.imported", "已为{1}导入{0}个cookies"),
+    ("config.ok", "配置正常"),
+    (
+        "config.no_config_unsupported",
+        "--no-config仅支持不带--add的lookup、store info与cache path",
+    ),
+    (
+        "config.tls_insecure",
+        "警告：已禁用tls证书校验(http.tls.danger_accept_invalid_certs)，仅用于mitmproxy等调试",
+    ),
+    ("config.no_eudic", "未在配置中找到eudic：{}"),
+    ("config.no_youdao", "未在配置中找到youdao：{}"),
+    (
+        "config.migrate_requires_sqlite",
+        "迁移store需要sqlite的dictionary_path或`storage: sqlite`",
+    ),
+    (
+        "data.no_local",
+        "{}中还没有本地数据，请使用-r/--refresh或--auto-refresh",
+    ),
+    ("notepad.not_logged_in", "未登录，请使用-r refresh"),
+    ("notepad.no_backups", "没有找到notepad的备份：{}"),
+    ("notepad.no_public", "没有找到公开的notepad"),
+    (
+        "notepad.open_api_unsupported",
+        "maimemo open api不支持{}，删除api_token后使用网页登录",
+    ),
+    (
+        "notepad.open_api_required",
+        "{}需要maimemo open api，请在maimemo配置中设置api_token",
+    ),
+    ("notepad.cache_decode", "读取mm缓存文件错误，请用-r修复"),
+    ("notepad.multiple_ids", "仅在--upload时可以指定多个--id"),
+    ("notepad.title_empty", "notepad标题为空"),
+    ("notepad.build_error", "生成notepad错误：{}"),
+    ("notepad.backup_error", "备份notepad错误：{}"),
+    ("notepad.upload_error", "上传错误：{}"),
+    ("notepad.uploaded", "{}：已上传"),
+    ("notepad.upload_skipped", "{}：已跳过"),
+    ("notepad.upload_failed", "{}：失败：{}"),
+    ("upload.fixed_lines", "已修复{}行：\n{}"),
+    (
+        "upload.lint_warning",
+        "警告：{}行包含maimemo会移除的字符，使用--fix规范化：\n{}",
+    ),
+    ("upload.phrases", "{}行像是短语：{}"),
+    ("error.save", "保存错误：{}"),
+    ("error.logging", "日志错误：{}"),
+    ("error.store_info", "读取store信息错误：{}"),
+    ("error.serialize", "序列化错误：{}"),
+    ("error.load_config", "加载配置错误：{}，路径：{}"),
+    ("error.http_config", "http配置错误：{}"),
+    ("error.record", "录制错误：{}"),
+    (
+        "record.replay_conflict",
+        "--record或DICT_RECORD_DIR不能与--replay同时使用",
+    ),
+    ("error.cache", "缓存错误：{}"),
+    ("error.auth", "认证错误：{}"),
+    ("error.config", "配置错误：{}"),
+    ("error.debug", "调试错误：{}"),
+    ("error.tag_word", "标记单词错误：{}"),
+    ("error.list_words", "显示单词错误：{}"),
+    ("error.migrate_store", "迁移store错误：{}"),
+    ("error.import_csv", "导入csv错误：{}"),
+    ("error.import_txt", "导入txt错误：{}"),
+    ("error.enrich", "补全单词错误：{}"),
+    ("error.export_csv", "导出csv错误：{}"),
+    ("error.plan", "plan错误：{}"),
+    ("diff.summary", "{} {}行，共{}处修改"),
+    (
+        "diff.more_hunks",
+        "... 还有{}处修改未显示，使用--diff-full显示全部",
+    ),
+    ("plan.step", "[{}/{}] {}"),
+    ("plan.step_failed", "步骤{}失败：{}"),
+    ("plan.continue", "已设置continue_on_error，继续执行"),
+    (
+        "watch.staged",
+        "未配置captcha_command，notepad {1}的{0}个新单词已暂存到{2}。上传：{3}",
+    ),
+    ("watch.next_round", "{}后执行下一轮（连续失败{}次）"),
+    ("error.export_anki", "导出anki错误：{}"),
+    ("error.push_anki", "推送anki错误：{}"),
+    ("error.restore_notepad", "恢复notepad错误：{}"),
+    ("error.search_public", "搜索公开notepad错误：{}"),
+    ("error.clone_notepad", "复制notepad错误：{}"),
+    ("error.create_notepad", "创建notepad错误：{}"),
+    ("error.delete_notepad", "删除notepad错误：{}"),
+    ("error.diff_notepad", "比较notepad错误：{}"),
+    ("error.find_duplicates", "查找重复单词错误：{}"),
+    ("error.list_notepads", "显示notepads错误：{}"),
+    ("error.eudic_add", "eudic添加单词错误：{}"),
+    ("error.eudic_delete", "eudic删除单词错误：{}"),
+    ("error.custom_source", "自定义来源{}错误：{}"),
+    ("error.youdao_client", "创建youdao client失败：{}"),
+    ("error.lookup", "查询错误：{}"),
+    ("error.youdao_add", "添加单词到youdao错误：{}"),
+    ("error.events", "打开事件输出错误：{}"),
+    ("error.clipboard", "剪贴板错误：{}"),
+    ("error.sample", "抽取单词错误：{}"),
+    ("error.quiz", "测验错误：{}"),
+    ("error.list_books", "显示单词本错误：{}"),
+    ("error.move_book", "移动单词本错误：{}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// template需要的参数数量
+    fn arg_count(template: &str) -> usize {
+        let mut next = 0;
+        let mut count = 0;
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            match &rest[start + 1..end] {
+                "" => {
+                    next += 1;
+                    count = count.max(next);
+                }
+                i => {
+                    if let Ok(i) = i.parse::<usize>() {
+                        count = count.max(i + 1);
+                    }
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+        count
+    }
+
+    fn keys(lang: Lang) -> Vec<&'static str> {
+        lang.messages().iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn catalogs_have_same_keys() {
+        for lang in [Lang::En, Lang::ZhCn] {
+            let keys = keys(lang);
+            let unique = keys.iter().collect::<HashSet<_>>();
+            assert_eq!(unique.len(), keys.len(), "duplicate keys in {:?}", lang);
+        }
+        let en = keys(Lang::En).into_iter().collect::<HashSet<_>>();
+        let zh = keys(Lang::ZhCn).into_iter().collect::<HashSet<_>>();
+        assert_eq!(en.difference(&zh).collect::<Vec<_>>(), Vec::<&&str>::new());
+        assert_eq!(zh.difference(&en).collect::<Vec<_>>(), Vec::<&&str>::new());
+        for (key, en) in EN {
+            let zh = lookup(Lang::ZhCn, key).unwrap();
+            assert_eq!(arg_count(en), arg_count(zh), "arguments of {}", key);
+        }
+    }
+
+    #[test]
+    fn format_args() {
+        assert_eq!(format("a {} b {}", &[&1, &"x"]), "a 1 b x");
+        assert_eq!(format("{1}, {0}", &[&1, &2]), "2, 1");
+        assert_eq!(format("{} {} {x}", &[&1]), "1 {} {x}");
+        assert_eq!(arg_count("{2} '{0}' {1}"), 3);
+        assert_eq!(arg_count("{} {}"), 2);
+    }
+
+    #[test]
+    fn resolve_lang() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |k: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == k)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert_eq!(Lang::resolve(None, env(&[])), Lang::En);
+        assert_eq!(
+            Lang::resolve(None, env(&[("LANG", "zh_CN.UTF-8")])),
+            Lang::ZhCn
+        );
+        // 不支持的locale被忽略
+        assert_eq!(
+            Lang::resolve(
+                None,
+                env(&[("LC_ALL", "fr_FR.UTF-8"), ("LANG", "zh_CN.UTF-8")])
+            ),
+            Lang::ZhCn
+        );
+        assert_eq!(
+            Lang::resolve(None, env(&[("DICT_LANG", "en"), ("LANG", "zh_CN.UTF-8")])),
+            Lang::En
+        );
+        assert_eq!(
+            Lang::resolve(Some(Lang::ZhCn), env(&[("DICT_LANG", "en")])),
+            Lang::ZhCn
+        );
+        assert_eq!("C".parse::<Lang>(), Ok(Lang::En));
+        assert!("fr".parse::<Lang>().is_err());
+    }
+}
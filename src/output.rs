@@ -0,0 +1,250 @@
+//! 命令的数据输出，如单词与notepads的列表。stdout为终端且输出超过终端高度时通过分页程序输出
+//!
+//! 验证码、确认等交互的提示通过[`crate::prompt`]直接读写终端，不会经过分页程序
+use std::io::{self, IsTerminal, Write};
+use std::process::{Child, Command, Stdio};
+
+/// 分页程序的环境变量
+pub const PAGER_ENV: &str = "PAGER";
+
+/// 未设置[`PAGER_ENV`]时的分页程序：内容不足一屏时直接退出，保留颜色，退出后不清屏
+pub const DEFAULT_PAGER: &str = "less -FRX";
+
+/// 分页程序的命令。pager为空时使用[`DEFAULT_PAGER`]，与git相同通过`sh -c`执行，
+/// 可以包含引号与环境变量
+pub fn pager_command(pager: Option<&str>) -> Command {
+    let pager = pager
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .unwrap_or(DEFAULT_PAGER);
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(pager);
+    command
+}
+
+/// 数据输出的writer。enabled且stdout为终端时为[`PagedOutput`]，否则为stdout
+pub fn stdout(enabled: bool) -> Box<dyn Write> {
+    if !enabled || !io::stdout().is_terminal() {
+        return Box::new(io::stdout());
+    }
+    let height = term_size::dimensions_stdout()
+        .map(|(_, h)| h)
+        .filter(|h| *h > 0);
+    let pager = std::env::var(PAGER_ENV).ok();
+    match height {
+        Some(height) => Box::new(PagedOutput::new(
+            pager_command(pager.as_deref()),
+            height.saturating_sub(1),
+            io::stdout(),
+        )),
+        None => Box::new(io::stdout()),
+    }
+}
+
+/// 先缓存输出，超过max_lines行时启动分页程序并将之后的输出写入它的stdin，否则在结束时写入direct。
+///
+/// 用户提前退出分页程序时丢弃之后的输出，broken pipe不是错误。drop时等待分页程序退出
+pub struct PagedOutput<W: Write> {
+    command: Command,
+    max_lines: usize,
+    direct: W,
+    buffer: Vec<u8>,
+    lines: usize,
+    child: Option<Child>,
+    /// 分页程序已退出或无法写入
+    closed: bool,
+    finished: bool,
+}
+
+impl<W: Write> PagedOutput<W> {
+    pub fn new(command: Command, max_lines: usize, direct: W) -> Self {
+        Self {
+            command,
+            max_lines,
+            direct,
+            buffer: vec![],
+            lines: 0,
+            child: None,
+            closed: false,
+            finished: false,
+        }
+    }
+
+    /// 是否已启动分页程序
+    pub fn is_paging(&self) -> bool {
+        self.child.is_some()
+    }
+
+    /// 输出缓存的内容，或关闭分页程序的stdin并等待它退出
+    ///
+    /// # Errors
+    ///
+    /// 如果写入direct失败
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        match self.child.as_mut() {
+            Some(child) => {
+                if let Some(mut stdin) = child.stdin.take() {
+                    ignore_broken_pipe(stdin.flush())?;
+                }
+                let status = child.wait()?;
+                debug!("pager exited with {}", status);
+                Ok(())
+            }
+            None => {
+                self.direct.write_all(&self.buffer)?;
+                self.buffer.clear();
+                self.direct.flush()
+            }
+        }
+    }
+
+    /// 启动分页程序并写入缓存的内容。无法启动时在结束时直接输出
+    fn spawn(&mut self) -> io::Result<()> {
+        match self.command.stdin(Stdio::piped()).spawn() {
+            Ok(child) => {
+                debug!("spawned pager: {:?}", self.command);
+                self.child = Some(child);
+                let buffer = std::mem::take(&mut self.buffer);
+                self.write_pager(&buffer)
+            }
+            Err(e) => {
+                warn!("spawn pager {:?} error: {}", self.command, e);
+                // 不再尝试启动
+                self.max_lines = usize::MAX;
+                Ok(())
+            }
+        }
+    }
+
+    fn write_pager(&mut self, buf: &[u8]) -> io::Result<()> {
+        let stdin = match self.child.as_mut().and_then(|c| c.stdin.as_mut()) {
+            Some(stdin) => stdin,
+            None => return Ok(()),
+        };
+        match stdin.write_all(buf) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                debug!("pager was closed. discarding the rest of output");
+                self.closed = true;
+                Ok(())
+            }
+            r => r,
+        }
+    }
+}
+
+impl<W: Write> Write for PagedOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Ok(buf.len());
+        }
+        if self.child.is_some() {
+            self.write_pager(buf)?;
+            return Ok(buf.len());
+        }
+        self.buffer.extend_from_slice(buf);
+        self.lines += buf.iter().filter(|b| **b == b'\n').count();
+        if self.lines > self.max_lines {
+            self.spawn()?;
+        }
+        Ok(buf.len())
+    }
+
+    /// 未启动分页程序时不输出缓存的内容，见[`PagedOutput::finish`]
+    fn flush(&mut self) -> io::Result<()> {
+        match self.child.as_mut().and_then(|c| c.stdin.as_mut()) {
+            Some(stdin) if !self.closed => ignore_broken_pipe(stdin.flush()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Drop for PagedOutput<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            warn!("finish paged output error: {}", e);
+        }
+    }
+}
+
+fn ignore_broken_pipe(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        r => r,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head() -> Command {
+        let mut command = Command::new("head");
+        command.arg("-n1").stdout(Stdio::null());
+        command
+    }
+
+    #[test]
+    fn pager_commands() {
+        let args = |command: Command| {
+            std::iter::once(command.get_program())
+                .chain(command.get_args())
+                .map(|s| s.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(args(pager_command(None)), ["sh", "-c", "less -FRX"]);
+        assert_eq!(args(pager_command(Some(" "))), ["sh", "-c", "less -FRX"]);
+        assert_eq!(args(pager_command(Some("more"))), ["sh", "-c", "more"]);
+    }
+
+    #[test]
+    fn pager_with_quotes() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("paged words.txt");
+        let pager = format!("cat > '{}'", path.display());
+        {
+            let mut output = PagedOutput::new(pager_command(Some(&pager)), 1, io::sink());
+            writeln!(output, "a\nb")?;
+            assert!(output.is_paging());
+            output.finish()?;
+        }
+        assert_eq!(std::fs::read_to_string(path)?, "a\nb\n");
+        Ok(())
+    }
+
+    #[test]
+    fn short_output_is_not_paged() -> io::Result<()> {
+        let mut direct = vec![];
+        {
+            let mut output = PagedOutput::new(head(), 3, &mut direct);
+            writeln!(output, "a\nb")?;
+            writeln!(output, "c")?;
+            output.flush()?;
+            assert!(!output.is_paging());
+            output.finish()?;
+        }
+        assert_eq!(String::from_utf8(direct).unwrap(), "a\nb\nc\n");
+        Ok(())
+    }
+
+    #[test]
+    fn closed_pager_is_not_error() -> io::Result<()> {
+        let mut direct = vec![];
+        {
+            let mut output = PagedOutput::new(head(), 3, &mut direct);
+            // head退出后超出pipe缓冲区的写入会出现broken pipe
+            for i in 0..200_000 {
+                writeln!(output, "line {}", i)?;
+            }
+            assert!(output.is_paging());
+            assert!(output.closed);
+            output.flush()?;
+            output.finish()?;
+        }
+        assert!(direct.is_empty());
+        Ok(())
+    }
+}
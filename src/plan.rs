@@ -0,0 +1,378 @@
+//! `dict run`执行的plan文件：按顺序执行多个步骤，步骤间共享clients与登录状态。
+//!
+//! ```yaml
+//! steps:
+//!   - refresh: youdao
+//!   - export_csv: words.csv
+//!   - sync:
+//!       book: GRE
+//!       notepad: "695835"
+//!     continue_on_error: true
+//!   - sync:
+//!       source: vocab
+//!       notepad: "695835"
+//! ```
+//!
+//! 相对路径相对于plan文件所在的目录
+use crate::config::Config;
+use crate::lemma;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+/// plan中的一个步骤。默认失败时停止执行之后的步骤
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Step {
+    #[serde(flatten)]
+    pub action: Action,
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Service {
+    Youdao,
+    Maimemo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// 从web上重新加载服务的数据，同`yd -r`与`mm -r`
+    Refresh(Service),
+    /// 导出本地youdao单词到csv文件，同`yd --export-csv`
+    ExportCsv(String),
+    /// 将youdao单词本或自定义来源中的单词添加到maimemo notepad的最后，notepad中已有的单词被忽略
+    Sync(SyncStep),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncStep {
+    /// youdao单词本名称，配置了source时不使用
+    #[serde(default)]
+    pub book: String,
+    /// 配置文件custom_sources中的来源名称，从该来源获取单词而不是youdao单词本
+    pub source: Option<String>,
+    /// maimemo notepad_id
+    pub notepad: String,
+    /// 在添加的单词前插入时间戳，同`mm -u -t`
+    #[serde(default)]
+    pub timestamp: bool,
+    /// 使用单词原形判断notepad中是否已有该单词，如已有`run`时忽略`running`
+    #[serde(default)]
+    pub lemma: bool,
+}
+
+impl Plan {
+    /// 读取path中的plan，相对路径转换为相对于path所在的目录
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("read plan {} error: {}", path, e))?;
+        let mut plan = Self::from_yaml(&contents)?;
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        for step in &mut plan.steps {
+            if let Action::ExportCsv(file) = &mut step.action {
+                *file = dir.join(&*file).to_str().unwrap_or(file).to_string();
+            }
+        }
+        Ok(plan)
+    }
+
+    /// `dict sync`的plan：refresh来源与maimemo后执行一个sync步骤
+    pub fn sync(sync: SyncStep) -> Result<Self, String> {
+        let refresh = |service| Step {
+            action: Action::Refresh(service),
+            continue_on_error: false,
+        };
+        let mut steps = vec![];
+        if sync.source.is_none() {
+            steps.push(refresh(Service::Youdao));
+        }
+        steps.push(refresh(Service::Maimemo));
+        steps.push(Step {
+            action: Action::Sync(sync),
+            continue_on_error: false,
+        });
+        let plan = Self { steps };
+        plan.validate()?;
+        Ok(plan)
+    }
+
+    /// 解析并检查plan
+    pub fn from_yaml(s: &str) -> Result<Self, String> {
+        let plan =
+            serde_yaml::from_str::<Self>(s).map_err(|e| format!("parse plan error: {}", e))?;
+        plan.validate()?;
+        Ok(plan)
+    }
+
+    /// 步骤的参数不能为空。refresh必须在使用该服务的其它步骤之前，且仅一次，
+    /// 保证每个服务仅加载一次
+    fn validate(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("invalid plan: no steps".to_string());
+        }
+        let mut used = vec![];
+        for (i, step) in self.steps.iter().enumerate() {
+            let invalid = |e: &str| format!("invalid plan: step {} `{}`: {}", i + 1, step, e);
+            match &step.action {
+                Action::Refresh(service) if used.contains(service) => {
+                    return Err(invalid(&format!(
+                        "refresh {} must be the first step using {}",
+                        service, service
+                    )));
+                }
+                Action::ExportCsv(file) if file.trim().is_empty() => {
+                    return Err(invalid("empty csv path"));
+                }
+                Action::Sync(sync) if sync.source.is_some() && !sync.book.is_empty() => {
+                    return Err(invalid("book is not used with source"));
+                }
+                Action::Sync(sync)
+                    if sync.source.as_ref().unwrap_or(&sync.book).trim().is_empty() =>
+                {
+                    return Err(invalid("empty book or source"));
+                }
+                Action::Sync(sync) if sync.notepad.trim().is_empty() => {
+                    return Err(invalid("empty notepad"));
+                }
+                Action::Sync(sync) if sync.lemma => {
+                    lemma::check_supported().map_err(|e| invalid(&e))?;
+                }
+                _ => {}
+            }
+            used.extend(step.action.services());
+        }
+        Ok(())
+    }
+
+    /// 检查步骤使用的服务与自定义来源都已配置
+    pub fn check_config(&self, config: &Config) -> Result<(), String> {
+        for (i, step) in self.steps.iter().enumerate() {
+            if let Action::Sync(SyncStep {
+                source: Some(source),
+                ..
+            }) = &step.action
+            {
+                config
+                    .custom_source(source)
+                    .map_err(|e| format!("plan step {} `{}`: {}", i + 1, step, e))?;
+            }
+            for service in step.action.services() {
+                let configured = match service {
+                    Service::Youdao => config.youdao.is_some(),
+                    Service::Maimemo => config.maimemo.is_some(),
+                };
+                if !configured {
+                    return Err(format!(
+                        "plan step {} `{}` uses {} but it is not configured",
+                        i + 1,
+                        step,
+                        service
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Action {
+    /// 步骤使用的服务
+    pub fn services(&self) -> Vec<Service> {
+        match self {
+            Self::Refresh(service) => vec![*service],
+            Self::ExportCsv(_) => vec![Service::Youdao],
+            Self::Sync(SyncStep {
+                source: Some(_), ..
+            }) => vec![Service::Maimemo],
+            Self::Sync(_) => vec![Service::Youdao, Service::Maimemo],
+        }
+    }
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Youdao => "youdao",
+            Self::Maimemo => "maimemo",
+        })
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.action {
+            Action::Refresh(service) => write!(f, "refresh {}", service)?,
+            Action::ExportCsv(file) => write!(f, "export csv to {}", file)?,
+            Action::Sync(sync) => {
+                match &sync.source {
+                    Some(source) => write!(f, "sync source {}", source)?,
+                    None => write!(f, "sync book {}", sync.book)?,
+                }
+                write!(f, " to notepad {}", sync.notepad)?;
+                if sync.timestamp {
+                    f.write_str(" with timestamp")?;
+                }
+                if sync.lemma {
+                    f.write_str(" by lemma")?;
+                }
+            }
+        }
+        if self.continue_on_error {
+            f.write_str(" (continue on error)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_plan() -> Result<(), String> {
+        let step = SyncStep {
+            book: "GRE".to_string(),
+            source: None,
+            notepad: "695835".to_string(),
+            timestamp: false,
+            lemma: false,
+        };
+        let steps = |plan: Plan| plan.steps.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            steps(Plan::sync(step.clone())?),
+            vec![
+                "refresh youdao",
+                "refresh maimemo",
+                "sync book GRE to notepad 695835"
+            ]
+        );
+        let step = SyncStep {
+            book: String::new(),
+            source: Some("vocab".to_string()),
+            ..step
+        };
+        assert_eq!(
+            steps(Plan::sync(step.clone())?),
+            vec!["refresh maimemo", "sync source vocab to notepad 695835"]
+        );
+        let e = Plan::sync(SyncStep {
+            notepad: String::new(),
+            ..step
+        })
+        .unwrap_err();
+        assert!(e.contains("empty notepad"), "{}", e);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_steps() -> Result<(), String> {
+        let plan = Plan::from_yaml(
+            "
+steps:
+  - refresh: youdao
+  - export_csv: words.csv
+  - sync:
+      book: GRE
+      notepad: '695835'
+      timestamp: true
+    continue_on_error: true
+",
+        )?;
+        assert_eq!(plan.steps.len(), 3);
+        assert_eq!(plan.steps[0].action, Action::Refresh(Service::Youdao));
+        assert_eq!(
+            plan.steps.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            vec![
+                "refresh youdao",
+                "export csv to words.csv",
+                "sync book GRE to notepad 695835 with timestamp (continue on error)",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn schema_errors() {
+        let err = |yaml: &str| Plan::from_yaml(yaml).unwrap_err();
+        assert!(err("steps: []").contains("no steps"));
+        assert!(err("steps:\n  - refresh: eudic").starts_with("parse plan error"));
+        assert!(err("steps:\n  - upload: x").starts_with("parse plan error"));
+        assert!(err("steps:\n  - sync:\n      book: GRE").contains("notepad"));
+        let e = err("steps:\n  - sync:\n      notepad: '1'");
+        assert!(e.ends_with("empty book or source"), "{}", e);
+        let e = err("steps:\n  - sync:\n      book: GRE\n      source: vocab\n      notepad: '1'");
+        assert!(e.ends_with("book is not used with source"), "{}", e);
+        assert!(err("stesp:\n  - refresh: youdao").contains("stesp"));
+        let e = err("steps:\n  - export_csv: ' '");
+        assert_eq!(e, "invalid plan: step 1 `export csv to  `: empty csv path");
+        let e = err("steps:\n  - export_csv: a.csv\n  - refresh: youdao");
+        assert!(
+            e.contains("step 2 `refresh youdao`: refresh youdao must be the first step"),
+            "{}",
+            e
+        );
+        // maimemo未被之前的步骤使用
+        assert!(Plan::from_yaml("steps:\n  - export_csv: a.csv\n  - refresh: maimemo").is_ok());
+        // 没有lemma feature时不能使用lemma比较
+        let plan = Plan::from_yaml(
+            "steps:\n  - sync:\n      book: GRE\n      notepad: '1'\n      lemma: true",
+        );
+        assert_eq!(plan.is_ok(), cfg!(feature = "lemma"));
+    }
+
+    #[test]
+    fn load_and_check_config() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = dir.path().join("plan.yml");
+        fs::write(&path, "steps:\n  - export_csv: out/words.csv\n").unwrap();
+        let plan = Plan::load(path.to_str().unwrap())?;
+        assert_eq!(
+            plan.steps[0].action,
+            Action::ExportCsv(
+                dir.path()
+                    .join("out/words.csv")
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            )
+        );
+
+        let config: Config =
+            serde_yaml::from_str("youdao:\n  dictionary_path: youdao.json").unwrap();
+        plan.check_config(&config)?;
+        let plan = Plan::from_yaml("steps:\n  - sync:\n      book: GRE\n      notepad: '1'")?;
+        let e = plan.check_config(&config).unwrap_err();
+        assert!(e.contains("uses maimemo but it is not configured"), "{}", e);
+
+        // source不使用youdao
+        let plan = Plan::from_yaml("steps:\n  - sync:\n      source: vocab\n      notepad: '1'")?;
+        assert_eq!(plan.steps[0].to_string(), "sync source vocab to notepad 1");
+        assert_eq!(plan.steps[0].action.services(), vec![Service::Maimemo]);
+        let config: Config = serde_yaml::from_str(
+            "maimemo:\n  dictionary_path: maimemo.json
+custom_sources:
+  other:
+    dictionary_path: other.json
+    request: {url: 'http://localhost/words', method: GET}
+    mapping: {word: /w}",
+        )
+        .unwrap();
+        let e = plan.check_config(&config).unwrap_err();
+        assert_eq!(
+            e,
+            "plan step 1 `sync source vocab to notepad 1`: not found custom source vocab in config. configured: [other]"
+        );
+        Ok(())
+    }
+}
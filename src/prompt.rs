@@ -0,0 +1,414 @@
+//! 与用户交互的终端。stdin为管道时也可以通过终端读取用户输入
+use crate::error::PROMPT_TIMED_OUT;
+use crate::msg;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+#[cfg(unix)]
+const TTY_INPUT: &str = "/dev/tty";
+#[cfg(unix)]
+const TTY_OUTPUT: &str = "/dev/tty";
+#[cfg(not(unix))]
+const TTY_INPUT: &str = "CONIN$";
+#[cfg(not(unix))]
+const TTY_OUTPUT: &str = "CONOUT$";
+
+/// 没有可用的终端，如在cron或CI中运行
+#[derive(Debug)]
+pub struct HeadlessError {
+    path: String,
+    source: io::Error,
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no terminal available: open {} error: {}",
+            self.path, self.source
+        )
+    }
+}
+
+impl std::error::Error for HeadlessError {}
+
+/// 用户在timeout内没有输入，如验证码提示时离开。错误中包含[`PROMPT_TIMED_OUT`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTimeout {
+    pub timeout: Duration,
+}
+
+impl fmt::Display for PromptTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} after {}s",
+            PROMPT_TIMED_OUT,
+            self.timeout.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for PromptTimeout {}
+
+/// [`UserPrompt::open`]的读取超时，见`--prompt-timeout`
+static TIMEOUT: Mutex<Option<Duration>> = Mutex::new(None);
+
+/// 设置之后[`UserPrompt::open`]打开的终端每次读取的最长等待时间，None时一直等待
+pub fn set_timeout(timeout: Option<Duration>) {
+    *TIMEOUT.lock().unwrap() = timeout;
+}
+
+/// 在blocking线程中读取的输入
+type TimedReader = BufReader<Box<dyn Read + Send>>;
+
+enum Input<'a> {
+    Direct(Box<dyn BufRead + 'a>),
+    /// 通过[`tokio::task::spawn_blocking`]读取，超时后仍在等待的读取结果用于下一次读取
+    Timed {
+        /// 读取中时为None，读取完成后归还
+        reader: Option<TimedReader>,
+        pending: Option<JoinHandle<(TimedReader, io::Result<String>)>>,
+        timeout: Duration,
+    },
+}
+
+/// 输出提示并读取用户输入
+pub struct UserPrompt<'a> {
+    input: Input<'a>,
+    output: Box<dyn Write + 'a>,
+}
+
+impl<'a> UserPrompt<'a> {
+    pub fn new(input: impl Read + 'a, output: impl Write + 'a) -> Self {
+        Self {
+            input: Input::Direct(Box::new(BufReader::new(input))),
+            output: Box::new(output),
+        }
+    }
+
+    /// 同[`UserPrompt::new`]，但每次读取最多等待timeout，超时时返回[`PromptTimeout`]。
+    ///
+    /// input在blocking线程中被阻塞地读取，超时后该读取继续等待输入，
+    /// 读取到的一行由下一次读取返回
+    pub fn with_timeout(
+        input: impl Read + Send + 'static,
+        output: impl Write + 'a,
+        timeout: Duration,
+    ) -> Self {
+        let input: Box<dyn Read + Send> = Box::new(input);
+        Self {
+            input: Input::Timed {
+                reader: Some(BufReader::new(input)),
+                pending: None,
+                timeout,
+            },
+            output: Box::new(output),
+        }
+    }
+
+    /// 打开当前终端：unix上为/dev/tty，windows上为CONIN$与CONOUT$。
+    /// 使用[`set_timeout`]设置的超时
+    ///
+    /// # Errors
+    ///
+    /// 如果没有可用的终端
+    pub fn open() -> Result<UserPrompt<'static>, HeadlessError> {
+        UserPrompt::open_paths(TTY_INPUT, TTY_OUTPUT)
+    }
+
+    fn open_paths(input: &str, output: &str) -> Result<UserPrompt<'static>, HeadlessError> {
+        let open = |path: &str, write: bool| {
+            OpenOptions::new()
+                .read(!write)
+                .write(write)
+                .open(path)
+                .map_err(|source| HeadlessError {
+                    path: path.to_string(),
+                    source,
+                })
+        };
+        let input = open(input, false)?;
+        let output = open(output, true)?;
+        Ok(match *TIMEOUT.lock().unwrap() {
+            Some(timeout) => UserPrompt::with_timeout(input, output, timeout),
+            None => UserPrompt::new(input, output),
+        })
+    }
+
+    /// 输出prompt后读取一行，不包含换行符
+    ///
+    /// # Errors
+    ///
+    /// 如果读取失败、已到达EOF或超时，见[`PromptTimeout`]
+    pub async fn read_line(&mut self, prompt: &str) -> Result<String, String> {
+        write!(self.output, "{}", prompt)
+            .and_then(|_| self.output.flush())
+            .map_err(|e| format!("write prompt error: {}", e))?;
+        let line = self.read_input().await?;
+        trace!("read {} bytes from prompt", line.len());
+        if line.is_empty() {
+            return Err("read line is empty".to_string());
+        }
+        Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+    }
+
+    /// 读取包括换行符的一行，EOF时为空
+    async fn read_input(&mut self) -> Result<String, String> {
+        let (reader, pending, timeout) = match &mut self.input {
+            Input::Direct(input) => {
+                let mut line = String::new();
+                input
+                    .read_line(&mut line)
+                    .map_err(|e| format!("read line error: {}", e))?;
+                return Ok(line);
+            }
+            Input::Timed {
+                reader,
+                pending,
+                timeout,
+            } => (reader, pending, *timeout),
+        };
+        let mut task = match (pending.take(), reader.take()) {
+            (Some(task), _) => task,
+            (None, Some(mut input)) => tokio::task::spawn_blocking(move || {
+                let mut line = String::new();
+                let result = input.read_line(&mut line).map(|_| line);
+                (input, result)
+            }),
+            (None, None) => return Err("read line error: prompt reader stopped".to_string()),
+        };
+        match tokio::time::timeout(timeout, &mut task).await {
+            Ok(Ok((input, line))) => {
+                *reader = Some(input);
+                line.map_err(|e| format!("read line error: {}", e))
+            }
+            Ok(Err(e)) => Err(format!("read line error: {}", e)),
+            Err(_) => {
+                *pending = Some(task);
+                // 结束提示所在的行
+                let _ = writeln!(self.output);
+                Err(PromptTimeout { timeout }.to_string())
+            }
+        }
+    }
+
+    /// 读取一行直到validate返回Ok。validate返回error时输出error并重新读取
+    ///
+    /// # Errors
+    ///
+    /// 如果读取失败或已到达EOF
+    pub async fn read_valid_line<T>(
+        &mut self,
+        prompt: &str,
+        validate: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<T, String> {
+        loop {
+            let line = self.read_line(prompt).await?;
+            match validate(&line) {
+                Ok(val) => return Ok(val),
+                Err(e) => writeln!(self.output, "{}", msg!("prompt.try_again", e))
+                    .map_err(|e| format!("write prompt error: {}", e))?,
+            }
+        }
+    }
+
+    /// 询问用户y/N，仅在输入y或yes时返回true
+    pub async fn confirm(&mut self, prompt: &str) -> Result<bool, String> {
+        let answer = self.read_line(&format!("{} [y/N] ", prompt)).await?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    }
+}
+
+/// 在覆盖、删除等破坏性操作前输出summary并询问用户。assume_yes时不询问直接返回true
+///
+/// # Errors
+///
+/// 如果没有终端且未指定assume_yes，或读取用户输入失败
+pub async fn confirm(
+    prompt: Option<&mut UserPrompt<'_>>,
+    assume_yes: bool,
+    summary: &str,
+) -> Result<bool, String> {
+    if assume_yes {
+        debug!("confirmed by --yes: {}", summary);
+        return Ok(true);
+    }
+    match prompt {
+        Some(prompt) => prompt.confirm(summary).await,
+        None => Err(msg!("prompt.confirm_required", summary)),
+    }
+}
+
+/// 等待delay，每秒在终端输出`{msg} {剩余秒数}s`。没有终端时仅等待
+pub async fn countdown(
+    mut prompt: Option<&mut UserPrompt<'_>>,
+    delay: Duration,
+    msg: &str,
+) -> Result<(), String> {
+    let deadline = Instant::now() + delay;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        let left = deadline - now;
+        if let Some(prompt) = prompt.as_deref_mut() {
+            let secs = left.as_millis().div_ceil(1000);
+            write!(prompt.output, "\r{} {}s ", msg, secs)
+                .and_then(|_| prompt.output.flush())
+                .map_err(|e| format!("write countdown error: {}", e))?;
+        }
+        tokio::time::sleep(left.min(Duration::from_secs(1))).await;
+    }
+    if let Some(prompt) = prompt {
+        writeln!(prompt.output).map_err(|e| format!("write countdown error: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn headless() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing-tty");
+        let path = path.to_str().unwrap();
+        let e = UserPrompt::open_paths(path, path).err().unwrap();
+        assert!(e.to_string().contains("missing-tty"), "{}", e);
+    }
+
+    #[tokio::test]
+    async fn read_lines() {
+        let mut output = vec![];
+        {
+            let mut prompt = UserPrompt::new(&b"1234\r\nYes\nn\n"[..], &mut output);
+            assert_eq!(prompt.read_line("captcha: ").await.unwrap(), "1234");
+            assert!(prompt.confirm("retry?").await.unwrap());
+            assert!(!prompt.confirm("retry?").await.unwrap());
+            // EOF
+            assert!(prompt.confirm("retry?").await.is_err());
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "captcha: retry? [y/N] retry? [y/N] retry? [y/N] "
+        );
+    }
+
+    #[tokio::test]
+    async fn read_valid_lines() {
+        let mut output = vec![];
+        {
+            let mut prompt = UserPrompt::new(&b" \nab c\n ab12 \n"[..], &mut output);
+            let answer = prompt
+                .read_valid_line("captcha: ", |line| {
+                    crate::client::maimemo_client::normalize_captcha(
+                        line,
+                        crate::config::CaptchaCase::Upper,
+                    )
+                })
+                .await;
+            assert_eq!(answer.as_deref(), Ok("AB12"));
+            // EOF
+            assert!(prompt
+                .read_valid_line("captcha: ", |_| Err::<(), _>("invalid".to_string()))
+                .await
+                .is_err());
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "captcha: captcha is empty, please try again
+captcha: invalid character ' ' in captcha, only letters and digits are allowed, please try again
+captcha: captcha: "
+        );
+    }
+
+    #[tokio::test]
+    async fn confirm_destructive() {
+        let mut output = vec![];
+        let mut prompt = UserPrompt::new(&b"y\n\n"[..], &mut output);
+        assert_eq!(confirm(Some(&mut prompt), false, "delete?").await, Ok(true));
+        // 默认为N
+        assert_eq!(
+            confirm(Some(&mut prompt), false, "delete?").await,
+            Ok(false)
+        );
+        // --yes时不读取输入
+        assert_eq!(confirm(Some(&mut prompt), true, "delete?").await, Ok(true));
+        // 没有终端时必须指定--yes
+        assert!(confirm(None, false, "delete?").await.is_err());
+        assert_eq!(confirm(None, true, "delete?").await, Ok(true));
+    }
+
+    /// 阻塞直到收到数据，没有发送者时为EOF
+    struct ChannelReader(mpsc::Receiver<&'static [u8]>);
+
+    impl Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.recv() {
+                Ok(data) => {
+                    buf[..data.len()].copy_from_slice(data);
+                    Ok(data.len())
+                }
+                Err(_) => Ok(0),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn read_timeout() {
+        let (tx, rx) = mpsc::channel();
+        let mut output = vec![];
+        {
+            let timeout = Duration::from_millis(200);
+            let mut prompt = UserPrompt::with_timeout(ChannelReader(rx), &mut output, timeout);
+            let e = prompt.read_line("captcha: ").await.unwrap_err();
+            assert_eq!(e, PromptTimeout { timeout }.to_string());
+            assert_eq!(
+                crate::error::DictError::classify(&e),
+                Some(crate::error::DictError::PromptTimeout)
+            );
+            assert!(prompt.confirm("retry?").await.is_err());
+            // 超时前的读取仍在等待，输入被下一次读取使用
+            tx.send(&b"y\n"[..]).unwrap();
+            assert_eq!(prompt.confirm("retry?").await, Ok(true));
+            drop(tx);
+            assert!(prompt.read_line("captcha: ").await.is_err());
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "captcha: \nretry? [y/N] \nretry? [y/N] captcha: "
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn countdown_seconds() {
+        let mut output = vec![];
+        let start = Instant::now();
+        {
+            let mut prompt = UserPrompt::new(&b""[..], &mut output);
+            countdown(Some(&mut prompt), Duration::from_millis(2500), "retry in")
+                .await
+                .unwrap();
+        }
+        assert_eq!(start.elapsed(), Duration::from_millis(2500));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\rretry in 3s \rretry in 2s \rretry in 1s \n"
+        );
+
+        let start = Instant::now();
+        countdown(None, Duration::from_secs(5), "retry in")
+            .await
+            .unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,242 @@
+//! 日志中敏感信息的隐藏。
+//!
+//! key名包含password, passwd, token, cookie(忽略大小写)的值被隐藏，仅保留长度与前2个字符
+use serde_json::Value;
+
+const SECRET_KEYS: [&str; 4] = ["password", "passwd", "token", "cookie"];
+
+/// key是否为敏感的key
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_KEYS.iter().any(|k| key.contains(k))
+}
+
+/// 隐藏val，如`se***(6)`。长度不超过4时不显示任何字符
+pub fn mask(val: &str) -> String {
+    let len = val.chars().count();
+    let prefix = if len > 4 {
+        val.chars().take(2).collect::<String>()
+    } else {
+        String::new()
+    };
+    format!("{}***({})", prefix, len)
+}
+
+/// 如果key是敏感的则隐藏val
+pub fn redact_pair(key: &str, val: &str) -> String {
+    if is_secret_key(key) {
+        mask(val)
+    } else {
+        val.to_string()
+    }
+}
+
+/// 隐藏url encoded form body中敏感的值
+pub fn redact_form(body: &str) -> String {
+    body.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, val)) if is_secret_key(key) => format!("{}={}", key, mask(val)),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// 递归隐藏json object中敏感的值
+pub fn redact_json(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, val)| {
+                    let val = match val {
+                        Value::String(s) if is_secret_key(key) => Value::String(mask(s)),
+                        v if is_secret_key(key) => Value::String(mask(&v.to_string())),
+                        v => redact_json(v),
+                    };
+                    (key.clone(), val)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_json).collect()),
+        v => v.clone(),
+    }
+}
+
+/// 隐藏cookie header `a=1; b=2`中所有cookie的值
+pub fn redact_cookies(cookies: &str) -> String {
+    cookies
+        .split(';')
+        .map(|pair| {
+            let pair = pair.trim();
+            match pair.split_once('=') {
+                Some((name, val)) => format!("{}={}", name, mask(val)),
+                None => pair.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// 隐藏set-cookie header的cookie值，保留属性
+pub fn redact_set_cookie(set_cookie: &str) -> String {
+    match set_cookie.split_once(';') {
+        Some((cookie, attrs)) => format!("{};{}", redact_cookies(cookie), attrs),
+        None => redact_cookies(set_cookie),
+    }
+}
+
+/// 隐藏header的值。cookie与set-cookie仅隐藏cookie的值
+pub fn redact_header(name: &str, val: &str) -> String {
+    if name.eq_ignore_ascii_case("cookie") {
+        redact_cookies(val)
+    } else if name.eq_ignore_ascii_case("set-cookie") {
+        redact_set_cookie(val)
+    } else if is_secret_key(name) || name.eq_ignore_ascii_case("authorization") {
+        mask(val)
+    } else {
+        val.to_string()
+    }
+}
+
+/// 隐藏响应body中敏感的值。json同[`redact_json`]，html中隐藏name为敏感key的input的value
+pub fn redact_body(body: &str) -> String {
+    if let Ok(value) = serde_json::from_str::<Value>(body) {
+        return redact_json(&value).to_string();
+    }
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find("<input") {
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |i| start + i + 1);
+        out.push_str(&rest[..start]);
+        out.push_str(&redact_input(&rest[start..end]));
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 隐藏错误信息等文本中敏感key的值，如`password=...`、`token: ...`与`"userToken":"..."`。
+/// 值在引号、空白或`&;,}`处结束
+pub fn redact_text(text: &str) -> String {
+    let is_key_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_key_char) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let key_end = rest.find(|c| !is_key_char(c)).unwrap_or(rest.len());
+        let (key, after) = rest.split_at(key_end);
+        out.push_str(key);
+        rest = after;
+        if !is_secret_key(key) {
+            continue;
+        }
+        // 分隔符前后可能有引号与空白
+        let sep = after.trim_start_matches(['"', '\'']).trim_start();
+        let value = match sep.strip_prefix(['=', ':']) {
+            Some(value) => value.trim_start(),
+            None => continue,
+        };
+        let value = value.strip_prefix(['"', '\'']).unwrap_or(value);
+        let end = value
+            .find(|c: char| {
+                c.is_whitespace() || matches!(c, '"' | '\'' | '`' | '&' | ';' | ',' | '}')
+            })
+            .unwrap_or(value.len());
+        if end == 0 {
+            continue;
+        }
+        out.push_str(&after[..after.len() - value.len()]);
+        out.push_str(&mask(&value[..end]));
+        rest = &value[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 隐藏`<input name="password" value="...">`的value
+fn redact_input(tag: &str) -> String {
+    match (attr_range(tag, "name"), attr_range(tag, "value")) {
+        (Some(name), Some(value)) if is_secret_key(&tag[name.clone()]) => format!(
+            "{}{}{}",
+            &tag[..value.start],
+            mask(&tag[value.clone()]),
+            &tag[value.end..]
+        ),
+        _ => tag.to_string(),
+    }
+}
+
+/// 双引号中属性值的位置
+fn attr_range(tag: &str, name: &str) -> Option<std::ops::Range<usize>> {
+    let key = format!(" {}=\"", name);
+    let start = tag.find(&key)? + key.len();
+    let end = start + tag[start..].find('"')?;
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_login_form() {
+        let body = "username=a%40b.com&password=5f4dcc3b5aa765d61d8327deb882cf99&savelogin=1";
+        let redacted = redact_form(body);
+        assert!(!redacted.contains("5f4dcc3b5aa765d61d8327deb882cf99"));
+        assert_eq!(
+            redacted,
+            "username=a%40b.com&password=5f***(32)&savelogin=1"
+        );
+    }
+
+    #[test]
+    fn redact_values() {
+        assert_eq!(mask("abc"), "***(3)");
+        assert_eq!(
+            redact_json(&serde_json::json!({"user": {"userToken": "abcdef"}, "id": 1})),
+            serde_json::json!({"user": {"userToken": "ab***(6)"}, "id": 1})
+        );
+        assert_eq!(redact_cookies("a=secret; b=12"), "a=se***(6); b=***(2)");
+        assert_eq!(
+            redact_header("Set-Cookie", "token=secret; Path=/"),
+            "token=se***(6); Path=/"
+        );
+        assert_eq!(redact_header("accept", "*/*"), "*/*");
+    }
+
+    #[test]
+    fn redact_bodies() {
+        let html = r#"<form><input type="text" name="username" value="a@b.com"><input type="password" name="password" value="secret"></form>"#;
+        assert_eq!(
+            redact_body(html),
+            r#"<form><input type="text" name="username" value="a@b.com"><input type="password" name="password" value="se***(6)"></form>"#
+        );
+        assert_eq!(
+            redact_body(r#"{"token":"abcdef","code":460}"#),
+            r#"{"code":460,"token":"ab***(6)"}"#
+        );
+        assert_eq!(redact_body("login failed"), "login failed");
+    }
+
+    #[test]
+    fn redact_error_text() {
+        assert_eq!(
+            redact_text("invalid yaml near `password: hunter22`, path: /a/b"),
+            "invalid yaml near `password: hu***(8)`, path: /a/b"
+        );
+        assert_eq!(
+            redact_text(r#"Response code error: 500, body: {"userToken":"abcdef","code":1}"#),
+            r#"Response code error: 500, body: {"userToken":"ab***(6)","code":1}"#
+        );
+        assert_eq!(
+            redact_text("url: http://a.com/login?username=a&password=secret&x=1"),
+            "url: http://a.com/login?username=a&password=se***(6)&x=1"
+        );
+        assert_eq!(redact_text("token expired"), "token expired");
+        assert_eq!(redact_text("token=; a"), "token=; a");
+        assert_eq!(redact_text("read cookies error"), "read cookies error");
+    }
+}
@@ -0,0 +1,173 @@
+//! 随机抽取单词复习与终端测验
+use crate::client::youdao_client::WordItem;
+use crate::msg;
+use crate::prompt::UserPrompt;
+use serde::Serialize;
+use std::fmt;
+
+/// 可以指定seed复现的伪随机数生成器(splitmix64)。不用于加密
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// 使用系统随机数作为seed
+    pub fn from_entropy() -> Result<Self, String> {
+        let mut seed = [0; 8];
+        getrandom::getrandom(&mut seed).map_err(|e| format!("generate seed error: {}", e))?;
+        Ok(Self(u64::from_le_bytes(seed)))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// `[0, n)`中均匀分布的数。拒绝采样避免取模的偏差
+    ///
+    /// # Panics
+    ///
+    /// 如果n为0
+    pub fn below(&mut self, n: usize) -> usize {
+        let n = n as u64;
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let v = self.next_u64();
+            if v < zone {
+                return (v % n) as usize;
+            }
+        }
+    }
+}
+
+/// 从items中不重复地均匀抽取n个，n大于数量时返回全部items的随机排列
+pub fn sample<T>(mut items: Vec<T>, n: usize, rng: &mut Rng) -> Vec<T> {
+    let n = n.min(items.len());
+    // 部分Fisher-Yates洗牌，前n个为结果
+    for i in 0..n {
+        let j = i + rng.below(items.len() - i);
+        items.swap(i, j);
+    }
+    items.truncate(n);
+    items
+}
+
+/// 测验的结果
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct QuizScore {
+    pub known: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for QuizScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = (self.known * 100).checked_div(self.total).unwrap_or(0);
+        write!(f, "{}", msg!("quiz.score", self.known, self.total, percent))
+    }
+}
+
+/// 逐个显示单词，用户按Enter后显示音标与释义，再询问是否认识
+///
+/// # Errors
+///
+/// 如果读取用户输入失败，如到达EOF
+pub async fn quiz(words: &[WordItem], prompt: &mut UserPrompt<'_>) -> Result<QuizScore, String> {
+    let mut score = QuizScore {
+        known: 0,
+        total: words.len(),
+    };
+    for (i, w) in words.iter().enumerate() {
+        prompt
+            .read_line(&format!("[{}/{}] {} ", i + 1, words.len(), w.word))
+            .await?;
+        let reveal = if w.phonetic.is_empty() {
+            w.trans.clone()
+        } else {
+            format!("[{}] {}", w.phonetic, w.trans)
+        };
+        if prompt
+            .confirm(&format!("{}\n{}", reveal, msg!("quiz.known")))
+            .await?
+        {
+            score.known += 1;
+        }
+    }
+    Ok(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str, trans: &str) -> WordItem {
+        WordItem {
+            item_id: word.to_string(),
+            book_id: String::new(),
+            book_name: String::new(),
+            word: word.to_string(),
+            trans: trans.to_string(),
+            phonetic: String::new(),
+            modified_time: 0,
+            local: None,
+        }
+    }
+
+    #[test]
+    fn sample_with_seed() {
+        let items = (0..10).collect::<Vec<_>>();
+        let a = sample(items.clone(), 3, &mut Rng::new(42));
+        assert_eq!(a, sample(items.clone(), 3, &mut Rng::new(42)));
+        assert_eq!(a.len(), 3);
+        let mut all = sample(items.clone(), 20, &mut Rng::new(1));
+        all.sort_unstable();
+        assert_eq!(all, items);
+        assert!(sample(Vec::<u8>::new(), 3, &mut Rng::new(1)).is_empty());
+    }
+
+    #[test]
+    fn sample_is_uniform() {
+        // 每个位置被选中的次数应该接近，不偏向开头
+        let mut counts = [0usize; 10];
+        let mut rng = Rng::new(7);
+        for _ in 0..10000 {
+            for i in sample((0..10).collect(), 2, &mut rng) {
+                counts[i] += 1;
+            }
+        }
+        for count in counts {
+            assert!((1800..2200).contains(&count), "{:?}", counts);
+        }
+    }
+
+    #[tokio::test]
+    async fn scripted_quiz() {
+        let words = vec![
+            word("run", "v. 跑"),
+            word("walk", "v. 走"),
+            word("swim", "v. 游"),
+        ];
+        let input = "\ny\n\nn\n\nyes\n";
+        let mut output = vec![];
+        let mut prompt = UserPrompt::new(input.as_bytes(), &mut output);
+        let score = quiz(&words, &mut prompt).await.unwrap();
+        drop(prompt);
+        assert_eq!(score, QuizScore { known: 2, total: 3 });
+        assert_eq!(score.to_string(), "known 2/3 (66%)");
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.starts_with("[1/3] run v. 跑\nknown? [y/N] "),
+            "{}",
+            output
+        );
+
+        // 输入结束时失败
+        let mut prompt = UserPrompt::new("\n".as_bytes(), std::io::sink());
+        assert!(quiz(&words, &mut prompt).await.is_err());
+    }
+}
@@ -0,0 +1,448 @@
+//! store文件的持久化格式。
+//!
+//! v1：json数组`[...]`；v2：`{"version": 2, "saved_at": "...", "kind": "words", "items": [...]}`。
+//! 读取时兼容v1并在内存中升级，保存时总是写入最新版本。
+//!
+//! kind标记items的类型，读取时检查，避免不同服务的dictionary_path为同一个文件时互相覆盖后
+//! 在反序列化时才出现难以理解的错误。没有kind的旧文件通过第一个item的字段识别
+use serde::{
+    de::{DeserializeOwned, IgnoredAny, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::Value;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::marker::PhantomData;
+
+/// 当前store文件格式版本
+pub const STORE_VERSION: u32 = 2;
+
+/// 无版本信息的json数组格式
+pub const LEGACY_STORE_VERSION: u32 = 1;
+
+/// youdao单词store的kind
+pub const WORDS_KIND: &str = "words";
+
+/// maimemo notepads store的kind
+pub const NOTEPADS_KIND: &str = "notepads";
+
+/// v2及之后的store文件。items必须是最后一个字段，流式读取时依赖该顺序
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoreEnvelope<T> {
+    pub version: u32,
+    #[serde(default)]
+    pub saved_at: Option<String>,
+    /// items的类型，如[`WORDS_KIND`]。之前保存的文件中没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    pub items: T,
+}
+
+/// store文件的概要信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StoreInfo {
+    pub path: String,
+    pub version: u32,
+    pub items: usize,
+    pub saved_at: Option<String>,
+}
+
+/// 从reader中读取store文件。v1的json数组被读取为version=1的envelope。
+///
+/// kind不为None时检查文件中的kind，没有kind时通过第一个item识别，无法识别时不检查
+///
+/// # Errors
+///
+/// 如果json解析失败、version高于当前支持的版本或kind不同
+pub fn read_store<T: DeserializeOwned, R: BufRead>(
+    mut reader: R,
+    kind: Option<&str>,
+) -> Result<StoreEnvelope<T>, String> {
+    let expected = match kind {
+        Some(kind) => kind,
+        None => return read_envelope(reader),
+    };
+    let mut bytes = vec![];
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("{:?}", e))?;
+    // 先仅读取kind与第一个item，避免其它类型的items在反序列化时出现难以理解的错误
+    if let Some(found) = peek_kind(&bytes)?.filter(|found| found != expected) {
+        return Err(format!(
+            "{}: expected {}, found {}",
+            STORE_KIND_MISMATCH,
+            describe_kind(expected),
+            describe_kind(&found)
+        ));
+    }
+    read_envelope(bytes.as_slice())
+}
+
+/// 文件中的kind，没有kind时通过第一个item识别
+fn peek_kind(bytes: &[u8]) -> Result<Option<String>, String> {
+    let parse_error = |e| format!("parse json error: {}", e);
+    let (kind, first) = match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'[') => (
+            None,
+            serde_json::from_slice::<FirstItem>(bytes).map_err(parse_error)?,
+        ),
+        _ => {
+            let peek = serde_json::from_slice::<KindPeek>(bytes).map_err(parse_error)?;
+            (peek.kind, peek.items)
+        }
+    };
+    Ok(kind.or_else(|| first.0.as_ref().and_then(detect_kind).map(str::to_string)))
+}
+
+/// envelope中用于检查kind的字段
+#[derive(Deserialize)]
+struct KindPeek {
+    #[serde(default)]
+    kind: Option<String>,
+    items: FirstItem,
+}
+
+/// items中的第一个item，其它item被忽略
+struct FirstItem(Option<Value>);
+
+impl<'de> Deserialize<'de> for FirstItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FirstItemVisitor;
+
+        impl<'de> Visitor<'de> for FirstItemVisitor {
+            type Value = FirstItem;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a json array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<FirstItem, A::Error> {
+                let first = seq.next_element::<Value>()?;
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(FirstItem(first))
+            }
+        }
+
+        deserializer.deserialize_seq(FirstItemVisitor)
+    }
+}
+
+/// store文件的kind与期望的不同时错误的标记
+pub const STORE_KIND_MISMATCH: &str = "store kind mismatch";
+
+fn read_envelope<T: DeserializeOwned, R: BufRead>(
+    mut reader: R,
+) -> Result<StoreEnvelope<T>, String> {
+    let envelope = match peek_non_whitespace(&mut reader).map_err(|e| format!("{:?}", e))? {
+        Some(b'[') => StoreEnvelope {
+            version: LEGACY_STORE_VERSION,
+            saved_at: None,
+            kind: None,
+            items: serde_json::from_reader(reader)
+                .map_err(|e| format!("parse json error: {}", e))?,
+        },
+        _ => serde_json::from_reader::<_, StoreEnvelope<T>>(reader)
+            .map_err(|e| format!("parse json error: {}", e))?,
+    };
+    check_version(envelope.version)?;
+    Ok(envelope)
+}
+
+fn describe_kind(kind: &str) -> String {
+    match kind {
+        WORDS_KIND => "youdao words".to_string(),
+        NOTEPADS_KIND => "maimemo notepads".to_string(),
+        _ => kind.to_string(),
+    }
+}
+
+/// 通过第一个item的字段识别没有kind的items
+fn detect_kind(first: &Value) -> Option<&'static str> {
+    let first = first.as_object()?;
+    if first.contains_key("notepad_id") {
+        Some(NOTEPADS_KIND)
+    } else if first.contains_key("itemId") {
+        Some(WORDS_KIND)
+    } else {
+        None
+    }
+}
+
+/// 读取path中store文件的版本、单词数量与保存时间，不会反序列化items的内容
+pub fn store_info(path: &str) -> Result<StoreInfo, String> {
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("open file error: {}, path: {}", e, path))?;
+    crate::lock::lock_shared(&file, path)?;
+    let envelope = read_store::<Vec<IgnoredAny>, _>(io::BufReader::new(file), None)
+        .map_err(|e| format!("{}, path: {}", e, path))?;
+    Ok(StoreInfo {
+        path: path.to_string(),
+        version: envelope.version,
+        items: envelope.items.len(),
+        saved_at: envelope.saved_at,
+    })
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version > STORE_VERSION {
+        Err(format!(
+            "unsupported store version: {}, latest supported version: {}",
+            version, STORE_VERSION
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// 跳过空白字符后查看下一个字节，不会消费该字节
+fn peek_non_whitespace<R: BufRead>(reader: &mut R) -> io::Result<Option<u8>> {
+    loop {
+        let buf = reader.fill_buf()?;
+        match buf.first() {
+            None => return Ok(None),
+            Some(b) if b.is_ascii_whitespace() => reader.consume(1),
+            Some(&b) => return Ok(Some(b)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ArrayState {
+    Start,
+    First,
+    Next,
+    Done,
+}
+
+/// 逐个反序列化store文件中的object元素，兼容v1与v2格式。
+///
+/// 每次仅读取一个元素，元素必须是json object，反序列化时不会预读元素外的字节
+pub struct JsonArrayIter<T, R> {
+    reader: R,
+    state: ArrayState,
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned, R: BufRead> JsonArrayIter<T, R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: ArrayState::Start,
+            _marker: PhantomData,
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, String> {
+        peek_non_whitespace(&mut self.reader).map_err(|e| format!("{:?}", e))
+    }
+
+    /// 期望下一个非空白字节是expected中的一个并消费
+    fn expect_byte(&mut self, expected: &[u8]) -> Result<u8, String> {
+        match self.peek()? {
+            Some(b) if expected.contains(&b) => {
+                self.reader.consume(1);
+                Ok(b)
+            }
+            Some(b) => Err(format!(
+                "unexpected char '{}' in json array, expected one of: {:?}",
+                b as char,
+                expected.iter().map(|b| *b as char).collect::<Vec<_>>()
+            )),
+            None => Err("unexpected EOF in json array".to_string()),
+        }
+    }
+
+    /// 跳过envelope中items之前的字段，直到items数组开始
+    fn skip_to_items(&mut self) -> Result<(), String> {
+        self.expect_byte(b"{")?;
+        loop {
+            let key =
+                String::deserialize(&mut serde_json::Deserializer::from_reader(&mut self.reader))
+                    .map_err(|e| format!("parse json error: {}", e))?;
+            self.expect_byte(b":")?;
+            if key == "items" {
+                return Ok(());
+            }
+            if key == "version" {
+                let version = self.read_scalar()?;
+                let version = version
+                    .parse::<u32>()
+                    .map_err(|e| format!("invalid store version {}: {}", version, e))?;
+                check_version(version)?;
+            } else {
+                self.skip_value()?;
+            }
+            self.expect_byte(b",")?;
+        }
+    }
+
+    /// 跳过一个json值
+    fn skip_value(&mut self) -> Result<(), String> {
+        match self.peek()? {
+            // string, object与array在结束符处停止，不会预读
+            Some(b'"') | Some(b'{') | Some(b'[') => {
+                IgnoredAny::deserialize(&mut serde_json::Deserializer::from_reader(
+                    &mut self.reader,
+                ))
+                .map_err(|e| format!("parse json error: {}", e))?;
+                Ok(())
+            }
+            _ => self.read_scalar().map(|_| ()),
+        }
+    }
+
+    /// 读取number, true, false, null直到分隔符
+    fn read_scalar(&mut self) -> Result<String, String> {
+        self.peek()?;
+        let mut scalar = vec![];
+        loop {
+            let buf = self.reader.fill_buf().map_err(|e| format!("{:?}", e))?;
+            match buf.first() {
+                Some(b) if !b.is_ascii_whitespace() && !b",}]".contains(b) => {
+                    scalar.push(*b);
+                    self.reader.consume(1);
+                }
+                _ => break,
+            }
+        }
+        Ok(String::from_utf8_lossy(&scalar).to_string())
+    }
+
+    fn next_element(&mut self) -> Result<Option<T>, String> {
+        match self.state {
+            ArrayState::Done => return Ok(None),
+            ArrayState::Start => {
+                if self.peek()? == Some(b'{') {
+                    self.skip_to_items()?;
+                }
+                self.expect_byte(b"[")?;
+                self.state = ArrayState::First;
+                return self.next_element();
+            }
+            ArrayState::First => {
+                if self.peek()? == Some(b']') {
+                    self.reader.consume(1);
+                    self.state = ArrayState::Done;
+                    return Ok(None);
+                }
+            }
+            ArrayState::Next => {
+                if self.expect_byte(b",]")? == b']' {
+                    self.state = ArrayState::Done;
+                    return Ok(None);
+                }
+            }
+        }
+        let mut de = serde_json::Deserializer::from_reader(&mut self.reader);
+        let item = T::deserialize(&mut de).map_err(|e| format!("parse json error: {}", e))?;
+        self.state = ArrayState::Next;
+        Ok(Some(item))
+    }
+}
+
+impl<T: DeserializeOwned, R: BufRead> Iterator for JsonArrayIter<T, R> {
+    type Item = Result<T, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_element() {
+            Ok(item) => item.map(Ok),
+            Err(e) => {
+                // 出错后不再继续读取
+                self.state = ArrayState::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::youdao_client::WordItem;
+
+    fn fixture_words() -> Vec<WordItem> {
+        serde_json::from_str(include_str!("../../tests/fixtures/youdao-words-v1.json")).unwrap()
+    }
+
+    #[test]
+    fn read_legacy_store() -> Result<(), String> {
+        let contents = include_str!("../../tests/fixtures/youdao-words-v1.json");
+        let envelope = read_store::<Vec<WordItem>, _>(contents.as_bytes(), Some(WORDS_KIND))?;
+        assert_eq!(envelope.version, LEGACY_STORE_VERSION);
+        assert_eq!(envelope.saved_at, None);
+        assert_eq!(envelope.items, fixture_words());
+        // 缺少的字段使用默认值
+        assert_eq!(envelope.items[2].phonetic, "");
+        Ok(())
+    }
+
+    #[test]
+    fn read_other_kind() {
+        let notepads = include_str!("../../tests/fixtures/maimemo-notepads-v1.json");
+        let e = read_store::<Vec<WordItem>, _>(notepads.as_bytes(), Some(WORDS_KIND)).unwrap_err();
+        assert_eq!(
+            e,
+            "store kind mismatch: expected youdao words, found maimemo notepads"
+        );
+        let words = include_str!("../../tests/fixtures/youdao-words-v1.json");
+        let e = read_store::<Vec<Value>, _>(words.as_bytes(), Some(NOTEPADS_KIND)).unwrap_err();
+        assert_eq!(
+            e,
+            "store kind mismatch: expected maimemo notepads, found youdao words"
+        );
+
+        // kind优先于item的字段
+        let tagged = r#"{"version": 2, "kind": "notepads", "items": [{"itemId": "1"}]}"#;
+        let e = read_store::<Vec<Value>, _>(tagged.as_bytes(), Some(WORDS_KIND)).unwrap_err();
+        assert!(
+            e.ends_with("expected youdao words, found maimemo notepads"),
+            "{}",
+            e
+        );
+        // 空的或无法识别的items不检查
+        for contents in ["[]", r#"[{"x": 1}]"#, r#"{"version": 2, "items": []}"#] {
+            assert!(read_store::<Vec<Value>, _>(contents.as_bytes(), Some(WORDS_KIND)).is_ok());
+        }
+    }
+
+    #[test]
+    fn read_unsupported_version() {
+        let contents = r#"{"version": 99, "items": []}"#;
+        assert!(read_store::<Vec<WordItem>, _>(contents.as_bytes(), None).is_err());
+        let mut iter = JsonArrayIter::<WordItem, _>::new(contents.as_bytes());
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn iter_json_array() -> Result<(), String> {
+        let items = fixture_words();
+        let json = serde_json::to_string_pretty(&items).unwrap();
+        let iter =
+            JsonArrayIter::<WordItem, _>::new(io::BufReader::with_capacity(7, json.as_bytes()));
+        assert_eq!(iter.collect::<Result<Vec<_>, _>>()?, items);
+
+        let empty = JsonArrayIter::<WordItem, _>::new(" [ ] ".as_bytes());
+        assert_eq!(empty.count(), 0);
+
+        let mut broken = JsonArrayIter::<WordItem, _>::new("[{\"word\": 1} ".as_bytes());
+        assert!(broken.next().unwrap().is_err());
+        assert!(broken.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn iter_envelope() -> Result<(), String> {
+        let items = fixture_words();
+        let envelope = StoreEnvelope {
+            version: STORE_VERSION,
+            saved_at: Some("2021-01-01T00:00:00+00:00".to_string()),
+            kind: Some(WORDS_KIND.to_string()),
+            items: &items,
+        };
+        let json = serde_json::to_string_pretty(&envelope).unwrap();
+        let iter =
+            JsonArrayIter::<WordItem, _>::new(io::BufReader::with_capacity(5, json.as_bytes()));
+        assert_eq!(iter.collect::<Result<Vec<_>, _>>()?, items);
+        Ok(())
+    }
+}
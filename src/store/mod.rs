@@ -0,0 +1,1205 @@
+pub mod format;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use format::{store_info, JsonArrayIter, StoreInfo, STORE_VERSION};
+
+use crate::client::{
+    context::read_only_error,
+    maimemo_client::Notepad,
+    youdao_client::{LocalMeta, WordItem},
+};
+use crate::config::AppConfig;
+use crate::hash;
+use crate::lock;
+use crate::trans;
+use chrono::Utc;
+use format::{StoreEnvelope, NOTEPADS_KIND, STORE_KIND_MISMATCH, WORDS_KIND};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// youdao单词本的本地存储
+pub type WordStore = Store<Vec<WordItem>>;
+
+/// maimemo notepads的本地存储
+pub type NotepadStore = Store<Vec<Notepad>>;
+
+/// 一个持久化到json文件的store。
+///
+/// 通过dirty标记记录数据是否被修改，仅在dirty时[`Store::flush`]才会写入文件。
+/// 持久化需要显式调用flush，不会在drop时自动保存。
+///
+/// 文件格式见[`format`]，旧版本的文件在加载时被升级并标记为dirty，下次flush时写入新格式。
+/// 读写文件时分别持有共享锁与排它锁，见[`crate::lock`]
+///
+/// 保存前通过[`StoreItems`]排序，相同的数据总是写入相同的内容，便于使用git管理文件
+#[derive(Debug)]
+pub struct Store<T> {
+    path: String,
+    data: T,
+    dirty: bool,
+    saved_at: Option<String>,
+    pretty: bool,
+}
+
+/// 保存前对数据排序，使文件内容与获取数据的顺序无关
+pub trait StoreItems {
+    /// 保存在文件中的类型，加载时检查，避免加载其它服务的文件。None时不检查
+    const KIND: Option<&'static str>;
+
+    fn sort_items(&mut self);
+}
+
+impl StoreItems for Vec<WordItem> {
+    const KIND: Option<&'static str> = Some(WORDS_KIND);
+
+    /// 按(modified_time, item_id)排序
+    fn sort_items(&mut self) {
+        self.sort_by(|a, b| {
+            a.modified_time
+                .cmp(&b.modified_time)
+                .then_with(|| a.item_id.cmp(&b.item_id))
+        });
+    }
+}
+
+impl StoreItems for Vec<Notepad> {
+    const KIND: Option<&'static str> = Some(NOTEPADS_KIND);
+
+    /// 按notepad_id排序
+    fn sort_items(&mut self) {
+        self.sort_by(|a, b| a.get_notepad_id().cmp(b.get_notepad_id()));
+    }
+}
+
+impl StoreItems for Vec<String> {
+    const KIND: Option<&'static str> = None;
+
+    fn sort_items(&mut self) {
+        self.sort();
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + StoreItems> Store<T> {
+    /// 用data创建一个store。新数据未被保存，标记为dirty
+    pub fn new(path: &str, data: T) -> Self {
+        Self {
+            path: path.to_string(),
+            data,
+            dirty: true,
+            saved_at: None,
+            pretty: true,
+        }
+    }
+
+    /// 是否保存为格式化的json，默认true
+    pub fn with_pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// 从path json文件中加载
+    ///
+    /// # Errors
+    ///
+    /// 如果文件不存在、json解析失败、文件版本不被支持或文件是其它服务的数据
+    pub async fn load(path: &str) -> Result<Self, String> {
+        trace!("Loading store from path: {}", path);
+        let file = tokio::fs::File::open(path)
+            .await
+            .map_err(|e| format!("open file error: {}, path: {}", e, path))?
+            .into_std()
+            .await;
+        lock::lock_shared(&file, path)?;
+        let envelope =
+            format::read_store::<T, _>(io::BufReader::new(file), T::KIND).map_err(|e| {
+                if e.starts_with(STORE_KIND_MISMATCH) {
+                    format!("{} at {}", e, path)
+                } else {
+                    format!("{}, path: {}", e, path)
+                }
+            })?;
+        let dirty = envelope.version < STORE_VERSION;
+        if dirty {
+            info!(
+                "upgrading store from version {} to {}: {}",
+                envelope.version, STORE_VERSION, path
+            );
+        }
+        Ok(Self {
+            path: path.to_string(),
+            data: envelope.items,
+            dirty,
+            saved_at: envelope.saved_at,
+            pretty: true,
+        })
+    }
+
+    /// 从path中加载，如果文件不存在则使用默认数据
+    pub async fn load_or_default(path: &str) -> Result<Self, String>
+    where
+        T: Default,
+    {
+        if Path::new(path).exists() {
+            Self::load(path).await
+        } else {
+            debug!("not found store file, using default data. path: {}", path);
+            Ok(Self::new(path, T::default()))
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get(&self) -> &T {
+        &self.data
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    /// 上次保存的时间。rfc3339格式，v1文件与未保存的store为None
+    pub fn get_saved_at(&self) -> Option<&str> {
+        self.saved_at.as_deref()
+    }
+
+    /// 获取可变数据，同时标记为dirty
+    pub fn get_mut(&mut self) -> &mut T {
+        self.dirty = true;
+        &mut self.data
+    }
+
+    /// 替换全部数据并标记为dirty
+    pub fn set(&mut self, data: T) {
+        self.data = data;
+        self.dirty = true;
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// 无论是否dirty都以最新格式写入文件。
+    ///
+    /// 数据与文件中的相同时保留原来的saved_at，两次保存的内容完全一致
+    pub fn save(&mut self) -> Result<(), String> {
+        self.data.sort_items();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(&self.path)
+            .map_err(|e| format!("open file error: {}, path: {}", e, self.path))?;
+        lock::lock_exclusive(&file, &self.path)?;
+        let unchanged = match self.saved_at.as_deref() {
+            Some(saved_at) => {
+                let mut existing = String::new();
+                file.read_to_string(&mut existing)
+                    .map_err(|e| format!("read file error: {}, path: {}", e, self.path))?;
+                let contents = self.serialize(saved_at)?;
+                Some(contents).filter(|c| *c == existing)
+            }
+            None => None,
+        };
+        let (saved_at, contents) = match unchanged {
+            Some(contents) => {
+                trace!("store is unchanged, keep saved_at. path: {}", self.path);
+                (self.saved_at.clone().unwrap_or_default(), contents)
+            }
+            None => {
+                let saved_at = Utc::now().to_rfc3339();
+                let contents = self.serialize(&saved_at)?;
+                (saved_at, contents)
+            }
+        };
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(contents.as_bytes()))
+            .map_err(|e| format!("save json error: {}, path: {}", e, self.path))?;
+        self.saved_at = Some(saved_at);
+        Ok(())
+    }
+
+    fn serialize(&self, saved_at: &str) -> Result<String, String> {
+        let envelope = StoreEnvelope {
+            version: STORE_VERSION,
+            saved_at: Some(saved_at.to_string()),
+            kind: T::KIND.map(str::to_string),
+            items: &self.data,
+        };
+        if self.pretty {
+            serde_json::to_string_pretty(&envelope)
+        } else {
+            serde_json::to_string(&envelope)
+        }
+        .map_err(|e| format!("serialize store error: {}, path: {}", e, self.path))
+    }
+
+    /// 仅在dirty时写入文件。返回是否发生了写入
+    pub fn flush(&mut self) -> Result<bool, String> {
+        if !self.dirty {
+            trace!("store is not dirty, skip flushing path: {}", self.path);
+            return Ok(false);
+        }
+        self.save()?;
+        self.dirty = false;
+        info!("store persistence successful path: {}", self.path);
+        Ok(true)
+    }
+}
+
+/// 合并后各类单词的数量
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeReport {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+/// merge时item中没有local则保留old的local，first_seen总是保留old的。
+///
+/// seen_at为从youdao refresh的时间，用于更新last_seen_in_remote。返回合并后的item
+pub(crate) fn keep_local<'a>(
+    old: &WordItem,
+    item: &'a mut WordItem,
+    seen_at: Option<usize>,
+) -> &'a WordItem {
+    if item.local.is_none() {
+        item.local = old.local.clone();
+    }
+    let first_seen = old.first_seen();
+    if item.first_seen() != first_seen {
+        item.local_mut().first_seen = Some(first_seen);
+    }
+    mark_seen(item, seen_at);
+    item
+}
+
+/// seen_at不为None时更新item的last_seen_in_remote
+pub(crate) fn mark_seen(item: &mut WordItem, seen_at: Option<usize>) {
+    if let Some(seen_at) = seen_at {
+        item.local_mut().last_seen_in_remote = Some(seen_at);
+    }
+}
+
+/// 除last_seen_in_remote外是否相同。refresh时仅last_seen_in_remote改变的单词不算作更新
+pub(crate) fn same_except_seen(a: &WordItem, b: &WordItem) -> bool {
+    let without_seen = |w: &WordItem| {
+        let mut w = w.clone();
+        if let Some(local) = w.local.as_mut() {
+            local.last_seen_in_remote = None;
+        }
+        w.local = w.local.filter(|local| *local != LocalMeta::default());
+        w
+    };
+    a == b || without_seen(a) == without_seen(b)
+}
+
+/// 当前时间的毫秒，用于refresh时的last_seen_in_remote
+fn now_millis() -> usize {
+    Utc::now().timestamp_millis() as usize
+}
+
+/// 单词存储的通用操作。json文件与sqlite存储都实现了该trait
+pub trait WordStorage {
+    /// 存储的位置
+    fn get_path(&self) -> &str;
+
+    /// 所有单词
+    fn words(&self) -> &[WordItem];
+
+    /// 通过item_id合并items，已存在的被更新，不存在的被添加。item中没有local时保留已存在的local
+    fn merge(&mut self, items: Vec<WordItem>) -> Result<MergeReport, String>;
+
+    /// 用从youdao refresh的items替换全部单词，不在items中的单词被删除。
+    /// items的last_seen_in_remote为当前时间
+    fn replace(&mut self, items: Vec<WordItem>) -> Result<MergeReport, String> {
+        self.replace_at(items, now_millis())
+    }
+
+    /// 同[`WordStorage::replace`]，last_seen_in_remote为seen_at
+    fn replace_at(&mut self, items: Vec<WordItem>, seen_at: usize) -> Result<MergeReport, String>;
+
+    /// 查询包含keyword的单词，忽略大小写
+    fn search(&self, keyword: &str) -> Result<Vec<WordItem>, String>;
+
+    /// 持久化未保存的修改。返回是否发生了写入
+    fn flush(&mut self) -> Result<bool, String>;
+
+    /// 修改本地单词前检查，见[`ReadOnlyStorage`]
+    fn check_writable(&self, _operation: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// 只读模式的单词存储：merge等本地修改返回[`crate::client::context::READ_ONLY_MODE`]错误，
+/// refresh使用的replace不受影响
+pub struct ReadOnlyStorage(Box<dyn WordStorage>);
+
+impl ReadOnlyStorage {
+    pub fn new(inner: Box<dyn WordStorage>) -> Self {
+        Self(inner)
+    }
+}
+
+impl WordStorage for ReadOnlyStorage {
+    fn get_path(&self) -> &str {
+        self.0.get_path()
+    }
+
+    fn words(&self) -> &[WordItem] {
+        self.0.words()
+    }
+
+    fn merge(&mut self, _items: Vec<WordItem>) -> Result<MergeReport, String> {
+        Err(read_only_error("merge words"))
+    }
+
+    fn replace_at(&mut self, items: Vec<WordItem>, seen_at: usize) -> Result<MergeReport, String> {
+        self.0.replace_at(items, seen_at)
+    }
+
+    fn search(&self, keyword: &str) -> Result<Vec<WordItem>, String> {
+        self.0.search(keyword)
+    }
+
+    fn flush(&mut self) -> Result<bool, String> {
+        self.0.flush()
+    }
+
+    fn check_writable(&self, operation: &str) -> Result<(), String> {
+        Err(read_only_error(operation))
+    }
+}
+
+/// 通过config打开单词存储。
+///
+/// 如果`storage: sqlite`或dictionary_path以`.db`结尾则使用sqlite存储，否则使用json文件。
+/// create_if_missing=true时如果json文件不存在则使用一个空的存储，否则返回error。
+/// read_only时为[`ReadOnlyStorage`]
+pub async fn open_word_storage(
+    config: &AppConfig,
+    create_if_missing: bool,
+    read_only: bool,
+) -> Result<Box<dyn WordStorage>, String> {
+    let path = config.get_dictionary_path();
+    let storage: Box<dyn WordStorage> = if is_sqlite_storage(config) {
+        open_sqlite_storage(path)?
+    } else if create_if_missing {
+        Box::new(
+            WordStore::load_or_default(path)
+                .await?
+                .with_pretty(config.is_pretty_store()),
+        )
+    } else {
+        Box::new(
+            WordStore::load(path)
+                .await?
+                .with_pretty(config.is_pretty_store()),
+        )
+    };
+    if read_only {
+        return Ok(Box::new(ReadOnlyStorage::new(storage)));
+    }
+    Ok(storage)
+}
+
+/// 将json文件path中的单词合并到storage中
+pub async fn migrate_json_words(
+    path: &str,
+    storage: &mut dyn WordStorage,
+) -> Result<MergeReport, String> {
+    storage.check_writable("migrate store")?;
+    if storage.get_path() == path {
+        return Err(format!("unable to migrate store to itself: {}", path));
+    }
+    let items = WordStore::load(path).await?.into_inner();
+    info!(
+        "migrating {} words from {} to {}",
+        items.len(),
+        path,
+        storage.get_path()
+    );
+    storage.merge(items)
+}
+
+/// 是否配置了sqlite存储
+pub fn is_sqlite_storage(config: &AppConfig) -> bool {
+    match config.get_storage() {
+        Some(storage) => storage.eq_ignore_ascii_case("sqlite"),
+        None => config.get_dictionary_path().ends_with(".db"),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite_storage(path: &str) -> Result<Box<dyn WordStorage>, String> {
+    Ok(Box::new(sqlite::SqliteWordStore::open(path)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite_storage(path: &str) -> Result<Box<dyn WordStorage>, String> {
+    Err(format!(
+        "sqlite storage is not supported for path: {}. please build with `--features sqlite`",
+        path
+    ))
+}
+
+/// csv中的一行。item_id为空时会生成一个确定的id
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRecord {
+    #[serde(default)]
+    item_id: Option<String>,
+    #[serde(default)]
+    book_id: String,
+    #[serde(default)]
+    book_name: String,
+    word: String,
+    #[serde(default)]
+    trans: String,
+    #[serde(default)]
+    phonetic: String,
+    #[serde(default)]
+    modified_time: Option<usize>,
+    /// 从trans中识别的词性与去掉词性的释义，仅用于导出，导入时忽略
+    #[serde(default)]
+    pos: String,
+    #[serde(default)]
+    gloss: String,
+}
+
+impl Store<Vec<WordItem>> {
+    /// 通过item_id将items合并到store中。已存在且内容不同的item被替换，不存在的被添加
+    pub fn merge(&mut self, items: Vec<WordItem>) -> MergeReport {
+        self.merge_at(items, None)
+    }
+
+    /// 同[`Store::merge`]，seen_at不为None时同时更新items的last_seen_in_remote
+    fn merge_at(&mut self, items: Vec<WordItem>, seen_at: Option<usize>) -> MergeReport {
+        let mut report = MergeReport::default();
+        let mut indexes = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (w.item_id.clone(), i))
+            .collect::<HashMap<_, _>>();
+        for mut item in items {
+            match indexes.get(&item.item_id) {
+                Some(&i) if *keep_local(&self.data[i], &mut item, seen_at) == self.data[i] => {
+                    report.unchanged += 1
+                }
+                Some(&i) => {
+                    if same_except_seen(&self.data[i], &item) {
+                        report.unchanged += 1;
+                    } else {
+                        report.updated += 1;
+                    }
+                    self.data[i] = item;
+                    self.dirty = true;
+                }
+                None => {
+                    mark_seen(&mut item, seen_at);
+                    indexes.insert(item.item_id.clone(), self.data.len());
+                    self.data.push(item);
+                    report.added += 1;
+                    self.dirty = true;
+                }
+            }
+        }
+        debug!("merged words: {:?}", report);
+        report
+    }
+
+    /// 用从youdao refresh的items替换全部单词，返回与原数据对比的结果。
+    /// items的last_seen_in_remote为当前时间
+    pub fn replace(&mut self, items: Vec<WordItem>) -> MergeReport {
+        self.replace_at(items, now_millis())
+    }
+
+    /// 同[`Store::replace`]，last_seen_in_remote为seen_at
+    pub fn replace_at(&mut self, items: Vec<WordItem>, seen_at: usize) -> MergeReport {
+        let ids = items
+            .iter()
+            .map(|w| w.item_id.clone())
+            .collect::<HashSet<_>>();
+        let len = self.data.len();
+        self.data.retain(|w| ids.contains(&w.item_id));
+        let removed = len - self.data.len();
+        if removed > 0 {
+            self.dirty = true;
+        }
+        let mut report = self.merge_at(items, Some(seen_at));
+        report.removed = removed;
+        report
+    }
+
+    /// 从store文件中逐个读取单词，不会一次性加载全部单词到内存中。兼容v1与v2格式
+    pub fn iter_from_file(
+        path: &str,
+    ) -> Result<JsonArrayIter<WordItem, io::BufReader<std::fs::File>>, String> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("open file error: {}, path: {}", e, path))?;
+        // 共享锁在iter drop时释放
+        lock::lock_shared(&file, path)?;
+        Ok(JsonArrayIter::new(io::BufReader::new(file)))
+    }
+
+    /// 以csv格式输出所有单词，见[`write_csv`]
+    pub fn export_csv<W: Write>(&self, w: W) -> Result<(), String> {
+        write_csv(&self.data, w)
+    }
+
+    /// 从csv中读取单词。缺少item_id的行使用word与book_name生成一个确定的id
+    pub fn import_csv<R: Read>(r: R) -> Result<Vec<WordItem>, String> {
+        let mut reader = csv::Reader::from_reader(r);
+        let mut items = vec![];
+        for record in reader.deserialize::<CsvRecord>() {
+            let CsvRecord {
+                item_id,
+                book_id,
+                book_name,
+                word,
+                trans,
+                phonetic,
+                modified_time,
+                ..
+            } = record.map_err(|e| format!("read csv error: {}", e))?;
+            let item_id = item_id
+                .filter(|id| !id.is_empty())
+                .unwrap_or_else(|| synthetic_item_id(&word, &book_name));
+            items.push(WordItem {
+                item_id,
+                book_id,
+                book_name,
+                word,
+                trans,
+                phonetic,
+                modified_time: modified_time.unwrap_or(0),
+                local: None,
+            });
+        }
+        debug!("imported {} words from csv", items.len());
+        Ok(items)
+    }
+}
+
+/// txt导入时单词与注释的分隔，注释作为trans
+const TXT_ANNOTATION_SEPARATORS: &[char] = &['—', ':', '：'];
+
+impl Store<Vec<Notepad>> {
+    /// 用notepads替换全部notepad，返回通过notepad_id与原数据对比的结果
+    pub fn replace(&mut self, notepads: Vec<Notepad>) -> MergeReport {
+        let mut report = MergeReport::default();
+        let old = self
+            .data
+            .iter()
+            .map(|n| (n.get_notepad_id(), n))
+            .collect::<HashMap<_, _>>();
+        for notepad in &notepads {
+            match old.get(notepad.get_notepad_id()) {
+                Some(&old) if old == notepad => report.unchanged += 1,
+                Some(_) => report.updated += 1,
+                None => report.added += 1,
+            }
+        }
+        let ids = notepads
+            .iter()
+            .map(|n| n.get_notepad_id())
+            .collect::<HashSet<_>>();
+        report.removed = old.keys().filter(|id| !ids.contains(*id)).count();
+        if report.added > 0 || report.updated > 0 || report.removed > 0 {
+            self.dirty = true;
+        }
+        self.data = notepads;
+        debug!("replaced notepads: {:?}", report);
+        report
+    }
+
+    /// 通过notepad_id添加或更新notepads，不删除其它的notepad
+    pub fn merge(&mut self, notepads: Vec<Notepad>) -> MergeReport {
+        let mut report = MergeReport::default();
+        for notepad in notepads {
+            match self
+                .data
+                .iter_mut()
+                .find(|n| n.get_notepad_id() == notepad.get_notepad_id())
+            {
+                Some(old) if *old == notepad => report.unchanged += 1,
+                Some(old) => {
+                    *old = notepad;
+                    report.updated += 1;
+                }
+                None => {
+                    self.data.push(notepad);
+                    report.added += 1;
+                }
+            }
+        }
+        if report.added > 0 || report.updated > 0 {
+            self.dirty = true;
+        }
+        debug!("merged notepads: {:?}", report);
+        report
+    }
+}
+
+/// 从文本或markdown中读取单词，每行一个单词。
+///
+/// 忽略空行与markdown标题，去除列表标记如`- `, `* `, `1. `。单词后`—`或`:`分隔的注释作为trans。
+/// item_id通过word与book_name生成，见[`synthetic_item_id`]
+pub fn import_txt<R: Read>(
+    mut r: R,
+    book_name: &str,
+    modified_time: usize,
+) -> Result<Vec<WordItem>, String> {
+    let mut contents = String::new();
+    r.read_to_string(&mut contents)
+        .map_err(|e| format!("read txt error: {}", e))?;
+    let items = contents
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .filter_map(parse_txt_line)
+        .map(|(word, trans)| WordItem {
+            item_id: synthetic_item_id(&word, book_name),
+            book_id: String::new(),
+            book_name: book_name.to_string(),
+            word,
+            trans,
+            phonetic: String::new(),
+            modified_time,
+            local: None,
+        })
+        .collect::<Vec<_>>();
+    debug!("imported {} words from txt", items.len());
+    Ok(items)
+}
+
+/// 解析一行为(word, trans)。空行与标题返回None
+fn parse_txt_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let line = strip_list_marker(line);
+    let (word, trans) = match line.find(TXT_ANNOTATION_SEPARATORS) {
+        Some(i) => {
+            let sep_len = line[i..].chars().next().map_or(1, char::len_utf8);
+            (&line[..i], line[i + sep_len..].trim())
+        }
+        None => (line, ""),
+    };
+    let word = word.trim();
+    if word.is_empty() {
+        return None;
+    }
+    Some((word.to_string(), trans.to_string()))
+}
+
+/// 去除markdown的无序与有序列表标记
+fn strip_list_marker(line: &str) -> &str {
+    if let Some(rest) = line.strip_prefix(&['-', '*', '+'][..]) {
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            return rest.trim_start();
+        }
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        let rest = &line[digits..];
+        if let Some(rest) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return rest.trim_start();
+        }
+    }
+    line
+}
+
+impl WordStorage for Store<Vec<WordItem>> {
+    fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    fn words(&self) -> &[WordItem] {
+        &self.data
+    }
+
+    fn merge(&mut self, items: Vec<WordItem>) -> Result<MergeReport, String> {
+        Ok(Store::<Vec<WordItem>>::merge(self, items))
+    }
+
+    fn replace_at(&mut self, items: Vec<WordItem>, seen_at: usize) -> Result<MergeReport, String> {
+        Ok(Store::<Vec<WordItem>>::replace_at(self, items, seen_at))
+    }
+
+    fn search(&self, keyword: &str) -> Result<Vec<WordItem>, String> {
+        let keyword = keyword.to_lowercase();
+        Ok(self
+            .data
+            .iter()
+            .filter(|w| w.word.to_lowercase().contains(&keyword))
+            .cloned()
+            .collect())
+    }
+
+    fn flush(&mut self) -> Result<bool, String> {
+        Store::flush(self)
+    }
+}
+
+/// 以csv格式输出items。列：item_id, book_id, book_name, word, trans, phonetic, modified_time,
+/// pos, gloss。pos与gloss见[`trans::parts_of_speech`]与[`trans::join_senses`]
+pub fn write_csv<W: Write>(items: &[WordItem], w: W) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(w);
+    for item in items {
+        let senses = trans::parse_trans(&item.trans);
+        writer
+            .serialize(CsvRecord {
+                item_id: Some(item.item_id.clone()),
+                book_id: item.book_id.clone(),
+                book_name: item.book_name.clone(),
+                word: item.word.clone(),
+                trans: item.trans.clone(),
+                phonetic: item.phonetic.clone(),
+                modified_time: Some(item.modified_time),
+                pos: trans::parts_of_speech(&senses),
+                gloss: trans::join_senses(&senses, false),
+            })
+            .map_err(|e| format!("write csv error: {}, word: {}", e, item.word))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("flush csv error: {}", e))
+}
+
+/// 用word与book_name的fnv-1a hash生成item_id，保证多次导入时id一致
+pub fn synthetic_item_id(word: &str, book_name: &str) -> String {
+    let hash = hash::fnv1a(format!("{}\0{}", word, book_name).as_bytes());
+    format!("imported-{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::youdao_client::LocalMeta;
+    use crate::config::save_json;
+    use std::path::Path;
+
+    fn temp_path(dir: &tempfile::TempDir, name: &str) -> String {
+        dir.path().join(name).to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn flush_skips_unchanged() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "words.json");
+        Store::new(&path, vec!["a".to_string()]).save()?;
+
+        let mut store = Store::<Vec<String>>::load(&path).await?;
+        assert!(!store.is_dirty());
+        assert_eq!(store.get(), &vec!["a".to_string()]);
+        std::fs::remove_file(&path).map_err(|e| format!("{:?}", e))?;
+
+        assert!(!store.flush()?);
+        assert!(!Path::new(&path).exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn flush_writes_dirty() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "words.json");
+
+        let mut store = Store::new(&path, vec!["a".to_string()]);
+        assert!(store.is_dirty());
+        assert!(store.flush()?);
+        assert!(!store.is_dirty());
+
+        store.get_mut().push("b".to_string());
+        assert!(store.is_dirty());
+        assert!(store.flush()?);
+
+        let loaded = Store::<Vec<String>>::load(&path).await?;
+        assert_eq!(loaded.get(), &vec!["a".to_string(), "b".to_string()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deterministic_saves() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "words.json");
+        let mut later = word_item("1", "a", "x");
+        later.modified_time += 1;
+        let items = vec![later, word_item("3", "c", "x"), word_item("2", "b", "x")];
+
+        let mut store = WordStore::new(&path, vec![]);
+        store.replace_at(items.clone(), 1000);
+        store.save()?;
+        let first = std::fs::read(&path).map_err(|e| format!("{:?}", e))?;
+        let ids = store
+            .get()
+            .iter()
+            .map(|w| w.item_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["2", "3", "1"]);
+        assert!(String::from_utf8_lossy(&first).contains("\n  \"items\": [\n"));
+
+        // 同一时间refresh时不同的获取顺序
+        let mut store = WordStore::load(&path).await?;
+        store.replace_at(items.into_iter().rev().collect(), 1000);
+        store.save()?;
+        let second = std::fs::read(&path).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(first, second);
+
+        let mut store = WordStore::load(&path).await?.with_pretty(false);
+        store.mark_dirty();
+        store.flush()?;
+        let compact = std::fs::read_to_string(&path).map_err(|e| format!("{:?}", e))?;
+        assert!(!compact.contains('\n'));
+        assert_eq!(WordStore::load(&path).await?.get(), store.get());
+        Ok(())
+    }
+
+    fn word_item(item_id: &str, word: &str, trans: &str) -> WordItem {
+        WordItem {
+            item_id: item_id.to_string(),
+            book_id: "1".to_string(),
+            book_name: "GRE".to_string(),
+            word: word.to_string(),
+            trans: trans.to_string(),
+            phonetic: "[test]".to_string(),
+            modified_time: 1602900865000,
+            local: None,
+        }
+    }
+
+    #[test]
+    fn merge_words() {
+        let mut store = WordStore::new("words.json", vec![word_item("1", "a", "x")]);
+        store.dirty = false;
+        let report = store.merge(vec![word_item("1", "a", "x")]);
+        assert_eq!(report.unchanged, 1);
+        assert!(!store.is_dirty());
+
+        let report = store.merge(vec![word_item("1", "a", "y"), word_item("2", "b", "z")]);
+        assert_eq!(
+            report,
+            MergeReport {
+                added: 1,
+                updated: 1,
+                ..Default::default()
+            }
+        );
+        assert!(store.is_dirty());
+        assert_eq!(store.get()[0].trans, "y");
+        assert_eq!(store.get().len(), 2);
+    }
+
+    #[test]
+    fn replace_words() {
+        let mut store = WordStore::new(
+            "words.json",
+            vec![word_item("1", "a", "x"), word_item("2", "b", "x")],
+        );
+        let report = store.replace(vec![word_item("2", "b", "x"), word_item("3", "c", "x")]);
+        assert_eq!(
+            report,
+            MergeReport {
+                added: 1,
+                unchanged: 1,
+                removed: 1,
+                ..Default::default()
+            }
+        );
+        let ids = store
+            .get()
+            .iter()
+            .map(|w| w.item_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn merge_keeps_local() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "words.json");
+        let local = LocalMeta {
+            tags: vec!["gre".to_string()],
+            note: Some("from Economist article".to_string()),
+            ..Default::default()
+        };
+        let mut tagged = word_item("1", "a", "x");
+        tagged.local = Some(local.clone());
+        let mut store = WordStore::new(&path, vec![tagged, word_item("2", "b", "x")]);
+        store.save()?;
+        // 没有local或local为默认值的单词不保存该字段
+        store.get_mut()[1].local_mut();
+        store.save()?;
+        let saved = std::fs::read_to_string(&path).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(saved.matches("\"local\"").count(), 1);
+
+        let mut store = WordStore::load(&path).await?;
+        let items = vec![word_item("1", "a", "x"), word_item("2", "b", "x")];
+        // 仅last_seen_in_remote改变不算作更新
+        let report = store.replace_at(items.clone(), 10);
+        assert_eq!(report.unchanged, 2);
+        store.dirty = false;
+        let report = store.replace_at(items, 10);
+        assert_eq!(report.unchanged, 2);
+        assert!(!store.is_dirty());
+
+        let report = store.replace_at(vec![word_item("1", "a", "y")], 20);
+        assert_eq!((report.updated, report.removed), (1, 1));
+        assert_eq!(store.get()[0].trans, "y");
+        assert_eq!(
+            store.get()[0].local,
+            Some(LocalMeta {
+                last_seen_in_remote: Some(20),
+                ..local
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn history_across_refreshes() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "words.json");
+        let item = |id: &str, modified_time: usize| WordItem {
+            modified_time,
+            ..word_item(id, id, "x")
+        };
+        let history = |store: &WordStore, id: &str| {
+            let w = store.get().iter().find(|w| w.item_id == id).unwrap();
+            (w.first_seen(), w.last_seen_in_remote())
+        };
+        let mut store = WordStore::new(&path, vec![]);
+        store.replace_at(vec![item("a", 100)], 1000);
+        assert_eq!(history(&store, "a"), (100, Some(1000)));
+        store.save()?;
+
+        // a在youdao中被修改，b为新单词
+        let mut store = WordStore::load(&path).await?;
+        let report = store.replace_at(vec![item("a", 300), item("b", 200)], 2000);
+        assert_eq!((report.updated, report.added), (1, 1));
+        assert_eq!(history(&store, "a"), (100, Some(2000)));
+        assert_eq!(history(&store, "b"), (200, Some(2000)));
+
+        // 本地修改不影响last_seen_in_remote
+        store.merge(vec![item("b", 400)]);
+        assert_eq!(history(&store, "b"), (200, Some(2000)));
+        let report = store.replace_at(vec![item("a", 300), item("b", 400)], 3000);
+        assert_eq!(report.unchanged, 2);
+        assert_eq!(history(&store, "a"), (100, Some(3000)));
+        assert_eq!(history(&store, "b"), (200, Some(3000)));
+        store.save()?;
+
+        let store = WordStore::load(&path).await?;
+        assert_eq!(history(&store, "a"), (100, Some(3000)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn history_of_old_file() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "words.json");
+        std::fs::copy("tests/fixtures/youdao-words-v1.json", &path)
+            .map_err(|e| format!("{:?}", e))?;
+        // 没有历史的旧文件first_seen为modified_time
+        let mut store = WordStore::load(&path).await?;
+        for w in store.get() {
+            assert_eq!(w.local, None);
+            assert_eq!(w.first_seen(), w.modified_time);
+            assert_eq!(w.last_seen_in_remote(), None);
+        }
+
+        // refresh后youdao中修改的单词保留原来的modified_time作为first_seen
+        let mut items = store.get().clone();
+        items[0].modified_time += 1000;
+        store.replace_at(items, 5000);
+        store.save()?;
+        let store = WordStore::load(&path).await?;
+        let words = store.get();
+        assert_eq!(words[0].first_seen(), 1602900865000);
+        assert_eq!(words[0].modified_time, 1602900866000);
+        assert_eq!(words[1].first_seen(), words[1].modified_time);
+        assert!(words.iter().all(|w| w.last_seen_in_remote() == Some(5000)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn iter_from_file() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "words.json");
+        let items = vec![word_item("1", "a", "x"), word_item("2", "b", "y")];
+        save_json(&items, &path).map_err(|e| format!("{:?}", e))?;
+        let loaded = WordStore::iter_from_file(&path)?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(loaded, items);
+
+        WordStore::new(&path, items.clone()).save()?;
+        let loaded = WordStore::iter_from_file(&path)?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(loaded, items);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upgrade_legacy_words() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "words.json");
+        std::fs::copy("tests/fixtures/youdao-words-v1.json", &path)
+            .map_err(|e| format!("{:?}", e))?;
+        let info = store_info(&path)?;
+        assert_eq!((info.version, info.items, info.saved_at), (1, 3, None));
+
+        let mut store = WordStore::load(&path).await?;
+        assert!(store.is_dirty());
+        assert_eq!(store.get_saved_at(), None);
+        let items = store.get().clone();
+        assert_eq!(items[0].word, "accommodate");
+        assert_eq!(items[2].phonetic, "");
+        assert!(store.flush()?);
+
+        let info = store_info(&path)?;
+        assert_eq!((info.version, info.items), (STORE_VERSION, 3));
+        assert_eq!(info.saved_at.as_deref(), store.get_saved_at());
+        let store = WordStore::load(&path).await?;
+        assert!(!store.is_dirty());
+        assert_eq!(store.get(), &items);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upgrade_legacy_notepads() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = temp_path(&dir, "notepads.json");
+        std::fs::copy("tests/fixtures/maimemo-notepads-v1.json", &path)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let mut store = NotepadStore::load(&path).await?;
+        assert!(store.is_dirty());
+        assert_eq!(store.get().len(), 2);
+        assert_eq!(store.get()[1].get_contents(), None);
+        assert!(store.flush()?);
+        assert_eq!(store_info(&path)?.version, STORE_VERSION);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_other_service_store() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let words = temp_path(&dir, "words.json");
+        let notepads = temp_path(&dir, "notepads.json");
+        // youdao与maimemo的文件被交换
+        std::fs::copy("tests/fixtures/maimemo-notepads-v1.json", &words)
+            .map_err(|e| format!("{:?}", e))?;
+        std::fs::copy("tests/fixtures/youdao-words-v1.json", &notepads)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let e = WordStore::load(&words).await.err().unwrap();
+        assert_eq!(
+            e,
+            format!(
+                "store kind mismatch: expected youdao words, found maimemo notepads at {}",
+                words
+            )
+        );
+        let e = NotepadStore::load(&notepads).await.err().unwrap();
+        assert_eq!(
+            e,
+            format!(
+                "store kind mismatch: expected maimemo notepads, found youdao words at {}",
+                notepads
+            )
+        );
+
+        // 保存后的文件通过kind识别，即使items为空
+        NotepadStore::new(&notepads, vec![]).save()?;
+        assert!(std::fs::read_to_string(&notepads)
+            .map_err(|e| format!("{:?}", e))?
+            .contains("\"kind\": \"notepads\""));
+        let e = WordStore::load(&notepads).await.err().unwrap();
+        assert!(e.contains("found maimemo notepads at"), "{}", e);
+        Ok(())
+    }
+
+    #[test]
+    fn search_words() -> Result<(), String> {
+        let store = WordStore::new(
+            "words.json",
+            vec![
+                word_item("1", "Accommodate", "x"),
+                word_item("2", "run", "x"),
+            ],
+        );
+        let found = WordStorage::search(&store, "accom")?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].item_id, "1");
+        Ok(())
+    }
+
+    #[test]
+    fn csv_round_trip() -> Result<(), String> {
+        let items = vec![
+            word_item("1", "accommodate", "vt. 容纳, 使适应\nvi. \"适应\""),
+            word_item("2", "run", "v. 跑"),
+        ];
+        let store = WordStore::new("words.json", items.clone());
+        let mut data = vec![];
+        store.export_csv(&mut data)?;
+        let contents = String::from_utf8(data.clone()).unwrap();
+        assert!(contents.starts_with(
+            "item_id,book_id,book_name,word,trans,phonetic,modified_time,pos,gloss\n"
+        ));
+        assert!(contents.ends_with(",1602900865000,v.,跑\n"), "{}", contents);
+        assert!(contents.contains(",vt. vi.,\"容纳, 使适应; \"\"适应\"\"\"\n"));
+
+        let imported = WordStore::import_csv(data.as_slice())?;
+        assert_eq!(imported, items);
+        Ok(())
+    }
+
+    #[test]
+    fn import_markdown_words() -> Result<(), String> {
+        let contents = "\u{feff}# GRE\r\n\r\n- accommodate — vt. 容纳\r\n* run: v. 跑\r\n\
+                        12. serendipity\r\n3) look up：查找\r\nplain\r\n- \r\n";
+        let items = import_txt(contents.as_bytes(), "imported:notes.md", 1)?;
+        let words = items
+            .iter()
+            .map(|w| (w.word.as_str(), w.trans.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            words,
+            vec![
+                ("accommodate", "vt. 容纳"),
+                ("run", "v. 跑"),
+                ("serendipity", ""),
+                ("look up", "查找"),
+                ("plain", ""),
+            ]
+        );
+        assert_eq!(items[0].book_name, "imported:notes.md");
+        assert_eq!(items[0].modified_time, 1);
+        assert_eq!(
+            items[0].item_id,
+            synthetic_item_id("accommodate", "imported:notes.md")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn csv_import_synthetic_id() -> Result<(), String> {
+        let contents = "item_id,book_name,word,trans\n,GRE,run,v. 跑\n,GRE,run,v. 跑\n";
+        let imported = WordStore::import_csv(contents.as_bytes())?;
+        assert_eq!(imported.len(), 2);
+        assert_eq!(imported[0].item_id, imported[1].item_id);
+        assert_eq!(imported[0].item_id, synthetic_item_id("run", "GRE"));
+        assert_ne!(imported[0].item_id, synthetic_item_id("run", "daily"));
+        Ok(())
+    }
+}
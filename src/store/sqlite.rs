@@ -0,0 +1,308 @@
+use super::{keep_local, mark_seen, same_except_seen, MergeReport, WordStorage};
+use crate::client::youdao_client::WordItem;
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS words (
+    item_id TEXT PRIMARY KEY,
+    word TEXT NOT NULL,
+    modified_time INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_words_word ON words(word);
+CREATE INDEX IF NOT EXISTS idx_words_modified_time ON words(modified_time);
+";
+
+/// 用sqlite保存单词的存储。
+///
+/// 打开时加载所有单词到内存中，所有修改通过item_id增量upsert并立即提交，
+/// 不需要重写整个文件
+pub struct SqliteWordStore {
+    path: String,
+    conn: Connection,
+    items: Vec<WordItem>,
+}
+
+impl SqliteWordStore {
+    /// 打开path上的db，如果不存在则创建
+    pub fn open(path: &str) -> Result<Self, String> {
+        debug!("opening sqlite word store: {}", path);
+        let conn = Connection::open(path)
+            .map_err(|e| format!("open sqlite error: {}, path: {}", e, path))?;
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("init sqlite schema error: {}, path: {}", e, path))?;
+        let items = Self::query(&conn, "SELECT data FROM words ORDER BY modified_time", &[])?;
+        debug!("loaded {} words from sqlite: {}", items.len(), path);
+        Ok(Self {
+            path: path.to_string(),
+            conn,
+            items,
+        })
+    }
+
+    fn query(
+        conn: &Connection,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<WordItem>, String> {
+        let mut stmt = conn.prepare(sql).map_err(|e| format!("{:?}", e))?;
+        let rows = stmt
+            .query_map(params, |row| row.get::<_, String>(0))
+            .map_err(|e| format!("{:?}", e))?;
+        let mut items = vec![];
+        for data in rows {
+            let data = data.map_err(|e| format!("{:?}", e))?;
+            items.push(
+                serde_json::from_str(&data)
+                    .map_err(|e| format!("parse word error: {}, data: {}", e, data))?,
+            );
+        }
+        Ok(items)
+    }
+
+    /// 同[`WordStorage::merge`]，seen_at不为None时同时更新items的last_seen_in_remote
+    fn merge_at(
+        &mut self,
+        items: Vec<WordItem>,
+        seen_at: Option<usize>,
+    ) -> Result<MergeReport, String> {
+        let mut report = MergeReport::default();
+        let mut indexes = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (w.item_id.clone(), i))
+            .collect::<HashMap<_, _>>();
+        let mut merged = self.items.clone();
+        let mut changed = vec![];
+        for mut item in items {
+            match indexes.get(&item.item_id) {
+                Some(&i) if *keep_local(&merged[i], &mut item, seen_at) == merged[i] => {
+                    report.unchanged += 1
+                }
+                Some(&i) => {
+                    if same_except_seen(&merged[i], &item) {
+                        report.unchanged += 1;
+                    } else {
+                        report.updated += 1;
+                    }
+                    merged[i] = item;
+                    changed.push(i);
+                }
+                None => {
+                    mark_seen(&mut item, seen_at);
+                    indexes.insert(item.item_id.clone(), merged.len());
+                    changed.push(merged.len());
+                    merged.push(item);
+                    report.added += 1;
+                }
+            }
+        }
+        let upserts = changed.iter().map(|&i| &merged[i]).collect::<Vec<_>>();
+        self.apply(&upserts, &[])?;
+        self.items = merged;
+        debug!("merged words: {:?}", report);
+        Ok(report)
+    }
+
+    /// 在一个事务中upsert与删除
+    fn apply(&mut self, upserts: &[&WordItem], removes: &[String]) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| format!("{:?}", e))?;
+        {
+            let mut upsert = tx
+                .prepare(
+                    "INSERT INTO words (item_id, word, modified_time, data) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(item_id) DO UPDATE SET
+                        word = excluded.word,
+                        modified_time = excluded.modified_time,
+                        data = excluded.data",
+                )
+                .map_err(|e| format!("{:?}", e))?;
+            for item in upserts {
+                let data = serde_json::to_string(item).map_err(|e| format!("{:?}", e))?;
+                upsert
+                    .execute(params![
+                        item.item_id,
+                        item.word,
+                        item.modified_time as i64,
+                        data
+                    ])
+                    .map_err(|e| format!("upsert word {} error: {}", item.word, e))?;
+            }
+            let mut delete = tx
+                .prepare("DELETE FROM words WHERE item_id = ?1")
+                .map_err(|e| format!("{:?}", e))?;
+            for item_id in removes {
+                delete
+                    .execute(params![item_id])
+                    .map_err(|e| format!("delete word {} error: {}", item_id, e))?;
+            }
+        }
+        tx.commit().map_err(|e| format!("{:?}", e))?;
+        debug!(
+            "sqlite upserted {} and removed {} words",
+            upserts.len(),
+            removes.len()
+        );
+        Ok(())
+    }
+}
+
+impl WordStorage for SqliteWordStore {
+    fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    fn words(&self) -> &[WordItem] {
+        &self.items
+    }
+
+    fn merge(&mut self, items: Vec<WordItem>) -> Result<MergeReport, String> {
+        self.merge_at(items, None)
+    }
+
+    fn replace_at(&mut self, items: Vec<WordItem>, seen_at: usize) -> Result<MergeReport, String> {
+        let ids = items
+            .iter()
+            .map(|w| w.item_id.as_str())
+            .collect::<HashSet<_>>();
+        let removes = self
+            .items
+            .iter()
+            .filter(|w| !ids.contains(w.item_id.as_str()))
+            .map(|w| w.item_id.clone())
+            .collect::<Vec<_>>();
+        self.apply(&[], &removes)?;
+        let removed = removes.into_iter().collect::<HashSet<_>>();
+        self.items.retain(|w| !removed.contains(&w.item_id));
+        let mut report = self.merge_at(items, Some(seen_at))?;
+        report.removed = removed.len();
+        Ok(report)
+    }
+
+    fn search(&self, keyword: &str) -> Result<Vec<WordItem>, String> {
+        let pattern = format!(
+            "%{}%",
+            keyword
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        Self::query(
+            &self.conn,
+            "SELECT data FROM words WHERE word LIKE ?1 ESCAPE '\\' ORDER BY modified_time",
+            &[&pattern],
+        )
+    }
+
+    fn flush(&mut self) -> Result<bool, String> {
+        // 修改已在merge时提交
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::save_json;
+    use crate::store::migrate_json_words;
+
+    fn word_item(item_id: &str, word: &str, trans: &str) -> WordItem {
+        WordItem {
+            item_id: item_id.to_string(),
+            book_id: "1".to_string(),
+            book_name: "GRE".to_string(),
+            word: word.to_string(),
+            trans: trans.to_string(),
+            phonetic: "[test]".to_string(),
+            modified_time: 1602900865000,
+            local: None,
+        }
+    }
+
+    fn db_path(dir: &tempfile::TempDir) -> String {
+        dir.path().join("words.db").to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn upsert_words() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = db_path(&dir);
+        let mut store = SqliteWordStore::open(&path)?;
+        let report = store.merge(vec![word_item("1", "a", "x"), word_item("2", "b", "x")])?;
+        assert_eq!(report.added, 2);
+
+        let report = store.merge(vec![word_item("1", "a", "y"), word_item("2", "b", "x")])?;
+        assert_eq!(
+            report,
+            MergeReport {
+                updated: 1,
+                unchanged: 1,
+                ..Default::default()
+            }
+        );
+        drop(store);
+
+        // 重新打开后数据仍然存在
+        let store = SqliteWordStore::open(&path)?;
+        assert_eq!(store.words().len(), 2);
+        let a = store.words().iter().find(|w| w.item_id == "1").unwrap();
+        assert_eq!(a.trans, "y");
+        Ok(())
+    }
+
+    #[test]
+    fn replace_words() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let path = db_path(&dir);
+        let mut store = SqliteWordStore::open(&path)?;
+        store.merge(vec![word_item("1", "a", "x"), word_item("2", "b", "x")])?;
+        let report = store.replace_at(vec![word_item("2", "b", "x")], 1000)?;
+        assert_eq!(report.removed, 1);
+        assert_eq!(report.unchanged, 1);
+        drop(store);
+
+        let store = SqliteWordStore::open(&path)?;
+        let mut seen = word_item("2", "b", "x");
+        seen.local_mut().last_seen_in_remote = Some(1000);
+        assert_eq!(store.words(), &[seen]);
+        Ok(())
+    }
+
+    #[test]
+    fn search_words() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let mut store = SqliteWordStore::open(&db_path(&dir))?;
+        store.merge(vec![
+            word_item("1", "Accommodate", "x"),
+            word_item("2", "run", "x"),
+            word_item("3", "100%_sure", "x"),
+        ])?;
+        let found = store.search("accom")?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].item_id, "1");
+        assert_eq!(store.search("%_")?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn migrate_json() -> Result<(), String> {
+        let dir = tempfile::tempdir().map_err(|e| format!("{:?}", e))?;
+        let json_path = dir.path().join("words.json").to_str().unwrap().to_string();
+        let items = vec![word_item("1", "a", "x"), word_item("2", "b", "x")];
+        save_json(&items, &json_path).map_err(|e| format!("{:?}", e))?;
+
+        let mut store = SqliteWordStore::open(&db_path(&dir))?;
+        let report = migrate_json_words(&json_path, &mut store).await?;
+        assert_eq!(report.added, 2);
+        // 再次迁移不会重复添加
+        let report = migrate_json_words(&json_path, &mut store).await?;
+        assert_eq!(report.unchanged, 2);
+        drop(store);
+
+        let store = SqliteWordStore::open(&db_path(&dir))?;
+        assert_eq!(store.words(), items.as_slice());
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+//! 终端中对齐的表格输出。
+//!
+//! 输出到终端时按列对齐，超出终端宽度时截断最宽的列；否则输出tab分隔的文本，便于通过管道处理
+use crate::color::paint;
+use std::io::{self, IsTerminal};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// 无法获取终端宽度时使用的宽度
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// 列之间的分隔
+const COLUMN_SEPARATOR: &str = "  ";
+
+/// 截断后列的最小宽度，包括省略号
+const MIN_COLUMN_WIDTH: usize = 4;
+
+const ELLIPSIS: char = '…';
+
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    /// 每行的颜色，见[`Table::add_colored_row`]
+    row_colors: Vec<Option<&'static str>>,
+    color: bool,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: vec![],
+            row_colors: vec![],
+            color: false,
+        }
+    }
+
+    /// 是否以[`Table::add_colored_row`]指定的颜色输出行
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// 添加一行。缺少的列为空，多余的列被忽略
+    pub fn add_row<S: Into<String>>(&mut self, row: impl IntoIterator<Item = S>) {
+        self.add_colored_row(row, None)
+    }
+
+    /// 添加一行，启用颜色时整行以ansi颜色color输出
+    pub fn add_colored_row<S: Into<String>>(
+        &mut self,
+        row: impl IntoIterator<Item = S>,
+        color: Option<&'static str>,
+    ) {
+        let mut row = row
+            .into_iter()
+            .map(|cell| single_line(&cell.into()))
+            .take(self.headers.len())
+            .collect::<Vec<_>>();
+        row.resize(self.headers.len(), String::new());
+        self.rows.push(row);
+        self.row_colors.push(color);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// width为终端宽度，为None时输出tab分隔的文本
+    pub fn render(&self, width: Option<usize>) -> String {
+        let lines = match width {
+            Some(width) => self.render_aligned(width),
+            None => self.render_tsv(),
+        };
+        // 表头没有颜色
+        let colors = std::iter::once(None).chain(self.row_colors.iter().copied());
+        lines
+            .into_iter()
+            .zip(colors)
+            .map(|(line, color)| match color.filter(|_| self.color) {
+                Some(color) => paint(color, &line),
+                None => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn render_tsv(&self) -> Vec<String> {
+        std::iter::once(&self.headers)
+            .chain(self.rows.iter())
+            .map(|row| row.join("\t"))
+            .collect()
+    }
+
+    fn render_aligned(&self, width: usize) -> Vec<String> {
+        let mut widths = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                self.rows
+                    .iter()
+                    .map(|row| row[i].width())
+                    .chain(std::iter::once(h.width()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        let total =
+            widths.iter().sum::<usize>() + COLUMN_SEPARATOR.len() * widths.len().saturating_sub(1);
+        if total > width {
+            if let Some((i, &widest)) = widths.iter().enumerate().max_by_key(|(_, w)| **w) {
+                widths[i] = widest
+                    .saturating_sub(total - width)
+                    .max(MIN_COLUMN_WIDTH)
+                    .min(widest);
+            }
+        }
+        std::iter::once(&self.headers)
+            .chain(self.rows.iter())
+            .map(|row| {
+                let line = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, &w)| pad(&truncate(cell, w), w))
+                    .collect::<Vec<_>>()
+                    .join(COLUMN_SEPARATOR);
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+}
+
+/// stdout为终端时返回终端宽度，否则为None
+pub fn stdout_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    Some(
+        term_size::dimensions_stdout()
+            .map(|(w, _)| w)
+            .filter(|w| *w > 0)
+            .unwrap_or(DEFAULT_WIDTH),
+    )
+}
+
+/// stderr为终端时返回终端宽度，否则为None
+pub fn stderr_width() -> Option<usize> {
+    if !io::stderr().is_terminal() {
+        return None;
+    }
+    Some(
+        term_size::dimensions_stderr()
+            .map(|(w, _)| w)
+            .filter(|w| *w > 0)
+            .unwrap_or(DEFAULT_WIDTH),
+    )
+}
+
+/// 将换行与tab替换为空格，避免破坏表格的行列
+fn single_line(s: &str) -> String {
+    s.replace(['\r', '\n', '\t'], " ")
+}
+
+/// 超出width时截断并以省略号结尾
+pub(crate) fn truncate(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or_default();
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push(ELLIPSIS);
+    out
+}
+
+/// 以空格填充到显示宽度width，中文等宽字符按2计算
+fn pad(s: &str, width: usize) -> String {
+    format!("{}{}", s, " ".repeat(width.saturating_sub(s.width())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words() -> Table {
+        let mut table = Table::new(&["word", "trans", "time"]);
+        table.add_row(vec![
+            "accommodate",
+            "vt. 容纳, 使适应\nvi. 适应",
+            "2021-03-08",
+        ]);
+        table.add_row(vec!["run", "v. 跑", "2021-03-09"]);
+        table
+    }
+
+    #[test]
+    fn aligned() {
+        assert_eq!(
+            words().render(Some(80)),
+            "word         trans                      time
+accommodate  vt. 容纳, 使适应 vi. 适应  2021-03-08
+run          v. 跑                      2021-03-09"
+        );
+    }
+
+    #[test]
+    fn truncate_widest_column() {
+        let out = words().render(Some(36));
+        assert_eq!(
+            out,
+            "word         trans        time
+accommodate  vt. 容纳, …  2021-03-08
+run          v. 跑        2021-03-09"
+        );
+        assert!(out.lines().all(|l| l.width() <= 36), "{}", out);
+        // 宽度过小时保留最小宽度
+        assert!(words().render(Some(10)).contains("vt.…"));
+    }
+
+    #[test]
+    fn tsv_fallback() {
+        let mut table = words();
+        table.add_row(vec!["a\tb"]);
+        assert_eq!(
+            table.render(None),
+            "word\ttrans\ttime
+accommodate\tvt. 容纳, 使适应 vi. 适应\t2021-03-08
+run\tv. 跑\t2021-03-09
+a b\t\t"
+        );
+    }
+
+    #[test]
+    fn colored_rows() {
+        let mut table = Table::new(&["status", "check"]);
+        table.add_colored_row(vec!["FAIL", "login"], Some(crate::color::RED));
+        table.add_row(vec!["pass", "config"]);
+        assert_eq!(
+            table.clone().with_color(true).render(None),
+            "status\tcheck\n\x1b[31mFAIL\tlogin\x1b[0m\npass\tconfig"
+        );
+        assert_eq!(
+            table.render(None),
+            "status\tcheck\nFAIL\tlogin\npass\tconfig"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! 解析youdao单词的释义trans，如`vt. 容纳；使适应\nvi. 适应`，按词性拆分为多个[`Sense`]，
+//! 用于csv与anki导出的词性列及`--detail`只显示第一个释义
+use serde::Serialize;
+use std::fmt;
+
+/// 可以识别的词性前缀，不区分大小写
+const PARTS_OF_SPEECH: &[&str] = &[
+    "n.", "v.", "vt.", "vi.", "adj.", "adv.", "prep.", "conj.", "pron.", "num.", "art.", "int.",
+    "interj.", "aux.", "abbr.", "pl.", "phr.", "det.",
+];
+
+/// `[人名]`、`【计】`等括号中说明的最大字符数，更长的被认为是释义的一部分
+const MAX_NOTE_CHARS: usize = 6;
+
+/// trans中的一个释义
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Sense {
+    /// 词性如`vt.`或`vt. & vi.`，括号中的说明如`人名`。无法识别时为None
+    pub pos: Option<String>,
+    pub gloss: String,
+}
+
+impl Sense {
+    fn new(pos: Option<&str>, gloss: &str) -> Self {
+        Self {
+            pos: pos.map(str::to_string),
+            gloss: gloss.trim().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Sense {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.pos {
+            Some(pos) if self.gloss.is_empty() => f.write_str(pos),
+            Some(pos) => write!(f, "{} {}", pos, self.gloss),
+            None => f.write_str(&self.gloss),
+        }
+    }
+}
+
+/// 将trans拆分为释义，忽略空行。
+///
+/// 以词性或括号说明开头的行中，之后出现的词性开始一个新的释义，如`n. 跑步 v. 跑`。
+/// 不以词性开头的行作为一个pos为None的释义
+///
+/// ```
+/// use dict::trans::{parse_trans, Sense};
+/// let senses = parse_trans("vt. 容纳；使适应\nvi. 适应");
+/// assert_eq!(senses[0].pos.as_deref(), Some("vt."));
+/// assert_eq!(senses[1].gloss, "适应");
+/// ```
+pub fn parse_trans(trans: &str) -> Vec<Sense> {
+    let mut senses = vec![];
+    for line in trans.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let (mut pos, mut rest) = match take_pos(line) {
+            Some(v) => v,
+            None => {
+                senses.push(Sense::new(None, line));
+                continue;
+            }
+        };
+        // 在之后的单词边界查找下一个词性
+        loop {
+            let next = word_starts(rest)
+                .filter(|i| *i > 0)
+                .find_map(|i| take_pos(&rest[i..]).map(|next| (i, next)));
+            match next {
+                Some((i, (next_pos, next_rest))) => {
+                    senses.push(Sense::new(Some(pos), &rest[..i]));
+                    pos = next_pos;
+                    rest = next_rest;
+                }
+                None => {
+                    senses.push(Sense::new(Some(pos), rest));
+                    break;
+                }
+            }
+        }
+    }
+    senses
+}
+
+/// senses中不重复的词性，空格分隔
+pub fn parts_of_speech(senses: &[Sense]) -> String {
+    let mut parts: Vec<&str> = vec![];
+    for pos in senses.iter().filter_map(|s| s.pos.as_deref()) {
+        if !parts.contains(&pos) {
+            parts.push(pos);
+        }
+    }
+    parts.join(" ")
+}
+
+/// 所有释义，`; `分隔。with_pos时包含词性
+pub fn join_senses(senses: &[Sense], with_pos: bool) -> String {
+    senses
+        .iter()
+        .map(|s| {
+            if with_pos {
+                s.to_string()
+            } else {
+                s.gloss.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// s中每个非空白单词开始的位置
+fn word_starts(s: &str) -> impl Iterator<Item = usize> + '_ {
+    let mut prev_space = true;
+    s.char_indices().filter_map(move |(i, c)| {
+        let start = prev_space && !c.is_whitespace();
+        prev_space = c.is_whitespace();
+        start.then_some(i)
+    })
+}
+
+/// s开头的词性或括号说明，返回词性与之后的内容。词性之后必须是空白或结尾
+fn take_pos(s: &str) -> Option<(&str, &str)> {
+    if let Some(v) = take_note(s) {
+        return Some(v);
+    }
+    // `vt.&vi.`、`vt./vi.`或`vt. & vi.`
+    let mut end = 0;
+    let mut rest = s;
+    loop {
+        let token_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token = &rest[..token_len];
+        if !token.split(['&', '/', ',']).all(is_pos) {
+            break;
+        }
+        end = s.len() - rest.len() + token_len;
+        let after = rest[token_len..].trim_start();
+        match after.strip_prefix('&') {
+            Some(next) if next.starts_with(char::is_whitespace) => rest = next.trim_start(),
+            _ => break,
+        }
+    }
+    (end > 0).then(|| (&s[..end], &s[end..]))
+}
+
+/// `[人名] 史密斯`或`【计】 字节`开头的说明，返回括号中的内容
+fn take_note(s: &str) -> Option<(&str, &str)> {
+    let close = match s.chars().next()? {
+        '[' => ']',
+        '【' => '】',
+        _ => return None,
+    };
+    let open_len = s.chars().next()?.len_utf8();
+    let end = s.find(close)?;
+    let note = s[open_len..end].trim();
+    if note.is_empty()
+        || note.chars().count() > MAX_NOTE_CHARS
+        || note.contains(char::is_whitespace)
+    {
+        return None;
+    }
+    Some((note, &s[end + close.len_utf8()..]))
+}
+
+fn is_pos(token: &str) -> bool {
+    PARTS_OF_SPEECH
+        .iter()
+        .any(|pos| pos.eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::youdao_client::WordItem;
+
+    /// trans与期望的(pos, gloss)
+    type Case<'a> = (&'a str, &'a [(Option<&'a str>, &'a str)]);
+
+    fn sense(pos: Option<&str>, gloss: &str) -> Sense {
+        Sense::new(pos, gloss)
+    }
+
+    #[test]
+    fn parse_common_prefixes() {
+        let cases: &[Case] = &[
+            (
+                "vt. 容纳；使适应\nvi. 适应",
+                &[(Some("vt."), "容纳；使适应"), (Some("vi."), "适应")],
+            ),
+            ("n. 苹果", &[(Some("n."), "苹果")]),
+            (
+                "adj. 仁慈的；乐善好施的",
+                &[(Some("adj."), "仁慈的；乐善好施的")],
+            ),
+            ("adv. 迅速地", &[(Some("adv."), "迅速地")]),
+            ("prep. 在…之上", &[(Some("prep."), "在…之上")]),
+            ("abbr. 研究与开发", &[(Some("abbr."), "研究与开发")]),
+            ("Adj. 大写的词性", &[(Some("Adj."), "大写的词性")]),
+            // 同一行中的多个词性
+            (
+                "n. 跑步；奔跑 v. 跑；运转",
+                &[(Some("n."), "跑步；奔跑"), (Some("v."), "跑；运转")],
+            ),
+            ("vt.&vi. 适应", &[(Some("vt.&vi."), "适应")]),
+            ("vt. & vi. 适应", &[(Some("vt. & vi."), "适应")]),
+            ("v./n. 运行", &[(Some("v./n."), "运行")]),
+            // 人名等括号说明
+            ("[人名] 史密斯", &[(Some("人名"), "史密斯")]),
+            (
+                "n. 金属\n【化】 铁\n[人名] 艾恩",
+                &[
+                    (Some("n."), "金属"),
+                    (Some("化"), "铁"),
+                    (Some("人名"), "艾恩"),
+                ],
+            ),
+            (
+                "n. (Smith)人名；(英)史密斯",
+                &[(Some("n."), "(Smith)人名；(英)史密斯")],
+            ),
+            // 无法识别的行
+            ("容纳；使适应", &[(None, "容纳；使适应")]),
+            (
+                "abbr. <研究与开发>\t\"research\"",
+                &[(Some("abbr."), "<研究与开发>\t\"research\"")],
+            ),
+            ("nt. 不是词性", &[(None, "nt. 不是词性")]),
+            ("n.苹果", &[(None, "n.苹果")]),
+            (
+                "[a long bracket note] text",
+                &[(None, "[a long bracket note] text")],
+            ),
+            ("[] text", &[(None, "[] text")]),
+            (
+                "容纳\r\n\r\nvi.  适应  ",
+                &[(None, "容纳"), (Some("vi."), "适应")],
+            ),
+            ("n.", &[(Some("n."), "")]),
+            ("", &[]),
+            (" \n ", &[]),
+        ];
+        for (trans, expected) in cases {
+            let expected = expected
+                .iter()
+                .map(|(pos, gloss)| sense(*pos, gloss))
+                .collect::<Vec<_>>();
+            assert_eq!(parse_trans(trans), expected, "{:?}", trans);
+        }
+    }
+
+    #[test]
+    fn parse_fixture_words() {
+        let words = serde_json::from_str::<Vec<WordItem>>(include_str!(
+            "../tests/fixtures/youdao-words-v1.json"
+        ))
+        .unwrap();
+        let senses = words
+            .iter()
+            .map(|w| (w.word.as_str(), parse_trans(&w.trans)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            senses,
+            vec![
+                (
+                    "accommodate",
+                    vec![
+                        sense(Some("vt."), "容纳；使适应"),
+                        sense(Some("vi."), "适应")
+                    ]
+                ),
+                (
+                    "benevolent",
+                    vec![sense(Some("adj."), "仁慈的；乐善好施的")]
+                ),
+                ("R&D", vec![sense(Some("abbr."), "研究与开发")]),
+            ]
+        );
+        // 所有的trans都能识别出词性
+        assert!(senses
+            .iter()
+            .flat_map(|(_, s)| s)
+            .all(|s| s.pos.is_some() && !s.gloss.is_empty()));
+    }
+
+    #[test]
+    fn format_senses() {
+        let senses = parse_trans("vt. 容纳\nvi. 适应\n[人名] 艾恩\nvt. 调节\n其它");
+        assert_eq!(parts_of_speech(&senses), "vt. vi. 人名");
+        assert_eq!(
+            join_senses(&senses, true),
+            "vt. 容纳; vi. 适应; 人名 艾恩; vt. 调节; 其它"
+        );
+        assert_eq!(join_senses(&senses, false), "容纳; 适应; 艾恩; 调节; 其它");
+        assert_eq!(sense(Some("n."), "").to_string(), "n.");
+        assert_eq!(parts_of_speech(&parse_trans("其它")), "");
+    }
+}
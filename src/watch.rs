@@ -0,0 +1,113 @@
+//! `dict run --watch`的调度：按间隔重复执行plan，连续失败时增加间隔
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// 连续失败时间隔最多增加到interval的`2^MAX_BACKOFF_SHIFT`倍
+pub const MAX_BACKOFF_SHIFT: u32 = 3;
+
+/// 一轮执行的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Round {
+    /// 添加或暂存了新的单词
+    Synced,
+    /// 没有新的单词，跳过了上传
+    NothingNew,
+    Failed,
+}
+
+/// 记录连续失败的次数并计算到下一轮的间隔
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    interval: Duration,
+    failures: u32,
+}
+
+impl Schedule {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            failures: 0,
+        }
+    }
+
+    /// 记录一轮的结果，返回到下一轮的间隔。连续失败n次时为`interval * 2^n`，
+    /// 最多[`MAX_BACKOFF_SHIFT`]次，成功后恢复为interval
+    pub fn next_delay(&mut self, round: Round) -> Duration {
+        self.failures = match round {
+            Round::Failed => self.failures.saturating_add(1),
+            Round::Synced | Round::NothingNew => 0,
+        };
+        self.interval * 2u32.pow(self.failures.min(MAX_BACKOFF_SHIFT))
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
+
+/// 通过桌面通知提示暂存了待上传的单词，需要`--features notify`。通知失败时仅记录日志
+#[cfg(feature = "notify")]
+pub fn notify_staged(message: &str) {
+    let result = notify_rust::Notification::new()
+        .appname("dict")
+        .summary("dict")
+        .body(message)
+        .show();
+    if let Err(e) = result {
+        warn!("show desktop notification error: {}", e);
+    }
+}
+
+#[cfg(not(feature = "notify"))]
+pub fn notify_staged(_message: &str) {}
+
+/// 等待delay，期间被cancel时提前返回false
+pub async fn sleep_or_cancel(delay: Duration, cancel: &CancellationToken) -> bool {
+    tokio::select! {
+        _ = cancel.cancelled() => false,
+        _ = tokio::time::sleep(delay) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Instant;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    #[test]
+    fn backoff_on_failures() {
+        let mut schedule = Schedule::new(6 * HOUR);
+        assert_eq!(schedule.next_delay(Round::NothingNew), 6 * HOUR);
+        assert_eq!(schedule.next_delay(Round::Failed), 12 * HOUR);
+        assert_eq!(schedule.next_delay(Round::Failed), 24 * HOUR);
+        assert_eq!(schedule.next_delay(Round::Failed), 48 * HOUR);
+        // 最多增加到8倍
+        assert_eq!(schedule.next_delay(Round::Failed), 48 * HOUR);
+        assert_eq!(schedule.failures(), 4);
+        assert_eq!(schedule.next_delay(Round::Synced), 6 * HOUR);
+        assert_eq!(schedule.failures(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sleep_until_next_round() {
+        let cancel = CancellationToken::new();
+        let start = Instant::now();
+        assert!(sleep_or_cancel(6 * HOUR, &cancel).await);
+        assert_eq!(start.elapsed(), 6 * HOUR);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_while_sleeping() {
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(HOUR).await;
+            canceller.cancel();
+        });
+        let start = Instant::now();
+        assert!(!sleep_or_cancel(6 * HOUR, &cancel).await);
+        assert_eq!(start.elapsed(), HOUR);
+    }
+}
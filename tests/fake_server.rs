@@ -0,0 +1,176 @@
+//! 通过假的youdao与maimemo server运行dict命令，不需要真实的账号与网络
+//!
+//! server与[`mock_server::FIXTURE_CONFIG`]同dict的单元测试，所有请求通过`http.proxy`发送到server
+use mock_server::{MockServer, FIXTURE_CAPTCHA};
+use std::process::{Output, Stdio};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// 使用配置文件config_path运行dict，stdin为input。dict必须成功退出
+async fn dict(config_path: &str, args: &[&str], input: &str) -> Output {
+    let output = run_dict(config_path, args, input).await;
+    assert!(
+        output.status.success(),
+        "dict {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+async fn run_dict(config_path: &str, args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dict"))
+        .args(["--config-path", config_path, "--lang", "en", "-y"])
+        .args(args)
+        .env_remove("DICT_OFFLINE")
+        .env_remove("DICT_RECORD_DIR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn dict");
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.as_bytes()).await.unwrap();
+    drop(stdin);
+    child.wait_with_output().await.unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[tokio::test]
+async fn youdao_login_fetch_add() {
+    let server = MockServer::start_recorded().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = server.write_fixture_config(dir.path());
+
+    let output = dict(&config, &["-q", "yd", "-r", "-l"], "").await;
+    let words = stdout(&output);
+    assert!(words.lines().any(|w| w == "accommodate"), "{}", words);
+    assert!(dir.path().join("youdao-cookies.json").exists());
+
+    // 之后从本地单词本中读取，不再请求
+    let requests = server.requests().len();
+    let output = dict(&config, &["yd", "-l"], "").await;
+    assert_eq!(stdout(&output), words);
+    assert_eq!(server.requests().len(), requests);
+
+    // 使用保存的cookies添加单词，不再登录
+    dict(&config, &["lookup", "serendipity", "--add"], "").await;
+    let requests = server.requests();
+    assert!(requests
+        .iter()
+        .any(|r| r.path.contains("/ajax/add?word=serendipity")));
+    let logins = requests
+        .iter()
+        .filter(|r| r.path.ends_with("/login/acc/login"))
+        .count();
+    assert_eq!(logins, 1);
+}
+
+#[tokio::test]
+async fn offline_youdao_refresh() {
+    let server = MockServer::start_recorded().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = server.write_fixture_config(dir.path());
+
+    let output = run_dict(&config, &["--offline", "yd", "-r"], "").await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("offline mode"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn lookup_without_youdao_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("dict-config.yml");
+    std::fs::write(&config, "data_dir: .\n").unwrap();
+    let config = config.to_str().unwrap();
+    let replay = dir.path().to_str().unwrap();
+
+    // 使用默认的lookup请求，没有录制时回放失败
+    let output = run_dict(config, &["--replay", replay, "lookup", "serendipity"], "").await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("no recording for GET"), "{}", stderr);
+
+    let output = run_dict(config, &["lookup", "serendipity", "--add"], "").await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr.starts_with("not found youdao config in:"),
+        "{}",
+        stderr
+    );
+}
+
+#[tokio::test]
+async fn maimemo_login_fetch_upload() {
+    let server = MockServer::start_recorded().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = server.write_fixture_config(dir.path());
+
+    let output = dict(&config, &["mm", "-r", "-l"], "").await;
+    assert!(stdout(&output).contains("english"), "{}", stdout(&output));
+
+    dict(
+        &config,
+        &[
+            "mm",
+            "-u",
+            "-a",
+            "--id",
+            "695835",
+            "--captcha",
+            FIXTURE_CAPTCHA,
+        ],
+        "serendipity\n",
+    )
+    .await;
+    let save = server
+        .requests()
+        .into_iter()
+        .rev()
+        .find(|r| r.path.ends_with("/notepad/save"))
+        .expect("notepad saved");
+    let body = save.body_str();
+    assert!(body.contains("serendipity"), "{}", body);
+    assert!(body.contains(&format!("captcha={}", FIXTURE_CAPTCHA)));
+
+    let output = dict(&config, &["mm", "-l", "--id", "695835"], "").await;
+    assert!(stdout(&output).contains("serendipity"));
+}
+
+#[tokio::test]
+async fn run_plan() {
+    let server = MockServer::start_recorded().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = server.write_fixture_config(dir.path());
+    let plan = dir.path().join("plan.yml");
+    std::fs::write(
+        &plan,
+        "steps:
+  - refresh: youdao
+  - export_csv: missing/words.csv
+    continue_on_error: true
+  - export_csv: words.csv
+",
+    )
+    .unwrap();
+    let plan = plan.to_str().unwrap();
+
+    let output = dict(&config, &["run", plan, "--dry-run"], "").await;
+    assert!(stdout(&output).starts_with("1. refresh youdao\n"));
+    assert!(server.requests().is_empty());
+
+    let output = dict(&config, &["run", plan], "").await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[2/3] export csv to"), "{}", stderr);
+    assert!(stderr.contains("step 2 failed"), "{}", stderr);
+    let csv = std::fs::read_to_string(dir.path().join("words.csv")).unwrap();
+    assert!(csv.contains("accommodate"), "{}", csv);
+    assert!(dir.path().join("youdao-cookies.json").exists());
+}
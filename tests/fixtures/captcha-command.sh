@@ -0,0 +1,9 @@
+#!/bin/sh
+# 测试用的captcha_command：$1为模式，$2为验证码图片路径
+[ -s "$2" ] || { echo "not found captcha: $2" >&2; exit 4; }
+case "$1" in
+    ok) printf '  1234\n\n' ;;
+    fail) echo "no answer" >&2; exit 3 ;;
+    empty) ;;
+    sleep) sleep 5 ;;
+esac